@@ -1,6 +1,6 @@
 use std::{fs::File, path::PathBuf, process};
 
-use clap::{builder::ValueParser, crate_name, crate_version, Arg, Command};
+use clap::{builder::ValueParser, crate_name, crate_version, Arg, ArgAction, Command};
 
 use fanttheysia_common::{StructuralFindAndReplace, TextReplacementRules};
 use tomestone_exdf::{
@@ -9,9 +9,10 @@ use tomestone_exdf::{
     Dataset, Language, Row, Value,
 };
 use tomestone_sqpack::{
-    encoding::{PackSetWriter, RealPackIO},
-    sidetables::build_side_tables,
-    Category, Expansion, GameData, IndexHash, IndexHash2, PlatformId, SqPackId,
+    encoding::{DryRunPackIO, PackIO, PackSetWriter, RealPackIO},
+    live::install_usage,
+    sidetables::{build_side_tables, SideTables},
+    Category, Expansion, GameData, IndexHash, IndexHash1, IndexHash2, PlatformId, SqPackId,
 };
 use tomestone_string_interp::Text;
 
@@ -36,6 +37,157 @@ fn app() -> Command {
                 .required(true)
                 .value_parser(ValueParser::path_buf()),
         )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print what would be written, without writing anything")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Write even if the game, its launcher, or another program is using the destination")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+/// Counts of the files written to the destination pack.
+#[derive(Debug, Default)]
+struct Summary {
+    copied: usize,
+    copied_bytes: u64,
+    rewritten: usize,
+    rewritten_bytes: u64,
+}
+
+/// Applies the text replacement rules to every row of a page of the `Addon` sheet, and returns
+/// the re-encoded page.
+fn rewrite_addon_page(
+    data: Vec<u8>,
+    addon_dataset: &Dataset,
+    visitor: &mut StructuralFindAndReplace,
+) -> Vec<u8> {
+    let source_exdf = match Exdf::new(data) {
+        Ok(exdf) => exdf,
+        Err(e) => {
+            eprintln!("error: couldn't read exdf header: {:?}", e);
+            process::exit(1);
+        }
+    };
+    let rows_res = source_exdf
+        .iter()
+        .map(|res| {
+            let (row_number, raw_row) = match res {
+                Ok(tuple) => tuple,
+                Err(e) => return Err(e.code),
+            };
+            let mut sub_rows = parse_row(raw_row, &addon_dataset.exhf)?;
+
+            for sub_row in &mut sub_rows {
+                for cell in sub_row.cells.iter_mut() {
+                    let text_data_opt = match cell {
+                        Value::String(text_data) => Some(*text_data),
+                        Value::StringOwned(text_data) => Some(&**text_data),
+                        _ => None,
+                    };
+                    if let Some(text_data) = text_data_opt {
+                        let mut text = match Text::parse(text_data) {
+                            Ok(text) => text,
+                            Err(e) => {
+                                eprintln!("error: couldn't parse tagged text: {}", e);
+                                process::exit(1);
+                            }
+                        };
+                        visitor.visit_text(&mut text);
+                        match tomestone_string_interp::encode(&text) {
+                            Ok(encoded) => *cell = Value::StringOwned(encoded),
+                            Err(e) => {
+                                eprintln!("error: couldn't re-encode modified text: {}", e);
+                                process::exit(1);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let row = Row {
+                number: row_number,
+                sub_rows,
+            };
+            Ok(row)
+        })
+        .collect::<Result<Vec<_>, nom::error::ErrorKind>>();
+    let rows = match rows_res {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("error: couldn't parse data row: {:?}", e);
+            process::exit(1);
+        }
+    };
+    encode_exdf_page(addon_dataset.name(), &addon_dataset.exhf, &rows)
+}
+
+/// Writes every file of the source pack through the given I/O interface, rewriting pages of the
+/// `Addon` sheet along the way.
+fn repack<IO: PackIO>(
+    io: IO,
+    pack_id: SqPackId,
+    side_table: SideTables,
+    files: impl Iterator<
+        Item = Result<(Option<IndexHash1>, Option<IndexHash2>, Vec<u8>), tomestone_sqpack::Error>,
+    >,
+    addon_dataset: &Dataset,
+    visitor: &mut StructuralFindAndReplace,
+) -> (IO, Summary) {
+    let mut writer = match PackSetWriter::new(io, PlatformId::Win32, pack_id) {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("error: couldn't create writer: {}", e);
+            process::exit(1);
+        }
+    };
+    writer.set_side_table(side_table);
+
+    let addon_exd_hashes = addon_dataset
+        .exd_path_iter()
+        .map(|path| IndexHash2::hash(&path))
+        .collect::<Vec<IndexHash2>>();
+    let mut summary = Summary::default();
+    for res in files {
+        match res {
+            Ok((Some(hash1), Some(hash2), mut data)) => {
+                if addon_exd_hashes.contains(&hash2) {
+                    data = rewrite_addon_page(data, addon_dataset, visitor);
+                    summary.rewritten += 1;
+                    summary.rewritten_bytes += data.len() as u64;
+                } else {
+                    summary.copied += 1;
+                    summary.copied_bytes += data.len() as u64;
+                }
+
+                if let Err(e) = writer.add_file_by_hashes(hash1, hash2, &data) {
+                    eprintln!("error: problem while writing files: {}", e);
+                    process::exit(1);
+                }
+            }
+            Ok((None, _, _)) | Ok((_, None, _)) => {
+                eprintln!("error: a file was present in only one index");
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("error: couldn't load original file entries: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    match writer.finalize() {
+        Ok(io) => (io, summary),
+        Err(e) => {
+            eprintln!("error: couldn't finalize written data files: {}", e);
+            process::exit(1);
+        }
+    }
 }
 
 fn main() {
@@ -45,6 +197,8 @@ fn main() {
         .get_one::<PathBuf>("destination directory")
         .unwrap();
     let rules_path = app_matches.get_one::<PathBuf>("rules").unwrap();
+    let dry_run = app_matches.get_flag("dry-run");
+    let force = app_matches.get_flag("force");
 
     let rules = {
         let file = match File::open(rules_path) {
@@ -75,6 +229,27 @@ fn main() {
         _ => {}
     }
 
+    if !dry_run && !force {
+        match install_usage(dest_path) {
+            Ok(usage) if !usage.is_empty() => {
+                eprintln!(
+                    "error: the destination is in use: {}. Close the game first, or pass --force",
+                    usage
+                );
+                process::exit(1);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!(
+                    "error: couldn't check whether the destination is in use: {}. Pass --force \
+                     to write anyway",
+                    e
+                );
+                process::exit(1);
+            }
+        }
+    }
+
     let source_game_data = match GameData::new(source_path) {
         Ok(game_data) => game_data,
         Err(e) => {
@@ -116,130 +291,46 @@ fn main() {
             process::exit(1);
         }
     };
-    let addon_exd_hashes = &source_addon_dataset
-        .exd_path_iter()
-        .map(|path| IndexHash2::hash(&path))
-        .collect::<Vec<IndexHash2>>();
-
     let side_table = build_side_tables(&source_game_data, &mut source_data_file_set, pack_id);
-    let dest_io = match RealPackIO::new(
-        PathBuf::from(dest_path).join("game").join("sqpack"),
-        PlatformId::Win32,
-        pack_id,
-    ) {
+    let mut visitor = StructuralFindAndReplace::new(&rules.structured_text_rules);
+    let files = source_data_file_set.iter_files_both_hashes(pack_id, source_index, source_index_2);
+    let dest_sqpack = PathBuf::from(dest_path).join("game").join("sqpack");
+
+    if dry_run {
+        let (io, summary) = repack(
+            DryRunPackIO::new(PlatformId::Win32, pack_id),
+            pack_id,
+            side_table,
+            files,
+            &source_addon_dataset,
+            &mut visitor,
+        );
+        println!(
+            "would copy {} files ({} bytes), and rewrite {} files ({} bytes)",
+            summary.copied, summary.copied_bytes, summary.rewritten, summary.rewritten_bytes
+        );
+        for (name, size) in io.file_sizes() {
+            let path = dest_sqpack.join(&*pack_id.expansion.name()).join(name);
+            println!("would write {} ({} bytes)", path.display(), size);
+        }
+        return;
+    }
+
+    let dest_io = match RealPackIO::new(dest_sqpack, PlatformId::Win32, pack_id) {
         Ok(io) => io,
         Err(e) => {
             eprintln!("error: couldn't create I/O interface: {}", e);
             process::exit(1);
         }
     };
-    let mut writer = match PackSetWriter::new(dest_io, PlatformId::Win32, pack_id) {
-        Ok(writer) => writer,
-        Err(e) => {
-            eprintln!("error: couldn't create writer: {}", e);
-            process::exit(1);
-        }
-    };
-    writer.set_side_table(side_table);
-
-    let mut visitor = StructuralFindAndReplace::new(&rules.structured_text_rules);
-
-    let file_iterator =
-        source_data_file_set.iter_files_both_hashes(pack_id, source_index, source_index_2);
-    for res in file_iterator {
-        match res {
-            Ok((Some(hash1), Some(hash2), mut data)) => {
-                if addon_exd_hashes.contains(&hash2) {
-                    let source_exdf = match Exdf::new(data) {
-                        Ok(exdf) => exdf,
-                        Err(e) => {
-                            eprintln!("error: couldn't read exdf header: {:?}", e);
-                            process::exit(1);
-                        }
-                    };
-                    let rows_res = source_exdf
-                        .iter()
-                        .map(|res| {
-                            let (row_number, raw_row) = match res {
-                                Ok(tuple) => tuple,
-                                Err(e) => return Err(e.code),
-                            };
-                            let mut sub_rows = parse_row(raw_row, &source_addon_dataset.exhf)?;
-
-                            for sub_row in &mut sub_rows {
-                                for cell in sub_row.cells.iter_mut() {
-                                    let text_data_opt = match cell {
-                                        Value::String(text_data) => Some(*text_data),
-                                        Value::StringOwned(text_data) => Some(&**text_data),
-                                        _ => None,
-                                    };
-                                    if let Some(text_data) = text_data_opt {
-                                        let mut text = match Text::parse(text_data) {
-                                            Ok(text) => text,
-                                            Err(e) => {
-                                                eprintln!(
-                                                    "error: couldn't parse tagged text: {}",
-                                                    e
-                                                );
-                                                process::exit(1);
-                                            }
-                                        };
-                                        visitor.visit_text(&mut text);
-                                        match tomestone_string_interp::encode(&text) {
-                                            Ok(encoded) => *cell = Value::StringOwned(encoded),
-                                            Err(e) => {
-                                                eprintln!(
-                                                    "error: couldn't re-encode modified text: {}",
-                                                    e
-                                                );
-                                                process::exit(1);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-
-                            let row = Row {
-                                number: row_number,
-                                sub_rows,
-                            };
-                            Ok(row)
-                        })
-                        .collect::<Result<Vec<_>, nom::error::ErrorKind>>();
-                    let rows = match rows_res {
-                        Ok(rows) => rows,
-                        Err(e) => {
-                            eprintln!("error: couldn't parse data row: {:?}", e);
-                            process::exit(1);
-                        }
-                    };
-                    data = encode_exdf_page(
-                        source_addon_dataset.name(),
-                        &source_addon_dataset.exhf,
-                        &rows,
-                    );
-                }
-
-                if let Err(e) = writer.add_file_by_hashes(hash1, hash2, &data) {
-                    eprintln!("error: problem while writing files: {}", e);
-                    process::exit(1);
-                }
-            }
-            Ok((None, _, _)) | Ok((_, None, _)) => {
-                eprintln!("error: a file was present in only one index");
-                process::exit(1);
-            }
-            Err(e) => {
-                eprintln!("error: couldn't load original file entries: {}", e);
-                process::exit(1);
-            }
-        }
-    }
-
-    if let Err(e) = writer.finalize() {
-        eprintln!("error: couldn't finalize written data files: {}", e);
-        process::exit(1);
-    }
+    repack(
+        dest_io,
+        pack_id,
+        side_table,
+        files,
+        &source_addon_dataset,
+        &mut visitor,
+    );
 }
 
 #[cfg(test)]
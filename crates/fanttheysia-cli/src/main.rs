@@ -192,10 +192,8 @@ fn foreach_grand_company_rank_diff<F: FnMut(GrandCompanyRankRecord)>(
             for (female_res, male_res) in female_page.zip(male_page) {
                 let female_row = female_res.unwrap();
                 let male_row = male_res.unwrap();
-                for (female_sub_row, male_sub_row) in female_row
-                    .sub_rows
-                    .into_iter()
-                    .zip(male_row.sub_rows.into_iter())
+                for (female_sub_row, male_sub_row) in
+                    female_row.sub_rows.into_iter().zip(male_row.sub_rows)
                 {
                     if let (
                         Value::String(female_singular_rank),
@@ -530,7 +528,7 @@ fn build_template(
             after: Text::new(vec![Segment::Literal("".to_string())]),
         });
     });
-    rules.pvp_rank_rules.extend(pvp_rank_set.into_iter());
+    rules.pvp_rank_rules.extend(pvp_rank_set);
 
     if serialization_error_count > 0 {
         eprintln!(
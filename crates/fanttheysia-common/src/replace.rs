@@ -9,7 +9,7 @@ pub struct StructuralFindAndReplace<'a> {
 }
 
 impl<'a> StructuralFindAndReplace<'a> {
-    pub fn new(rules: &'a [StructuredTextRule]) -> StructuralFindAndReplace {
+    pub fn new(rules: &'a [StructuredTextRule]) -> StructuralFindAndReplace<'a> {
         StructuralFindAndReplace { rules }
     }
 
@@ -67,7 +67,7 @@ impl<'a> StructuralFindAndReplace<'a> {
                             }
                         }
                         let result_text: Text = result.into();
-                        text.splice(i..=i, result_text.into_iter());
+                        text.splice(i..=i, result_text);
                     }
                 }
             } else if find.len() == 1 {
@@ -139,7 +139,7 @@ impl<'a> StructuralFindAndReplace<'a> {
                                 ));
                             }
                         }
-                        text.splice(i..i + find.len(), Text::from(result).into_iter());
+                        text.splice(i..i + find.len(), Text::from(result));
                     }
                 }
             }
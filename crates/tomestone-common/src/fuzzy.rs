@@ -0,0 +1,94 @@
+//! Approximate string matching, for suggesting corrections to names entered by users.
+
+use alloc::{vec, vec::Vec};
+
+/// Computes the edit distance between two strings. This is the Levenshtein distance, i.e. the
+/// number of single character insertions, deletions, and substitutions needed to turn one string
+/// into the other, except that swapping two adjacent characters also counts as one edit, since
+/// that is a common typo.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        rows[i][0] = i;
+        for j in 1..=b.len() {
+            let substitution_cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j - 1] + substitution_cost)
+                .min(rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Returns the candidates that are close to `target`, ignoring case, sorted from closest to
+/// furthest. Candidates are considered close if they are within one edit per three characters of
+/// the target, and always within one edit.
+pub fn closest_matches<'a, I>(target: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let target = target.to_lowercase();
+    let max_distance = (target.chars().count() / 3).max(1);
+    let mut matches = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(&target, &candidate.to_lowercase());
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+    matches
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Returns the candidate closest to `target`, if any is close enough. See [`closest_matches`].
+pub fn best_match<'a, I>(target: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    closest_matches(target, candidates).into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{best_match, closest_matches, edit_distance};
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("item", ""), 4);
+        assert_eq!(edit_distance("", "item"), 4);
+        assert_eq!(edit_distance("item", "item"), 0);
+        assert_eq!(edit_distance("itme", "item"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("アイテム", "アイテ"), 1);
+    }
+
+    #[test]
+    fn matches() {
+        let names = ["Item", "ItemAction", "Action", "Quest", "QuestBattle"];
+        assert_eq!(best_match("itme", names), Some("Item"));
+        assert_eq!(best_match("Quest", names), Some("Quest"));
+        assert_eq!(best_match("mount", names), None);
+        assert_eq!(closest_matches("ItemActoin", names), ["ItemAction"]);
+        assert_eq!(
+            closest_matches(
+                "exd/item.exh",
+                ["exd/items.exh", "exd/mount.exh", "exd/item.exd"]
+            ),
+            ["exd/item.exd", "exd/items.exh"]
+        );
+    }
+}
@@ -1,10 +1,18 @@
-use std::num::NonZeroUsize;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use core::num::NonZeroUsize;
 
 use nom::{
     error::{ErrorKind, ParseError},
     Err, IResult, InputLength, InputTake, Needed,
 };
 
+pub mod fuzzy;
+#[cfg(feature = "std")]
+pub mod paths;
+
 pub fn null_padding<'a, E>(length: usize) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], (), E>
 where
     E: ParseError<&'a [u8]>,
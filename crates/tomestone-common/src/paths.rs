@@ -0,0 +1,97 @@
+//! Helpers for paths that files are written to.
+
+use std::path::{Path, PathBuf};
+
+/// Prepares an output path for writing files with long paths. On Windows, this returns the
+/// absolute, extended-length (`\\?\`) form of the path, since paths longer than `MAX_PATH` can't
+/// be opened otherwise. Elsewhere, or if the path can't be made absolute, the path is returned
+/// unchanged.
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        use std::{
+            ffi::OsString,
+            path::{Component, Prefix},
+        };
+
+        let absolute = match std::path::absolute(path) {
+            Ok(absolute) => absolute,
+            Err(_) => return path.to_path_buf(),
+        };
+        let mut components = absolute.components();
+        let mut prefixed = match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) => PathBuf::from(format!(r"\\?\{}:\", char::from(letter))),
+                Prefix::UNC(server, share) => {
+                    // `\\server\share` becomes `\\?\UNC\server\share`.
+                    let mut prefixed = OsString::from(r"\\?\UNC\");
+                    prefixed.push(server);
+                    prefixed.push(r"\");
+                    prefixed.push(share);
+                    prefixed.push(r"\");
+                    PathBuf::from(prefixed)
+                }
+                // Already a verbatim or device path.
+                _ => return absolute,
+            },
+            _ => return absolute,
+        };
+        prefixed.extend(components.filter(|component| *component != Component::RootDir));
+        prefixed
+    }
+    #[cfg(not(windows))]
+    {
+        path.to_path_buf()
+    }
+}
+
+/// Replaces characters that are not allowed in file names on common platforms, and trims
+/// surrounding whitespace, so that names from game data can be used in exported file names.
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{extended_length_path, sanitize_file_name};
+
+    #[test]
+    fn sanitize() {
+        assert_eq!(sanitize_file_name(" Wind-up Aldgoat "), "Wind-up Aldgoat");
+        assert_eq!(sanitize_file_name("A/B: \"C\"\n"), "A_B_ _C__");
+    }
+
+    #[test]
+    fn extended_length() {
+        #[cfg(windows)]
+        {
+            assert_eq!(
+                extended_length_path(Path::new(r"C:\out\..\icons")),
+                Path::new(r"\\?\C:\icons")
+            );
+            assert_eq!(
+                extended_length_path(Path::new(r"\\server\share\icons")),
+                Path::new(r"\\?\UNC\server\share\icons")
+            );
+            assert_eq!(
+                extended_length_path(Path::new(r"\\?\C:\icons")),
+                Path::new(r"\\?\C:\icons")
+            );
+        }
+        #[cfg(not(windows))]
+        assert_eq!(
+            extended_length_path(Path::new("out/icons")),
+            Path::new("out/icons")
+        );
+    }
+}
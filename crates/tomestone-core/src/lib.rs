@@ -0,0 +1,62 @@
+//! A single dependency for applications, re-exporting the parts of the tomestone crates that are
+//! meant to stay stable.
+//!
+//! The format crates, such as `tomestone-sqpack` and `tomestone-exdf`, expose their parsers and
+//! other internals, which change as more of the game's formats are understood. This crate only
+//! re-exports the high-level API: opening an installation and reading files from it, loading
+//! sheets, working with strings, and exporting textures, sounds, and models. Items are only
+//! removed or changed here in a new major version, even if the crate they come from changes.
+//!
+//! ```no_run
+//! use tomestone_core::{
+//!     sheets::{Dataset, Language},
+//!     GameData,
+//! };
+//!
+//! let game_data = GameData::new("/path/to/FINAL FANTASY XIV - A Realm Reborn").unwrap();
+//! let mut data_file_set = game_data.data_files();
+//! let dataset = Dataset::load(&game_data, &mut data_file_set, "Item", Language::English).unwrap();
+//! ```
+
+pub use tomestone_sqpack::{
+    AccessLog, Category, DataFileSet, EntryType, Error, Expansion, FileMetadata, FileReader,
+    GameData, GameDataBuilder, GameVersion, InstallVersions, MemoryBudget, MemoryProvider,
+    Reservation, SqPackId,
+};
+
+/// Reading Excel sheets, the game's tables of items, quests, text, and so on.
+pub mod sheets {
+    pub use tomestone_exdf::{
+        Dataset, DatasetPageIter, Error, Language, RootList, Row, SubRow, Value,
+    };
+}
+
+/// The game's strings, which contain tags for formatting and for text that depends on the
+/// player or other context.
+pub mod text {
+    pub use tomestone_string_interp::{Error, Expression, Segment, Text};
+}
+
+/// Converting the game's textures, sounds, and models to common formats.
+pub mod export {
+    /// Textures, which can be decoded and written out as PNG with
+    /// [`RgbaImage::write_png`](crate::export::texture::RgbaImage::write_png).
+    pub mod texture {
+        pub use tomestone_texture::{decode_tex, Error, RgbaImage, TextureFormat};
+    }
+
+    /// Sound banks (`.scd` files), and the audio streams they contain.
+    pub mod sound {
+        pub use tomestone_sound::{audio_entries, AudioEntry, Codec, Error};
+    }
+
+    /// Furniture models for housing.
+    pub mod housing {
+        pub use tomestone_model::{
+            housing::{
+                export_file_name, export_furniture, list_furniture, Furniture, FurnitureKind,
+            },
+            Error,
+        };
+    }
+}
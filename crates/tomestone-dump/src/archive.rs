@@ -0,0 +1,604 @@
+//! Archives of an installation's contents, one per game version, for `archive`.
+//!
+//! An archive directory holds a content-addressed store of decompressed files, shared between
+//! game versions, and a directory for each game version:
+//!
+//! ```text
+//! objects/ab/cdef…           each file, named by the SHA-1 hash of its contents
+//! 2024.01.09.0000.0000/
+//!     state.json             when each stage started and finished, and its results
+//!     manifest.json          the size and SHA-1 hash of each index, data, and version file
+//!     entries.tsv            the object holding each entry of each pack, by its hashes
+//!     sheets.sqlite          every sheet, one table each, in one language where possible
+//! ```
+//!
+//! The stages run in order, and each is recorded in `state.json` when it finishes, so running
+//! the command again after an interruption resumes at the first unfinished stage. Files are
+//! written under a temporary name and renamed once complete, and objects that already exist
+//! aren't written again, so an interrupted stage is simply started over.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{types::Value as SqlValue, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use tomestone_exdf::{ColumnFormat, Dataset, Language, RootList, Value};
+use tomestone_patch::install::{hash_install, CopyManifest, CopyOptions};
+use tomestone_sqpack::{DataFileSet, GameData};
+
+use crate::{csv::Cell, exit::ErrorMode, pack_name};
+
+/// The stages of an archive, in the order they run.
+pub const STAGES: &[&str] = &["manifest", "objects", "sheets", "verify"];
+
+const STATE: &str = "state.json";
+const MANIFEST: &str = "manifest.json";
+const ENTRIES: &str = "entries.tsv";
+const SHEETS: &str = "sheets.sqlite";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Sqpack(tomestone_sqpack::Error),
+    Patch(tomestone_patch::Error),
+    Sheet(String, tomestone_exdf::Error),
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+    /// The installation has no `game/ffxivgame.ver` file.
+    NoVersion,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Sqpack(e) => write!(f, "{}", e),
+            Error::Patch(e) => write!(f, "{}", e),
+            Error::Sheet(sheet, e) => write!(f, "reading sheet {} failed: {}", sheet, e),
+            Error::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+            Error::NoVersion => f.write_str("the installation has no game version file"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<tomestone_sqpack::Error> for Error {
+    fn from(e: tomestone_sqpack::Error) -> Error {
+        Error::Sqpack(e)
+    }
+}
+
+impl From<tomestone_patch::Error> for Error {
+    fn from(e: tomestone_patch::Error) -> Error {
+        Error::Patch(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Error {
+        Error::Sqlite(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageRecord {
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    /// Seconds since the Unix epoch, or `None` if the stage didn't finish.
+    pub finished: Option<u64>,
+    /// The results of the stage, such as counts of files.
+    pub summary: JsonValue,
+}
+
+/// The contents of `state.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub game_version: String,
+    pub stages: BTreeMap<String, StageRecord>,
+}
+
+impl State {
+    /// Returns the problems found by the `verify` stage.
+    pub fn problems(&self) -> Vec<String> {
+        self.stages
+            .get("verify")
+            .and_then(|record| record.summary["problems"].as_array())
+            .map(|problems| {
+                problems
+                    .iter()
+                    .filter_map(|problem| problem.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the sheets that the `sheets` stage couldn't read, with the reason for each.
+    pub fn failed_sheets(&self) -> Vec<String> {
+        self.stages
+            .get("sheets")
+            .and_then(|record| record.summary["failed"].as_array())
+            .map(|failed| {
+                failed
+                    .iter()
+                    .filter_map(|failure| failure.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub struct Outcome {
+    /// The archive directory for this game version.
+    pub dir: PathBuf,
+    pub state: State,
+    /// Stages that had finished in an earlier run, and were skipped.
+    pub skipped: Vec<&'static str>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Writes a file under a temporary name, and renames it once it is complete.
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let partial = partial_path(path);
+    let mut writer = BufWriter::new(File::create(&partial)?);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    fs::rename(partial, path)?;
+    Ok(())
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_owned();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+fn object_path(objects: &Path, sha1: &str) -> PathBuf {
+    objects.join(&sha1[..2]).join(&sha1[2..])
+}
+
+/// Archives an installation into `output`, resuming an earlier run for the same game version.
+///
+/// With [`ErrorMode::KeepGoing`], sheets that can't be read are left out of the sheet database
+/// and listed in the stage's summary, instead of stopping the archive.
+pub fn run(
+    root: &Path,
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    output: &Path,
+    language: Language,
+    mode: ErrorMode,
+) -> Result<Outcome, Error> {
+    let game_version = game_data.game_version()?.ok_or(Error::NoVersion)?;
+    let dir = output.join(game_version.to_string());
+    fs::create_dir_all(&dir)?;
+    let state_path = dir.join(STATE);
+    let mut state = match File::open(&state_path) {
+        Ok(file) => serde_json::from_reader(BufReader::new(file))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => State {
+            game_version: game_version.to_string(),
+            stages: BTreeMap::new(),
+        },
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut skipped = Vec::new();
+    for stage in STAGES {
+        if let Some(StageRecord {
+            finished: Some(_), ..
+        }) = state.stages.get(*stage)
+        {
+            skipped.push(*stage);
+            continue;
+        }
+        let started = now();
+        state.stages.insert(
+            stage.to_string(),
+            StageRecord {
+                started,
+                finished: None,
+                summary: JsonValue::Null,
+            },
+        );
+        write_atomic(&state_path, |writer| {
+            Ok(serde_json::to_writer_pretty(writer, &state)?)
+        })?;
+
+        let summary = match *stage {
+            "manifest" => manifest(root, &dir)?,
+            "objects" => objects(game_data, data_file_set, &dir, &output.join("objects"))?,
+            "sheets" => sheets(game_data, data_file_set, &dir, language, mode)?,
+            "verify" => verify(root, &dir, &output.join("objects"))?,
+            _ => unreachable!(),
+        };
+        state.stages.insert(
+            stage.to_string(),
+            StageRecord {
+                started,
+                finished: Some(now()),
+                summary,
+            },
+        );
+        write_atomic(&state_path, |writer| {
+            Ok(serde_json::to_writer_pretty(writer, &state)?)
+        })?;
+    }
+    Ok(Outcome {
+        dir,
+        state,
+        skipped,
+    })
+}
+
+fn manifest(root: &Path, dir: &Path) -> Result<JsonValue, Error> {
+    let manifest = hash_install(root, &CopyOptions::default())?;
+    write_atomic(&dir.join(MANIFEST), |writer| Ok(manifest.write(writer)?))?;
+    Ok(json!({
+        "files": manifest.files.len(),
+        "size": manifest.files.iter().map(|entry| entry.size).sum::<u64>(),
+    }))
+}
+
+fn hash_name(hash: Option<String>) -> String {
+    hash.unwrap_or_else(|| "-".to_string())
+}
+
+fn objects(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    dir: &Path,
+    objects: &Path,
+) -> Result<JsonValue, Error> {
+    let (mut entries, mut written, mut written_size) = (0u64, 0u64, 0u64);
+    write_atomic(&dir.join(ENTRIES), |writer| {
+        writeln!(writer, "pack\tindex_hash\tindex2_hash\tsha1\tsize")?;
+        for pack_id in game_data.iter_packs() {
+            let index = game_data.get_index_1(&pack_id).unwrap()?;
+            let index2 = game_data.get_index_2(&pack_id).unwrap()?;
+            for res in data_file_set.iter_files_both_hashes(pack_id, index, index2) {
+                let (hash1, hash2, data) = res?;
+                let sha1 = hex::encode(tomestone_sqpack::sha1(&data));
+                let path = object_path(objects, &sha1);
+                if !path.is_file() {
+                    fs::create_dir_all(path.parent().unwrap())?;
+                    write_atomic(&path, |writer| Ok(writer.write_all(&data)?))?;
+                    written += 1;
+                    written_size += data.len() as u64;
+                }
+                entries += 1;
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}",
+                    pack_name(pack_id),
+                    hash_name(hash1.map(|h| format!("{:08x}{:08x}", h.folder_crc, h.filename_crc))),
+                    hash_name(hash2.map(|h| format!("{:08x}", h.path_crc))),
+                    sha1,
+                    data.len()
+                )?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(json!({
+        "entries": entries,
+        "objects_written": written,
+        "size_written": written_size,
+    }))
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_value(value: &Value<'_>) -> SqlValue {
+    match Cell::new(value) {
+        Cell::Null => SqlValue::Null,
+        Cell::Bool(flag) => SqlValue::Integer(flag.into()),
+        Cell::Integer(number) => SqlValue::Integer(number),
+        Cell::Float(number) => SqlValue::Real(number.into()),
+        Cell::Text(text) => SqlValue::Text(text),
+    }
+}
+
+/// Writes each sheet to the sheet database, in `language` if it has it, and otherwise in Japanese,
+/// which every localized sheet has.
+fn sheets(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    dir: &Path,
+    language: Language,
+    mode: ErrorMode,
+) -> Result<JsonValue, Error> {
+    let root_list = RootList::open(game_data, data_file_set)
+        .map_err(|e| Error::Sheet("root.exl".to_string(), e))?;
+    let path = dir.join(SHEETS);
+    let partial = partial_path(&path);
+    match fs::remove_file(&partial) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let mut connection = Connection::open(&partial)?;
+    let transaction = connection.transaction()?;
+    let languages = [language, Language::Japanese];
+    let (mut tables, mut rows, mut skipped) = (0u64, 0u64, 0u64);
+    let (mut fallback, mut failed) = (Vec::new(), Vec::new());
+    for name in root_list.iter() {
+        let result = match Dataset::load_with_fallback(game_data, data_file_set, name, &languages) {
+            Ok(dataset) => sheet_table(&transaction, name, &dataset),
+            Err(tomestone_exdf::Error::LanguageUnavailable | tomestone_exdf::Error::NoSuchFile) => {
+                skipped += 1;
+                continue;
+            }
+            Err(e) => Err(Error::Sheet(name.to_string(), e)),
+        };
+        match result {
+            Ok((sheet_rows, sheet_language)) => {
+                tables += 1;
+                rows += sheet_rows;
+                if sheet_language.is_some_and(|sheet_language| sheet_language != language) {
+                    fallback.push(name);
+                }
+            }
+            Err(e) if mode == ErrorMode::KeepGoing => {
+                transaction.execute(
+                    &format!("DROP TABLE IF EXISTS {}", quote_identifier(name)),
+                    [],
+                )?;
+                failed.push(e.to_string());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    transaction.commit()?;
+    connection.close().map_err(|(_, e)| e)?;
+    fs::rename(partial, path)?;
+    Ok(json!({
+        "language": language.short_code(),
+        "tables": tables,
+        "rows": rows,
+        "skipped": skipped,
+        "fallback": fallback,
+        "failed": failed,
+    }))
+}
+
+/// Creates a sheet's table and fills it, returning the number of rows written and the language
+/// that was read.
+fn sheet_table(
+    transaction: &rusqlite::Transaction<'_>,
+    name: &str,
+    dataset: &Dataset<'_>,
+) -> Result<(u64, Option<Language>), Error> {
+    let mut rows = 0;
+    let columns = dataset.exhf.columns_table_order();
+    let mut definition = "row INTEGER NOT NULL, sub_row INTEGER NOT NULL".to_string();
+    for (i, column) in columns.iter().enumerate() {
+        let sql_type = match column.format() {
+            ColumnFormat::String | ColumnFormat::I16x4 => "TEXT",
+            ColumnFormat::Float => "REAL",
+            _ => "INTEGER",
+        };
+        definition.push_str(&format!(", c{} {}", i, sql_type));
+    }
+    let table = quote_identifier(name);
+    transaction.execute(&format!("CREATE TABLE {} ({})", table, definition), [])?;
+    let placeholders = vec!["?"; columns.len() + 2].join(", ");
+    let mut statement =
+        transaction.prepare(&format!("INSERT INTO {} VALUES ({})", table, placeholders))?;
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res.map_err(|e| Error::Sheet(name.to_string(), e))?;
+            for sub_row in row.sub_rows.iter() {
+                let values = [
+                    SqlValue::Integer(row.number.into()),
+                    SqlValue::Integer(sub_row.number.into()),
+                ]
+                .into_iter()
+                .chain(sub_row.cells.iter().map(sql_value));
+                statement.execute(rusqlite::params_from_iter(values))?;
+                rows += 1;
+            }
+        }
+    }
+    Ok((rows, dataset.language()))
+}
+
+/// Reads the manifest of an archive directory, listing the hash of each index, data, and version
+/// file that was archived.
+pub fn read_manifest(dir: &Path) -> Result<CopyManifest, Error> {
+    Ok(CopyManifest::read(BufReader::new(File::open(
+        dir.join(MANIFEST),
+    )?))?)
+}
+
+/// Checks that the installation didn't change while it was archived, that every object is
+/// intact, and that the sheet database can be read.
+fn verify(root: &Path, dir: &Path, objects: &Path) -> Result<JsonValue, Error> {
+    let mut problems = Vec::new();
+
+    let manifest = read_manifest(dir)?;
+    let current = hash_install(root, &CopyOptions::default())?;
+    if current != manifest {
+        problems.push("the installation changed while it was being archived".to_string());
+    }
+
+    let mut hashes = BTreeSet::new();
+    for line in BufReader::new(File::open(dir.join(ENTRIES))?)
+        .lines()
+        .skip(1)
+    {
+        if let Some(sha1) = line?.split('\t').nth(3) {
+            hashes.insert(sha1.to_string());
+        }
+    }
+    for sha1 in hashes.iter() {
+        match fs::read(object_path(objects, sha1)) {
+            Ok(data) if hex::encode(tomestone_sqpack::sha1(&data)) == *sha1 => {}
+            Ok(_) => problems.push(format!("object {} is damaged", sha1)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                problems.push(format!("object {} is missing", sha1))
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let connection = Connection::open(dir.join(SHEETS))?;
+    let check: String = connection.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        problems.push(format!("the sheet database is damaged: {}", check));
+    }
+
+    Ok(json!({
+        "files": manifest.files.len(),
+        "objects": hashes.len(),
+        "problems": problems,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rusqlite::Connection;
+    use tomestone_exdf::{
+        encoding::encode_exdf_page, parser::exhf::parse_exhf, Language, Row, SubRow, Value,
+    };
+    use tomestone_sqpack::{encoding::write_pack, Category, Expansion, GameData, SqPackId};
+
+    use super::{object_path, run, STAGES};
+    use crate::exit::ErrorMode;
+
+    #[test]
+    fn archive() {
+        // One string column and one u32 column, in a sheet without sub-rows or languages.
+        let mut exh_data = b"EXHF\x00\x03\x00\x08\x00\x02\x00\x01\x00\x01\x00\x00\x00\x01".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 7, 0, 4]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+        let page = encode_exdf_page(
+            "Item",
+            &exhf,
+            &[Row {
+                number: 2,
+                sub_rows: vec![SubRow {
+                    number: 0,
+                    cells: vec![Value::String(b"Potion"), Value::U32(5)],
+                }],
+            }],
+        );
+        let mut broken_page = page.clone();
+        broken_page[..4].copy_from_slice(b"EXDX");
+
+        let install = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_pack(
+            &install.path().join("game/sqpack"),
+            pack_id,
+            [
+                ("exd/root.exl", &b"EXLT,2\r\nItem,-1\r\nBroken,-1\r\n"[..]),
+                ("exd/Item.exh", &exh_data),
+                ("exd/Item_0.exd", &page),
+                // A sheet whose page can't be parsed.
+                ("exd/Broken.exh", &exh_data),
+                ("exd/Broken_0.exd", &broken_page),
+            ],
+        )
+        .unwrap();
+        fs::write(
+            install.path().join("game/ffxivgame.ver"),
+            "2023.01.10.0000.0000",
+        )
+        .unwrap();
+
+        let game_data = GameData::new(install.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        let fail_fast_output = tempfile::tempdir().unwrap();
+        assert!(run(
+            install.path(),
+            &game_data,
+            &mut data_file_set,
+            fail_fast_output.path(),
+            Language::English,
+            ErrorMode::FailFast,
+        )
+        .is_err());
+
+        let outcome = run(
+            install.path(),
+            &game_data,
+            &mut data_file_set,
+            output.path(),
+            Language::English,
+            ErrorMode::KeepGoing,
+        )
+        .unwrap();
+        assert_eq!(outcome.dir, output.path().join("2023.01.10.0000.0000"));
+        assert!(outcome.skipped.is_empty());
+        assert!(outcome.state.problems().is_empty());
+        assert_eq!(outcome.state.failed_sheets().len(), 1);
+        assert!(outcome.state.failed_sheets()[0].contains("Broken"));
+        assert_eq!(outcome.state.stages["sheets"].summary["tables"], 1);
+        assert_eq!(outcome.state.stages["objects"].summary["entries"], 5);
+        assert_eq!(
+            fs::read(object_path(
+                &output.path().join("objects"),
+                &hex::encode(tomestone_sqpack::sha1(&page))
+            ))
+            .unwrap(),
+            page
+        );
+        let connection = Connection::open(outcome.dir.join("sheets.sqlite")).unwrap();
+        let (name, count): (String, u32) = connection
+            .query_row("SELECT c0, c1 FROM Item WHERE row = 2", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((name.as_str(), count), ("Potion", 5));
+
+        let outcome = run(
+            install.path(),
+            &game_data,
+            &mut data_file_set,
+            output.path(),
+            Language::English,
+            ErrorMode::KeepGoing,
+        )
+        .unwrap();
+        assert_eq!(outcome.skipped, STAGES);
+    }
+}
@@ -0,0 +1,325 @@
+//! Bundles, which join related sheets into one JSON document, for `bundle`.
+//!
+//! A bundle is defined in TOML. It names a sheet, the columns to include, by the name they get in
+//! the output, and joins, which follow a column holding a row number into another sheet. Joins
+//! are defined the same way, and may have joins of their own:
+//!
+//! ```toml
+//! sheet = "Achievement"
+//! icons = ["icon"]
+//!
+//! [columns]
+//! name = 1
+//! icon = 11
+//!
+//! [joins.category]
+//! column = 0
+//! sheet = "AchievementCategory"
+//!
+//! [joins.category.columns]
+//! name = 0
+//! ```
+//!
+//! Each row of the sheet becomes an object with its `row` number, its columns, and its joins,
+//! which are `null` if the referenced row doesn't exist. Text is converted to plain text. Columns
+//! listed in `icons` hold icon IDs, and get a sibling field with `_path` appended, holding the
+//! path of the icon's texture. Only the first sub-row of each row is read.
+//!
+//! Column numbers follow the community-maintained sheet definitions, and may need to be updated
+//! for new game versions.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+};
+
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+use tomestone_exdf::{Dataset, Language, Value};
+use tomestone_sqpack::{DataFileSet, GameData};
+use tomestone_string_interp::plain_text;
+use tomestone_texture::icon::icon_path;
+
+/// The bundles that come with this tool, by name.
+pub const BUILT_IN: &[(&str, &str)] = &[
+    ("achievement", include_str!("../bundles/achievement.toml")),
+    ("emote", include_str!("../bundles/emote.toml")),
+    ("title", include_str!("../bundles/title.toml")),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bundle {
+    pub sheet: String,
+    #[serde(default)]
+    pub columns: BTreeMap<String, usize>,
+    /// Names of columns that hold icon IDs.
+    #[serde(default)]
+    pub icons: Vec<String>,
+    #[serde(default)]
+    pub joins: BTreeMap<String, Join>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Join {
+    /// The column holding a row number of the joined sheet.
+    pub column: usize,
+    #[serde(flatten)]
+    pub bundle: Bundle,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Definition(String),
+    Sheet(String, tomestone_exdf::Error),
+    /// A column is past the end of a sheet's rows, or a join column doesn't hold a number.
+    Column {
+        sheet: String,
+        column: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Definition(message) => write!(f, "invalid bundle definition: {}", message),
+            Error::Sheet(sheet, e) => write!(f, "reading sheet {} failed: {}", sheet, e),
+            Error::Column { sheet, column } => {
+                write!(f, "column {} of sheet {} can't be used", column, sheet)
+            }
+        }
+    }
+}
+
+impl Bundle {
+    pub fn parse(definition: &str) -> Result<Bundle, Error> {
+        let bundle: Bundle =
+            toml::from_str(definition).map_err(|e| Error::Definition(e.to_string()))?;
+        bundle.check()?;
+        Ok(bundle)
+    }
+
+    /// Loads a built-in bundle by name, or else a definition file.
+    pub fn load(name_or_path: &str) -> Result<Bundle, Error> {
+        if let Some((_, definition)) = BUILT_IN.iter().find(|(name, _)| *name == name_or_path) {
+            return Bundle::parse(definition);
+        }
+        let definition = fs::read_to_string(name_or_path).map_err(|e| {
+            Error::Definition(format!(
+                "{:?} isn't a built-in bundle, and can't be read: {}",
+                name_or_path, e
+            ))
+        })?;
+        Bundle::parse(&definition)
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        for icon in self.icons.iter() {
+            if !self.columns.contains_key(icon) {
+                return Err(Error::Definition(format!(
+                    "icon column {:?} of {} isn't one of its columns",
+                    icon, self.sheet
+                )));
+            }
+        }
+        for name in self.joins.keys() {
+            if self.columns.contains_key(name) {
+                return Err(Error::Definition(format!(
+                    "{:?} of {} is both a column and a join",
+                    name, self.sheet
+                )));
+            }
+        }
+        self.joins.values().try_for_each(|join| join.bundle.check())
+    }
+
+    /// Returns the names of this bundle's sheet and every joined sheet.
+    fn sheets<'a>(&'a self, sheets: &mut Vec<&'a str>) {
+        if !sheets.contains(&self.sheet.as_str()) {
+            sheets.push(&self.sheet);
+        }
+        for join in self.joins.values() {
+            join.bundle.sheets(sheets);
+        }
+    }
+
+    /// Reads the sheets and builds the bundle's document.
+    pub fn export(
+        &self,
+        game_data: &GameData,
+        data_file_set: &mut DataFileSet,
+        language: Language,
+    ) -> Result<JsonValue, Error> {
+        let mut names = Vec::new();
+        self.sheets(&mut names);
+        let mut tables = Tables::new();
+        for name in names {
+            let table = read_sheet(game_data, data_file_set, name, language)
+                .map_err(|e| Error::Sheet(name.to_string(), e))?;
+            tables.insert(name.to_string(), table);
+        }
+        self.build(&tables)
+    }
+
+    /// Builds the bundle's document from sheets that were already read.
+    fn build(&self, tables: &Tables) -> Result<JsonValue, Error> {
+        let table = &tables[&self.sheet];
+        let mut rows = table.keys().copied().collect::<Vec<_>>();
+        rows.sort_unstable();
+        rows.into_iter()
+            .map(|row| self.build_row(tables, row, &table[&row]))
+            .collect()
+    }
+
+    fn build_row(
+        &self,
+        tables: &Tables,
+        row: u32,
+        cells: &[JsonValue],
+    ) -> Result<JsonValue, Error> {
+        let cell = |column: usize| {
+            cells.get(column).ok_or_else(|| Error::Column {
+                sheet: self.sheet.clone(),
+                column,
+            })
+        };
+        let mut object = Map::new();
+        object.insert("row".to_string(), row.into());
+        for (name, column) in self.columns.iter() {
+            object.insert(name.clone(), cell(*column)?.clone());
+        }
+        for name in self.icons.iter() {
+            let path = match object[name].as_u64() {
+                Some(0) | None => JsonValue::Null,
+                Some(icon) => u32::try_from(icon)
+                    .map(|icon| icon_path(icon, false).into())
+                    .unwrap_or(JsonValue::Null),
+            };
+            object.insert(format!("{}_path", name), path);
+        }
+        for (name, join) in self.joins.iter() {
+            let target = cell(join.column)?
+                .as_u64()
+                .and_then(|row| u32::try_from(row).ok())
+                .ok_or_else(|| Error::Column {
+                    sheet: self.sheet.clone(),
+                    column: join.column,
+                })?;
+            let value = match tables[&join.bundle.sheet].get(&target) {
+                Some(cells) => join.bundle.build_row(tables, target, cells)?,
+                None => JsonValue::Null,
+            };
+            object.insert(name.clone(), value);
+        }
+        Ok(JsonValue::Object(object))
+    }
+}
+
+/// The cells of the first sub-row of each row, by sheet name and row number.
+type Tables = HashMap<String, HashMap<u32, Vec<JsonValue>>>;
+
+fn read_sheet(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    sheet: &str,
+    language: Language,
+) -> Result<HashMap<u32, Vec<JsonValue>>, tomestone_exdf::Error> {
+    let dataset = Dataset::load(game_data, data_file_set, sheet, language)?;
+    let mut table = HashMap::new();
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            if let Some(sub_row) = row.sub_rows.first() {
+                table.insert(row.number, sub_row.cells.iter().map(plain_json).collect());
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// Converts a cell to JSON, with text as plain strings.
+fn plain_json(value: &Value<'_>) -> JsonValue {
+    match value.as_bytes() {
+        Some(data) => plain_text(data).into(),
+        None => crate::cell_json(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Bundle, Tables, BUILT_IN};
+
+    #[test]
+    fn built_in_definitions() {
+        for (name, definition) in BUILT_IN {
+            if let Err(e) = Bundle::parse(definition) {
+                panic!("{}: {}", name, e);
+            }
+        }
+        assert!(Bundle::parse("sheet = \"Emote\"\nicons = [\"icon\"]\n").is_err());
+        assert!(Bundle::parse("sheet = \"Emote\"\nunknown = 1\n").is_err());
+    }
+
+    #[test]
+    fn joins() {
+        let bundle = Bundle::load("achievement").unwrap();
+        let mut achievement = vec![json!(0); 12];
+        achievement[0] = json!(3);
+        achievement[1] = json!("To Crush Your Enemies I");
+        achievement[2] = json!("Defeat 100 enemies.");
+        achievement[5] = json!(5);
+        achievement[6] = json!(7);
+        achievement[11] = json!(121201);
+        let mut unknown_category = achievement.clone();
+        unknown_category[0] = json!(4);
+        unknown_category[11] = json!(0);
+        let tables: Tables = [
+            (
+                "Achievement".to_string(),
+                [(1, achievement), (2, unknown_category)].into(),
+            ),
+            (
+                "AchievementCategory".to_string(),
+                [(3, vec![json!("Battle"), json!(1)])].into(),
+            ),
+            (
+                "AchievementKind".to_string(),
+                [(1, vec![json!("Battle")])].into(),
+            ),
+            ("Title".to_string(), [].into()),
+        ]
+        .into();
+
+        assert_eq!(
+            bundle.build(&tables).unwrap(),
+            json!([
+                {
+                    "row": 1,
+                    "name": "To Crush Your Enemies I",
+                    "description": "Defeat 100 enemies.",
+                    "points": 5,
+                    "icon": 121201,
+                    "icon_path": "ui/icon/121000/121201.tex",
+                    "category": {
+                        "row": 3,
+                        "name": "Battle",
+                        "kind": {"row": 1, "name": "Battle"},
+                    },
+                    "title": null,
+                },
+                {
+                    "row": 2,
+                    "name": "To Crush Your Enemies I",
+                    "description": "Defeat 100 enemies.",
+                    "points": 5,
+                    "icon": 0,
+                    "icon_path": null,
+                    "category": null,
+                    "title": null,
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Exports whole sheets as CSV, for use in spreadsheets.
+//!
+//! Each sub-row becomes one record, starting with the row and sub-row numbers, so sheets with and
+//! without sub-rows have the same layout. Columns are named `c0`, `c1`, and so on, in table order,
+//! as in the sheet database written by the `archive` command. Text is converted to plain text, and vectors are written as JSON arrays.
+
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use tomestone_exdf::{Dataset, Value};
+use tomestone_string_interp::plain_text;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Sheet(tomestone_exdf::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Sheet(e) => write!(f, "reading sheet failed: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<tomestone_exdf::Error> for Error {
+    fn from(e: tomestone_exdf::Error) -> Error {
+        Error::Sheet(e)
+    }
+}
+
+/// A cell converted to a plain value, for flat formats such as CSV and SQLite.
+pub(crate) enum Cell {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f32),
+    /// Plain text, or a vector as a JSON array.
+    Text(String),
+}
+
+impl Cell {
+    pub(crate) fn new(value: &Value<'_>) -> Cell {
+        if let Some(data) = value.as_bytes() {
+            return Cell::Text(plain_text(data));
+        }
+        match value {
+            Value::Float(value) => Cell::Float(*value),
+            Value::I16x4(values) => Cell::Text(serde_json::to_string(values).unwrap()),
+            value => match (value.as_i64(), value.as_bool()) {
+                (Some(number), _) => Cell::Integer(number),
+                (None, Some(flag)) => Cell::Bool(flag),
+                (None, None) => Cell::Null,
+            },
+        }
+    }
+}
+
+/// Quotes a field if it contains a separator, a quote, or a line break.
+pub(crate) fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn field(value: &Value<'_>) -> String {
+    match Cell::new(value) {
+        Cell::Null => String::new(),
+        Cell::Bool(flag) => flag.to_string(),
+        Cell::Integer(number) => number.to_string(),
+        Cell::Float(number) => number.to_string(),
+        Cell::Text(text) => quote(&text),
+    }
+}
+
+/// Writes every page of a sheet, with a header line, and returns the number of records written.
+pub fn write_sheet<W: Write>(writer: &mut W, dataset: &Dataset<'_>) -> Result<u64, Error> {
+    let mut header = "row,sub_row".to_string();
+    for i in 0..dataset.exhf.columns_table_order().len() {
+        header.push_str(&format!(",c{}", i));
+    }
+    writeln!(writer, "{}", header)?;
+    let mut records = 0;
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            for sub_row in row.sub_rows.iter() {
+                let mut line = format!("{},{}", row.number, sub_row.number);
+                for value in sub_row.cells.iter() {
+                    line.push(',');
+                    line.push_str(&field(value));
+                }
+                writeln!(writer, "{}", line)?;
+                records += 1;
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_exdf::{
+        encoding::encode_exdf_page, parser::exhf::parse_exhf, Dataset, Language, Row, SubRow, Value,
+    };
+    use tomestone_sqpack::{Category, Expansion, GameData, MemoryProvider, SqPackId};
+
+    use super::{quote, write_sheet};
+
+    #[test]
+    fn multi_page_sheet() {
+        // A u32 column and an i16x4 column, in a sheet with sub-rows and two pages.
+        let mut exh_data = b"EXHF\x00\x03\x00\x0c\x00\x02\x00\x02\x00\x01\x00\x00\x00\x02".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 7, 0, 0, 0, 0xb, 0, 4]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+        exh_data.extend_from_slice(&[0, 0, 0, 10, 0, 0, 0, 1]);
+        exh_data.extend_from_slice(&[0, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+        let sub_row = |number, value| SubRow {
+            number,
+            cells: vec![Value::U32(value), Value::I16x4([1, 2, 3, -4])],
+        };
+        let first_page = encode_exdf_page(
+            "Quest",
+            &exhf,
+            &[
+                Row {
+                    number: 0,
+                    sub_rows: vec![sub_row(0, 1), sub_row(1, 2)],
+                },
+                Row {
+                    number: 1,
+                    sub_rows: vec![sub_row(0, 3)],
+                },
+            ],
+        );
+        let second_page = encode_exdf_page(
+            "Quest",
+            &exhf,
+            &[Row {
+                number: 10,
+                sub_rows: vec![sub_row(0, 4)],
+            }],
+        );
+
+        let mut provider = MemoryProvider::new();
+        provider
+            .write_pack(
+                SqPackId {
+                    category: Category::Exd,
+                    expansion: Expansion::Base,
+                    number: 0,
+                },
+                [
+                    ("exd/Quest.exh", &exh_data[..]),
+                    ("exd/Quest_0.exd", &first_page),
+                    ("exd/Quest_10.exd", &second_page),
+                ],
+            )
+            .unwrap();
+        let game_data = GameData::from_provider(provider);
+        let mut data_file_set = game_data.data_files();
+        let dataset =
+            Dataset::load(&game_data, &mut data_file_set, "Quest", Language::English).unwrap();
+
+        let mut csv = Vec::new();
+        assert_eq!(write_sheet(&mut csv, &dataset).unwrap(), 4);
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "row,sub_row,c0,c1\n\
+             0,0,1,\"[1,2,3,-4]\"\n\
+             0,1,2,\"[1,2,3,-4]\"\n\
+             1,0,3,\"[1,2,3,-4]\"\n\
+             10,0,4,\"[1,2,3,-4]\"\n"
+        );
+
+        assert_eq!(quote("Potion"), "Potion");
+        assert_eq!(quote("Hello, \"world\""), "\"Hello, \"\"world\"\"\"");
+        assert_eq!(quote("two\nlines"), "\"two\nlines\"");
+    }
+}
@@ -0,0 +1,66 @@
+//! Exit codes, and how batch commands handle errors.
+//!
+//! Every command exits with one of these codes, so scripts can tell problems in the game's files
+//! apart from problems running the tool:
+//!
+//! - 0: success.
+//! - 1: the command failed, for example because a file couldn't be read.
+//! - 2: the command line was invalid.
+//! - 3: the command ran, and found corruption or other problems (`check_indexes`, `verify`,
+//!   `compatibility`, `archive`).
+//! - 4: the requested file or sheet doesn't exist.
+//! - 5: partial success. A batch command skipped some items because of errors, and finished the
+//!   rest.
+//!
+//! Batch commands (`grep`, `check_indexes`, `verify`, and `icons`) take `--fail-fast` or
+//! `--keep-going`. With `--fail-fast`, they stop at the first error or problem. With
+//! `--keep-going`, they report errors with individual items as warnings, and carry on. Without
+//! either flag, `grep` fails fast, and the others keep going.
+
+use clap::ArgMatches;
+
+pub const FAILURE: i32 = 1;
+pub const USAGE: i32 = 2;
+pub const PROBLEMS_FOUND: i32 = 3;
+pub const NOT_FOUND: i32 = 4;
+pub const PARTIAL_SUCCESS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorMode {
+    FailFast,
+    KeepGoing,
+}
+
+impl ErrorMode {
+    /// Reads the mode from `--fail-fast` or `--keep-going`, falling back to the command's default.
+    pub fn from_matches(matches: &ArgMatches, default: ErrorMode) -> ErrorMode {
+        if matches.get_flag("fail-fast") {
+            ErrorMode::FailFast
+        } else if matches.get_flag("keep-going") {
+            ErrorMode::KeepGoing
+        } else {
+            default
+        }
+    }
+}
+
+/// What a batch command found, which decides its exit code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// Whether any corruption or other problems were found.
+    pub problems: bool,
+    /// Number of items skipped because of errors.
+    pub skipped: usize,
+}
+
+impl Outcome {
+    pub fn exit_code(&self) -> i32 {
+        if self.problems {
+            PROBLEMS_FOUND
+        } else if self.skipped > 0 {
+            PARTIAL_SUCCESS
+        } else {
+            0
+        }
+    }
+}
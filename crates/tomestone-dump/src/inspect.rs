@@ -0,0 +1,879 @@
+//! Annotated hex dumps of file headers, for `inspect`.
+//!
+//! Each header is broken down into fields, and each field is printed with its byte range, its
+//! raw bytes, its name, and its parsed value. Padding that isn't all zeros, and unexpected magic
+//! numbers, are pointed out, since they are the first sign of a new variation of a format.
+
+use std::{
+    fmt::Write,
+    io::{Read, Seek, SeekFrom},
+};
+
+use serde_json::json;
+use tomestone_exdf::ColumnFormat;
+use tomestone_sqpack::{DataFileSet, GameData, GameVersion};
+use tomestone_texture::{decode_tex, TextureFormat, MAX_MIP_LEVELS};
+
+use crate::{output::Output, INSPECT_READ_LIMIT};
+
+/// The size of SqPack headers, and of the index header that follows one.
+const HEADER_SIZE: usize = 0x400;
+/// The offset of the SHA-1 hash within a SqPack header or index header.
+const HASH_OFFSET: usize = 0x3c0;
+
+pub struct Field {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    pub name: String,
+    pub value: String,
+}
+
+pub struct Section {
+    pub title: &'static str,
+    pub fields: Vec<Field>,
+    /// Set if the data ended before the end of the header.
+    pub truncated: bool,
+}
+
+/// Reads fields one after another, recording each as it goes.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    fields: Vec<Field>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Reader<'a> {
+        Reader {
+            data,
+            offset,
+            fields: Vec::new(),
+        }
+    }
+
+    fn field(
+        &mut self,
+        name: impl Into<String>,
+        len: usize,
+        value: impl FnOnce(&[u8]) -> String,
+    ) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.fields.push(Field {
+            offset: self.offset,
+            bytes: bytes.to_vec(),
+            name: name.into(),
+            value: value(bytes),
+        });
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn le_u16(&mut self, name: impl Into<String>) -> Option<u16> {
+        let bytes = self.field(name, 2, |bytes| {
+            u16::from_le_bytes(bytes.try_into().unwrap()).to_string()
+        })?;
+        Some(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn be_u16(&mut self, name: impl Into<String>) -> Option<u16> {
+        let bytes = self.field(name, 2, |bytes| {
+            u16::from_be_bytes(bytes.try_into().unwrap()).to_string()
+        })?;
+        Some(u16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads a big-endian `u16`, and describes its value with the given function.
+    fn be_u16_with(
+        &mut self,
+        name: impl Into<String>,
+        describe: impl FnOnce(u16) -> String,
+    ) -> Option<u16> {
+        let bytes = self.field(name, 2, |bytes| {
+            describe(u16::from_be_bytes(bytes.try_into().unwrap()))
+        })?;
+        Some(u16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn le_u32(&mut self, name: impl Into<String>) -> Option<u32> {
+        self.le_u32_with(name, |value| value.to_string())
+    }
+
+    /// Reads a little-endian `u32`, and describes its value with the given function.
+    fn le_u32_with(
+        &mut self,
+        name: impl Into<String>,
+        describe: impl FnOnce(u32) -> String,
+    ) -> Option<u32> {
+        let bytes = self.field(name, 4, |bytes| {
+            describe(u32::from_le_bytes(bytes.try_into().unwrap()))
+        })?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn be_u32(&mut self, name: impl Into<String>) -> Option<u32> {
+        let bytes = self.field(name, 4, |bytes| {
+            u32::from_be_bytes(bytes.try_into().unwrap()).to_string()
+        })?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads a fixed magic number, noting if it has a different value.
+    fn magic(&mut self, name: impl Into<String>, expected: &[u8]) -> Option<&'a [u8]> {
+        self.field(name, expected.len(), |bytes| {
+            let mut value = format!("\"{}\"", bytes.escape_ascii());
+            if bytes != expected {
+                write!(value, " (expected \"{}\")", expected.escape_ascii()).unwrap();
+            }
+            value
+        })
+    }
+
+    fn hash(&mut self, name: impl Into<String>, expected: Option<[u8; 20]>) -> Option<&'a [u8]> {
+        self.field(name, 20, |bytes| {
+            let mut value = hex::encode(bytes);
+            match expected {
+                Some(expected) if expected == bytes => value.push_str(" (valid)"),
+                Some(expected) => write!(value, " (expected {})", hex::encode(expected)).unwrap(),
+                None => {}
+            }
+            value
+        })
+    }
+
+    fn padding(&mut self, len: usize) -> Option<&'a [u8]> {
+        self.field("padding", len, |bytes| {
+            if bytes.iter().all(|byte| *byte == 0) {
+                String::new()
+            } else {
+                "(not all zeros)".to_string()
+            }
+        })
+    }
+
+    /// Reads padding up to the given offset.
+    fn padding_until(&mut self, offset: usize) -> Option<&'a [u8]> {
+        if offset <= self.offset {
+            return Some(&[]);
+        }
+        self.padding(offset - self.offset)
+    }
+
+    fn finish(self, title: &'static str, complete: Option<()>) -> Section {
+        Section {
+            title,
+            fields: self.fields,
+            truncated: complete.is_none(),
+        }
+    }
+}
+
+/// Expected hash of a header, which covers everything before the hash itself.
+fn header_hash(data: &[u8], start: usize) -> Option<[u8; 20]> {
+    Some(tomestone_sqpack::sha1(
+        data.get(start..start + HASH_OFFSET)?,
+    ))
+}
+
+/// Breaks down the SqPack header at the start of an index or data file. Returns the header, and
+/// the file type.
+pub fn sqpack_header(data: &[u8]) -> (Section, Option<u32>) {
+    let mut reader = Reader::new(data, 0);
+    let mut sqpack_type = None;
+    let complete = (|| {
+        reader.magic("magic", b"SqPack\0\0")?;
+        reader.field("platform", 1, |bytes| {
+            match bytes[0] {
+                0 => "0 (Win32)",
+                1 => "1 (PS3)",
+                2 => "2 (PS4)",
+                _ => "(unknown)",
+            }
+            .to_string()
+        })?;
+        reader.padding(3)?;
+        reader.le_u32("size")?;
+        reader.le_u32("version")?;
+        sqpack_type = Some(reader.le_u32_with("type", |value| match value {
+            0 => "0 (SqDB)".to_string(),
+            1 => "1 (data)".to_string(),
+            2 => "2 (index)".to_string(),
+            _ => format!("{} (unknown)", value),
+        })?);
+        reader.le_u32("date")?;
+        reader.le_u32("time")?;
+        reader.magic("marker", b"\xff\xff\xff\xff")?;
+        reader.padding_until(HASH_OFFSET)?;
+        reader.hash("sha1", header_hash(data, 0))?;
+        reader.padding_until(HEADER_SIZE)?;
+        Some(())
+    })();
+    (reader.finish("SqPack header", complete), sqpack_type)
+}
+
+/// Breaks down the header that follows the SqPack header in an index file.
+pub fn index_header(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, HEADER_SIZE);
+    let complete = (|| {
+        reader.le_u32("header length")?;
+        reader.le_u32("unknown")?;
+        for segment in 1..=4 {
+            if segment == 2 {
+                reader.le_u32("dat file count")?;
+            }
+            reader.le_u32(format!("segment {} offset", segment))?;
+            reader.le_u32(format!("segment {} size", segment))?;
+            reader.hash(format!("segment {} sha1", segment), None)?;
+            if segment < 4 {
+                reader.padding(44)?;
+            }
+        }
+        reader.padding_until(HEADER_SIZE + HASH_OFFSET)?;
+        reader.hash("sha1", header_hash(data, HEADER_SIZE))?;
+        reader.padding_until(HEADER_SIZE * 2)?;
+        Some(())
+    })();
+    reader.finish("index header", complete)
+}
+
+/// Breaks down the headers of a data entry, including its block table. Offsets are relative to
+/// the start of the entry.
+pub fn data_entry_headers(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = (|| {
+        let header_length = reader.le_u32("header length")?;
+        let content_type = reader.le_u32_with("type", |value| match value {
+            0 => "0 (tombstone)".to_string(),
+            1 => "1 (empty)".to_string(),
+            2 => "2 (binary)".to_string(),
+            3 => "3 (model)".to_string(),
+            4 => "4 (texture)".to_string(),
+            _ => format!("{} (unknown)", value),
+        })?;
+        reader.le_u32("uncompressed size")?;
+        if content_type == 3 {
+            model_block_table(&mut reader)?;
+        } else {
+            reader.le_u32("unknown")?;
+            reader.le_u32_with("block buffer size", |value| {
+                format!("{} ({} bytes)", value, u64::from(value) << 7)
+            })?;
+            let num_blocks = reader.le_u16("number of blocks")?;
+            reader.le_u16("unknown")?;
+            match content_type {
+                2 => {
+                    for block in 0..num_blocks {
+                        reader.le_u32(format!("block {} offset", block))?;
+                        reader.le_u16(format!("block {} size", block))?;
+                        reader.le_u16(format!("block {} decompressed size", block))?;
+                    }
+                }
+                4 => {
+                    let mut block_size_count = 0;
+                    for frame in 0..num_blocks {
+                        reader.le_u32(format!("frame {} offset", frame))?;
+                        reader.le_u32(format!("frame {} size", frame))?;
+                        reader.le_u32(format!("frame {} decompressed size", frame))?;
+                        reader.le_u32(format!("frame {} first block", frame))?;
+                        block_size_count +=
+                            reader.le_u32(format!("frame {} block count", frame))?;
+                    }
+                    for block in 0..block_size_count {
+                        reader.le_u16(format!("block {} size", block))?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let header_length = usize::try_from(header_length).unwrap();
+        if header_length > reader.offset {
+            reader.field("rest of headers", header_length - reader.offset, |_| {
+                String::new()
+            })?;
+        }
+        Some(())
+    })();
+    reader.finish("data entry headers", complete)
+}
+
+/// Names of the sections of a model entry, in the order of its tables.
+const MODEL_SECTIONS: [&str; 11] = [
+    "stack",
+    "runtime",
+    "vertex buffer 0",
+    "vertex buffer 1",
+    "vertex buffer 2",
+    "edge geometry 0",
+    "edge geometry 1",
+    "edge geometry 2",
+    "index buffer 0",
+    "index buffer 1",
+    "index buffer 2",
+];
+
+/// Breaks down the rest of a model entry's headers, after the uncompressed size.
+fn model_block_table(reader: &mut Reader<'_>) -> Option<()> {
+    let num_blocks = reader.le_u32("number of blocks")?;
+    reader.le_u32("number of blocks used")?;
+    reader.le_u32("model version")?;
+    for table in ["decompressed size", "compressed size", "offset"] {
+        for section in MODEL_SECTIONS {
+            reader.le_u32(format!("{} {}", section, table))?;
+        }
+    }
+    for table in ["first block", "block count"] {
+        for section in MODEL_SECTIONS {
+            reader.le_u16(format!("{} {}", section, table))?;
+        }
+    }
+    reader.le_u16("number of vertex declarations")?;
+    reader.le_u16("number of materials")?;
+    reader.field("number of levels of detail", 1, |bytes| {
+        bytes[0].to_string()
+    })?;
+    reader.field("index buffer streaming", 1, |bytes| {
+        (bytes[0] != 0).to_string()
+    })?;
+    reader.field("edge geometry", 1, |bytes| (bytes[0] != 0).to_string())?;
+    reader.padding(1)?;
+    for block in 0..num_blocks {
+        reader.le_u16(format!("block {} size", block))?;
+    }
+    Some(())
+}
+
+/// Breaks down an EXH file, with its column, page, and language tables.
+pub fn exh_header(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = (|| {
+        reader.magic("magic", b"EXHF")?;
+        reader.be_u16("version")?;
+        reader.be_u16("row size")?;
+        let num_columns = reader.be_u16("number of columns")?;
+        let num_pages = reader.be_u16("number of pages")?;
+        let num_language_codes = reader.be_u16("number of languages")?;
+        reader.be_u16_with("unknown", |value| {
+            format!(
+                "{} (flag: {}, number: {})",
+                value,
+                value & 0x4000 != 0,
+                value & 0x3fff
+            )
+        })?;
+        reader.be_u16_with("cardinality", |value| match value {
+            1 => "1 (single)".to_string(),
+            2 => "2 (multiple)".to_string(),
+            _ => format!("{} (unknown)", value),
+        })?;
+        reader.be_u16("unknown")?;
+        reader.be_u32("total sub-rows")?;
+        reader.padding(8)?;
+        for column in 0..num_columns {
+            reader.be_u16_with(format!("column {} format", column), |value| {
+                match ColumnFormat::from_u16(value) {
+                    Ok(format) => format!("{} ({:?})", value, format),
+                    Err(_) => format!("{} (unknown)", value),
+                }
+            })?;
+            reader.be_u16(format!("column {} offset", column))?;
+        }
+        for page in 0..num_pages {
+            reader.be_u32(format!("page {} first row", page))?;
+            reader.be_u32(format!("page {} row count", page))?;
+        }
+        for language in 0..num_language_codes {
+            reader.le_u16(format!("language {}", language))?;
+        }
+        Some(())
+    })();
+    reader.finish("EXH header", complete)
+}
+
+/// Breaks down an EXD page's header and its table of row offsets.
+pub fn exd_header(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = (|| {
+        reader.magic("magic", b"EXDF")?;
+        reader.be_u16("version")?;
+        reader.be_u16("unknown")?;
+        let offset_table_size = reader.be_u32("offset table size")?;
+        reader.be_u32("data section size")?;
+        reader.padding(16)?;
+        for row in 0..offset_table_size / 8 {
+            reader.be_u32(format!("row {} number", row))?;
+            reader.be_u32(format!("row {} offset", row))?;
+        }
+        Some(())
+    })();
+    reader.finish("EXD header", complete)
+}
+
+/// Breaks down the header of a decompressed `.mdl` file, which gives the size of each section.
+pub fn mdl_header(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = (|| {
+        reader.le_u32("version")?;
+        reader.le_u32("stack size")?;
+        reader.le_u32("runtime size")?;
+        reader.le_u16("number of vertex declarations")?;
+        reader.le_u16("number of materials")?;
+        for table in [
+            "vertex buffer offset",
+            "index buffer offset",
+            "vertex buffer size",
+            "index buffer size",
+        ] {
+            for lod in 0..3 {
+                reader.le_u32(format!("level of detail {} {}", lod, table))?;
+            }
+        }
+        reader.field("number of levels of detail", 1, |bytes| {
+            bytes[0].to_string()
+        })?;
+        reader.field("index buffer streaming", 1, |bytes| {
+            (bytes[0] != 0).to_string()
+        })?;
+        reader.field("edge geometry", 1, |bytes| (bytes[0] != 0).to_string())?;
+        reader.padding(1)?;
+        Some(())
+    })();
+    reader.finish("model header", complete)
+}
+
+/// Breaks down an `.scd` sound bank's file header and table header.
+pub fn scd_headers(data: &[u8]) -> Vec<Section> {
+    let mut reader = Reader::new(data, 0);
+    let mut table_header_offset = None;
+    let complete = (|| {
+        reader.magic("magic", b"SEDBSSCF")?;
+        reader.le_u32("version")?;
+        reader.le_u16("unknown")?;
+        table_header_offset = Some(reader.le_u16("table header offset")?);
+        Some(())
+    })();
+    let mut sections = vec![reader.finish("SCD header", complete)];
+    if let Some(offset) = table_header_offset {
+        let mut reader = Reader::new(data, offset.into());
+        let complete = (|| {
+            reader.le_u16("table 1 count")?;
+            reader.le_u16("table 2 count")?;
+            let entry_count = reader.le_u16("audio entry count")?;
+            reader.le_u16("unknown")?;
+            reader.le_u32("table 1 offset")?;
+            let entry_table_offset = reader.le_u32("audio entry table offset")?;
+            reader.offset = usize::try_from(entry_table_offset).unwrap();
+            for entry in 0..entry_count {
+                reader.le_u32(format!("audio entry {} offset", entry))?;
+            }
+            Some(())
+        })();
+        sections.push(reader.finish("SCD table header", complete));
+    }
+    sections
+}
+
+/// Breaks down the chunks of a `.patch` file, up to the end of file chunk. Only the start of large
+/// patches is read, so the chunk list usually ends early.
+pub fn patch_chunks(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = (|| {
+        reader.magic("magic", tomestone_patch::MAGIC)?;
+        for chunk in 0.. {
+            let size = reader.be_u32(format!("chunk {} size", chunk))?;
+            let tag = reader.field(format!("chunk {} tag", chunk), 4, |bytes| {
+                format!("\"{}\"", bytes.escape_ascii())
+            })?;
+            reader.field(
+                format!("chunk {} body", chunk),
+                size.try_into().unwrap(),
+                |_| String::new(),
+            )?;
+            reader.field(format!("chunk {} crc32", chunk), 4, |bytes| {
+                hex::encode(bytes)
+            })?;
+            if tag == b"EOF_" {
+                break;
+            }
+        }
+        Some(())
+    })();
+    reader.finish("patch chunks", complete)
+}
+
+/// Breaks down a `.ver` file, which holds the version of the game or of an expansion as text.
+pub fn ver_file(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = reader
+        .field("version", data.len(), |bytes| {
+            let text = String::from_utf8_lossy(bytes);
+            match text.parse::<GameVersion>() {
+                Ok(version) => version.to_string(),
+                Err(_) => "(not a version)".to_string(),
+            }
+        })
+        .map(|_| ());
+    reader.finish("version file", complete)
+}
+
+/// Breaks down the header of a `.tex` file.
+pub fn tex_header(data: &[u8]) -> Section {
+    let mut reader = Reader::new(data, 0);
+    let complete = (|| {
+        reader.le_u32_with("attribute", |value| format!("{:#010x}", value))?;
+        reader.le_u32_with("format", |value| match TextureFormat::from_u32(value) {
+            Some(format) => format!("{:#06x} ({:?})", value, format),
+            None => format!("{:#06x} (unknown)", value),
+        })?;
+        reader.le_u16("width")?;
+        reader.le_u16("height")?;
+        reader.le_u16("depth")?;
+        reader.le_u16("mipmap count")?;
+        for lod in 0..3 {
+            reader.le_u32(format!("level of detail {} first mipmap", lod))?;
+        }
+        for mip in 0..MAX_MIP_LEVELS {
+            reader.le_u32(format!("mipmap {} offset", mip))?;
+        }
+        Some(())
+    })();
+    reader.finish("texture header", complete)
+}
+
+/// A file format that can be broken down into sections. Formats are recognized by their magic
+/// number, or by the extension of paths in the game's files for formats without one.
+pub trait FormatHandler: Sync {
+    /// The magic number at the start of every file in this format, if it has one.
+    fn magic(&self) -> Option<&'static [u8]>;
+
+    /// The extension of files in this format, without the dot.
+    fn extension(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section>;
+
+    /// Converts a file to a widely supported format, for `raw --convert`. Returns `None` if there
+    /// is no such format.
+    fn convert(&self, _data: &[u8]) -> Option<Result<Vec<u8>, String>> {
+        None
+    }
+}
+
+struct SqPackHandler;
+
+impl FormatHandler for SqPackHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        Some(b"SqPack")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        let (header, sqpack_type) = sqpack_header(data);
+        let mut sections = vec![header];
+        if sqpack_type == Some(2) {
+            sections.push(index_header(data));
+        }
+        sections
+    }
+}
+
+struct ExhHandler;
+
+impl FormatHandler for ExhHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        Some(b"EXHF")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        vec![exh_header(data)]
+    }
+}
+
+struct ExdHandler;
+
+impl FormatHandler for ExdHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        Some(b"EXDF")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        vec![exd_header(data)]
+    }
+}
+
+struct ScdHandler;
+
+impl FormatHandler for ScdHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        Some(b"SEDBSSCF")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        scd_headers(data)
+    }
+}
+
+struct PatchHandler;
+
+impl FormatHandler for PatchHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        Some(tomestone_patch::MAGIC)
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        vec![patch_chunks(data)]
+    }
+}
+
+struct MdlHandler;
+
+impl FormatHandler for MdlHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("mdl")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        vec![mdl_header(data)]
+    }
+}
+
+struct VerHandler;
+
+impl FormatHandler for VerHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("ver")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        vec![ver_file(data)]
+    }
+}
+
+struct TexHandler;
+
+impl FormatHandler for TexHandler {
+    fn magic(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("tex")
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Section> {
+        vec![tex_header(data)]
+    }
+
+    fn convert(&self, data: &[u8]) -> Option<Result<Vec<u8>, String>> {
+        Some((|| {
+            let image = decode_tex(data).map_err(|e| e.to_string())?;
+            let mut png = Vec::new();
+            image.write_png(&mut png).map_err(|e| e.to_string())?;
+            Ok(png)
+        })())
+    }
+}
+
+/// Every recognized format. Add new formats here.
+static FORMAT_HANDLERS: &[&dyn FormatHandler] = &[
+    &SqPackHandler,
+    &ExhHandler,
+    &ExdHandler,
+    &ScdHandler,
+    &PatchHandler,
+    &TexHandler,
+    &MdlHandler,
+    &VerHandler,
+];
+
+/// Finds the handler for a file's format, by sniffing its magic number.
+pub fn find_handler(data: &[u8]) -> Option<&'static dyn FormatHandler> {
+    FORMAT_HANDLERS
+        .iter()
+        .copied()
+        .find(|handler| handler.magic().is_some_and(|magic| data.starts_with(magic)))
+}
+
+/// Finds the handler for a file's format, by its magic number, or else by the extension of its
+/// path.
+pub fn find_handler_for_path(path: &str, data: &[u8]) -> Option<&'static dyn FormatHandler> {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+    find_handler(data).or_else(|| {
+        FORMAT_HANDLERS
+            .iter()
+            .copied()
+            .find(|handler| handler.extension().is_some() && handler.extension() == extension)
+    })
+}
+
+/// Breaks down a file from disk, recognizing it by its magic number or its extension.
+pub fn inspect_file(path: &str, data: &[u8]) -> Option<Vec<Section>> {
+    find_handler_for_path(path, data).map(|handler| handler.inspect(data))
+}
+
+/// Breaks down the headers of the data entry for a path in the game's files, and the headers of
+/// the file itself, if its magic number is recognized. Returns `None` if the path isn't found.
+pub fn inspect_game_file(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    path: &str,
+) -> Result<Option<Vec<Section>>, tomestone_sqpack::Error> {
+    let (pack_id, pointer) = match game_data.lookup_path_locator(path)? {
+        Some(locator) => locator,
+        None => return Ok(None),
+    };
+    let file = data_file_set.open(pack_id, pointer.data_file_id())?;
+    file.seek(SeekFrom::Start(pointer.offset().into()))?;
+    let mut header_length = [0; 4];
+    file.read_exact(&mut header_length)?;
+    file.seek(SeekFrom::Start(pointer.offset().into()))?;
+    let mut headers = Vec::new();
+    file.take(u32::from_le_bytes(header_length).into())
+        .read_to_end(&mut headers)?;
+    let mut sections = vec![data_entry_headers(&headers)];
+
+    let mut data = Vec::new();
+    data_file_set
+        .open_entry(pack_id, pointer)?
+        .take(INSPECT_READ_LIMIT)
+        .read_to_end(&mut data)?;
+    if let Some(handler) = find_handler_for_path(path, &data) {
+        sections.extend(handler.inspect(&data));
+    }
+    Ok(Some(sections))
+}
+
+/// Number of bytes of each field to print in text output.
+const SHOWN_BYTES: usize = 8;
+
+pub fn print_sections(sections: &[Section], output: &mut Output) {
+    for section in sections {
+        if output.is_text() {
+            println!("{}:", section.title);
+        }
+        for field in section.fields.iter() {
+            if output.is_text() {
+                let mut bytes = field
+                    .bytes
+                    .iter()
+                    .take(SHOWN_BYTES)
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if field.bytes.len() > SHOWN_BYTES {
+                    bytes.push_str(" ..");
+                }
+                let mut line = format!(
+                    "  {:08x}-{:08x}  {:<26}  {}",
+                    field.offset,
+                    field.offset + field.bytes.len(),
+                    bytes,
+                    field.name,
+                );
+                if !field.value.is_empty() {
+                    write!(line, ": {}", field.value).unwrap();
+                }
+                println!("{}", line);
+            } else {
+                output.record(json!({
+                    "section": section.title,
+                    "offset": field.offset,
+                    "size": field.bytes.len(),
+                    "name": field.name,
+                    "value": field.value,
+                    "data": hex::encode(&field.bytes),
+                }));
+            }
+        }
+        if section.truncated {
+            eprintln!("warning: the {} ended early", section.title);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_texture::{encode_tex, RgbaImage, TextureFormat};
+
+    use super::{data_entry_headers, find_handler_for_path, inspect_file};
+
+    #[test]
+    fn annotates_headers() {
+        let mut data = b"SqPack\0\0\0\0\0\0".to_vec();
+        for field in [0x400u32, 1, 2, 0, 0, 0xffffffff] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.resize(0x3c0, 0);
+        let hash = tomestone_sqpack::sha1(&data);
+        data.extend_from_slice(&hash);
+        data.resize(0x400, 0);
+
+        let sections = inspect_file("000000.win32.index", &data).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert!(!sections[0].truncated);
+        let type_field = &sections[0].fields[5];
+        assert_eq!(
+            (type_field.offset, type_field.value.as_str()),
+            (0x14, "2 (index)")
+        );
+        assert!(sections[0].fields[10].value.ends_with("(valid)"));
+        // The index header is missing.
+        assert!(sections[1].truncated);
+
+        let mut entry = Vec::new();
+        for field in [128u32, 2, 0x100, 0, 2] {
+            entry.extend_from_slice(&field.to_le_bytes());
+        }
+        entry.extend_from_slice(&1u16.to_le_bytes());
+        entry.extend_from_slice(&0u16.to_le_bytes());
+        entry.extend_from_slice(&0u32.to_le_bytes());
+        entry.extend_from_slice(&0x80u16.to_le_bytes());
+        entry.extend_from_slice(&0x100u16.to_le_bytes());
+        entry.resize(128, 0);
+        let section = data_entry_headers(&entry);
+        assert!(!section.truncated);
+        let names = section
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            &names[7..],
+            [
+                "block 0 offset",
+                "block 0 size",
+                "block 0 decompressed size",
+                "rest of headers"
+            ]
+        );
+        assert_eq!(section.fields[1].value, "2 (binary)");
+    }
+
+    #[test]
+    fn recognizes_textures_by_extension() {
+        let image = RgbaImage::new(2, 1, vec![255; 8]).unwrap();
+        let tex = encode_tex(&image, TextureFormat::B8G8R8A8, false).unwrap();
+        assert!(inspect_file("icon.bin", &tex).is_none());
+        let sections = inspect_file("icon.tex", &tex).unwrap();
+        assert_eq!(sections[0].title, "texture header");
+        assert!(!sections[0].truncated);
+        assert_eq!(sections[0].fields[1].value, "0x1450 (B8G8R8A8)");
+        assert_eq!(sections[0].fields[2].value, "2");
+
+        let handler = find_handler_for_path("icon.tex", &tex).unwrap();
+        assert!(handler
+            .convert(&tex)
+            .unwrap()
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+        // Other formats have no converter.
+        let handler = find_handler_for_path("exd/Item.exh", b"EXHF").unwrap();
+        assert!(handler.convert(b"EXHF").is_none());
+    }
+}
@@ -1,28 +1,66 @@
+mod archive;
+mod bundle;
+mod csv;
+mod exit;
+mod inspect;
+mod output;
+mod provenance;
+mod stats;
+
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fmt::Write as FmtWrite,
-    io::{self, stdout, Write},
-    path::PathBuf,
+    fs::{self, File},
+    io::{self, stdout, BufWriter, Read, Write},
+    path::{Path, PathBuf},
     process,
 };
 
 use clap::{
-    builder::{EnumValueParser, ValueParser},
-    crate_authors, crate_description, crate_name, crate_version, Arg, ArgAction, Command,
+    builder::{EnumValueParser, PossibleValuesParser, ValueParser},
+    crate_authors, crate_description, crate_name, crate_version, Arg, ArgAction, ArgMatches,
+    Command,
 };
+use clap_complete::Shell;
 use once_cell::sync::Lazy;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 use regex::{
     bytes::{Regex as BytesRegex, RegexBuilder as BytesRegexBuilder},
     Regex,
 };
+use serde_json::json;
 
-use tomestone_exdf::{Dataset, Language, RootList, Value};
+use tomestone_common::{fuzzy, paths};
+use tomestone_exdf::{localization, Dataset, Language, RootList, Value};
+use tomestone_model::{
+    collision::CollisionMesh,
+    housing::{export_furniture, list_furniture},
+};
+use tomestone_patch::{
+    install::{check_boot_files, BootProblem, InstallVersions},
+    versions::VersionDatabase,
+};
+use tomestone_sound::names::{best_name, sound_names};
 use tomestone_sqpack::{
+    compatibility, normalize_path,
     pathdb::{PathDb, PreparedStatements},
-    Category, DataFileSet, Expansion, FilePointer, GameData, Index, IndexEntry2, IndexHash1,
-    IndexHash2,
+    patterns::PatternSet,
+    Category, DataFileSet, Expansion, FilePointer, GameData, Index, IndexDiscrepancy, IndexEntry2,
+    IndexHash1, IndexHash2, ReadAhead, Reservation, SqPackId,
+};
+use tomestone_string_interp::{plain_text, TagStatistics, Text};
+use tomestone_texture::{
+    decode_tex,
+    icon::{contact_sheet, icon_path},
+    RgbaImage,
+};
+
+use crate::{
+    bundle::Bundle,
+    exit::{ErrorMode, Outcome},
+    output::{Format, Output},
+    provenance::Provenance,
 };
-use tomestone_string_interp::Text;
 
 /// Looks up a file by any combination of folders, filenames, their CRCs, or path CRCs, and
 /// returns the contents of the file. This function is permissive with regards to formatting,
@@ -63,12 +101,12 @@ fn lookup<'a>(
                 0 | 1 => Ok(results.into_iter().next()),
                 _ => {
                     eprintln!("error: multiple files share this CRC-32 hash");
-                    process::exit(1);
+                    process::exit(exit::FAILURE);
                 }
             }
         } else {
             eprintln!("error: invalid CRC-32 hashes or multiple paths provided");
-            process::exit(1);
+            process::exit(exit::USAGE);
         }
     } else if CRC_RE.is_match(first_arg) {
         // one CRC-32
@@ -79,7 +117,7 @@ fn lookup<'a>(
             0 | 1 => Ok(results.into_iter().next()),
             _ => {
                 eprintln!("error: multiple files share this CRC-32 hash");
-                process::exit(1);
+                process::exit(exit::FAILURE);
             }
         }
     } else {
@@ -110,11 +148,11 @@ fn lookup<'a>(
                 .or_else(|| caps.get(12))
                 .unwrap()
                 .as_str();
-            let mut folder_crcs = vec![tomestone_sqpack::crc32(left.to_lowercase().as_bytes())];
+            let mut folder_crcs = vec![tomestone_sqpack::crc32(normalize_path(left).as_bytes())];
             if let Ok(crc) = u32::from_str_radix(left, 16) {
                 folder_crcs.push(crc);
             }
-            let mut filename_crcs = vec![tomestone_sqpack::crc32(right.to_lowercase().as_bytes())];
+            let mut filename_crcs = vec![tomestone_sqpack::crc32(normalize_path(right).as_bytes())];
             if let Ok(crc) = u32::from_str_radix(right, 16) {
                 filename_crcs.push(crc);
             }
@@ -127,7 +165,7 @@ fn lookup<'a>(
                         1 => return Ok(results.into_iter().next()),
                         _ => {
                             eprintln!("error: multiple files share this CRC-32 hash");
-                            process::exit(1);
+                            process::exit(exit::FAILURE);
                         }
                     }
                 }
@@ -252,6 +290,56 @@ fn write_file_name_2<W: Write>(
     Ok(())
 }
 
+/// Formats a pack's ID the way its files are named, e.g. `0a0000`.
+fn pack_name(id: SqPackId) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}",
+        id.category.to_u8(),
+        id.expansion.to_u8(),
+        id.number
+    )
+}
+
+/// Returns the name [`write_file_name_1`] would print.
+fn file_name_1(
+    statements: &mut PreparedStatements<'_>,
+    hash: IndexHash1,
+    pointer: FilePointer,
+    index_2: Option<&Index<IndexEntry2>>,
+) -> Result<String, tomestone_sqpack::Error> {
+    let mut name = Vec::new();
+    write_file_name_1(&mut name, statements, hash, pointer, index_2)?;
+    // Names found by cross-referencing the indexes are written with a trailing newline.
+    Ok(String::from_utf8_lossy(&name).trim_end().to_string())
+}
+
+/// Returns the name [`write_file_name_2`] would print.
+fn file_name_2(
+    statements: &mut PreparedStatements<'_>,
+    hash: IndexHash2,
+) -> Result<String, tomestone_sqpack::Error> {
+    let mut name = Vec::new();
+    write_file_name_2(&mut name, statements, hash)?;
+    Ok(String::from_utf8_lossy(&name).into_owned())
+}
+
+/// Report a file found by a command, either as a line of text between the given prefix and
+/// suffix, or as a result with its pack and path.
+fn report_path<W: Write>(
+    writer: &mut W,
+    output: &mut Output,
+    id: SqPackId,
+    path: String,
+    prefix: &str,
+    suffix: &str,
+) {
+    if output.is_text() {
+        writeln!(writer, "{}{}{}", prefix, path, suffix).unwrap();
+    } else {
+        output.record(json!({"pack": pack_name(id), "path": path}));
+    }
+}
+
 /// List all files in a given category and expansion, printing paths if they are known to the CRC
 /// database, or CRC hashes otherwise. The listing will be printed to standard output.
 fn list_files(
@@ -259,6 +347,7 @@ fn list_files(
     category: Category,
     expansion: Expansion,
     statements: &mut PreparedStatements<'_>,
+    output: &mut Output,
 ) -> Result<(), tomestone_sqpack::Error> {
     let stdout = stdout();
     let mut locked = stdout.lock();
@@ -267,21 +356,21 @@ fn list_files(
             (None, None) => unreachable!(),
             (None, Some(index_2_res)) => {
                 for (hash, _pointer) in index_2_res?.iter() {
-                    write_file_name_2(&mut locked, statements, hash)?;
-                    locked.write_all(b"\n").unwrap();
+                    let path = file_name_2(statements, hash)?;
+                    report_path(&mut locked, output, id, path, "", "");
                 }
             }
             (Some(index_1_res), None) => {
                 for (hash, pointer) in index_1_res?.iter() {
-                    write_file_name_1(&mut locked, statements, hash, pointer, None)?;
-                    locked.write_all(b"\n").unwrap();
+                    let path = file_name_1(statements, hash, pointer, None)?;
+                    report_path(&mut locked, output, id, path, "", "");
                 }
             }
             (Some(index_1_res), Some(index_2_res)) => {
                 let index_2 = index_2_res?;
                 for (hash, pointer) in index_1_res?.iter() {
-                    write_file_name_1(&mut locked, statements, hash, pointer, Some(index_2))?;
-                    locked.write_all(b"\n").unwrap();
+                    let path = file_name_1(statements, hash, pointer, Some(index_2))?;
+                    report_path(&mut locked, output, id, path, "", "");
                 }
             }
         }
@@ -289,6 +378,352 @@ fn list_files(
     Ok(())
 }
 
+/// Name of each kind of index discrepancy, used in machine-readable output.
+fn discrepancy_kind(discrepancy: &IndexDiscrepancy) -> &'static str {
+    match discrepancy {
+        IndexDiscrepancy::DatFileCount { .. } => "dat_file_count",
+        IndexDiscrepancy::MissingFromIndex2 { .. } => "missing_from_index2",
+        IndexDiscrepancy::MissingFromIndex1 { .. } => "missing_from_index1",
+        IndexDiscrepancy::SharedPointer { .. } => "shared_pointer",
+    }
+}
+
+/// Print one disagreement between the `.index` and `.index2` files of a pack.
+fn write_discrepancy<W: Write>(
+    writer: &mut W,
+    statements: &mut PreparedStatements<'_>,
+    id: SqPackId,
+    discrepancy: IndexDiscrepancy,
+) -> Result<(), tomestone_sqpack::Error> {
+    write!(writer, "{}: ", pack_name(id)).unwrap();
+    write_discrepancy_message(writer, statements, discrepancy)?;
+    writer.write_all(b"\n").unwrap();
+    Ok(())
+}
+
+/// Report one index discrepancy, either as a line of text, or as a result with the given extra
+/// fields.
+fn report_discrepancy<W: Write>(
+    writer: &mut W,
+    output: &mut Output,
+    statements: &mut PreparedStatements<'_>,
+    id: SqPackId,
+    discrepancy: IndexDiscrepancy,
+    mut result: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), tomestone_sqpack::Error> {
+    if output.is_text() {
+        return write_discrepancy(writer, statements, id, discrepancy);
+    }
+    result.insert("pack".to_string(), pack_name(id).into());
+    result.insert(
+        "discrepancy".to_string(),
+        discrepancy_kind(&discrepancy).into(),
+    );
+    let mut message = Vec::new();
+    write_discrepancy_message(&mut message, statements, discrepancy)?;
+    result.insert(
+        "message".to_string(),
+        String::from_utf8_lossy(&message).into_owned().into(),
+    );
+    output.record(result);
+    Ok(())
+}
+
+fn write_discrepancy_message<W: Write>(
+    writer: &mut W,
+    statements: &mut PreparedStatements<'_>,
+    discrepancy: IndexDiscrepancy,
+) -> Result<(), tomestone_sqpack::Error> {
+    match discrepancy {
+        IndexDiscrepancy::DatFileCount { index_1, index_2 } => write!(
+            writer,
+            ".index has {} data files, .index2 has {}",
+            index_1, index_2
+        )
+        .unwrap(),
+        IndexDiscrepancy::MissingFromIndex2 { hash, pointer } => {
+            write_file_name_1(writer, statements, hash, pointer, None)?;
+            write!(
+                writer,
+                " at dat{}:{:08x} is missing from .index2",
+                pointer.data_file_id(),
+                pointer.offset()
+            )
+            .unwrap();
+        }
+        IndexDiscrepancy::MissingFromIndex1 { hash, pointer } => {
+            write_file_name_2(writer, statements, hash)?;
+            write!(
+                writer,
+                " at dat{}:{:08x} is missing from .index",
+                pointer.data_file_id(),
+                pointer.offset()
+            )
+            .unwrap();
+        }
+        IndexDiscrepancy::SharedPointer {
+            pointer,
+            hashes_1,
+            hashes_2,
+        } => write!(
+            writer,
+            "dat{}:{:08x} is used by {} entries in .index and {} in .index2",
+            pointer.data_file_id(),
+            pointer.offset(),
+            hashes_1.len(),
+            hashes_2.len()
+        )
+        .unwrap(),
+    }
+    Ok(())
+}
+
+/// Compare the `.index` and `.index2` files of every pack, and print any entries that only appear
+/// in one of them. With [`ErrorMode::KeepGoing`], packs whose indexes can't be read are skipped,
+/// otherwise this stops at the first such pack, or the first pack with discrepancies.
+fn check_indexes(
+    game_data: &GameData,
+    statements: &mut PreparedStatements<'_>,
+    output: &mut Output,
+    mode: ErrorMode,
+) -> Result<Outcome, tomestone_sqpack::Error> {
+    let stdout = stdout();
+    let mut locked = stdout.lock();
+    let mut outcome = Outcome::default();
+    for id in game_data.iter_packs() {
+        let discrepancies = match game_data.check_index_consistency(&id) {
+            Some(Ok(discrepancies)) => discrepancies,
+            Some(Err(e)) if mode == ErrorMode::KeepGoing => {
+                eprintln!("warning: couldn't read indices of {}, {}", pack_name(id), e);
+                outcome.skipped += 1;
+                continue;
+            }
+            Some(Err(e)) => return Err(e),
+            None => continue,
+        };
+        for discrepancy in discrepancies {
+            outcome.problems = true;
+            report_discrepancy(
+                &mut locked,
+                output,
+                statements,
+                id,
+                discrepancy,
+                serde_json::Map::new(),
+            )?;
+        }
+        if outcome.problems && mode == ErrorMode::FailFast {
+            break;
+        }
+    }
+    Ok(outcome)
+}
+
+/// Number of files read by each task when checking file data. Each pack is split into tasks of
+/// this size, so that idle threads can take work from large packs.
+const VERIFY_TASK_FILES: usize = 512;
+
+/// Problems found in one pack by [`check_pack`].
+struct PackReport {
+    id: SqPackId,
+    discrepancies: Vec<IndexDiscrepancy>,
+    /// Files that could not be read, with the error message.
+    unreadable: Vec<(IndexHash2, FilePointer, String)>,
+}
+
+/// Compare the indexes of one pack, and read every file in it. Reads are spread across the
+/// current thread pool. Each task reads one file at a time, and only keeps problems, so memory use
+/// is bounded by the number of threads.
+fn check_pack(game_data: &GameData, id: SqPackId) -> Result<PackReport, tomestone_sqpack::Error> {
+    let discrepancies = game_data
+        .check_index_consistency(&id)
+        .transpose()?
+        .unwrap_or_default();
+    let mut unreadable = Vec::new();
+    if let Some(index_2) = game_data.get_index_2(&id).transpose()? {
+        let mut entries = index_2.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(_, pointer)| *pointer);
+        unreadable = entries
+            .par_chunks(VERIFY_TASK_FILES)
+            .map_init(
+                || {
+                    let mut data_file_set = game_data.data_files();
+                    data_file_set.set_read_ahead(ReadAhead::Sequential);
+                    data_file_set
+                },
+                |data_file_set, chunk| {
+                    chunk
+                        .iter()
+                        .filter_map(|(hash, pointer)| {
+                            let e = data_file_set.fetch_data_reserved(id, *pointer).err()?;
+                            Some((*hash, *pointer, e.to_string()))
+                        })
+                        .collect::<Vec<_>>()
+                },
+            )
+            .flatten()
+            .collect();
+    }
+    Ok(PackReport {
+        id,
+        discrepancies,
+        unreadable,
+    })
+}
+
+/// Check an entire installation: its version files, its boot executables, if their hashes are
+/// known for the installed boot version, the agreement of each pack's indexes, and the data of
+/// every file. Packs are checked on the given thread pool, and problems are printed in pack order
+/// once all checks are done.
+///
+/// With [`ErrorMode::FailFast`], packs are checked one at a time, and checking stops after the
+/// first step or pack with problems. With [`ErrorMode::KeepGoing`], packs whose indexes can't be
+/// read are skipped.
+fn verify(
+    root: &Path,
+    game_data: &GameData,
+    statements: &mut PreparedStatements<'_>,
+    database: Option<&VersionDatabase>,
+    thread_pool: &ThreadPool,
+    output: &mut Output,
+    mode: ErrorMode,
+) -> Result<Outcome, tomestone_sqpack::Error> {
+    let mut outcome = Outcome::default();
+    let versions = InstallVersions::read(root)?;
+    let mut repositories = vec![
+        ("boot".to_string(), versions.boot.as_ref()),
+        ("ffxiv".to_string(), versions.game.as_ref()),
+    ];
+    repositories.extend(
+        versions
+            .expansions
+            .iter()
+            .map(|(number, version)| (format!("ex{}", number), Some(version))),
+    );
+    for (name, version) in repositories {
+        match version {
+            Some(version) if output.is_text() => println!("{} version {}", name, version),
+            Some(version) => output.record(json!({
+                "kind": "version",
+                "repository": name,
+                "version": version,
+            })),
+            None => {
+                outcome.problems = true;
+                if output.is_text() {
+                    println!("{} version file is missing", name);
+                } else {
+                    output.record(json!({"kind": "missing_version_file", "repository": name}));
+                }
+            }
+        }
+    }
+
+    let expected = versions
+        .boot
+        .as_deref()
+        .and_then(|version| database?.boot_files(version));
+    match expected {
+        Some(expected) => {
+            for problem in check_boot_files(root, expected)? {
+                outcome.problems = true;
+                match problem {
+                    BootProblem::Missing(name) if output.is_text() => {
+                        println!("boot/{} is missing", name)
+                    }
+                    BootProblem::Missing(name) => {
+                        output.record(json!({"kind": "boot_file_missing", "file": name}))
+                    }
+                    BootProblem::Mismatch { expected, actual } if output.is_text() => println!(
+                        "boot/{} has size {} and hash {}, expected size {} and hash {}",
+                        actual.name, actual.size, actual.sha1, expected.size, expected.sha1
+                    ),
+                    BootProblem::Mismatch { expected, actual } => output.record(json!({
+                        "kind": "boot_file_mismatch",
+                        "file": actual.name,
+                        "expected": expected,
+                        "actual": actual,
+                    })),
+                }
+            }
+        }
+        None => eprintln!("warning: boot executable hashes are not known, skipping them"),
+    }
+
+    if outcome.problems && mode == ErrorMode::FailFast {
+        return Ok(outcome);
+    }
+
+    let packs = game_data.iter_packs().collect::<Vec<_>>();
+    let reports = match mode {
+        ErrorMode::FailFast => Vec::new(),
+        ErrorMode::KeepGoing => thread_pool.install(|| {
+            packs
+                .par_iter()
+                .map(|id| check_pack(game_data, *id))
+                .collect::<Vec<_>>()
+        }),
+    };
+    let mut reports = reports.into_iter();
+    let stdout = stdout();
+    let mut locked = stdout.lock();
+    for id in packs {
+        let report = match mode {
+            ErrorMode::FailFast => thread_pool.install(|| check_pack(game_data, id))?,
+            ErrorMode::KeepGoing => match reports.next().unwrap() {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("warning: couldn't check {}, {}", pack_name(id), e);
+                    outcome.skipped += 1;
+                    continue;
+                }
+            },
+        };
+        for discrepancy in report.discrepancies {
+            outcome.problems = true;
+            let mut result = serde_json::Map::new();
+            result.insert("kind".to_string(), "index_discrepancy".into());
+            report_discrepancy(
+                &mut locked,
+                output,
+                statements,
+                report.id,
+                discrepancy,
+                result,
+            )?;
+        }
+        for (hash, pointer, e) in report.unreadable {
+            outcome.problems = true;
+            let path = file_name_2(statements, hash)?;
+            if output.is_text() {
+                writeln!(
+                    locked,
+                    "{}: {} at dat{}:{:08x} could not be read, {}",
+                    pack_name(report.id),
+                    path,
+                    pointer.data_file_id(),
+                    pointer.offset(),
+                    e
+                )
+                .unwrap();
+            } else {
+                output.record(json!({
+                    "kind": "unreadable_file",
+                    "pack": pack_name(report.id),
+                    "path": path,
+                    "dat": pointer.data_file_id(),
+                    "offset": pointer.offset(),
+                    "error": e,
+                }));
+            }
+        }
+        if outcome.problems && mode == ErrorMode::FailFast {
+            break;
+        }
+    }
+    Ok(outcome)
+}
+
 /// Print the given bytes as a hex dump, with four groups of four bytes each on the left, and the
 /// ASCII representation (of any printable ASCII bytes) on the right.
 fn write_hex_dump<W: Write>(data: &[u8], mut writer: W) -> io::Result<()> {
@@ -373,7 +808,7 @@ fn parse_repository_path(path: Option<&str>) -> Option<(Category, Expansion)> {
                     Some((category, Expansion::Base))
                 } else {
                     eprintln!("error: invalid category {:?}", segments[0]);
-                    process::exit(1);
+                    process::exit(exit::USAGE);
                 }
             }
             2 => {
@@ -382,16 +817,16 @@ fn parse_repository_path(path: Option<&str>) -> Option<(Category, Expansion)> {
                         Some((category, expansion))
                     } else {
                         eprintln!("error: invalid expansion {:?}", segments[1]);
-                        process::exit(1);
+                        process::exit(exit::USAGE);
                     }
                 } else {
                     eprintln!("error: invalid category {:?}", segments[0]);
-                    process::exit(1);
+                    process::exit(exit::USAGE);
                 }
             }
             _ => {
                 eprintln!("error: only up to two path segments are supported");
-                process::exit(1);
+                process::exit(exit::USAGE);
             }
         }
     } else {
@@ -399,8 +834,79 @@ fn parse_repository_path(path: Option<&str>) -> Option<(Category, Expansion)> {
     }
 }
 
+/// Returns whether a command was asked to write a provenance record, either with a flag or with
+/// the path of the record.
+fn wants_provenance(matches: &ArgMatches) -> bool {
+    matches!(matches.try_get_one::<bool>("provenance"), Ok(Some(true)))
+        || matches!(matches.try_get_one::<PathBuf>("provenance"), Ok(Some(_)))
+}
+
+/// Starts a provenance record, if one was requested with `--provenance`.
+fn start_provenance(matches: &ArgMatches, game_data: &GameData) -> Option<Provenance> {
+    if !wants_provenance(matches) {
+        return None;
+    }
+    match game_data.install_versions() {
+        Ok(versions) => Some(Provenance::new(&versions)),
+        Err(e) => {
+            eprintln!("error: couldn't read version files, {}", e);
+            process::exit(exit::FAILURE);
+        }
+    }
+}
+
+/// Adds the files that were read from the game's data to a provenance record, and writes it.
+fn finish_provenance(
+    mut provenance: Provenance,
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    path: &Path,
+) {
+    if let Err(e) = provenance.add_accessed_files(game_data, data_file_set) {
+        eprintln!("error: couldn't hash the files that were read, {}", e);
+        process::exit(exit::FAILURE);
+    }
+    write_provenance(&provenance, path);
+}
+
+fn write_provenance(provenance: &Provenance, path: &Path) {
+    if let Err(e) = provenance.write(path) {
+        eprintln!("error: couldn't write {:?}, {}", path, e);
+        process::exit(exit::FAILURE);
+    }
+}
+
+/// Reads a file for a batch command, along with its reservation from the memory budget, if one
+/// is set. With [`ErrorMode::KeepGoing`], a file that can't be read is reported as a warning,
+/// counted as skipped, and `None` is returned.
+#[allow(clippy::type_complexity)]
+fn fetch_or_skip(
+    data_file_set: &mut DataFileSet,
+    pack_id: SqPackId,
+    pointer: FilePointer,
+    mode: ErrorMode,
+    outcome: &mut Outcome,
+) -> Result<Option<(Vec<u8>, Option<Reservation>)>, tomestone_sqpack::Error> {
+    match data_file_set.fetch_data_reserved(pack_id, pointer) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if mode == ErrorMode::KeepGoing => {
+            eprintln!(
+                "warning: couldn't read {} dat{}:{:08x}, {}",
+                pack_name(pack_id),
+                pointer.data_file_id(),
+                pointer.offset(),
+                e
+            );
+            outcome.skipped += 1;
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 /// This is a helper function to search all files in one set of sqpack files for a pattern, and
 /// print any matching filenames to standard output.
+#[allow(clippy::too_many_arguments)]
 fn do_grep(
     game_data: &GameData,
     data_file_set: &mut DataFileSet,
@@ -408,6 +914,9 @@ fn do_grep(
     category: Category,
     expansion: Expansion,
     re: &BytesRegex,
+    output: &mut Output,
+    mode: ErrorMode,
+    outcome: &mut Outcome,
 ) -> Result<(), tomestone_sqpack::Error> {
     let stdout = stdout();
     let mut locked = stdout.lock();
@@ -419,32 +928,41 @@ fn do_grep(
             (None, None) => unreachable!(),
             (None, Some(index_2_res)) => {
                 for (hash, pointer) in index_2_res?.iter() {
-                    let file = data_file_set.fetch_data(pack_id, pointer)?;
+                    let (file, _reservation) =
+                        match fetch_or_skip(data_file_set, pack_id, pointer, mode, outcome)? {
+                            Some(fetched) => fetched,
+                            None => continue,
+                        };
                     if re.is_match(&file) {
-                        locked.write_all(b"File ").unwrap();
-                        write_file_name_2(&mut locked, statements, hash)?;
-                        locked.write_all(b" matches\n").unwrap();
+                        let path = file_name_2(statements, hash)?;
+                        report_path(&mut locked, output, pack_id, path, "File ", " matches");
                     }
                 }
             }
             (Some(index_1_res), None) => {
                 for (hash, pointer) in index_1_res?.iter() {
-                    let file = data_file_set.fetch_data(pack_id, pointer)?;
+                    let (file, _reservation) =
+                        match fetch_or_skip(data_file_set, pack_id, pointer, mode, outcome)? {
+                            Some(fetched) => fetched,
+                            None => continue,
+                        };
                     if re.is_match(&file) {
-                        locked.write_all(b"File ").unwrap();
-                        write_file_name_1(&mut locked, statements, hash, pointer, None)?;
-                        locked.write_all(b" matches\n").unwrap();
+                        let path = file_name_1(statements, hash, pointer, None)?;
+                        report_path(&mut locked, output, pack_id, path, "File ", " matches");
                     }
                 }
             }
             (Some(index_1_res), Some(index_2_res)) => {
                 let index_2 = index_2_res?;
                 for (hash, pointer) in index_1_res?.iter() {
-                    let file = data_file_set.fetch_data(pack_id, pointer)?;
+                    let (file, _reservation) =
+                        match fetch_or_skip(data_file_set, pack_id, pointer, mode, outcome)? {
+                            Some(fetched) => fetched,
+                            None => continue,
+                        };
                     if re.is_match(&file) {
-                        locked.write_all(b"File ").unwrap();
-                        write_file_name_1(&mut locked, statements, hash, pointer, Some(index_2))?;
-                        locked.write_all(b" matches\n").unwrap();
+                        let path = file_name_1(statements, hash, pointer, Some(index_2))?;
+                        report_path(&mut locked, output, pack_id, path, "File ", " matches");
                     }
                 }
             }
@@ -583,17 +1101,183 @@ fn discover_paths(
     Ok(())
 }
 
+/// Returns each combination of category and expansion that has at least one pack, including
+/// categories unknown to this tool.
+fn category_expansion_pairs(game_data: &GameData) -> BTreeSet<(Category, Expansion)> {
+    game_data
+        .iter_packs()
+        .map(|id| (id.category, id.expansion))
+        .collect()
+}
+
+/// Report that a file wasn't found. If the file was given as a full path, suggest similar paths
+/// from the same folder that are known to the CRC database.
+fn report_file_not_found<'a>(
+    statements: &mut PreparedStatements<'_>,
+    mut path_or_crc: impl Iterator<Item = &'a String>,
+) {
+    let suggestion = match (path_or_crc.next(), path_or_crc.next()) {
+        (Some(path), None) => path.rsplit_once('/').and_then(|(folder, _)| {
+            let normalized = normalize_path(path);
+            let known_paths = statements.paths_in_folder(folder).ok()?;
+            let candidates = known_paths
+                .iter()
+                .map(String::as_str)
+                .filter(|known_path| *known_path != normalized);
+            fuzzy::best_match(path, candidates).map(str::to_string)
+        }),
+        _ => None,
+    };
+    match suggestion {
+        Some(suggestion) => eprintln!("error: file not found, did you mean {}?", suggestion),
+        None => eprintln!("error: file not found"),
+    }
+}
+
 /// Convenience method to open the path CRC database.
 fn open_db() -> PathDb {
     match PathDb::open() {
         Ok(db) => db,
         Err(e) => {
             eprintln!("error: couldn't open path hash database, {}", e);
-            process::exit(1);
+            process::exit(exit::FAILURE);
+        }
+    }
+}
+
+/// Gathers tag statistics over every string of every sheet in the given language.
+fn tag_stats(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    language: Language,
+) -> Result<TagStatistics, tomestone_exdf::Error> {
+    let mut statistics = TagStatistics::new();
+    let root_list = RootList::open(game_data, data_file_set)?;
+    for name in root_list.iter() {
+        let dataset = match Dataset::load(game_data, data_file_set, name, language) {
+            Ok(dataset) => dataset,
+            Err(tomestone_exdf::Error::LanguageUnavailable) => continue,
+            Err(e) => return Err(e),
+        };
+        for page in dataset.page_iter() {
+            for res in page {
+                for sub_row in res?.sub_rows.iter() {
+                    for value in sub_row.cells.iter() {
+                        if let Value::String(data) = value {
+                            statistics.add_encoded(data);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(statistics)
+}
+
+/// Converts a cell to JSON. Strings are parsed, and serialized as structured text, or as a plain
+/// string if they can't be parsed.
+fn cell_json(value: &Value<'_>) -> serde_json::Value {
+    match value {
+        Value::String(data) => match Text::parse(data) {
+            Ok(text) => serde_json::to_value(text).expect("text is valid JSON"),
+            Err(_) => String::from_utf8_lossy(data).into(),
+        },
+        Value::StringOwned(data) => match Text::parse(data) {
+            Ok(text) => serde_json::to_value(text).expect("text is valid JSON"),
+            Err(_) => String::from_utf8_lossy(data).into(),
+        },
+        Value::Bool(value) | Value::Bitflag(value) => (*value).into(),
+        Value::I8(value) => (*value).into(),
+        Value::U8(value) => (*value).into(),
+        Value::I16(value) => (*value).into(),
+        Value::U16(value) => (*value).into(),
+        Value::I32(value) => (*value).into(),
+        Value::U32(value) => (*value).into(),
+        Value::I64(value) => (*value).into(),
+        Value::Float(value) => (*value).into(),
+        Value::I16x4(values) => json!(values),
+    }
+}
+
+/// An icon referenced by a row of a sheet.
+struct IconReference {
+    row: u32,
+    icon: u32,
+    /// Plain text from the row's name column, if one was given.
+    name: String,
+}
+
+/// Reads the icon column, and optionally a name column, of each row of a sheet. Rows without an
+/// icon are skipped.
+fn sheet_icons(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    sheet: &str,
+    icon_column: usize,
+    name_column: Option<usize>,
+    language: Language,
+) -> Result<Vec<IconReference>, tomestone_exdf::Error> {
+    let dataset = Dataset::load(game_data, data_file_set, sheet, language)?;
+    let mut icons = Vec::new();
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            let cells = match row.sub_rows.first() {
+                Some(sub_row) => &sub_row.cells,
+                None => continue,
+            };
+            let icon = match cells.get(icon_column) {
+                Some(Value::U16(icon)) => u32::from(*icon),
+                Some(Value::U32(icon)) => *icon,
+                Some(Value::I32(icon)) => u32::try_from(*icon).unwrap_or(0),
+                _ => return Err(tomestone_exdf::Error::ColumnMismatch(icon_column)),
+            };
+            if icon == 0 {
+                continue;
+            }
+            let name = match name_column.map(|column| cells.get(column)) {
+                None => String::new(),
+                Some(Some(Value::String(data))) => plain_text(data),
+                Some(_) => return Err(tomestone_exdf::Error::ColumnMismatch(name_column.unwrap())),
+            };
+            icons.push(IconReference {
+                row: row.number,
+                icon,
+                name,
+            });
         }
     }
+    Ok(icons)
+}
+
+/// File name for an exported icon, from the row number and name, e.g. `1601 Wind-up Aldgoat.png`.
+fn icon_file_name(reference: &IconReference) -> String {
+    let name = paths::sanitize_file_name(&reference.name);
+    if name.is_empty() {
+        format!("{}.png", reference.row)
+    } else {
+        format!("{} {}.png", reference.row, name)
+    }
+}
+
+/// Converts a file to a widely supported format, for `raw --convert`, using the converter of its
+/// format handler. Files in formats without a converter are returned unchanged.
+fn convert_file(path: &str, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    match inspect::find_handler_for_path(path, &data).and_then(|handler| handler.convert(&data)) {
+        Some(converted) => converted,
+        None => Ok(data),
+    }
 }
 
+fn write_png_file(path: &Path, image: &RgbaImage) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    image.write_png(&mut writer)?;
+    writer.flush()
+}
+
+/// Files given to `inspect` are only read up to this size, since data files can be gigabytes long.
+const INSPECT_READ_LIMIT: u64 = 0x10000;
+
 fn app() -> Command {
     Command::new(crate_name!())
         .version(crate_version!())
@@ -602,10 +1286,50 @@ fn app() -> Command {
         .arg(
             Arg::new("ffxiv-install-dir")
                 .long("ffxiv-install-dir")
-                .required(true)
+                .required(false)
                 .value_parser(ValueParser::path_buf())
                 .env("FFXIV_INSTALL_DIR"),
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format, for scripting")
+                .global(true)
+                .default_value("text")
+                .value_parser(EnumValueParser::<Format>::new()),
+        )
+        .arg(
+            Arg::new("fail-fast")
+                .long("fail-fast")
+                .help("Stop batch commands at the first error")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .overrides_with("keep-going"),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .long("keep-going")
+                .help("Skip items with errors in batch commands, and exit with status 5")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .overrides_with("fail-fast"),
+        )
+        .arg(
+            Arg::new("memory-budget")
+                .long("memory-budget")
+                .value_name("MIB")
+                .help("Limit the memory used by files being decompressed, in MiB")
+                .global(true)
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("access-log")
+                .long("access-log")
+                .value_name("FILE")
+                .help("Write every file lookup, with its time and outcome, to a JSON file")
+                .global(true)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
         .subcommand(
             Command::new("raw")
                 .about("Extract a file and write it to standard output")
@@ -614,6 +1338,12 @@ fn app() -> Command {
                         .required(true)
                         .index(1)
                         .num_args(1..=2),
+                )
+                .arg(
+                    Arg::new("convert")
+                        .long("convert")
+                        .help("Convert textures to PNG, if a path is given")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(
@@ -626,11 +1356,45 @@ fn app() -> Command {
                         .num_args(1..=2),
                 ),
         )
+        .subcommand(
+            Command::new("inspect")
+                .about("Print the headers of a file, next to an annotated hex dump")
+                .long_about(
+                    "Print the headers of a file, next to an annotated hex dump. The file may be \
+                    an index, data, or EXH file on disk, or a path in the game's files, in which \
+                    case the headers of its data entry are printed.",
+                )
+                .arg(Arg::new("file_or_path").required(true).index(1)),
+        )
         .subcommand(
             Command::new("list")
                 .about("List files by hash or path (where available)")
                 .arg(Arg::new("path").required(false).index(1)),
         )
+        .subcommand(
+            Command::new("extract")
+                .about(
+                    "Extract the files of every pack, or of the packs in one category, into a \
+                    directory, named after their hashes",
+                )
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .index(1)
+                        .value_parser(ValueParser::path_buf()),
+                )
+                .arg(Arg::new("path").required(false).index(2))
+                .arg(
+                    Arg::new("patterns")
+                        .long("patterns")
+                        .value_name("FILE")
+                        .help(
+                            "Only extract the files selected by a pattern set file, such as \
+                            .tomestone-patterns",
+                        )
+                        .value_parser(ValueParser::path_buf()),
+                ),
+        )
         .subcommand(
             Command::new("grep")
                 .about("Search file contents for regular expressions")
@@ -648,6 +1412,79 @@ fn app() -> Command {
             Command::new("discover_paths")
                 .about("Search all files for paths of other files, and update the path database"),
         )
+        .subcommand(
+            Command::new("check_indexes")
+                .about("Check that the .index and .index2 files of each pack agree"),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check version files, boot executables, indexes, and file data")
+                .arg(
+                    Arg::new("versions")
+                        .long("versions")
+                        .help("Version database with expected boot executable hashes")
+                        .required(false)
+                        .value_parser(ValueParser::path_buf()),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .help("Number of threads to use, defaults to the number of CPUs")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(Command::new("compatibility").about(
+            "Check header versions, entry types, and packs for features that aren't supported",
+        ))
+        .subcommand(
+            Command::new("stats")
+                .about("Summarize entry counts and sizes by category and expansion")
+                .arg(
+                    Arg::new("csv")
+                        .long("csv")
+                        .help("Print the summary as CSV")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("tag_stats")
+                .about("Count tags and expressions used in the text of every sheet")
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .required(false)
+                        .value_parser(EnumValueParser::<Language>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("localization")
+                .about("Report strings with missing, empty, or untranslated translations")
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("Language the translations are compared against")
+                        .default_value("ja")
+                        .value_parser(EnumValueParser::<Language>::new()),
+                )
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .help("Languages to check, may be given more than once")
+                        .action(ArgAction::Append)
+                        .default_values(["en", "de", "fr"])
+                        .value_parser(EnumValueParser::<Language>::new()),
+                )
+                .arg(
+                    Arg::new("csv")
+                        .long("csv")
+                        .help("Print the report as CSV")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
         .subcommand(
             Command::new("exd")
                 .about("Extract and dump EXHF/EXDF files")
@@ -656,55 +1493,375 @@ fn app() -> Command {
                     Arg::new("language")
                         .long("language")
                         .short('l')
+                        .help("Language to read, repeated to fall back to other languages in order")
                         .required(false)
+                        .action(ArgAction::Append)
                         .value_parser(EnumValueParser::<Language>::new()),
+                )
+                .arg(
+                    Arg::new("csv")
+                        .long("csv")
+                        .help("Print every row as CSV, with text as plain text")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("provenance")
+                        .long("provenance")
+                        .help(
+                            "Also write a provenance record to this file, with the game version \
+                            and a hash of each file that was read",
+                        )
+                        .value_name("FILE")
+                        .value_parser(ValueParser::path_buf()),
                 ),
         )
-}
-
-fn main() {
-    dotenvy::dotenv().ok();
-
-    let app_matches = app().get_matches();
-
-    let db = open_db();
-    let connection = db.get_connection().unwrap();
-    let mut statements = PathDb::prepare(&connection).unwrap();
-
-    let root = app_matches.get_one::<PathBuf>("ffxiv-install-dir").unwrap();
-    let game_data = match GameData::new(root) {
-        Ok(game_data) => game_data,
-        Err(e) => {
-            eprintln!(
-            "error: couldn't read the directory {:?} (from environment variable FFXIV_INSTALL_DIR), {}",
-            root, e
-        );
-            process::exit(1);
-        }
-    };
-    let mut data_file_set = game_data.data_files();
-
+        .subcommand(
+            Command::new("bundle")
+                .about("Join related sheets into one JSON file, following a bundle definition")
+                .arg(
+                    Arg::new("bundle")
+                        .required(true)
+                        .index(1)
+                        .help("A built-in bundle (achievement, emote, or title), or a TOML file"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .index(2)
+                        .value_parser(ValueParser::path_buf()),
+                )
+                .arg(
+                    Arg::new("provenance")
+                        .long("provenance")
+                        .help(
+                            "Also write a provenance record next to the output, named like \
+                            achievements.provenance.json, with the game version and a hash of \
+                            each file that was read",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .required(false)
+                        .value_parser(EnumValueParser::<Language>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Archive the files and sheets of the installed version, resuming if interrupted")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .required(true)
+                        .value_parser(ValueParser::path_buf()),
+                )
+                .arg(
+                    Arg::new("provenance")
+                        .long("provenance")
+                        .help(
+                            "Also write provenance.json in the version's directory, with the game \
+                            version and the hash of each archived index, data, and version file",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .required(false)
+                        .value_parser(EnumValueParser::<Language>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("collision")
+                .about("Convert a collision mesh (.pcb) to Wavefront OBJ on standard output")
+                .arg(Arg::new("path").required(true).index(1)),
+        )
+        .subcommand(
+            Command::new("icons")
+                .about("Export the icons referenced by a sheet column as PNG files")
+                .arg(Arg::new("sheet").required(true).index(1))
+                .arg(
+                    Arg::new("icon-column")
+                        .required(true)
+                        .index(2)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .index(3)
+                        .value_parser(ValueParser::path_buf()),
+                )
+                .arg(
+                    Arg::new("name-column")
+                        .long("name-column")
+                        .help("Column of row names, used in file names")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("contact-sheet")
+                        .long("contact-sheet")
+                        .help(
+                            "Also combine all icons into contact-sheet.png, with this many columns",
+                        )
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("high-resolution")
+                        .long("high-resolution")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("provenance")
+                        .long("provenance")
+                        .help(
+                            "Also write provenance.json, with the game version and a hash of \
+                            each file that was read",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .required(false)
+                        .value_parser(EnumValueParser::<Language>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("sound_names")
+                .about("List sound files referenced by sheets, with names taken from the sheets")
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .required(false)
+                        .value_parser(EnumValueParser::<Language>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("housing_models")
+                .about("Export the model of every piece of housing furniture, named after its item")
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .index(1)
+                        .value_parser(ValueParser::path_buf()),
+                )
+                .arg(
+                    Arg::new("provenance")
+                        .long("provenance")
+                        .help(
+                            "Also write provenance.json, with the game version and a hash of \
+                            each file that was read",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .required(false)
+                        .value_parser(EnumValueParser::<Language>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .index(1)
+                        .value_parser(EnumValueParser::<Shell>::new()),
+                ),
+        )
+        .subcommand(
+            Command::new("man")
+                .about("Print a manual page, or write one for every command to a directory")
+                .arg(
+                    Arg::new("output-dir")
+                        .long("output-dir")
+                        .required(false)
+                        .value_parser(ValueParser::path_buf()),
+                ),
+        )
+}
+
+/// Returns the names of sheets known to the path database. These are found by `discover_paths`,
+/// from the list of sheets in `exd/root.exl`.
+fn known_sheet_names(statements: &mut PreparedStatements<'_>) -> Vec<String> {
+    statements
+        .paths_in_folder("exd")
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|path| Some(path.strip_prefix("exd/")?.strip_suffix(".exh")?.to_string()))
+        .collect()
+}
+
+/// Returns the command line interface, with the given sheet names offered as completions of
+/// arguments that take a sheet name. The names are not checked when parsing arguments.
+fn completion_app(sheet_names: Vec<String>) -> Command {
+    if sheet_names.is_empty() {
+        return app();
+    }
+    let sheets = PossibleValuesParser::new(sheet_names);
+    app()
+        .mut_subcommand("exd", |command| {
+            command.mut_arg("path", |arg| arg.value_parser(sheets.clone()))
+        })
+        .mut_subcommand("icons", |command| {
+            command.mut_arg("sheet", |arg| arg.value_parser(sheets))
+        })
+}
+
+fn main() {
+    dotenvy::dotenv().ok();
+
+    let app_matches = app().get_matches();
+
+    let db = open_db();
+    let connection = db.get_connection().unwrap();
+    let mut statements = PathDb::prepare(&connection).unwrap();
+
+    // These commands don't need the game's files.
+    match app_matches.subcommand() {
+        Some(("completions", matches)) => {
+            let shell = *matches.get_one::<Shell>("shell").unwrap();
+            let mut command = completion_app(known_sheet_names(&mut statements));
+            clap_complete::generate(shell, &mut command, crate_name!(), &mut stdout());
+            return;
+        }
+        Some(("man", matches)) => {
+            let res = match matches.get_one::<PathBuf>("output-dir") {
+                Some(output_dir) => fs::create_dir_all(output_dir)
+                    .and_then(|()| clap_mangen::generate_to(app(), output_dir)),
+                None => clap_mangen::Man::new(app()).render(&mut stdout()),
+            };
+            if let Err(e) = res {
+                eprintln!("error: couldn't write manual pages, {}", e);
+                process::exit(exit::FAILURE);
+            }
+            return;
+        }
+        Some(("inspect", matches))
+            if Path::new(matches.get_one::<String>("file_or_path").unwrap()).is_file() =>
+        {
+            let format = *app_matches.get_one::<Format>("format").unwrap();
+            let mut output = Output::new(format, "inspect");
+            let path = matches.get_one::<String>("file_or_path").unwrap();
+            let mut data = Vec::new();
+            if let Err(e) = File::open(path)
+                .and_then(|file| file.take(INSPECT_READ_LIMIT).read_to_end(&mut data))
+            {
+                eprintln!("error: couldn't read {}, {}", path, e);
+                process::exit(exit::FAILURE);
+            }
+            match inspect::inspect_file(path, &data) {
+                Some(sections) => inspect::print_sections(&sections, &mut output),
+                None => {
+                    eprintln!("error: {} is not in a recognized format", path);
+                    process::exit(exit::FAILURE);
+                }
+            }
+            output.finish();
+            return;
+        }
+        _ => {}
+    }
+
+    let root = match app_matches.get_one::<PathBuf>("ffxiv-install-dir") {
+        Some(root) => root,
+        None => {
+            eprintln!(
+                "error: the installation directory must be given with --ffxiv-install-dir, or the \
+                environment variable FFXIV_INSTALL_DIR"
+            );
+            process::exit(exit::USAGE);
+        }
+    };
+    let mut builder = GameData::builder();
+    if let Some(mebibytes) = app_matches.get_one::<u64>("memory-budget") {
+        builder = builder.memory_budget(mebibytes.saturating_mul(1024 * 1024));
+    }
+    let access_log_path = app_matches.get_one::<PathBuf>("access-log");
+    // Provenance records list the files that were read, taken from the access log. Archives
+    // list the files in their manifest instead, and would otherwise log every file.
+    let record_provenance = match app_matches.subcommand() {
+        Some(("archive", _)) | None => false,
+        Some((_, matches)) => wants_provenance(matches),
+    };
+    builder = builder.access_log(access_log_path.is_some() || record_provenance);
+    let game_data = match builder.open(root) {
+        Ok(game_data) => game_data,
+        Err(e) => {
+            eprintln!(
+            "error: couldn't read the directory {:?} (from environment variable FFXIV_INSTALL_DIR), {}",
+            root, e
+        );
+            process::exit(exit::FAILURE);
+        }
+    };
+    for path in game_data.skipped_paths() {
+        eprintln!("warning: skipped {:?}, its name isn't valid UTF-8", path);
+    }
+    let mut data_file_set = game_data.data_files();
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    data_file_set.set_read_backend(tomestone_sqpack::ReadBackend::IoUring { queue_depth: 64 });
+
+    let format = *app_matches.get_one::<Format>("format").unwrap();
+    let mut output = match app_matches.subcommand_name() {
+        Some(command) => Output::new(format, command),
+        None => {
+            eprintln!("{}", app().render_usage());
+            process::exit(exit::USAGE);
+        }
+    };
+
+    let mut exit_code = 0;
     match app_matches.subcommand() {
         Some(("raw", matches)) => {
-            match lookup(
+            let mut path_or_crc = matches.get_many::<String>("path_or_crc").unwrap();
+            let res = lookup(
                 &game_data,
                 &mut data_file_set,
                 Some(&mut statements),
-                matches
-                    .get_many::<String>("path_or_crc")
-                    .unwrap()
-                    .map(AsRef::as_ref),
-            ) {
-                Ok(Some(data)) => {
+                path_or_crc.clone().map(AsRef::as_ref),
+            );
+            let res = match res {
+                Ok(Some(data)) if matches.get_flag("convert") && path_or_crc.len() == 1 => {
+                    let path = path_or_crc.next().unwrap();
+                    match convert_file(path, data) {
+                        Ok(data) => Ok(Some(data)),
+                        Err(e) => {
+                            eprintln!("error: couldn't convert {}, {}", path, e);
+                            process::exit(exit::FAILURE);
+                        }
+                    }
+                }
+                res => res,
+            };
+            match res {
+                Ok(Some(data)) if output.is_text() => {
                     stdout().write_all(&data).unwrap();
                 }
+                Ok(Some(data)) => output.record(json!({
+                    "size": data.len(),
+                    "data": hex::encode(&data),
+                })),
                 Ok(None) => {
-                    eprintln!("error: file not found");
-                    process::exit(1);
+                    report_file_not_found(
+                        &mut statements,
+                        matches.get_many::<String>("path_or_crc").unwrap(),
+                    );
+                    process::exit(exit::NOT_FOUND);
                 }
                 Err(e) => {
                     eprintln!("error: {}", e);
-                    process::exit(1);
+                    process::exit(exit::FAILURE);
                 }
             }
         }
@@ -718,39 +1875,131 @@ fn main() {
                     .unwrap()
                     .map(AsRef::as_ref),
             ) {
-                Ok(Some(data)) => print_hex_dump(&data),
+                Ok(Some(data)) if output.is_text() => print_hex_dump(&data),
+                Ok(Some(data)) => output.record(json!({
+                    "size": data.len(),
+                    "data": hex::encode(&data),
+                })),
                 Ok(None) => {
-                    eprintln!("error: file not found");
-                    process::exit(1);
+                    report_file_not_found(
+                        &mut statements,
+                        matches.get_many::<String>("path_or_crc").unwrap(),
+                    );
+                    process::exit(exit::NOT_FOUND);
                 }
                 Err(e) => {
                     eprintln!("{}", e);
-                    process::exit(1);
+                    process::exit(exit::FAILURE);
+                }
+            }
+        }
+        Some(("inspect", matches)) => {
+            let path = matches.get_one::<String>("file_or_path").unwrap();
+            match inspect::inspect_game_file(&game_data, &mut data_file_set, path) {
+                Ok(Some(sections)) => inspect::print_sections(&sections, &mut output),
+                Ok(None) => {
+                    eprintln!(
+                        "error: {} is neither a file nor a path in the game's files",
+                        path
+                    );
+                    process::exit(exit::NOT_FOUND);
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    process::exit(exit::FAILURE);
                 }
             }
         }
         Some(("list", matches)) => {
             match parse_repository_path(matches.get_one::<String>("path").map(AsRef::as_ref)) {
                 Some((category, expansion)) => {
-                    if let Err(e) = list_files(&game_data, category, expansion, &mut statements) {
+                    if let Err(e) = list_files(
+                        &game_data,
+                        category,
+                        expansion,
+                        &mut statements,
+                        &mut output,
+                    ) {
                         eprintln!("error: couldn't read indices, {}", e);
-                        process::exit(1);
+                        process::exit(exit::FAILURE);
                     }
                 }
                 None => {
-                    for category in Category::iter_all() {
-                        for expansion in Expansion::iter_all() {
-                            if let Err(e) =
-                                list_files(&game_data, *category, *expansion, &mut statements)
-                            {
-                                eprintln!("error: couldn't read indices, {}", e);
-                                process::exit(1);
-                            }
+                    for (category, expansion) in category_expansion_pairs(&game_data) {
+                        if let Err(e) = list_files(
+                            &game_data,
+                            category,
+                            expansion,
+                            &mut statements,
+                            &mut output,
+                        ) {
+                            eprintln!("error: couldn't read indices, {}", e);
+                            process::exit(exit::FAILURE);
                         }
                     }
                 }
             }
         }
+        Some(("extract", matches)) => {
+            let output_dir =
+                &paths::extended_length_path(matches.get_one::<PathBuf>("output").unwrap());
+            let patterns =
+                matches
+                    .get_one::<PathBuf>("patterns")
+                    .map(|path| match PatternSet::load(path) {
+                        Ok(patterns) => patterns,
+                        Err(e) => {
+                            eprintln!("error: couldn't read {:?}, {}", path, e);
+                            process::exit(exit::FAILURE);
+                        }
+                    });
+            let pairs =
+                match parse_repository_path(matches.get_one::<String>("path").map(AsRef::as_ref)) {
+                    Some(pair) => BTreeSet::from([pair]),
+                    None => category_expansion_pairs(&game_data),
+                };
+            let mut total = 0;
+            for (category, expansion) in pairs {
+                for id in game_data.iter_packs_category_expansion(category, expansion) {
+                    let pack_dir = output_dir.join(pack_name(id));
+                    let mut db_error = None;
+                    let result = match patterns.as_ref() {
+                        Some(patterns) => game_data.extract_pack_matching(
+                            &mut data_file_set,
+                            &id,
+                            &pack_dir,
+                            patterns,
+                            |hash_1, hash_2| match statements.resolve_path(hash_1, hash_2) {
+                                Ok(path) => path,
+                                Err(e) => {
+                                    db_error.get_or_insert(e);
+                                    None
+                                }
+                            },
+                        ),
+                        None => game_data.extract_pack(&mut data_file_set, &id, &pack_dir),
+                    };
+                    if let Some(e) = db_error {
+                        eprintln!("error: couldn't look up paths, {}", e);
+                        process::exit(exit::FAILURE);
+                    }
+                    match result {
+                        Some(Ok(count)) => {
+                            total += count;
+                            output.record(json!({"pack": pack_name(id), "files": count}));
+                        }
+                        Some(Err(e)) => {
+                            eprintln!("error: couldn't extract {}, {}", pack_name(id), e);
+                            process::exit(exit::FAILURE);
+                        }
+                        None => {}
+                    }
+                }
+            }
+            if output.is_text() {
+                println!("extracted {} files", total);
+            }
+        }
         Some(("grep", matches)) => {
             let mut builder = BytesRegexBuilder::new(matches.get_one::<String>("pattern").unwrap());
             if matches.get_one("ignore-case").copied().unwrap_or_default() {
@@ -760,9 +2009,12 @@ fn main() {
                 Ok(re) => re,
                 Err(e) => {
                     eprintln!("error: invalid regular expression, {}", e);
-                    process::exit(1);
+                    process::exit(exit::USAGE);
                 }
             };
+            let mode = ErrorMode::from_matches(matches, ErrorMode::FailFast);
+            let mut outcome = Outcome::default();
+            data_file_set.set_read_ahead(ReadAhead::Sequential);
             match parse_repository_path(matches.get_one::<String>("path").map(AsRef::as_ref)) {
                 Some((category, expansion)) => {
                     if let Err(e) = do_grep(
@@ -772,42 +2024,295 @@ fn main() {
                         category,
                         expansion,
                         &re,
+                        &mut output,
+                        mode,
+                        &mut outcome,
                     ) {
                         eprintln!("error: couldn't read files, {}", e);
-                        process::exit(1);
+                        process::exit(exit::FAILURE);
                     }
                 }
                 None => {
-                    for category in Category::iter_all() {
-                        for expansion in Expansion::iter_all() {
-                            if let Err(e) = do_grep(
-                                &game_data,
-                                &mut data_file_set,
-                                &mut statements,
-                                *category,
-                                *expansion,
-                                &re,
-                            ) {
-                                eprintln!("error: couldn't read files, {}", e);
-                                process::exit(1);
-                            }
+                    for (category, expansion) in category_expansion_pairs(&game_data) {
+                        if let Err(e) = do_grep(
+                            &game_data,
+                            &mut data_file_set,
+                            &mut statements,
+                            category,
+                            expansion,
+                            &re,
+                            &mut output,
+                            mode,
+                            &mut outcome,
+                        ) {
+                            eprintln!("error: couldn't read files, {}", e);
+                            process::exit(exit::FAILURE);
                         }
                     }
                 }
             }
+            exit_code = outcome.exit_code();
         }
         Some(("discover_paths", _matches)) => {
+            data_file_set.set_read_ahead(ReadAhead::Sequential);
             if let Err(e) = discover_paths(&game_data, &mut data_file_set) {
                 eprintln!("error: {}", e);
-                process::exit(1);
+                process::exit(exit::FAILURE);
             }
         }
-        Some(("exd", matches)) => {
-            let original_path = matches.get_one::<String>("path").unwrap();
+        Some(("check_indexes", matches)) => {
+            let mode = ErrorMode::from_matches(matches, ErrorMode::KeepGoing);
+            match check_indexes(&game_data, &mut statements, &mut output, mode) {
+                Ok(outcome) => exit_code = outcome.exit_code(),
+                Err(e) => {
+                    eprintln!("error: couldn't read indices, {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            }
+        }
+        Some(("verify", matches)) => {
+            let database = matches.get_one::<PathBuf>("versions").map(|path| {
+                match File::open(path)
+                    .map_err(Into::into)
+                    .and_then(VersionDatabase::read)
+                {
+                    Ok(database) => database,
+                    Err(e) => {
+                        eprintln!("error: couldn't read version database, {}", e);
+                        process::exit(exit::FAILURE);
+                    }
+                }
+            });
+            let mut builder = ThreadPoolBuilder::new();
+            if let Some(jobs) = matches.get_one::<usize>("jobs") {
+                builder = builder.num_threads(*jobs);
+            }
+            let thread_pool = match builder.build() {
+                Ok(thread_pool) => thread_pool,
+                Err(e) => {
+                    eprintln!("error: couldn't start threads, {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            match verify(
+                root,
+                &game_data,
+                &mut statements,
+                database.as_ref(),
+                &thread_pool,
+                &mut output,
+                ErrorMode::from_matches(matches, ErrorMode::KeepGoing),
+            ) {
+                Ok(outcome) => exit_code = outcome.exit_code(),
+                Err(e) => {
+                    eprintln!("error: verification failed, {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            }
+        }
+        Some(("compatibility", _)) => {
+            let report = match compatibility::probe(&game_data, &mut data_file_set) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("error: couldn't read the installation, {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            let problems = report.problems();
+            let entry_types = report
+                .entry_types
+                .iter()
+                .map(|(entry_type, count)| (format!("{:?}", entry_type).to_lowercase(), *count))
+                .collect::<BTreeMap<_, _>>();
+            if output.is_text() {
+                for (version, count) in report.header_versions.iter() {
+                    println!("{:>10} files with header version {}", count, version);
+                }
+                for (entry_type, count) in entry_types.iter() {
+                    println!("{:>10} {} entries", count, entry_type);
+                }
+                for (field, values) in report.unknown_fields.iter() {
+                    for (value, count) in values.iter() {
+                        println!(
+                            "{:>10} headers with {:#x} in {}",
+                            count,
+                            value,
+                            field.description()
+                        );
+                    }
+                }
+                for (file, error) in report.unrecognized_headers.iter() {
+                    println!("unrecognized header in {}: {}", file, error);
+                }
+                for (file, offset, error) in report.unreadable_entries.iter() {
+                    println!("unreadable entry in {} at {:#x}: {}", file, offset, error);
+                }
+                if problems.is_empty() {
+                    println!("\nall features of this installation are supported");
+                } else {
+                    println!("\nthis installation uses features that aren't fully supported:");
+                    for problem in problems.iter() {
+                        println!("  {}", problem);
+                    }
+                }
+            } else {
+                let unknown_fields = report
+                    .unknown_fields
+                    .iter()
+                    .map(|(field, values)| (field.description(), values))
+                    .collect::<BTreeMap<_, _>>();
+                output.record(json!({
+                    "header_versions": report.header_versions,
+                    "entry_types": entry_types,
+                    "unknown_fields": unknown_fields,
+                    "unrecognized_headers": report.unrecognized_headers,
+                    "unreadable_entries": report.unreadable_entries,
+                    "unknown_categories": report.unknown_categories,
+                    "unknown_expansions": report.unknown_expansions,
+                    "problems": problems,
+                }));
+            }
+            if !problems.is_empty() {
+                exit_code = exit::PROBLEMS_FOUND;
+            }
+        }
+        Some(("stats", matches)) => {
+            let mut packs = Vec::new();
+            for id in game_data.iter_packs() {
+                match stats::pack_totals(&game_data, &mut data_file_set, id) {
+                    Ok(totals) => packs.push((id, totals)),
+                    Err(e) => {
+                        eprintln!("error: couldn't read {}, {}", pack_name(id), e);
+                        process::exit(exit::FAILURE);
+                    }
+                }
+            }
+            let versions = match InstallVersions::read(root) {
+                Ok(versions) => versions,
+                Err(e) => {
+                    eprintln!("error: couldn't read version files, {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            let rows = stats::summarize(&packs, &versions);
+            if matches.get_flag("csv") {
+                stats::write_csv(&mut stdout(), &rows).unwrap();
+            } else {
+                stats::print_rows(rows, &mut output);
+            }
+        }
+        Some(("tag_stats", matches)) => {
             let language = matches
                 .get_one("language")
                 .copied()
                 .unwrap_or(Language::English);
+            let statistics = match tag_stats(&game_data, &mut data_file_set, language) {
+                Ok(statistics) => statistics,
+                Err(e) => {
+                    eprintln!("error: reading sheets failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            if output.is_text() {
+                println!(
+                    "{} strings, {} failed to parse, maximum nesting depth {}",
+                    statistics.strings, statistics.parse_failures, statistics.max_depth
+                );
+                for (heading, counts) in [
+                    ("tags", &statistics.tags),
+                    ("expressions", &statistics.expressions),
+                ] {
+                    println!("\n{}:", heading);
+                    let mut counts = counts.iter().collect::<Vec<_>>();
+                    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+                    for (kind, count) in counts {
+                        println!("{:>10} {}", count, kind);
+                    }
+                }
+            } else {
+                output.record(json!({
+                    "strings": statistics.strings,
+                    "parse_failures": statistics.parse_failures,
+                    "max_depth": statistics.max_depth,
+                    "tags": statistics.tags,
+                    "expressions": statistics.expressions,
+                }));
+            }
+        }
+        Some(("localization", matches)) => {
+            let source = *matches.get_one::<Language>("source").unwrap();
+            let languages = matches
+                .get_many::<Language>("language")
+                .unwrap()
+                .copied()
+                .collect::<Vec<_>>();
+            let csv = matches.get_flag("csv");
+            let root_list = match RootList::open(&game_data, &mut data_file_set) {
+                Ok(root_list) => root_list,
+                Err(e) => {
+                    eprintln!("error: reading the list of sheets failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            if csv {
+                println!("sheet,row,sub_row,column,language,kind");
+            }
+            let mut total = 0;
+            for sheet in root_list.iter() {
+                let issues = match localization::check_sheet(
+                    &game_data,
+                    &mut data_file_set,
+                    sheet,
+                    source,
+                    &languages,
+                ) {
+                    Ok(issues) => issues,
+                    Err(e) => {
+                        eprintln!("error: checking {} failed: {}", sheet, e);
+                        process::exit(exit::FAILURE);
+                    }
+                };
+                total += issues.len();
+                for issue in issues {
+                    let language = issue.language.short_code();
+                    if csv {
+                        println!(
+                            "{},{},{},{},{},{}",
+                            csv::quote(sheet),
+                            issue.row,
+                            issue.sub_row,
+                            issue.column,
+                            language,
+                            issue.kind
+                        );
+                    } else if output.is_text() {
+                        println!(
+                            "{} {}.{} column {}, {}: {}",
+                            sheet, issue.row, issue.sub_row, issue.column, language, issue.kind
+                        );
+                    } else {
+                        output.record(json!({
+                            "sheet": sheet,
+                            "row": issue.row,
+                            "sub_row": issue.sub_row,
+                            "column": issue.column,
+                            "language": language,
+                            "kind": issue.kind.name(),
+                        }));
+                    }
+                }
+            }
+            if output.is_text() && !csv {
+                println!("{} issues", total);
+            }
+        }
+        Some(("exd", matches)) => {
+            let original_path = matches.get_one::<String>("path").unwrap();
+            let languages = match matches.get_many::<Language>("language") {
+                Some(languages) => languages.copied().collect(),
+                None => vec![Language::English],
+            };
+            let provenance = start_provenance(matches, &game_data);
             let path_base = match (original_path.rfind('.'), original_path.starts_with("exd/")) {
                 (Some(dot_position), false) => &original_path[..dot_position],
                 (Some(dot_position), true) => &original_path[4..dot_position],
@@ -815,56 +2320,416 @@ fn main() {
                 (None, true) => &original_path[4..],
             };
 
-            let dataset = match Dataset::load(&game_data, &mut data_file_set, path_base, language) {
+            let dataset = match Dataset::load_with_fallback(
+                &game_data,
+                &mut data_file_set,
+                path_base,
+                &languages,
+            ) {
                 Ok(dataset) => dataset,
+                Err(tomestone_exdf::Error::NoSuchFile) => {
+                    let suggestion = RootList::open(&game_data, &mut data_file_set)
+                        .ok()
+                        .and_then(|root_list| {
+                            fuzzy::best_match(path_base, root_list.iter()).map(str::to_string)
+                        });
+                    match suggestion {
+                        Some(name) => eprintln!(
+                            "error: no sheet named {:?}, did you mean {}?",
+                            path_base, name
+                        ),
+                        None => eprintln!("error: no sheet named {:?}", path_base),
+                    }
+                    process::exit(exit::NOT_FOUND);
+                }
                 Err(e) => {
                     eprintln!("error: loading dataset failed: {}", e);
-                    process::exit(1);
+                    process::exit(exit::FAILURE);
                 }
             };
-            println!("{:#?}", &dataset.exhf);
-            for page_iter in dataset.page_iter() {
-                for res in page_iter {
-                    let row = match res {
-                        Ok(row) => row,
-                        Err(e) => {
-                            eprintln!("error: reading dataset failed: {}", e);
-                            process::exit(1);
-                        }
-                    };
+            if matches.get_flag("csv") {
+                let mut writer = BufWriter::new(stdout().lock());
+                if let Err(e) = csv::write_sheet(&mut writer, &dataset)
+                    .and_then(|_| writer.flush().map_err(csv::Error::Io))
+                {
+                    eprintln!("error: writing CSV failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            } else {
+                if output.is_text() {
+                    println!("{:#?}", &dataset.exhf);
+                }
+                for page_iter in dataset.page_iter() {
+                    for res in page_iter {
+                        let row = match res {
+                            Ok(row) => row,
+                            Err(e) => {
+                                eprintln!("error: reading dataset failed: {}", e);
+                                process::exit(exit::FAILURE);
+                            }
+                        };
 
-                    let mut line = format!("{} [[", row.number);
-                    for (sub_row_counter, sub_row) in row.sub_rows.iter().enumerate() {
-                        if sub_row_counter != 0 {
-                            line.push_str("], [");
+                        if !output.is_text() {
+                            let sub_rows = row
+                                .sub_rows
+                                .iter()
+                                .map(|sub_row| {
+                                    sub_row.cells.iter().map(cell_json).collect::<Vec<_>>()
+                                })
+                                .collect::<Vec<_>>();
+                            output.record(json!({"row": row.number, "sub_rows": sub_rows}));
+                            continue;
                         }
-                        for (i, value) in sub_row.cells.iter().enumerate() {
-                            if i != 0 {
-                                line.push_str(", ");
+
+                        let mut line = format!("{} [[", row.number);
+                        for (sub_row_counter, sub_row) in row.sub_rows.iter().enumerate() {
+                            if sub_row_counter != 0 {
+                                line.push_str("], [");
                             }
-                            if let Value::String(data) = value {
-                                match Text::parse(data) {
-                                    Ok(text) => write!(&mut line, "{:?}", text).unwrap(),
-                                    Err(e) => {
-                                        eprintln!("error: parsing tagged text failed: {}", e);
-                                        process::exit(1);
+                            for (i, value) in sub_row.cells.iter().enumerate() {
+                                if i != 0 {
+                                    line.push_str(", ");
+                                }
+                                if let Value::String(data) = value {
+                                    match Text::parse(data) {
+                                        Ok(text) => write!(&mut line, "{:?}", text).unwrap(),
+                                        Err(e) => {
+                                            eprintln!("error: parsing tagged text failed: {}", e);
+                                            process::exit(exit::FAILURE);
+                                        }
                                     }
+                                } else {
+                                    write!(&mut line, "{:?}", value).unwrap();
                                 }
-                            } else {
-                                write!(&mut line, "{:?}", value).unwrap();
                             }
                         }
+                        line.push_str("]]");
+                        println!("{}", line);
+                    }
+                }
+            }
+            if let Some(provenance) = provenance {
+                let path = matches.get_one::<PathBuf>("provenance").unwrap();
+                finish_provenance(provenance, &game_data, &mut data_file_set, path);
+            }
+        }
+        Some(("collision", matches)) => {
+            let path = matches.get_one::<String>("path").unwrap();
+            let data = match game_data.lookup_path_data(&mut data_file_set, path) {
+                Ok(Some(data)) => data,
+                Ok(None) => {
+                    report_file_not_found(&mut statements, std::iter::once(path));
+                    process::exit(exit::NOT_FOUND);
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            let mesh = match CollisionMesh::parse(&data) {
+                Ok(mesh) => mesh,
+                Err(e) => {
+                    eprintln!("error: parsing collision mesh failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            if output.is_text() {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                mesh.write_obj(name, stdout().lock()).unwrap();
+            } else {
+                output.record(json!({"vertices": mesh.vertices, "triangles": mesh.triangles}));
+            }
+        }
+        Some(("bundle", matches)) => {
+            let language = matches
+                .get_one("language")
+                .copied()
+                .unwrap_or(Language::English);
+            let file = matches.get_one::<PathBuf>("output").unwrap();
+            let provenance = start_provenance(matches, &game_data);
+            let document = match Bundle::load(matches.get_one::<String>("bundle").unwrap())
+                .and_then(|bundle| bundle.export(&game_data, &mut data_file_set, language))
+            {
+                Ok(document) => document,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            let rows = document.as_array().map_or(0, Vec::len);
+            let res = File::create(file)
+                .map(BufWriter::new)
+                .and_then(|mut writer| {
+                    serde_json::to_writer_pretty(&mut writer, &document)?;
+                    writer.flush()
+                });
+            if let Err(e) = res {
+                eprintln!("error: couldn't write {:?}, {}", file, e);
+                process::exit(exit::FAILURE);
+            }
+            if let Some(provenance) = provenance {
+                let path = provenance::sidecar_path(file);
+                finish_provenance(provenance, &game_data, &mut data_file_set, &path);
+            }
+            output.record(json!({"file": file, "rows": rows}));
+            if output.is_text() {
+                println!("exported {} rows", rows);
+            }
+        }
+        Some(("archive", matches)) => {
+            let language = matches
+                .get_one("language")
+                .copied()
+                .unwrap_or(Language::English);
+            let mode = ErrorMode::from_matches(matches, ErrorMode::KeepGoing);
+            let output_dir =
+                &paths::extended_length_path(matches.get_one::<PathBuf>("output").unwrap());
+            let provenance = start_provenance(matches, &game_data);
+            let outcome = match archive::run(
+                root,
+                &game_data,
+                &mut data_file_set,
+                output_dir,
+                language,
+                mode,
+            ) {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    eprintln!("error: archiving failed, {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            if let Some(mut provenance) = provenance {
+                match archive::read_manifest(&outcome.dir) {
+                    Ok(manifest) => {
+                        for entry in manifest.files {
+                            provenance.add_source_hash(&entry.path, entry.sha1);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("error: couldn't read the archive's manifest, {}", e);
+                        process::exit(exit::FAILURE);
+                    }
+                }
+                write_provenance(&provenance, &outcome.dir.join(provenance::FILE_NAME));
+            }
+            let problems = outcome.state.problems();
+            let failed_sheets = outcome.state.failed_sheets();
+            if output.is_text() {
+                println!(
+                    "archived game version {} in {:?}",
+                    outcome.state.game_version, outcome.dir
+                );
+                for stage in archive::STAGES {
+                    let record = &outcome.state.stages[*stage];
+                    let note = if outcome.skipped.contains(stage) {
+                        ", finished in an earlier run"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "{}: {} seconds{}, {}",
+                        stage,
+                        record
+                            .finished
+                            .unwrap_or(record.started)
+                            .saturating_sub(record.started),
+                        note,
+                        record.summary
+                    );
+                }
+                for failure in failed_sheets.iter() {
+                    println!("skipped: {}", failure);
+                }
+                for problem in problems.iter() {
+                    println!("problem: {}", problem);
+                }
+            } else {
+                output.record(json!({
+                    "dir": outcome.dir,
+                    "game_version": outcome.state.game_version,
+                    "stages": outcome.state.stages,
+                    "skipped": outcome.skipped,
+                    "problems": problems,
+                }));
+            }
+            exit_code = exit::Outcome {
+                problems: !problems.is_empty(),
+                skipped: failed_sheets.len(),
+            }
+            .exit_code();
+        }
+        Some(("icons", matches)) => {
+            let sheet = matches.get_one::<String>("sheet").unwrap();
+            let icon_column = *matches.get_one::<usize>("icon-column").unwrap();
+            let output_dir =
+                &paths::extended_length_path(matches.get_one::<PathBuf>("output").unwrap());
+            let name_column = matches.get_one::<usize>("name-column").copied();
+            let contact_sheet_columns = matches.get_one::<usize>("contact-sheet").copied();
+            let high_resolution = matches.get_flag("high-resolution");
+            let language = matches
+                .get_one("language")
+                .copied()
+                .unwrap_or(Language::English);
+            let mode = ErrorMode::from_matches(matches, ErrorMode::KeepGoing);
+            let provenance = start_provenance(matches, &game_data);
+
+            let references = match sheet_icons(
+                &game_data,
+                &mut data_file_set,
+                sheet,
+                icon_column,
+                name_column,
+                language,
+            ) {
+                Ok(references) => references,
+                Err(e) => {
+                    eprintln!("error: reading sheet failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            if let Err(e) = fs::create_dir_all(output_dir) {
+                eprintln!("error: couldn't create {:?}, {}", output_dir, e);
+                process::exit(exit::FAILURE);
+            }
+            let mut images = Vec::new();
+            for reference in references.iter() {
+                let path = icon_path(reference.icon, high_resolution);
+                let image = match game_data.lookup_path_data(&mut data_file_set, &path) {
+                    Ok(Some(data)) => match decode_tex(&data) {
+                        Ok(image) => image,
+                        Err(e) if mode == ErrorMode::KeepGoing => {
+                            eprintln!("warning: couldn't decode {}, {}", path, e);
+                            continue;
+                        }
+                        Err(e) => {
+                            eprintln!("error: couldn't decode {}, {}", path, e);
+                            process::exit(exit::FAILURE);
+                        }
+                    },
+                    Ok(None) if mode == ErrorMode::KeepGoing => {
+                        eprintln!("warning: {} not found", path);
+                        continue;
+                    }
+                    Ok(None) => {
+                        eprintln!("error: {} not found", path);
+                        process::exit(exit::NOT_FOUND);
+                    }
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        process::exit(exit::FAILURE);
                     }
-                    line.push_str("]]");
-                    println!("{}", line);
+                };
+                let file = output_dir.join(icon_file_name(reference));
+                if let Err(e) = write_png_file(&file, &image) {
+                    eprintln!("error: couldn't write {:?}, {}", file, e);
+                    process::exit(exit::FAILURE);
                 }
+                output.record(json!({"row": reference.row, "icon": reference.icon, "file": file}));
+                // Icons are only kept in memory if they will be combined into a contact sheet.
+                if contact_sheet_columns.is_some() {
+                    images.push(image);
+                }
+            }
+            if let Some(sheet) =
+                contact_sheet_columns.and_then(|columns| contact_sheet(&images, columns))
+            {
+                let file = output_dir.join("contact-sheet.png");
+                if let Err(e) = write_png_file(&file, &sheet) {
+                    eprintln!("error: couldn't write {:?}, {}", file, e);
+                    process::exit(exit::FAILURE);
+                }
+            }
+            if let Some(provenance) = provenance {
+                let path = output_dir.join(provenance::FILE_NAME);
+                finish_provenance(provenance, &game_data, &mut data_file_set, &path);
+            }
+            if output.is_text() {
+                println!("exported {} of {} icons", images.len(), references.len());
+            }
+            exit_code = Outcome {
+                problems: false,
+                skipped: references.len() - images.len(),
+            }
+            .exit_code();
+        }
+        Some(("sound_names", matches)) => {
+            let language = matches
+                .get_one("language")
+                .copied()
+                .unwrap_or(Language::English);
+            let names = match sound_names(&game_data, &mut data_file_set, language) {
+                Ok(names) => names,
+                Err(e) => {
+                    eprintln!("error: reading sheets failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            for (path, rows) in names.iter() {
+                let name = best_name(rows).unwrap_or_default();
+                if output.is_text() {
+                    println!("{}\t{}", path, name);
+                    continue;
+                }
+                let rows = rows
+                    .iter()
+                    .map(|row| json!({"sheet": row.sheet, "row": row.row, "name": row.name}))
+                    .collect::<Vec<_>>();
+                output.record(json!({"path": path, "name": name, "rows": rows}));
+            }
+        }
+        Some(("housing_models", matches)) => {
+            let output_dir =
+                &paths::extended_length_path(matches.get_one::<PathBuf>("output").unwrap());
+            let language = matches
+                .get_one("language")
+                .copied()
+                .unwrap_or(Language::English);
+            let provenance = start_provenance(matches, &game_data);
+            let furniture = match list_furniture(&game_data, &mut data_file_set, language) {
+                Ok(furniture) => furniture,
+                Err(e) => {
+                    eprintln!("error: reading housing sheets failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            };
+            match export_furniture(&game_data, &mut data_file_set, &furniture, output_dir) {
+                Ok(written) if output.is_text() => println!(
+                    "exported {} of {} furniture models",
+                    written.len(),
+                    furniture.len()
+                ),
+                Ok(written) => {
+                    for file in written {
+                        output.record(json!({"file": file}));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: exporting models failed: {}", e);
+                    process::exit(exit::FAILURE);
+                }
+            }
+            if let Some(provenance) = provenance {
+                let path = output_dir.join(provenance::FILE_NAME);
+                finish_provenance(provenance, &game_data, &mut data_file_set, &path);
             }
         }
         _ => {
             eprintln!("{}", app().render_usage());
-            process::exit(1);
+            process::exit(exit::USAGE);
+        }
+    }
+    output.finish();
+    if let (Some(path), Some(log)) = (access_log_path, game_data.access_log()) {
+        let res = File::create(path).and_then(|file| log.write_json(BufWriter::new(file)));
+        if let Err(e) = res {
+            eprintln!("error: couldn't write the access log to {:?}, {}", path, e);
+            process::exit(exit::FAILURE);
         }
     }
+    if exit_code != 0 {
+        process::exit(exit_code);
+    }
 }
 
 #[cfg(test)]
@@ -872,7 +2737,15 @@ mod tests {
     use tomestone_common::test_game_data_or_skip;
     use tomestone_sqpack::GameData;
 
-    use crate::{app, lookup, write_hex_dump, PATH_DISCOVERY_RE};
+    use clap_complete::Shell;
+
+    use crate::{
+        app, completion_app, convert_file,
+        exit::{self, ErrorMode, Outcome},
+        lookup,
+        output::Format,
+        write_hex_dump, PATH_DISCOVERY_RE,
+    };
 
     #[test]
     fn path_discovery_regex() {
@@ -893,6 +2766,21 @@ mod tests {
         assert!(it.next().is_none());
     }
 
+    #[test]
+    fn convert_texture() {
+        use tomestone_texture::{encode_tex, RgbaImage, TextureFormat};
+
+        let image = RgbaImage::new(2, 2, vec![0x80; 16]).unwrap();
+        let tex = encode_tex(&image, TextureFormat::B8G8R8A8, false).unwrap();
+        let png = convert_file("ui/icon/000000/000001.tex", tex).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(
+            convert_file("exd/root.exl", b"EXLT".to_vec()).unwrap(),
+            b"EXLT"
+        );
+        assert!(convert_file("ui/icon/000000/000002.tex", b"short".to_vec()).is_err());
+    }
+
     #[test]
     fn hex_dump() {
         fn reftest(data: &[u8], reference: &[u8]) {
@@ -1165,4 +3053,71 @@ mod tests {
     fn verify_app() {
         app().debug_assert();
     }
+
+    #[test]
+    fn sheet_name_completions() {
+        let mut command = completion_app(vec!["Item".to_string(), "quest/000/Sample".to_string()]);
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Zsh, &mut command, "tomestone-dump", &mut script);
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("quest/000/Sample"));
+
+        // Sheet names are only offered as completions, and are not enforced.
+        completion_app(vec!["Item".to_string()])
+            .try_get_matches_from(["tomestone-dump", "exd", "Item"])
+            .unwrap();
+        app()
+            .try_get_matches_from(["tomestone-dump", "exd", "Other"])
+            .unwrap();
+    }
+
+    #[test]
+    fn error_modes() {
+        let mode = |args: &[&str]| {
+            let matches = app()
+                .try_get_matches_from(
+                    ["tomestone-dump", "grep"]
+                        .iter()
+                        .chain(args)
+                        .chain(["x"].iter()),
+                )
+                .unwrap();
+            let (_, matches) = matches.subcommand().unwrap();
+            ErrorMode::from_matches(matches, ErrorMode::FailFast)
+        };
+        assert_eq!(mode(&[]), ErrorMode::FailFast);
+        assert_eq!(mode(&["--keep-going"]), ErrorMode::KeepGoing);
+        assert_eq!(mode(&["--keep-going", "--fail-fast"]), ErrorMode::FailFast);
+
+        assert_eq!(Outcome::default().exit_code(), 0);
+        let outcome = Outcome {
+            problems: false,
+            skipped: 2,
+        };
+        assert_eq!(outcome.exit_code(), exit::PARTIAL_SUCCESS);
+        let outcome = Outcome {
+            problems: true,
+            ..outcome
+        };
+        assert_eq!(outcome.exit_code(), exit::PROBLEMS_FOUND);
+    }
+
+    #[test]
+    fn format_after_subcommand() {
+        let matches = app()
+            .try_get_matches_from(["tomestone-dump", "--ffxiv-install-dir", ".", "list"])
+            .unwrap();
+        assert_eq!(matches.get_one::<Format>("format"), Some(&Format::Text));
+        let matches = app()
+            .try_get_matches_from([
+                "tomestone-dump",
+                "--ffxiv-install-dir",
+                ".",
+                "list",
+                "--format",
+                "ndjson",
+            ])
+            .unwrap();
+        assert_eq!(matches.get_one::<Format>("format"), Some(&Format::Ndjson));
+    }
 }
@@ -0,0 +1,126 @@
+//! Machine-readable output, selected with `--format`.
+//!
+//! With `--format json`, a command prints one JSON document once it finishes:
+//! `{"version": 1, "command": "list", "results": [...]}`. With `--format ndjson`, it prints a
+//! header line, `{"version": 1, "command": "list"}`, then each result on its own line as soon as
+//! it is found. Errors and warnings are still printed to standard error as text.
+//!
+//! The version is incremented whenever a field is removed or changes meaning. Results have these
+//! fields, by command:
+//!
+//! - `raw`, `hex`: `size`, and `data` in hexadecimal.
+//! - `list`, `grep`: `pack`, and `path`, which is as much of the path as is known, with unknown
+//!   parts given as CRCs in angle brackets.
+//! - `check_indexes`: `pack`, `discrepancy`, one of `dat_file_count`, `missing_from_index1`,
+//!   `missing_from_index2`, or `shared_pointer`, and a human-readable `message`.
+//! - `verify`: `kind`, one of `version`, `missing_version_file`, `boot_file_missing`,
+//!   `boot_file_mismatch`, `index_discrepancy`, or `unreadable_file`, and further fields
+//!   depending on the kind. Index discrepancies have the same fields as in `check_indexes`.
+//! - `inspect`: `section`, `offset`, `size`, `name`, `value`, and `data` in hexadecimal, for each
+//!   header field.
+//! - `compatibility`: `header_versions` and `entry_types`, mapping versions and types to counts,
+//!   `unrecognized_headers` and `unreadable_entries`, examples of files and entries that couldn't
+//!   be parsed, `unknown_categories`, `unknown_expansions`, and `problems`, as human-readable
+//!   messages.
+//! - `stats`: `group`, one of `category`, `expansion`, or `total`, `name`, `version`, `entries`,
+//!   `stored_size`, `uncompressed_size`, and `ratio`.
+//! - `tag_stats`: `strings`, `parse_failures`, `max_depth`, and `tags` and `expressions`, mapping
+//!   kinds to counts.
+//! - `localization`: `sheet`, `row`, `sub_row`, `column`, `language`, and `kind`, one of
+//!   `missing`, `empty`, or `identical_to_source`.
+//! - `exd`: `row`, and `sub_rows`, a list of lists of cells. Text cells are serialized as parsed
+//!   text.
+//! - `bundle`: `file`, and the number of `rows` written.
+//! - `archive`: `dir`, `game_version`, `stages`, mapping each stage to when it `started` and
+//!   `finished` and its `summary`, `skipped`, the stages that finished in an earlier run, and
+//!   `problems` found when verifying the archive.
+//! - `collision`: `vertices` and `triangles`, as lists of three element lists.
+//! - `icons`: `row`, `icon`, and `file`, for each icon written.
+//! - `sound_names`: `path`, `name`, and `rows`, each with `sheet`, `row`, and `name`.
+//! - `housing_models`: `file`, for each model written.
+
+use std::io::{stdout, Write};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    Ndjson,
+}
+
+/// Collects or streams the results of a command, depending on the output format. Nothing is
+/// printed for text output, which commands print themselves.
+pub struct Output {
+    format: Format,
+    command: String,
+    results: Vec<Value>,
+}
+
+impl Output {
+    pub fn new(format: Format, command: &str) -> Output {
+        if format == Format::Ndjson {
+            print_line(&json!({"version": FORMAT_VERSION, "command": command}));
+        }
+        Output {
+            format,
+            command: command.to_string(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.format == Format::Text
+    }
+
+    pub fn record<T: Serialize>(&mut self, result: T) {
+        match self.format {
+            Format::Text => {}
+            Format::Json => self
+                .results
+                .push(serde_json::to_value(result).expect("results are valid JSON")),
+            Format::Ndjson => print_line(&result),
+        }
+    }
+
+    /// Prints the JSON document, if the format is `json`.
+    pub fn finish(self) {
+        if self.format == Format::Json {
+            print_line(&json!({
+                "version": FORMAT_VERSION,
+                "command": self.command,
+                "results": self.results,
+            }));
+        }
+    }
+}
+
+fn print_line<T: Serialize>(value: &T) {
+    let stdout = stdout();
+    let mut locked = stdout.lock();
+    serde_json::to_writer(&mut locked, value).expect("results are valid JSON");
+    locked.write_all(b"\n").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Format, Output};
+
+    #[test]
+    fn collects_json_results() {
+        let mut output = Output::new(Format::Json, "list");
+        assert!(!output.is_text());
+        output.record(json!({"pack": "0a0000", "path": "exd/root.exl"}));
+        assert_eq!(output.results.len(), 1);
+
+        let mut output = Output::new(Format::Text, "list");
+        output.record(json!({}));
+        assert!(output.results.is_empty());
+    }
+}
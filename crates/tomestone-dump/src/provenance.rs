@@ -0,0 +1,185 @@
+//! Provenance records, written next to exported files with `--provenance`.
+//!
+//! A record notes the installed game version, the version of this tool, when the export was made,
+//! and the SHA-1 hash of each file that was read from the game's data, so that an archive of
+//! exported files can be traced back to its source. Commands that export into a directory write
+//! [`FILE_NAME`] inside it, and commands that export a single file write a record next to it,
+//! named by [`sidecar_path`].
+//!
+//! The files that were read are taken from the [`AccessLog`](tomestone_sqpack::AccessLog) of the
+//! `GameData`, which `main` enables when a record is requested.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tomestone_sqpack::{DataFileSet, GameData, InstallVersions};
+
+/// The name of the provenance record, within an output directory.
+pub const FILE_NAME: &str = "provenance.json";
+
+#[derive(Debug, Serialize)]
+pub struct Source {
+    pub path: String,
+    pub sha1: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Provenance {
+    pub tool: &'static str,
+    pub tool_version: &'static str,
+    pub game_version: Option<String>,
+    /// Expansion versions, by directory name, e.g. `ex1`.
+    pub expansion_versions: BTreeMap<String, String>,
+    /// Seconds since the Unix epoch.
+    pub exported_at: u64,
+    pub sources: Vec<Source>,
+}
+
+impl Provenance {
+    pub fn new(versions: &InstallVersions) -> Provenance {
+        Provenance {
+            tool: env!("CARGO_PKG_NAME"),
+            tool_version: env!("CARGO_PKG_VERSION"),
+            game_version: versions.game.clone(),
+            expansion_versions: versions
+                .expansions
+                .iter()
+                .map(|(number, version)| (format!("ex{}", number), version.clone()))
+                .collect(),
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Records a file that was read from the game's data.
+    pub fn add_source(&mut self, path: &str, data: &[u8]) {
+        self.add_source_hash(path, hex::encode(tomestone_sqpack::sha1(data)));
+    }
+
+    /// Records a file whose hash is already known, as lowercase hexadecimal.
+    pub fn add_source_hash(&mut self, path: &str, sha1: String) {
+        self.sources.push(Source {
+            path: path.to_string(),
+            sha1,
+        });
+    }
+
+    /// Records every file found through the access log of `game_data`, reading each one again to
+    /// hash it. Does nothing if the access log isn't enabled.
+    pub fn add_accessed_files(
+        &mut self,
+        game_data: &GameData,
+        data_file_set: &mut DataFileSet,
+    ) -> Result<(), tomestone_sqpack::Error> {
+        let paths = match game_data.access_log() {
+            Some(log) => log.found_paths(),
+            None => return Ok(()),
+        };
+        for path in paths {
+            if let Some(data) = game_data.lookup_path_data(data_file_set, &path)? {
+                self.add_source(&path, &data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the record to a file, usually [`FILE_NAME`] in the output directory.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+/// Returns the path of the record for an export written to a single file, such as
+/// `achievements.provenance.json` for `achievements.json`.
+pub fn sidecar_path(file: &Path) -> PathBuf {
+    file.with_extension("provenance.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tomestone_sqpack::{
+        encoding::write_pack, Category, Expansion, GameData, InstallVersions, SqPackId,
+    };
+
+    use super::{sidecar_path, Provenance, FILE_NAME};
+
+    #[test]
+    fn write_provenance() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut versions = InstallVersions {
+            game: Some("2023.01.05.0000.0000".to_string()),
+            ..Default::default()
+        };
+        versions
+            .expansions
+            .insert(1, "2023.01.04.0000.0000".to_string());
+        let mut provenance = Provenance::new(&versions);
+        provenance.add_source("ui/icon/000000/000001.tex", b"abc");
+        provenance.write(&dir.path().join(FILE_NAME)).unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join(FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(written["tool"], "tomestone-dump");
+        assert_eq!(written["game_version"], "2023.01.05.0000.0000");
+        assert_eq!(written["expansion_versions"]["ex1"], "2023.01.04.0000.0000");
+        assert_eq!(
+            written["sources"][0]["sha1"],
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+    }
+
+    #[test]
+    fn accessed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_pack(
+            &dir.path().join("game").join("sqpack"),
+            SqPackId {
+                category: Category::Exd,
+                expansion: Expansion::Base,
+                number: 0,
+            },
+            [("exd/a.exh", &b"abc"[..]), ("exd/b.exh", b"def")],
+        )
+        .unwrap();
+        let game_data = GameData::builder()
+            .access_log(true)
+            .open(dir.path())
+            .unwrap();
+        let mut data_file_set = game_data.data_files();
+        game_data
+            .lookup_path_data(&mut data_file_set, "exd/a.exh")
+            .unwrap();
+        game_data
+            .lookup_path_data(&mut data_file_set, "exd/missing.exh")
+            .unwrap();
+
+        let mut provenance = Provenance::new(&InstallVersions::default());
+        provenance
+            .add_accessed_files(&game_data, &mut data_file_set)
+            .unwrap();
+        assert_eq!(provenance.sources.len(), 1);
+        assert_eq!(provenance.sources[0].path, "exd/a.exh");
+        assert_eq!(
+            provenance.sources[0].sha1,
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+
+        assert_eq!(
+            sidecar_path(Path::new("out/achievements.json")),
+            Path::new("out/achievements.provenance.json")
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Sizes of an installation's files, by category and by expansion, for `stats`.
+//!
+//! Stored sizes count each entry's headers and blocks in the data files, and uncompressed sizes
+//! come from the entry headers. Entries shared by several hashes are counted once.
+
+use std::{collections::BTreeMap, io::Write};
+
+use serde::Serialize;
+use tomestone_patch::install::InstallVersions;
+use tomestone_sqpack::{Category, DataFileSet, Expansion, GameData, SqPackId};
+
+use crate::output::Output;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub entries: u64,
+    pub stored_size: u64,
+    pub uncompressed_size: u64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.entries += other.entries;
+        self.stored_size += other.stored_size;
+        self.uncompressed_size += other.uncompressed_size;
+    }
+
+    /// Stored size divided by uncompressed size.
+    pub fn ratio(&self) -> f64 {
+        if self.uncompressed_size == 0 {
+            1.0
+        } else {
+            self.stored_size as f64 / self.uncompressed_size as f64
+        }
+    }
+}
+
+/// Adds up the entries of one pack.
+pub fn pack_totals(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    pack_id: SqPackId,
+) -> Result<Totals, tomestone_sqpack::Error> {
+    let index = match game_data.get_index_2(&pack_id) {
+        Some(index) => index?,
+        None => return Ok(Totals::default()),
+    };
+    let mut pointers = index.iter().map(|(_, pointer)| pointer).collect::<Vec<_>>();
+    pointers.sort_unstable();
+    pointers.dedup();
+
+    let mut totals = Totals::default();
+    for pointer in pointers {
+        let uncompressed_size = data_file_set.entry_size(pack_id, pointer)?;
+        let blocks = data_file_set.entry_blocks(pack_id, pointer)?;
+        let end = blocks
+            .blocks()
+            .map(|block| block.offset + block.compressed_size)
+            .max()
+            .unwrap_or_else(|| pointer.offset());
+        totals.entries += 1;
+        totals.stored_size += u64::from(end - pointer.offset());
+        totals.uncompressed_size += u64::from(uncompressed_size);
+    }
+    Ok(totals)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Row {
+    /// Either `category`, `expansion`, or `total`.
+    pub group: &'static str,
+    pub name: String,
+    /// The installed version, for expansions.
+    pub version: Option<String>,
+    #[serde(flatten)]
+    pub totals: Totals,
+    pub ratio: f64,
+}
+
+impl Row {
+    fn new(group: &'static str, name: String, version: Option<String>, totals: Totals) -> Row {
+        Row {
+            group,
+            name,
+            version,
+            totals,
+            ratio: totals.ratio(),
+        }
+    }
+}
+
+/// Groups per-pack totals by category and by expansion, followed by the total of everything.
+pub fn summarize(packs: &[(SqPackId, Totals)], versions: &InstallVersions) -> Vec<Row> {
+    let mut categories = BTreeMap::<u8, Totals>::new();
+    let mut expansions = BTreeMap::<u8, Totals>::new();
+    let mut total = Totals::default();
+    for (id, totals) in packs {
+        categories
+            .entry(id.category.to_u8())
+            .or_default()
+            .add(totals);
+        expansions
+            .entry(id.expansion.to_u8())
+            .or_default()
+            .add(totals);
+        total.add(totals);
+    }
+
+    let mut rows = Vec::new();
+    for (number, totals) in categories {
+        let category = Category::from_u8(number);
+        let name = match category.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:02x}", number),
+        };
+        rows.push(Row::new("category", name, None, totals));
+    }
+    for (number, totals) in expansions {
+        let expansion = Expansion::from_u8(number);
+        let version = match expansion {
+            Expansion::Base => versions.game.clone(),
+            _ => versions.expansions.get(&number).cloned(),
+        };
+        rows.push(Row::new(
+            "expansion",
+            expansion.name().into_owned(),
+            version,
+            totals,
+        ));
+    }
+    rows.push(Row::new("total", "total".to_string(), None, total));
+    rows
+}
+
+pub fn print_rows(rows: Vec<Row>, output: &mut Output) {
+    if !output.is_text() {
+        for row in rows {
+            output.record(row);
+        }
+        return;
+    }
+    println!(
+        "{:<10} {:<12} {:<22} {:>9} {:>15} {:>17} {:>6}",
+        "group", "name", "version", "entries", "stored size", "uncompressed size", "ratio"
+    );
+    for row in rows {
+        println!(
+            "{:<10} {:<12} {:<22} {:>9} {:>15} {:>17} {:>6.3}",
+            row.group,
+            row.name,
+            row.version.as_deref().unwrap_or(""),
+            row.totals.entries,
+            row.totals.stored_size,
+            row.totals.uncompressed_size,
+            row.ratio,
+        );
+    }
+}
+
+pub fn write_csv<W: Write>(writer: &mut W, rows: &[Row]) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "group,name,version,entries,stored_size,uncompressed_size,ratio"
+    )?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{:.6}",
+            row.group,
+            row.name,
+            row.version.as_deref().unwrap_or(""),
+            row.totals.entries,
+            row.totals.stored_size,
+            row.totals.uncompressed_size,
+            row.ratio,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_patch::install::InstallVersions;
+    use tomestone_sqpack::{Category, Expansion, SqPackId};
+
+    use super::{summarize, write_csv, Totals};
+
+    #[test]
+    fn summarize_packs() {
+        let totals = |entries, stored_size, uncompressed_size| Totals {
+            entries,
+            stored_size,
+            uncompressed_size,
+        };
+        let id = |category, expansion, number| SqPackId {
+            category,
+            expansion,
+            number,
+        };
+        let packs = [
+            (id(Category::Exd, Expansion::Base, 0), totals(10, 100, 400)),
+            (id(Category::Bg, Expansion::Base, 0), totals(5, 500, 1000)),
+            (id(Category::Bg, Expansion::Ex1, 0), totals(5, 300, 600)),
+            (id(Category::Bg, Expansion::Ex1, 1), totals(1, 100, 100)),
+        ];
+        let mut versions = InstallVersions::default();
+        versions
+            .expansions
+            .insert(1, "2023.01.05.0000.0000".to_string());
+
+        let rows = summarize(&packs, &versions);
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &rows).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "group,name,version,entries,stored_size,uncompressed_size,ratio\n\
+            category,bg,,11,900,1700,0.529412\n\
+            category,exd,,10,100,400,0.250000\n\
+            expansion,ffxiv,,15,600,1400,0.428571\n\
+            expansion,ex1,2023.01.05.0000.0000,6,400,700,0.571429\n\
+            total,total,,21,1000,2100,0.476190\n"
+        );
+    }
+}
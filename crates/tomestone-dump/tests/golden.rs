@@ -0,0 +1,58 @@
+//! Regression tests for the format parsers, run through `inspect`.
+//!
+//! Each sample file in `tests/golden` is inspected, and the JSON output is compared against the
+//! expected output in a file of the same name with `.json` appended. Samples are small, and only
+//! hold headers, so no game data is needed. To add a sample, or to accept changes to the output,
+//! run the tests with `GOLDEN=overwrite` set, and review the changes to the `.json` files.
+
+use std::{fs, path::Path, process::Command};
+
+#[test]
+fn golden_samples() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let overwrite = std::env::var_os("GOLDEN").is_some_and(|value| value == "overwrite");
+
+    let mut samples = fs::read_dir(&golden_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_none_or(|extension| extension != "json"))
+        .collect::<Vec<_>>();
+    samples.sort();
+    assert!(!samples.is_empty());
+
+    let mut failures = Vec::new();
+    for sample in samples {
+        let output = Command::new(env!("CARGO_BIN_EXE_tomestone-dump"))
+            .args(["inspect", "--format", "json"])
+            .arg(&sample)
+            .env_remove("FFXIV_INSTALL_DIR")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "inspecting {:?} failed", sample);
+        let actual: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        let mut expected_path = sample.clone().into_os_string();
+        expected_path.push(".json");
+        if overwrite {
+            let mut text = serde_json::to_string_pretty(&actual).unwrap();
+            text.push('\n');
+            fs::write(&expected_path, text).unwrap();
+            continue;
+        }
+        let expected: serde_json::Value = match fs::read(&expected_path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap(),
+            Err(_) => {
+                failures.push(format!("{:?} has no expected output", sample));
+                continue;
+            }
+        };
+        if actual != expected {
+            failures.push(format!(
+                "{:?} doesn't match, got:\n{}",
+                sample,
+                serde_json::to_string_pretty(&actual).unwrap()
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -1,7 +1,7 @@
 use std::{convert::TryInto, process};
 
 use tomestone_exdf::{Dataset, Language, RootList};
-use tomestone_sqpack::GameData;
+use tomestone_sqpack::{Category, GameData};
 
 fn main() {
     dotenvy::dotenv().ok();
@@ -11,7 +11,7 @@ fn main() {
         eprintln!("error: set the environment variable FFXIV_INSTALL_DIR to the game's installation directory");
         process::exit(1);
     };
-    let game_data = match GameData::new(&root) {
+    let game_data = match GameData::builder().only(&[Category::Exd]).open(&root) {
         Ok(game_data) => game_data,
         Err(e) => {
             eprintln!(
@@ -0,0 +1,309 @@
+//! Row-by-row comparison of two versions of a sheet.
+//!
+//! Changes can be emitted as [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902) JSON Patch
+//! operations. Paths refer to a JSON document that represents a sheet as an object keyed by row
+//! number, where each row is an array of sub-rows, and each sub-row is an array of cell values.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::{Row, SubRow, Value};
+
+/// How one row differs between two versions of a sheet.
+#[derive(Debug)]
+pub enum RowDiff<'r, 'a> {
+    Added(&'r Row<'a>),
+    Removed(&'r Row<'a>),
+    Changed { old: &'r Row<'a>, new: &'r Row<'a> },
+}
+
+impl<'r, 'a> RowDiff<'r, 'a> {
+    pub fn number(&self) -> u32 {
+        match self {
+            RowDiff::Added(row) | RowDiff::Removed(row) => row.number,
+            RowDiff::Changed { new, .. } => new.number,
+        }
+    }
+
+    /// Lists the JSON Patch operations for this row.
+    fn operations(&self) -> Vec<Operation<'r, 'a>> {
+        let operation = |op, path, value| Operation { op, path, value };
+        match self {
+            RowDiff::Added(row) => vec![operation(
+                "add",
+                format!("/{}", row.number),
+                Some(Json::SubRows(&row.sub_rows)),
+            )],
+            RowDiff::Removed(row) => vec![operation("remove", format!("/{}", row.number), None)],
+            RowDiff::Changed { old, new } => {
+                let number = new.number;
+                let mut operations = Vec::new();
+                for (i, (old_sub_row, new_sub_row)) in
+                    old.sub_rows.iter().zip(new.sub_rows.iter()).enumerate()
+                {
+                    if old_sub_row.cells.len() != new_sub_row.cells.len() {
+                        operations.push(operation(
+                            "replace",
+                            format!("/{}/{}", number, i),
+                            Some(Json::SubRow(new_sub_row)),
+                        ));
+                        continue;
+                    }
+                    for (column, (old_value, new_value)) in old_sub_row
+                        .cells
+                        .iter()
+                        .zip(new_sub_row.cells.iter())
+                        .enumerate()
+                    {
+                        if !values_equal(old_value, new_value) {
+                            operations.push(operation(
+                                "replace",
+                                format!("/{}/{}/{}", number, i, column),
+                                Some(Json::Cell(new_value)),
+                            ));
+                        }
+                    }
+                }
+                for (i, sub_row) in new.sub_rows.iter().enumerate().skip(old.sub_rows.len()) {
+                    operations.push(operation(
+                        "add",
+                        format!("/{}/{}", number, i),
+                        Some(Json::SubRow(sub_row)),
+                    ));
+                }
+                // Remove trailing sub-rows from the end, so that earlier indices stay valid.
+                for i in (new.sub_rows.len()..old.sub_rows.len()).rev() {
+                    operations.push(operation("remove", format!("/{}/{}", number, i), None));
+                }
+                operations
+            }
+        }
+    }
+
+    /// Writes a JSON Patch document that applies this row's change to a sheet document.
+    pub fn write_json_patch<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        serde_json::to_writer(&mut writer, &self.operations())?;
+        writeln!(writer)
+    }
+}
+
+/// The differences between two versions of a sheet, sorted by row number.
+#[derive(Debug)]
+pub struct SheetDiff<'r, 'a> {
+    rows: Vec<RowDiff<'r, 'a>>,
+}
+
+impl<'r, 'a> SheetDiff<'r, 'a> {
+    pub fn compare(old: &'r [Row<'a>], new: &'r [Row<'a>]) -> SheetDiff<'r, 'a> {
+        let mut pairs: BTreeMap<u32, (Option<&'r Row<'a>>, Option<&'r Row<'a>>)> = BTreeMap::new();
+        for row in old {
+            pairs.entry(row.number).or_default().0 = Some(row);
+        }
+        for row in new {
+            pairs.entry(row.number).or_default().1 = Some(row);
+        }
+
+        let rows = pairs
+            .into_values()
+            .filter_map(|pair| match pair {
+                (None, Some(new)) => Some(RowDiff::Added(new)),
+                (Some(old), None) => Some(RowDiff::Removed(old)),
+                (Some(old), Some(new)) if !rows_equal(old, new) => {
+                    Some(RowDiff::Changed { old, new })
+                }
+                _ => None,
+            })
+            .collect();
+        SheetDiff { rows }
+    }
+
+    pub fn rows(&self) -> &[RowDiff<'r, 'a>] {
+        &self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Writes a single JSON Patch document containing the changes to every row.
+    pub fn write_json_patch<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let operations = self
+            .rows
+            .iter()
+            .flat_map(RowDiff::operations)
+            .collect::<Vec<_>>();
+        serde_json::to_writer(&mut writer, &operations)?;
+        writeln!(writer)
+    }
+}
+
+fn rows_equal(old: &Row, new: &Row) -> bool {
+    old.sub_rows.len() == new.sub_rows.len()
+        && old
+            .sub_rows
+            .iter()
+            .zip(new.sub_rows.iter())
+            .all(|(old, new)| {
+                old.cells.len() == new.cells.len()
+                    && old
+                        .cells
+                        .iter()
+                        .zip(new.cells.iter())
+                        .all(|(old, new)| values_equal(old, new))
+            })
+}
+
+/// Compares cell values, treating borrowed and owned strings alike, and comparing floats
+/// bitwise so that NaN values are not reported as changes.
+fn values_equal(old: &Value, new: &Value) -> bool {
+    match (old, new) {
+        (Value::String(_) | Value::StringOwned(_), Value::String(_) | Value::StringOwned(_)) => {
+            string_bytes(old) == string_bytes(new)
+        }
+        (Value::Float(old), Value::Float(new)) => old.to_bits() == new.to_bits(),
+        _ => old == new,
+    }
+}
+
+fn string_bytes<'v>(value: &'v Value) -> &'v [u8] {
+    match value {
+        Value::String(data) => data,
+        Value::StringOwned(data) => data.as_slice(),
+        _ => &[],
+    }
+}
+
+/// One JSON Patch operation.
+#[derive(Serialize)]
+struct Operation<'r, 'a> {
+    op: &'static str,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Json<'r, 'a>>,
+}
+
+/// Part of a row, serialized as in the sheet document.
+enum Json<'r, 'a> {
+    SubRows(&'r [SubRow<'a>]),
+    SubRow(&'r SubRow<'a>),
+    Cell(&'r Value<'a>),
+}
+
+impl<'r, 'a> Serialize for Json<'r, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Json::SubRows(sub_rows) => serializer.collect_seq(sub_rows.iter().map(Json::SubRow)),
+            Json::SubRow(sub_row) => serializer.collect_seq(sub_row.cells.iter().map(Json::Cell)),
+            Json::Cell(value) => serialize_value(value, serializer),
+        }
+    }
+}
+
+/// Serializes a cell value. Strings are decoded lossily, and non-finite floats become `null`, as
+/// JSON has no representation for them.
+fn serialize_value<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Value::String(_) | Value::StringOwned(_) => {
+            serializer.serialize_str(&String::from_utf8_lossy(string_bytes(value)))
+        }
+        Value::Bool(value) | Value::Bitflag(value) => serializer.serialize_bool(*value),
+        Value::I8(value) => serializer.serialize_i8(*value),
+        Value::U8(value) => serializer.serialize_u8(*value),
+        Value::I16(value) => serializer.serialize_i16(*value),
+        Value::U16(value) => serializer.serialize_u16(*value),
+        Value::I32(value) => serializer.serialize_i32(*value),
+        Value::U32(value) => serializer.serialize_u32(*value),
+        Value::I64(value) => serializer.serialize_i64(*value),
+        Value::Float(value) if value.is_finite() => serializer.serialize_f32(*value),
+        Value::Float(_) => serializer.serialize_unit(),
+        Value::I16x4(values) => values.serialize(serializer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RowDiff, SheetDiff};
+    use crate::{Row, SubRow, Value};
+
+    fn row<'a>(number: u32, sub_rows: Vec<Vec<Value<'a>>>) -> Row<'a> {
+        Row {
+            number,
+            sub_rows: sub_rows
+                .into_iter()
+                .enumerate()
+                .map(|(i, cells)| SubRow {
+                    number: i as u16,
+                    cells,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn json_patch() {
+        let old = vec![
+            row(1, vec![vec![Value::String(b"Potion"), Value::U16(10)]]),
+            row(2, vec![vec![Value::String(b"Ether"), Value::U16(20)]]),
+            row(
+                3,
+                vec![
+                    vec![Value::Float(1.5), Value::Bool(true)],
+                    vec![Value::Float(2.0), Value::Bool(false)],
+                ],
+            ),
+        ];
+        let new = vec![
+            row(
+                1,
+                vec![vec![Value::StringOwned(b"Potion".to_vec()), Value::U16(10)]],
+            ),
+            row(
+                3,
+                vec![
+                    vec![Value::Float(1.5), Value::Bool(false)],
+                    vec![Value::Float(2.0), Value::Bool(false)],
+                    vec![Value::Float(f32::NAN), Value::Bool(true)],
+                ],
+            ),
+            row(4, vec![vec![Value::String(b"\"Elixir\""), Value::U16(30)]]),
+        ];
+
+        let diff = SheetDiff::compare(&old, &new);
+        assert!(matches!(
+            diff.rows(),
+            [
+                RowDiff::Removed(Row { number: 2, .. }),
+                RowDiff::Changed { .. },
+                RowDiff::Added(Row { number: 4, .. }),
+            ]
+        ));
+        assert_eq!(
+            diff.rows().iter().map(RowDiff::number).collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+
+        let mut patch = Vec::new();
+        diff.rows()[1].write_json_patch(&mut patch).unwrap();
+        assert_eq!(
+            String::from_utf8(patch).unwrap(),
+            "[{\"op\":\"replace\",\"path\":\"/3/0/1\",\"value\":false},\
+            {\"op\":\"add\",\"path\":\"/3/2\",\"value\":[null,true]}]\n"
+        );
+
+        let mut patch = Vec::new();
+        diff.write_json_patch(&mut patch).unwrap();
+        assert_eq!(
+            String::from_utf8(patch).unwrap(),
+            "[{\"op\":\"remove\",\"path\":\"/2\"},\
+            {\"op\":\"replace\",\"path\":\"/3/0/1\",\"value\":false},\
+            {\"op\":\"add\",\"path\":\"/3/2\",\"value\":[null,true]},\
+            {\"op\":\"add\",\"path\":\"/4\",\"value\":[[\"\\\"Elixir\\\"\",30]]}]\n"
+        );
+
+        assert!(SheetDiff::compare(&new, &new).is_empty());
+    }
+}
@@ -1,43 +1,122 @@
-use std::convert::TryInto;
+use std::{collections::HashMap, convert::TryInto};
 
 use crate::{parser::exhf::Exhf, Cardinality, Row, SubRow, Value};
 
 // TODO, future work: write new code to pre-compute file sizes, and then encode in-place with one allocation.
 
+/// How strings within a row share space in the row's string data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringDedup {
+    /// Every string cell gets its own copy of its string.
+    #[default]
+    Off,
+    /// Identical strings share one copy.
+    Exact,
+    /// Identical strings share one copy, and strings that are a suffix of another string point
+    /// into the end of that string.
+    Suffix,
+}
+
+/// Lays out the string data for a row, returning the string data and the offset of each string
+/// cell, in the order that cells are encoded.
+fn build_string_data(row: &[SubRow], header: &Exhf, dedup: StringDedup) -> (Vec<u8>, Vec<u32>) {
+    let mut strings = Vec::new();
+    for sub_row in row.iter() {
+        for column_def in header.columns_offset_order() {
+            if let crate::ColumnFormat::String = column_def.format {
+                match &sub_row.cells[column_def.index] {
+                    Value::String(data) => strings.push(*data),
+                    Value::StringOwned(data) => strings.push(data.as_slice()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut string_data = Vec::new();
+    let mut place = |data: &[u8]| -> u32 {
+        let offset = string_data.len().try_into().unwrap();
+        string_data.extend_from_slice(data);
+        string_data.push(0);
+        offset
+    };
+    match dedup {
+        StringDedup::Off => {
+            let offsets = strings.iter().map(|data| place(data)).collect();
+            (string_data, offsets)
+        }
+        StringDedup::Exact | StringDedup::Suffix => {
+            // Map each distinct string to the string whose storage it will use, and the
+            // distance from the start of that string.
+            let mut owners: HashMap<&[u8], (&[u8], usize)> = HashMap::new();
+            let mut unique = strings.clone();
+            unique.sort_unstable_by(|a, b| a.iter().rev().cmp(b.iter().rev()));
+            unique.dedup();
+            let mut owner: Option<&[u8]> = None;
+            for data in unique.into_iter().rev() {
+                // Sorting by reversed contents puts each string right before any strings it is a
+                // suffix of.
+                match owner {
+                    Some(owner) if dedup == StringDedup::Suffix && owner.ends_with(data) => {
+                        owners.insert(data, (owner, owner.len() - data.len()));
+                    }
+                    _ => {
+                        owners.insert(data, (data, 0));
+                        owner = Some(data);
+                    }
+                }
+            }
+
+            let mut placed: HashMap<&[u8], u32> = HashMap::new();
+            let offsets = strings
+                .iter()
+                .map(|data| {
+                    let (owner, delta) = owners[data];
+                    let owner_offset = *placed.entry(owner).or_insert_with(|| place(owner));
+                    owner_offset + u32::try_from(delta).unwrap()
+                })
+                .collect();
+            (string_data, offsets)
+        }
+    }
+}
+
 pub fn encode_row(row: &[SubRow], header: &Exhf, padding_offset: u32) -> Vec<u8> {
+    encode_row_with_dedup(row, header, padding_offset, StringDedup::Off)
+}
+
+pub fn encode_row_with_dedup(
+    row: &[SubRow],
+    header: &Exhf,
+    padding_offset: u32,
+    dedup: StringDedup,
+) -> Vec<u8> {
     let row_size: usize = header.row_size().into();
     let inner_length_fixed: usize = match header.cardinality() {
         Cardinality::Single => row_size * row.len(),
         Cardinality::Multiple => (row_size + 2) * row.len(),
     };
-    let mut inner_length_variable: usize = 0;
-    for sub_row in row.iter() {
-        for value in sub_row.cells.iter() {
-            match &value {
-                Value::String(data) => inner_length_variable += data.len() + 1,
-                Value::StringOwned(data) => inner_length_variable += data.len() + 1,
-                _ => {}
-            }
-        }
-    }
+    let (string_data, string_offsets) = build_string_data(row, header, dedup);
+    let inner_length_variable = string_data.len();
     let inner_length_unpadded: u32 = (inner_length_fixed + inner_length_variable)
         .try_into()
         .unwrap();
     let inner_length = match header.cardinality() {
         Cardinality::Single => {
-            ((inner_length_unpadded + padding_offset + 2) + 3) / 4 * 4 - padding_offset - 2
+            (inner_length_unpadded + padding_offset + 2).div_ceil(4) * 4 - padding_offset - 2
         }
-        Cardinality::Multiple => (inner_length_unpadded + 3) / 4 * 4,
+        Cardinality::Multiple => inner_length_unpadded.div_ceil(4) * 4,
     };
     let outer_length = TryInto::<usize>::try_into(inner_length).unwrap() + 6;
 
     let mut data = vec![0; outer_length];
     data[..4].copy_from_slice(&inner_length.to_be_bytes());
     data[4..6].copy_from_slice(&TryInto::<u16>::try_into(row.len()).unwrap().to_be_bytes());
-    let row_size = TryInto::<usize>::try_into(header.row_size()).unwrap();
+    let row_size = usize::from(header.row_size());
     let mut fixed_data_offset = 6;
-    let mut string_data_offset_relative: u32 = 0;
-    let mut string_data_offset_vec = 6 + row_size * row.len();
+    let string_data_start = 6 + row_size * row.len();
+    data[string_data_start..string_data_start + string_data.len()].copy_from_slice(&string_data);
+    let mut string_offsets = string_offsets.into_iter();
     for sub_row in row.iter() {
         if let Cardinality::Multiple = header.cardinality() {
             data[fixed_data_offset..fixed_data_offset + 2]
@@ -48,29 +127,9 @@ pub fn encode_row(row: &[SubRow], header: &Exhf, padding_offset: u32) -> Vec<u8>
             let off = fixed_data_offset + column_def.offset;
             let value = &sub_row.cells[column_def.index];
             match (value, column_def.format) {
-                (Value::String(val), crate::ColumnFormat::String) => {
-                    data[off..off + 4].copy_from_slice(
-                        &TryInto::<u32>::try_into(string_data_offset_relative)
-                            .unwrap()
-                            .to_be_bytes(),
-                    );
-                    data[string_data_offset_vec..string_data_offset_vec + val.len()]
-                        .copy_from_slice(val);
-                    data[string_data_offset_vec + val.len()] = 0;
-                    string_data_offset_relative += TryInto::<u32>::try_into(val.len()).unwrap() + 1;
-                    string_data_offset_vec += val.len() + 1;
-                }
-                (Value::StringOwned(val), crate::ColumnFormat::String) => {
-                    data[off..off + 4].copy_from_slice(
-                        &TryInto::<u32>::try_into(string_data_offset_relative)
-                            .unwrap()
-                            .to_be_bytes(),
-                    );
-                    data[string_data_offset_vec..string_data_offset_vec + val.len()]
-                        .copy_from_slice(val);
-                    data[string_data_offset_vec + val.len()] = 0;
-                    string_data_offset_relative += TryInto::<u32>::try_into(val.len()).unwrap() + 1;
-                    string_data_offset_vec += val.len() + 1;
+                (Value::String(_) | Value::StringOwned(_), crate::ColumnFormat::String) => {
+                    let offset = string_offsets.next().unwrap();
+                    data[off..off + 4].copy_from_slice(&offset.to_be_bytes());
                 }
                 (Value::Bool(val), crate::ColumnFormat::Bool) => data[off] = *val as u8,
                 (Value::I8(val), crate::ColumnFormat::I8) => data[off] = *val as u8,
@@ -90,6 +149,9 @@ pub fn encode_row(row: &[SubRow], header: &Exhf, padding_offset: u32) -> Vec<u8>
                 (Value::Float(val), crate::ColumnFormat::Float) => {
                     data[off..off + 4].copy_from_slice(&val.to_be_bytes())
                 }
+                (Value::I64(val), crate::ColumnFormat::I64) => {
+                    data[off..off + 8].copy_from_slice(&val.to_be_bytes())
+                }
                 (Value::I16x4(val), crate::ColumnFormat::I16x4) => {
                     data[off..off + 2].copy_from_slice(&val[0].to_be_bytes());
                     data[off + 2..off + 4].copy_from_slice(&val[1].to_be_bytes());
@@ -111,6 +173,15 @@ pub fn encode_row(row: &[SubRow], header: &Exhf, padding_offset: u32) -> Vec<u8>
 }
 
 pub fn encode_exdf_page(name: &str, header: &Exhf, rows: &[Row]) -> Vec<u8> {
+    encode_exdf_page_with_dedup(name, header, rows, StringDedup::Off)
+}
+
+pub fn encode_exdf_page_with_dedup(
+    name: &str,
+    header: &Exhf,
+    rows: &[Row],
+    dedup: StringDedup,
+) -> Vec<u8> {
     const HEADER_LENGTH: u32 = 32;
 
     let padding_offset = match name {
@@ -131,7 +202,7 @@ pub fn encode_exdf_page(name: &str, header: &Exhf, rows: &[Row]) -> Vec<u8> {
     let mut offsets_section = Vec::with_capacity(offsets_len.try_into().unwrap());
     let mut data_section = Vec::new();
     for row in rows {
-        let mut encoded_row = encode_row(&row.sub_rows, header, padding_offset);
+        let mut encoded_row = encode_row_with_dedup(&row.sub_rows, header, padding_offset, dedup);
         let row_offset: u32 = data_section.len().try_into().unwrap();
         data_section.append(&mut encoded_row);
         offsets_section.extend_from_slice(&row.number.to_be_bytes());
@@ -211,7 +282,55 @@ mod tests {
     use tomestone_common::test_game_data_or_skip;
     use tomestone_sqpack::GameData;
 
-    use crate::{parser::exhf::parse_exhf, RootList};
+    use super::{encode_row_with_dedup, StringDedup};
+    use crate::{
+        parser::{exhf::parse_exhf, parse_row},
+        RawDataRow, RootList, SubRow, Value,
+    };
+
+    #[test]
+    fn string_dedup() {
+        // Two string columns, in a sheet with a single page and no languages.
+        let mut exh_data = b"EXHF\x00\x03\x00\x08\x00\x02\x00\x01\x00\x01\x00\x00\x00\x01".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 4]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+
+        for (first, second, dedup, string_data_length) in [
+            ("Potion", "Hi-Potion", StringDedup::Off, 17),
+            ("Potion", "Hi-Potion", StringDedup::Exact, 17),
+            ("Potion", "Hi-Potion", StringDedup::Suffix, 10),
+            ("Potion", "Potion", StringDedup::Exact, 7),
+            ("", "Potion", StringDedup::Suffix, 7),
+        ] {
+            let row = [SubRow {
+                number: 0,
+                cells: vec![
+                    Value::String(first.as_bytes()),
+                    Value::StringOwned(second.as_bytes().to_vec()),
+                ],
+            }];
+            let encoded = encode_row_with_dedup(&row, &exhf, 0, dedup);
+            let string_data = &encoded[6 + 8..];
+            assert_eq!(
+                string_data.iter().rposition(|byte| *byte != 0).unwrap() + 2,
+                string_data_length,
+                "{:?} {:?} {:?}",
+                first,
+                second,
+                dedup
+            );
+
+            let raw = RawDataRow {
+                data: &encoded[6..],
+                sub_row_count: 1,
+            };
+            let decoded = parse_row(raw, &exhf).unwrap();
+            assert_eq!(decoded[0].cells[0], Value::String(first.as_bytes()));
+            assert_eq!(decoded[0].cells[1], Value::String(second.as_bytes()));
+        }
+    }
 
     #[test]
     #[ignore = "slow test"]
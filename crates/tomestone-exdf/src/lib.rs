@@ -1,34 +1,61 @@
-use std::{fmt, str::FromStr, string::FromUtf8Error};
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::{
+    string::{FromUtf8Error, String},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+
+#[cfg(feature = "std")]
 use nom::Finish;
+#[cfg(feature = "std")]
 use parser::{
     exdf::{Exdf, ExdfIterator},
     exhf::{parse_exhf, Exhf},
     parse_row,
 };
+#[cfg(feature = "std")]
 use tomestone_sqpack::{DataFileSet, GameData};
 
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
 pub mod encoding;
+#[cfg(feature = "std")]
+pub mod localization;
 pub mod parser;
+#[cfg(feature = "std")]
+pub mod quest;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(all(feature = "std", feature = "core-sheets"))]
+pub mod sheets;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod stats;
 
 #[derive(Debug)]
 pub struct EnumParseError;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(clap::ValueEnum))]
 pub enum Language {
-    #[value(name = "ja")]
+    #[cfg_attr(feature = "std", value(name = "ja"))]
     Japanese = 1,
-    #[value(name = "en")]
+    #[cfg_attr(feature = "std", value(name = "en"))]
     English = 2,
-    #[value(name = "de")]
+    #[cfg_attr(feature = "std", value(name = "de"))]
     German = 3,
-    #[value(name = "fr")]
+    #[cfg_attr(feature = "std", value(name = "fr"))]
     French = 4,
-    #[value(name = "cns")]
+    #[cfg_attr(feature = "std", value(name = "cns"))]
     ChineseSimplified = 5,
-    #[value(name = "cnt")]
+    #[cfg_attr(feature = "std", value(name = "cnt"))]
     ChineseTraditional = 6,
-    #[value(name = "kr")]
+    #[cfg_attr(feature = "std", value(name = "kr"))]
     Korean = 7,
 }
 
@@ -95,6 +122,7 @@ pub enum ColumnFormat {
     I32,
     U32,
     Float,
+    I64,
     I16x4,
     Bitflag(u8),
 }
@@ -111,6 +139,7 @@ impl ColumnFormat {
             6 => Ok(ColumnFormat::I32),
             7 => Ok(ColumnFormat::U32),
             9 => Ok(ColumnFormat::Float),
+            0xa => Ok(ColumnFormat::I64),
             0xb => Ok(ColumnFormat::I16x4),
             0x19..=0x20 => Ok(ColumnFormat::Bitflag((value - 0x19) as u8)),
             _ => Err(EnumParseError),
@@ -128,6 +157,7 @@ impl ColumnFormat {
             ColumnFormat::I32 => 6,
             ColumnFormat::U32 => 7,
             ColumnFormat::Float => 9,
+            ColumnFormat::I64 => 0xa,
             ColumnFormat::I16x4 => 0xb,
             ColumnFormat::Bitflag(bit) => bit as u16 + 0x19,
         }
@@ -178,6 +208,7 @@ pub enum Value<'a> {
     I32(i32),
     U32(u32),
     Float(f32),
+    I64(i64),
     I16x4([i16; 4]),
     Bitflag(bool),
 }
@@ -195,12 +226,59 @@ impl<'a> fmt::Debug for Value<'a> {
             Value::I32(value) => value.fmt(f),
             Value::U32(value) => value.fmt(f),
             Value::Float(value) => value.fmt(f),
+            Value::I64(value) => value.fmt(f),
             Value::I16x4(value) => value.fmt(f),
             Value::Bitflag(value) => value.fmt(f),
         }
     }
 }
 
+impl<'a> Value<'a> {
+    /// Returns the raw bytes of a string cell, which can be parsed with
+    /// `tomestone_string_interp::Text::parse`.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::String(data) => Some(data),
+            Value::StringOwned(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) | Value::Bitflag(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of an integer cell of any width.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::I8(value) => Some(value.into()),
+            Value::U8(value) => Some(value.into()),
+            Value::I16(value) => Some(value.into()),
+            Value::U16(value) => Some(value.into()),
+            Value::I32(value) => Some(value.into()),
+            Value::U32(value) => Some(value.into()),
+            Value::I64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of an integer cell, if it isn't negative. This is the usual type of
+    /// cells that hold row numbers of other sheets, or icon IDs.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|value| u64::try_from(value).ok())
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Sqpack(tomestone_sqpack::Error),
@@ -208,6 +286,8 @@ pub enum Error {
     NoSuchFile,
     LanguageUnavailable,
     Utf8(FromUtf8Error),
+    /// A column was missing, or had a different type than expected.
+    ColumnMismatch(usize),
 }
 
 impl From<tomestone_sqpack::Error> for Error {
@@ -236,6 +316,9 @@ impl fmt::Display for Error {
             Error::NoSuchFile => write!(f, "file not found"),
             Error::LanguageUnavailable => write!(f, "language data not available"),
             Error::Utf8(e) => e.fmt(f),
+            Error::ColumnMismatch(column) => {
+                write!(f, "column {} is missing or has an unexpected type", column)
+            }
         }
     }
 }
@@ -257,16 +340,59 @@ pub struct Row<'a> {
     pub sub_rows: Vec<SubRow<'a>>,
 }
 
+/// A sub-row allocated in an arena. See [`DatasetPageIter::next_in`].
+#[cfg(feature = "bumpalo")]
+#[derive(Debug)]
+pub struct SubRowRef<'a, 'bump> {
+    pub number: u16,
+    pub cells: &'bump [Value<'a>],
+}
+
+/// A row allocated in an arena. See [`DatasetPageIter::next_in`].
+#[cfg(feature = "bumpalo")]
+#[derive(Debug)]
+pub struct RowRef<'a, 'bump> {
+    pub number: u32,
+    pub sub_rows: &'bump [SubRowRef<'a, 'bump>],
+}
+
+#[cfg(feature = "std")]
 struct DatasetPage {
     row_start: u32,
     exdf: Exdf,
 }
 
+#[cfg(feature = "std")]
 pub struct DatasetPageIter<'a> {
     exdf_iter: ExdfIterator<'a>,
     exhf: &'a Exhf,
 }
 
+#[cfg(feature = "std")]
+impl<'a> DatasetPageIter<'a> {
+    /// Parses the next row, allocating its sub-rows and cells in `bump` instead of the global
+    /// allocator. The arena may be reset once the rows of a page are no longer needed. String
+    /// cells can be parsed into the same arena with `tomestone_string_interp::Text::parse_in`.
+    #[cfg(feature = "bumpalo")]
+    pub fn next_in<'bump>(
+        &mut self,
+        bump: &'bump bumpalo::Bump,
+    ) -> Option<Result<RowRef<'a, 'bump>, Error>> {
+        let (row_number, row_data) = match self.exdf_iter.next()? {
+            Ok((row_number, row_data)) => (row_number, row_data),
+            Err(e) => return Some(Err(e.into())),
+        };
+        match parser::parse_row_in(row_data, self.exhf, bump) {
+            Ok(sub_rows) => Some(Ok(RowRef {
+                number: row_number,
+                sub_rows,
+            })),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl<'a> Iterator for DatasetPageIter<'a> {
     type Item = Result<Row<'a>, Error>;
 
@@ -285,6 +411,7 @@ impl<'a> Iterator for DatasetPageIter<'a> {
     }
 }
 
+#[cfg(feature = "std")]
 pub struct Dataset<'a> {
     pub exhf: Exhf,
     pages: Vec<DatasetPage>,
@@ -292,6 +419,7 @@ pub struct Dataset<'a> {
     language: Option<Language>,
 }
 
+#[cfg(feature = "std")]
 impl<'a> Dataset<'a> {
     fn exh_path_helper(base: &str) -> String {
         format!("exd/{}.exh", base)
@@ -309,11 +437,25 @@ impl<'a> Dataset<'a> {
         }
     }
 
+    /// Loads a sheet in the given language. Sheets that aren't localized are loaded without a
+    /// language, and otherwise [`Error::LanguageUnavailable`] is returned if the sheet doesn't
+    /// have the language.
     pub fn load(
         game_data: &GameData,
         data_file_set: &mut DataFileSet,
         base: &'a str,
         language: Language,
+    ) -> Result<Dataset<'a>, Error> {
+        Self::load_with_fallback(game_data, data_file_set, base, &[language])
+    }
+
+    /// Loads a sheet in the first of the given languages that it has, such as English, then
+    /// Japanese. See [`load`](Self::load).
+    pub fn load_with_fallback(
+        game_data: &GameData,
+        data_file_set: &mut DataFileSet,
+        base: &'a str,
+        languages: &[Language],
     ) -> Result<Dataset<'a>, Error> {
         let exh_path = Self::exh_path_helper(base);
         let exh_data = match game_data.lookup_path_data(data_file_set, &exh_path) {
@@ -326,15 +468,18 @@ impl<'a> Dataset<'a> {
             .map_err(|e| Error::Nom(e.code))?
             .1;
 
-        let language = if exhf.languages().contains(&Some(language)) {
-            Some(language)
+        let language = if let Some(language) = languages
+            .iter()
+            .find(|language| exhf.languages().contains(&Some(**language)))
+        {
+            Some(*language)
         } else if exhf.languages().contains(&None) {
             None
         } else {
             return Err(Error::LanguageUnavailable);
         };
 
-        let pages = exhf
+        let mut pages = exhf
             .pages()
             .iter()
             .map(|(page_start, _)| {
@@ -351,6 +496,7 @@ impl<'a> Dataset<'a> {
                 })
             })
             .collect::<Result<Vec<_>, _>>()?;
+        pages.sort_by_key(|page| page.row_start);
         Ok(Dataset {
             exhf,
             pages,
@@ -359,6 +505,22 @@ impl<'a> Dataset<'a> {
         })
     }
 
+    /// Lists the name of every sheet in the game, from `exd/root.exl`, so that each can be
+    /// loaded with [`load`](Self::load). See [`RootList`] for sheet IDs as well.
+    pub fn iter_sheet_names(
+        game_data: &GameData,
+        data_file_set: &mut DataFileSet,
+    ) -> Result<impl Iterator<Item = String>, Error> {
+        let root_list = RootList::open(game_data, data_file_set)?;
+        Ok(root_list
+            .iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+
+    /// Iterates over the pages of this dataset, sorted by their first row number. Rows within
+    /// each page are sorted by row number as well.
     pub fn page_iter(&self) -> impl Iterator<Item = DatasetPageIter<'_>> {
         let exhf = &self.exhf;
         self.pages.iter().map(move |p| DatasetPageIter {
@@ -367,6 +529,42 @@ impl<'a> Dataset<'a> {
         })
     }
 
+    /// Looks up a row by its number. Returns `None` if the sheet has no such row.
+    pub fn row(&self, number: u32) -> Option<Result<Row<'_>, Error>> {
+        let page_index = self
+            .pages
+            .partition_point(|page| page.row_start <= number)
+            .checked_sub(1)?;
+        let row_data = match self.pages[page_index].exdf.lookup(number)? {
+            Ok(row_data) => row_data,
+            Err(e) => return Some(Err(e.into())),
+        };
+        Some(
+            parse_row(row_data, &self.exhf)
+                .map(|sub_rows| Row { number, sub_rows })
+                .map_err(Into::into),
+        )
+    }
+
+    /// Looks up one cell of a row, in its first sub-row. Returns `None` if the sheet has no such
+    /// row or column.
+    pub fn cell(&self, row: u32, column: usize) -> Option<Result<Value<'_>, Error>> {
+        match self.row(row)? {
+            Ok(row) => row
+                .sub_rows
+                .into_iter()
+                .next()
+                .and_then(|sub_row| sub_row.cells.into_iter().nth(column))
+                .map(Ok),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Returns the language that was loaded, or `None` if the sheet isn't localized.
+    pub fn language(&self) -> Option<Language> {
+        self.language
+    }
+
     pub fn exh_path(&self) -> String {
         Self::exh_path_helper(self.base)
     }
@@ -382,31 +580,45 @@ impl<'a> Dataset<'a> {
     }
 }
 
+/// The list of every sheet in the game, from `exd/root.exl`.
 pub struct RootList {
     text: String,
 }
 
 impl RootList {
+    #[cfg(feature = "std")]
     pub fn open(game_data: &GameData, data_file_set: &mut DataFileSet) -> Result<RootList, Error> {
         let data = match game_data.lookup_path_data(data_file_set, "exd/root.exl") {
             Ok(Some(toc_data)) => toc_data,
             Ok(None) => return Err(Error::NoSuchFile),
             Err(e) => return Err(Error::Sqpack(e)),
         };
+        RootList::parse(data)
+    }
+
+    /// Reads a root list from the contents of `exd/root.exl`.
+    pub fn parse(data: Vec<u8>) -> Result<RootList, Error> {
         match String::from_utf8(data) {
             Ok(text) => Ok(RootList { text }),
             Err(e) => Err(Error::Utf8(e)),
         }
     }
 
+    /// Iterates over the names of all sheets.
     pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.iter_with_ids().map(|(name, _)| name)
+    }
+
+    /// Iterates over the names of all sheets, with their IDs. Most sheets have no ID, and are
+    /// only referred to by name.
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (&str, Option<u32>)> {
         self.text.split_ascii_whitespace().filter_map(|line| {
-            if let Some(comma_pos) = line.find(',') {
-                let name = &line[..comma_pos];
+            if let Some((name, id)) = line.split_once(',') {
                 if name == "EXLT" {
                     return None;
                 }
-                Some(name)
+                // IDs are -1 for sheets without one.
+                Some((name, id.parse().ok()))
             } else {
                 // skip empty line
                 None
@@ -417,7 +629,146 @@ impl RootList {
 
 #[cfg(test)]
 mod tests {
-    use super::{EnumParseError, Language};
+    use tomestone_sqpack::{Category, Expansion, GameData, MemoryProvider, SqPackId};
+
+    use super::{EnumParseError, Language, RootList};
+    use crate::{
+        encoding::encode_exdf_page, parser::exhf::parse_exhf, Dataset, Error, Row, SubRow, Value,
+    };
+
+    fn game_data_with_files(files: &[(&str, &[u8])]) -> GameData {
+        let mut provider = MemoryProvider::new();
+        provider
+            .write_pack(
+                SqPackId {
+                    category: Category::Exd,
+                    expansion: Expansion::Base,
+                    number: 0,
+                },
+                files.iter().copied(),
+            )
+            .unwrap();
+        GameData::from_provider(provider)
+    }
+
+    #[test]
+    fn row_lookup() {
+        // One u32 column, in a sheet without sub-rows or languages, with pages starting at rows
+        // 0 and 10.
+        let mut exh_data = b"EXHF\x00\x03\x00\x04\x00\x01\x00\x02\x00\x01\x00\x00\x00\x01".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 7, 0, 0]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 10, 0, 0, 0, 1, 0, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+        let row = |number, value| Row {
+            number,
+            sub_rows: vec![SubRow {
+                number: 0,
+                cells: vec![Value::U32(value)],
+            }],
+        };
+        let page_0 = encode_exdf_page("Test", &exhf, &[row(1, 100), row(3, 300)]);
+        let page_10 = encode_exdf_page("Test", &exhf, &[row(12, 1200)]);
+
+        let game_data = game_data_with_files(&[
+            ("exd/Test.exh", &exh_data),
+            ("exd/Test_0.exd", &page_0),
+            ("exd/Test_10.exd", &page_10),
+        ]);
+        let mut data_file_set = game_data.data_files();
+        let dataset =
+            Dataset::load(&game_data, &mut data_file_set, "Test", Language::English).unwrap();
+        assert_eq!(dataset.language(), None);
+        assert_eq!(dataset.cell(3, 0).unwrap().unwrap(), Value::U32(300));
+        assert_eq!(dataset.cell(12, 0).unwrap().unwrap(), Value::U32(1200));
+        assert_eq!(dataset.row(12).unwrap().unwrap().number, 12);
+        assert!(dataset.row(2).is_none());
+        assert!(dataset.row(20).is_none());
+        assert!(dataset.cell(1, 1).is_none());
+    }
+
+    #[test]
+    fn language_fallback() {
+        // One u8 column, in a sheet with only Japanese and German text.
+        let mut exh_data = b"EXHF\x00\x03\x00\x01\x00\x01\x00\x01\x00\x02\x00\x00\x00\x01".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 3, 0, 0]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 3, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+        let page = |value| {
+            encode_exdf_page(
+                "Test",
+                &exhf,
+                &[Row {
+                    number: 0,
+                    sub_rows: vec![SubRow {
+                        number: 0,
+                        cells: vec![Value::U8(value)],
+                    }],
+                }],
+            )
+        };
+        let (page_ja, page_de) = (page(1), page(3));
+        let game_data = game_data_with_files(&[
+            ("exd/Test.exh", &exh_data),
+            ("exd/Test_0_ja.exd", &page_ja),
+            ("exd/Test_0_de.exd", &page_de),
+        ]);
+        let mut data_file_set = game_data.data_files();
+
+        assert!(matches!(
+            Dataset::load(&game_data, &mut data_file_set, "Test", Language::English),
+            Err(Error::LanguageUnavailable)
+        ));
+        let dataset = Dataset::load_with_fallback(
+            &game_data,
+            &mut data_file_set,
+            "Test",
+            &[Language::English, Language::German, Language::Japanese],
+        )
+        .unwrap();
+        assert_eq!(dataset.language(), Some(Language::German));
+        assert_eq!(dataset.cell(0, 0).unwrap().unwrap(), Value::U8(3));
+    }
+
+    #[test]
+    fn sheet_names() {
+        let game_data =
+            game_data_with_files(&[("exd/root.exl", b"EXLT,2\r\nAchievement,209\r\nItem,-1\r\n")]);
+        let mut data_file_set = game_data.data_files();
+        assert_eq!(
+            Dataset::iter_sheet_names(&game_data, &mut data_file_set)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            ["Achievement", "Item"]
+        );
+
+        let game_data = game_data_with_files(&[]);
+        let mut data_file_set = game_data.data_files();
+        assert!(matches!(
+            Dataset::iter_sheet_names(&game_data, &mut data_file_set),
+            Err(Error::NoSuchFile)
+        ));
+    }
+
+    #[test]
+    fn root_list() {
+        let root_list = RootList::parse(
+            b"EXLT,2\r\nAchievement,209\r\n\r\nquest/000/ClsHyu001_00003,-1\r\n".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(
+            root_list.iter_with_ids().collect::<Vec<_>>(),
+            [
+                ("Achievement", Some(209)),
+                ("quest/000/ClsHyu001_00003", None)
+            ]
+        );
+        assert_eq!(
+            root_list.iter().collect::<Vec<_>>(),
+            ["Achievement", "quest/000/ClsHyu001_00003"]
+        );
+    }
 
     #[test]
     fn language_round_trip() {
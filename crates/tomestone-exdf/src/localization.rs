@@ -0,0 +1,192 @@
+//! Checks of how completely a sheet's text is translated.
+//!
+//! Each string cell of the source language, usually Japanese, is compared against the same cell
+//! in each translation. A translation is flagged if its row is missing, if the cell is empty
+//! while the source isn't, or if it is identical to the source. Identical text is only flagged
+//! when it contains letters, since numbers and punctuation are often the same in every language.
+
+use std::{collections::HashMap, fmt};
+
+use tomestone_sqpack::{DataFileSet, GameData};
+
+use crate::{Dataset, Error, Language, Row, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The translation has no such row or sub-row.
+    Missing,
+    /// The translated cell is empty.
+    Empty,
+    /// The translated cell is the same as the source text.
+    IdenticalToSource,
+}
+
+impl IssueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IssueKind::Missing => "missing",
+            IssueKind::Empty => "empty",
+            IssueKind::IdenticalToSource => "identical_to_source",
+        }
+    }
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A string cell with an incomplete translation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub row: u32,
+    pub sub_row: u16,
+    pub column: usize,
+    pub language: Language,
+    pub kind: IssueKind,
+}
+
+fn has_letters(data: &[u8]) -> bool {
+    String::from_utf8_lossy(data)
+        .chars()
+        .any(char::is_alphabetic)
+}
+
+/// Compares the rows of a sheet in the source language against the same sheet in another
+/// language.
+pub fn compare_rows(source: &[Row], language: Language, translation: &[Row]) -> Vec<Issue> {
+    let translated_cells = translation
+        .iter()
+        .flat_map(|row| {
+            row.sub_rows
+                .iter()
+                .map(move |sub_row| ((row.number, sub_row.number), &sub_row.cells))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut issues = Vec::new();
+    for row in source {
+        for sub_row in row.sub_rows.iter() {
+            let cells = translated_cells.get(&(row.number, sub_row.number));
+            for (column, value) in sub_row.cells.iter().enumerate() {
+                let source_text = match value.as_bytes() {
+                    Some(text) if !text.is_empty() => text,
+                    _ => continue,
+                };
+                let kind = match cells.map(|cells| cells.get(column).and_then(Value::as_bytes)) {
+                    None | Some(None) => IssueKind::Missing,
+                    Some(Some([])) => IssueKind::Empty,
+                    Some(Some(text)) if text == source_text && has_letters(text) => {
+                        IssueKind::IdenticalToSource
+                    }
+                    Some(Some(_)) => continue,
+                };
+                issues.push(Issue {
+                    row: row.number,
+                    sub_row: sub_row.number,
+                    column,
+                    language,
+                    kind,
+                });
+            }
+        }
+    }
+    issues
+}
+
+fn load_rows<'a>(dataset: &'a Dataset) -> Result<Vec<Row<'a>>, Error> {
+    dataset.page_iter().flatten().collect()
+}
+
+/// Checks the translations of one sheet. Sheets that aren't localized, or that don't have the
+/// source language, have no issues. A language the sheet doesn't have at all is reported as
+/// missing for every string.
+pub fn check_sheet(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    sheet: &str,
+    source: Language,
+    languages: &[Language],
+) -> Result<Vec<Issue>, Error> {
+    let source_dataset = match Dataset::load(game_data, data_file_set, sheet, source) {
+        Ok(dataset) => dataset,
+        Err(Error::LanguageUnavailable) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    if !source_dataset.exhf.languages().contains(&Some(source)) {
+        return Ok(Vec::new());
+    }
+    let source_rows = load_rows(&source_dataset)?;
+
+    let mut issues = Vec::new();
+    for &language in languages.iter().filter(|language| **language != source) {
+        if !source_dataset.exhf.languages().contains(&Some(language)) {
+            issues.extend(compare_rows(&source_rows, language, &[]));
+            continue;
+        }
+        let dataset = Dataset::load(game_data, data_file_set, sheet, language)?;
+        issues.extend(compare_rows(&source_rows, language, &load_rows(&dataset)?));
+    }
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Language, Row, SubRow, Value};
+
+    use super::{compare_rows, Issue, IssueKind};
+
+    fn row(number: u32, cells: Vec<Value<'static>>) -> Row<'static> {
+        Row {
+            number,
+            sub_rows: vec![SubRow { number: 0, cells }],
+        }
+    }
+
+    #[test]
+    fn translation_issues() {
+        let source = [
+            row(
+                0,
+                vec![
+                    Value::String("剣".as_bytes()),
+                    Value::U8(1),
+                    Value::String(b""),
+                ],
+            ),
+            row(1, vec![Value::String("盾".as_bytes())]),
+            row(
+                2,
+                vec![Value::String("槍".as_bytes()), Value::String(b"100")],
+            ),
+            row(3, vec![Value::String("斧".as_bytes())]),
+        ];
+        let translation = [
+            row(
+                0,
+                vec![Value::String(b"Sword"), Value::U8(1), Value::String(b"")],
+            ),
+            row(1, vec![Value::String(b"")]),
+            row(
+                2,
+                vec![Value::String("槍".as_bytes()), Value::String(b"100")],
+            ),
+        ];
+        let issue = |row, kind| Issue {
+            row,
+            sub_row: 0,
+            column: 0,
+            language: Language::English,
+            kind,
+        };
+        assert_eq!(
+            compare_rows(&source, Language::English, &translation),
+            [
+                issue(1, IssueKind::Empty),
+                issue(2, IssueKind::IdenticalToSource),
+                issue(3, IssueKind::Missing),
+            ]
+        );
+    }
+}
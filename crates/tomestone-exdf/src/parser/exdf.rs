@@ -1,4 +1,5 @@
-use std::convert::TryInto;
+use alloc::{boxed::Box, vec::Vec};
+use core::convert::TryInto;
 
 use nom::{
     bytes::streaming::tag,
@@ -14,6 +15,8 @@ use tomestone_common::null_padding;
 
 use crate::RawDataRow;
 
+use super::{exhf::Exhf, raw_sub_rows};
+
 #[derive(Debug)]
 struct ExdfHeader {
     offset_table_size: u32,
@@ -37,13 +40,19 @@ impl Exdf {
         let input = &data;
         let (input, header) = exdf_header(input).finish().map_err(|e| e.code)?;
         let offset_entry_count = TryInto::<usize>::try_into(header.offset_table_size / 8).unwrap();
-        let (_input, offsets) = count(offset_entry, offset_entry_count)(input)
+        let (_input, mut offsets) = count(offset_entry, offset_entry_count)(input)
             .finish()
             .map_err(|e| e.code)?;
+        // The offset table is sorted by row number in game files, but make sure of it, since
+        // lookups use a binary search, and iteration should always be in row order.
+        offsets.sort_by_key(|entry| entry.row_number);
         Ok(Exdf { data, offsets })
     }
 
-    pub fn lookup(&self, row_number: u32) -> Option<Result<RawDataRow, nom::error::Error<&[u8]>>> {
+    pub fn lookup(
+        &self,
+        row_number: u32,
+    ) -> Option<Result<RawDataRow<'_>, nom::error::Error<&[u8]>>> {
         match self
             .offsets
             .binary_search_by_key(&row_number, |entry| entry.row_number)
@@ -61,17 +70,39 @@ impl Exdf {
         }
     }
 
+    /// Iterates over the rows of this page, sorted by row number.
     pub fn iter(&self) -> ExdfIterator<'_> {
         ExdfIterator {
             data: &self.data,
             offsets: self.offsets.iter(),
         }
     }
+
+    /// Iterates over the sub-rows of this page, sorted by row number, then sub-row number, with
+    /// the bytes of each sub-row. See [`raw_sub_rows`](super::raw_sub_rows).
+    pub fn raw_sub_rows<'a>(
+        &'a self,
+        exhf: &'a Exhf,
+    ) -> impl Iterator<Item = Result<(u32, u16, &'a [u8]), nom::error::ErrorKind>> + 'a {
+        self.iter().flat_map(move |res| {
+            let sub_rows = res.map_err(|e| e.code).and_then(|(row_number, row_data)| {
+                Ok(raw_sub_rows(&row_data, exhf)?
+                    .into_iter()
+                    .map(move |(sub_row_number, data)| (row_number, sub_row_number, data)))
+            });
+            match sub_rows {
+                Ok(sub_rows) => {
+                    Box::new(sub_rows.map(Ok)) as Box<dyn Iterator<Item = Result<_, _>> + 'a>
+                }
+                Err(e) => Box::new(core::iter::once(Err(e))),
+            }
+        })
+    }
 }
 
 pub struct ExdfIterator<'a> {
     data: &'a [u8],
-    offsets: std::slice::Iter<'a, OffsetEntry>,
+    offsets: core::slice::Iter<'a, OffsetEntry>,
 }
 
 impl<'a> Iterator for ExdfIterator<'a> {
@@ -141,7 +172,11 @@ mod tests {
     use tomestone_sqpack::{Category, Expansion, GameData};
 
     use super::{exdf_header, Exdf};
-    use crate::{parser::exhf::parse_exhf, Dataset, Language, RootList};
+    use crate::{
+        encoding::encode_exdf_page,
+        parser::{exhf::parse_exhf, parse_row},
+        Dataset, Language, RootList, Row, SubRow, Value,
+    };
 
     #[test]
     fn exdf_game_data() {
@@ -163,6 +198,71 @@ mod tests {
         exdf_header(&exd_data).unwrap();
     }
 
+    #[test]
+    fn raw_sub_rows() {
+        // One u16 column, in a sheet with sub-rows, a single page, and no languages.
+        let mut exh_data = b"EXHF\x00\x03\x00\x02\x00\x01\x00\x01\x00\x01\x00\x00\x00\x02".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 5, 0, 0]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 0, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+
+        let sub_row = |number, value| SubRow {
+            number,
+            cells: vec![Value::U16(value)],
+        };
+        let rows = [
+            Row {
+                number: 6,
+                sub_rows: vec![sub_row(0, 8)],
+            },
+            Row {
+                number: 5,
+                sub_rows: vec![sub_row(0, 6), sub_row(1, 7)],
+            },
+        ];
+        let exdf = Exdf::new(encode_exdf_page("Test", &exhf, &rows)).unwrap();
+        let sub_rows = exdf
+            .raw_sub_rows(&exhf)
+            .map(|res| {
+                let (row, sub_row, data) = res.unwrap();
+                (row, sub_row, data[..2].to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sub_rows,
+            [(5, 0, vec![0, 6]), (5, 1, vec![0, 7]), (6, 0, vec![0, 8])]
+        );
+    }
+
+    #[test]
+    fn typed_cells() {
+        // An i64 column and a u32 column, in a sheet without sub-rows.
+        let mut exh_data = b"EXHF\x00\x03\x00\x0c\x00\x02\x00\x01\x00\x01\x00\x00\x00\x01".to_vec();
+        exh_data.extend_from_slice(&[0; 14]);
+        exh_data.extend_from_slice(&[0, 0xa, 0, 0, 0, 7, 0, 8]);
+        exh_data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+        let exhf = parse_exhf(&exh_data).unwrap().1;
+
+        let rows = [Row {
+            number: 3,
+            sub_rows: vec![SubRow {
+                number: 0,
+                cells: vec![Value::I64(-1 << 40), Value::U32(7)],
+            }],
+        }];
+        let exdf = Exdf::new(encode_exdf_page("Test", &exhf, &rows)).unwrap();
+        let sub_rows = parse_row(exdf.lookup(3).unwrap().unwrap(), &exhf).unwrap();
+        let cells = &sub_rows[0].cells;
+        assert_eq!(cells, &[Value::I64(-1 << 40), Value::U32(7)]);
+        assert_eq!(cells[0].as_i64(), Some(-1 << 40));
+        assert_eq!(cells[0].as_u64(), None);
+        assert_eq!(cells[1].as_u64(), Some(7));
+        assert_eq!(cells[1].as_bytes(), None);
+        assert_eq!(Value::String(b"text").as_bytes(), Some(&b"text"[..]));
+        assert_eq!(Value::Bitflag(true).as_bool(), Some(true));
+    }
+
     #[test]
     #[ignore = "slow test"]
     fn check_exdf_offset_table_order() {
@@ -200,7 +300,7 @@ mod tests {
                     let file = res.unwrap().1;
                     if file.len() > 32 && &file[..4] == b"EXDF" {
                         let header = exdf_header(&file).unwrap().1;
-                        let expected_len =
+                        let expected_len: usize =
                             (32 + header.offset_table_size + header._data_section_size)
                                 .try_into()
                                 .unwrap();
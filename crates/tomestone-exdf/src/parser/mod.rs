@@ -1,102 +1,181 @@
-use std::convert::TryInto;
+use alloc::vec::Vec;
+use core::convert::TryInto;
 
 use nom::{
     combinator::map,
-    number::complete::{be_f32, be_i16, be_i32, be_i8, be_u16, be_u32, be_u8},
+    number::complete::{be_f32, be_i16, be_i32, be_i64, be_i8, be_u16, be_u32, be_u8},
     sequence::tuple,
 };
 
-use crate::{Cardinality, ColumnFormat, RawDataRow, SubRow, Value};
+#[cfg(feature = "bumpalo")]
+use crate::SubRowRef;
+use crate::{Cardinality, ColumnDefinition, ColumnFormat, RawDataRow, SubRow, Value};
 
 use self::exhf::Exhf;
 
 pub mod exdf;
 pub mod exhf;
 
+/// Describes where the sub-rows of a row are found, for a given sheet header.
+struct SubRowLayout {
+    row_size: usize,
+    is_multiple: bool,
+    wrapped_sub_row_length: usize,
+    values_offset: usize,
+}
+
+impl SubRowLayout {
+    fn new(row_data: &RawDataRow, exhf: &Exhf) -> Result<SubRowLayout, nom::error::ErrorKind> {
+        let row_size: usize = exhf.row_size().into();
+        let (is_multiple, wrapped_sub_row_length, values_offset) = match exhf.cardinality() {
+            Cardinality::Single => {
+                if row_data.sub_row_count != 1 {
+                    return Err(nom::error::ErrorKind::Verify);
+                }
+                (false, row_size, 0)
+            }
+            Cardinality::Multiple => (true, row_size + 2, 2),
+        };
+        Ok(SubRowLayout {
+            row_size,
+            is_multiple,
+            wrapped_sub_row_length,
+            values_offset,
+        })
+    }
+
+    /// Returns the sub-row index and the offset of the sub-row's first value.
+    fn sub_row<'a>(
+        &self,
+        row_data: &RawDataRow<'a>,
+        sub_row_counter: u16,
+    ) -> Result<(u16, usize), nom::error::ErrorKind> {
+        let sub_row_start_precursor = usize::from(sub_row_counter) * self.wrapped_sub_row_length;
+        let sub_row_index = if self.is_multiple {
+            be_u16(&row_data.data[sub_row_start_precursor..])
+                .map_err(|_: nom::Err<nom::error::Error<&'a [u8]>>| nom::error::ErrorKind::Eof)?
+                .1
+        } else {
+            0
+        };
+        Ok((sub_row_index, sub_row_start_precursor + self.values_offset))
+    }
+
+    fn cell<'a>(
+        &self,
+        row_data: &RawDataRow<'a>,
+        sub_row_start: usize,
+        column_def: &ColumnDefinition,
+    ) -> Result<Value<'a>, nom::error::ErrorKind> {
+        let offset = sub_row_start + column_def.offset;
+        let input = &row_data.data[offset..];
+        let result = || -> Result<Value<'a>, nom::Err<nom::error::Error<&'a [u8]>>> {
+            Ok(match column_def.format {
+                ColumnFormat::String => {
+                    let value: usize = be_u32(input)?.1.try_into().unwrap();
+                    let row_end = sub_row_start + self.row_size;
+                    let start_offset = row_end + value;
+                    let end_offset = if let Some(null_byte) = row_data.data[start_offset..]
+                        .iter()
+                        .position(|byte| *byte == 0)
+                    {
+                        start_offset + null_byte
+                    } else {
+                        row_data.data.len()
+                    };
+                    Value::String(&row_data.data[start_offset..end_offset])
+                }
+                ColumnFormat::Bool => Value::Bool(map(be_u8, |value| value != 0)(input)?.1),
+                ColumnFormat::I8 => Value::I8(be_i8(input)?.1),
+                ColumnFormat::U8 => Value::U8(be_u8(input)?.1),
+                ColumnFormat::I16 => Value::I16(be_i16(input)?.1),
+                ColumnFormat::U16 => Value::U16(be_u16(input)?.1),
+                ColumnFormat::I32 => Value::I32(be_i32(input)?.1),
+                ColumnFormat::U32 => Value::U32(be_u32(input)?.1),
+                ColumnFormat::Float => Value::Float(be_f32(input)?.1),
+                ColumnFormat::I64 => Value::I64(be_i64(input)?.1),
+                ColumnFormat::I16x4 => Value::I16x4(
+                    map(tuple((be_i16, be_i16, be_i16, be_i16)), |(a, b, c, d)| {
+                        [a, b, c, d]
+                    })(input)?
+                    .1,
+                ),
+                ColumnFormat::Bitflag(bit) => Value::Bitflag((be_u8(input)?.1 >> bit) & 1 != 0),
+            })
+        };
+        result().map_err(|e| match e {
+            nom::Err::Incomplete(_) => unreachable!(),
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.code,
+        })
+    }
+}
+
+/// Splits a row into its sub-rows, without decoding any cells. Each sub-row is returned with its
+/// number, and its bytes, starting with its fixed-size values. The bytes run to the end of the
+/// row, so that string offsets can be resolved relative to the end of the fixed-size values.
+pub fn raw_sub_rows<'a>(
+    row_data: &RawDataRow<'a>,
+    exhf: &Exhf,
+) -> Result<Vec<(u16, &'a [u8])>, nom::error::ErrorKind> {
+    let layout = SubRowLayout::new(row_data, exhf)?;
+    (0..row_data.sub_row_count)
+        .map(|sub_row_counter| {
+            let sub_row_end = (usize::from(sub_row_counter) + 1) * layout.wrapped_sub_row_length;
+            if sub_row_end > row_data.data.len() {
+                return Err(nom::error::ErrorKind::Eof);
+            }
+            let (number, sub_row_start) = layout.sub_row(row_data, sub_row_counter)?;
+            Ok((number, &row_data.data[sub_row_start..]))
+        })
+        .collect()
+}
+
 pub fn parse_row<'a>(
     row_data: RawDataRow<'a>,
     exhf: &Exhf,
 ) -> Result<Vec<SubRow<'a>>, nom::error::ErrorKind> {
-    let row_size: usize = exhf.row_size().try_into().unwrap();
-    let (is_multiple, wrapped_sub_row_length, values_offset) = match exhf.cardinality() {
-        Cardinality::Single => {
-            if row_data.sub_row_count != 1 {
-                return Err(nom::error::ErrorKind::Verify);
-            }
-            (false, row_size, 0)
-        }
-        Cardinality::Multiple => (true, row_size + 2, 2),
-    };
+    let layout = SubRowLayout::new(&row_data, exhf)?;
     (0..row_data.sub_row_count)
         .map(|sub_row_counter| {
-            let sub_row_start_precursor =
-                TryInto::<usize>::try_into(sub_row_counter).unwrap() * wrapped_sub_row_length;
-            let sub_row_index = if is_multiple {
-                be_u16(&row_data.data[sub_row_start_precursor..])
-                    .map_err(|_: nom::Err<nom::error::Error<&'a [u8]>>| nom::error::ErrorKind::Eof)?
-                    .1
-            } else {
-                0
-            };
-            let sub_row_start = sub_row_start_precursor + values_offset;
-            let sub_row = exhf
+            let (number, sub_row_start) = layout.sub_row(&row_data, sub_row_counter)?;
+            let cells = exhf
                 .columns_table_order()
                 .iter()
-                .map(
-                    |column_def| -> Result<Value<'a>, nom::Err<nom::error::Error<&'a [u8]>>> {
-                        let offset = sub_row_start + column_def.offset;
-                        let input = &row_data.data[offset..];
-                        Ok(match column_def.format {
-                            ColumnFormat::String => {
-                                let value: usize = be_u32(input)?.1.try_into().unwrap();
-                                let row_end = sub_row_start + row_size;
-                                let start_offset = row_end + value;
-                                let end_offset = if let Some(null_byte) = row_data.data
-                                    [start_offset..]
-                                    .iter()
-                                    .position(|byte| *byte == 0)
-                                {
-                                    start_offset + null_byte
-                                } else {
-                                    row_data.data.len()
-                                };
-                                Value::String(&row_data.data[start_offset..end_offset])
-                            }
-                            ColumnFormat::Bool => {
-                                Value::Bool(map(be_u8, |value| value != 0)(input)?.1)
-                            }
-                            ColumnFormat::I8 => Value::I8(be_i8(input)?.1),
-                            ColumnFormat::U8 => Value::U8(be_u8(input)?.1),
-                            ColumnFormat::I16 => Value::I16(be_i16(input)?.1),
-                            ColumnFormat::U16 => Value::U16(be_u16(input)?.1),
-                            ColumnFormat::I32 => Value::I32(be_i32(input)?.1),
-                            ColumnFormat::U32 => Value::U32(be_u32(input)?.1),
-                            ColumnFormat::Float => Value::Float(be_f32(input)?.1),
-                            ColumnFormat::I16x4 => Value::I16x4(
-                                map(tuple((be_i16, be_i16, be_i16, be_i16)), |(a, b, c, d)| {
-                                    [a, b, c, d]
-                                })(input)?
-                                .1,
-                            ),
-                            ColumnFormat::Bitflag(bit) => {
-                                Value::Bitflag((be_u8(input)?.1 >> bit) & 1 != 0)
-                            }
-                        })
-                    },
-                )
-                .collect::<Result<Vec<Value<'a>>, nom::Err<nom::error::Error<&'a [u8]>>>>()
-                .map_err(|e| match e {
-                    nom::Err::Incomplete(_) => unreachable!(),
-                    nom::Err::Error(e) | nom::Err::Failure(e) => e.code,
-                })?;
-            Ok(SubRow {
-                number: sub_row_index,
-                cells: sub_row,
-            })
+                .map(|column_def| layout.cell(&row_data, sub_row_start, column_def))
+                .collect::<Result<Vec<Value<'a>>, nom::error::ErrorKind>>()?;
+            Ok(SubRow { number, cells })
         })
         .collect::<Result<Vec<SubRow<'a>>, nom::error::ErrorKind>>()
 }
 
+/// Parses a row like [`parse_row`], but allocates the sub-rows and their cells in `bump`. When
+/// processing many rows, resetting the arena between pages avoids a pair of allocations per
+/// sub-row.
+#[cfg(feature = "bumpalo")]
+pub fn parse_row_in<'a, 'bump>(
+    row_data: RawDataRow<'a>,
+    exhf: &Exhf,
+    bump: &'bump bumpalo::Bump,
+) -> Result<&'bump [SubRowRef<'a, 'bump>], nom::error::ErrorKind> {
+    use bumpalo::collections::Vec as BumpVec;
+
+    let layout = SubRowLayout::new(&row_data, exhf)?;
+    let columns = exhf.columns_table_order();
+    let mut sub_rows = BumpVec::with_capacity_in(row_data.sub_row_count.into(), bump);
+    for sub_row_counter in 0..row_data.sub_row_count {
+        let (number, sub_row_start) = layout.sub_row(&row_data, sub_row_counter)?;
+        let mut cells = BumpVec::with_capacity_in(columns.len(), bump);
+        for column_def in columns.iter() {
+            cells.push(layout.cell(&row_data, sub_row_start, column_def)?);
+        }
+        sub_rows.push(SubRowRef {
+            number,
+            cells: cells.into_bump_slice(),
+        });
+    }
+    Ok(sub_rows.into_bump_slice())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{Language, RootList};
@@ -138,4 +217,37 @@ mod tests {
             }
         }
     }
+
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    #[ignore = "slow test"]
+    fn exdf_game_data_arena() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+
+        let mut bump = bumpalo::Bump::new();
+        let root_list = RootList::open(&game_data, &mut data_file_set).unwrap();
+        for name in root_list.iter() {
+            let dataset =
+                match crate::Dataset::load(&game_data, &mut data_file_set, name, Language::English)
+                {
+                    Ok(dataset) => dataset,
+                    Err(crate::Error::LanguageUnavailable) => continue,
+                    Err(e) => panic!("{}", e),
+                };
+            for (mut page_iter, page_iter_2) in dataset.page_iter().zip(dataset.page_iter()) {
+                for expected in page_iter_2 {
+                    let expected = expected.unwrap();
+                    let actual = page_iter.next_in(&bump).unwrap().unwrap();
+                    assert_eq!(actual.number, expected.number);
+                    assert_eq!(actual.sub_rows.len(), expected.sub_rows.len());
+                    for (actual, expected) in actual.sub_rows.iter().zip(expected.sub_rows.iter()) {
+                        assert_eq!(actual.number, expected.number);
+                        assert_eq!(actual.cells, &expected.cells[..]);
+                    }
+                }
+                assert!(page_iter.next_in(&bump).is_none());
+                bump.reset();
+            }
+        }
+    }
 }
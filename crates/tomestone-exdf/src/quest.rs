@@ -0,0 +1,166 @@
+//! Pairs quest dialogue with the quest scripts that display it.
+//!
+//! Each quest has a dialogue sheet, such as `quest/000/ClsGla001_00001`, with a key column and a
+//! text column. Dialogue keys follow the pattern `TEXT_<QUEST>_<SPEAKER>_<SCENE>_<LINE>`, for
+//! example `TEXT_CLSGLA001_00001_MILITH_000_2`. The quest's compiled Lua script lives at
+//! `game_script/quest/000/ClsGla001_00001.luab`, and plays each scene from a handler named
+//! `OnScene` followed by the zero-padded scene number.
+
+use tomestone_sqpack::{DataFileSet, GameData};
+
+use crate::{Dataset, Error, Language, Value};
+
+/// One line of quest text, with the script handler that shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueLine {
+    /// Quest identifier, as in the dialogue sheet's file name, e.g. `ClsGla001_00001`.
+    pub quest: String,
+    /// Name of the scene handler in the quest script, or `None` for keys that don't follow the
+    /// dialogue pattern, such as journal entries.
+    pub scene: Option<String>,
+    /// Speaker name, in the upper case form used by keys. Some keys use pseudo-speakers, such as
+    /// `SYSTEM` or `Q1`, for other kinds of text.
+    pub speaker: Option<String>,
+    pub line: Option<u32>,
+    pub key: String,
+    /// Raw SeString data of the text.
+    pub text: Vec<u8>,
+}
+
+/// The parts of a dialogue key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueKey<'a> {
+    pub speaker: &'a str,
+    pub scene: u32,
+    pub line: u32,
+}
+
+/// Splits a dialogue key into speaker, scene, and line. Returns `None` if the key does not belong
+/// to the given quest, or does not follow the dialogue pattern.
+pub fn parse_dialogue_key<'a>(quest: &str, key: &'a str) -> Option<DialogueKey<'a>> {
+    let rest = key.strip_prefix("TEXT_")?;
+    // `get` returns `None` if the key has a multi-byte character across the end of the prefix.
+    if rest.len() <= quest.len() || !rest.get(..quest.len())?.eq_ignore_ascii_case(quest) {
+        return None;
+    }
+    let rest = rest[quest.len()..].strip_prefix('_')?;
+    let (rest, line) = rest.rsplit_once('_')?;
+    let (speaker, scene) = rest.rsplit_once('_')?;
+    if speaker.is_empty() {
+        return None;
+    }
+    Some(DialogueKey {
+        speaker,
+        scene: parse_number(scene)?,
+        line: parse_number(line)?,
+    })
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Returns the name of the quest script handler that plays a scene.
+pub fn scene_handler(scene: u32) -> String {
+    format!("OnScene{:05}", scene)
+}
+
+/// Returns the path of the script for a quest dialogue sheet, e.g. `quest/000/ClsGla001_00001`.
+pub fn script_path(sheet: &str) -> String {
+    format!("game_script/{}.luab", sheet)
+}
+
+/// Loads a quest dialogue sheet, and pairs each line with its speaker and scene handler. Lines
+/// are returned in sheet order.
+pub fn align_quest_dialogue(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    sheet: &str,
+    language: Language,
+) -> Result<Vec<DialogueLine>, Error> {
+    let quest = sheet.rsplit('/').next().unwrap_or(sheet);
+    let dataset = Dataset::load(game_data, data_file_set, sheet, language)?;
+    let mut lines = Vec::new();
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            for sub_row in row.sub_rows.iter() {
+                let (key, text) = match sub_row.cells.as_slice() {
+                    [Value::String(key), Value::String(text), ..] => (*key, *text),
+                    _ => return Err(Error::ColumnMismatch(0)),
+                };
+                if key.is_empty() {
+                    continue;
+                }
+                let key = String::from_utf8_lossy(key).into_owned();
+                let parsed = parse_dialogue_key(quest, &key);
+                lines.push(DialogueLine {
+                    quest: quest.to_string(),
+                    scene: parsed.as_ref().map(|parsed| scene_handler(parsed.scene)),
+                    speaker: parsed.as_ref().map(|parsed| parsed.speaker.to_string()),
+                    line: parsed.as_ref().map(|parsed| parsed.line),
+                    text: text.to_vec(),
+                    key,
+                });
+            }
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dialogue_key, scene_handler, script_path, DialogueKey};
+
+    #[test]
+    fn dialogue_keys() {
+        assert_eq!(
+            parse_dialogue_key("ClsGla001_00001", "TEXT_CLSGLA001_00001_MILITH_000_2"),
+            Some(DialogueKey {
+                speaker: "MILITH",
+                scene: 0,
+                line: 2,
+            })
+        );
+        assert_eq!(
+            parse_dialogue_key(
+                "SubFst010_00001",
+                "TEXT_SUBFST010_00001_BOWMAN_SAMPLE_010_35"
+            ),
+            Some(DialogueKey {
+                speaker: "BOWMAN_SAMPLE",
+                scene: 10,
+                line: 35,
+            })
+        );
+        assert_eq!(
+            parse_dialogue_key("ClsGla001_00001", "TEXT_CLSGLA001_00001_TODO_00"),
+            None
+        );
+        assert_eq!(
+            parse_dialogue_key("ClsGla001_00001", "TEXT_CLSGLA002_00002_MILITH_000_2"),
+            None
+        );
+        // Multi-byte characters, including one that straddles the end of the quest name.
+        assert_eq!(
+            parse_dialogue_key("ClsGla001_00001", "TEXT_ClsGla001_0000ü_A_0_1"),
+            None
+        );
+        assert_eq!(
+            parse_dialogue_key("ClsGla001_00001", "TEXT_CLSGLA001_00001_MÜLLER_001_4"),
+            Some(DialogueKey {
+                speaker: "MÜLLER",
+                scene: 1,
+                line: 4,
+            })
+        );
+        assert_eq!(scene_handler(10), "OnScene00010");
+        assert_eq!(
+            script_path("quest/000/ClsGla001_00001"),
+            "game_script/quest/000/ClsGla001_00001.luab"
+        );
+    }
+}
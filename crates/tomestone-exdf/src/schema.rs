@@ -0,0 +1,225 @@
+//! Schemas describe the meaning of each sheet's columns. Sheet headers only record the data type
+//! of each column, so schemas are maintained separately by tools, and attached to sheets by name.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+};
+
+use serde::Serialize;
+
+/// Describes one column of a sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    /// Index of the column, as in [`ColumnDefinition::index`](crate::ColumnDefinition::index).
+    pub index: usize,
+    pub name: Option<String>,
+    /// Sheets that this column refers to, by row number. Some columns may refer to one of
+    /// several sheets, depending on the value of another column.
+    pub links: Vec<String>,
+}
+
+/// A collection of sheet schemas, keyed by sheet name.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    sheets: BTreeMap<String, Vec<ColumnSchema>>,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    /// Adds a sheet with no columns described, if it is not already present.
+    pub fn add_sheet(&mut self, sheet: &str) {
+        self.sheets.entry(sheet.to_string()).or_default();
+    }
+
+    /// Adds a column to a sheet's schema, replacing any existing column with the same index.
+    pub fn add_column(&mut self, sheet: &str, column: ColumnSchema) {
+        let columns = self.sheets.entry(sheet.to_string()).or_default();
+        match columns.binary_search_by_key(&column.index, |existing| existing.index) {
+            Ok(position) => columns[position] = column,
+            Err(position) => columns.insert(position, column),
+        }
+    }
+
+    /// Returns the described columns of a sheet, sorted by index.
+    pub fn columns(&self, sheet: &str) -> Option<&[ColumnSchema]> {
+        self.sheets.get(sheet).map(Vec::as_slice)
+    }
+
+    pub fn sheets(&self) -> impl Iterator<Item = &str> {
+        self.sheets.keys().map(String::as_str)
+    }
+
+    /// Collects every column that links to another sheet into a graph of sheet references.
+    pub fn reference_graph(&self) -> ReferenceGraph {
+        let mut sheets = BTreeSet::new();
+        let mut references = Vec::new();
+        for (sheet, columns) in self.sheets.iter() {
+            sheets.insert(sheet.clone());
+            for column in columns {
+                for target in column.links.iter() {
+                    sheets.insert(target.clone());
+                    references.push(Reference {
+                        sheet: sheet.clone(),
+                        column: column.index,
+                        column_name: column.name.clone(),
+                        target: target.clone(),
+                    });
+                }
+            }
+        }
+        ReferenceGraph { sheets, references }
+    }
+}
+
+/// A column of one sheet that refers to rows of another sheet.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Reference {
+    pub sheet: String,
+    pub column: usize,
+    pub column_name: Option<String>,
+    pub target: String,
+}
+
+/// The graph of references between sheets, as described by a [`Schema`]. Sheets that are only
+/// referenced, and not described in the schema, are included as well.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceGraph {
+    sheets: BTreeSet<String>,
+    references: Vec<Reference>,
+}
+
+impl ReferenceGraph {
+    pub fn sheets(&self) -> impl Iterator<Item = &str> {
+        self.sheets.iter().map(String::as_str)
+    }
+
+    /// Returns all references, sorted by sheet and column.
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// Writes the graph in GraphViz DOT format. Each edge is labeled with the column name, or
+    /// the column index if the column has no name.
+    pub fn write_dot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "digraph sheets {{")?;
+        for sheet in self.sheets.iter() {
+            writeln!(writer, "    {};", quote_dot(sheet))?;
+        }
+        for reference in self.references.iter() {
+            writeln!(
+                writer,
+                "    {} -> {} [label={}];",
+                quote_dot(&reference.sheet),
+                quote_dot(&reference.target),
+                quote_dot(&column_label(reference)),
+            )?;
+        }
+        writeln!(writer, "}}")
+    }
+
+    /// Writes the graph as a JSON object, with a list of sheet names and a list of references.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        serde_json::to_writer(&mut writer, self)?;
+        writeln!(writer)
+    }
+}
+
+fn column_label(reference: &Reference) -> String {
+    match &reference.column_name {
+        Some(name) => name.clone(),
+        None => format!("#{}", reference.column),
+    }
+}
+
+fn quote_dot(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnSchema, Schema};
+
+    fn schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.add_sheet("ClassJob");
+        schema.add_column(
+            "Item",
+            ColumnSchema {
+                index: 15,
+                name: Some("ItemUICategory".to_string()),
+                links: vec!["ItemUICategory".to_string()],
+            },
+        );
+        schema.add_column(
+            "Item",
+            ColumnSchema {
+                index: 0,
+                name: Some("Singular".to_string()),
+                links: Vec::new(),
+            },
+        );
+        schema.add_column(
+            "Recipe",
+            ColumnSchema {
+                index: 4,
+                name: None,
+                links: vec!["Item".to_string(), "Event\"Item".to_string()],
+            },
+        );
+        schema
+    }
+
+    #[test]
+    fn reference_graph() {
+        let schema = schema();
+        assert_eq!(
+            schema
+                .columns("Item")
+                .unwrap()
+                .iter()
+                .map(|column| column.index)
+                .collect::<Vec<_>>(),
+            [0, 15]
+        );
+
+        let graph = schema.reference_graph();
+        assert_eq!(
+            graph.sheets().collect::<Vec<_>>(),
+            [
+                "ClassJob",
+                "Event\"Item",
+                "Item",
+                "ItemUICategory",
+                "Recipe"
+            ]
+        );
+        assert_eq!(graph.references().len(), 3);
+
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        assert_eq!(
+            String::from_utf8(dot).unwrap(),
+            "digraph sheets {\n    \"ClassJob\";\n    \"Event\\\"Item\";\n    \"Item\";\n    \
+            \"ItemUICategory\";\n    \"Recipe\";\n    \
+            \"Item\" -> \"ItemUICategory\" [label=\"ItemUICategory\"];\n    \
+            \"Recipe\" -> \"Item\" [label=\"#4\"];\n    \
+            \"Recipe\" -> \"Event\\\"Item\" [label=\"#4\"];\n}\n"
+        );
+
+        let mut json = Vec::new();
+        graph.write_json(&mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"sheets\":[\"ClassJob\",\"Event\\\"Item\",\"Item\",\"ItemUICategory\",\"Recipe\"],\
+            \"references\":[\
+            {\"sheet\":\"Item\",\"column\":15,\"column_name\":\"ItemUICategory\",\
+            \"target\":\"ItemUICategory\"},\
+            {\"sheet\":\"Recipe\",\"column\":4,\"column_name\":null,\"target\":\"Item\"},\
+            {\"sheet\":\"Recipe\",\"column\":4,\"column_name\":null,\"target\":\"Event\\\"Item\"}]}\n"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Typed views of a few widely used sheets.
+//!
+//! Column indices follow the community-maintained sheet definitions. Each view checks the types
+//! of the columns it reads when it is constructed, so a layout change in a future game version
+//! produces [`Error::ColumnMismatch`] rather than misread data.
+
+use crate::{Error, Row, Value};
+
+/// Conversion from a cell value into a field of a typed row.
+trait FromValue<'r>: Sized {
+    fn from_value(value: &'r Value<'_>) -> Option<Self>;
+}
+
+impl<'r> FromValue<'r> for &'r [u8] {
+    fn from_value(value: &'r Value<'_>) -> Option<Self> {
+        match value {
+            Value::String(data) => Some(data),
+            Value::StringOwned(data) => Some(data.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_value {
+    ($($ty:ty => $($variant:ident)|+),* $(,)?) => {
+        $(
+            impl<'r> FromValue<'r> for $ty {
+                fn from_value(value: &'r Value<'_>) -> Option<Self> {
+                    match value {
+                        $(Value::$variant(value) => Some(*value),)+
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_value!(
+    bool => Bool | Bitflag,
+    i8 => I8,
+    u8 => U8,
+    i16 => I16,
+    u16 => U16,
+    i32 => I32,
+    u32 => U32,
+    f32 => Float,
+);
+
+macro_rules! sheet {
+    (
+        $(#[$meta:meta])*
+        $name:ident$(<$lt:lifetime>)? = $sheet:literal {
+            $($(#[$field_meta:meta])* $field:ident: $ty:ty = $column:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name$(<$lt>)? {
+            pub row: u32,
+            $($(#[$field_meta])* pub $field: $ty,)*
+        }
+
+        impl$(<$lt>)? $name$(<$lt>)? {
+            /// Name of the sheet, for use with [`Dataset::load`](crate::Dataset::load).
+            pub const SHEET: &'static str = $sheet;
+
+            /// Reads the first sub-row of a row of this sheet.
+            pub fn from_row(row: &$($lt)? Row<'_>) -> Result<$name$(<$lt>)?, Error> {
+                let cells = match row.sub_rows.first() {
+                    Some(sub_row) => &sub_row.cells,
+                    None => return Err(Error::ColumnMismatch(0)),
+                };
+                Ok($name {
+                    row: row.number,
+                    $($field: cells
+                        .get($column)
+                        .and_then(FromValue::from_value)
+                        .ok_or(Error::ColumnMismatch($column))?,)*
+                })
+            }
+        }
+    };
+}
+
+sheet! {
+    /// An item, from the `Item` sheet.
+    Item<'r> = "Item" {
+        singular: &'r [u8] = 0,
+        plural: &'r [u8] = 2,
+        description: &'r [u8] = 8,
+        name: &'r [u8] = 9,
+        icon: u16 = 10,
+        /// Row of the `ItemLevel` sheet.
+        level_item: u16 = 11,
+        rarity: u8 = 12,
+        /// Row of the `ItemUICategory` sheet.
+        item_ui_category: u8 = 15,
+    }
+}
+
+sheet! {
+    /// A player or NPC action, from the `Action` sheet.
+    Action<'r> = "Action" {
+        name: &'r [u8] = 0,
+        icon: u16 = 2,
+        /// Row of the `ActionCategory` sheet.
+        action_category: u8 = 3,
+    }
+}
+
+sheet! {
+    /// A status effect, from the `Status` sheet.
+    Status<'r> = "Status" {
+        name: &'r [u8] = 0,
+        description: &'r [u8] = 1,
+        icon: u32 = 2,
+        max_stacks: u8 = 3,
+    }
+}
+
+sheet! {
+    /// A map of an area, from the `Map` sheet.
+    Map<'r> = "Map" {
+        /// Map identifier, used in the paths of map textures.
+        id: &'r [u8] = 6,
+        size_factor: u16 = 7,
+        offset_x: i16 = 8,
+        offset_y: i16 = 9,
+        /// Row of the `PlaceName` sheet.
+        place_name_region: u16 = 10,
+        /// Row of the `PlaceName` sheet.
+        place_name: u16 = 11,
+        /// Row of the `PlaceName` sheet.
+        place_name_sub: u16 = 12,
+    }
+}
+
+sheet! {
+    /// A zone, from the `TerritoryType` sheet.
+    TerritoryType<'r> = "TerritoryType" {
+        name: &'r [u8] = 0,
+        /// Path of the zone's level data, relative to `bg/`.
+        bg: &'r [u8] = 1,
+        /// Row of the `PlaceName` sheet.
+        place_name_region: u16 = 3,
+        /// Row of the `PlaceName` sheet.
+        place_name_zone: u16 = 4,
+        /// Row of the `PlaceName` sheet.
+        place_name: u16 = 5,
+        /// Row of the `Map` sheet.
+        map: u16 = 6,
+    }
+}
+
+sheet! {
+    /// A music track, from the `BGM` sheet.
+    Bgm<'r> = "BGM" {
+        /// Path of the track's sound file.
+        file: &'r [u8] = 0,
+        priority: u8 = 1,
+    }
+}
+
+sheet! {
+    /// A piece of indoor furniture, from the `HousingFurniture` sheet.
+    HousingFurniture = "HousingFurniture" {
+        /// Number used in the paths of the furniture's models.
+        model_key: u16 = 0,
+        /// Row of the `HousingItemCategory` sheet.
+        housing_item_category: u8 = 1,
+        /// Row of the `Item` sheet.
+        item: u32 = 7,
+    }
+}
+
+sheet! {
+    /// A piece of outdoor furniture, from the `HousingYardObject` sheet.
+    HousingYardObject = "HousingYardObject" {
+        /// Number used in the paths of the furniture's models.
+        model_key: u8 = 0,
+        /// Row of the `HousingItemCategory` sheet.
+        housing_item_category: u8 = 1,
+        /// Row of the `Item` sheet.
+        item: u32 = 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_sqpack::GameData;
+
+    use super::{Bgm, Item};
+    use crate::{Dataset, Error, Language, Row, SubRow, Value};
+
+    #[test]
+    fn typed_row() {
+        let row = Row {
+            number: 3,
+            sub_rows: vec![SubRow {
+                number: 0,
+                cells: vec![
+                    Value::String(b"music/ffxiv/BGM_System_Title.scd"),
+                    Value::U8(2),
+                ],
+            }],
+        };
+        assert_eq!(
+            Bgm::from_row(&row).unwrap(),
+            Bgm {
+                row: 3,
+                file: b"music/ffxiv/BGM_System_Title.scd",
+                priority: 2,
+            }
+        );
+        assert!(matches!(
+            Item::from_row(&row),
+            Err(Error::ColumnMismatch(2))
+        ));
+    }
+
+    #[test]
+    fn typed_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let dataset = Dataset::load(
+            &game_data,
+            &mut data_file_set,
+            Item::SHEET,
+            Language::English,
+        )
+        .unwrap();
+        for page in dataset.page_iter() {
+            for res in page {
+                Item::from_row(&res.unwrap()).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Compact snapshots of parsed sheets, which can be reloaded much faster than the original EXH and
+//! EXD files can be read out of the SqPack archives and parsed.
+//!
+//! A snapshot file (conventionally named with a `.tsnap` extension) has the following layout.
+//! All integers are little-endian.
+//!
+//! | Size             | Contents                                                   |
+//! |------------------|------------------------------------------------------------|
+//! | 4                | Magic number, `TSNP`                                       |
+//! | 2                | Format version, currently 1                                |
+//! | 2                | Number of columns                                          |
+//! | 2 × columns      | Column formats, as in [`ColumnFormat::to_u16`]             |
+//! | 4                | Number of sub-rows                                         |
+//! | 6 × sub-rows     | Row number (4 bytes) and sub-row number (2 bytes)          |
+//! | 8 × cells        | One fixed-width cell per column per sub-row                |
+//! | 4                | Length of the string heap                                  |
+//! | variable         | String heap                                                |
+//!
+//! Numeric cells hold the value's bits in the low bytes. String cells hold the offset of the
+//! string in the heap in the low four bytes, and its length in the high four bytes.
+
+use std::io::{self, Read, Write};
+
+use crate::{ColumnFormat, Dataset, Error, Row, SubRow, Value};
+
+const MAGIC: &[u8; 4] = b"TSNP";
+const VERSION: u16 = 1;
+
+/// A sheet's rows, stored in fixed-width columns with a shared string heap.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    formats: Vec<ColumnFormat>,
+    /// Row and sub-row number of each sub-row, in order.
+    sub_rows: Vec<(u32, u16)>,
+    cells: Vec<u64>,
+    strings: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Parses every row of a dataset into a snapshot.
+    pub fn from_dataset(dataset: &Dataset) -> Result<Snapshot, Error> {
+        let formats = dataset
+            .exhf
+            .columns_table_order()
+            .iter()
+            .map(|column| *column.format())
+            .collect();
+        let mut snapshot = Snapshot::new(formats);
+        for page in dataset.page_iter() {
+            for res in page {
+                snapshot.push_row(&res?);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Creates a snapshot from rows that have already been parsed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cells of any row do not match the given column formats.
+    pub fn from_rows<'r, 'a: 'r>(
+        formats: Vec<ColumnFormat>,
+        rows: impl IntoIterator<Item = &'r Row<'a>>,
+    ) -> Snapshot {
+        let mut snapshot = Snapshot::new(formats);
+        for row in rows {
+            snapshot.push_row(row);
+        }
+        snapshot
+    }
+
+    fn new(formats: Vec<ColumnFormat>) -> Snapshot {
+        Snapshot {
+            formats,
+            sub_rows: Vec::new(),
+            cells: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    fn push_row(&mut self, row: &Row) {
+        for sub_row in row.sub_rows.iter() {
+            assert_eq!(
+                sub_row.cells.len(),
+                self.formats.len(),
+                "row {} does not match the column formats",
+                row.number
+            );
+            self.sub_rows.push((row.number, sub_row.number));
+            for (format, value) in self.formats.iter().zip(sub_row.cells.iter()) {
+                let cell = match (format, value) {
+                    (ColumnFormat::String, Value::String(_) | Value::StringOwned(_)) => {
+                        let data = match value {
+                            Value::String(data) => data,
+                            Value::StringOwned(data) => data.as_slice(),
+                            _ => unreachable!(),
+                        };
+                        let offset = u32::try_from(self.strings.len()).unwrap();
+                        let length = u32::try_from(data.len()).unwrap();
+                        self.strings.extend_from_slice(data);
+                        u64::from(offset) | u64::from(length) << 32
+                    }
+                    (ColumnFormat::Bool, Value::Bool(value))
+                    | (ColumnFormat::Bitflag(_), Value::Bitflag(value)) => u64::from(*value),
+                    (ColumnFormat::I8, Value::I8(value)) => u64::from(*value as u8),
+                    (ColumnFormat::U8, Value::U8(value)) => u64::from(*value),
+                    (ColumnFormat::I16, Value::I16(value)) => u64::from(*value as u16),
+                    (ColumnFormat::U16, Value::U16(value)) => u64::from(*value),
+                    (ColumnFormat::I32, Value::I32(value)) => u64::from(*value as u32),
+                    (ColumnFormat::U32, Value::U32(value)) => u64::from(*value),
+                    (ColumnFormat::Float, Value::Float(value)) => u64::from(value.to_bits()),
+                    (ColumnFormat::I64, Value::I64(value)) => *value as u64,
+                    (ColumnFormat::I16x4, Value::I16x4(values)) => values
+                        .iter()
+                        .rev()
+                        .fold(0, |acc, value| acc << 16 | u64::from(*value as u16)),
+                    _ => panic!(
+                        "row {} does not match the column formats, expected {:?} but found {:?}",
+                        row.number, format, value
+                    ),
+                };
+                self.cells.push(cell);
+            }
+        }
+    }
+
+    pub fn formats(&self) -> &[ColumnFormat] {
+        &self.formats
+    }
+
+    /// Returns the total number of sub-rows in the snapshot.
+    pub fn sub_row_count(&self) -> usize {
+        self.sub_rows.len()
+    }
+
+    fn value(&self, format: ColumnFormat, cell: u64) -> Value<'_> {
+        match format {
+            ColumnFormat::String => {
+                let offset = (cell & 0xffff_ffff) as usize;
+                let length = (cell >> 32) as usize;
+                Value::String(&self.strings[offset..offset + length])
+            }
+            ColumnFormat::Bool => Value::Bool(cell != 0),
+            ColumnFormat::I8 => Value::I8(cell as u8 as i8),
+            ColumnFormat::U8 => Value::U8(cell as u8),
+            ColumnFormat::I16 => Value::I16(cell as u16 as i16),
+            ColumnFormat::U16 => Value::U16(cell as u16),
+            ColumnFormat::I32 => Value::I32(cell as u32 as i32),
+            ColumnFormat::U32 => Value::U32(cell as u32),
+            ColumnFormat::Float => Value::Float(f32::from_bits(cell as u32)),
+            ColumnFormat::I64 => Value::I64(cell as i64),
+            ColumnFormat::I16x4 => Value::I16x4([
+                cell as u16 as i16,
+                (cell >> 16) as u16 as i16,
+                (cell >> 32) as u16 as i16,
+                (cell >> 48) as u16 as i16,
+            ]),
+            ColumnFormat::Bitflag(_) => Value::Bitflag(cell != 0),
+        }
+    }
+
+    /// Iterates over the rows of the snapshot, in the order they were added. String cells borrow
+    /// from the snapshot's string heap.
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> {
+        let column_count = self.formats.len();
+        let mut position = 0;
+        std::iter::from_fn(move || {
+            let (number, _) = *self.sub_rows.get(position)?;
+            let mut sub_rows = Vec::new();
+            while let Some((row_number, sub_row_number)) = self.sub_rows.get(position) {
+                if *row_number != number {
+                    break;
+                }
+                let cells = &self.cells[position * column_count..(position + 1) * column_count];
+                sub_rows.push(SubRow {
+                    number: *sub_row_number,
+                    cells: self
+                        .formats
+                        .iter()
+                        .zip(cells.iter())
+                        .map(|(format, cell)| self.value(*format, *cell))
+                        .collect(),
+                });
+                position += 1;
+            }
+            Some(Row { number, sub_rows })
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        let column_count = u16::try_from(self.formats.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many columns"))?;
+        writer.write_all(&column_count.to_le_bytes())?;
+        for format in self.formats.iter() {
+            writer.write_all(&format.to_u16().to_le_bytes())?;
+        }
+
+        let mut buffer = Vec::with_capacity(4 + self.sub_rows.len() * 6 + self.cells.len() * 8);
+        buffer.extend_from_slice(&u32::try_from(self.sub_rows.len()).unwrap().to_le_bytes());
+        for (row_number, sub_row_number) in self.sub_rows.iter() {
+            buffer.extend_from_slice(&row_number.to_le_bytes());
+            buffer.extend_from_slice(&sub_row_number.to_le_bytes());
+        }
+        for cell in self.cells.iter() {
+            buffer.extend_from_slice(&cell.to_le_bytes());
+        }
+        buffer.extend_from_slice(&u32::try_from(self.strings.len()).unwrap().to_le_bytes());
+        writer.write_all(&buffer)?;
+        writer.write_all(&self.strings)
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Snapshot> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a sheet snapshot"));
+        }
+        if u16::from_le_bytes([header[4], header[5]]) != VERSION {
+            return Err(invalid("unsupported snapshot version"));
+        }
+        let column_count = usize::from(u16::from_le_bytes([header[6], header[7]]));
+
+        let data = read_vec(&mut reader, column_count * 2)?;
+        let formats = data
+            .chunks_exact(2)
+            .map(|chunk| ColumnFormat::from_u16(u16::from_le_bytes([chunk[0], chunk[1]])))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid("unknown column format"))?;
+
+        let sub_row_count = usize::try_from(read_u32(&mut reader)?).unwrap();
+        let data = read_vec(&mut reader, sub_row_count * 6)?;
+        let sub_rows = data
+            .chunks_exact(6)
+            .map(|chunk| {
+                (
+                    u32::from_le_bytes(chunk[..4].try_into().unwrap()),
+                    u16::from_le_bytes(chunk[4..].try_into().unwrap()),
+                )
+            })
+            .collect();
+
+        let data = read_vec(&mut reader, sub_row_count * column_count * 8)?;
+        let cells = data
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        let strings_length = usize::try_from(read_u32(&mut reader)?).unwrap();
+        let strings = read_vec(&mut reader, strings_length)?;
+
+        let snapshot = Snapshot {
+            formats,
+            sub_rows,
+            cells,
+            strings,
+        };
+        for (i, cell) in snapshot.cells.iter().enumerate() {
+            if let ColumnFormat::String = snapshot.formats[i % column_count] {
+                let end = (cell & 0xffff_ffff) + (cell >> 32);
+                if end > snapshot.strings.len() as u64 {
+                    return Err(invalid("string is out of bounds"));
+                }
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_vec<R: Read>(reader: &mut R, length: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader.take(length as u64).read_to_end(&mut buffer)?;
+    if buffer.len() != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_sqpack::GameData;
+
+    use super::Snapshot;
+    use crate::{diff::SheetDiff, ColumnFormat, Dataset, Language, Row, SubRow, Value};
+
+    #[test]
+    fn round_trip() {
+        let formats = vec![
+            ColumnFormat::String,
+            ColumnFormat::I8,
+            ColumnFormat::U32,
+            ColumnFormat::Float,
+            ColumnFormat::I16x4,
+            ColumnFormat::Bitflag(3),
+        ];
+        let rows = vec![
+            Row {
+                number: 1,
+                sub_rows: vec![SubRow {
+                    number: 0,
+                    cells: vec![
+                        Value::String(b"Potion"),
+                        Value::I8(-5),
+                        Value::U32(0xdeadbeef),
+                        Value::Float(-1.25),
+                        Value::I16x4([-1, 2, -3, 4]),
+                        Value::Bitflag(true),
+                    ],
+                }],
+            },
+            Row {
+                number: 7,
+                sub_rows: (0..2)
+                    .map(|number| SubRow {
+                        number,
+                        cells: vec![
+                            Value::StringOwned(format!("Sub-row {}", number).into_bytes()),
+                            Value::I8(0),
+                            Value::U32(7),
+                            Value::Float(0.5),
+                            Value::I16x4([0; 4]),
+                            Value::Bitflag(false),
+                        ],
+                    })
+                    .collect(),
+            },
+        ];
+
+        let snapshot = Snapshot::from_rows(formats, &rows);
+        assert_eq!(snapshot.sub_row_count(), 3);
+        let mut buffer = Vec::new();
+        snapshot.write(&mut buffer).unwrap();
+        let snapshot = Snapshot::read(buffer.as_slice()).unwrap();
+        let reloaded = snapshot.rows().collect::<Vec<_>>();
+        assert!(SheetDiff::compare(&rows, &reloaded).is_empty());
+
+        assert!(Snapshot::read(&buffer[..buffer.len() - 1]).is_err());
+        buffer[0] = b'X';
+        assert!(Snapshot::read(buffer.as_slice()).is_err());
+    }
+
+    #[test]
+    #[ignore = "slow test"]
+    fn snapshot_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let dataset =
+            Dataset::load(&game_data, &mut data_file_set, "Item", Language::English).unwrap();
+        let rows = dataset
+            .page_iter()
+            .flatten()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        Snapshot::from_dataset(&dataset)
+            .unwrap()
+            .write(&mut buffer)
+            .unwrap();
+        let snapshot = Snapshot::read(buffer.as_slice()).unwrap();
+        let reloaded = snapshot.rows().collect::<Vec<_>>();
+        assert!(SheetDiff::compare(&rows, &reloaded).is_empty());
+    }
+}
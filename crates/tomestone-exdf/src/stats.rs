@@ -0,0 +1,277 @@
+//! Per-column statistics for a sheet, and heuristics that flag columns whose contents changed
+//! suspiciously between two versions of a sheet. After a game update, a column that was
+//! inserted or moved shows up as a run of neighboring columns with changed types or values.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use crate::{ColumnFormat, Dataset, Error, Row, Value};
+
+/// Statistics summarizing one column.
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    pub format: ColumnFormat,
+    /// Smallest value of a numeric column, or `None` for strings, packed vectors, and empty
+    /// sheets.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Number of distinct values.
+    pub distinct: usize,
+}
+
+/// Statistics summarizing every column of a sheet.
+#[derive(Debug, Clone)]
+pub struct SheetStats {
+    columns: Vec<ColumnStats>,
+    sub_rows: usize,
+}
+
+/// A difference between two versions of a sheet that suggests a column's meaning changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anomaly {
+    ColumnCountChanged {
+        old: usize,
+        new: usize,
+    },
+    FormatChanged {
+        column: usize,
+        old: u16,
+        new: u16,
+    },
+    /// The column's values in the two versions do not overlap at all.
+    RangeChanged {
+        column: usize,
+        old: (f64, f64),
+        new: (f64, f64),
+    },
+    /// The number of distinct values changed by at least a factor of four, or the column became
+    /// constant, or stopped being constant.
+    CardinalityChanged {
+        column: usize,
+        old: usize,
+        new: usize,
+    },
+}
+
+struct Accumulator {
+    format: ColumnFormat,
+    min: Option<f64>,
+    max: Option<f64>,
+    hashes: HashSet<u64>,
+}
+
+impl SheetStats {
+    pub fn from_dataset(dataset: &Dataset) -> Result<SheetStats, Error> {
+        let formats = dataset
+            .exhf
+            .columns_table_order()
+            .iter()
+            .map(|column| *column.format())
+            .collect::<Vec<_>>();
+        let mut rows = Vec::new();
+        for page in dataset.page_iter() {
+            for res in page {
+                rows.push(res?);
+            }
+        }
+        Ok(SheetStats::from_rows(&formats, &rows))
+    }
+
+    pub fn from_rows(formats: &[ColumnFormat], rows: &[Row]) -> SheetStats {
+        let mut accumulators = formats
+            .iter()
+            .map(|format| Accumulator {
+                format: *format,
+                min: None,
+                max: None,
+                hashes: HashSet::new(),
+            })
+            .collect::<Vec<_>>();
+        let mut sub_rows = 0;
+        for sub_row in rows.iter().flat_map(|row| row.sub_rows.iter()) {
+            sub_rows += 1;
+            for (accumulator, value) in accumulators.iter_mut().zip(sub_row.cells.iter()) {
+                let mut hasher = DefaultHasher::new();
+                let number = match value {
+                    Value::String(data) => {
+                        data.hash(&mut hasher);
+                        None
+                    }
+                    Value::StringOwned(data) => {
+                        data.as_slice().hash(&mut hasher);
+                        None
+                    }
+                    Value::I16x4(values) => {
+                        values.hash(&mut hasher);
+                        None
+                    }
+                    Value::Float(value) => {
+                        value.to_bits().hash(&mut hasher);
+                        Some(f64::from(*value)).filter(|value| value.is_finite())
+                    }
+                    Value::Bool(value) | Value::Bitflag(value) => Some(f64::from(u8::from(*value))),
+                    Value::I8(value) => Some(f64::from(*value)),
+                    Value::U8(value) => Some(f64::from(*value)),
+                    Value::I16(value) => Some(f64::from(*value)),
+                    Value::U16(value) => Some(f64::from(*value)),
+                    Value::I32(value) => Some(f64::from(*value)),
+                    Value::U32(value) => Some(f64::from(*value)),
+                    Value::I64(value) => Some(*value as f64),
+                };
+                if let Some(number) = number {
+                    number.to_bits().hash(&mut hasher);
+                    accumulator.min = Some(accumulator.min.map_or(number, |min| min.min(number)));
+                    accumulator.max = Some(accumulator.max.map_or(number, |max| max.max(number)));
+                }
+                accumulator.hashes.insert(hasher.finish());
+            }
+        }
+        SheetStats {
+            columns: accumulators
+                .into_iter()
+                .map(|accumulator| ColumnStats {
+                    format: accumulator.format,
+                    min: accumulator.min,
+                    max: accumulator.max,
+                    distinct: accumulator.hashes.len(),
+                })
+                .collect(),
+            sub_rows,
+        }
+    }
+
+    pub fn columns(&self) -> &[ColumnStats] {
+        &self.columns
+    }
+
+    pub fn sub_row_count(&self) -> usize {
+        self.sub_rows
+    }
+
+    /// Compares these statistics, from an older version of a sheet, against statistics from a
+    /// newer version. Columns are matched by index.
+    pub fn anomalies(&self, newer: &SheetStats) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        if self.columns.len() != newer.columns.len() {
+            anomalies.push(Anomaly::ColumnCountChanged {
+                old: self.columns.len(),
+                new: newer.columns.len(),
+            });
+        }
+        for (column, (old, new)) in self.columns.iter().zip(newer.columns.iter()).enumerate() {
+            if old.format.to_u16() != new.format.to_u16() {
+                anomalies.push(Anomaly::FormatChanged {
+                    column,
+                    old: old.format.to_u16(),
+                    new: new.format.to_u16(),
+                });
+                continue;
+            }
+            if let (Some(old_min), Some(old_max), Some(new_min), Some(new_max)) =
+                (old.min, old.max, new.min, new.max)
+            {
+                if new_min > old_max || new_max < old_min {
+                    anomalies.push(Anomaly::RangeChanged {
+                        column,
+                        old: (old_min, old_max),
+                        new: (new_min, new_max),
+                    });
+                    continue;
+                }
+            }
+            let (smaller, larger) = if old.distinct < new.distinct {
+                (old.distinct, new.distinct)
+            } else {
+                (new.distinct, old.distinct)
+            };
+            let became_constant = smaller == 1 && larger > 1;
+            if became_constant || (smaller > 0 && larger >= smaller * 4 && larger - smaller >= 16) {
+                anomalies.push(Anomaly::CardinalityChanged {
+                    column,
+                    old: old.distinct,
+                    new: new.distinct,
+                });
+            }
+        }
+        anomalies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anomaly, SheetStats};
+    use crate::{ColumnFormat, Row, SubRow, Value};
+
+    fn rows(cells: impl Fn(u32) -> Vec<Value<'static>>) -> Vec<Row<'static>> {
+        (0..100)
+            .map(|number| Row {
+                number,
+                sub_rows: vec![SubRow {
+                    number: 0,
+                    cells: cells(number),
+                }],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn anomalies() {
+        let formats = [
+            ColumnFormat::String,
+            ColumnFormat::U16,
+            ColumnFormat::U8,
+            ColumnFormat::Bool,
+        ];
+        let old = rows(|number| {
+            vec![
+                Value::String(b"Potion"),
+                Value::U16(number as u16),
+                Value::U8(number as u8 % 4),
+                Value::Bool(number % 2 == 0),
+            ]
+        });
+        let new = rows(|number| {
+            vec![
+                Value::String(if number == 0 { b"Ether" } else { b"Potion" }),
+                Value::U16(number as u16 + 50),
+                Value::U8(200),
+                Value::Bool(number % 3 == 0),
+            ]
+        });
+
+        let old_stats = SheetStats::from_rows(&formats, &old);
+        assert_eq!(old_stats.sub_row_count(), 100);
+        let columns = old_stats.columns();
+        assert_eq!(columns[0].distinct, 1);
+        assert_eq!(columns[0].min, None);
+        assert_eq!((columns[1].min, columns[1].max), (Some(0.0), Some(99.0)));
+        assert_eq!(columns[1].distinct, 100);
+        assert_eq!(columns[2].distinct, 4);
+
+        let new_stats = SheetStats::from_rows(&formats, &new);
+        assert!(old_stats.anomalies(&old_stats).is_empty());
+        assert_eq!(
+            old_stats.anomalies(&new_stats),
+            [
+                Anomaly::CardinalityChanged {
+                    column: 0,
+                    old: 1,
+                    new: 2
+                },
+                Anomaly::RangeChanged {
+                    column: 2,
+                    old: (0.0, 3.0),
+                    new: (200.0, 200.0)
+                },
+            ]
+        );
+
+        let fewer_formats = &formats[..3];
+        assert_eq!(
+            SheetStats::from_rows(fewer_formats, &old).anomalies(&old_stats),
+            [Anomaly::ColumnCountChanged { old: 3, new: 4 }]
+        );
+    }
+}
@@ -0,0 +1,608 @@
+//! Resolves the files that make up a player character's model.
+//!
+//! Character models are assembled from separate parts: the body, face, hair, and tail from
+//! `chara/human/`, and one model per equipment slot from `chara/equipment/` or
+//! `chara/accessory/`. Paths are keyed by a four digit race code, such as `c0101` for male
+//! Midlanders.
+//!
+//! Not every race has its own model for every piece of equipment. The equipment deformer
+//! parameter (EQDP) files record which races have one, and the rest borrow the model of another
+//! race. Each piece of equipment also has an IMC file, listing the material variant and hidden
+//! attributes for each of its variants.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use nom::{
+    multi::count,
+    number::complete::{le_u16, le_u8},
+    sequence::tuple,
+    Finish, IResult,
+};
+use tomestone_sqpack::{DataFileSet, GameData};
+
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Race {
+    Midlander,
+    Highlander,
+    Elezen,
+    Miqote,
+    Roegadyn,
+    Lalafell,
+    AuRa,
+    Hrothgar,
+    Viera,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gender {
+    Male,
+    Female,
+}
+
+/// Returns the race code used in model paths, e.g. 101 for `c0101`.
+pub fn race_code(race: Race, gender: Gender) -> u16 {
+    let index = race as u16 * 2 + gender as u16;
+    (index + 1) * 100 + 1
+}
+
+/// Returns the race whose equipment models are borrowed when a race has no model of its own.
+pub fn fallback_race(race_code: u16) -> Option<u16> {
+    match race_code {
+        101 | 1101 => None,
+        201 => Some(101),
+        1201 => Some(1101),
+        code if code / 100 % 2 == 0 => Some(201),
+        _ => Some(101),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipSlot {
+    Head,
+    Body,
+    Hands,
+    Legs,
+    Feet,
+    Ears,
+    Neck,
+    Wrists,
+    RightRing,
+    LeftRing,
+}
+
+impl EquipSlot {
+    /// Suffix of the slot's model file names.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            EquipSlot::Head => "met",
+            EquipSlot::Body => "top",
+            EquipSlot::Hands => "glv",
+            EquipSlot::Legs => "dwn",
+            EquipSlot::Feet => "sho",
+            EquipSlot::Ears => "ear",
+            EquipSlot::Neck => "nek",
+            EquipSlot::Wrists => "wrs",
+            EquipSlot::RightRing => "rir",
+            EquipSlot::LeftRing => "ril",
+        }
+    }
+
+    pub fn is_accessory(&self) -> bool {
+        matches!(
+            self,
+            EquipSlot::Ears
+                | EquipSlot::Neck
+                | EquipSlot::Wrists
+                | EquipSlot::RightRing
+                | EquipSlot::LeftRing
+        )
+    }
+
+    /// Position of the slot within IMC variants and EQDP entries.
+    fn index(&self) -> usize {
+        match self {
+            EquipSlot::Head | EquipSlot::Ears => 0,
+            EquipSlot::Body | EquipSlot::Neck => 1,
+            EquipSlot::Hands | EquipSlot::Wrists => 2,
+            EquipSlot::Legs | EquipSlot::RightRing => 3,
+            EquipSlot::Feet | EquipSlot::LeftRing => 4,
+        }
+    }
+
+    fn folder(&self, set: u16) -> String {
+        if self.is_accessory() {
+            format!("chara/accessory/a{:04}", set)
+        } else {
+            format!("chara/equipment/e{:04}", set)
+        }
+    }
+
+    fn prefix(&self) -> char {
+        if self.is_accessory() {
+            'a'
+        } else {
+            'e'
+        }
+    }
+
+    pub fn model_path(&self, set: u16, race_code: u16) -> String {
+        format!(
+            "{}/model/c{:04}{}{:04}_{}.mdl",
+            self.folder(set),
+            race_code,
+            self.prefix(),
+            set,
+            self.suffix()
+        )
+    }
+
+    /// Path of a material. The material's letter, usually `a`, comes from the material names
+    /// listed in the model.
+    pub fn material_path(&self, set: u16, race_code: u16, material_id: u8, letter: &str) -> String {
+        format!(
+            "{}/material/v{:04}/mt_c{:04}{}{:04}_{}_{}.mtrl",
+            self.folder(set),
+            material_id,
+            race_code,
+            self.prefix(),
+            set,
+            self.suffix(),
+            letter
+        )
+    }
+
+    pub fn imc_path(&self, set: u16) -> String {
+        format!("{}/{}{:04}.imc", self.folder(set), self.prefix(), set)
+    }
+
+    pub fn eqdp_path(&self, race_code: u16) -> String {
+        let kind = if self.is_accessory() {
+            "accessory"
+        } else {
+            "equipment"
+        };
+        format!(
+            "chara/xls/charadb/{}deformerparameter/c{:04}.eqdp",
+            kind, race_code
+        )
+    }
+}
+
+/// One slot of one variant in an IMC file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImcEntry {
+    /// Selects the material folder, `v0001` and so on.
+    pub material_id: u8,
+    pub decal_id: u8,
+    /// Ten bits, one per optional mesh attribute, set for attributes that are shown.
+    pub attribute_mask: u16,
+    pub sound_id: u8,
+    pub vfx_id: u8,
+    pub material_animation_id: u8,
+}
+
+fn imc_entry(input: &[u8]) -> IResult<&[u8], ImcEntry> {
+    let (input, (material_id, decal_id, attribute_and_sound, vfx_id, material_animation_id)) =
+        tuple((le_u8, le_u8, le_u16, le_u8, le_u8))(input)?;
+    Ok((
+        input,
+        ImcEntry {
+            material_id,
+            decal_id,
+            attribute_mask: attribute_and_sound & 0x3ff,
+            sound_id: (attribute_and_sound >> 10) as u8,
+            vfx_id,
+            material_animation_id,
+        },
+    ))
+}
+
+fn imc_header(input: &[u8]) -> IResult<&[u8], (u16, u16)> {
+    tuple((le_u16, le_u16))(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImcFile {
+    part_mask: u16,
+    /// The default variant, followed by each numbered variant, with one entry per part.
+    entries: Vec<ImcEntry>,
+}
+
+impl ImcFile {
+    pub fn parse(data: &[u8]) -> Result<ImcFile, Error> {
+        let (rest, (variant_count, part_mask)) = imc_header(data).finish()?;
+        let parts = part_mask.count_ones() as usize;
+        let total = (usize::from(variant_count) + 1) * parts;
+        let (_, entries) = count(imc_entry, total)(rest).finish()?;
+        Ok(ImcFile { part_mask, entries })
+    }
+
+    pub fn variant_count(&self) -> usize {
+        let parts = self.part_mask.count_ones() as usize;
+        (self.entries.len() / parts.max(1)).saturating_sub(1)
+    }
+
+    /// Returns the entry for a slot in a variant. Variant zero is the default.
+    pub fn entry(&self, slot: EquipSlot, variant: u8) -> Option<&ImcEntry> {
+        let bit = 1u16 << slot.index();
+        if self.part_mask & bit == 0 {
+            return None;
+        }
+        let parts = self.part_mask.count_ones() as usize;
+        let part = (self.part_mask & (bit - 1)).count_ones() as usize;
+        self.entries.get(usize::from(variant) * parts + part)
+    }
+}
+
+fn eqdp_header(input: &[u8]) -> IResult<&[u8], (u16, u16, u16)> {
+    tuple((le_u16, le_u16, le_u16))(input)
+}
+
+fn u16_array(input: &[u8], length: usize) -> IResult<&[u8], Vec<u16>> {
+    count(le_u16, length)(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EqdpFile {
+    block_size: u16,
+    block_offsets: Vec<u16>,
+    data: Vec<u16>,
+}
+
+impl EqdpFile {
+    pub fn parse(data: &[u8]) -> Result<EqdpFile, Error> {
+        let (rest, (_identifier, block_size, block_count)) = eqdp_header(data).finish()?;
+        let (rest, block_offsets) = u16_array(rest, usize::from(block_count)).finish()?;
+        let (_, data) = u16_array(rest, rest.len() / 2).finish()?;
+        Ok(EqdpFile {
+            block_size,
+            block_offsets,
+            data,
+        })
+    }
+
+    fn entry(&self, set: u16) -> u16 {
+        if self.block_size == 0 {
+            return 0;
+        }
+        let block = usize::from(set / self.block_size);
+        match self.block_offsets.get(block) {
+            Some(0xffff) | None => 0,
+            Some(offset) => self
+                .data
+                .get(usize::from(*offset) + usize::from(set % self.block_size))
+                .copied()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Returns true if the race has its own model for this slot and set.
+    pub fn has_model(&self, slot: EquipSlot, set: u16) -> bool {
+        self.entry(set) >> (slot.index() * 2 + 1) & 1 != 0
+    }
+
+    /// Returns true if the race has its own material for this slot and set.
+    pub fn has_material(&self, slot: EquipSlot, set: u16) -> bool {
+        self.entry(set) >> (slot.index() * 2) & 1 != 0
+    }
+}
+
+/// Follows the fallback chain from a race until one has its own model, using `eqdp` to look up
+/// each race's EQDP file. If no race in the chain has a model, the last race is returned.
+pub fn resolve_model_race<'a>(
+    race_code: u16,
+    slot: EquipSlot,
+    set: u16,
+    mut eqdp: impl FnMut(u16) -> Result<&'a EqdpFile, Error>,
+) -> Result<u16, Error> {
+    let mut race = race_code;
+    loop {
+        if eqdp(race)?.has_model(slot, set) {
+            return Ok(race);
+        }
+        match fallback_race(race) {
+            Some(next) => race = next,
+            None => return Ok(race),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartKind {
+    Body,
+    Face,
+    Hair,
+    Tail,
+    Equipment(EquipSlot),
+}
+
+/// One model making up a character, with the information needed to find its materials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelPart {
+    pub kind: PartKind,
+    pub model_path: String,
+    /// Race code of the model, which differs from the character's when a model is borrowed.
+    pub model_race: u16,
+    /// Variant settings of a piece of equipment.
+    pub imc: Option<ImcEntry>,
+}
+
+impl ModelPart {
+    /// Path of one of this part's materials, for equipment. The letter comes from the material
+    /// names listed in the model, and is usually `a`.
+    pub fn material_path(&self, race_code: u16, letter: &str) -> Option<String> {
+        match (self.kind, self.imc, self.equipment_set()) {
+            (PartKind::Equipment(slot), Some(imc), Some(set)) => {
+                Some(slot.material_path(set, race_code, imc.material_id, letter))
+            }
+            _ => None,
+        }
+    }
+
+    fn equipment_set(&self) -> Option<u16> {
+        let file_name = self.model_path.rsplit('/').next()?;
+        file_name.get(6..10)?.parse().ok()
+    }
+}
+
+/// The resolved parts of a character, ready to be loaded and combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharacterModel {
+    pub race_code: u16,
+    pub parts: Vec<ModelPart>,
+}
+
+/// Collects a character's appearance and equipment, and resolves the files to load.
+#[derive(Debug, Clone)]
+pub struct CharacterBuilder {
+    race_code: u16,
+    body: u16,
+    face: u16,
+    hair: u16,
+    tail: Option<u16>,
+    equipment: Vec<(EquipSlot, u16, u8)>,
+}
+
+impl CharacterBuilder {
+    pub fn new(race: Race, gender: Gender) -> CharacterBuilder {
+        CharacterBuilder {
+            race_code: race_code(race, gender),
+            body: 1,
+            face: 1,
+            hair: 1,
+            tail: None,
+            equipment: Vec::new(),
+        }
+    }
+
+    pub fn body(mut self, body: u16) -> CharacterBuilder {
+        self.body = body;
+        self
+    }
+
+    pub fn face(mut self, face: u16) -> CharacterBuilder {
+        self.face = face;
+        self
+    }
+
+    pub fn hair(mut self, hair: u16) -> CharacterBuilder {
+        self.hair = hair;
+        self
+    }
+
+    /// Sets the tail model, for races with tails.
+    pub fn tail(mut self, tail: u16) -> CharacterBuilder {
+        self.tail = Some(tail);
+        self
+    }
+
+    /// Equips a piece of equipment, given its model set and variant, as found in the item's
+    /// model column. Replaces anything already in the slot.
+    pub fn equip(mut self, slot: EquipSlot, set: u16, variant: u8) -> CharacterBuilder {
+        self.equipment.retain(|(existing, _, _)| *existing != slot);
+        self.equipment.push((slot, set, variant));
+        self
+    }
+
+    /// Returns the parts not needing any game files: the body, face, hair, and tail. The body is
+    /// left out if body equipment is worn, since the equipment model includes the skin.
+    fn human_parts(&self) -> Vec<ModelPart> {
+        let race = self.race_code;
+        let human = |kind, folder: &str, prefix: char, id: u16, suffix: &str| {
+            ModelPart {
+            kind,
+            model_path: format!(
+                "chara/human/c{race:04}/obj/{folder}/{prefix}{id:04}/model/c{race:04}{prefix}{id:04}_{suffix}.mdl",
+            ),
+            model_race: race,
+            imc: None,
+        }
+        };
+        let mut parts = Vec::new();
+        if !self
+            .equipment
+            .iter()
+            .any(|(slot, _, _)| *slot == EquipSlot::Body)
+        {
+            parts.push(human(PartKind::Body, "body", 'b', self.body, "top"));
+        }
+        parts.push(human(PartKind::Face, "face", 'f', self.face, "fac"));
+        parts.push(human(PartKind::Hair, "hair", 'h', self.hair, "hir"));
+        if let Some(tail) = self.tail {
+            parts.push(human(PartKind::Tail, "tail", 't', tail, "til"));
+        }
+        parts
+    }
+
+    /// Resolves every part, reading EQDP and IMC files to pick models and material variants.
+    pub fn build(
+        &self,
+        game_data: &GameData,
+        data_file_set: &mut DataFileSet,
+    ) -> Result<CharacterModel, Error> {
+        let mut load = |path: String| -> Result<Vec<u8>, Error> {
+            game_data
+                .lookup_path_data(data_file_set, &path)?
+                .ok_or(Error::NoSuchFile(path))
+        };
+
+        let mut eqdp_files = HashMap::new();
+        let mut parts = self.human_parts();
+        for (slot, set, variant) in self.equipment.iter() {
+            // Load the EQDP file of every race in the fallback chain up front.
+            let mut chain = Some(self.race_code);
+            while let Some(race) = chain {
+                let path = slot.eqdp_path(race);
+                if let Entry::Vacant(entry) = eqdp_files.entry(path) {
+                    let eqdp = EqdpFile::parse(&load(entry.key().clone())?)?;
+                    entry.insert(eqdp);
+                }
+                chain = fallback_race(race);
+            }
+            let race = resolve_model_race(self.race_code, *slot, *set, |race| {
+                let path = slot.eqdp_path(race);
+                eqdp_files.get(&path).ok_or(Error::NoSuchFile(path))
+            })?;
+            let imc = ImcFile::parse(&load(slot.imc_path(*set))?)?;
+            parts.push(ModelPart {
+                kind: PartKind::Equipment(*slot),
+                model_path: slot.model_path(*set, race),
+                model_race: race,
+                imc: imc.entry(*slot, *variant).copied(),
+            });
+        }
+        Ok(CharacterModel {
+            race_code: self.race_code,
+            parts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_sqpack::GameData;
+
+    use super::{
+        race_code, resolve_model_race, CharacterBuilder, EqdpFile, EquipSlot, Gender, ImcEntry,
+        ImcFile, PartKind, Race,
+    };
+
+    #[test]
+    fn paths() {
+        assert_eq!(race_code(Race::Midlander, Gender::Male), 101);
+        assert_eq!(race_code(Race::Viera, Gender::Female), 1801);
+        assert_eq!(
+            EquipSlot::Body.model_path(6016, 201),
+            "chara/equipment/e6016/model/c0201e6016_top.mdl"
+        );
+        assert_eq!(
+            EquipSlot::RightRing.model_path(53, 101),
+            "chara/accessory/a0053/model/c0101a0053_rir.mdl"
+        );
+        assert_eq!(
+            EquipSlot::Hands.material_path(6016, 201, 3, "a"),
+            "chara/equipment/e6016/material/v0003/mt_c0201e6016_glv_a.mtrl"
+        );
+        assert_eq!(
+            EquipSlot::Neck.imc_path(12),
+            "chara/accessory/a0012/a0012.imc"
+        );
+        assert_eq!(
+            EquipSlot::Feet.eqdp_path(1401),
+            "chara/xls/charadb/equipmentdeformerparameter/c1401.eqdp"
+        );
+    }
+
+    #[test]
+    fn imc() {
+        // Two variants of a set with all five parts.
+        let mut data = vec![2, 0, 0x1f, 0];
+        for variant in 0..3u8 {
+            for part in 0..5u8 {
+                let attributes_and_sound: u16 = (u16::from(part) << 10) | 0x3;
+                data.push(variant + 1);
+                data.push(0);
+                data.extend_from_slice(&attributes_and_sound.to_le_bytes());
+                data.extend_from_slice(&[0, 0]);
+            }
+        }
+        let imc = ImcFile::parse(&data).unwrap();
+        assert_eq!(imc.variant_count(), 2);
+        assert_eq!(
+            imc.entry(EquipSlot::Legs, 2),
+            Some(&ImcEntry {
+                material_id: 3,
+                decal_id: 0,
+                attribute_mask: 3,
+                sound_id: 3,
+                vfx_id: 0,
+                material_animation_id: 0,
+            })
+        );
+        assert_eq!(imc.entry(EquipSlot::Body, 3), None);
+    }
+
+    #[test]
+    fn eqdp() {
+        // Blocks of 100 sets. The second block is empty.
+        let mut data = Vec::new();
+        for value in [0u16, 100, 3, 0, 0xffff, 100] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut entries = [0u16; 200];
+        // Set 5 has a model and material for the body only.
+        entries[5] = 0b11 << 2;
+        // Set 201 has a model for everything.
+        entries[101] = 0x3ff;
+        for entry in entries.iter() {
+            data.extend_from_slice(&entry.to_le_bytes());
+        }
+        let eqdp = EqdpFile::parse(&data).unwrap();
+        assert!(eqdp.has_model(EquipSlot::Body, 5));
+        assert!(eqdp.has_material(EquipSlot::Body, 5));
+        assert!(!eqdp.has_model(EquipSlot::Head, 5));
+        assert!(!eqdp.has_model(EquipSlot::Body, 150));
+        assert!(eqdp.has_model(EquipSlot::Feet, 201));
+        assert!(!eqdp.has_model(EquipSlot::Feet, 1000));
+
+        let empty = EqdpFile::parse(&[0, 0, 100, 0, 0, 0]).unwrap();
+        let lookup = |race| Ok(if race == 201 { &empty } else { &eqdp });
+        assert_eq!(
+            resolve_model_race(1401, EquipSlot::Body, 5, lookup).unwrap(),
+            1401
+        );
+        let lookup = |race| Ok(if race == 101 { &eqdp } else { &empty });
+        assert_eq!(
+            resolve_model_race(1401, EquipSlot::Body, 5, lookup).unwrap(),
+            101
+        );
+    }
+
+    #[test]
+    fn character_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let model = CharacterBuilder::new(Race::AuRa, Gender::Female)
+            .tail(1)
+            .equip(EquipSlot::Body, 1, 1)
+            .equip(EquipSlot::Ears, 1, 1)
+            .build(&game_data, &mut data_file_set)
+            .unwrap();
+        assert_eq!(model.parts.len(), 5);
+        for part in model.parts.iter() {
+            assert!(
+                game_data
+                    .lookup_path_data(&mut data_file_set, &part.model_path)
+                    .unwrap()
+                    .is_some(),
+                "{}",
+                part.model_path
+            );
+            if let PartKind::Equipment(_) = part.kind {
+                assert!(part.imc.is_some());
+            }
+        }
+    }
+}
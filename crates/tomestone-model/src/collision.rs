@@ -0,0 +1,224 @@
+//! Reads collision meshes (`.pcb` files), and writes them as Wavefront OBJ.
+//!
+//! A collision file is a 24 byte header followed by a tree of nodes. Group nodes have type 0x30
+//! and a fixed size, and are followed directly by their children. Other nodes hold a bounding
+//! box and a small mesh: vertices stored as floats, then vertices stored as 16 bit fractions of
+//! the bounding box, then triangles indexing into both lists, in that order. Each node's size
+//! field gives the offset of the next node. The layout follows the community documentation of
+//! the format, and the meaning of several fields is unknown.
+
+use std::io::{self, Write};
+
+use nom::{
+    multi::count,
+    number::complete::{le_f32, le_u16, le_u32, le_u8},
+    sequence::tuple,
+    Finish, IResult,
+};
+
+use crate::Error;
+
+const FILE_HEADER_SIZE: usize = 0x18;
+const NODE_HEADER_SIZE: usize = 0x28;
+const GROUP_NODE: u32 = 0x30;
+const GROUP_NODE_SIZE: usize = 0x30;
+
+/// Triangles from every node of a collision file, sharing one vertex list.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollisionMesh {
+    pub vertices: Vec<[f32; 3]>,
+    /// Indices into `vertices`, three per triangle.
+    pub triangles: Vec<[u32; 3]>,
+}
+
+struct NodeHeader {
+    kind: u32,
+    size: u32,
+    min: [f32; 3],
+    max: [f32; 3],
+    packed_vertex_count: u16,
+    triangle_count: u16,
+    vertex_count: u32,
+}
+
+fn vector(input: &[u8]) -> IResult<&[u8], [f32; 3]> {
+    let (input, (x, y, z)) = tuple((le_f32, le_f32, le_f32))(input)?;
+    Ok((input, [x, y, z]))
+}
+
+fn node_header(input: &[u8]) -> IResult<&[u8], NodeHeader> {
+    let (input, (kind, size, min, max, packed_vertex_count, triangle_count, vertex_count)) =
+        tuple((le_u32, le_u32, vector, vector, le_u16, le_u16, le_u32))(input)?;
+    Ok((
+        input,
+        NodeHeader {
+            kind,
+            size,
+            min,
+            max,
+            packed_vertex_count,
+            triangle_count,
+            vertex_count,
+        },
+    ))
+}
+
+fn packed_vertex(input: &[u8]) -> IResult<&[u8], [u16; 3]> {
+    let (input, (x, y, z)) = tuple((le_u16, le_u16, le_u16))(input)?;
+    Ok((input, [x, y, z]))
+}
+
+/// Three vertex indices, followed by nine bytes of unknown meaning, possibly material flags.
+fn triangle(input: &[u8]) -> IResult<&[u8], [u8; 3]> {
+    let (input, (a, b, c, _)) = tuple((le_u8, le_u8, le_u8, count(le_u8, 9)))(input)?;
+    Ok((input, [a, b, c]))
+}
+
+type NodeMesh = (Vec<[f32; 3]>, Vec<[u16; 3]>, Vec<[u8; 3]>);
+
+fn node_mesh<'a>(input: &'a [u8], header: &NodeHeader) -> IResult<&'a [u8], NodeMesh> {
+    tuple((
+        count(vector, header.vertex_count as usize),
+        count(packed_vertex, usize::from(header.packed_vertex_count)),
+        count(triangle, usize::from(header.triangle_count)),
+    ))(input)
+}
+
+fn unpack(min: f32, max: f32, value: u16) -> f32 {
+    min + (max - min) * f32::from(value) / f32::from(u16::MAX)
+}
+
+impl CollisionMesh {
+    pub fn parse(data: &[u8]) -> Result<CollisionMesh, Error> {
+        let mut mesh = CollisionMesh::default();
+        let mut offset = FILE_HEADER_SIZE;
+        while offset + NODE_HEADER_SIZE <= data.len() {
+            let (rest, header) = node_header(&data[offset..]).finish()?;
+            if header.kind == GROUP_NODE {
+                offset += GROUP_NODE_SIZE;
+                continue;
+            }
+            let (_, (vertices, packed_vertices, triangles)) = node_mesh(rest, &header).finish()?;
+
+            let base = mesh.vertices.len() as u32;
+            let node_vertex_count = vertices.len() + packed_vertices.len();
+            mesh.vertices.extend(vertices);
+            mesh.vertices.extend(packed_vertices.iter().map(|packed| {
+                [0, 1, 2].map(|axis| unpack(header.min[axis], header.max[axis], packed[axis]))
+            }));
+            for indices in triangles {
+                if indices
+                    .iter()
+                    .any(|index| usize::from(*index) >= node_vertex_count)
+                {
+                    return Err(Error::Nom(nom::error::ErrorKind::Verify));
+                }
+                mesh.triangles
+                    .push(indices.map(|index| base + u32::from(index)));
+            }
+
+            if header.size == 0 {
+                break;
+            }
+            offset += header.size as usize;
+        }
+        Ok(mesh)
+    }
+
+    /// Writes the mesh as a Wavefront OBJ file, with one object named `name`.
+    pub fn write_obj<W: Write>(&self, name: &str, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "o {}", name)?;
+        for [x, y, z] in self.vertices.iter() {
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        // OBJ indices start at one.
+        for [a, b, c] in self.triangles.iter() {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CollisionMesh;
+
+    fn node(
+        kind: u32,
+        size: u32,
+        min: [f32; 3],
+        max: [f32; 3],
+        vertices: &[[f32; 3]],
+        packed_vertices: &[[u16; 3]],
+        triangles: &[[u8; 3]],
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&size.to_le_bytes());
+        for value in min.iter().chain(max.iter()) {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&(packed_vertices.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(triangles.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(vertices.len() as u32).to_le_bytes());
+        for value in vertices.iter().flatten() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in packed_vertices.iter().flatten() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for triangle in triangles.iter() {
+            data.extend_from_slice(triangle);
+            data.extend_from_slice(&[0; 9]);
+        }
+        data
+    }
+
+    #[test]
+    fn collision_mesh() {
+        let mut data = vec![0; 0x18];
+        let mut group = node(0x30, 0, [0.0; 3], [0.0; 3], &[], &[], &[]);
+        group.resize(0x30, 0);
+        data.extend_from_slice(&group);
+        // Nodes point to the next one with their size.
+        let mut first = node(
+            0,
+            0,
+            [0.0; 3],
+            [2.0, 4.0, 8.0],
+            &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+            &[[0, 0xffff, 0x8000]],
+            &[[0, 1, 2]],
+        );
+        let size = first.len() as u32;
+        first[4..8].copy_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&first);
+        data.extend_from_slice(&node(
+            0,
+            0,
+            [0.0; 3],
+            [1.0; 3],
+            &[[5.0, 5.0, 5.0], [6.0, 5.0, 5.0], [5.0, 6.0, 5.0]],
+            &[],
+            &[[2, 1, 0]],
+        ));
+
+        let mesh = CollisionMesh::parse(&data).unwrap();
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.vertices[2][0], 0.0);
+        assert_eq!(mesh.vertices[2][1], 4.0);
+        assert!((mesh.vertices[2][2] - 4.0).abs() < 0.001);
+        assert_eq!(mesh.triangles, [[0, 1, 2], [5, 4, 3]]);
+
+        let mut obj = Vec::new();
+        mesh.write_obj("test", &mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert!(obj.starts_with("o test\nv 0 0 0\nv 1 0 0\n"));
+        assert!(obj.ends_with("f 1 2 3\nf 6 5 4\n"));
+
+        // A triangle referring past the node's vertices is rejected.
+        let mut bad = vec![0; 0x18];
+        bad.extend_from_slice(&node(0, 0, [0.0; 3], [1.0; 3], &[], &[], &[[0, 1, 2]]));
+        assert!(CollisionMesh::parse(&bad).is_err());
+    }
+}
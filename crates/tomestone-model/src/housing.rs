@@ -0,0 +1,226 @@
+//! Enumerates housing furniture, and the models used to display it.
+//!
+//! Indoor furniture is listed in the `HousingFurniture` sheet, and outdoor furniture in the
+//! `HousingYardObject` sheet. Each row has a model key, which selects a folder under
+//! `bgcommon/hou/indoor/general/` or `bgcommon/hou/outdoor/general/`. The folder holds a shared
+//! group (`.sgb`) that places the furniture's parts, and the parts' models, the first of which is
+//! named after the key.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tomestone_common::paths::sanitize_file_name;
+use tomestone_exdf::{
+    sheets::{HousingFurniture, HousingYardObject, Item},
+    Dataset, Language,
+};
+use tomestone_sqpack::{DataFileSet, GameData};
+use tomestone_string_interp::plain_text;
+
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FurnitureKind {
+    Indoor,
+    Outdoor,
+}
+
+impl FurnitureKind {
+    fn folder(&self, model_key: u16) -> String {
+        match self {
+            FurnitureKind::Indoor => format!("bgcommon/hou/indoor/general/{:04}", model_key),
+            FurnitureKind::Outdoor => format!("bgcommon/hou/outdoor/general/{:04}", model_key),
+        }
+    }
+
+    fn prefix(&self) -> &'static str {
+        match self {
+            FurnitureKind::Indoor => "fun",
+            FurnitureKind::Outdoor => "gar",
+        }
+    }
+
+    /// Path of the shared group that assembles the furniture.
+    pub fn shared_group_path(&self, model_key: u16) -> String {
+        format!(
+            "{}/asset/{}_b0_m{:04}.sgb",
+            self.folder(model_key),
+            self.prefix(),
+            model_key
+        )
+    }
+
+    /// Path of the furniture's main model. Furniture made of several parts has further models,
+    /// which are only listed in the shared group.
+    pub fn model_path(&self, model_key: u16) -> String {
+        format!(
+            "{}/bgparts/{}_b0_m{:04}.mdl",
+            self.folder(model_key),
+            self.prefix(),
+            model_key
+        )
+    }
+}
+
+/// One piece of furniture, with its item name and model paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Furniture {
+    pub kind: FurnitureKind,
+    pub model_key: u16,
+    /// Row of the `Item` sheet.
+    pub item: u32,
+    /// Item name, as plain text.
+    pub name: String,
+    pub shared_group_path: String,
+    pub model_path: String,
+}
+
+/// Lists all indoor and outdoor furniture with a model and an item, sorted by kind and model
+/// key. Item names are in the given language.
+pub fn list_furniture(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    language: Language,
+) -> Result<Vec<Furniture>, Error> {
+    let mut rows = Vec::new();
+    let dataset = Dataset::load(game_data, data_file_set, HousingFurniture::SHEET, language)?;
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            let furniture = HousingFurniture::from_row(&row)?;
+            rows.push((FurnitureKind::Indoor, furniture.model_key, furniture.item));
+        }
+    }
+    let dataset = Dataset::load(game_data, data_file_set, HousingYardObject::SHEET, language)?;
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            let yard_object = HousingYardObject::from_row(&row)?;
+            rows.push((
+                FurnitureKind::Outdoor,
+                u16::from(yard_object.model_key),
+                yard_object.item,
+            ));
+        }
+    }
+    rows.retain(|(_, model_key, item)| *model_key != 0 && *item != 0);
+
+    let wanted = rows
+        .iter()
+        .map(|(_, _, item)| *item)
+        .collect::<HashSet<_>>();
+    let mut names = HashMap::new();
+    let dataset = Dataset::load(game_data, data_file_set, Item::SHEET, language)?;
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            if !wanted.contains(&row.number) {
+                continue;
+            }
+            let item = Item::from_row(&row)?;
+            names.insert(row.number, plain_text(item.name));
+        }
+    }
+
+    let mut furniture = rows
+        .into_iter()
+        .map(|(kind, model_key, item)| Furniture {
+            kind,
+            model_key,
+            item,
+            name: names.get(&item).cloned().unwrap_or_default(),
+            shared_group_path: kind.shared_group_path(model_key),
+            model_path: kind.model_path(model_key),
+        })
+        .collect::<Vec<_>>();
+    furniture.sort_by_key(|furniture| (furniture.kind as u8, furniture.model_key));
+    Ok(furniture)
+}
+
+/// File name for an exported model, made from the model key and item name, e.g.
+/// `0123 Riviera Bed.mdl`.
+pub fn export_file_name(furniture: &Furniture) -> String {
+    let name = sanitize_file_name(&furniture.name);
+    if name.is_empty() {
+        format!("{:04}.mdl", furniture.model_key)
+    } else {
+        format!("{:04} {}.mdl", furniture.model_key, name)
+    }
+}
+
+/// Writes each piece of furniture's main model into `indoor` and `outdoor` folders under
+/// `output`. Furniture whose model is missing is skipped. Returns the paths written.
+pub fn export_furniture(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    furniture: &[Furniture],
+    output: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut written = Vec::new();
+    for furniture in furniture.iter() {
+        let data = match game_data.lookup_path_data(data_file_set, &furniture.model_path)? {
+            Some(data) => data,
+            None => continue,
+        };
+        let folder = output.join(match furniture.kind {
+            FurnitureKind::Indoor => "indoor",
+            FurnitureKind::Outdoor => "outdoor",
+        });
+        fs::create_dir_all(&folder)?;
+        let path = folder.join(export_file_name(furniture));
+        fs::write(&path, data)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_exdf::Language;
+    use tomestone_sqpack::GameData;
+
+    use super::{export_file_name, list_furniture, Furniture, FurnitureKind};
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            FurnitureKind::Indoor.model_path(123),
+            "bgcommon/hou/indoor/general/0123/bgparts/fun_b0_m0123.mdl"
+        );
+        assert_eq!(
+            FurnitureKind::Outdoor.shared_group_path(45),
+            "bgcommon/hou/outdoor/general/0045/asset/gar_b0_m0045.sgb"
+        );
+        let furniture = Furniture {
+            kind: FurnitureKind::Indoor,
+            model_key: 7,
+            item: 1,
+            name: "Wood/Stone Table?".to_string(),
+            shared_group_path: String::new(),
+            model_path: String::new(),
+        };
+        assert_eq!(export_file_name(&furniture), "0007 Wood_Stone Table_.mdl");
+        let unnamed = Furniture {
+            name: String::new(),
+            ..furniture
+        };
+        assert_eq!(export_file_name(&unnamed), "0007.mdl");
+    }
+
+    #[test]
+    fn furniture_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let furniture = list_furniture(&game_data, &mut data_file_set, Language::English).unwrap();
+        assert!(furniture
+            .iter()
+            .any(|furniture| furniture.kind == FurnitureKind::Indoor));
+        assert!(furniture
+            .iter()
+            .any(|furniture| furniture.kind == FurnitureKind::Outdoor));
+        assert!(furniture.iter().all(|furniture| !furniture.name.is_empty()));
+    }
+}
@@ -0,0 +1,53 @@
+//! Locating and reading the game's models, and the files that describe how they are combined.
+
+use std::{fmt, io};
+
+pub mod character;
+pub mod collision;
+pub mod housing;
+
+#[derive(Debug)]
+pub enum Error {
+    Sqpack(tomestone_sqpack::Error),
+    Exdf(tomestone_exdf::Error),
+    Io(io::Error),
+    Nom(nom::error::ErrorKind),
+    /// A file that was needed to resolve a model was not found. Contains the file's path.
+    NoSuchFile(String),
+}
+
+impl From<tomestone_sqpack::Error> for Error {
+    fn from(e: tomestone_sqpack::Error) -> Error {
+        Error::Sqpack(e)
+    }
+}
+
+impl From<tomestone_exdf::Error> for Error {
+    fn from(e: tomestone_exdf::Error) -> Error {
+        Error::Exdf(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error {
+    fn from(e: nom::error::Error<&'a [u8]>) -> Error {
+        Error::Nom(e.code)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sqpack(e) => e.fmt(f),
+            Error::Exdf(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+            Error::Nom(e) => write!(f, "parsing error: {:?}", e),
+            Error::NoSuchFile(path) => write!(f, "file not found: {}", path),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Locating, downloading, and verifying official patch files.
+//!
+//! Patches are served over plain HTTP, from a folder for each repository. The launcher's patch
+//! list gives the expected size of each patch, along with SHA-1 hashes of each fixed-size block
+//! of it, which are used to check downloads. Downloading requires the `download` feature.
+
+use std::io::{self, Read};
+#[cfg(feature = "download")]
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+#[cfg(feature = "download")]
+use crate::PatchFile;
+
+pub const PATCH_SERVER: &str = "http://patch-dl.ffxiv.com";
+
+/// A series of patches, each applying on top of the previous one. Repositories are named
+/// `boot`, `ffxiv`, `ex1`, and so on, when serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Repository {
+    Boot,
+    Game,
+    Expansion(u8),
+}
+
+impl Repository {
+    pub fn name(&self) -> String {
+        match self {
+            Repository::Boot => "boot".to_string(),
+            Repository::Game => "ffxiv".to_string(),
+            Repository::Expansion(number) => format!("ex{}", number),
+        }
+    }
+
+    pub fn parse_name(name: &str) -> Option<Repository> {
+        match name {
+            "boot" => Some(Repository::Boot),
+            "ffxiv" => Some(Repository::Game),
+            _ => name
+                .strip_prefix("ex")?
+                .parse()
+                .ok()
+                .filter(|number| *number > 0)
+                .map(Repository::Expansion),
+        }
+    }
+
+    /// Returns the folder holding this repository's patches on the patch server, if known.
+    pub fn folder(&self) -> Option<&'static str> {
+        match self {
+            Repository::Boot => Some("boot/2b5cbc63"),
+            Repository::Game => Some("game/4e9a232b"),
+            Repository::Expansion(1) => Some("game/ex1/6b936f08"),
+            Repository::Expansion(2) => Some("game/ex2/f29a3eb2"),
+            Repository::Expansion(3) => Some("game/ex3/859d0e24"),
+            Repository::Expansion(4) => Some("game/ex4/1bf99b87"),
+            Repository::Expansion(_) => None,
+        }
+    }
+
+    /// Returns the URL of a patch, given its version string, such as `2023.01.10.0000.0000`.
+    /// Historical patches, which are prefixed with `H` rather than `D`, should include the prefix
+    /// in the version string.
+    pub fn patch_url(&self, version: &str) -> Option<String> {
+        let version = if version.starts_with(['D', 'H']) {
+            version.to_string()
+        } else {
+            format!("D{}", version)
+        };
+        Some(format!(
+            "{}/{}/{}.patch",
+            PATCH_SERVER,
+            self.folder()?,
+            version
+        ))
+    }
+}
+
+impl From<Repository> for String {
+    fn from(repository: Repository) -> String {
+        repository.name()
+    }
+}
+
+impl TryFrom<String> for Repository {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Repository, String> {
+        Repository::parse_name(&name).ok_or_else(|| format!("unknown repository {:?}", name))
+    }
+}
+
+/// Everything needed to download and check one patch file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchInfo {
+    pub url: String,
+    /// Size of the patch file, in bytes.
+    pub size: u64,
+    /// Size of the blocks that are hashed, in bytes.
+    pub hash_block_size: u64,
+    /// Lowercase hexadecimal SHA-1 hashes of each block, in order.
+    pub hashes: Vec<String>,
+}
+
+impl PatchInfo {
+    pub fn file_name(&self) -> &str {
+        self.url.rsplit('/').next().unwrap_or(&self.url)
+    }
+}
+
+pub(crate) fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Checks the size and block hashes of a patch file.
+pub fn verify<R: Read>(mut reader: R, info: &PatchInfo) -> Result<(), Error> {
+    let mut size = 0;
+    let mut block = Vec::new();
+    for (index, expected) in info.hashes.iter().enumerate() {
+        block.clear();
+        (&mut reader)
+            .take(info.hash_block_size)
+            .read_to_end(&mut block)?;
+        size += block.len() as u64;
+        if block.is_empty() || hex(&tomestone_sqpack::sha1(&block)) != expected.to_lowercase() {
+            return Err(Error::HashMismatch(index));
+        }
+    }
+    size += io::copy(&mut reader, &mut io::sink())?;
+    if size != info.size {
+        return Err(Error::SizeMismatch {
+            expected: info.size,
+            actual: size,
+        });
+    }
+    Ok(())
+}
+
+/// Downloads a patch to the given path, and checks it. If a partial download is already at
+/// that path, the rest of it is requested, if the server supports ranges.
+#[cfg(feature = "download")]
+pub fn download(info: &PatchInfo, destination: &Path) -> Result<PatchFile<BufReader<File>>, Error> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(destination)?;
+    let mut existing = file.metadata()?.len();
+    if existing > info.size {
+        file.set_len(0)?;
+        existing = 0;
+    }
+    if existing < info.size {
+        let mut request = ureq::get(&info.url).set("User-Agent", "FFXIV PATCH CLIENT");
+        if existing > 0 {
+            request = request.set("Range", &format!("bytes={}-", existing));
+        }
+        let response = request.call().map_err(|e| Error::Http(Box::new(e)))?;
+        if response.status() != 206 {
+            // The whole file was sent.
+            file.set_len(0)?;
+        }
+        io::copy(&mut response.into_reader(), &mut file)?;
+    }
+    file.seek(SeekFrom::Start(0))?;
+    verify(BufReader::new(&mut file), info)?;
+    PatchFile::open(destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, PatchInfo, Repository};
+    use crate::Error;
+
+    fn info(data: &[u8], hash_block_size: u64) -> PatchInfo {
+        PatchInfo {
+            url: "http://localhost/game/D2023.01.10.0000.0000.patch".to_string(),
+            size: data.len() as u64,
+            hash_block_size,
+            hashes: data
+                .chunks(hash_block_size as usize)
+                .map(|block| super::hex(&tomestone_sqpack::sha1(block)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            Repository::Game
+                .patch_url("2023.01.10.0000.0000")
+                .as_deref(),
+            Some("http://patch-dl.ffxiv.com/game/4e9a232b/D2023.01.10.0000.0000.patch")
+        );
+        assert_eq!(
+            Repository::Expansion(2)
+                .patch_url("H2017.06.06.0000.0001a")
+                .as_deref(),
+            Some("http://patch-dl.ffxiv.com/game/ex2/f29a3eb2/H2017.06.06.0000.0001a.patch")
+        );
+        assert_eq!(
+            Repository::Expansion(9).patch_url("2023.01.10.0000.0000"),
+            None
+        );
+        for repository in [Repository::Boot, Repository::Game, Repository::Expansion(3)] {
+            assert_eq!(Repository::parse_name(&repository.name()), Some(repository));
+        }
+        assert_eq!(Repository::parse_name("ex0"), None);
+        assert_eq!(Repository::parse_name("sqpack"), None);
+    }
+
+    #[test]
+    fn verification() {
+        let data = (0..100u8).collect::<Vec<u8>>();
+        let info = info(&data, 32);
+        assert_eq!(info.hashes.len(), 4);
+        assert_eq!(info.file_name(), "D2023.01.10.0000.0000.patch");
+        verify(&data[..], &info).unwrap();
+
+        let mut corrupt = data.clone();
+        corrupt[40] ^= 1;
+        assert!(matches!(
+            verify(&corrupt[..], &info),
+            Err(Error::HashMismatch(1))
+        ));
+        assert!(matches!(
+            verify(&data[..90], &info),
+            Err(Error::HashMismatch(2))
+        ));
+        let mut longer = data.clone();
+        longer.push(0);
+        assert!(matches!(
+            verify(&longer[..], &info),
+            Err(Error::HashMismatch(3))
+        ));
+        let wrong_size = PatchInfo { size: 99, ..info };
+        assert!(matches!(
+            verify(&data[..], &wrong_size),
+            Err(Error::SizeMismatch {
+                expected: 99,
+                actual: 100
+            })
+        ));
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn download_resume() {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+            thread,
+        };
+
+        use crate::{tests::chunk, MAGIC};
+
+        let mut patch = MAGIC.to_vec();
+        chunk(&mut patch, b"FHDR", &[0; 200]);
+        chunk(&mut patch, b"EOF_", &[]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let served = patch.clone();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut start = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(range) = line.to_lowercase().strip_prefix("range: bytes=") {
+                    start = range.trim().trim_end_matches('-').parse().unwrap();
+                }
+            }
+            let body = &served[start..];
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+            start
+        });
+
+        let mut info = info(&patch, 64);
+        info.url = format!("http://{}/game/D2023.01.10.0000.0000.patch", address);
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join(info.file_name());
+        std::fs::write(&path, &patch[..100]).unwrap();
+        let mut patch_file = super::download(&info, &path).unwrap();
+        assert_eq!(server.join().unwrap(), 100);
+        assert_eq!(std::fs::read(&path).unwrap(), patch);
+        assert_eq!(patch_file.entries().unwrap(), []);
+    }
+}
@@ -0,0 +1,406 @@
+//! Checks of a game installation outside of its pack files: version files and the boot
+//! executables. Installations can also be copied with [`copy_install`]. Whether the game is
+//! running is checked by [`tomestone_sqpack::live`], and version files are read by
+//! [`tomestone_sqpack::InstallVersions`]. Both are re-exported here.
+//!
+//! Each repository records its version in a `.ver` file. The launcher reports the size and SHA-1
+//! hash of each boot executable when checking for boot updates, and a mismatch is treated as a
+//! damaged installation.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tomestone_sqpack::{Category, Expansion, GameData};
+
+use crate::{Error, PackFile, Platform};
+
+/// The boot executables whose hashes are reported by the launcher, in the order it reports
+/// them.
+pub use tomestone_sqpack::live::BOOT_EXECUTABLES as BOOT_FILES;
+pub use tomestone_sqpack::live::{running_game_processes, GameProcess};
+
+pub use tomestone_sqpack::{read_version_file, InstallVersions};
+
+/// The size and hash of one boot executable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootFileHash {
+    pub name: String,
+    pub size: u64,
+    /// Lowercase hexadecimal SHA-1 hash.
+    pub sha1: String,
+}
+
+fn boot_path(root: &Path, name: &str) -> PathBuf {
+    root.join("boot").join(name)
+}
+
+fn hash_file(root: &Path, name: &str) -> io::Result<Option<BootFileHash>> {
+    let data = match fs::read(boot_path(root, name)) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(Some(BootFileHash {
+        name: name.to_string(),
+        size: data.len() as u64,
+        sha1: crate::cdn::hex(&tomestone_sqpack::sha1(&data)),
+    }))
+}
+
+/// Hashes each boot executable that is present.
+pub fn hash_boot_files(root: &Path) -> io::Result<Vec<BootFileHash>> {
+    let mut hashes = Vec::new();
+    for name in BOOT_FILES {
+        hashes.extend(hash_file(root, name)?);
+    }
+    Ok(hashes)
+}
+
+/// Formats boot file hashes the way the launcher sends them with a boot version check.
+pub fn boot_hash_report(hashes: &[BootFileHash]) -> String {
+    hashes
+        .iter()
+        .map(|hash| format!("{}/{}/{}", hash.name, hash.size, hash.sha1))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootProblem {
+    Missing(String),
+    Mismatch {
+        expected: BootFileHash,
+        actual: BootFileHash,
+    },
+}
+
+/// Compares the boot executables against their expected hashes.
+pub fn check_boot_files(root: &Path, expected: &[BootFileHash]) -> io::Result<Vec<BootProblem>> {
+    let mut problems = Vec::new();
+    for expected in expected {
+        match hash_file(root, &expected.name)? {
+            None => problems.push(BootProblem::Missing(expected.name.clone())),
+            Some(actual) if actual.size != expected.size || actual.sha1 != expected.sha1 => {
+                problems.push(BootProblem::Mismatch {
+                    expected: expected.clone(),
+                    actual,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(problems)
+}
+
+/// Options for [`copy_install`].
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    /// Categories whose packs are left out.
+    pub exclude_categories: Vec<Category>,
+    /// Leaves out the `boot` directory.
+    pub skip_boot: bool,
+}
+
+/// One file copied by [`copy_install`], or hashed by [`hash_install`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the installation directory, with forward slashes.
+    pub path: String,
+    pub size: u64,
+    /// Lowercase hexadecimal SHA-1 hash.
+    pub sha1: String,
+}
+
+/// The files copied by [`copy_install`], in the order they were copied, or hashed by
+/// [`hash_install`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CopyManifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl CopyManifest {
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<CopyManifest, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Copies the files of an installation that [`GameData`] reads: the index and data files of
+/// each pack, the version files, and the boot executables. Other files, such as screenshots and
+/// logs, are left behind. Each file is hashed as it is copied, and read back afterwards to check
+/// the copy. The destination must not be the source directory, or be inside it, or contain it.
+pub fn copy_install(src: &Path, dst: &Path, options: &CopyOptions) -> Result<CopyManifest, Error> {
+    let (resolved_src, resolved_dst) = (resolve_path(src)?, resolve_path(dst)?);
+    if resolved_src.starts_with(&resolved_dst) || resolved_dst.starts_with(&resolved_src) {
+        return Err(Error::CopyOverlap(resolved_src, resolved_dst));
+    }
+    let mut manifest = CopyManifest::default();
+    for path in install_files(src, options)? {
+        manifest.files.extend(copy_verified(src, dst, &path)?);
+    }
+    Ok(manifest)
+}
+
+/// Canonicalizes a path that may not exist yet, by canonicalizing its closest existing ancestor.
+/// The missing components are then applied lexically, as they cannot be symbolic links.
+fn resolve_path(path: &Path) -> Result<PathBuf, Error> {
+    let absolute = std::path::absolute(path)?;
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(mut resolved) => {
+                for component in missing.into_iter().rev() {
+                    match component {
+                        Component::ParentDir => {
+                            resolved.pop();
+                        }
+                        Component::CurDir => {}
+                        component => resolved.push(component),
+                    }
+                }
+                return Ok(resolved);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => match existing.parent() {
+                Some(parent) => {
+                    missing.extend(existing.components().next_back());
+                    existing = parent;
+                }
+                None => return Err(e.into()),
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Hashes the files that [`copy_install`] would copy, without copying them.
+pub fn hash_install(root: &Path, options: &CopyOptions) -> Result<CopyManifest, Error> {
+    let mut manifest = CopyManifest::default();
+    for path in install_files(root, options)? {
+        let mut file = match File::open(root.join(&path)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut hasher = Sha1::new();
+        let size = io::copy(&mut file, &mut hasher)?;
+        manifest.files.push(ManifestEntry {
+            path,
+            size,
+            sha1: crate::cdn::hex(&hasher.finalize()),
+        });
+    }
+    Ok(manifest)
+}
+
+/// Lists the files of an installation that [`copy_install`] copies. Version files and boot
+/// executables are listed whether or not they exist.
+fn install_files(src: &Path, options: &CopyOptions) -> Result<Vec<String>, Error> {
+    let game_data = GameData::new(src)?;
+    let mut paths = Vec::new();
+    if !options.skip_boot {
+        paths.push("boot/ffxivboot.ver".to_string());
+        paths.extend(BOOT_FILES.iter().map(|name| format!("boot/{}", name)));
+    }
+    paths.push("game/ffxivgame.ver".to_string());
+    let mut expansions = BTreeSet::new();
+    for id in game_data.iter_packs() {
+        if options.exclude_categories.contains(&id.category) {
+            continue;
+        }
+        expansions.insert(id.expansion);
+        let pack_file = |file_id| PackFile {
+            main_id: id.category.to_u8().into(),
+            sub_id: u16::from(id.expansion.to_u8()) << 8 | u16::from(id.number),
+            file_id,
+        };
+        for file_id in [0, 2] {
+            paths.push(format!(
+                "game/{}",
+                pack_file(file_id).index_path(Platform::Win32)
+            ));
+        }
+        for file_id in 0.. {
+            let path = format!("game/{}", pack_file(file_id).dat_path(Platform::Win32));
+            if !src.join(&path).is_file() {
+                break;
+            }
+            paths.push(path);
+        }
+    }
+    for expansion in expansions {
+        if expansion != Expansion::Base {
+            paths.push(format!("game/sqpack/{0}/{0}.ver", expansion.name()));
+        }
+    }
+    Ok(paths)
+}
+
+/// Copies one file, if it exists, and checks the copy.
+fn copy_verified(src: &Path, dst: &Path, path: &str) -> Result<Option<ManifestEntry>, Error> {
+    let mut source = match File::open(src.join(path)) {
+        Ok(source) => source,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let destination_path = dst.join(path);
+    if let Some(parent) = destination_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut destination = BufWriter::new(File::create(&destination_path)?);
+    let mut hasher = Sha1::new();
+    let mut size = 0;
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        let len = source.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+        destination.write_all(&buf[..len])?;
+        size += len as u64;
+    }
+    destination
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    let hash = hasher.finalize();
+
+    let mut check = Sha1::new();
+    io::copy(&mut File::open(&destination_path)?, &mut check)?;
+    if check.finalize() != hash {
+        return Err(Error::CopyMismatch(path.to_string()));
+    }
+    Ok(Some(ManifestEntry {
+        path: path.to_string(),
+        size,
+        sha1: crate::cdn::hex(&hash),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tomestone_sqpack::{encoding::write_pack, Category, Expansion, SqPackId};
+
+    use super::{
+        boot_hash_report, check_boot_files, copy_install, hash_boot_files, hash_install,
+        BootProblem, CopyManifest, CopyOptions, Error,
+    };
+
+    #[test]
+    fn install_checks() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        fs::create_dir_all(root.join("boot")).unwrap();
+        fs::create_dir_all(root.join("game/sqpack/ex1")).unwrap();
+        fs::create_dir_all(root.join("game/sqpack/ex3")).unwrap();
+        fs::write(root.join("boot/ffxivboot.exe"), b"").unwrap();
+        fs::write(root.join("boot/ffxivlauncher.exe"), b"launcher").unwrap();
+        let hashes = hash_boot_files(root).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(
+            boot_hash_report(&hashes[..1]),
+            "ffxivboot.exe/0/da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+        assert!(check_boot_files(root, &hashes).unwrap().is_empty());
+
+        fs::write(root.join("boot/ffxivlauncher.exe"), b"tampered").unwrap();
+        fs::remove_file(root.join("boot/ffxivboot.exe")).unwrap();
+        let problems = check_boot_files(root, &hashes).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0],
+            BootProblem::Missing("ffxivboot.exe".to_string())
+        );
+        assert!(matches!(problems[1], BootProblem::Mismatch { .. }));
+    }
+
+    #[test]
+    fn copy() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        let sqpack_dir = src.path().join("game/sqpack");
+        let exd = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let music = SqPackId {
+            category: Category::Music,
+            expansion: Expansion::Ex1,
+            number: 0,
+        };
+        write_pack(&sqpack_dir, exd, [("exd/a.exh", &b"abc"[..])]).unwrap();
+        write_pack(&sqpack_dir, music, [("music/ex1/a.scd", &b"def"[..])]).unwrap();
+        fs::write(
+            src.path().join("game/ffxivgame.ver"),
+            "2023.01.10.0000.0000",
+        )
+        .unwrap();
+        fs::write(
+            src.path().join("game/sqpack/ex1/ex1.ver"),
+            "2023.01.05.0000.0000",
+        )
+        .unwrap();
+        fs::write(src.path().join("game/screenshot.png"), b"").unwrap();
+
+        let options = CopyOptions {
+            exclude_categories: vec![Category::Music],
+            ..Default::default()
+        };
+        let manifest = copy_install(src.path(), dst.path(), &options).unwrap();
+        assert_eq!(
+            manifest
+                .files
+                .iter()
+                .map(|entry| entry.path.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "game/ffxivgame.ver",
+                "game/sqpack/ffxiv/0a0000.win32.index",
+                "game/sqpack/ffxiv/0a0000.win32.index2",
+                "game/sqpack/ffxiv/0a0000.win32.dat0",
+            ]
+        );
+        for entry in manifest.files.iter() {
+            let data = fs::read(dst.path().join(&entry.path)).unwrap();
+            assert_eq!(entry.size, data.len() as u64);
+            assert_eq!(entry.sha1, crate::cdn::hex(&tomestone_sqpack::sha1(&data)));
+        }
+        assert_eq!(hash_install(src.path(), &options).unwrap(), manifest);
+        assert!(!dst.path().join("game/screenshot.png").exists());
+        assert!(!dst.path().join("game/sqpack/ex1").exists());
+
+        let mut written = Vec::new();
+        manifest.write(&mut written).unwrap();
+        assert_eq!(CopyManifest::read(&written[..]).unwrap(), manifest);
+
+        // Copies onto or into the installation, or around it, are refused before anything is
+        // written, including through `..` after a directory that does not exist.
+        let nested = src.path().join("game/../backup");
+        let around = dst
+            .path()
+            .join("missing/../..")
+            .join(src.path().file_name().unwrap());
+        for dst in [src.path(), &nested, &around, src.path().parent().unwrap()] {
+            assert!(matches!(
+                copy_install(src.path(), dst, &options),
+                Err(Error::CopyOverlap(..))
+            ));
+        }
+        assert!(!src.path().join("backup").exists());
+        assert_eq!(hash_install(src.path(), &options).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,801 @@
+//! Reading of the game's `.patch` files.
+//!
+//! A patch file is a magic number followed by a sequence of chunks. Each chunk has a big-endian
+//! length, a four byte tag, a body, and a CRC-32 of the tag and body. Nearly all of the work is
+//! done by `SQPK` chunks, each holding one command that edits a pack file or another file in
+//! the game directory. Block offsets and sizes inside pack files are stored in units of 128
+//! bytes.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use nom::{
+    bytes::complete::take,
+    number::complete::{be_u16, be_u32, be_u64, be_u8},
+    sequence::tuple,
+    Finish, IResult,
+};
+use tomestone_sqpack::{IndexHash, IndexHash1};
+
+pub mod cdn;
+pub mod install;
+pub mod versions;
+
+pub const MAGIC: &[u8; 12] = b"\x91ZIPATCH\r\n\x1a\n";
+
+/// The most bytes of a chunk body that are read to describe it. Chunk contents past this, such
+/// as file data, are skipped.
+const MAX_COMMAND_HEADER_SIZE: usize = 0x400;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Nom(nom::error::ErrorKind),
+    /// The file does not start with the patch file magic number.
+    BadMagic,
+    Sqpack(tomestone_sqpack::Error),
+    /// A downloaded patch has the wrong size.
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// The hash of a block of a downloaded patch is wrong.
+    HashMismatch(usize),
+    Json(serde_json::Error),
+    /// A copied file, read back after copying, doesn't match the original.
+    CopyMismatch(String),
+    /// The source and destination of a copy are the same directory, or one contains the other.
+    CopyOverlap(PathBuf, PathBuf),
+    #[cfg(feature = "download")]
+    Http(Box<ureq::Error>),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<tomestone_sqpack::Error> for Error {
+    fn from(e: tomestone_sqpack::Error) -> Error {
+        Error::Sqpack(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error {
+    fn from(e: nom::error::Error<&'a [u8]>) -> Error {
+        Error::Nom(e.code)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => e.fmt(f),
+            Error::Nom(e) => write!(f, "parsing error: {:?}", e),
+            Error::BadMagic => write!(f, "not a patch file"),
+            Error::Sqpack(e) => e.fmt(f),
+            Error::SizeMismatch { expected, actual } => write!(
+                f,
+                "patch is {} bytes long, expected {} bytes",
+                actual, expected
+            ),
+            Error::HashMismatch(block) => write!(f, "hash mismatch in block {}", block),
+            Error::Json(e) => e.fmt(f),
+            Error::CopyMismatch(path) => {
+                write!(f, "the copy of {} doesn't match the original", path)
+            }
+            Error::CopyOverlap(src, dst) => write!(
+                f,
+                "can't copy {} to {}, one directory contains the other",
+                src.display(),
+                dst.display()
+            ),
+            #[cfg(feature = "download")]
+            Error::Http(e) => e.fmt(f),
+        }
+    }
+}
+
+/// A pack file, identified the way patch commands do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackFile {
+    /// The pack's category.
+    pub main_id: u16,
+    /// The pack's expansion in the high byte, and its number in the low byte.
+    pub sub_id: u16,
+    /// Which `.dat` file, or which index file.
+    pub file_id: u32,
+}
+
+impl PackFile {
+    pub fn expansion_folder(&self) -> String {
+        match self.sub_id >> 8 {
+            0 => "ffxiv".to_string(),
+            expansion => format!("ex{}", expansion),
+        }
+    }
+
+    fn base_path(&self, platform: Platform) -> String {
+        format!(
+            "sqpack/{}/{:02x}{:04x}.{}",
+            self.expansion_folder(),
+            self.main_id,
+            self.sub_id,
+            platform.name()
+        )
+    }
+
+    /// Path of the data file, relative to the game directory.
+    pub fn dat_path(&self, platform: Platform) -> String {
+        format!("{}.dat{}", self.base_path(platform), self.file_id)
+    }
+
+    /// Path of the index file, relative to the game directory. File zero is the `.index` file.
+    pub fn index_path(&self, platform: Platform) -> String {
+        match self.file_id {
+            0 => format!("{}.index", self.base_path(platform)),
+            file_id => format!("{}.index{}", self.base_path(platform), file_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Win32,
+    Ps3,
+    Ps4,
+}
+
+impl Platform {
+    fn from_u16(value: u16) -> Platform {
+        match value {
+            1 => Platform::Ps3,
+            2 => Platform::Ps4,
+            _ => Platform::Win32,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Win32 => "win32",
+            Platform::Ps3 => "ps3",
+            Platform::Ps4 => "ps4",
+        }
+    }
+}
+
+/// Which file a header command replaces the header of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderTarget {
+    Data,
+    Index,
+}
+
+/// One change made by a patch. Offsets named `patch_offset` locate the change's data within the
+/// patch file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchEntry {
+    /// Writes data into a `.dat` file, then zeroes `delete_size` bytes after it.
+    AddData {
+        target: PackFile,
+        offset: u64,
+        size: u64,
+        delete_size: u64,
+        patch_offset: u64,
+    },
+    /// Replaces a range of a `.dat` file with empty blocks.
+    DeleteData {
+        target: PackFile,
+        offset: u64,
+        size: u64,
+    },
+    /// Extends a `.dat` file with empty blocks.
+    ExpandData {
+        target: PackFile,
+        offset: u64,
+        size: u64,
+    },
+    /// Replaces one of the 1024 byte headers of a pack file.
+    Header {
+        target: PackFile,
+        file: HeaderTarget,
+        /// `V` for the version header, or `I` or `D` for the index or data header.
+        kind: u8,
+        patch_offset: u64,
+    },
+    /// Writes part of a file, relative to the game directory. Files are written in pieces,
+    /// starting with the piece at offset zero, which truncates the file. The piece is stored as
+    /// a series of compressed blocks.
+    AddFile {
+        path: String,
+        offset: u64,
+        size: u64,
+        patch_offset: u64,
+    },
+    DeleteFile {
+        path: String,
+    },
+    /// Deletes every pack file of an expansion, before they are rewritten from scratch.
+    RemoveAll {
+        expansion: u16,
+    },
+    MakeDirectoryTree {
+        path: String,
+    },
+    /// Adds or removes an index entry. The hash holds the folder hash in its high half, and the
+    /// file name hash in its low half. The entry points to `offset` in data file `data_file_id`.
+    Index {
+        target: PackFile,
+        add: bool,
+        hash: u64,
+        offset: u64,
+        data_file_id: u32,
+    },
+    AddDirectory {
+        path: String,
+    },
+    DeleteDirectory {
+        path: String,
+    },
+}
+
+impl PatchEntry {
+    /// Path of the file this entry changes, relative to the game directory.
+    pub fn target_path(&self, platform: Platform) -> String {
+        match self {
+            PatchEntry::AddData { target, .. }
+            | PatchEntry::DeleteData { target, .. }
+            | PatchEntry::ExpandData { target, .. }
+            | PatchEntry::Header {
+                target,
+                file: HeaderTarget::Data,
+                ..
+            } => target.dat_path(platform),
+            PatchEntry::Header { target, .. } | PatchEntry::Index { target, .. } => {
+                target.index_path(platform)
+            }
+            PatchEntry::AddFile { path, .. }
+            | PatchEntry::DeleteFile { path }
+            | PatchEntry::MakeDirectoryTree { path }
+            | PatchEntry::AddDirectory { path }
+            | PatchEntry::DeleteDirectory { path } => path.clone(),
+            PatchEntry::RemoveAll { expansion } => match expansion {
+                0 => "sqpack/ffxiv".to_string(),
+                expansion => format!("sqpack/ex{}", expansion),
+            },
+        }
+    }
+}
+
+fn pack_file(input: &[u8]) -> IResult<&[u8], PackFile> {
+    let (input, (main_id, sub_id, file_id)) = tuple((be_u16, be_u16, be_u32))(input)?;
+    Ok((
+        input,
+        PackFile {
+            main_id,
+            sub_id,
+            file_id,
+        },
+    ))
+}
+
+/// Parses a range command, which has a target and a block offset and count.
+fn block_range(input: &[u8]) -> IResult<&[u8], (PackFile, u64, u64)> {
+    let (input, (_, target, offset, count)) =
+        tuple((take(3usize), pack_file, be_u32, be_u32))(input)?;
+    Ok((
+        input,
+        (target, u64::from(offset) << 7, u64::from(count) << 7),
+    ))
+}
+
+fn path_string(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// Parses the start of an `SQPK` command, given the command byte and the bytes after it.
+/// `body_offset` is the position in the patch file of the byte after the command byte. Returns
+/// `None` for commands that don't change any files.
+fn sqpack_command(
+    command: u8,
+    input: &[u8],
+    body_offset: u64,
+) -> IResult<&[u8], Option<PatchEntry>> {
+    let entry = match command {
+        b'A' => {
+            let (rest, (target, offset, size)) = block_range(input)?;
+            let (_, delete_count) = be_u32(rest)?;
+            PatchEntry::AddData {
+                target,
+                offset,
+                size,
+                delete_size: u64::from(delete_count) << 7,
+                patch_offset: body_offset + 23,
+            }
+        }
+        b'D' => {
+            let (_, (target, offset, size)) = block_range(input)?;
+            PatchEntry::DeleteData {
+                target,
+                offset,
+                size,
+            }
+        }
+        b'E' => {
+            let (_, (target, offset, size)) = block_range(input)?;
+            PatchEntry::ExpandData {
+                target,
+                offset,
+                size,
+            }
+        }
+        b'H' => {
+            let (_, (file, kind, _, target)) = tuple((be_u8, be_u8, be_u8, pack_file))(input)?;
+            PatchEntry::Header {
+                target,
+                file: if file == b'I' {
+                    HeaderTarget::Index
+                } else {
+                    HeaderTarget::Data
+                },
+                kind,
+                patch_offset: body_offset + 11,
+            }
+        }
+        b'F' => {
+            let (rest, (operation, _, offset, size, path_length, expansion, _)) =
+                tuple((
+                    be_u8,
+                    take(2usize),
+                    be_u64,
+                    be_u64,
+                    be_u32,
+                    be_u16,
+                    take(2usize),
+                ))(input)?;
+            let (_, path) = take(path_length as usize)(rest)?;
+            let path = path_string(path);
+            match operation {
+                b'A' => PatchEntry::AddFile {
+                    path,
+                    offset,
+                    size,
+                    patch_offset: body_offset + 27 + u64::from(path_length),
+                },
+                b'D' => PatchEntry::DeleteFile { path },
+                b'R' => PatchEntry::RemoveAll { expansion },
+                b'M' => PatchEntry::MakeDirectoryTree { path },
+                _ => return Ok((input, None)),
+            }
+        }
+        b'I' => {
+            let (_, (add, _, _, target, hash, block_offset, data_file_id)) =
+                tuple((be_u8, be_u8, be_u8, pack_file, be_u64, be_u32, be_u32))(input)?;
+            PatchEntry::Index {
+                target,
+                add: add == b'A',
+                hash,
+                offset: u64::from(block_offset) << 7,
+                data_file_id,
+            }
+        }
+        _ => return Ok((input, None)),
+    };
+    Ok((input, Some(entry)))
+}
+
+/// The length of an `SQPK` chunk's body, and its command byte.
+fn command_header(input: &[u8]) -> IResult<&[u8], (u32, u8)> {
+    tuple((be_u32, be_u8))(input)
+}
+
+/// Parses the body of a target info command, returning the platform.
+fn target_platform(input: &[u8]) -> IResult<&[u8], (&[u8], u16)> {
+    tuple((take(8usize), be_u16))(input)
+}
+
+/// Parses the body of a directory chunk.
+fn length_prefixed_path(input: &[u8]) -> IResult<&[u8], String> {
+    let (input, length) = be_u32(input)?;
+    let (input, path) = take(length as usize)(input)?;
+    Ok((input, path_string(path)))
+}
+
+/// A chunk's tag, and the position and length of its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub tag: [u8; 4],
+    pub offset: u64,
+    pub size: u32,
+}
+
+pub struct PatchFile<R> {
+    reader: R,
+}
+
+impl PatchFile<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        PatchFile::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> PatchFile<R> {
+    /// Checks the magic number, and wraps the reader.
+    pub fn new(mut reader: R) -> Result<PatchFile<R>, Error> {
+        let mut magic = [0; 12];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        Ok(PatchFile { reader })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Lists the chunks of the patch, up to the end of file chunk. Chunk bodies are skipped.
+    pub fn chunks(&mut self) -> Result<Vec<Chunk>, Error> {
+        let mut chunks = Vec::new();
+        let mut position = MAGIC.len() as u64;
+        loop {
+            self.reader.seek(SeekFrom::Start(position))?;
+            let mut header = [0; 8];
+            match self.reader.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let size = u32::from_be_bytes(header[..4].try_into().unwrap());
+            let tag = header[4..].try_into().unwrap();
+            chunks.push(Chunk {
+                tag,
+                offset: position + 8,
+                size,
+            });
+            if &tag == b"EOF_" {
+                break;
+            }
+            // Skip the body and the checksum.
+            position += 8 + u64::from(size) + 4;
+        }
+        Ok(chunks)
+    }
+
+    /// Reads the start of a chunk's body, up to `limit` bytes.
+    fn read_body_start(&mut self, chunk: &Chunk, limit: usize) -> Result<Vec<u8>, Error> {
+        let mut buffer = vec![0; (chunk.size as usize).min(limit)];
+        self.reader.seek(SeekFrom::Start(chunk.offset))?;
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Returns the platform the patch targets, from its target info command, defaulting to
+    /// Windows.
+    pub fn platform(&mut self) -> Result<Platform, Error> {
+        for chunk in self.chunks()? {
+            if &chunk.tag != b"SQPK" {
+                continue;
+            }
+            let body = self.read_body_start(&chunk, 16)?;
+            if body.get(4) == Some(&b'T') {
+                let (_, (_, platform)) = target_platform(&body).finish()?;
+                return Ok(Platform::from_u16(platform));
+            }
+        }
+        Ok(Platform::Win32)
+    }
+
+    /// Lists every change the patch makes, in order, without applying any of them. Only the
+    /// headers of each command are read.
+    pub fn entries(&mut self) -> Result<Vec<PatchEntry>, Error> {
+        let mut entries = Vec::new();
+        for chunk in self.chunks()? {
+            match &chunk.tag {
+                b"SQPK" => {
+                    let body = self.read_body_start(&chunk, MAX_COMMAND_HEADER_SIZE)?;
+                    // The body starts with its own length, then the command byte.
+                    let (rest, (_, command)) = command_header(&body).finish()?;
+                    let (_, entry) = sqpack_command(command, rest, chunk.offset + 5).finish()?;
+                    if let Some(entry) = entry {
+                        entries.push(entry);
+                    }
+                }
+                b"ADIR" | b"DELD" => {
+                    let body = self.read_body_start(&chunk, MAX_COMMAND_HEADER_SIZE)?;
+                    let (_, path) = length_prefixed_path(&body).finish()?;
+                    entries.push(if &chunk.tag == b"ADIR" {
+                        PatchEntry::AddDirectory { path }
+                    } else {
+                        PatchEntry::DeleteDirectory { path }
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Reads a data entry out of the patch, given its location in a `.dat` file, and
+    /// decompresses it. Returns `None` unless the patch writes data at that location. The whole
+    /// entry must be contained in one command. If the location is written more than once, the
+    /// last write is used.
+    pub fn read_data(&mut self, target: PackFile, offset: u64) -> Result<Option<Vec<u8>>, Error> {
+        let entries = self.entries()?;
+        let found = entries.iter().rev().find_map(|entry| match entry {
+            PatchEntry::AddData {
+                target: entry_target,
+                offset: entry_offset,
+                size,
+                patch_offset,
+                ..
+            } if *entry_target == target
+                && (*entry_offset..*entry_offset + *size).contains(&offset) =>
+            {
+                Some((
+                    patch_offset + (offset - entry_offset),
+                    entry_offset + size - offset,
+                ))
+            }
+            _ => None,
+        });
+        let (position, length) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let mut data = vec![0; length as usize];
+        self.reader.seek(SeekFrom::Start(position))?;
+        self.reader.read_exact(&mut data)?;
+        Ok(Some(tomestone_sqpack::decompress_entry(&data)?))
+    }
+
+    /// Reads a file out of the patch, by path, without applying it. This only finds files that
+    /// the patch adds an index entry for, and whose data it writes in full.
+    pub fn extract(&mut self, path: &str) -> Result<Option<Vec<u8>>, Error> {
+        let hash = IndexHash1::hash(path);
+        let hash = u64::from(hash.folder_crc) << 32 | u64::from(hash.filename_crc);
+        let location = self
+            .entries()?
+            .into_iter()
+            .rev()
+            .find_map(|entry| match entry {
+                PatchEntry::Index {
+                    target,
+                    add: true,
+                    hash: entry_hash,
+                    offset,
+                    data_file_id,
+                } if entry_hash == hash => Some((
+                    PackFile {
+                        file_id: data_file_id,
+                        ..target
+                    },
+                    offset,
+                )),
+                _ => None,
+            });
+        match location {
+            Some((target, offset)) => self.read_data(target, offset),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use tomestone_sqpack::{IndexHash, IndexHash1};
+
+    use super::{HeaderTarget, PackFile, PatchEntry, PatchFile, Platform, MAGIC};
+
+    /// Appends a chunk to a patch. The checksum is left as zero, since it isn't checked.
+    pub(crate) fn chunk(patch: &mut Vec<u8>, tag: &[u8; 4], body: &[u8]) {
+        patch.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        patch.extend_from_slice(tag);
+        patch.extend_from_slice(body);
+        patch.extend_from_slice(&[0; 4]);
+    }
+
+    /// Appends an `SQPK` chunk holding one command.
+    pub(crate) fn sqpack_chunk(patch: &mut Vec<u8>, command: u8, body: &[u8]) {
+        let mut sqpack = ((body.len() + 5) as u32).to_be_bytes().to_vec();
+        sqpack.push(command);
+        sqpack.extend_from_slice(body);
+        chunk(patch, b"SQPK", &sqpack);
+    }
+
+    pub(crate) fn target(main_id: u16, sub_id: u16, file_id: u32) -> Vec<u8> {
+        let mut data = main_id.to_be_bytes().to_vec();
+        data.extend_from_slice(&sub_id.to_be_bytes());
+        data.extend_from_slice(&file_id.to_be_bytes());
+        data
+    }
+
+    /// Builds the body of a file operation command.
+    pub(crate) fn file_command(operation: u8, path: &str, offset: u64, size: u64) -> Vec<u8> {
+        let mut body = vec![operation, 0, 0];
+        body.extend_from_slice(&offset.to_be_bytes());
+        body.extend_from_slice(&size.to_be_bytes());
+        body.extend_from_slice(&(path.len() as u32 + 1).to_be_bytes());
+        body.extend_from_slice(&0u16.to_be_bytes());
+        body.extend_from_slice(&[0; 2]);
+        body.extend_from_slice(path.as_bytes());
+        body.push(0);
+        body
+    }
+
+    #[test]
+    fn entries() {
+        let mut patch = MAGIC.to_vec();
+        chunk(&mut patch, b"FHDR", &[0; 8]);
+
+        let mut target_info = vec![0; 3];
+        target_info.extend_from_slice(&2u16.to_be_bytes());
+        target_info.extend_from_slice(&[0; 22]);
+        sqpack_chunk(&mut patch, b'T', &target_info);
+
+        let mut add = vec![0; 3];
+        add.extend_from_slice(&target(0x0a, 0x0100, 2));
+        for value in [4u32, 1, 2] {
+            add.extend_from_slice(&value.to_be_bytes());
+        }
+        let data_position = patch.len() + 8 + 5 + 23;
+        add.extend_from_slice(&[0xab; 128]);
+        sqpack_chunk(&mut patch, b'A', &add);
+
+        let mut header = vec![b'I', b'V', 0];
+        header.extend_from_slice(&target(0x0a, 0, 0));
+        header.extend_from_slice(&[0; 1024]);
+        sqpack_chunk(&mut patch, b'H', &header);
+
+        let mut file = file_command(b'A', "boot/ffxivboot.exe", 0, 5);
+        file.extend_from_slice(&[0; 128]);
+        sqpack_chunk(&mut patch, b'F', &file);
+        sqpack_chunk(
+            &mut patch,
+            b'F',
+            &file_command(b'D', "sqpack/ffxiv/0a0000.win32.dat9", 0, 0),
+        );
+
+        let mut directory = 5u32.to_be_bytes().to_vec();
+        directory.extend_from_slice(b"movie");
+        chunk(&mut patch, b"ADIR", &directory);
+        chunk(&mut patch, b"EOF_", &[]);
+
+        let mut patch_file = PatchFile::new(Cursor::new(&patch)).unwrap();
+        assert_eq!(patch_file.platform().unwrap(), Platform::Ps4);
+        let entries = patch_file.entries().unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(
+            entries[0],
+            PatchEntry::AddData {
+                target: PackFile {
+                    main_id: 0x0a,
+                    sub_id: 0x0100,
+                    file_id: 2,
+                },
+                offset: 4 * 128,
+                size: 128,
+                delete_size: 256,
+                patch_offset: data_position as u64,
+            }
+        );
+        assert_eq!(patch[data_position], 0xab);
+        assert_eq!(
+            entries[0].target_path(Platform::Win32),
+            "sqpack/ex1/0a0100.win32.dat2"
+        );
+        assert!(matches!(
+            entries[1],
+            PatchEntry::Header {
+                file: HeaderTarget::Index,
+                kind: b'V',
+                ..
+            }
+        ));
+        assert_eq!(
+            entries[1].target_path(Platform::Win32),
+            "sqpack/ffxiv/0a0000.win32.index"
+        );
+        match &entries[2] {
+            PatchEntry::AddFile {
+                path,
+                size,
+                patch_offset,
+                ..
+            } => {
+                assert_eq!(path, "boot/ffxivboot.exe");
+                assert_eq!(*size, 5);
+                assert_eq!(patch[*patch_offset as usize - 1], 0);
+            }
+            entry => panic!("{:?}", entry),
+        }
+        assert_eq!(
+            entries[3],
+            PatchEntry::DeleteFile {
+                path: "sqpack/ffxiv/0a0000.win32.dat9".to_string()
+            }
+        );
+        assert_eq!(
+            entries[4],
+            PatchEntry::AddDirectory {
+                path: "movie".to_string()
+            }
+        );
+
+        assert!(PatchFile::new(Cursor::new(b"not a patch!")).is_err());
+    }
+
+    /// Builds a binary data entry, with one uncompressed block.
+    pub(crate) fn data_entry(contents: &[u8]) -> Vec<u8> {
+        let mut entry = Vec::new();
+        for value in [0x80u32, 2, contents.len() as u32, 0, 1] {
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+        entry.extend_from_slice(&1u16.to_le_bytes());
+        entry.extend_from_slice(&[0; 2]);
+        entry.extend_from_slice(&0u32.to_le_bytes());
+        entry.extend_from_slice(&0x80u16.to_le_bytes());
+        entry.extend_from_slice(&(contents.len() as u16).to_le_bytes());
+        entry.resize(0x80, 0);
+        for value in [16u32, 0, 32000, contents.len() as u32] {
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+        entry.extend_from_slice(contents);
+        entry.resize(0x100, 0);
+        entry
+    }
+
+    #[test]
+    fn extract() {
+        let path = "exd/root.exl";
+        let mut patch = MAGIC.to_vec();
+
+        let mut add = vec![0; 3];
+        add.extend_from_slice(&target(0x0a, 0, 1));
+        for value in [2u32, 2, 0] {
+            add.extend_from_slice(&value.to_be_bytes());
+        }
+        add.extend_from_slice(&data_entry(b"EXLT,2"));
+        sqpack_chunk(&mut patch, b'A', &add);
+
+        let hash = IndexHash1::hash(path);
+        let mut index = vec![b'A', 0, 0];
+        index.extend_from_slice(&target(0x0a, 0, 0));
+        index.extend_from_slice(
+            &(u64::from(hash.folder_crc) << 32 | u64::from(hash.filename_crc)).to_be_bytes(),
+        );
+        index.extend_from_slice(&2u32.to_be_bytes());
+        index.extend_from_slice(&1u32.to_be_bytes());
+        sqpack_chunk(&mut patch, b'I', &index);
+        chunk(&mut patch, b"EOF_", &[]);
+
+        let mut patch_file = PatchFile::new(Cursor::new(&patch)).unwrap();
+        assert_eq!(patch_file.extract(path).unwrap().unwrap(), b"EXLT,2");
+        assert_eq!(patch_file.extract("exd/item.exh").unwrap(), None);
+        let target = PackFile {
+            main_id: 0x0a,
+            sub_id: 0,
+            file_id: 1,
+        };
+        assert_eq!(patch_file.read_data(target, 0).unwrap(), None);
+        assert!(patch_file.read_data(target, 0x180).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! A database of known patches, and the order they apply in.
+//!
+//! Each patch names the version it applies on top of, so every repository's patches form a
+//! chain from its first patch to its latest one. The database is stored as JSON, so it can be
+//! shared and merged. It also records the expected hashes of the boot executables for each boot
+//! version.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cdn::{PatchInfo, Repository},
+    install::BootFileHash,
+    Error,
+};
+
+/// One patch, and how to download and check it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownPatch {
+    pub repository: Repository,
+    /// The version the game is at after this patch, including any `D` or `H` prefix.
+    pub version: String,
+    /// The version this patch applies on top of, or `None` for the first patch.
+    pub previous: Option<String>,
+    pub size: u64,
+    pub hash_block_size: u64,
+    pub hashes: Vec<String>,
+}
+
+impl KnownPatch {
+    /// Returns the information needed to download this patch, if its repository's location on
+    /// the patch server is known.
+    pub fn patch_info(&self) -> Option<PatchInfo> {
+        Some(PatchInfo {
+            url: self.repository.patch_url(&self.version)?,
+            size: self.size,
+            hash_block_size: self.hash_block_size,
+            hashes: self.hashes.clone(),
+        })
+    }
+}
+
+/// Compares version strings, ignoring their prefixes. Patch file names start with `D` or `H`,
+/// but the `.ver` files of an installation don't.
+fn version_key(version: &str) -> &str {
+    version.trim_start_matches(['D', 'H'])
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionDatabase {
+    patches: Vec<KnownPatch>,
+    /// Expected boot executable hashes, by boot version.
+    #[serde(default)]
+    boot_files: BTreeMap<String, Vec<BootFileHash>>,
+}
+
+impl VersionDatabase {
+    pub fn new() -> VersionDatabase {
+        VersionDatabase::default()
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<VersionDatabase, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn patches(&self) -> &[KnownPatch] {
+        &self.patches
+    }
+
+    /// Adds a patch, replacing any patch with the same repository and version.
+    pub fn insert(&mut self, patch: KnownPatch) {
+        match self.patches.iter_mut().find(|known| {
+            known.repository == patch.repository
+                && version_key(&known.version) == version_key(&patch.version)
+        }) {
+            Some(known) => *known = patch,
+            None => self.patches.push(patch),
+        }
+    }
+
+    /// Adds every patch from another database. Patches in the other database take precedence.
+    pub fn merge(&mut self, other: VersionDatabase) {
+        for patch in other.patches {
+            self.insert(patch);
+        }
+        self.boot_files.extend(other.boot_files);
+    }
+
+    pub fn boot_files(&self, boot_version: &str) -> Option<&[BootFileHash]> {
+        self.boot_files.get(boot_version).map(Vec::as_slice)
+    }
+
+    /// Records the expected boot executable hashes for a boot version.
+    pub fn set_boot_files(&mut self, boot_version: &str, hashes: Vec<BootFileHash>) {
+        self.boot_files.insert(boot_version.to_string(), hashes);
+    }
+
+    /// Finds the patch that brings a repository to a version. The version may be given with or
+    /// without its prefix.
+    pub fn get(&self, repository: Repository, version: &str) -> Option<&KnownPatch> {
+        self.patches.iter().find(|patch| {
+            patch.repository == repository && version_key(&patch.version) == version_key(version)
+        })
+    }
+
+    /// Returns the latest patch of a repository: one that no other patch applies on top of. If
+    /// there are several, because the chain is incomplete, the one with the greatest version is
+    /// returned.
+    pub fn latest(&self, repository: Repository) -> Option<&KnownPatch> {
+        let patches = self
+            .patches
+            .iter()
+            .filter(|patch| patch.repository == repository);
+        let superseded = patches
+            .clone()
+            .filter_map(|patch| patch.previous.as_deref().map(version_key))
+            .collect::<BTreeSet<_>>();
+        patches
+            .filter(|patch| !superseded.contains(version_key(&patch.version)))
+            .max_by(|a, b| version_key(&a.version).cmp(version_key(&b.version)))
+    }
+
+    /// Returns the patches needed to bring a repository from version `from` to version `to`, in
+    /// the order they must be applied. If `from` is `None`, the chain starts from the first
+    /// patch. Versions may be given with or without their prefixes, so `from` can be read from an
+    /// installation's `.ver` file. Returns `None` if the chain has a gap.
+    pub fn chain(
+        &self,
+        repository: Repository,
+        from: Option<&str>,
+        to: &str,
+    ) -> Option<Vec<&KnownPatch>> {
+        let mut chain = Vec::new();
+        let from = from.map(version_key);
+        let mut version = to;
+        while Some(version_key(version)) != from {
+            let patch = self.get(repository, version)?;
+            chain.push(patch);
+            match &patch.previous {
+                Some(previous) => version = previous,
+                None if from.is_none() => break,
+                None => return None,
+            }
+            if chain.len() > self.patches.len() {
+                // The chain has a cycle.
+                return None;
+            }
+        }
+        chain.reverse();
+        Some(chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KnownPatch, VersionDatabase};
+    use crate::{cdn::Repository, install::BootFileHash};
+
+    fn patch(repository: Repository, version: &str, previous: Option<&str>) -> KnownPatch {
+        KnownPatch {
+            repository,
+            version: version.to_string(),
+            previous: previous.map(str::to_string),
+            size: 100,
+            hash_block_size: 50_000_000,
+            hashes: vec!["da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string()],
+        }
+    }
+
+    #[test]
+    fn version_database() {
+        let mut database = VersionDatabase::new();
+        database.insert(patch(Repository::Game, "H2017.06.06.0000.0001a", None));
+        database.insert(patch(
+            Repository::Game,
+            "D2017.06.10.0000.0000",
+            Some("H2017.06.06.0000.0001a"),
+        ));
+        database.insert(patch(
+            Repository::Game,
+            "D2017.07.01.0000.0000",
+            Some("D2017.06.10.0000.0000"),
+        ));
+        database.insert(patch(
+            Repository::Expansion(1),
+            "D2017.06.06.0000.0000",
+            None,
+        ));
+
+        assert_eq!(
+            database.latest(Repository::Game).unwrap().version,
+            "D2017.07.01.0000.0000"
+        );
+        let chain = database
+            .chain(Repository::Game, None, "D2017.07.01.0000.0000")
+            .unwrap();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[0].version, "H2017.06.06.0000.0001a");
+        let chain = database
+            .chain(
+                Repository::Game,
+                Some("D2017.06.10.0000.0000"),
+                "D2017.07.01.0000.0000",
+            )
+            .unwrap();
+        assert_eq!(chain.len(), 1);
+        // Installed versions, from `.ver` files, have no prefix.
+        let chain = database
+            .chain(
+                Repository::Game,
+                Some("2017.06.10.0000.0000"),
+                "D2017.07.01.0000.0000",
+            )
+            .unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].version, "D2017.07.01.0000.0000");
+        assert!(database
+            .chain(
+                Repository::Game,
+                Some("2017.07.01.0000.0000"),
+                "D2017.07.01.0000.0000"
+            )
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            database
+                .get(Repository::Game, "2017.06.06.0000.0001a")
+                .unwrap()
+                .version,
+            "H2017.06.06.0000.0001a"
+        );
+        assert!(database
+            .chain(
+                Repository::Game,
+                Some("D2016.01.01.0000.0000"),
+                "D2017.07.01.0000.0000"
+            )
+            .is_none());
+        assert!(database
+            .chain(Repository::Expansion(1), None, "D2017.07.01.0000.0000")
+            .is_none());
+
+        let info = chain[0].patch_info().unwrap();
+        assert_eq!(
+            info.url,
+            "http://patch-dl.ffxiv.com/game/4e9a232b/D2017.07.01.0000.0000.patch"
+        );
+
+        database.set_boot_files(
+            "2017.06.06.0000.0001",
+            vec![BootFileHash {
+                name: "ffxivboot.exe".to_string(),
+                size: 0,
+                sha1: "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string(),
+            }],
+        );
+        assert_eq!(
+            database.boot_files("2017.06.06.0000.0001").unwrap()[0].name,
+            "ffxivboot.exe"
+        );
+        assert!(database.boot_files("2017.06.06.0000.0000").is_none());
+        assert!(VersionDatabase::read(&br#"{"patches": []}"#[..])
+            .unwrap()
+            .boot_files("2017.06.06.0000.0001")
+            .is_none());
+
+        let mut json = Vec::new();
+        database.write(&mut json).unwrap();
+        assert!(String::from_utf8_lossy(&json).contains("\"ex1\""));
+        let mut read = VersionDatabase::read(&json[..]).unwrap();
+        assert_eq!(read, database);
+
+        let mut replacement = patch(Repository::Expansion(1), "D2017.06.06.0000.0000", None);
+        replacement.size = 200;
+        let mut other = VersionDatabase::new();
+        other.insert(replacement);
+        read.merge(other);
+        assert_eq!(read.patches().len(), 4);
+        assert_eq!(
+            read.get(Repository::Expansion(1), "D2017.06.06.0000.0000")
+                .unwrap()
+                .size,
+            200
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+use tomestone_exdf::{Dataset, Language, RootList, Value};
+use tomestone_sqpack::{DataFileSet, GameData};
+use tomestone_string_interp::Text;
+
+#[derive(Debug)]
+pub enum Error {
+    Exdf(tomestone_exdf::Error),
+    Text(tomestone_string_interp::Error),
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl From<tomestone_exdf::Error> for Error {
+    fn from(e: tomestone_exdf::Error) -> Error {
+        Error::Exdf(e)
+    }
+}
+
+impl From<tomestone_string_interp::Error> for Error {
+    fn from(e: tomestone_string_interp::Error) -> Error {
+        Error::Text(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Exdf(e) => e.fmt(f),
+            Error::Text(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+            Error::Json(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Identifies one string cell in a sheet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub sheet: String,
+    pub row: u32,
+    pub sub_row: u16,
+    pub column: usize,
+}
+
+/// A string from the game, rendered as plain text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    pub location: Location,
+    pub text: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LanguageIndex {
+    documents: Vec<Document>,
+    /// Maps each token to the sorted indices of the documents that contain it.
+    postings: BTreeMap<String, Vec<u32>>,
+}
+
+/// An inverted index over the text of every string in the game's sheets, with a separate index
+/// for each language.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    /// Keyed by language short code.
+    languages: BTreeMap<String, LanguageIndex>,
+}
+
+impl SearchIndex {
+    pub fn new() -> SearchIndex {
+        SearchIndex::default()
+    }
+
+    /// Extracts every string from every sheet in the given languages, and indexes them. Sheets
+    /// that are not available in a language are skipped, while sheets without any language are
+    /// indexed under every language.
+    pub fn build(
+        game_data: &GameData,
+        data_file_set: &mut DataFileSet,
+        languages: &[Language],
+    ) -> Result<SearchIndex, Error> {
+        let mut index = SearchIndex::new();
+        let root_list = RootList::open(game_data, data_file_set)?;
+        for name in root_list.iter() {
+            for language in languages.iter() {
+                let dataset = match Dataset::load(game_data, data_file_set, name, *language) {
+                    Ok(dataset) => dataset,
+                    Err(tomestone_exdf::Error::LanguageUnavailable) => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                for page in dataset.page_iter() {
+                    for res in page {
+                        let row = res?;
+                        for sub_row in row.sub_rows.iter() {
+                            for (column, value) in sub_row.cells.iter().enumerate() {
+                                let data = match value {
+                                    Value::String(data) => data,
+                                    Value::StringOwned(data) => data.as_slice(),
+                                    _ => continue,
+                                };
+                                let text = Text::parse(data)?.to_plain_text();
+                                let location = Location {
+                                    sheet: name.to_string(),
+                                    row: row.number,
+                                    sub_row: sub_row.number,
+                                    column,
+                                };
+                                index.insert(*language, location, text);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    /// Adds one string to the index. Strings without any searchable text are ignored.
+    pub fn insert(&mut self, language: Language, location: Location, text: String) {
+        let mut tokens = tokenize(&text).collect::<Vec<_>>();
+        if tokens.is_empty() {
+            return;
+        }
+        tokens.sort();
+        tokens.dedup();
+
+        let language_index = self
+            .languages
+            .entry(language.short_code().to_string())
+            .or_default();
+        let document_id = u32::try_from(language_index.documents.len()).unwrap();
+        language_index.documents.push(Document { location, text });
+        for token in tokens {
+            language_index
+                .postings
+                .entry(token)
+                .or_default()
+                .push(document_id);
+        }
+    }
+
+    /// Finds all strings in the given language that contain every word of the query. Matching
+    /// ignores case and punctuation between words. Results are returned in the order they were
+    /// indexed.
+    pub fn search(&self, query: &str, language: Language) -> Vec<&Document> {
+        let language_index = match self.languages.get(language.short_code()) {
+            Some(language_index) => language_index,
+            None => return Vec::new(),
+        };
+
+        let mut postings = Vec::new();
+        for token in tokenize(query) {
+            match language_index.postings.get(&token) {
+                Some(list) => postings.push(list),
+                None => return Vec::new(),
+            }
+        }
+        postings.sort_by_key(|list| list.len());
+        let (first, rest) = match postings.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+
+        // Tokens only identify candidates, since words written without spaces are indexed one
+        // character at a time. Confirm that each term of the query appears intact.
+        let terms = query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        first
+            .iter()
+            .filter(|id| rest.iter().all(|list| list.binary_search(id).is_ok()))
+            .map(|id| &language_index.documents[usize::try_from(*id).unwrap()])
+            .filter(|document| {
+                let text = document.text.to_lowercase();
+                terms.iter().all(|term| text.contains(term.as_str()))
+            })
+            .collect()
+    }
+
+    /// Returns the number of strings indexed in the given language.
+    pub fn document_count(&self, language: Language) -> usize {
+        self.languages
+            .get(language.short_code())
+            .map_or(0, |language_index| language_index.documents.len())
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<SearchIndex, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Returns true for characters of scripts that don't separate words with spaces. Each of these
+/// characters is indexed as a separate token.
+fn is_unspaced_script(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // Hiragana and Katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK Unified Ideographs Extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK Unified Ideographs
+        | '\u{ff66}'..='\u{ff9f}' // Halfwidth Katakana
+    )
+}
+
+/// Splits text into lowercase tokens. Runs of alphanumeric characters form one token, except in
+/// scripts that don't use spaces, where each character is a token.
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || loop {
+        let (start, c) = chars.next()?;
+        if is_unspaced_script(c) {
+            return Some(c.to_lowercase().collect());
+        }
+        if !c.is_alphanumeric() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some((i, c)) = chars.peek() {
+            if !c.is_alphanumeric() || is_unspaced_script(*c) {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        return Some(text[start..end].to_lowercase());
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_exdf::Language;
+    use tomestone_sqpack::GameData;
+
+    use super::{tokenize, Location, SearchIndex};
+
+    fn location(row: u32) -> Location {
+        Location {
+            sheet: "Item".to_string(),
+            row,
+            sub_row: 0,
+            column: 0,
+        }
+    }
+
+    #[test]
+    fn tokenize_words() {
+        assert_eq!(
+            tokenize("Ifrit's Hide, Grade-2").collect::<Vec<_>>(),
+            ["ifrit", "s", "hide", "grade", "2"]
+        );
+        assert_eq!(
+            tokenize("ハイポーション").collect::<Vec<_>>(),
+            ["ハ", "イ", "ポ", "ー", "シ", "ョ", "ン"]
+        );
+        assert_eq!(tokenize("Lv.50 剣").collect::<Vec<_>>(), ["lv", "50", "剣"]);
+    }
+
+    #[test]
+    fn search() {
+        let mut index = SearchIndex::new();
+        index.insert(Language::English, location(1), "Hi-Potion".to_string());
+        index.insert(Language::English, location(2), "Potion".to_string());
+        index.insert(
+            Language::English,
+            location(3),
+            "Potion of Strength".to_string(),
+        );
+        index.insert(Language::English, location(4), "".to_string());
+        index.insert(
+            Language::Japanese,
+            location(1),
+            "ハイポーション".to_string(),
+        );
+        index.insert(Language::Japanese, location(2), "ポーション".to_string());
+        assert_eq!(index.document_count(Language::English), 3);
+
+        let rows = |query: &str, language: Language| -> Vec<u32> {
+            index
+                .search(query, language)
+                .iter()
+                .map(|document| document.location.row)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows("potion", Language::English), [1, 2, 3]);
+        assert_eq!(rows("STRENGTH potion", Language::English), [3]);
+        assert!(rows("elixir", Language::English).is_empty());
+        assert!(rows("", Language::English).is_empty());
+        assert_eq!(rows("ハイポーション", Language::Japanese), [1]);
+        assert_eq!(rows("ポーション", Language::Japanese), [1, 2]);
+        assert!(rows("ションポー", Language::Japanese).is_empty());
+        assert!(rows("potion", Language::German).is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut index = SearchIndex::new();
+        index.insert(Language::English, location(1), "Hi-Potion".to_string());
+        index.insert(Language::English, location(2), "Potion".to_string());
+
+        let mut buffer = Vec::new();
+        index.write(&mut buffer).unwrap();
+        let index = SearchIndex::read(buffer.as_slice()).unwrap();
+        let results = index.search("potion", Language::English);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].location, location(2));
+        assert_eq!(results[1].text, "Potion");
+    }
+
+    #[test]
+    #[ignore = "slow test"]
+    fn search_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let index =
+            SearchIndex::build(&game_data, &mut data_file_set, &[Language::English]).unwrap();
+        assert!(index
+            .search("potion", Language::English)
+            .iter()
+            .any(|document| document.location.sheet == "Item"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Reading of the game's `.scd` sound banks, and naming them from the sheets that use them.
+//!
+//! An `.scd` file starts with a `SEDBSSCF` header, which gives the offset of a table header.
+//! The table header points to several tables of offsets, one of which lists the bank's audio
+//! entries. Each audio entry has a 32 byte header describing the encoded stream that follows.
+
+use std::fmt;
+
+use nom::{
+    bytes::complete::tag,
+    multi::count,
+    number::complete::{le_i32, le_u16, le_u32},
+    sequence::tuple,
+    Finish, IResult,
+};
+
+pub mod names;
+
+#[derive(Debug)]
+pub enum Error {
+    Nom(nom::error::ErrorKind),
+    Exdf(tomestone_exdf::Error),
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error {
+    fn from(e: nom::error::Error<&'a [u8]>) -> Error {
+        Error::Nom(e.code)
+    }
+}
+
+impl From<tomestone_exdf::Error> for Error {
+    fn from(e: tomestone_exdf::Error) -> Error {
+        Error::Exdf(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Nom(e) => write!(f, "parsing error: {:?}", e),
+            Error::Exdf(e) => e.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// A placeholder entry with no audio.
+    Empty,
+    Pcm,
+    Vorbis,
+    MsAdpcm,
+    Other(i32),
+}
+
+impl Codec {
+    fn from_i32(value: i32) -> Codec {
+        match value {
+            -1 => Codec::Empty,
+            0x01 => Codec::Pcm,
+            0x06 => Codec::Vorbis,
+            0x0c => Codec::MsAdpcm,
+            value => Codec::Other(value),
+        }
+    }
+}
+
+/// One audio stream in a sound bank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioEntry {
+    /// Offset of the entry's header from the start of the file.
+    pub offset: u32,
+    /// Size of the encoded stream, in bytes.
+    pub stream_size: u32,
+    pub channels: u32,
+    pub sample_rate: u32,
+    pub codec: Codec,
+    /// Loop points, in bytes of the encoded stream. Both are zero if the stream does not loop.
+    pub loop_start: u32,
+    pub loop_end: u32,
+}
+
+impl AudioEntry {
+    pub fn loops(&self) -> bool {
+        self.loop_end > 0
+    }
+}
+
+fn entry_header(input: &[u8], offset: u32) -> IResult<&[u8], AudioEntry> {
+    let (input, (stream_size, channels, sample_rate, codec, loop_start, loop_end)) =
+        tuple((le_u32, le_u32, le_u32, le_i32, le_u32, le_u32))(input)?;
+    Ok((
+        input,
+        AudioEntry {
+            offset,
+            stream_size,
+            channels,
+            sample_rate,
+            codec: Codec::from_i32(codec),
+            loop_start,
+            loop_end,
+        },
+    ))
+}
+
+fn file_header(input: &[u8]) -> IResult<&[u8], u16> {
+    let (input, (_, _, _, table_header_offset)) =
+        tuple((tag(b"SEDBSSCF"), le_u32, le_u16, le_u16))(input)?;
+    Ok((input, table_header_offset))
+}
+
+/// Returns the number of audio entries, and the offset of their offset table.
+fn table_header(input: &[u8]) -> IResult<&[u8], (u16, u32)> {
+    let (input, (_, _, entry_count, _, _, entry_table_offset)) =
+        tuple((le_u16, le_u16, le_u16, le_u16, le_u32, le_u32))(input)?;
+    Ok((input, (entry_count, entry_table_offset)))
+}
+
+fn offsets(input: &[u8], length: usize) -> IResult<&[u8], Vec<u32>> {
+    count(le_u32, length)(input)
+}
+
+fn at(data: &[u8], offset: usize) -> Result<&[u8], Error> {
+    data.get(offset..)
+        .ok_or(Error::Nom(nom::error::ErrorKind::Eof))
+}
+
+/// Lists the audio entries of a sound bank, in order.
+pub fn audio_entries(data: &[u8]) -> Result<Vec<AudioEntry>, Error> {
+    let (_, table_header_offset) = file_header(data).finish()?;
+    let (_, (entry_count, entry_table_offset)) =
+        table_header(at(data, usize::from(table_header_offset))?).finish()?;
+    let (_, entry_offsets) = offsets(
+        at(data, entry_table_offset as usize)?,
+        usize::from(entry_count),
+    )
+    .finish()?;
+    entry_offsets
+        .into_iter()
+        .map(|offset| {
+            let (_, entry) = entry_header(at(data, offset as usize)?, offset).finish()?;
+            Ok(entry)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_sqpack::GameData;
+
+    use super::{audio_entries, Codec};
+
+    #[test]
+    fn sound_bank() {
+        let mut data = b"SEDBSSCF".to_vec();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&0x30u16.to_le_bytes());
+        data.resize(0x30, 0);
+        for value in [0u16, 0, 2, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0x40u32.to_le_bytes());
+        data.resize(0x40, 0);
+        data.extend_from_slice(&0x50u32.to_le_bytes());
+        data.extend_from_slice(&0x70u32.to_le_bytes());
+        data.resize(0x50, 0);
+        for value in [1000u32, 2, 44100, 6, 0, 0, 0, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [0u32, 0, 0, u32::MAX, 0, 0, 0, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let entries = audio_entries(&data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].codec, Codec::Vorbis);
+        assert_eq!(entries[0].sample_rate, 44100);
+        assert!(!entries[0].loops());
+        assert_eq!(entries[1].codec, Codec::Empty);
+
+        assert!(audio_entries(&data[..0x48]).is_err());
+        assert!(audio_entries(b"SEDBSSCF").is_err());
+    }
+
+    #[test]
+    fn sound_bank_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let data = game_data
+            .lookup_path_data(&mut data_file_set, "music/ex1/bgm_ex1_deep01.scd")
+            .unwrap()
+            .unwrap();
+        let entries = audio_entries(&data).unwrap();
+        assert!(!entries.is_empty());
+        assert!(entries.iter().any(|entry| entry.codec == Codec::Vorbis));
+    }
+}
@@ -0,0 +1,195 @@
+//! Finds names for sound files, from the sheets that refer to them.
+//!
+//! Sheets refer to sound banks by path, in string columns. Most such rows also have a name or
+//! description in another column, which is used as the sound's name. Orchestrion rolls are the
+//! exception: the `OrchestrionPath` sheet holds the paths, and the `Orchestrion` sheet holds the
+//! names, under the same row numbers.
+
+use std::collections::{BTreeMap, HashMap};
+
+use tomestone_exdf::{Dataset, Language, RootList, Value};
+use tomestone_sqpack::{DataFileSet, GameData};
+use tomestone_string_interp::plain_text;
+
+use crate::Error;
+
+/// A row that refers to a sound file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SoundName {
+    pub sheet: String,
+    pub row: u32,
+    /// The row's name, as plain text, or an empty string if no name was found.
+    pub name: String,
+}
+
+/// Returns true if a string cell holds the path of a sound bank.
+pub fn is_sound_path(data: &[u8]) -> bool {
+    let lowercase = data.to_ascii_lowercase();
+    (lowercase.starts_with(b"sound/") || lowercase.starts_with(b"music/"))
+        && lowercase.ends_with(b".scd")
+}
+
+/// Picks a name for a row from its string cells: the first one that isn't empty or a path.
+fn row_name(cells: &[Value<'_>]) -> String {
+    cells
+        .iter()
+        .filter_map(|cell| match cell {
+            Value::String(data) if !data.is_empty() && !data.contains(&b'/') => {
+                Some(plain_text(data))
+            }
+            _ => None,
+        })
+        .find(|name| !name.trim().is_empty())
+        .unwrap_or_default()
+}
+
+/// Reads the first column of every row of a sheet, keyed by row number.
+fn first_column(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    sheet: &str,
+    language: Language,
+) -> Result<HashMap<u32, Vec<u8>>, Error> {
+    let dataset = Dataset::load(game_data, data_file_set, sheet, language)?;
+    let mut values = HashMap::new();
+    for page in dataset.page_iter() {
+        for res in page {
+            let row = res?;
+            if let Some(Value::String(data)) = row
+                .sub_rows
+                .first()
+                .and_then(|sub_row| sub_row.cells.first())
+            {
+                values.insert(row.number, data.to_vec());
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Scans every sheet for sound paths, and returns the rows referring to each path. Paths are
+/// lowercase. Names are in the given language, where the sheet has one.
+pub fn sound_names(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+    language: Language,
+) -> Result<BTreeMap<String, Vec<SoundName>>, Error> {
+    let mut names: BTreeMap<String, Vec<SoundName>> = BTreeMap::new();
+    let root_list = RootList::open(game_data, data_file_set)?;
+    for sheet in root_list.iter() {
+        let dataset = match Dataset::load(game_data, data_file_set, sheet, language) {
+            Ok(dataset) => dataset,
+            Err(tomestone_exdf::Error::LanguageUnavailable) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for page in dataset.page_iter() {
+            for res in page {
+                let row = res?;
+                for sub_row in row.sub_rows.iter() {
+                    for cell in sub_row.cells.iter() {
+                        if let Value::String(data) = cell {
+                            if is_sound_path(data) {
+                                names
+                                    .entry(String::from_utf8_lossy(data).to_lowercase())
+                                    .or_default()
+                                    .push(SoundName {
+                                        sheet: sheet.to_string(),
+                                        row: row.number,
+                                        name: row_name(&sub_row.cells),
+                                    });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let paths = first_column(game_data, data_file_set, "OrchestrionPath", language)?;
+    let titles = first_column(game_data, data_file_set, "Orchestrion", language)?;
+    for (row, path) in paths {
+        if let (true, Some(title)) = (is_sound_path(&path), titles.get(&row)) {
+            let name = plain_text(title);
+            if name.is_empty() {
+                continue;
+            }
+            names
+                .entry(String::from_utf8_lossy(&path).to_lowercase())
+                .or_default()
+                .push(SoundName {
+                    sheet: "Orchestrion".to_string(),
+                    row,
+                    name,
+                });
+        }
+    }
+
+    for rows in names.values_mut() {
+        rows.sort();
+        rows.dedup();
+    }
+    Ok(names)
+}
+
+/// Picks the most useful name for a sound from the rows referring to it: the first named row,
+/// or the first row's sheet and row number.
+pub fn best_name(rows: &[SoundName]) -> Option<String> {
+    rows.iter()
+        .find(|row| !row.name.is_empty())
+        .map(|row| row.name.clone())
+        .or_else(|| rows.first().map(|row| format!("{} {}", row.sheet, row.row)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_exdf::{Language, Value};
+    use tomestone_sqpack::GameData;
+
+    use super::{best_name, is_sound_path, row_name, sound_names, SoundName};
+
+    #[test]
+    fn names() {
+        assert!(is_sound_path(b"music/ffxiv/BGM_System_Title.scd"));
+        assert!(is_sound_path(b"sound/system/SE_UI.scd"));
+        assert!(!is_sound_path(b"ui/icon/000000/000001.tex"));
+
+        let cells = [
+            Value::String(b"sound/battle/se_skill.scd"),
+            Value::U8(1),
+            Value::String(b""),
+            Value::String(b"Skill Chime"),
+        ];
+        assert_eq!(row_name(&cells), "Skill Chime");
+        assert_eq!(row_name(&cells[..2]), "");
+
+        let unnamed = SoundName {
+            sheet: "BGM".to_string(),
+            row: 3,
+            name: String::new(),
+        };
+        let named = SoundName {
+            sheet: "Orchestrion".to_string(),
+            row: 1,
+            name: "Prelude".to_string(),
+        };
+        assert_eq!(
+            best_name(&[unnamed.clone(), named]).as_deref(),
+            Some("Prelude")
+        );
+        assert_eq!(best_name(&[unnamed]).as_deref(), Some("BGM 3"));
+        assert_eq!(best_name(&[]), None);
+    }
+
+    #[test]
+    #[ignore = "slow test"]
+    fn names_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let names = sound_names(&game_data, &mut data_file_set, Language::English).unwrap();
+        assert!(names.keys().any(|path| path.starts_with("music/")));
+        assert!(names
+            .values()
+            .flatten()
+            .any(|name| name.sheet == "Orchestrion"));
+    }
+}
@@ -0,0 +1,52 @@
+//! Benchmarks for the checksums used when verifying data packs.
+//!
+//! Compare `cargo bench -p tomestone-sqpack` against
+//! `cargo bench -p tomestone-sqpack --features asm` (or `--features asm,nightly` on aarch64 with
+//! a nightly compiler) to measure the hardware-accelerated backends.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use tomestone_sqpack::{crc32, sha1, IndexHash, IndexHash1, IndexHash2};
+
+const SIZES: [usize; 3] = [0x3c0, 64 * 1024, 8 * 1024 * 1024];
+
+fn test_data(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 31 + i / 256) as u8).collect()
+}
+
+fn bench_crc32(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc32");
+    for size in SIZES {
+        let data = test_data(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| crc32(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_sha1(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sha1");
+    for size in SIZES {
+        let data = test_data(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| sha1(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_path_hashes(c: &mut Criterion) {
+    let path = "bgcommon/hou/indoor/general/0019/asset/fun_b0_m0019.sgb";
+    c.bench_function("index_hash_1", |b| {
+        b.iter(|| IndexHash1::hash(black_box(path)))
+    });
+    c.bench_function("index_hash_2", |b| {
+        b.iter(|| IndexHash2::hash(black_box(path)))
+    });
+}
+
+criterion_group!(benches, bench_crc32, bench_sha1, bench_path_hashes);
+criterion_main!(benches);
@@ -0,0 +1,194 @@
+//! A record of the files looked up through a [`GameData`](crate::GameData), enabled with
+//! [`GameDataBuilder::access_log`](crate::GameDataBuilder::access_log).
+//!
+//! Every lookup by path or hash is recorded with the time it was made and whether it was found.
+//! This shows which files an export pipeline actually touched, which is useful for building a
+//! minimal set of path patterns, and for finding lookups that unexpectedly miss.
+
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{IndexHash1, IndexHash2, SqPackId};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessTarget {
+    /// A lookup by path, after normalization.
+    Path(String),
+    Hash1(IndexHash1),
+    Hash2(IndexHash2),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessOutcome {
+    /// The file was found in the given packs. Lookups by path stop at the first match, while
+    /// lookups by hash may find several files.
+    Found(Vec<SqPackId>),
+    NotFound,
+    /// The lookup failed, usually because an index file couldn't be read.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessRecord {
+    /// The time of the lookup, since the log was created.
+    pub elapsed: Duration,
+    pub target: AccessTarget,
+    pub outcome: AccessOutcome,
+}
+
+#[derive(Debug)]
+pub struct AccessLog {
+    started: SystemTime,
+    start: Instant,
+    records: Mutex<Vec<AccessRecord>>,
+}
+
+impl AccessLog {
+    pub fn new() -> AccessLog {
+        AccessLog {
+            started: SystemTime::now(),
+            start: Instant::now(),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn record(&self, target: AccessTarget, outcome: AccessOutcome) {
+        let record = AccessRecord {
+            elapsed: self.start.elapsed(),
+            target,
+            outcome,
+        };
+        self.records.lock().unwrap().push(record);
+    }
+
+    /// Returns a copy of the records so far, in the order the lookups were made.
+    pub fn records(&self) -> Vec<AccessRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Returns the distinct paths that were found, sorted.
+    pub fn found_paths(&self) -> Vec<String> {
+        let mut paths = self
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|record| match (&record.target, &record.outcome) {
+                (AccessTarget::Path(path), AccessOutcome::Found(_)) => Some(path.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths.dedup();
+        paths
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+
+    /// Writes the log as a JSON object, with the time the log was created in seconds since the
+    /// Unix epoch, and an array of records. Times of records are in seconds since the log was
+    /// created. Hashes are written as hexadecimal strings, and packs by their file name stem,
+    /// such as `"0a0000"`.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let started = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let records = self.records.lock().unwrap();
+        let log = LogJson {
+            started,
+            records: records.iter().map(RecordJson::new).collect(),
+        };
+        serde_json::to_writer(&mut writer, &log)?;
+        writeln!(writer)
+    }
+}
+
+impl Default for AccessLog {
+    fn default() -> AccessLog {
+        AccessLog::new()
+    }
+}
+
+#[derive(Serialize)]
+struct LogJson<'a> {
+    started: f64,
+    records: Vec<RecordJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct RecordJson<'a> {
+    time: f64,
+    #[serde(flatten)]
+    target: TargetJson<'a>,
+    #[serde(flatten)]
+    outcome: OutcomeJson<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum TargetJson<'a> {
+    Path {
+        path: &'a str,
+    },
+    Hash1 {
+        folder_crc: String,
+        filename_crc: String,
+    },
+    Hash2 {
+        path_crc: String,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+enum OutcomeJson<'a> {
+    Found { packs: Vec<String> },
+    NotFound,
+    Error { error: &'a str },
+}
+
+impl<'a> RecordJson<'a> {
+    fn new(record: &'a AccessRecord) -> RecordJson<'a> {
+        let target = match &record.target {
+            AccessTarget::Path(path) => TargetJson::Path { path },
+            AccessTarget::Hash1(hash) => TargetJson::Hash1 {
+                folder_crc: format!("{:08x}", hash.folder_crc),
+                filename_crc: format!("{:08x}", hash.filename_crc),
+            },
+            AccessTarget::Hash2(hash) => TargetJson::Hash2 {
+                path_crc: format!("{:08x}", hash.path_crc),
+            },
+        };
+        let outcome = match &record.outcome {
+            AccessOutcome::Found(packs) => OutcomeJson::Found {
+                packs: packs
+                    .iter()
+                    .map(|id| {
+                        format!(
+                            "{:02x}{:02x}{:02x}",
+                            id.category.to_u8(),
+                            id.expansion.to_u8(),
+                            id.number
+                        )
+                    })
+                    .collect(),
+            },
+            AccessOutcome::NotFound => OutcomeJson::NotFound,
+            AccessOutcome::Error(message) => OutcomeJson::Error { error: message },
+        };
+        RecordJson {
+            time: record.elapsed.as_secs_f64(),
+            target,
+            outcome,
+        }
+    }
+}
@@ -0,0 +1,721 @@
+//! Bulk reads of data files, used when iterating over every file in a pack.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use crate::{parser::decompress_file, DataFileSet, Error, FilePointer, SqPackId};
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+use crate::{
+    parser::{decompress_blocks, read_data_entry_headers},
+    DataBlocks, DataFile, DataFileKey,
+};
+
+/// Selects how [`DataFileSet`](crate::DataFileSet) reads entries when iterating over entire packs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadBackend {
+    /// Seek to and read each entry in turn.
+    #[default]
+    Synchronous,
+    /// Submit reads for up to `queue_depth` entries at once through io_uring. The headers of each
+    /// entry are read first, and then its blocks, as far as the block table says they extend.
+    /// Entries are decompressed from memory, except for those larger than 16 MiB, which are read
+    /// synchronously.
+    ///
+    /// There is no equivalent backend using overlapped I/O on Windows yet.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    IoUring { queue_depth: u32 },
+}
+
+/// Read-ahead hints passed to the operating system when [`DataFileSet`](crate::DataFileSet) opens
+/// data files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadAhead {
+    /// Use the operating system's default read-ahead behavior.
+    #[default]
+    Normal,
+    /// Data files will be read from start to end, as when scanning entire packs. This uses
+    /// `posix_fadvise(POSIX_FADV_SEQUENTIAL)` on Linux, Android, and FreeBSD, and
+    /// `FILE_FLAG_SEQUENTIAL_SCAN` on Windows. It has no effect on other platforms.
+    Sequential,
+    /// Data files will be accessed in no particular order, as when looking up individual files.
+    /// This disables read-ahead, using `POSIX_FADV_RANDOM` or `FILE_FLAG_RANDOM_ACCESS`.
+    Random,
+}
+
+/// A limit on the memory used by decompressed entries, shared by every
+/// [`DataFileSet`](crate::DataFileSet) of a [`GameData`](crate::GameData) when set with
+/// [`GameDataBuilder::memory_budget`](crate::GameDataBuilder::memory_budget). Threads reserve the
+/// size of each entry before decompressing it, and wait while the budget is used up, so that
+/// parallel reads of large files, such as those in the `cut` and `music` categories, don't
+/// exhaust memory.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> MemoryBudget {
+        MemoryBudget {
+            limit,
+            in_use: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Reserves memory, waiting until enough of the budget is free. A reservation larger than
+    /// the whole budget is granted once nothing else is reserved, so that it can't wait forever.
+    /// The memory is released when the reservation is dropped.
+    pub fn reserve(self: &Arc<Self>, bytes: u64) -> Reservation {
+        let mut in_use = self.in_use.lock().unwrap();
+        while *in_use > 0 && in_use.saturating_add(bytes) > self.limit {
+            in_use = self.released.wait(in_use).unwrap();
+        }
+        *in_use += bytes;
+        Reservation {
+            budget: Arc::clone(self),
+            bytes,
+        }
+    }
+}
+
+/// Memory reserved from a [`MemoryBudget`], until this is dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    bytes: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        *self.budget.in_use.lock().unwrap() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// Opens a data file for reading, applying the given read-ahead hint.
+pub(crate) fn open_data_file(path: &Path, read_ahead: ReadAhead) -> io::Result<File> {
+    let mut options = File::options();
+    options.read(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        const FILE_FLAG_RANDOM_ACCESS: u32 = 0x10000000;
+        const FILE_FLAG_SEQUENTIAL_SCAN: u32 = 0x08000000;
+
+        match read_ahead {
+            ReadAhead::Normal => {}
+            ReadAhead::Sequential => {
+                options.custom_flags(FILE_FLAG_SEQUENTIAL_SCAN);
+            }
+            ReadAhead::Random => {
+                options.custom_flags(FILE_FLAG_RANDOM_ACCESS);
+            }
+        }
+    }
+    let file = options.open(path)?;
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let advice = match read_ahead {
+            ReadAhead::Normal => None,
+            ReadAhead::Sequential => Some(libc::POSIX_FADV_SEQUENTIAL),
+            ReadAhead::Random => Some(libc::POSIX_FADV_RANDOM),
+        };
+        if let Some(advice) = advice {
+            // Safety: the file descriptor is valid for the lifetime of `file`.
+            let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) };
+            if result != 0 {
+                return Err(io::Error::from_raw_os_error(result));
+            }
+        }
+    }
+    #[cfg(not(any(
+        windows,
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd"
+    )))]
+    let _ = read_ahead;
+    Ok(file)
+}
+
+/// A region of a data file, held in memory. Seeks and reads use positions in the data file, so
+/// that parsers can be driven the same way as with the file itself.
+#[cfg_attr(not(all(target_os = "linux", feature = "io-uring")), allow(dead_code))]
+pub(crate) struct Extent {
+    base: u64,
+    data: Vec<u8>,
+    position: u64,
+}
+
+#[cfg_attr(not(all(target_os = "linux", feature = "io-uring")), allow(dead_code))]
+impl Extent {
+    pub(crate) fn new(base: u64, data: Vec<u8>) -> Extent {
+        Extent {
+            base,
+            data,
+            position: base,
+        }
+    }
+}
+
+impl Read for Extent {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self
+            .position
+            .checked_sub(self.base)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "read before extent"))?;
+        let start = usize::try_from(start)
+            .unwrap_or(usize::MAX)
+            .min(self.data.len());
+        let len = buf.len().min(self.data.len() - start);
+        buf[..len].copy_from_slice(&self.data[start..start + len]);
+        self.position += u64::try_from(len).unwrap();
+        Ok(len)
+    }
+}
+
+impl Seek for Extent {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => {
+                (self.base + u64::try_from(self.data.len()).unwrap()).checked_add_signed(offset)
+            }
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = new_position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// A request to read `length` bytes from `file`, starting at `offset`.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub(crate) struct ExtentRequest<'a> {
+    pub(crate) file: &'a std::fs::File,
+    pub(crate) offset: u64,
+    pub(crate) length: usize,
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub(crate) struct UringReader {
+    ring: io_uring::IoUring,
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+impl UringReader {
+    pub(crate) fn new(queue_depth: u32) -> io::Result<UringReader> {
+        Ok(UringReader {
+            ring: io_uring::IoUring::new(queue_depth)?,
+        })
+    }
+
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.ring.params().sq_entries().try_into().unwrap()
+    }
+
+    /// Reads every requested extent, keeping up to the ring's queue depth of reads in flight.
+    /// Short reads are resubmitted until each extent is full.
+    pub(crate) fn read_extents(&mut self, requests: &[ExtentRequest]) -> io::Result<Vec<Extent>> {
+        use std::os::unix::io::AsRawFd;
+
+        use io_uring::{opcode, types};
+
+        let mut buffers: Vec<Vec<u8>> = requests
+            .iter()
+            .map(|request| vec![0; request.length])
+            .collect();
+        let mut filled = vec![0usize; requests.len()];
+        let mut pending: Vec<usize> = (0..requests.len()).rev().collect();
+        let mut in_flight = 0;
+        while !pending.is_empty() || in_flight > 0 {
+            while in_flight < self.queue_depth() {
+                let Some(i) = pending.pop() else {
+                    break;
+                };
+                if filled[i] == requests[i].length {
+                    continue;
+                }
+                let request = &requests[i];
+                let remaining = &mut buffers[i][filled[i]..];
+                let entry = opcode::Read::new(
+                    types::Fd(request.file.as_raw_fd()),
+                    remaining.as_mut_ptr(),
+                    remaining.len().try_into().unwrap_or(u32::MAX),
+                )
+                .offset(request.offset + u64::try_from(filled[i]).unwrap())
+                .build()
+                .user_data(i.try_into().unwrap());
+                // Safety: the buffer is not touched again until the corresponding completion
+                // has been reaped, and every submitted read is reaped before returning.
+                unsafe {
+                    self.ring
+                        .submission()
+                        .push(&entry)
+                        .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+                }
+                in_flight += 1;
+            }
+            self.ring.submit_and_wait(1)?;
+            let mut error = None;
+            for completion in self.ring.completion() {
+                in_flight -= 1;
+                let i = usize::try_from(completion.user_data()).unwrap();
+                let result = completion.result();
+                if result < 0 {
+                    error.get_or_insert(io::Error::from_raw_os_error(-result));
+                } else if result == 0 {
+                    error.get_or_insert(io::Error::from(io::ErrorKind::UnexpectedEof));
+                } else {
+                    filled[i] += usize::try_from(result).unwrap();
+                    if filled[i] < requests[i].length {
+                        pending.push(i);
+                    }
+                }
+            }
+            if let Some(error) = error {
+                // Drain outstanding reads before their buffers are dropped.
+                while in_flight > 0 {
+                    self.ring.submit_and_wait(in_flight)?;
+                    in_flight -= self.ring.completion().count();
+                }
+                return Err(error);
+            }
+        }
+        Ok(requests
+            .iter()
+            .zip(buffers)
+            .map(|(request, data)| Extent::new(request.offset, data))
+            .collect())
+    }
+}
+
+/// Number of bytes read at the start of each entry to find its headers, when reading through
+/// io_uring. Entries with longer headers are read again once their header length is known.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+const ENTRY_HEADER_PROBE: u64 = 0x400;
+
+/// Largest entry whose blocks are read through io_uring. Larger entries are read synchronously,
+/// so that only a bounded amount of compressed data is held in memory at once.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+const MAX_URING_ENTRY: u64 = 16 << 20;
+
+/// Returns the range of a data file holding the blocks of an entry, after its headers, or `None`
+/// if it has no blocks.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+fn block_range(blocks: &DataBlocks) -> Option<std::ops::Range<u64>> {
+    let (start, header_end) = match blocks {
+        DataBlocks::Empty | DataBlocks::Unsupported => return None,
+        DataBlocks::Binary { base_position, .. } | DataBlocks::Model { base_position, .. } => {
+            (*base_position, *base_position)
+        }
+        // The texture header is stored before the first block.
+        DataBlocks::Texture {
+            base_position,
+            header_size,
+            ..
+        } => (*base_position, base_position + header_size),
+    };
+    let end = blocks
+        .blocks()
+        .map(|block| block.offset + block.compressed_size)
+        .chain([header_end])
+        .max()
+        .unwrap();
+    Some(start.into()..end.into())
+}
+
+/// Iterates over decompressed entries, in the order given, using the data file set's configured
+/// [`ReadBackend`]. Entries should be sorted by file pointer, for disk locality.
+pub(crate) struct EntryReader<'a, K> {
+    data_file_set: &'a mut DataFileSet,
+    pack_id: SqPackId,
+    entries: Vec<(K, FilePointer)>,
+    position: usize,
+    ready: VecDeque<Result<(K, Vec<u8>), Error>>,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    uring: Option<UringReader>,
+}
+
+impl<'a, K: Copy> EntryReader<'a, K> {
+    pub(crate) fn new(
+        data_file_set: &'a mut DataFileSet,
+        pack_id: SqPackId,
+        entries: Vec<(K, FilePointer)>,
+    ) -> EntryReader<'a, K> {
+        EntryReader {
+            data_file_set,
+            pack_id,
+            entries,
+            position: 0,
+            ready: VecDeque::new(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            uring: None,
+        }
+    }
+
+    fn fill(&mut self) {
+        match self.data_file_set.read_backend {
+            // Packs held in memory are always read synchronously.
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            ReadBackend::IoUring { queue_depth } if self.data_file_set.memory.is_none() => {
+                if let Err(e) = self.fill_uring(queue_depth) {
+                    self.ready.push_back(Err(e));
+                }
+            }
+            _ => {
+                if let Some(&(key, pointer)) = self.entries.get(self.position) {
+                    self.position += 1;
+                    let endianness = self.data_file_set.endianness(self.pack_id);
+                    let result = self
+                        .data_file_set
+                        .open(self.pack_id, pointer.data_file_id())
+                        .map_err(Error::from)
+                        .and_then(|file| decompress_file(file, pointer.offset(), endianness));
+                    self.ready.push_back(result.map(|data| (key, data)));
+                }
+            }
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn fill_uring(&mut self, queue_depth: u32) -> Result<(), Error> {
+        let start = self.position;
+        let end = self
+            .entries
+            .len()
+            .min(start + usize::try_from(queue_depth).unwrap());
+        if start == end {
+            return Ok(());
+        }
+        self.position = end;
+        let pointers = self.entries[start..end]
+            .iter()
+            .map(|(_, pointer)| *pointer)
+            .collect::<Vec<_>>();
+        let endianness = self.data_file_set.endianness(self.pack_id);
+
+        // Read the start of each entry, and then the rest of any headers that were cut off.
+        let mut file_lengths = Vec::with_capacity(pointers.len());
+        for pointer in pointers.iter() {
+            let file = self
+                .data_file_set
+                .open(self.pack_id, pointer.data_file_id())?;
+            file_lengths.push(file.len()?);
+        }
+        let probes = pointers
+            .iter()
+            .zip(file_lengths.iter())
+            .map(|(pointer, file_length)| {
+                let offset = u64::from(pointer.offset());
+                let length = file_length.saturating_sub(offset).min(ENTRY_HEADER_PROBE);
+                (*pointer, offset..offset + length)
+            })
+            .collect::<Vec<_>>();
+        let mut headers = self.read_ranges(queue_depth, &probes)?;
+        let long_headers = headers
+            .iter()
+            .zip(pointers.iter())
+            .enumerate()
+            .filter_map(|(i, (extent, pointer))| {
+                let (_, header_length) =
+                    nom::number::complete::u32::<_, ()>(endianness)(&extent.data[..]).ok()?;
+                let header_length = u64::from(header_length);
+                (header_length > u64::try_from(extent.data.len()).unwrap()).then(|| {
+                    let offset = u64::from(pointer.offset());
+                    (i, (*pointer, offset..offset + header_length))
+                })
+            })
+            .collect::<Vec<_>>();
+        let (long_indices, long_ranges): (Vec<_>, Vec<_>) = long_headers.into_iter().unzip();
+        for (i, extent) in long_indices
+            .into_iter()
+            .zip(self.read_ranges(queue_depth, &long_ranges)?)
+        {
+            headers[i] = extent;
+        }
+
+        // Read the blocks of each entry, as far as its block table says they extend.
+        let parsed = pointers
+            .iter()
+            .zip(headers.iter_mut())
+            .map(|(pointer, header)| read_data_entry_headers(header, pointer.offset(), endianness))
+            .collect::<Vec<_>>();
+        let block_ranges = pointers
+            .iter()
+            .zip(parsed.iter())
+            .filter_map(|(pointer, blocks)| {
+                let range = block_range(blocks.as_ref().ok()?)?;
+                (range.end - range.start <= MAX_URING_ENTRY).then_some((*pointer, range))
+            })
+            .collect::<Vec<_>>();
+        let mut extents = self
+            .read_ranges(queue_depth, &block_ranges)?
+            .into_iter()
+            .zip(block_ranges.iter().map(|(pointer, _)| *pointer))
+            .peekable();
+
+        for ((key, pointer), blocks) in self.entries[start..end].iter().zip(parsed) {
+            let extent = extents
+                .next_if(|(_, extent_pointer)| extent_pointer == pointer)
+                .map(|(extent, _)| extent);
+            let result = match (blocks, extent) {
+                (Err(e), _) => Err(e),
+                (Ok(blocks), Some(mut extent)) => {
+                    decompress_blocks(&mut extent, &blocks, endianness)
+                }
+                (Ok(blocks), None) if block_range(&blocks).is_none() => {
+                    decompress_blocks(&mut io::empty(), &blocks, endianness)
+                }
+                // Too large to hold in memory, read it directly instead.
+                (Ok(blocks), None) => self
+                    .data_file_set
+                    .open(self.pack_id, pointer.data_file_id())
+                    .map_err(Error::from)
+                    .and_then(|file| decompress_blocks(file, &blocks, endianness)),
+            };
+            self.ready.push_back(result.map(|data| (*key, data)));
+        }
+        Ok(())
+    }
+
+    /// Reads ranges of this pack's data files through io_uring. The data files must already be
+    /// open.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    fn read_ranges(
+        &mut self,
+        queue_depth: u32,
+        ranges: &[(FilePointer, std::ops::Range<u64>)],
+    ) -> Result<Vec<Extent>, Error> {
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+        let uring = match &mut self.uring {
+            Some(uring) => uring,
+            None => self.uring.insert(UringReader::new(queue_depth)?),
+        };
+        let requests = ranges
+            .iter()
+            .map(|(pointer, range)| ExtentRequest {
+                file: match &self.data_file_set.files[&DataFileKey {
+                    pack_id: self.pack_id,
+                    dat_number: pointer.data_file_id(),
+                }] {
+                    DataFile::File(file) => file,
+                    DataFile::Memory(_) => unreachable!("packs in memory are read synchronously"),
+                },
+                offset: range.start,
+                length: usize::try_from(range.end - range.start).unwrap(),
+            })
+            .collect::<Vec<_>>();
+        Ok(uring.read_extents(&requests)?)
+    }
+}
+
+impl<'a, K: Copy> Iterator for EntryReader<'a, K> {
+    type Item = Result<(K, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            self.fill();
+        }
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use super::{open_data_file, Extent, ReadAhead};
+
+    #[test]
+    fn memory_budget() {
+        use std::{
+            sync::{
+                atomic::{AtomicBool, Ordering},
+                Arc,
+            },
+            thread,
+            time::Duration,
+        };
+
+        use super::MemoryBudget;
+
+        let budget = Arc::new(MemoryBudget::new(100));
+        let first = budget.reserve(60);
+        let released = Arc::new(AtomicBool::new(false));
+        let waiter = {
+            let budget = Arc::clone(&budget);
+            let released = Arc::clone(&released);
+            thread::spawn(move || {
+                let _second = budget.reserve(60);
+                assert!(released.load(Ordering::SeqCst));
+            })
+        };
+        // Fits alongside the first reservation.
+        drop(budget.reserve(40));
+        thread::sleep(Duration::from_millis(50));
+        released.store(true, Ordering::SeqCst);
+        drop(first);
+        waiter.join().unwrap();
+
+        // Oversized reservations are granted when nothing else is reserved.
+        drop(budget.reserve(1000));
+    }
+
+    #[test]
+    fn extent_absolute_positions() {
+        let mut extent = Extent::new(0x100, (0u8..16).collect());
+        let mut buf = [0; 4];
+        extent.seek(SeekFrom::Start(0x104)).unwrap();
+        extent.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [4, 5, 6, 7]);
+        assert_eq!(extent.stream_position().unwrap(), 0x108);
+        extent.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(extent.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], &[14, 15]);
+        extent.seek(SeekFrom::Start(0xff)).unwrap();
+        assert!(extent.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn open_with_read_ahead() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.dat");
+        std::fs::write(&path, b"data").unwrap();
+        for read_ahead in [ReadAhead::Normal, ReadAhead::Sequential, ReadAhead::Random] {
+            let mut buf = Vec::new();
+            open_data_file(&path, read_ahead)
+                .unwrap()
+                .read_to_end(&mut buf)
+                .unwrap();
+            assert_eq!(buf, b"data");
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn uring_read_extents() {
+        use std::io::Write;
+
+        use super::{ExtentRequest, UringReader};
+
+        let mut file = tempfile::tempfile().unwrap();
+        let contents: Vec<u8> = (0..0x10000u32).map(|i| i as u8).collect();
+        file.write_all(&contents).unwrap();
+        let mut reader = UringReader::new(2).unwrap();
+        let requests: Vec<ExtentRequest> = [(0, 16), (0x80, 0x4000), (0x8000, 0x8000), (5, 1)]
+            .into_iter()
+            .map(|(offset, length)| ExtentRequest {
+                file: &file,
+                offset,
+                length,
+            })
+            .collect();
+        let extents = reader.read_extents(&requests).unwrap();
+        for (request, extent) in requests.iter().zip(extents) {
+            let start = usize::try_from(request.offset).unwrap();
+            assert_eq!(extent.data, &contents[start..start + request.length]);
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn block_range_ends_at_next_entry() {
+        use super::block_range;
+        use crate::{tests::write_test_pack, Category, Expansion, GameData, SqPackId};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let large: Vec<u8> = (0..40000u32).map(|i| (i * 7 % 256) as u8).collect();
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[
+                ("exd/a.bin", b"a"),
+                ("exd/b.bin", &large),
+                ("exd/c.bin", b"c"),
+            ],
+        );
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        let mut pointers = index.iter().map(|(_, pointer)| pointer).collect::<Vec<_>>();
+        pointers.sort();
+        let mut data_file_set = game_data.data_files();
+        let dat_length = data_file_set.open(pack_id, 0).unwrap().len().unwrap();
+        let ends = pointers
+            .iter()
+            .skip(1)
+            .map(|pointer| u64::from(pointer.offset()))
+            .chain([dat_length]);
+        for (pointer, end) in pointers.iter().zip(ends) {
+            let blocks = data_file_set.entry_blocks(pack_id, *pointer).unwrap();
+            let range = block_range(blocks).unwrap();
+            assert!(range.start > u64::from(pointer.offset()));
+            assert_eq!(range.end, end);
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn uring_iter_files() {
+        use crate::{tests::write_test_pack, Category, Expansion, GameData, ReadBackend, SqPackId};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let files: Vec<(String, Vec<u8>)> = (0..20u32)
+            .map(|i| {
+                let contents = (0..i * 3001).map(|j| (j % 251) as u8).collect();
+                (format!("exd/file{}.bin", i), contents)
+            })
+            .collect();
+        let file_refs: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_slice()))
+            .collect();
+        write_test_pack(dir.path(), pack_id, &file_refs);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        let mut data_file_set = game_data.data_files();
+        let expected = data_file_set
+            .iter_files(pack_id, index)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(expected.len(), files.len());
+        data_file_set.set_read_backend(ReadBackend::IoUring { queue_depth: 4 });
+        let actual = data_file_set
+            .iter_files(pack_id, index)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(actual, expected);
+    }
+}
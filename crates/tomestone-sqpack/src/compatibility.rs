@@ -0,0 +1,322 @@
+//! A survey of an installation for format features this crate doesn't know about.
+//!
+//! Big client updates occasionally change the file formats. Rather than failing with parse
+//! errors scattered across whatever tools happen to touch the new files, [`probe`] reads the
+//! header of every index and data file, and the headers of every data entry, and summarizes what
+//! it found, including the values of header fields whose meaning isn't known.
+//! [`CompatibilityReport::problems`] lists anything unexpected.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use nom::number::Endianness;
+
+use crate::{
+    parse_sqpack_header, parser::mapped_field, DataFileSet, EntryType, Error, Expansion, GameData,
+    IndexEntry1, IndexEntry2, SqPackHeader,
+};
+
+/// The number of examples of each kind of problem kept in a report.
+const MAX_EXAMPLES: usize = 10;
+
+/// A header field whose meaning isn't known. The parsers ignore these fields, so a client update
+/// that starts using one would otherwise go unnoticed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnknownField {
+    /// The field at 0x408 of a data file, in the data header.
+    DataHeader408,
+    /// The field at 0x0c of the headers of a data entry, other than a model entry.
+    EntryHeader0c,
+    /// The field at 0x16 of the headers of a data entry, other than a model entry, after the
+    /// number of blocks.
+    EntryHeader16,
+}
+
+impl UnknownField {
+    /// The value this field has always held, if it has been the same in every file seen so far.
+    pub fn expected_value(self) -> Option<u32> {
+        match self {
+            UnknownField::DataHeader408 => Some(16),
+            UnknownField::EntryHeader0c => None,
+            UnknownField::EntryHeader16 => Some(0),
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            UnknownField::DataHeader408 => "data header field 0x408",
+            UnknownField::EntryHeader0c => "entry header field 0x0c",
+            UnknownField::EntryHeader16 => "entry header field 0x16",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// Number of index and data files with each SqPack header version. Versions other than 0
+    /// and 1 aren't supported, and are counted in `unrecognized_header_count` instead.
+    pub header_versions: BTreeMap<u32, u64>,
+    /// Files whose SqPack header couldn't be parsed, such as because of an unknown version or
+    /// platform, with the error.
+    pub unrecognized_headers: Vec<(String, String)>,
+    pub unrecognized_header_count: u64,
+    /// Number of data entries of each type. Entries shared by several files are counted once.
+    pub entry_types: BTreeMap<EntryType, u64>,
+    /// Data entries whose headers couldn't be parsed, as pack file names and offsets, with the
+    /// error.
+    pub unreadable_entries: Vec<(String, u32, String)>,
+    pub unreadable_entry_count: u64,
+    /// Category numbers of packs that aren't a known category.
+    pub unknown_categories: BTreeSet<u8>,
+    /// Expansion numbers of packs that aren't a known expansion.
+    pub unknown_expansions: BTreeSet<u8>,
+    /// Values of each header field whose meaning isn't known, with the number of headers
+    /// holding each value.
+    pub unknown_fields: BTreeMap<UnknownField, BTreeMap<u32, u64>>,
+}
+
+impl CompatibilityReport {
+    /// Describes everything found that this crate doesn't fully support. An empty list means
+    /// the installation should be read without problems.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.unrecognized_header_count > 0 {
+            problems.push(format!(
+                "{} files have unrecognized SqPack headers",
+                self.unrecognized_header_count
+            ));
+        }
+        if let Some(count) = self.entry_types.get(&EntryType::Unsupported) {
+            problems.push(format!("{} data entries have an unknown entry type", count));
+        }
+        if self.unreadable_entry_count > 0 {
+            problems.push(format!(
+                "{} data entries have headers that can't be parsed",
+                self.unreadable_entry_count
+            ));
+        }
+        for category in self.unknown_categories.iter() {
+            problems.push(format!("category {:02x} is unknown", category));
+        }
+        for expansion in self.unknown_expansions.iter() {
+            problems.push(format!("expansion {:02x} is unknown", expansion));
+        }
+        for (field, values) in self.unknown_fields.iter() {
+            let expected = match field.expected_value() {
+                Some(expected) => expected,
+                None => continue,
+            };
+            let count = values
+                .iter()
+                .filter(|(value, _)| **value != expected)
+                .map(|(_, count)| count)
+                .sum::<u64>();
+            if count > 0 {
+                problems.push(format!(
+                    "{} headers have an unexpected value in {}",
+                    count,
+                    field.description()
+                ));
+            }
+        }
+        problems
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.problems().is_empty()
+    }
+
+    /// Records the SqPack header of a file, returning it if it could be parsed.
+    fn header(&mut self, name: String, data: &[u8]) -> Option<SqPackHeader> {
+        match parse_sqpack_header(data) {
+            Ok(header) => {
+                *self.header_versions.entry(header.version).or_default() += 1;
+                Some(header)
+            }
+            Err(e) => {
+                self.unrecognized_header_count += 1;
+                if self.unrecognized_headers.len() < MAX_EXAMPLES {
+                    self.unrecognized_headers.push((name, e.to_string()));
+                }
+                None
+            }
+        }
+    }
+
+    /// Records the fields of a data entry's headers whose meaning isn't known. These are in the
+    /// byte order of the pack's platform, like the rest of the entry headers.
+    fn entry_header(&mut self, data: &[u8; 0x18], endianness: Endianness) {
+        let field_0c = mapped_field(data, 0x0c, endianness);
+        let field_16 = [data[0x16], data[0x17]];
+        let field_16 = match endianness {
+            Endianness::Big => u16::from_be_bytes(field_16),
+            Endianness::Little | Endianness::Native => u16::from_le_bytes(field_16),
+        };
+        self.unknown_field(UnknownField::EntryHeader0c, field_0c);
+        self.unknown_field(UnknownField::EntryHeader16, field_16.into());
+    }
+
+    fn unknown_field(&mut self, field: UnknownField, value: u32) {
+        *self
+            .unknown_fields
+            .entry(field)
+            .or_default()
+            .entry(value)
+            .or_default() += 1;
+    }
+}
+
+/// Reads up to `length` bytes from the start of a file. Shorter files are left for the header
+/// parser to reject.
+fn read_header<R: Read + Seek>(file: &mut R, length: u64) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::with_capacity(length.try_into().unwrap());
+    file.seek(SeekFrom::Start(0))?;
+    file.take(length).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Surveys every pack of an installation. Errors reading files are returned, while files and
+/// entries that can be read but not parsed are recorded in the report.
+pub fn probe(
+    game_data: &GameData,
+    data_file_set: &mut DataFileSet,
+) -> Result<CompatibilityReport, Error> {
+    let mut report = CompatibilityReport::default();
+    for pack_id in game_data.iter_packs() {
+        let platform_id = game_data.platform_id(pack_id);
+        if pack_id.category.name().is_none() {
+            report.unknown_categories.insert(pack_id.category.to_u8());
+        }
+        if let Expansion::Unknown(number) = pack_id.expansion {
+            report.unknown_expansions.insert(number);
+        }
+
+        let index_headers = match &game_data.memory {
+            Some(memory) => {
+                let pack = &memory.packs()[&pack_id];
+                [&pack.index, &pack.index2].map(|data| data[..data.len().min(0x400)].to_vec())
+            }
+            None => [
+                read_header(
+                    &mut File::open(game_data.build_index_path::<IndexEntry1>(pack_id))?,
+                    0x400,
+                )?,
+                read_header(
+                    &mut File::open(game_data.build_index_path::<IndexEntry2>(pack_id))?,
+                    0x400,
+                )?,
+            ],
+        };
+        for (extension, data) in ["index", "index2"].into_iter().zip(index_headers) {
+            report.header(pack_id.file_name(platform_id, extension), &data);
+        }
+        for dat_number in 0..=data_file_set.max_dat_number(pack_id) {
+            let data = read_header(data_file_set.open(pack_id, dat_number)?, 0x800)?;
+            let header = report.header(
+                pack_id.file_name(platform_id, &format!("dat{}", dat_number)),
+                &data,
+            );
+            // Files too short for a data header are rejected when their entries are read.
+            match header {
+                Some(header) if data.len() >= 0x40c => {
+                    let value = mapped_field(&data, 0x408, header.platform_id.endianness());
+                    report.unknown_field(UnknownField::DataHeader408, value);
+                }
+                _ => {}
+            }
+        }
+
+        let index = match game_data.get_index_2(&pack_id) {
+            Some(Ok(index)) => index,
+            // Index files that can't be parsed at all were already recorded above.
+            Some(Err(Error::Nom(_))) | None => continue,
+            Some(Err(e)) => return Err(e),
+        };
+        let mut pointers = index.iter().map(|(_, pointer)| pointer).collect::<Vec<_>>();
+        pointers.sort_unstable();
+        pointers.dedup();
+        for pointer in pointers {
+            let entry_type = match data_file_set.entry_blocks(pack_id, pointer) {
+                Ok(blocks) => blocks.entry_type(),
+                Err(Error::Io(e)) => return Err(Error::Io(e)),
+                Err(e) => {
+                    report.unreadable_entry_count += 1;
+                    if report.unreadable_entries.len() < MAX_EXAMPLES {
+                        report.unreadable_entries.push((
+                            pack_id
+                                .file_name(platform_id, &format!("dat{}", pointer.data_file_id())),
+                            pointer.offset(),
+                            e.to_string(),
+                        ));
+                    }
+                    continue;
+                }
+            };
+            *report.entry_types.entry(entry_type).or_default() += 1;
+            // Model entries use these fields for their own block table.
+            if entry_type != EntryType::Model {
+                let file = data_file_set.open(pack_id, pointer.data_file_id())?;
+                let mut data = [0; 0x18];
+                file.seek(SeekFrom::Start(pointer.offset().into()))?;
+                file.read_exact(&mut data)?;
+                report.entry_header(&data, platform_id.endianness());
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::write_test_pack, Category, EntryType, Expansion, GameData, SqPackId};
+
+    use super::{probe, UnknownField};
+
+    #[test]
+    fn probe_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[("exd/root.exl", b"EXLT,2\r\n"), ("exd/item.exh", b"EXHF")],
+        );
+        let unknown_pack = SqPackId {
+            category: Category::from_u8(0x1f),
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), unknown_pack, &[("other/file", b"data")]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        let report = probe(&game_data, &mut data_file_set).unwrap();
+        assert_eq!(report.entry_types[&EntryType::Binary], 3);
+        assert_eq!(report.header_versions.values().sum::<u64>(), 6);
+        assert_eq!(report.unrecognized_header_count, 0);
+        assert_eq!(report.unknown_fields[&UnknownField::DataHeader408][&16], 2);
+        assert_eq!(report.unknown_fields[&UnknownField::EntryHeader16][&0], 3);
+        assert_eq!(report.problems(), ["category 1f is unknown"]);
+
+        let mut report = report;
+        report
+            .unknown_fields
+            .get_mut(&UnknownField::EntryHeader16)
+            .unwrap()
+            .insert(1, 2);
+        assert_eq!(
+            report.problems(),
+            [
+                "category 1f is unknown",
+                "2 headers have an unexpected value in entry header field 0x16",
+            ]
+        );
+    }
+}
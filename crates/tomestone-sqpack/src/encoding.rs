@@ -1,22 +1,51 @@
 use std::{
+    cell::Cell,
     collections::{BTreeMap, BTreeSet},
     fs::File,
     io::{self, Read, Seek, SeekFrom, Write},
     ops::Range,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
+use nom::number::Endianness;
 use sha1::{Digest, Sha1};
 
 #[allow(unused)]
 use crate::compression::compress_sqpack_block;
 use crate::sidetables::SideTables;
 use crate::{
-    compression, Category, Expansion, FilePointer, IndexHash, IndexHash1, IndexHash2, IndexPointer,
-    PlatformId, SqPackId, SqPackType,
+    compression, Category, Expansion, FilePointer, FolderEntry, IndexHash, IndexHash1, IndexHash2,
+    IndexPointer, PlatformId, SqPackId, SqPackType,
 };
 
-static ZEROS: [u8; 4096] = [0; 4096];
+/// Encodes a 16-bit integer in the byte order of a platform's pack files.
+fn u16_bytes(endianness: Endianness, value: u16) -> [u8; 2] {
+    match endianness {
+        Endianness::Big => value.to_be_bytes(),
+        Endianness::Little => value.to_le_bytes(),
+        Endianness::Native => value.to_ne_bytes(),
+    }
+}
+
+/// Encodes a 32-bit integer in the byte order of a platform's pack files.
+fn u32_bytes(endianness: Endianness, value: u32) -> [u8; 4] {
+    match endianness {
+        Endianness::Big => value.to_be_bytes(),
+        Endianness::Little => value.to_le_bytes(),
+        Endianness::Native => value.to_ne_bytes(),
+    }
+}
+
+/// Decodes a 32-bit integer in the byte order of a platform's pack files.
+fn u32_from_bytes(endianness: Endianness, bytes: &[u8]) -> u32 {
+    let bytes = bytes.try_into().unwrap();
+    match endianness {
+        Endianness::Big => u32::from_be_bytes(bytes),
+        Endianness::Little => u32::from_le_bytes(bytes),
+        Endianness::Native => u32::from_ne_bytes(bytes),
+    }
+}
 
 pub trait SetLen {
     fn set_len(&self, size: u64) -> Result<(), io::Error>;
@@ -60,65 +89,396 @@ impl PackIO for RealPackIO {
     type F = File;
 
     fn open_index_file(&mut self) -> Result<File, io::Error> {
-        assert_eq!(self.platform_id, PlatformId::Win32);
-        File::create(self.base.join(self.pack_id.expansion.name()).join(format!(
-            "{:02x}{:02x}{:02x}.win32.index",
-            self.pack_id.category as u8, self.pack_id.expansion as u8, self.pack_id.number
-        )))
+        File::create(
+            self.base
+                .join(&*self.pack_id.expansion.name())
+                .join(self.pack_id.file_name(self.platform_id, "index")),
+        )
     }
 
     fn open_index2_file(&mut self) -> Result<File, io::Error> {
-        assert_eq!(self.platform_id, PlatformId::Win32);
-        File::create(self.base.join(self.pack_id.expansion.name()).join(format!(
-            "{:02x}{:02x}{:02x}.win32.index2",
-            self.pack_id.category as u8, self.pack_id.expansion as u8, self.pack_id.number
-        )))
+        File::create(
+            self.base
+                .join(&*self.pack_id.expansion.name())
+                .join(self.pack_id.file_name(self.platform_id, "index2")),
+        )
     }
 
     fn open_dat_file(&mut self, number: u8) -> Result<File, io::Error> {
-        assert_eq!(self.platform_id, PlatformId::Win32);
-        File::options().read(true).write(true).create(true).open(
-            self.base.join(self.pack_id.expansion.name()).join(format!(
-                "{:02x}{:02x}{:02x}.win32.dat{}",
-                self.pack_id.category as u8,
-                self.pack_id.expansion as u8,
-                self.pack_id.number,
-                number
-            )),
-        )
+        File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(
+                self.base.join(&*self.pack_id.expansion.name()).join(
+                    self.pack_id
+                        .file_name(self.platform_id, &format!("dat{}", number)),
+                ),
+            )
+    }
+}
+
+/// Writes a new pack, with its `.index`, `.index2`, and `.dat` files, containing the given files.
+/// `sqpack_dir` is the `game/sqpack` directory of an installation, and the expansion's directory
+/// within it is created if needed. Existing files of the pack are overwritten.
+pub fn write_pack<'a>(
+    sqpack_dir: &Path,
+    pack_id: SqPackId,
+    files: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+) -> Result<(), io::Error> {
+    let mut writer = create_pack_writer(sqpack_dir, PlatformId::Win32, pack_id)?;
+    for (path, data) in files {
+        writer.add_file(path, data)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Creates a writer for a new pack in `sqpack_dir`, the `game/sqpack` directory of an
+/// installation, creating the expansion's directory if needed.
+pub(crate) fn create_pack_writer(
+    sqpack_dir: &Path,
+    platform_id: PlatformId,
+    pack_id: SqPackId,
+) -> Result<PackSetWriter<RealPackIO>, io::Error> {
+    std::fs::create_dir_all(sqpack_dir.join(&*pack_id.expansion.name()))?;
+    let io = RealPackIO::new(sqpack_dir.to_path_buf(), platform_id, pack_id)?;
+    PackSetWriter::new(io, platform_id, pack_id)
+}
+
+/// Offset of the header that follows the SqPack header, in index and data files.
+const SECOND_HEADER_OFFSET: u64 = 0x400;
+/// Offset of the contents of index and data files, after both headers.
+const CONTENTS_OFFSET: u64 = 0x800;
+
+/// Replaces files in an existing pack, without rewriting the rest of it. The new data entry is
+/// appended to the data file holding the old entry, and the index entries are updated to point at
+/// it in place. The old data entry is left behind, unreferenced.
+///
+/// Files that share a hash with another file, and are listed in the collision table, can't be
+/// replaced yet.
+pub struct PackEditor {
+    sqpack_dir: PathBuf,
+    platform_id: PlatformId,
+    pack_id: SqPackId,
+}
+
+impl PackEditor {
+    /// `sqpack_dir` is the `game/sqpack` directory of an installation.
+    pub fn new(sqpack_dir: &Path, platform_id: PlatformId, pack_id: SqPackId) -> PackEditor {
+        PackEditor {
+            sqpack_dir: sqpack_dir.to_path_buf(),
+            platform_id,
+            pack_id,
+        }
+    }
+
+    fn path(&self, extension: &str) -> PathBuf {
+        self.sqpack_dir
+            .join(&*self.pack_id.expansion.name())
+            .join(self.pack_id.file_name(self.platform_id, extension))
+    }
+
+    /// Replaces the contents of a file. Returns `false`, and changes nothing, if the file isn't
+    /// in this pack.
+    pub fn replace_file(&mut self, path: &str, data: &[u8]) -> Result<bool, io::Error> {
+        let hash1 = IndexHash1::hash(path);
+        let hash2 = IndexHash2::hash(path);
+        let endianness = self.platform_id.endianness();
+        let mut index = IndexFileEditor::open(&self.path("index"), endianness)?;
+        let mut index2 = IndexFileEditor::open(&self.path("index2"), endianness)?;
+        let entry_1 = index.find(16, 8, |entry| {
+            entry[0..4] == u32_bytes(endianness, hash1.filename_crc)
+                && entry[4..8] == u32_bytes(endianness, hash1.folder_crc)
+        });
+        let entry_2 = index2.find(8, 4, |entry| {
+            entry[0..4] == u32_bytes(endianness, hash2.path_crc)
+        });
+        let ((entry_1, pointer_1), (entry_2, pointer_2)) = match (entry_1, entry_2) {
+            (Some(entry_1), Some(entry_2)) => (entry_1, entry_2),
+            (None, None) => return Ok(false),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "file is only present in one index",
+                ))
+            }
+        };
+        let old_pointer = match (
+            IndexPointer::from_u32(pointer_1),
+            IndexPointer::from_u32(pointer_2),
+        ) {
+            (IndexPointer::Pointer(pointer_1), IndexPointer::Pointer(pointer_2))
+                if pointer_1 == pointer_2 =>
+            {
+                pointer_1
+            }
+            (IndexPointer::Pointer(_), IndexPointer::Pointer(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "indexes point to different data entries",
+                ))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "replacing files in the collision table is not supported",
+                ))
+            }
+        };
+
+        let entry = encode_binary_entry(endianness, data, |_| true, None)?;
+        let dat_path = self.path(&format!("dat{}", old_pointer.data_file_id()));
+        let mut dat_file = File::options().read(true).write(true).open(dat_path)?;
+        let mut data_header = [0; 1024];
+        dat_file.seek(SeekFrom::Start(SECOND_HEADER_OFFSET))?;
+        dat_file.read_exact(&mut data_header)?;
+        let file_size_limit = u32_from_bytes(endianness, &data_header[24..28]);
+
+        let offset = dat_file.seek(SeekFrom::End(0))?.div_ceil(128) * 128;
+        let end = offset + u64::try_from(entry.len()).unwrap();
+        if end > u64::from(file_size_limit) {
+            return Err(io::Error::other("not enough room in the data file"));
+        }
+        let new_pointer = FilePointer::new(old_pointer.data_file_id(), offset.try_into().unwrap());
+        dat_file.seek(SeekFrom::Start(offset))?;
+        dat_file.write_all(&entry)?;
+
+        // Update the length and hash of the data section.
+        dat_file.seek(SeekFrom::Start(CONTENTS_OFFSET))?;
+        let mut data_section_hash = Sha1::new();
+        io::copy(&mut dat_file, &mut data_section_hash)?;
+        let data_length = end - CONTENTS_OFFSET;
+        data_header[12..16].copy_from_slice(&u32_bytes(
+            endianness,
+            (data_length >> 7).try_into().unwrap(),
+        ));
+        if data_header[32..52].iter().any(|byte| *byte != 0) {
+            data_header[32..52].copy_from_slice(&data_section_hash.finalize());
+        }
+        let hash = crate::sha1(&data_header[..0x3c0]);
+        data_header[0x3c0..0x3d4].copy_from_slice(&hash);
+        dat_file.seek(SeekFrom::Start(SECOND_HEADER_OFFSET))?;
+        dat_file.write_all(&data_header)?;
+
+        let packed = u32_bytes(endianness, IndexPointer::Pointer(new_pointer).to_u32());
+        index.contents[entry_1 + 8..entry_1 + 12].copy_from_slice(&packed);
+        index2.contents[entry_2 + 4..entry_2 + 8].copy_from_slice(&packed);
+        index.save()?;
+        index2.save()?;
+        Ok(true)
+    }
+}
+
+/// An index file, read into memory to be edited.
+struct IndexFileEditor {
+    file: File,
+    endianness: Endianness,
+    contents: Vec<u8>,
+}
+
+impl IndexFileEditor {
+    fn open(path: &Path, endianness: Endianness) -> Result<IndexFileEditor, io::Error> {
+        let mut file = File::options().read(true).write(true).open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        if contents.len() < CONTENTS_OFFSET as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "index file is truncated",
+            ));
+        }
+        Ok(IndexFileEditor {
+            file,
+            endianness,
+            contents,
+        })
+    }
+
+    fn field(&self, offset: usize) -> usize {
+        u32_from_bytes(self.endianness, &self.contents[offset..offset + 4]) as usize
+    }
+
+    /// Returns the range of the file table, which is the first segment.
+    fn file_table(&self) -> Range<usize> {
+        let header = SECOND_HEADER_OFFSET as usize;
+        let start = self.field(header + 8);
+        let end = (start + self.field(header + 12)).min(self.contents.len());
+        start.min(end)..end
+    }
+
+    /// Finds an entry in the file table. Returns the position of the entry and its packed
+    /// pointer, which follows the hash.
+    fn find(
+        &self,
+        entry_size: usize,
+        hash_size: usize,
+        matches: impl Fn(&[u8]) -> bool,
+    ) -> Option<(usize, u32)> {
+        let table = self.file_table();
+        self.contents[table.clone()]
+            .chunks_exact(entry_size)
+            .position(matches)
+            .map(|i| {
+                let position = table.start + i * entry_size;
+                (position, self.field(position + hash_size) as u32)
+            })
+    }
+
+    /// Updates the hash of the file table and the index header, and writes the file back.
+    fn save(mut self) -> Result<(), io::Error> {
+        let header = SECOND_HEADER_OFFSET as usize;
+        let table = self.file_table();
+        if self.contents[header + 16..header + 36]
+            .iter()
+            .any(|byte| *byte != 0)
+        {
+            let hash = crate::sha1(&self.contents[table]);
+            self.contents[header + 16..header + 36].copy_from_slice(&hash);
+        }
+        let hash = crate::sha1(&self.contents[header..header + 0x3c0]);
+        self.contents[header + 0x3c0..header + 0x3d4].copy_from_slice(&hash);
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&self.contents)
+    }
+}
+
+/// A [`PackIO`] that writes nothing, and only records how large each file would be, to preview
+/// what a [`PackSetWriter`] would do. Reading from its files always returns end of file, so hashes
+/// computed while finalizing are meaningless.
+pub struct DryRunPackIO {
+    platform_id: PlatformId,
+    pack_id: SqPackId,
+    files: Vec<(String, Rc<Cell<u64>>)>,
+}
+
+impl DryRunPackIO {
+    pub fn new(platform_id: PlatformId, pack_id: SqPackId) -> DryRunPackIO {
+        DryRunPackIO {
+            platform_id,
+            pack_id,
+            files: Vec::new(),
+        }
+    }
+
+    /// Returns the name and size of each file that would have been written, in the order they
+    /// were opened.
+    pub fn file_sizes(&self) -> Vec<(String, u64)> {
+        self.files
+            .iter()
+            .map(|(name, len)| (name.clone(), len.get()))
+            .collect()
+    }
+
+    fn open(&mut self, extension: &str) -> DryRunFile {
+        let name = self.pack_id.file_name(self.platform_id, extension);
+        let len = match self.files.iter().find(|(existing, _)| *existing == name) {
+            Some((_, len)) => len.clone(),
+            None => {
+                let len = Rc::new(Cell::new(0));
+                self.files.push((name, len.clone()));
+                len
+            }
+        };
+        DryRunFile { position: 0, len }
+    }
+}
+
+impl PackIO for DryRunPackIO {
+    type F = DryRunFile;
+
+    fn open_index_file(&mut self) -> Result<DryRunFile, io::Error> {
+        Ok(self.open("index"))
+    }
+
+    fn open_index2_file(&mut self) -> Result<DryRunFile, io::Error> {
+        Ok(self.open("index2"))
+    }
+
+    fn open_dat_file(&mut self, number: u8) -> Result<DryRunFile, io::Error> {
+        Ok(self.open(&format!("dat{}", number)))
     }
 }
 
-struct SqPackHeader([u8; 1024]);
+/// A file opened through [`DryRunPackIO`], which only tracks its length.
+pub struct DryRunFile {
+    position: u64,
+    len: Rc<Cell<u64>>,
+}
+
+impl Read for DryRunFile {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for DryRunFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.position += buf.len() as u64;
+        self.len.set(self.len.get().max(self.position));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for DryRunFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.get().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl SetLen for DryRunFile {
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        self.len.set(size);
+        Ok(())
+    }
+}
 
-impl SqPackHeader {
-    fn new(platform_id: PlatformId, pack_type: SqPackType, dat_file_number: u8) -> SqPackHeader {
+struct SqPackHeaderBuffer([u8; 1024]);
+
+impl SqPackHeaderBuffer {
+    fn new(
+        platform_id: PlatformId,
+        pack_type: SqPackType,
+        dat_file_number: u8,
+    ) -> SqPackHeaderBuffer {
+        let endianness = platform_id.endianness();
         let mut header = [0u8; 1024];
         if dat_file_number == 0 {
             header[..6].copy_from_slice(b"SqPack");
-            header[12..16].copy_from_slice(&1024u32.to_le_bytes());
-            header[16..20].copy_from_slice(&1u32.to_le_bytes());
-            header[20..24].copy_from_slice(&(pack_type as u32).to_le_bytes());
+            header[12..16].copy_from_slice(&u32_bytes(endianness, 1024));
+            header[16..20].copy_from_slice(&u32_bytes(endianness, 1));
+            header[20..24].copy_from_slice(&u32_bytes(endianness, pack_type as u32));
         } else {
             // Do not understand these differences yet.
             header[0] = 0x80;
-            header[12..16].copy_from_slice(&0xfu32.to_le_bytes());
-            header[20] = 2;
+            header[12..16].copy_from_slice(&u32_bytes(endianness, 0xf));
+            header[20..24].copy_from_slice(&u32_bytes(endianness, 2));
         }
-        header[8..12].copy_from_slice(&(platform_id as u32).to_le_bytes());
+        header[8] = platform_id as u8;
         header[32..36].copy_from_slice(b"\xff\xff\xff\xff");
-        SqPackHeader(header)
+        SqPackHeaderBuffer(header)
     }
 
-    fn write_date_time(&mut self, packed_date: u32, packed_time: u32) {
-        self.0[24..28].copy_from_slice(&packed_date.to_le_bytes());
-        self.0[28..32].copy_from_slice(&packed_time.to_le_bytes());
+    fn write_date_time(&mut self, endianness: Endianness, packed_date: u32, packed_time: u32) {
+        self.0[24..28].copy_from_slice(&u32_bytes(endianness, packed_date));
+        self.0[28..32].copy_from_slice(&u32_bytes(endianness, packed_time));
     }
 
     fn finalize(&mut self) {
-        let mut sha = Sha1::new();
-        sha.update(&self.0[..0x3c0]);
-        let hash = sha.finalize();
+        let hash = crate::sha1(&self.0[..0x3c0]);
         self.0[0x3c0..0x3d4].copy_from_slice(&hash);
     }
 }
@@ -135,10 +495,10 @@ struct IndexHeader {
     segment_accumulators: [SegmentAccumulator; 4],
 }
 
-fn index_segment_headers_skeleton() -> IndexHeader {
+fn index_segment_headers_skeleton(endianness: Endianness) -> IndexHeader {
     let mut header = [0u8; 1024];
-    header[..4].copy_from_slice(&1024u32.to_le_bytes());
-    header[4..8].copy_from_slice(&1u32.to_le_bytes());
+    header[..4].copy_from_slice(&u32_bytes(endianness, 1024));
+    header[4..8].copy_from_slice(&u32_bytes(endianness, 1));
     let segment_accumulators: [SegmentAccumulator; 4] = [
         SegmentAccumulator {
             offset: Some(2048),
@@ -169,6 +529,7 @@ fn index_segment_headers_skeleton() -> IndexHeader {
 
 fn index_segment_headers_finalize(
     header: &mut IndexHeader,
+    endianness: Endianness,
     second_segment: &[u8],
     segments_not_present_heuristic: bool,
     number_of_dat_files: u8,
@@ -201,9 +562,14 @@ fn index_segment_headers_finalize(
         header.segment_accumulators[3].offset = Some(0);
     }
 
-    header.buf[8..12]
-        .copy_from_slice(&header.segment_accumulators[0].offset.unwrap().to_le_bytes());
-    header.buf[12..16].copy_from_slice(&header.segment_accumulators[0].length.to_le_bytes());
+    header.buf[8..12].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[0].offset.unwrap(),
+    ));
+    header.buf[12..16].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[0].length,
+    ));
     if !segments_not_present_heuristic {
         header.buf[16..36].copy_from_slice(
             &header.segment_accumulators[0]
@@ -214,11 +580,16 @@ fn index_segment_headers_finalize(
         );
     }
 
-    header.buf[80] = number_of_dat_files;
+    header.buf[80..84].copy_from_slice(&u32_bytes(endianness, number_of_dat_files.into()));
 
-    header.buf[84..88]
-        .copy_from_slice(&header.segment_accumulators[1].offset.unwrap().to_le_bytes());
-    header.buf[88..92].copy_from_slice(&header.segment_accumulators[1].length.to_le_bytes());
+    header.buf[84..88].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[1].offset.unwrap(),
+    ));
+    header.buf[88..92].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[1].length,
+    ));
     header.buf[92..112].copy_from_slice(
         &header.segment_accumulators[1]
             .hash
@@ -227,9 +598,14 @@ fn index_segment_headers_finalize(
             .finalize(),
     );
 
-    header.buf[156..160]
-        .copy_from_slice(&header.segment_accumulators[2].offset.unwrap().to_le_bytes());
-    header.buf[160..164].copy_from_slice(&header.segment_accumulators[2].length.to_le_bytes());
+    header.buf[156..160].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[2].offset.unwrap(),
+    ));
+    header.buf[160..164].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[2].length,
+    ));
     if !segments_not_present_heuristic {
         header.buf[164..184].copy_from_slice(
             &header.segment_accumulators[2]
@@ -240,9 +616,14 @@ fn index_segment_headers_finalize(
         );
     }
 
-    header.buf[228..232]
-        .copy_from_slice(&header.segment_accumulators[3].offset.unwrap().to_le_bytes());
-    header.buf[232..236].copy_from_slice(&header.segment_accumulators[3].length.to_le_bytes());
+    header.buf[228..232].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[3].offset.unwrap(),
+    ));
+    header.buf[232..236].copy_from_slice(&u32_bytes(
+        endianness,
+        header.segment_accumulators[3].length,
+    ));
     if !segments_not_present_heuristic {
         header.buf[236..256].copy_from_slice(
             &header.segment_accumulators[3]
@@ -254,12 +635,10 @@ fn index_segment_headers_finalize(
     }
     if header.segment_accumulators[3].length == 0 {
         // what is this? comes after fourth segment header and another 44 null bytes.
-        header.buf[300] = 2;
+        header.buf[300..304].copy_from_slice(&u32_bytes(endianness, 2));
     }
 
-    let mut sha = Sha1::new();
-    sha.update(&header.buf[..0x3c0]);
-    let hash = sha.finalize();
+    let hash = crate::sha1(&header.buf[..0x3c0]);
     header.buf[0x3c0..0x3d4].copy_from_slice(&hash);
 }
 
@@ -270,7 +649,7 @@ fn index_segment_headers_finalize(
 /// 0x004-0x008: Null bytes
 /// 0x008-0x00C: 16
 /// 0x00C-0x010: File length, shifted right by 7
-/// 0x010-0x014: Data file number plus one (1 through 8) (incorrect!)
+/// 0x010-0x014: Data file number plus one (1 through 8)
 /// 0x014-0x018: Null bytes
 /// 0x018-0x01C: Data file size limit
 /// 0x01C-0x020: Null bytes
@@ -283,37 +662,42 @@ struct DataHeader {
     buf: [u8; 1024],
 }
 
-fn data_header_skeleton(_dat_file_number: u8, file_size_limit: u32) -> DataHeader {
+fn data_header_skeleton(
+    endianness: Endianness,
+    dat_file_number: u8,
+    file_size_limit: u32,
+) -> DataHeader {
     let mut header = [0u8; 1024];
-    header[..4].copy_from_slice(&1024u32.to_le_bytes());
-    header[8..12].copy_from_slice(&16u32.to_le_bytes());
-    // TODO: it seems this is path dependent, and needs to be stored in the sidetables
-    header[16] = 1; //dat_file_number + 1;
-    header[24..28].copy_from_slice(&file_size_limit.to_le_bytes());
+    header[..4].copy_from_slice(&u32_bytes(endianness, 1024));
+    header[8..12].copy_from_slice(&u32_bytes(endianness, 16));
+    header[16..20].copy_from_slice(&u32_bytes(endianness, u32::from(dat_file_number) + 1));
+    header[24..28].copy_from_slice(&u32_bytes(endianness, file_size_limit));
     DataHeader { buf: header }
 }
 
 fn data_header_finalize(
     header: &mut DataHeader,
+    endianness: Endianness,
     segments_not_present_heuristic: bool,
     data_length: u64,
     data_section_hash: Sha1,
 ) {
-    header.buf[12..16].copy_from_slice(&u32::to_le_bytes((data_length >> 7).try_into().unwrap()));
+    header.buf[12..16].copy_from_slice(&u32_bytes(
+        endianness,
+        (data_length >> 7).try_into().unwrap(),
+    ));
     if !segments_not_present_heuristic {
         header.buf[32..52].copy_from_slice(&data_section_hash.finalize());
     }
 
-    let mut sha = Sha1::new();
-    sha.update(&header.buf[..0x3c0]);
-    let hash = sha.finalize();
+    let hash = crate::sha1(&header.buf[..0x3c0]);
     header.buf[0x3c0..0x3d4].copy_from_slice(&hash);
 }
 
 struct DatFileRecord<IO: PackIO> {
     dat_file_number: u8,
     file: IO::F,
-    sqpack_header: SqPackHeader,
+    sqpack_header: SqPackHeaderBuffer,
     data_header: DataHeader,
     free_list: FreeList,
 }
@@ -340,20 +724,132 @@ impl Block {
     }
 }
 
-struct FolderEntry {
-    folder_crc: u32,
-    files_offset: u32,
-    files_span: u32,
+/// Encodes a binary data entry, with its headers, blocks, and padding. `compress_block` decides
+/// whether each block is compressed, by block number. The purpose of the field at 0x0c is not yet
+/// known, and an approximation is used unless `unknown` is given. Integers are written in the given
+/// byte order.
+fn encode_binary_entry(
+    endianness: Endianness,
+    data: &[u8],
+    compress_block: impl Fn(usize) -> bool,
+    unknown: Option<u32>,
+) -> Result<Vec<u8>, io::Error> {
+    let blocks: Vec<Block> = data
+        .chunks(16000)
+        .enumerate()
+        .map(|(i, slice)| {
+            if compress_block(i) {
+                let compressed = compression::compress_sqpack_block(slice)?;
+                let block_size =
+                    u16::try_from((16 + compressed.len()).div_ceil(128) * 128).unwrap();
+                Ok(Block::Compressed {
+                    uncompressed_len: slice.len(),
+                    compressed,
+                    block_size,
+                })
+            } else {
+                let len: u32 = slice.len().try_into().unwrap();
+                let block_size = u16::try_from((16 + len).div_ceil(128) * 128).unwrap();
+                Ok(Block::Uncompressed {
+                    len,
+                    data: slice.to_vec(),
+                    block_size,
+                })
+            }
+        })
+        .collect::<Result<Vec<Block>, io::Error>>()?;
+    let entry_header_size: u32 = ((24 + 8 * blocks.len()).div_ceil(128) * 128)
+        .try_into()
+        .unwrap();
+    let total_size_unpadded: u32 = entry_header_size
+        + blocks
+            .iter()
+            .map(|chunk| u32::from(chunk.block_size()))
+            .sum::<u32>();
+    let total_size_padded_shifted = total_size_unpadded.div_ceil(128);
+    let total_size_padded = total_size_padded_shifted * 128;
+
+    // still working on this, needs corrections
+    let unknown = unknown.unwrap_or(total_size_padded_shifted - 1);
+
+    let block_buffer_size: u32 = blocks
+        .iter()
+        .map(|block| u32::from(block.block_size() >> 7))
+        .sum();
+    let mut entry_header_buf = vec![0; entry_header_size as usize];
+    entry_header_buf[0..4].copy_from_slice(&u32_bytes(endianness, entry_header_size)); // data entry header length
+    entry_header_buf[4..8].copy_from_slice(&u32_bytes(endianness, 2)); // content type, binary
+    entry_header_buf[8..12].copy_from_slice(&u32_bytes(endianness, data.len().try_into().unwrap()));
+    entry_header_buf[12..16].copy_from_slice(&u32_bytes(endianness, unknown)); // unknown
+    entry_header_buf[16..20].copy_from_slice(&u32_bytes(endianness, block_buffer_size)); // block buffer size
+    entry_header_buf[20..22]
+        .copy_from_slice(&u16_bytes(endianness, blocks.len().try_into().unwrap())); // number of blocks
+
+    // next, block table
+    let mut block_offset: u32 = 0;
+    for (i, block) in blocks.iter().enumerate() {
+        entry_header_buf[24 + i * 8..28 + i * 8]
+            .copy_from_slice(&u32_bytes(endianness, block_offset)); // offset
+        entry_header_buf[28 + i * 8..30 + i * 8]
+            .copy_from_slice(&u16_bytes(endianness, block.block_size())); // block size
+        entry_header_buf[30 + i * 8..32 + i * 8].copy_from_slice(&u16_bytes(
+            endianness,
+            match block {
+                Block::Compressed {
+                    uncompressed_len, ..
+                } => (*uncompressed_len).try_into().unwrap(),
+                Block::Uncompressed { len, .. } => (*len).try_into().unwrap(),
+            },
+        ));
+        block_offset += u32::from(block.block_size());
+    }
+
+    let mut entry = entry_header_buf;
+
+    for block in blocks.iter() {
+        let (data, original_len) = match block {
+            Block::Compressed {
+                compressed,
+                uncompressed_len,
+                ..
+            } => (compressed, *uncompressed_len),
+            Block::Uncompressed { data, .. } => (data, data.len()),
+        };
+
+        let compressed_size: u32 = data.len().try_into().unwrap();
+        let mut compressed_size_field = compressed_size;
+        if let Block::Uncompressed { .. } = block {
+            compressed_size_field = 32000;
+        }
+        // the block itself is next
+        let mut block_header_buf = [0; 16];
+        block_header_buf[0..4].copy_from_slice(&u32_bytes(endianness, 16));
+        block_header_buf[8..12].copy_from_slice(&u32_bytes(endianness, compressed_size_field));
+        block_header_buf[12..16]
+            .copy_from_slice(&u32_bytes(endianness, original_len.try_into().unwrap()));
+
+        entry.extend_from_slice(&block_header_buf);
+
+        // compressed data is next
+        entry.extend_from_slice(data);
+        // pad out before next block
+        let padding_length = (16 + compressed_size).div_ceil(128) * 128 - (16 + compressed_size);
+        entry.extend_from_slice(&[0; 127][..padding_length as usize]);
+    }
+
+    assert_eq!(entry.len(), usize::try_from(total_size_unpadded).unwrap());
+    entry.resize(usize::try_from(total_size_padded).unwrap(), 0);
+    Ok(entry)
 }
 
 pub struct PackSetWriter<IO: PackIO> {
     io: IO,
     platform_id: PlatformId,
     index: IO::F,
-    index_sqpack_header: SqPackHeader,
+    index_sqpack_header: SqPackHeaderBuffer,
     index_segment_headers: IndexHeader,
     index2: IO::F,
-    index2_sqpack_header: SqPackHeader,
+    index2_sqpack_header: SqPackHeaderBuffer,
     index2_segment_headers: IndexHeader,
     dats: Vec<DatFileRecord<IO>>,
     dat_file_number: u8,
@@ -402,14 +898,14 @@ impl<IO: PackIO> PackSetWriter<IO> {
             _ => 2000000000,
         };
         let mut index = io.open_index_file()?;
-        let index_sqpack_header = SqPackHeader::new(platform_id, SqPackType::Index, 0);
+        let index_sqpack_header = SqPackHeaderBuffer::new(platform_id, SqPackType::Index, 0);
         index.write_all(&index_sqpack_header.0)?;
-        let index_segment_headers = index_segment_headers_skeleton();
+        let index_segment_headers = index_segment_headers_skeleton(platform_id.endianness());
         index.write_all(&index_segment_headers.buf)?;
         let mut index2 = io.open_index2_file()?;
-        let index2_sqpack_header = SqPackHeader::new(platform_id, SqPackType::Index, 0);
+        let index2_sqpack_header = SqPackHeaderBuffer::new(platform_id, SqPackType::Index, 0);
         index2.write_all(&index2_sqpack_header.0)?;
-        let index2_segment_headers = index_segment_headers_skeleton();
+        let index2_segment_headers = index_segment_headers_skeleton(platform_id.endianness());
         index2.write_all(&index2_segment_headers.buf)?;
         let writer = PackSetWriter {
             io,
@@ -451,9 +947,13 @@ impl<IO: PackIO> PackSetWriter<IO> {
         }
 
         let sqpack_header =
-            SqPackHeader::new(self.platform_id, SqPackType::Data, self.dat_file_number);
+            SqPackHeaderBuffer::new(self.platform_id, SqPackType::Data, self.dat_file_number);
         file.write_all(&sqpack_header.0)?;
-        let data_header = data_header_skeleton(self.dat_file_number, self.file_size_limit);
+        let data_header = data_header_skeleton(
+            self.platform_id.endianness(),
+            self.dat_file_number,
+            self.file_size_limit,
+        );
         file.write_all(&data_header.buf)?;
 
         let mut free_list = FreeList::new(self.file_size_limit);
@@ -550,134 +1050,26 @@ impl<IO: PackIO> PackSetWriter<IO> {
         }
 
         let file_entry_opt = self.side_table.file_entries.get(&hash2);
-
-        // for now, assume the data entry has the binary type
-        let blocks: Vec<Block> = data
-            .chunks(16000)
-            .enumerate()
-            .map(|(i, slice)| {
-                if file_entry_opt
+        let entry = encode_binary_entry(
+            self.platform_id.endianness(),
+            data,
+            |i| {
+                file_entry_opt
                     .and_then(|file_entry| file_entry.block_compression.get(i).copied())
                     .unwrap_or(true)
-                {
-                    let compressed = compression::compress_sqpack_block(slice)?;
-                    let block_size =
-                        u16::try_from((16 + compressed.len() + 127) / 128 * 128).unwrap();
-                    Ok(Block::Compressed {
-                        uncompressed_len: slice.len(),
-                        compressed,
-                        block_size,
-                    })
-                } else {
-                    let len = slice.len().try_into().unwrap();
-                    let block_size = u16::try_from((16 + len + 127) / 128 * 128).unwrap();
-                    Ok(Block::Uncompressed {
-                        len,
-                        data: slice.to_vec(),
-                        block_size,
-                    })
-                }
-            })
-            .collect::<Result<Vec<Block>, io::Error>>()?;
-        let entry_header_size: u32 = (((24 + 8 * blocks.len()) + 127) / 128 * 128)
-            .try_into()
-            .unwrap();
-        let total_size_unpadded: u32 = entry_header_size
-            + blocks
-                .iter()
-                .map(|chunk| u32::from(chunk.block_size()))
-                .sum::<u32>();
-        let total_size_padded_shifted = (total_size_unpadded + 127) / 128;
-        let total_size_padded = total_size_padded_shifted * 128;
-
-        let mut unknown = total_size_padded_shifted - 1; // still working on this, needs corrections
-        if let Some(entry) = file_entry_opt {
-            unknown = entry.unknown_entry_field;
-        }
+            },
+            file_entry_opt.map(|entry| entry.unknown_entry_field),
+        )?;
 
-        let pointer = self.choose_entry_location(hash2, total_size_padded)?;
-
-        let block_buffer_size: u32 = blocks
-            .iter()
-            .map(|block| u32::from(block.block_size() >> 7))
-            .sum();
-        let mut entry_header_buf = vec![0; entry_header_size as usize];
-        entry_header_buf[0..4].copy_from_slice(&u32::to_le_bytes(entry_header_size)); // data entry header length
-        entry_header_buf[4] = 2; // content type, binary
-        entry_header_buf[8..12].copy_from_slice(&u32::to_le_bytes(data.len().try_into().unwrap()));
-        entry_header_buf[12..16].copy_from_slice(&u32::to_le_bytes(unknown)); // unknown
-        entry_header_buf[16..20].copy_from_slice(&u32::to_le_bytes(block_buffer_size)); // block buffer size
-        entry_header_buf[20..24]
-            .copy_from_slice(&u32::to_le_bytes(blocks.len().try_into().unwrap())); // number of blocks
-
-        // next, block table
-        let mut block_offset: u32 = 0;
-        for (i, block) in blocks.iter().enumerate() {
-            entry_header_buf[24 + i * 8..28 + i * 8].copy_from_slice(&block_offset.to_le_bytes()); // offset
-            entry_header_buf[28 + i * 8..30 + i * 8]
-                .copy_from_slice(&u16::to_le_bytes(block.block_size())); // block size
-            entry_header_buf[30 + i * 8..32 + i * 8].copy_from_slice(&u16::to_le_bytes(
-                match block {
-                    Block::Compressed {
-                        uncompressed_len, ..
-                    } => (*uncompressed_len).try_into().unwrap(),
-                    Block::Uncompressed { len, .. } => (*len).try_into().unwrap(),
-                },
-            ));
-            block_offset += u32::from(block.block_size());
-        }
+        let pointer = self.choose_entry_location(hash2, entry.len().try_into().unwrap())?;
 
         let dat_file_record = &mut self
             .dats
-            .get_mut(usize::try_from(pointer.data_file_id).unwrap())
+            .get_mut(usize::from(pointer.data_file_id))
             .unwrap();
         let dat_file = &mut dat_file_record.file;
         dat_file.seek(SeekFrom::Start(pointer.offset.into()))?;
-        dat_file.write_all(&entry_header_buf)?;
-
-        for block in blocks.iter() {
-            let (data, original_len) = match block {
-                Block::Compressed {
-                    compressed,
-                    uncompressed_len,
-                    ..
-                } => (compressed, *uncompressed_len),
-                Block::Uncompressed { data, .. } => (data, data.len()),
-            };
-
-            let compressed_size: u32 = data.len().try_into().unwrap();
-            let mut compressed_size_field = compressed_size;
-            if let Block::Uncompressed { .. } = block {
-                compressed_size_field = 32000;
-            }
-            // the block itself is next
-            let mut block_header_buf = [0; 16];
-            block_header_buf[0] = 16;
-            block_header_buf[8..12].copy_from_slice(&compressed_size_field.to_le_bytes());
-            block_header_buf[12..16]
-                .copy_from_slice(&u32::to_le_bytes(original_len.try_into().unwrap()));
-
-            dat_file.write_all(&block_header_buf)?;
-
-            // compressed data is next
-            dat_file.write_all(data)?;
-            // pad out before next block
-            let padding_length = (16 + compressed_size + 127) / 128 * 128 - (16 + compressed_size);
-            dat_file.write_all(&[0; 127][..padding_length as usize])?;
-        }
-
-        let position_after = dat_file.stream_position()?;
-        assert_eq!(
-            u64::from(total_size_unpadded),
-            position_after - u64::from(pointer.offset)
-        );
-
-        let padding_length =
-            TryInto::<usize>::try_into(total_size_padded - total_size_unpadded).unwrap();
-        for _ in 0..(padding_length / ZEROS.len()) {
-            dat_file.write_all(&ZEROS)?;
-        }
-        dat_file.write_all(&ZEROS[..padding_length % ZEROS.len()])?;
+        dat_file.write_all(&entry)?;
 
         // TODO: not handling collisions yet
         assert!(self.entries.insert(hash1, pointer).is_none());
@@ -686,6 +1078,7 @@ impl<IO: PackIO> PackSetWriter<IO> {
     }
 
     pub fn finalize(mut self) -> Result<IO, io::Error> {
+        let endianness = self.platform_id.endianness();
         // Write file index entries into the first segment of the body, and
         // save folder hashes and ranges for a later section.
         let mut entry_buffer = [0; 16];
@@ -705,10 +1098,12 @@ impl<IO: PackIO> PackSetWriter<IO> {
                     files_span: 0,
                 });
             }
-            entry_buffer[0..4].copy_from_slice(&hash.filename_crc.to_le_bytes());
-            entry_buffer[4..8].copy_from_slice(&hash.folder_crc.to_le_bytes());
-            entry_buffer[8..12]
-                .copy_from_slice(&IndexPointer::Pointer(locator).to_u32().to_le_bytes());
+            entry_buffer[0..4].copy_from_slice(&u32_bytes(endianness, hash.filename_crc));
+            entry_buffer[4..8].copy_from_slice(&u32_bytes(endianness, hash.folder_crc));
+            entry_buffer[8..12].copy_from_slice(&u32_bytes(
+                endianness,
+                IndexPointer::Pointer(locator).to_u32(),
+            ));
             self.index.write_all(&entry_buffer)?;
             seg_accum.length += 16;
             seg_accum.hash.as_mut().unwrap().update(entry_buffer);
@@ -756,16 +1151,21 @@ impl<IO: PackIO> PackSetWriter<IO> {
             let dat_file_record = &mut self.dats[usize::from(data_file_id)];
             if dat_file_record.free_list.reserve(to_reserve).is_ok() {
                 // write entry header to data file.
-                entry_header_buf[0] = 0x80;
-                entry_header_buf[12..16].copy_from_slice(&entry.shifted_length.to_le_bytes());
+                entry_header_buf[0..4].copy_from_slice(&u32_bytes(endianness, 0x80));
+                entry_header_buf[12..16]
+                    .copy_from_slice(&u32_bytes(endianness, entry.shifted_length));
                 dat_file_record.file.seek(SeekFrom::Start(offset.into()))?;
                 dat_file_record.file.write_all(&entry_header_buf)?;
 
                 // write index entry to index segment.
-                index_entry_buf[0..4]
-                    .copy_from_slice(&u32::from(entry.pointer.data_file_id()).to_le_bytes());
-                index_entry_buf[4..8].copy_from_slice(&(entry.pointer.offset() >> 7).to_le_bytes());
-                index_entry_buf[8..12].copy_from_slice(&(entry.shifted_length + 1).to_le_bytes());
+                index_entry_buf[0..4].copy_from_slice(&u32_bytes(
+                    endianness,
+                    u32::from(entry.pointer.data_file_id()),
+                ));
+                index_entry_buf[4..8]
+                    .copy_from_slice(&u32_bytes(endianness, entry.pointer.offset() >> 7));
+                index_entry_buf[8..12]
+                    .copy_from_slice(&u32_bytes(endianness, entry.shifted_length + 1));
                 self.index.write_all(&index_entry_buf)?;
                 seg_accum.length += 16;
                 seg_accum.hash.as_mut().unwrap().update(index_entry_buf);
@@ -776,9 +1176,9 @@ impl<IO: PackIO> PackSetWriter<IO> {
         let mut entry_buffer = [0; 16];
         let seg_accum = &mut self.index_segment_headers.segment_accumulators[3];
         for folder in folder_table {
-            entry_buffer[0..4].copy_from_slice(&folder.folder_crc.to_le_bytes());
-            entry_buffer[4..8].copy_from_slice(&folder.files_offset.to_le_bytes());
-            entry_buffer[8..12].copy_from_slice(&folder.files_span.to_le_bytes());
+            entry_buffer[0..4].copy_from_slice(&u32_bytes(endianness, folder.folder_crc));
+            entry_buffer[4..8].copy_from_slice(&u32_bytes(endianness, folder.files_offset));
+            entry_buffer[8..12].copy_from_slice(&u32_bytes(endianness, folder.files_span));
             self.index.write_all(&entry_buffer)?;
             seg_accum.length += 16;
             seg_accum.hash.as_mut().unwrap().update(entry_buffer);
@@ -790,6 +1190,7 @@ impl<IO: PackIO> PackSetWriter<IO> {
         self.index.write_all(&self.index_sqpack_header.0)?;
         index_segment_headers_finalize(
             &mut self.index_segment_headers,
+            endianness,
             &index_second_segment,
             self.segments_not_present_heuristic,
             self.dat_file_number + 1,
@@ -801,9 +1202,11 @@ impl<IO: PackIO> PackSetWriter<IO> {
         let mut entry_buffer = [0; 8];
         let seg_accum = &mut self.index2_segment_headers.segment_accumulators[0];
         for (hash, pointer) in self.entries2 {
-            entry_buffer[0..4].copy_from_slice(&hash.path_crc.to_le_bytes());
-            entry_buffer[4..8]
-                .copy_from_slice(&IndexPointer::Pointer(pointer).to_u32().to_le_bytes());
+            entry_buffer[0..4].copy_from_slice(&u32_bytes(endianness, hash.path_crc));
+            entry_buffer[4..8].copy_from_slice(&u32_bytes(
+                endianness,
+                IndexPointer::Pointer(pointer).to_u32(),
+            ));
             self.index2.write_all(&entry_buffer)?;
             seg_accum.length += 8;
             seg_accum.hash.as_mut().unwrap().update(entry_buffer);
@@ -824,6 +1227,7 @@ impl<IO: PackIO> PackSetWriter<IO> {
         self.index2.write_all(&self.index2_sqpack_header.0)?;
         index_segment_headers_finalize(
             &mut self.index2_segment_headers,
+            endianness,
             &index2_second_segment,
             self.segments_not_present_heuristic,
             self.dat_file_number + 1,
@@ -842,7 +1246,7 @@ impl<IO: PackIO> PackSetWriter<IO> {
             if let Some((packed_date, packed_time)) =
                 self.side_table.sqpack_data_datetimes.get(dat_file_number)
             {
-                header.write_date_time(*packed_date, *packed_time);
+                header.write_date_time(endianness, *packed_date, *packed_time);
             }
 
             let data_section_start = (header.0.len() + data_header.buf.len()).try_into().unwrap();
@@ -868,6 +1272,7 @@ impl<IO: PackIO> PackSetWriter<IO> {
             file.write_all(&header.0)?;
             data_header_finalize(
                 data_header,
+                endianness,
                 self.segments_not_present_heuristic,
                 data_length,
                 data_section_hash,
@@ -1016,7 +1421,9 @@ impl FreeList {
 
 #[cfg(test)]
 mod tests {
-    use super::{FreeList, FreeListExhaustedError, PackSetWriter, RealPackIO};
+    use super::{
+        write_pack, DryRunPackIO, FreeList, FreeListExhaustedError, PackSetWriter, RealPackIO,
+    };
     use crate::{Category, Expansion, PlatformId, SqPackId};
     use quickcheck::{Arbitrary, QuickCheck, TestResult};
     use std::ops::Range;
@@ -1185,4 +1592,86 @@ mod tests {
             .unwrap();
         writer.finalize().unwrap();
     }
+
+    #[test]
+    fn dry_run_pack_io() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("ffxiv")).unwrap();
+        let platform_id = PlatformId::Win32;
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let files: [(&str, &[u8]); 2] = [("exd/a.exh", &[1; 300]), ("exd/b.exh", &[2; 20000])];
+
+        let io = RealPackIO::new(dir.path().to_path_buf(), platform_id, pack_id).unwrap();
+        let mut writer = PackSetWriter::new(io, platform_id, pack_id).unwrap();
+        for (path, contents) in files {
+            writer.add_file(path, contents).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut writer = PackSetWriter::new(
+            DryRunPackIO::new(platform_id, pack_id),
+            platform_id,
+            pack_id,
+        )
+        .unwrap();
+        for (path, contents) in files {
+            writer.add_file(path, contents).unwrap();
+        }
+        let file_sizes = writer.finalize().unwrap().file_sizes();
+        assert_eq!(file_sizes.len(), 3);
+        for (name, size) in file_sizes {
+            let metadata = std::fs::metadata(dir.path().join("ffxiv").join(&name)).unwrap();
+            assert_eq!(metadata.len(), size, "{}", name);
+        }
+    }
+
+    #[test]
+    fn write_pack_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let sqpack_dir = dir.path().join("game").join("sqpack");
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Ex1,
+            number: 0,
+        };
+        let files: [(&str, &[u8]); 3] = [
+            ("exd/ex1/a.exh", &[1; 300]),
+            ("exd/ex1/b.exh", &[2; 20000]),
+            ("exd/ex1/c.exh", b""),
+        ];
+        write_pack(&sqpack_dir, pack_id, files).unwrap();
+
+        for extension in ["index", "index2"] {
+            let path = sqpack_dir
+                .join("ex1")
+                .join(format!("0a0100.win32.{}", extension));
+            let data = std::fs::read(path).unwrap();
+            for header_offset in [0x408, 0x454, 0x49c, 0x4e4] {
+                let field = |offset: usize| {
+                    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+                };
+                let (offset, size) = (field(header_offset), field(header_offset + 4));
+                assert_eq!(
+                    &data[header_offset + 8..header_offset + 28],
+                    crate::sha1(&data[offset..offset + size]),
+                    "{} segment at {:#x}",
+                    extension,
+                    header_offset
+                );
+            }
+        }
+
+        let game_data = crate::GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        for (path, contents) in files {
+            let data = game_data
+                .lookup_path_data(&mut data_file_set, path)
+                .unwrap();
+            assert_eq!(data.as_deref(), Some(contents), "{}", path);
+        }
+    }
 }
@@ -1,49 +1,171 @@
-use std::{
-    collections::{BTreeMap, BTreeSet},
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering,
     convert::TryInto,
     fmt,
-    fs::File,
+    ops::{Bound, RangeBounds},
+};
+#[cfg(feature = "std")]
+use std::{
+    collections::{BTreeSet, VecDeque},
     io,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
+#[cfg(feature = "std")]
+use memmap2::Mmap;
+use nom::number::Endianness;
+#[cfg(feature = "std")]
 use once_cell::sync::{Lazy, OnceCell};
-use parser::{decompress_file, load_index_1, load_index_2};
+#[cfg(feature = "std")]
+use parser::{
+    decompress_blocks, load_index_1, load_index_2, map_index_1, map_index_2,
+    read_data_entry_headers, read_data_entry_size, read_data_header, salvage_index_1,
+    salvage_index_2,
+};
+#[cfg(feature = "std")]
 use pathdb::DbError;
+#[cfg(feature = "std")]
+use patterns::PatternSet;
+#[cfg(feature = "std")]
 use regex::Regex;
+#[cfg(feature = "std")]
 use sidetables::SideTables;
+#[cfg(feature = "std")]
+use tomestone_common::paths::extended_length_path;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+#[cfg(feature = "std")]
+use crate::{
+    bulk::{open_data_file, EntryReader},
+    encoding::create_pack_writer,
+};
 
-use crate::encoding::{PackSetWriter, RealPackIO};
+pub use crate::parser::{
+    parse_data_entry_headers, parse_data_header, parse_index_1, parse_index_2,
+    salvage_index_1_data, salvage_index_2_data,
+};
+#[cfg(feature = "std")]
+pub use crate::{
+    access_log::{AccessLog, AccessOutcome, AccessRecord, AccessTarget},
+    bulk::{MemoryBudget, ReadAhead, ReadBackend, Reservation},
+    memory::{DataFile, MemoryProvider},
+    stream::FileReader,
+};
 
+#[cfg(feature = "std")]
+mod access_log;
+#[cfg(feature = "std")]
+mod bulk;
+#[cfg(feature = "std")]
+pub mod compatibility;
+#[cfg(feature = "std")]
 mod compression;
+#[cfg(feature = "std")]
 pub mod encoding;
+#[cfg(feature = "std")]
+pub mod live;
+#[cfg(feature = "std")]
+mod memory;
 pub(crate) mod parser;
+#[cfg(feature = "std")]
 pub mod pathdb;
+#[cfg(feature = "std")]
+pub mod patterns;
+#[cfg(feature = "std")]
 pub mod sidetables;
+#[cfg(feature = "std")]
+mod stream;
 
 pub(crate) const SHA1_OUTPUT_SIZE: usize = 20;
 
 #[derive(Debug)]
 pub enum Error {
+    #[cfg(feature = "std")]
     Io(io::Error),
     Nom(nom::error::ErrorKind),
     Inflate(miniz_oxide::inflate::TINFLStatus),
+    #[cfg(feature = "std")]
     Db(DbError),
+    /// An index points to a data file that doesn't exist, or that is past the number of data
+    /// files recorded in the index's header.
+    MissingDataFile {
+        pack_id: SqPackId,
+        dat_number: u8,
+    },
+    /// A data file's header records a different data file number than the one in its name,
+    /// so it may have been renamed or copied from elsewhere.
+    DataFileMismatch {
+        pack_id: SqPackId,
+        dat_number: u8,
+        spanned_dat: u32,
+    },
+    /// A `.ver` file doesn't hold a version in the expected format.
+    InvalidVersion(String),
+    /// The installation is in use by the game or another program, so its files can't be
+    /// modified. See [`GameDataBuilder::allow_writes_while_in_use`].
+    #[cfg(feature = "std")]
+    InUse(live::Usage),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            #[cfg(feature = "std")]
             Error::Io(e) => e.fmt(f),
             Error::Nom(e) => write!(f, "error: {:?}", e),
             Error::Inflate(e) => write!(f, "error: {:?}", e),
+            #[cfg(feature = "std")]
             Error::Db(e) => e.fmt(f),
+            Error::MissingDataFile {
+                pack_id,
+                dat_number,
+            } => write!(
+                f,
+                "the index points to .dat{} of pack {:02x}{:02x}{:02x}, which doesn't exist",
+                dat_number,
+                pack_id.category.to_u8(),
+                pack_id.expansion.to_u8(),
+                pack_id.number
+            ),
+            Error::DataFileMismatch {
+                pack_id,
+                dat_number,
+                spanned_dat,
+            } => write!(
+                f,
+                "the header of .dat{} of pack {:02x}{:02x}{:02x} records data file number {}",
+                dat_number,
+                pack_id.category.to_u8(),
+                pack_id.expansion.to_u8(),
+                pack_id.number,
+                i64::from(*spanned_dat) - 1
+            ),
+            Error::InvalidVersion(version) => write!(f, "invalid version: {:?}", version),
+            #[cfg(feature = "std")]
+            Error::InUse(usage) => write!(f, "the installation is in use: {}", usage),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {}
 
+#[cfg(feature = "std")]
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Error {
         Error::Io(e)
@@ -62,6 +184,7 @@ impl From<miniz_oxide::inflate::TINFLStatus> for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<DbError> for Error {
     fn from(e: DbError) -> Error {
         Error::Db(e)
@@ -71,7 +194,12 @@ impl From<DbError> for Error {
 #[derive(Debug)]
 pub struct EnumParseError;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The platform that SqPack files were built for, from their SqPack header. Pack file names also
+/// include the platform, such as `0a0000.ps3.index`.
+///
+/// PS3 files store integers big-endian, and every header, index, and data entry is parsed in the
+/// byte order of its platform. The contents of the files stored in packs aren't converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum PlatformId {
     Win32 = 0,
     Ps3 = 1,
@@ -87,12 +215,39 @@ impl PlatformId {
             _ => Err(EnumParseError),
         }
     }
+
+    /// Byte order of integers in SqPack files for this platform.
+    pub(crate) fn endianness(self) -> Endianness {
+        match self {
+            PlatformId::Ps3 => Endianness::Big,
+            PlatformId::Win32 | PlatformId::Ps4 => Endianness::Little,
+        }
+    }
+
+    /// Name of this platform in pack file names, e.g. `win32` in `0a0000.win32.index`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PlatformId::Win32 => "win32",
+            PlatformId::Ps3 => "ps3",
+            PlatformId::Ps4 => "ps4",
+        }
+    }
+
+    /// Looks up a platform by its name in pack file names. See [`name`](Self::name).
+    pub fn from_name(name: &str) -> Option<PlatformId> {
+        match name {
+            "win32" => Some(PlatformId::Win32),
+            "ps3" => Some(PlatformId::Ps3),
+            "ps4" => Some(PlatformId::Ps4),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct SqPackTypeParseError;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SqPackType {
     Sqdb = 0,
     Data = 1,
@@ -110,6 +265,114 @@ impl SqPackType {
     }
 }
 
+/// The first header of every SqPack file, common to index and data files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqPackHeader {
+    pub platform_id: PlatformId,
+    /// Size of this header. Index files have a second header immediately after this one. Note
+    /// that this is not accurate in `.dat1` and later data files.
+    pub size: u32,
+    /// Format version, either 0 or 1.
+    pub version: u32,
+    pub sqpack_type: SqPackType,
+    /// Packed date field, or zero if unset.
+    pub packed_date: u32,
+    /// Packed time field, or zero if unset.
+    pub packed_time: u32,
+}
+
+impl SqPackHeader {
+    /// Decodes the date and time fields, if they are set.
+    pub fn date_time(&self) -> Option<SqPackDateTime> {
+        SqPackDateTime::from_packed(self.packed_date, self.packed_time)
+    }
+}
+
+/// A timestamp from a SqPack header. The date is stored as a decimal number of the form
+/// YYYYMMDD, and the time is stored as HHMMSScc, where cc is hundredths of a second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SqPackDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl SqPackDateTime {
+    /// Decodes packed date and time fields. Returns `None` if both fields are zero, or if any
+    /// component is out of range.
+    pub fn from_packed(packed_date: u32, packed_time: u32) -> Option<SqPackDateTime> {
+        if packed_date == 0 && packed_time == 0 {
+            return None;
+        }
+        let year = packed_date / 10000;
+        let month = (packed_date / 100) % 100;
+        let day = packed_date % 100;
+        let hour = packed_time / 1000000;
+        let minute = (packed_time / 10000) % 100;
+        let second = (packed_time / 100) % 100;
+        if year <= 1900
+            || !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 24
+            || minute > 59
+            || second > 59
+        {
+            return None;
+        }
+        Some(SqPackDateTime {
+            year: year.try_into().ok()?,
+            month: month.try_into().unwrap(),
+            day: day.try_into().unwrap(),
+            hour: hour.try_into().unwrap(),
+            minute: minute.try_into().unwrap(),
+        })
+    }
+}
+
+impl fmt::Display for SqPackDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+/// Parses a SqPack header, including its integrity check, from the start of a file.
+pub fn parse_sqpack_header(input: &[u8]) -> Result<SqPackHeader, Error> {
+    nom::Finish::finish(parser::sqpack_header(input))
+        .map(|(_, header)| header)
+        .map_err(|e| Error::Nom(e.code))
+}
+
+/// Decompresses a data entry held in memory, such as one copied out of a patch. The entry's
+/// headers must start at the beginning of `data`.
+#[cfg(feature = "std")]
+pub fn decompress_entry(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut cursor = io::Cursor::new(data);
+    let blocks = read_data_entry_headers(&mut cursor, 0, Endianness::Little)?;
+    decompress_blocks(&mut cursor, &blocks, Endianness::Little)
+}
+
+/// Parses a SqPack header, returning its platform ID, header size, version, and type as a tuple.
+#[deprecated(note = "use parse_sqpack_header, which returns a SqPackHeader")]
+#[allow(clippy::type_complexity)]
+pub fn sqpack_header_outer(
+    input: &[u8],
+) -> nom::IResult<&[u8], (PlatformId, u32, u32, SqPackType)> {
+    nom::combinator::map(parser::sqpack_header, |header| {
+        (
+            header.platform_id,
+            header.size,
+            header.version,
+            header.sqpack_type,
+        )
+    })(input)
+}
+
 #[derive(Debug)]
 pub struct IndexSegmentHeader {
     pub offset: u32,
@@ -117,23 +380,91 @@ pub struct IndexSegmentHeader {
     pub hash: [u8; SHA1_OUTPUT_SIZE],
 }
 
-#[derive(Debug)]
+/// The header that follows the SqPack header in a data file. See
+/// [`DataFileSet::data_header`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DataHeader {
+    /// Size of the data file's contents, after both headers.
     pub data_size: u64,
+    /// The data file's number plus one. [`DataFileSet::data_header`] checks this against the
+    /// number of the file it was read from.
     pub spanned_dat: u32,
+    /// Size limit of the data file, after which the next data file is started.
     pub max_file_size: u32,
 }
 
 pub trait IndexHash: Clone + Copy + PartialEq + Eq + PartialOrd + Ord {
+    /// Hashes a path, after normalizing it with [`normalize_path`].
     fn hash(path: &str) -> Self;
 }
 
+/// Normalizes a path to a file in the game's data, so that different spellings of the same path
+/// hash the same way. Path hashing, path lookups, and the path database all apply this first.
+///
+/// - Surrounding whitespace is removed.
+/// - Backslashes are replaced with forward slashes, runs of slashes are collapsed, and leading
+///   slashes are removed.
+/// - The path is converted to Unicode Normalization Form C, so that precomposed and decomposed
+///   spellings of the same accented letter hash the same way.
+/// - ASCII letters are lowercased. Other letters keep their case, which keeps hashes consistent
+///   with the game's own hashing, which only folds ASCII case. Game paths are plain ASCII, so this
+///   only matters for paths that don't exist.
+///
+/// Hashes used to be computed after lowercasing every letter with [`str::to_lowercase`], so the
+/// hashes of paths with non-ASCII uppercase letters, such as `Ä`, differ from those computed by
+/// earlier versions. Path databases holding such paths should be rebuilt.
+pub fn normalize_path(path: &str) -> Cow<'_, str> {
+    let path = path.trim();
+    if !path.is_ascii() && !is_nfc(path) {
+        return Cow::Owned(normalize_chars(path.nfc(), path.len()));
+    }
+    let is_normalized = !path.starts_with('/')
+        && !path.contains("//")
+        && !path
+            .bytes()
+            .any(|byte| byte == b'\\' || byte.is_ascii_uppercase());
+    if is_normalized {
+        return Cow::Borrowed(path);
+    }
+    Cow::Owned(normalize_chars(path.chars(), path.len()))
+}
+
+fn normalize_chars(chars: impl Iterator<Item = char>, capacity: usize) -> String {
+    let mut normalized = String::with_capacity(capacity);
+    for c in chars {
+        let c = if c == '\\' {
+            '/'
+        } else {
+            c.to_ascii_lowercase()
+        };
+        if c == '/' && (normalized.is_empty() || normalized.ends_with('/')) {
+            continue;
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// Computes the CRC-32 checksum used for path hashes in index files.
+///
+/// This uses PCLMULQDQ/SSE4.2 on x86 when available at runtime, and the ARMv8 CRC32 instructions
+/// on aarch64 when the `nightly` feature is enabled.
 pub fn crc32(data: &[u8]) -> u32 {
     let mut hasher = crc32fast::Hasher::new();
     hasher.update(data);
     !hasher.finalize()
 }
 
+/// Computes the SHA-1 digest used for header and segment integrity checks.
+///
+/// SHA-NI is used on x86 when available at runtime, and the `asm` feature enables an assembly
+/// backend that uses the ARMv8 SHA extensions.
+pub fn sha1(data: &[u8]) -> [u8; SHA1_OUTPUT_SIZE] {
+    use sha1::{Digest, Sha1};
+
+    Sha1::digest(data).into()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct IndexHash1 {
     pub folder_crc: u32,
@@ -148,13 +479,15 @@ impl IndexHash1 {
         }
     }
 
+    /// Normalizes a path, and splits it into its folder and filename.
     fn split_path(path: &str) -> (String, String) {
+        let path = normalize_path(path);
         if let Some(last_separator_pos) = path.rfind('/') {
             let folder_slice = &path[..last_separator_pos];
             let filename_slice = &path[last_separator_pos + 1..];
-            (folder_slice.to_lowercase(), filename_slice.to_lowercase())
+            (folder_slice.to_string(), filename_slice.to_string())
         } else {
-            ("".to_string(), path.to_lowercase())
+            ("".to_string(), path.into_owned())
         }
     }
 }
@@ -183,7 +516,7 @@ impl IndexHash2 {
 impl IndexHash for IndexHash2 {
     fn hash(path: &str) -> Self {
         IndexHash2 {
-            path_crc: crc32(path.to_lowercase().as_bytes()),
+            path_crc: crc32(normalize_path(path).as_bytes()),
         }
     }
 }
@@ -256,13 +589,109 @@ impl ZeroEntry {
     }
 }
 
+/// An entry in the folder table, the fourth segment of `.index` files. Each folder's file entries
+/// are stored contiguously in the first segment, since entries are sorted by folder hash first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FolderEntry {
+    pub folder_crc: u32,
+    /// Offset of the folder's first file entry, from the start of the index file.
+    pub files_offset: u32,
+    /// Total size of the folder's file entries, in bytes.
+    pub files_span: u32,
+}
+
+/// The first segment of an index, either parsed up front or read from a memory-mapped file as it
+/// is used.
+#[derive(Debug)]
+enum IndexTable<E> {
+    Loaded(Vec<E>),
+    #[cfg(feature = "std")]
+    Mapped {
+        map: Mmap,
+        range: Range<usize>,
+        parse: fn(&[u8], Endianness) -> E,
+        endianness: Endianness,
+    },
+}
+
+impl<E: IndexEntry> IndexTable<E> {
+    fn len(&self) -> usize {
+        match self {
+            IndexTable::Loaded(entries) => entries.len(),
+            #[cfg(feature = "std")]
+            IndexTable::Mapped { range, .. } => range.len() / E::SIZE as usize,
+        }
+    }
+
+    fn entry(&self, i: usize) -> E {
+        match self {
+            IndexTable::Loaded(entries) => entries[i].clone(),
+            #[cfg(feature = "std")]
+            IndexTable::Mapped {
+                map,
+                range,
+                parse,
+                endianness,
+            } => {
+                let start = range.start + i * E::SIZE as usize;
+                parse(&map[start..start + E::SIZE as usize], *endianness)
+            }
+        }
+    }
+
+    fn binary_search_by_key<K: Ord>(&self, key: &K, f: impl Fn(&E) -> K) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match f(&self.entry(middle)).cmp(key) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Ok(middle),
+            }
+        }
+        Err(low)
+    }
+
+    /// Returns the number of leading entries for which `pred` holds, assuming it holds for a
+    /// prefix of the table, like [`slice::partition_point`].
+    fn partition_point(&self, pred: impl Fn(&E) -> bool) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if pred(&self.entry(middle)) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+}
+
 #[derive(Debug)]
 pub struct Index<E: IndexEntry> {
-    index_table: Vec<E>,
+    index_table: IndexTable<E>,
+    /// Every entry of a memory-mapped table, parsed the first time [`Index::get`] needs to borrow
+    /// one.
+    #[cfg(feature = "std")]
+    parsed_table: OnceCell<Vec<E>>,
     collision_table: Vec<CollisionEntry<E::Hash>>,
     /// Note: it is expected this will be populated for `.index` files, and empty for `.index2`
     /// files.
     tombstone_table: Vec<ZeroEntry>,
+    /// Note: as above, this is only populated for `.index` files.
+    folder_table: Vec<FolderEntry>,
+    dat_file_count: u32,
+}
+
+/// The result of recovering a possibly truncated index file.
+pub struct SalvagedIndex<E: IndexEntry> {
+    /// Every entry that could be recovered. Within each table, entries are in their original
+    /// order, and stop at the first entry that was missing or invalid.
+    pub index: Index<E>,
+    /// The file offset of the first entry that could not be recovered, or `None` if the whole
+    /// index was intact.
+    pub truncated_at: Option<u64>,
 }
 
 impl<E: IndexEntry> Index<E> {
@@ -270,36 +699,201 @@ impl<E: IndexEntry> Index<E> {
         index_table: Vec<E>,
         collision_table: Vec<CollisionEntry<E::Hash>>,
         tombstone_table: Vec<ZeroEntry>,
+        folder_table: Vec<FolderEntry>,
+        dat_file_count: u32,
+    ) -> Index<E> {
+        Index {
+            index_table: IndexTable::Loaded(index_table),
+            #[cfg(feature = "std")]
+            parsed_table: OnceCell::new(),
+            collision_table,
+            tombstone_table,
+            folder_table,
+            dat_file_count,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new_mapped(
+        map: Mmap,
+        range: Range<usize>,
+        parse: fn(&[u8], Endianness) -> E,
+        endianness: Endianness,
+        collision_table: Vec<CollisionEntry<E::Hash>>,
+        tombstone_table: Vec<ZeroEntry>,
+        folder_table: Vec<FolderEntry>,
+        dat_file_count: u32,
     ) -> Index<E> {
         Index {
-            index_table,
+            index_table: IndexTable::Mapped {
+                map,
+                range,
+                parse,
+                endianness,
+            },
+            parsed_table: OnceCell::new(),
             collision_table,
             tombstone_table,
+            folder_table,
+            dat_file_count,
         }
     }
 
+    /// Returns the number of `.dat*` files in this pack set, as recorded in the index header.
+    pub fn dat_file_count(&self) -> u32 {
+        self.dat_file_count
+    }
+
+    /// Returns the collision table, from the second segment, sorted by hash.
+    pub fn collision_table(&self) -> &[CollisionEntry<E::Hash>] {
+        &self.collision_table
+    }
+
+    /// Returns the entries of the collision table for one hash. These are the files the game
+    /// records as sharing that hash, and the slice is empty if the hash has no collisions.
+    pub fn collisions(&self, hash: &E::Hash) -> &[CollisionEntry<E::Hash>] {
+        let start = self
+            .collision_table
+            .partition_point(|entry| entry.hash < *hash);
+        let end = self
+            .collision_table
+            .partition_point(|entry| entry.hash <= *hash);
+        &self.collision_table[start..end]
+    }
+
+    /// Returns the table of empty data entries, from the third segment, sorted by length and
+    /// then by location.
+    pub fn tombstone_table(&self) -> &[ZeroEntry] {
+        &self.tombstone_table
+    }
+
+    /// Returns the folder table, from the fourth segment, sorted by folder hash.
+    pub fn folder_table(&self) -> &[FolderEntry] {
+        &self.folder_table
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (E::Hash, FilePointer)> + '_ {
         IndexIter {
-            iter: self.index_table.iter(),
+            index_table: &self.index_table,
+            position: 0,
             collision_table: &self.collision_table,
             collision_extra: None,
         }
     }
 
+    /// Finds an entry in the file table by its hash. On a memory-mapped index, the first call
+    /// parses the whole table, so that there is an entry to borrow. Use
+    /// [`get_entry`](Self::get_entry) to avoid that.
+    #[cfg_attr(not(feature = "std"), allow(clippy::infallible_destructuring_match))]
     pub fn get(&self, hash: &E::Hash) -> Option<&E> {
+        let entries = match &self.index_table {
+            IndexTable::Loaded(entries) => entries,
+            #[cfg(feature = "std")]
+            IndexTable::Mapped { .. } => self.parsed_table.get_or_init(|| {
+                (0..self.index_table.len())
+                    .map(|i| self.index_table.entry(i))
+                    .collect()
+            }),
+        };
+        entries
+            .binary_search_by_key(hash, IndexEntry::hash)
+            .ok()
+            .map(|index| &entries[index])
+    }
+
+    /// Finds an entry in the file table by its hash, like [`get`](Self::get), but returns a copy
+    /// of the entry. A memory-mapped index only parses the entries that the search visits.
+    pub fn get_entry(&self, hash: &E::Hash) -> Option<E> {
         if let Ok(index) = self
             .index_table
             .binary_search_by_key(hash, IndexEntry::hash)
         {
-            Some(&self.index_table[index])
+            Some(self.index_table.entry(index))
         } else {
             None
         }
     }
 
+    /// Returns every entry in the file table with the given hash. The game lists files that share
+    /// a hash in the collision table, so there is normally at most one, but [`get`](Self::get)
+    /// only returns one of them if there are duplicates.
+    pub fn get_all(&self, hash: &E::Hash) -> Vec<E> {
+        self.range(*hash..=*hash).collect()
+    }
+
+    /// Returns the entries of the file table with hashes in the given range, in order. The
+    /// table is sorted, so this is found by binary search rather than by scanning every entry.
+    /// Entries pointing to the collision table are returned as they are, without being resolved.
+    pub fn range(
+        &self,
+        range: impl RangeBounds<E::Hash>,
+    ) -> impl DoubleEndedIterator<Item = E> + ExactSizeIterator + '_ {
+        let table = &self.index_table;
+        let start = match range.start_bound() {
+            Bound::Included(start) => table.partition_point(|entry| entry.hash() < *start),
+            Bound::Excluded(start) => table.partition_point(|entry| entry.hash() <= *start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => table.partition_point(|entry| entry.hash() <= *end),
+            Bound::Excluded(end) => table.partition_point(|entry| entry.hash() < *end),
+            Bound::Unbounded => table.len(),
+        };
+        (start..end.max(start)).map(move |i| table.entry(i))
+    }
+
+    /// Lists every hash that is shared by more than one file, either because it appears more
+    /// than once in the file table, or because the game recorded it in the collision table.
+    /// Results are sorted by hash.
+    pub fn hash_collisions(&self) -> Vec<HashCollision<'_, E::Hash>> {
+        let mut collisions = BTreeMap::new();
+        let mut previous = None;
+        for i in 0..self.index_table.len() {
+            let hash = self.index_table.entry(i).hash();
+            if previous == Some(hash) {
+                collisions
+                    .entry(hash)
+                    .or_insert(HashCollision {
+                        hash,
+                        table_entries: 1,
+                        paths: Vec::new(),
+                    })
+                    .table_entries += 1;
+            }
+            previous = Some(hash);
+        }
+        for entry in self.collision_table.iter() {
+            collisions
+                .entry(entry.hash)
+                .or_insert_with(|| HashCollision {
+                    hash: entry.hash,
+                    table_entries: self.get_all(&entry.hash).len(),
+                    paths: Vec::new(),
+                })
+                .paths
+                .push(entry.path());
+        }
+        collisions.into_values().collect()
+    }
+
+    /// Returns the location of every file with the given hash. If the hash is shared by several
+    /// files, their locations are taken from the collision table.
+    pub fn get_pointers(&self, hash: &E::Hash) -> Vec<FilePointer> {
+        match self.get_entry(hash).map(|entry| entry.pointer()) {
+            None => Vec::new(),
+            Some(IndexPointer::Pointer(pointer)) => vec![pointer],
+            Some(IndexPointer::Collision) => self
+                .collisions(hash)
+                .iter()
+                .map(|entry| entry.pointer)
+                .collect(),
+        }
+    }
+
     pub fn lookup(&self, path: &str) -> Option<FilePointer> {
         let hash = E::Hash::hash(path);
-        let pointer = if let Some(entry) = self.get(&hash) {
+        let pointer = if let Some(entry) = self.get_entry(&hash) {
             entry.pointer()
         } else {
             return None;
@@ -307,39 +901,58 @@ impl<E: IndexEntry> Index<E> {
         match pointer {
             IndexPointer::Pointer(pointer) => Some(pointer),
             IndexPointer::Collision => {
-                let path_lower = path.to_lowercase();
-                let start_index = self
-                    .collision_table
-                    .partition_point(|entry| entry.hash < hash);
-                for collision_entry in self.collision_table[start_index..].iter() {
-                    if collision_entry.hash != hash {
-                        break;
-                    }
-                    if collision_entry.path == path_lower {
-                        return Some(collision_entry.pointer);
-                    }
-                }
-                None
+                let path = normalize_path(path);
+                self.collisions(&hash)
+                    .iter()
+                    .find(|collision_entry| collision_entry.path == path)
+                    .map(|collision_entry| collision_entry.pointer)
             }
         }
     }
 }
 
 impl Index<IndexEntry1> {
+    /// Finds a folder in the folder table by the CRC of its path.
+    pub fn folder(&self, crc: u32) -> Option<&FolderEntry> {
+        self.folder_table
+            .binary_search_by_key(&crc, |folder| folder.folder_crc)
+            .ok()
+            .map(|position| &self.folder_table[position])
+    }
+
     pub fn contains_folder(&self, crc: &u32) -> bool {
         self.index_table
             .binary_search_by_key(crc, |e| e.hash().folder_crc)
             .is_ok()
     }
+
+    /// Returns the entries of the file table for every file directly in the folder with the
+    /// given CRC, in order of filename hash. Unlike [`folder`](Self::folder), this doesn't rely
+    /// on the folder table.
+    pub fn folder_entries(
+        &self,
+        crc: u32,
+    ) -> impl DoubleEndedIterator<Item = IndexEntry1> + ExactSizeIterator + '_ {
+        self.range(
+            IndexHash1 {
+                folder_crc: crc,
+                filename_crc: 0,
+            }..=IndexHash1 {
+                folder_crc: crc,
+                filename_crc: u32::MAX,
+            },
+        )
+    }
 }
 
 struct CollisionIterExtraData<'a, E: IndexEntry> {
-    collision_table_iter: std::slice::Iter<'a, CollisionEntry<E::Hash>>,
+    collision_table_iter: core::slice::Iter<'a, CollisionEntry<E::Hash>>,
     hash_to_match: E::Hash,
 }
 
 struct IndexIter<'a, E: IndexEntry> {
-    iter: std::slice::Iter<'a, E>,
+    index_table: &'a IndexTable<E>,
+    position: usize,
     collision_table: &'a [CollisionEntry<E::Hash>],
     collision_extra: Option<CollisionIterExtraData<'a, E>>,
 }
@@ -357,7 +970,9 @@ impl<'a, E: IndexEntry> Iterator for IndexIter<'a, E> {
                 }
                 self.collision_extra = None;
             }
-            if let Some(entry) = self.iter.next() {
+            if self.position < self.index_table.len() {
+                let entry = self.index_table.entry(self.position);
+                self.position += 1;
                 match entry.pointer() {
                     IndexPointer::Pointer(pointer) => return Some((entry.hash(), pointer)),
                     IndexPointer::Collision => {
@@ -375,23 +990,28 @@ impl<'a, E: IndexEntry> Iterator for IndexIter<'a, E> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// The category of a pack, which is the first byte of its ID. Category numbers that this library
+/// doesn't recognize are represented with `Unknown`, so that packs in new categories can still be
+/// listed and read. Use [`Category::from_u8`] to convert numbers, since it never returns
+/// `Unknown` for a known category. Comparisons are done by category number.
+#[derive(Debug, Clone, Copy)]
 pub enum Category {
-    Common = 0,
-    BgCommon = 1,
-    Bg = 2,
-    Cut = 3,
-    Chara = 4,
-    Shader = 5,
-    Ui = 6,
-    Sound = 7,
-    Vfx = 8,
-    UiScript = 9,
-    Exd = 0xA,
-    GameScript = 0xB,
-    Music = 0xC,
-    SqpackTest = 0x12,
-    Debug = 0x13,
+    Common,
+    BgCommon,
+    Bg,
+    Cut,
+    Chara,
+    Shader,
+    Ui,
+    Sound,
+    Vfx,
+    UiScript,
+    Exd,
+    GameScript,
+    Music,
+    SqpackTest,
+    Debug,
+    Unknown(u8),
 }
 
 impl Category {
@@ -416,27 +1036,71 @@ impl Category {
         }
     }
 
-    pub fn from_u8(value: u8) -> Result<Category, EnumParseError> {
+    /// Returns the name of this category, as used in paths, or `None` for unknown categories.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Category::Common => Some("common"),
+            Category::BgCommon => Some("bgcommon"),
+            Category::Bg => Some("bg"),
+            Category::Cut => Some("cut"),
+            Category::Chara => Some("chara"),
+            Category::Shader => Some("shader"),
+            Category::Ui => Some("ui"),
+            Category::Sound => Some("sound"),
+            Category::Vfx => Some("vfx"),
+            Category::UiScript => Some("ui_script"),
+            Category::Exd => Some("exd"),
+            Category::GameScript => Some("game_script"),
+            Category::Music => Some("music"),
+            Category::SqpackTest => Some("sqpack_test"),
+            Category::Debug => Some("debug"),
+            Category::Unknown(_) => None,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Category {
         match value {
-            0 => Ok(Category::Common),
-            1 => Ok(Category::BgCommon),
-            2 => Ok(Category::Bg),
-            3 => Ok(Category::Cut),
-            4 => Ok(Category::Chara),
-            5 => Ok(Category::Shader),
-            6 => Ok(Category::Ui),
-            7 => Ok(Category::Sound),
-            8 => Ok(Category::Vfx),
-            9 => Ok(Category::UiScript),
-            0xA => Ok(Category::Exd),
-            0xB => Ok(Category::GameScript),
-            0xC => Ok(Category::Music),
-            0x12 => Ok(Category::SqpackTest),
-            0x13 => Ok(Category::Debug),
-            _ => Err(EnumParseError),
+            0 => Category::Common,
+            1 => Category::BgCommon,
+            2 => Category::Bg,
+            3 => Category::Cut,
+            4 => Category::Chara,
+            5 => Category::Shader,
+            6 => Category::Ui,
+            7 => Category::Sound,
+            8 => Category::Vfx,
+            9 => Category::UiScript,
+            0xA => Category::Exd,
+            0xB => Category::GameScript,
+            0xC => Category::Music,
+            0x12 => Category::SqpackTest,
+            0x13 => Category::Debug,
+            _ => Category::Unknown(value),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Category::Common => 0,
+            Category::BgCommon => 1,
+            Category::Bg => 2,
+            Category::Cut => 3,
+            Category::Chara => 4,
+            Category::Shader => 5,
+            Category::Ui => 6,
+            Category::Sound => 7,
+            Category::Vfx => 8,
+            Category::UiScript => 9,
+            Category::Exd => 0xA,
+            Category::GameScript => 0xB,
+            Category::Music => 0xC,
+            Category::SqpackTest => 0x12,
+            Category::Debug => 0x13,
+            Category::Unknown(value) => value,
         }
     }
 
+    /// Iterates over all known categories. This does not include any `Unknown` categories.
     pub fn iter_all() -> impl Iterator<Item = &'static Category> {
         const LIST: [Category; 15] = [
             Category::Common,
@@ -459,41 +1123,88 @@ impl Category {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+impl PartialEq for Category {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_u8() == other.to_u8()
+    }
+}
+
+impl Eq for Category {}
+
+impl PartialOrd for Category {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Category {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_u8().cmp(&other.to_u8())
+    }
+}
+
+/// The expansion a pack belongs to, which is the second byte of its ID, and the name of its
+/// directory. Expansion numbers past the last known expansion are represented with `Unknown`, so
+/// that repositories for new expansions can still be listed and read. Use
+/// [`Expansion::from_u8`] to convert numbers, since it never returns `Unknown` for a known
+/// expansion. Comparisons are done by expansion number.
+#[derive(Debug, Clone, Copy)]
 pub enum Expansion {
-    Base = 0,
-    Ex1 = 1,
-    Ex2 = 2,
-    Ex3 = 3,
-    Ex4 = 4,
-    Ex5 = 5,
+    Base,
+    Ex1,
+    Ex2,
+    Ex3,
+    Ex4,
+    Ex5,
+    Unknown(u8),
 }
 
 impl Expansion {
+    /// Parses the name of an expansion's directory, either `ffxiv` or `ex` followed by the
+    /// expansion number.
     pub fn parse_name(name: &str) -> Result<Expansion, EnumParseError> {
-        match name {
-            "ffxiv" => Ok(Expansion::Base),
-            "ex1" => Ok(Expansion::Ex1),
-            "ex2" => Ok(Expansion::Ex2),
-            "ex3" => Ok(Expansion::Ex3),
-            "ex4" => Ok(Expansion::Ex4),
-            "ex5" => Ok(Expansion::Ex5),
-            _ => Err(EnumParseError),
+        if name == "ffxiv" {
+            return Ok(Expansion::Base);
+        }
+        let expansion = name
+            .strip_prefix("ex")
+            .filter(|number| number.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|number| number.parse().ok())
+            .map(Expansion::from_u8)
+            .ok_or(EnumParseError)?;
+        // Reject other spellings of the same number, like "ex01" or "ex0".
+        if expansion.name() == name {
+            Ok(expansion)
+        } else {
+            Err(EnumParseError)
         }
     }
 
-    pub fn from_u8(value: u8) -> Result<Expansion, EnumParseError> {
+    pub fn from_u8(value: u8) -> Expansion {
         match value {
-            0 => Ok(Expansion::Base),
-            1 => Ok(Expansion::Ex1),
-            2 => Ok(Expansion::Ex2),
-            3 => Ok(Expansion::Ex3),
-            4 => Ok(Expansion::Ex4),
-            5 => Ok(Expansion::Ex5),
-            _ => Err(EnumParseError),
+            0 => Expansion::Base,
+            1 => Expansion::Ex1,
+            2 => Expansion::Ex2,
+            3 => Expansion::Ex3,
+            4 => Expansion::Ex4,
+            5 => Expansion::Ex5,
+            _ => Expansion::Unknown(value),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Expansion::Base => 0,
+            Expansion::Ex1 => 1,
+            Expansion::Ex2 => 2,
+            Expansion::Ex3 => 3,
+            Expansion::Ex4 => 4,
+            Expansion::Ex5 => 5,
+            Expansion::Unknown(value) => value,
         }
     }
 
+    /// Iterates over all known expansions. This does not include any `Unknown` expansions.
     pub fn iter_all() -> impl Iterator<Item = &'static Expansion> {
         const LIST: [Expansion; 6] = [
             Expansion::Base,
@@ -506,15 +1217,129 @@ impl Expansion {
         LIST.iter()
     }
 
-    pub fn name(&self) -> &'static str {
-        match self {
-            Expansion::Base => "ffxiv",
-            Expansion::Ex1 => "ex1",
-            Expansion::Ex2 => "ex2",
-            Expansion::Ex3 => "ex3",
-            Expansion::Ex4 => "ex4",
-            Expansion::Ex5 => "ex5",
+    /// Returns the name of this expansion's directory.
+    pub fn name(&self) -> Cow<'static, str> {
+        match self.to_u8() {
+            0 => Cow::Borrowed("ffxiv"),
+            1 => Cow::Borrowed("ex1"),
+            2 => Cow::Borrowed("ex2"),
+            3 => Cow::Borrowed("ex3"),
+            4 => Cow::Borrowed("ex4"),
+            5 => Cow::Borrowed("ex5"),
+            number => Cow::Owned(format!("ex{}", number)),
+        }
+    }
+}
+
+impl PartialEq for Expansion {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_u8() == other.to_u8()
+    }
+}
+
+impl Eq for Expansion {}
+
+impl PartialOrd for Expansion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Expansion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_u8().cmp(&other.to_u8())
+    }
+}
+
+/// A version of the game or one of its expansions, as recorded in a `.ver` file, such as
+/// `2023.09.28.0000.0000`. Versions are ordered by release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameVersion {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub part: u16,
+    pub revision: u16,
+}
+
+impl core::str::FromStr for GameVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<GameVersion, Error> {
+        let invalid = || Error::InvalidVersion(s.to_string());
+        let fields = s
+            .trim()
+            .split('.')
+            .map(|field| {
+                if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                field.parse::<u16>().map_err(|_| invalid())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match fields[..] {
+            [year, month, day, part, revision] => Ok(GameVersion {
+                year,
+                month: month.try_into().map_err(|_| invalid())?,
+                day: day.try_into().map_err(|_| invalid())?,
+                part,
+                revision,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}.{:02}.{:02}.{:04}.{:04}",
+            self.year, self.month, self.day, self.part, self.revision
+        )
+    }
+}
+
+/// Reads a `.ver` file, returning `None` if it doesn't exist. Surrounding whitespace is removed.
+#[cfg(feature = "std")]
+pub fn read_version_file(path: &Path) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents.trim().to_string())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The installed version of each repository, as written in its `.ver` file. Versions are kept
+/// as they are written, and can be parsed into a [`GameVersion`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallVersions {
+    pub boot: Option<String>,
+    pub game: Option<String>,
+    /// Expansion versions, by expansion number.
+    pub expansions: BTreeMap<u8, String>,
+}
+
+#[cfg(feature = "std")]
+impl InstallVersions {
+    /// Reads the version files of an installation, given the directory containing `boot` and
+    /// `game`. Expansion versions stop at the first expansion without a version file.
+    pub fn read(root: &Path) -> io::Result<InstallVersions> {
+        let sqpack_dir = root.join("game").join("sqpack");
+        let mut expansions = BTreeMap::new();
+        for number in 1..=u8::MAX {
+            let name = Expansion::from_u8(number).name();
+            let path = sqpack_dir.join(&*name).join(format!("{}.ver", name));
+            match read_version_file(&path)? {
+                Some(version) => expansions.insert(number, version),
+                None => break,
+            };
         }
+        Ok(InstallVersions {
+            boot: read_version_file(&root.join("boot").join("ffxivboot.ver"))?,
+            game: read_version_file(&root.join("game").join("ffxivgame.ver"))?,
+            expansions,
+        })
     }
 }
 
@@ -525,6 +1350,21 @@ pub struct SqPackId {
     pub number: u8,
 }
 
+impl SqPackId {
+    /// Returns the name of one of this pack's files for a platform, given its extension, e.g.
+    /// `0a0000.win32.index`.
+    pub fn file_name(&self, platform_id: PlatformId, extension: &str) -> String {
+        format!(
+            "{:02x}{:02x}{:02x}.{}.{}",
+            self.category.to_u8(),
+            self.expansion.to_u8(),
+            self.number,
+            platform_id.name(),
+            extension
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum DataBlocks {
     Empty,
@@ -533,64 +1373,254 @@ pub enum DataBlocks {
         base_position: u32,
         blocks: Vec<(u32, u16, u16)>,
     },
-    Model(),
-    Texture(),
+    Model {
+        base_position: u32,
+        header: ModelHeader,
+        /// Number of blocks in each section of the model file, in the order the sections appear
+        /// in the decompressed file. See [`MODEL_SECTION_ORDER`].
+        section_block_counts: [u16; 11],
+        /// Offset and stored size of each block, in the same order.
+        blocks: Vec<(u32, u16)>,
+    },
+    Texture {
+        base_position: u32,
+        /// Size of the uncompressed texture header, which is stored before the first block.
+        header_size: u32,
+        /// Offset and stored size of each block.
+        blocks: Vec<(u32, u16)>,
+    },
+}
+
+/// Order of the sections of a model file after decompression, as indices into the section tables
+/// of a model entry's headers. The headers list the stack, the runtime data, then the vertex
+/// buffers, edge geometry, and index buffers of each of three levels of detail. The decompressed
+/// file has the stack and runtime data, followed by the vertex buffer, edge geometry, and index
+/// buffer of the first level of detail, and so on.
+pub const MODEL_SECTION_ORDER: [usize; 11] = [0, 1, 2, 5, 8, 3, 6, 9, 4, 7, 10];
+
+/// Fields of a model file's header. Model entries don't store this header as-is, and it is
+/// rebuilt from these fields and the sizes of each section when decompressing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelHeader {
+    pub version: u32,
+    pub vertex_declaration_count: u16,
+    pub material_count: u16,
+    pub lod_count: u8,
+    pub index_buffer_streaming: bool,
+    pub edge_geometry: bool,
+}
+
+/// The kind of a data entry, which determines how its blocks are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntryType {
+    Empty,
+    Binary,
+    Model,
+    Texture,
+    Unsupported,
+}
+
+/// Summary of a data entry, read from its headers without decompressing it. See
+/// [`GameData::file_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub entry_type: EntryType,
+    pub uncompressed_size: u32,
+    /// Total size of the entry's blocks in the data file. See
+    /// [`DataBlocks::total_compressed_size`].
+    pub compressed_size: u32,
+    pub block_count: usize,
+    /// Number of the `.datN` file holding the entry.
+    pub dat_number: u8,
+}
+
+/// The location and size of one compressed block of a data entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// Absolute position of the block within the data file.
+    pub offset: u32,
+    /// Size of the block in the data file, including its header and padding.
+    pub compressed_size: u32,
+    /// Size of the block's data after decompression, if it is recorded in the entry headers.
+    /// Model and texture entries only record this in each block's own header.
+    pub decompressed_size: Option<u32>,
 }
 
 impl DataBlocks {
-    pub fn all_blocks<'a>(&'a self) -> Box<dyn Iterator<Item = u32> + 'a> {
+    pub fn entry_type(&self) -> EntryType {
+        match self {
+            DataBlocks::Empty => EntryType::Empty,
+            DataBlocks::Unsupported => EntryType::Unsupported,
+            DataBlocks::Binary { .. } => EntryType::Binary,
+            DataBlocks::Model { .. } => EntryType::Model,
+            DataBlocks::Texture { .. } => EntryType::Texture,
+        }
+    }
+
+    /// Iterates over the blocks of this entry, in the order their contents appear in the
+    /// decompressed file.
+    pub fn blocks(&self) -> Box<dyn Iterator<Item = BlockInfo> + '_> {
         match self {
             DataBlocks::Binary {
                 base_position,
                 blocks,
             } => {
                 let base_position = *base_position;
-                Box::new(blocks.iter().map(
-                    move |(offset, _block_size, _decompressed_data_size)| base_position + *offset,
-                ))
+                Box::new(
+                    blocks
+                        .iter()
+                        .map(
+                            move |(offset, block_size, decompressed_data_size)| BlockInfo {
+                                offset: base_position + *offset,
+                                compressed_size: (*block_size).into(),
+                                decompressed_size: Some((*decompressed_data_size).into()),
+                            },
+                        ),
+                )
+            }
+            DataBlocks::Model {
+                base_position,
+                blocks,
+                ..
+            }
+            | DataBlocks::Texture {
+                base_position,
+                blocks,
+                ..
+            } => {
+                let base_position = *base_position;
+                Box::new(blocks.iter().map(move |(offset, block_size)| BlockInfo {
+                    offset: base_position + *offset,
+                    compressed_size: (*block_size).into(),
+                    decompressed_size: None,
+                }))
             }
-            _ => Box::new(vec![].into_iter()), // TODO!
+            DataBlocks::Empty | DataBlocks::Unsupported => Box::new(core::iter::empty()),
         }
     }
-}
 
-fn list_packs(root_path: &Path) -> io::Result<BTreeSet<SqPackId>> {
-    static RE: Lazy<Regex> = Lazy::new(|| {
-        Regex::new("^([0-9a-f]{2})([0-9a-f]{2})([0-9a-f]{2})\\.win32\\.index2?$").unwrap()
-    });
+    pub fn all_blocks(&self) -> impl Iterator<Item = u32> + '_ {
+        self.blocks().map(|block| block.offset)
+    }
+
+    /// Total size of all blocks in the data file, not including the entry headers.
+    pub fn total_compressed_size(&self) -> u32 {
+        self.blocks().map(|block| block.compressed_size).sum()
+    }
+
+    /// Total size of all blocks after decompression, or `None` if any block's decompressed size
+    /// is not recorded in the entry headers.
+    pub fn total_decompressed_size(&self) -> Option<u32> {
+        self.blocks().map(|block| block.decompressed_size).sum()
+    }
+}
+
+/// Checks that a file pointer from an index refers to one of the data files the index's header
+/// counts.
+#[cfg(feature = "std")]
+fn check_dat_number<E: IndexEntry>(
+    pack_id: SqPackId,
+    index: &Index<E>,
+    pointer: FilePointer,
+) -> Result<FilePointer, Error> {
+    if u32::from(pointer.data_file_id()) < index.dat_file_count() {
+        Ok(pointer)
+    } else {
+        Err(Error::MissingDataFile {
+            pack_id,
+            dat_number: pointer.data_file_id(),
+        })
+    }
+}
+
+/// The packs found in an installation by [`list_packs`].
+#[cfg(feature = "std")]
+struct PackListing {
+    /// Every pack found, with the platform in its file names.
+    packs: BTreeMap<SqPackId, PlatformId>,
+    /// Every expansion with a directory, including those excluded by the builder.
+    expansions: BTreeSet<Expansion>,
+    /// Files and directories that were skipped because their names aren't valid UTF-8.
+    skipped_paths: Vec<PathBuf>,
+}
+
+/// Lists the packs in an installation, for any platform. Expansion directories that don't exist
+/// are skipped. If a pack has files for more than one platform, the first platform in the order of
+/// [`PlatformId`] is used.
+#[cfg(feature = "std")]
+fn list_packs(root_path: &Path, builder: &GameDataBuilder) -> io::Result<PackListing> {
+    static RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new("^([0-9a-f]{2})([0-9a-f]{2})([0-9a-f]{2})\\.([0-9a-z]+)\\.index2?$").unwrap()
+    });
 
     let sqpack_dir = root_path.join("game").join("sqpack");
-    let mut ids = BTreeSet::new();
-    for expansion in Expansion::iter_all() {
-        let expansion_dir = sqpack_dir.join(expansion.name());
+    let mut listing = PackListing {
+        packs: BTreeMap::new(),
+        expansions: BTreeSet::new(),
+        skipped_paths: Vec::new(),
+    };
+    if !sqpack_dir.is_dir() {
+        return Ok(listing);
+    }
+    // Look for every expansion directory, rather than only known expansions, so that packs from
+    // new expansions are listed too.
+    for entry in sqpack_dir.read_dir()? {
+        let expansion_dir = entry?.path();
+        let expansion = match expansion_dir.file_name().and_then(|name| name.to_str()) {
+            Some(name) => match Expansion::parse_name(name) {
+                Ok(expansion) => expansion,
+                Err(_) => continue,
+            },
+            None => {
+                listing.skipped_paths.push(expansion_dir);
+                continue;
+            }
+        };
         if !expansion_dir.is_dir() {
             continue;
         }
+        listing.expansions.insert(expansion);
+        if !builder.includes_expansion(expansion) {
+            continue;
+        }
         for entry in expansion_dir.read_dir()? {
-            if let Ok(name) = entry?.file_name().into_string() {
-                if let Some(caps) = RE.captures(&name) {
-                    if let (Ok(category_num), Ok(expansion_num), Ok(number)) = (
-                        u8::from_str_radix(caps.get(1).unwrap().as_str(), 16),
-                        u8::from_str_radix(caps.get(2).unwrap().as_str(), 16),
-                        u8::from_str_radix(caps.get(3).unwrap().as_str(), 16),
-                    ) {
-                        if let (Ok(category), Ok(expansion)) = (
-                            Category::from_u8(category_num),
-                            Expansion::from_u8(expansion_num),
+            let entry = entry?;
+            match entry.file_name().into_string() {
+                Ok(name) => {
+                    if let Some(caps) = RE.captures(&name) {
+                        if let (
+                            Ok(category_num),
+                            Ok(expansion_num),
+                            Ok(number),
+                            Some(platform_id),
+                        ) = (
+                            u8::from_str_radix(caps.get(1).unwrap().as_str(), 16),
+                            u8::from_str_radix(caps.get(2).unwrap().as_str(), 16),
+                            u8::from_str_radix(caps.get(3).unwrap().as_str(), 16),
+                            PlatformId::from_name(caps.get(4).unwrap().as_str()),
                         ) {
+                            let category = Category::from_u8(category_num);
+                            if !builder.includes_category(category) {
+                                continue;
+                            }
                             let id = SqPackId {
                                 category,
-                                expansion,
+                                expansion: Expansion::from_u8(expansion_num),
                                 number,
                             };
-                            ids.insert(id);
+                            listing
+                                .packs
+                                .entry(id)
+                                .and_modify(|existing| *existing = platform_id.min(*existing))
+                                .or_insert(platform_id);
                         }
                     }
                 }
+                Err(_) => listing.skipped_paths.push(entry.path()),
             }
         }
     }
-    Ok(ids)
+    Ok(listing)
 }
 
 /// This represents a pointer to an entry in one of the data files. It consists of a number
@@ -672,47 +1702,260 @@ pub struct CollisionEntry<H: IndexHash> {
     path: String,
 }
 
+/// A hash shared by more than one file in an index. See [`Index::hash_collisions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashCollision<'a, H: IndexHash> {
+    pub hash: H,
+    /// Number of entries in the file table with this hash.
+    pub table_entries: usize,
+    /// Paths recorded for this hash in the collision table.
+    pub paths: Vec<&'a str>,
+}
+
+impl<H: IndexHash> CollisionEntry<H> {
+    pub fn hash(&self) -> H {
+        self.hash
+    }
+
+    pub fn pointer(&self) -> FilePointer {
+        self.pointer
+    }
+
+    /// The full path of the file, in lowercase.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Opens a [`GameData`] with only a subset of its packs. Packs outside of the selected categories
+/// and expansions are skipped during discovery, and will not be visible through the resulting
+/// `GameData`. By default, all packs are included.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct GameDataBuilder {
+    categories: Option<Vec<Category>>,
+    expansions: Option<Vec<Expansion>>,
+    memory_map_indexes: bool,
+    memory_budget: Option<Arc<MemoryBudget>>,
+    access_log: bool,
+    allow_writes_while_in_use: bool,
+}
+
+#[cfg(feature = "std")]
+impl GameDataBuilder {
+    pub fn new() -> GameDataBuilder {
+        GameDataBuilder::default()
+    }
+
+    /// Restricts discovery to packs in the given categories.
+    pub fn only(mut self, categories: &[Category]) -> GameDataBuilder {
+        self.categories = Some(categories.to_vec());
+        self
+    }
+
+    /// Restricts discovery to packs in the given expansions.
+    pub fn only_expansions(mut self, expansions: &[Expansion]) -> GameDataBuilder {
+        self.expansions = Some(expansions.to_vec());
+        self
+    }
+
+    /// Memory-maps index files instead of reading all of their entries into memory. Entries are
+    /// then parsed as they are looked up, which saves memory and time for tools that only look up
+    /// a few files. Index files must not be modified by other programs while they are mapped.
+    pub fn memory_map_indexes(mut self, enabled: bool) -> GameDataBuilder {
+        self.memory_map_indexes = enabled;
+        self
+    }
+
+    /// Limits the memory used by decompressed entries across all of the `DataFileSet`s created
+    /// by [`GameData::data_files`]. See [`MemoryBudget`].
+    pub fn memory_budget(mut self, bytes: u64) -> GameDataBuilder {
+        self.memory_budget = Some(Arc::new(MemoryBudget::new(bytes)));
+        self
+    }
+
+    /// Records every lookup by path or hash in an [`AccessLog`], which can be read back through
+    /// [`GameData::access_log`].
+    pub fn access_log(mut self, enabled: bool) -> GameDataBuilder {
+        self.access_log = enabled;
+        self
+    }
+
+    /// Skips the check in [`GameData::replace_file`] for the game or other programs using the
+    /// installation. This is also needed on platforms where the check isn't supported, since
+    /// `replace_file` fails there otherwise.
+    pub fn allow_writes_while_in_use(mut self, enabled: bool) -> GameDataBuilder {
+        self.allow_writes_while_in_use = enabled;
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<GameData> {
+        let root_path = path.as_ref().to_owned();
+        let listing = list_packs(&root_path, self)?;
+        let mut index_map_1 = BTreeMap::new();
+        let mut index_map_2 = BTreeMap::new();
+        for id in listing.packs.keys() {
+            index_map_1.insert(*id, OnceCell::new());
+            index_map_2.insert(*id, OnceCell::new());
+        }
+        Ok(GameData {
+            root_path,
+            index_map_1,
+            index_map_2,
+            memory_map_indexes: self.memory_map_indexes,
+            memory_budget: self.memory_budget.clone(),
+            platforms: listing.packs,
+            detected_expansions: listing.expansions,
+            skipped_paths: listing.skipped_paths,
+            memory: None,
+            access_log: self.access_log.then(AccessLog::new),
+            allow_writes_while_in_use: self.allow_writes_while_in_use,
+        })
+    }
+
+    fn includes_category(&self, category: Category) -> bool {
+        self.categories
+            .as_ref()
+            .is_none_or(|categories| categories.contains(&category))
+    }
+
+    fn includes_expansion(&self, expansion: Expansion) -> bool {
+        self.expansions
+            .as_ref()
+            .is_none_or(|expansions| expansions.contains(&expansion))
+    }
+}
+
+#[cfg(feature = "std")]
 pub struct GameData {
     root_path: PathBuf,
     index_map_1: BTreeMap<SqPackId, OnceCell<Index<IndexEntry1>>>,
     index_map_2: BTreeMap<SqPackId, OnceCell<Index<IndexEntry2>>>,
+    /// The platform each pack was built for.
+    platforms: BTreeMap<SqPackId, PlatformId>,
+    memory_map_indexes: bool,
+    memory_budget: Option<Arc<MemoryBudget>>,
+    detected_expansions: BTreeSet<Expansion>,
+    skipped_paths: Vec<PathBuf>,
+    /// Set if the packs are held in memory, in which case `root_path` is empty.
+    memory: Option<Arc<MemoryProvider>>,
+    access_log: Option<AccessLog>,
+    allow_writes_while_in_use: bool,
 }
 
+#[cfg(feature = "std")]
 impl GameData {
     pub fn new<P: AsRef<Path>>(path: P) -> io::Result<GameData> {
-        let root_path = path.as_ref().to_owned();
-        let ids = list_packs(&root_path)?;
+        GameDataBuilder::new().open(path)
+    }
+
+    /// Uses packs held in memory, rather than an installation directory. Everything that reads
+    /// packs works the same way, but there are no version files, and files can't be replaced.
+    pub fn from_provider(provider: MemoryProvider) -> GameData {
         let mut index_map_1 = BTreeMap::new();
         let mut index_map_2 = BTreeMap::new();
-        for id in ids {
-            index_map_1.insert(id, OnceCell::new());
-            index_map_2.insert(id, OnceCell::new());
+        let mut platforms = BTreeMap::new();
+        let mut detected_expansions = BTreeSet::new();
+        for (id, pack) in provider.packs() {
+            index_map_1.insert(*id, OnceCell::new());
+            index_map_2.insert(*id, OnceCell::new());
+            // Packs held in memory have no file names, so their platform comes from the SqPack
+            // header instead.
+            let platform_id = parse_sqpack_header(&pack.index2)
+                .map_or(PlatformId::Win32, |header| header.platform_id);
+            platforms.insert(*id, platform_id);
+            detected_expansions.insert(id.expansion);
         }
-        Ok(GameData {
-            root_path,
+        GameData {
+            root_path: PathBuf::new(),
             index_map_1,
             index_map_2,
-        })
+            platforms,
+            memory_map_indexes: false,
+            memory_budget: None,
+            detected_expansions,
+            skipped_paths: Vec::new(),
+            memory: Some(Arc::new(provider)),
+            access_log: None,
+            allow_writes_while_in_use: false,
+        }
+    }
+
+    /// Returns a builder, to open only some of the packs.
+    pub fn builder() -> GameDataBuilder {
+        GameDataBuilder::new()
+    }
+
+    /// Returns the platform a pack was built for. Packs that weren't found are assumed to be for
+    /// Windows.
+    pub fn platform_id(&self, pack_id: SqPackId) -> PlatformId {
+        self.platforms
+            .get(&pack_id)
+            .copied()
+            .unwrap_or(PlatformId::Win32)
+    }
+
+    /// Returns the paths of files and directories in the `sqpack` directory that were skipped
+    /// when listing packs, because their names aren't valid UTF-8. These can't be packs, but may
+    /// point to a damaged or unusual installation.
+    pub fn skipped_paths(&self) -> &[PathBuf] {
+        &self.skipped_paths
+    }
+
+    /// Returns the memory budget set with [`GameDataBuilder::memory_budget`], which is shared by
+    /// every [`DataFileSet`] of this installation.
+    pub fn memory_budget(&self) -> Option<&Arc<MemoryBudget>> {
+        self.memory_budget.as_ref()
+    }
+
+    /// Returns the log of lookups, if it was enabled with [`GameDataBuilder::access_log`].
+    pub fn access_log(&self) -> Option<&AccessLog> {
+        self.access_log.as_ref()
+    }
+
+    fn log_access<T>(
+        &self,
+        target: impl FnOnce() -> AccessTarget,
+        result: &Result<T, Error>,
+        packs: impl FnOnce(&T) -> Vec<SqPackId>,
+    ) {
+        if let Some(log) = self.access_log.as_ref() {
+            let outcome = match result {
+                Ok(value) => match packs(value) {
+                    packs if packs.is_empty() => AccessOutcome::NotFound,
+                    packs => AccessOutcome::Found(packs),
+                },
+                Err(e) => AccessOutcome::Error(e.to_string()),
+            };
+            log.record(target(), outcome);
+        }
     }
 
     fn build_index_path<I: IndexEntry>(&self, id: SqPackId) -> PathBuf {
         self.root_path
             .join("game")
             .join("sqpack")
-            .join(id.expansion.name())
-            .join(format!(
-                "{:02x}{:02x}{:02x}.win32.{}",
-                id.category as u8,
-                id.expansion as u8,
-                id.number,
-                I::FILE_EXTENSION
-            ))
+            .join(&*id.expansion.name())
+            .join(id.file_name(self.platform_id(id), I::FILE_EXTENSION))
     }
 
+    /// Finds the pack and location of a file by its path. The `.index2` files are searched first,
+    /// and then the `.index` files, since some `.index2` files are missing entries.
     pub fn lookup_path_locator(
         &self,
         path: &str,
     ) -> Result<Option<(SqPackId, FilePointer)>, Error> {
+        let path = &*normalize_path(path);
+        let result = self.find_path_locator(path);
+        self.log_access(
+            || AccessTarget::Path(path.to_string()),
+            &result,
+            |found| found.iter().map(|(id, _)| *id).collect(),
+        );
+        result
+    }
+
+    fn find_path_locator(&self, path: &str) -> Result<Option<(SqPackId, FilePointer)>, Error> {
         let segments: Vec<_> = path.splitn(3, '/').collect();
         let category = if let Ok(category) = Category::parse_name(segments[0]) {
             category
@@ -732,7 +1975,39 @@ impl GameData {
         for id in self.iter_packs_category_expansion(category, expansion) {
             let index = self.get_index_2(&id).unwrap()?;
             if let Some(pointer) = index.lookup(path) {
-                return Ok(Some((id, pointer)));
+                return Ok(Some((id, check_dat_number(id, index, pointer)?)));
+            }
+        }
+        for id in self.iter_packs_category_expansion(category, expansion) {
+            let index = self.get_index_1(&id).unwrap()?;
+            if let Some(pointer) = index.lookup(path) {
+                return Ok(Some((id, check_dat_number(id, index, pointer)?)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether a file exists, using only the index files.
+    pub fn exists(&self, path: &str) -> Result<bool, Error> {
+        Ok(self.lookup_path_locator(path)?.is_some())
+    }
+
+    /// Checks whether any pack has a file with the given path hash, using only the `.index2`
+    /// files.
+    pub fn exists_hash(&self, hash: &IndexHash2) -> Result<bool, Error> {
+        let result = self.find_hash(hash);
+        self.log_access(
+            || AccessTarget::Hash2(*hash),
+            &result,
+            |found| found.iter().copied().collect(),
+        );
+        Ok(result?.is_some())
+    }
+
+    fn find_hash(&self, hash: &IndexHash2) -> Result<Option<SqPackId>, Error> {
+        for id in self.iter_packs() {
+            if self.get_index_2(&id).unwrap()?.get_entry(hash).is_some() {
+                return Ok(Some(id));
             }
         }
         Ok(None)
@@ -750,7 +2025,69 @@ impl GameData {
         }
     }
 
+    /// Looks up a file and reads a summary of its data entry, without decompressing it. Returns
+    /// `None` if the file isn't found.
+    pub fn file_metadata(
+        &self,
+        data_file_set: &mut DataFileSet,
+        path: &str,
+    ) -> Result<Option<FileMetadata>, Error> {
+        if let Some((pack_id, file_pointer)) = self.lookup_path_locator(path)? {
+            Ok(Some(data_file_set.entry_metadata(pack_id, file_pointer)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Opens a file for reading, decompressing its contents as they are read. This avoids
+    /// holding large files in memory all at once. Returns `None` if the file isn't found.
+    pub fn open_file<'a>(
+        &self,
+        data_file_set: &'a mut DataFileSet,
+        path: &str,
+    ) -> Result<Option<FileReader<'a>>, Error> {
+        if let Some((pack_id, file_pointer)) = self.lookup_path_locator(path)? {
+            Ok(Some(data_file_set.open_entry(pack_id, file_pointer)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Replaces the contents of a file in its pack, using [`encoding::PackEditor`]. Returns
+    /// `false` if the file isn't found.
+    ///
+    /// Fails with [`Error::InUse`] if the game, its launcher, or its updater is running, or if
+    /// another process has files in the installation open, unless this was allowed with
+    /// [`GameDataBuilder::allow_writes_while_in_use`].
+    pub fn replace_file(&mut self, path: &str, data: &[u8]) -> Result<bool, Error> {
+        if self.memory.is_some() {
+            return Err(io::Error::from(io::ErrorKind::Unsupported).into());
+        }
+        if !self.allow_writes_while_in_use {
+            let usage = live::install_usage(&self.root_path)?;
+            if !usage.is_empty() {
+                return Err(Error::InUse(usage));
+            }
+        }
+        let pack_id = match self.lookup_path_locator(path)? {
+            Some((pack_id, _)) => pack_id,
+            None => return Ok(false),
+        };
+        let sqpack_dir = self.root_path.join("game").join("sqpack");
+        let replaced = encoding::PackEditor::new(&sqpack_dir, self.platform_id(pack_id), pack_id)
+            .replace_file(path, data)?;
+        // Drop the cached indexes, so they are read again with the new pointers.
+        if let Some(cell) = self.index_map_1.get_mut(&pack_id) {
+            cell.take();
+        }
+        if let Some(cell) = self.index_map_2.get_mut(&pack_id) {
+            cell.take();
+        }
+        Ok(replaced)
+    }
+
     pub fn contains_folder(&self, path: &str) -> Result<bool, Error> {
+        let path = &*normalize_path(path);
         let segments: Vec<_> = path.splitn(3, '/').collect();
         let category = if let Ok(category) = Category::parse_name(segments[0]) {
             category
@@ -767,7 +2104,7 @@ impl GameData {
             Expansion::Base
         };
 
-        let crc = crc32(path.to_lowercase().as_bytes());
+        let crc = crc32(path.as_bytes());
 
         for id in self.iter_packs_category_expansion(category, expansion) {
             if let Some(Ok(index)) = self.get_index_1(&id) {
@@ -783,34 +2120,44 @@ impl GameData {
         &self,
         hash: &IndexHash1,
     ) -> Result<Vec<(SqPackId, FilePointer)>, Error> {
-        let mut pointers = Vec::new();
-        for id in self.iter_packs() {
-            let index = self.get_index_1(&id).unwrap()?;
-            if let Some(entry) = index.get(hash) {
-                match entry.pointer() {
-                    IndexPointer::Pointer(pointer) => pointers.push((id, pointer)),
-                    IndexPointer::Collision => todo!(),
+        let result = (|| {
+            let mut pointers = Vec::new();
+            for id in self.iter_packs() {
+                let index = self.get_index_1(&id).unwrap()?;
+                for pointer in index.get_pointers(hash) {
+                    pointers.push((id, check_dat_number(id, index, pointer)?));
                 }
             }
-        }
-        Ok(pointers)
+            Ok(pointers)
+        })();
+        self.log_access(
+            || AccessTarget::Hash1(*hash),
+            &result,
+            |found| found.iter().map(|(id, _)| *id).collect(),
+        );
+        result
     }
 
     pub fn lookup_hash_2_locator(
         &self,
         hash: &IndexHash2,
     ) -> Result<Vec<(SqPackId, FilePointer)>, Error> {
-        let mut pointers = Vec::new();
-        for id in self.iter_packs() {
-            let index = self.get_index_2(&id).unwrap()?;
-            if let Some(entry) = index.get(hash) {
-                match entry.pointer() {
-                    IndexPointer::Pointer(pointer) => pointers.push((id, pointer)),
-                    IndexPointer::Collision => todo!(),
+        let result = (|| {
+            let mut pointers = Vec::new();
+            for id in self.iter_packs() {
+                let index = self.get_index_2(&id).unwrap()?;
+                for pointer in index.get_pointers(hash) {
+                    pointers.push((id, check_dat_number(id, index, pointer)?));
                 }
             }
-        }
-        Ok(pointers)
+            Ok(pointers)
+        })();
+        self.log_access(
+            || AccessTarget::Hash2(*hash),
+            &result,
+            |found| found.iter().map(|(id, _)| *id).collect(),
+        );
+        result
     }
 
     pub fn lookup_hash_1_data(
@@ -835,10 +2182,47 @@ impl GameData {
             .collect()
     }
 
+    /// Iterates over the IDs of all packs, sorted by category, then expansion, then number.
     pub fn iter_packs(&self) -> impl Iterator<Item = SqPackId> + '_ {
         self.index_map_2.keys().copied()
     }
 
+    /// Iterates over the expansions whose directories were found in the installation, in order.
+    /// This includes expansions that were excluded with
+    /// [`GameDataBuilder::only_expansions`], and expansion directories without any packs.
+    pub fn detected_expansions(&self) -> impl Iterator<Item = Expansion> + '_ {
+        self.detected_expansions.iter().copied()
+    }
+
+    /// Reads the version files of the installation. Packs held in memory have none.
+    pub fn install_versions(&self) -> io::Result<InstallVersions> {
+        if self.memory.is_some() {
+            return Ok(InstallVersions::default());
+        }
+        InstallVersions::read(&self.root_path)
+    }
+
+    /// Reads the version of the base game from `game/ffxivgame.ver`. Returns `None` if the file
+    /// is missing.
+    pub fn game_version(&self) -> Result<Option<GameVersion>, Error> {
+        self.install_versions()?
+            .game
+            .map(|version| version.parse())
+            .transpose()
+    }
+
+    /// Reads the version of each expansion from the `.ver` file in its directory, as in
+    /// [`InstallVersions::read`].
+    pub fn expansion_versions(&self) -> Result<BTreeMap<Expansion, GameVersion>, Error> {
+        self.install_versions()?
+            .expansions
+            .into_iter()
+            .map(|(number, version)| Ok((Expansion::from_u8(number), version.parse()?)))
+            .collect()
+    }
+
+    /// Iterates over the IDs of all packs with the given category and expansion, sorted by
+    /// number.
     pub fn iter_packs_category_expansion(
         &self,
         category: Category,
@@ -862,8 +2246,15 @@ impl GameData {
     pub fn get_index_1(&self, id: &SqPackId) -> Option<Result<&Index<IndexEntry1>, Error>> {
         self.index_map_1.get(id).map(|cell| {
             cell.get_or_try_init(|| -> Result<Index<IndexEntry1>, Error> {
+                if let Some(memory) = &self.memory {
+                    return parse_index_1(&memory.packs()[id].index);
+                }
                 let path = self.build_index_path::<IndexEntry1>(*id);
-                load_index_1(path)
+                if self.memory_map_indexes {
+                    map_index_1(path)
+                } else {
+                    load_index_1(path)
+                }
             })
         })
     }
@@ -871,94 +2262,603 @@ impl GameData {
     pub fn get_index_2(&self, id: &SqPackId) -> Option<Result<&Index<IndexEntry2>, Error>> {
         self.index_map_2.get(id).map(|cell| {
             cell.get_or_try_init(|| {
+                if let Some(memory) = &self.memory {
+                    return parse_index_2(&memory.packs()[id].index2);
+                }
                 let path = self.build_index_path::<IndexEntry2>(*id);
-                load_index_2(path)
+                if self.memory_map_indexes {
+                    map_index_2(path)
+                } else {
+                    load_index_2(path)
+                }
             })
         })
     }
 
+    /// Loads an index file that may be truncated, such as after an interrupted download,
+    /// recovering as many entries as possible. The result is not cached.
+    pub fn salvage_index_1(
+        &self,
+        id: &SqPackId,
+    ) -> Option<Result<SalvagedIndex<IndexEntry1>, Error>> {
+        self.index_map_1
+            .contains_key(id)
+            .then(|| match &self.memory {
+                Some(memory) => salvage_index_1_data(&memory.packs()[id].index),
+                None => salvage_index_1(self.build_index_path::<IndexEntry1>(*id)),
+            })
+    }
+
+    /// Loads an `.index2` file that may be truncated, as with
+    /// [`salvage_index_1`](GameData::salvage_index_1).
+    pub fn salvage_index_2(
+        &self,
+        id: &SqPackId,
+    ) -> Option<Result<SalvagedIndex<IndexEntry2>, Error>> {
+        self.index_map_2
+            .contains_key(id)
+            .then(|| match &self.memory {
+                Some(memory) => salvage_index_2_data(&memory.packs()[id].index2),
+                None => salvage_index_2(self.build_index_path::<IndexEntry2>(*id)),
+            })
+    }
+
+    /// Loads both index files of a pack, and checks that they agree with each other. See
+    /// [`compare_indexes`].
+    pub fn check_index_consistency(
+        &self,
+        id: &SqPackId,
+    ) -> Option<Result<Vec<IndexDiscrepancy>, Error>> {
+        let index_1_res = self.get_index_1(id)?;
+        let index_2_res = self.get_index_2(id)?;
+        Some(index_1_res.and_then(|index_1| Ok(compare_indexes(index_1, index_2_res?))))
+    }
+
+    /// Iterates over every file in a pack, with its hashes from both index files, sorted by file
+    /// pointer. If several hashes in either file point to the same data, each hash is returned
+    /// separately, with the other hash left as `None`.
+    #[allow(clippy::type_complexity)]
+    pub fn iter_files(
+        &self,
+        id: &SqPackId,
+    ) -> Option<
+        Result<impl Iterator<Item = (Option<IndexHash1>, Option<IndexHash2>, FilePointer)>, Error>,
+    > {
+        let index_1_res = self.get_index_1(id)?;
+        let index_2_res = self.get_index_2(id)?;
+        Some(index_1_res.and_then(|index_1| {
+            let index_2 = index_2_res?;
+            let mut entries = BTreeMap::<FilePointer, (Vec<IndexHash1>, Vec<IndexHash2>)>::new();
+            for (hash, pointer) in index_1.iter() {
+                entries.entry(pointer).or_default().0.push(hash);
+            }
+            for (hash, pointer) in index_2.iter() {
+                entries.entry(pointer).or_default().1.push(hash);
+            }
+            let mut files = Vec::with_capacity(entries.len());
+            for (pointer, (hashes_1, hashes_2)) in entries {
+                if hashes_1.len() <= 1 && hashes_2.len() <= 1 {
+                    files.push((
+                        hashes_1.first().copied(),
+                        hashes_2.first().copied(),
+                        pointer,
+                    ));
+                } else {
+                    files.extend(hashes_1.into_iter().map(|hash| (Some(hash), None, pointer)));
+                    files.extend(hashes_2.into_iter().map(|hash| (None, Some(hash), pointer)));
+                }
+            }
+            Ok(files.into_iter())
+        }))
+    }
+
+    /// Writes every file in a pack to a directory, without needing to know their paths. Files are
+    /// named after their hashes in hexadecimal, as `{folder CRC}/{filename CRC}` if they are in
+    /// the `.index` file, or as `{path CRC}` otherwise. Returns the number of files written.
+    pub fn extract_pack(
+        &self,
+        data_file_set: &mut DataFileSet,
+        id: &SqPackId,
+        output_dir: &Path,
+    ) -> Option<Result<usize, Error>> {
+        let files = match self.iter_files(id)? {
+            Ok(files) => files,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(extract_files(data_file_set, *id, files, output_dir))
+    }
+
+    /// Writes the files in a pack that match a pattern set to a directory, as with
+    /// [`extract_pack`](Self::extract_pack). `resolve_path` looks up the path of a file from its
+    /// hashes, for example with [`pathdb::PreparedStatements::resolve_path`], so it can be
+    /// matched against path patterns. Files may have an `.index` hash, an `.index2` hash, or
+    /// both.
+    pub fn extract_pack_matching(
+        &self,
+        data_file_set: &mut DataFileSet,
+        id: &SqPackId,
+        output_dir: &Path,
+        patterns: &PatternSet,
+        mut resolve_path: impl FnMut(Option<IndexHash1>, Option<IndexHash2>) -> Option<String>,
+    ) -> Option<Result<usize, Error>> {
+        let files = match self.iter_files(id)? {
+            Ok(files) => files,
+            Err(e) => return Some(Err(e)),
+        };
+        let files = files.filter(|(hash_1, hash_2, _)| {
+            let path = resolve_path(*hash_1, *hash_2);
+            patterns.matches(path.as_deref(), *hash_1, *hash_2)
+        });
+        Some(extract_files(data_file_set, *id, files, output_dir))
+    }
+
     pub fn data_files(&self) -> DataFileSet {
-        DataFileSet::new(self.root_path.clone())
+        let mut data_file_set = DataFileSet::new(self.root_path.clone());
+        data_file_set.platforms = self.platforms.clone();
+        data_file_set.memory = self.memory.clone();
+        data_file_set.memory_budget = self.memory_budget.clone();
+        data_file_set
     }
 }
 
+#[cfg(feature = "std")]
+fn extract_files(
+    data_file_set: &mut DataFileSet,
+    id: SqPackId,
+    files: impl Iterator<Item = (Option<IndexHash1>, Option<IndexHash2>, FilePointer)>,
+    output_dir: &Path,
+) -> Result<usize, Error> {
+    let output_dir = extended_length_path(output_dir);
+    let mut count = 0;
+    for (hash_1, hash_2, pointer) in files {
+        let path = match (hash_1, hash_2) {
+            (Some(hash), _) => output_dir
+                .join(format!("{:08x}", hash.folder_crc))
+                .join(format!("{:08x}", hash.filename_crc)),
+            (None, Some(hash)) => output_dir.join(format!("{:08x}", hash.path_crc)),
+            (None, None) => unreachable!(),
+        };
+        let data = data_file_set.fetch_data(id, pointer)?;
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, data)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// A disagreement between the `.index` and `.index2` files of one pack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexDiscrepancy {
+    /// The two files disagree on the number of data files in the pack.
+    DatFileCount { index_1: u32, index_2: u32 },
+    /// A file in the `.index` file points to a location that no file in the `.index2` file
+    /// points to.
+    MissingFromIndex2 {
+        hash: IndexHash1,
+        pointer: FilePointer,
+    },
+    /// A file in the `.index2` file points to a location that no file in the `.index` file
+    /// points to.
+    MissingFromIndex1 {
+        hash: IndexHash2,
+        pointer: FilePointer,
+    },
+    /// Both files point to this location, but from a different number of entries.
+    SharedPointer {
+        pointer: FilePointer,
+        hashes_1: Vec<IndexHash1>,
+        hashes_2: Vec<IndexHash2>,
+    },
+}
+
+/// Checks that every entry in a pack's `.index` file has an entry in its `.index2` file that
+/// points to the same data, and vice versa. Since the two files are written together, any
+/// discrepancy indicates that one of them was corrupted or modified. Discrepancies are returned
+/// sorted by file pointer, after any data file count mismatch.
+pub fn compare_indexes(
+    index_1: &Index<IndexEntry1>,
+    index_2: &Index<IndexEntry2>,
+) -> Vec<IndexDiscrepancy> {
+    let mut discrepancies = Vec::new();
+    if index_1.dat_file_count() != index_2.dat_file_count() {
+        discrepancies.push(IndexDiscrepancy::DatFileCount {
+            index_1: index_1.dat_file_count(),
+            index_2: index_2.dat_file_count(),
+        });
+    }
+
+    let mut pointers = BTreeMap::<FilePointer, (Vec<IndexHash1>, Vec<IndexHash2>)>::new();
+    for (hash, pointer) in index_1.iter() {
+        pointers.entry(pointer).or_default().0.push(hash);
+    }
+    for (hash, pointer) in index_2.iter() {
+        pointers.entry(pointer).or_default().1.push(hash);
+    }
+    for (pointer, (hashes_1, hashes_2)) in pointers {
+        if hashes_1.len() == hashes_2.len() {
+            continue;
+        }
+        if hashes_2.is_empty() {
+            discrepancies.extend(
+                hashes_1
+                    .into_iter()
+                    .map(|hash| IndexDiscrepancy::MissingFromIndex2 { hash, pointer }),
+            );
+        } else if hashes_1.is_empty() {
+            discrepancies.extend(
+                hashes_2
+                    .into_iter()
+                    .map(|hash| IndexDiscrepancy::MissingFromIndex1 { hash, pointer }),
+            );
+        } else {
+            discrepancies.push(IndexDiscrepancy::SharedPointer {
+                pointer,
+                hashes_1,
+                hashes_2,
+            });
+        }
+    }
+    discrepancies
+}
+
+/// Selects the order in which [`DataFileSet::iter_files_ordered`] returns files.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOrder {
+    /// Sort by file pointer, i.e. by data file number and then by offset. This is the fastest
+    /// order to read files in.
+    Offset,
+    /// Sort by the hashes used in the index.
+    Hash,
+    /// Sort by uncompressed size, from smallest to largest.
+    Size,
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct DataFileKey {
     pack_id: SqPackId,
     dat_number: u8,
 }
 
+/// Maximum number of parsed data entry headers kept by each [`DataFileSet`].
+#[cfg(feature = "std")]
+const ENTRY_HEADER_CACHE_CAPACITY: usize = 256;
+
+/// A small cache of parsed data entry headers, keyed by pack and file pointer, so that looking up
+/// an entry's blocks and then fetching its contents only reads the headers once. The oldest
+/// entries are evicted first.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct EntryHeaderCache {
+    map: BTreeMap<(SqPackId, FilePointer), Arc<DataBlocks>>,
+    order: VecDeque<(SqPackId, FilePointer)>,
+}
+
+#[cfg(feature = "std")]
+impl EntryHeaderCache {
+    fn get(&self, key: &(SqPackId, FilePointer)) -> Option<&Arc<DataBlocks>> {
+        self.map.get(key)
+    }
+
+    fn insert(&mut self, key: (SqPackId, FilePointer), blocks: Arc<DataBlocks>) {
+        if self.map.insert(key, blocks).is_none() {
+            self.order.push_back(key);
+            if self.order.len() > ENTRY_HEADER_CACHE_CAPACITY {
+                let oldest = self.order.pop_front().unwrap();
+                self.map.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Entries with fewer blocks than this are always decompressed on the calling thread.
+#[cfg(feature = "rayon")]
+const PARALLEL_MIN_BLOCKS: usize = 4;
+
 /// This provides access to `.dat?` files, and lazily caches open file handles, so they can be
 /// reused. It is intended that each unit of parallelism should have its own `DataFileSet`.
+#[cfg(feature = "std")]
 pub struct DataFileSet {
     root_path: PathBuf,
-    files: BTreeMap<DataFileKey, File>,
+    files: BTreeMap<DataFileKey, DataFile>,
+    /// The platform each pack was built for, which determines the byte order of its entries.
+    platforms: BTreeMap<SqPackId, PlatformId>,
+    read_backend: ReadBackend,
+    read_ahead: ReadAhead,
+    header_cache: EntryHeaderCache,
+    #[cfg(feature = "rayon")]
+    parallel_decompression: bool,
+    memory_budget: Option<Arc<MemoryBudget>>,
+    memory: Option<Arc<MemoryProvider>>,
 }
 
+#[cfg(feature = "std")]
 impl DataFileSet {
     fn new(root_path: PathBuf) -> DataFileSet {
         DataFileSet {
             root_path,
             files: BTreeMap::new(),
+            platforms: BTreeMap::new(),
+            read_backend: ReadBackend::default(),
+            read_ahead: ReadAhead::default(),
+            header_cache: EntryHeaderCache::default(),
+            #[cfg(feature = "rayon")]
+            parallel_decompression: false,
+            memory_budget: None,
+            memory: None,
+        }
+    }
+
+    /// Sets how entries are read by [`iter_files`](Self::iter_files) and
+    /// [`iter_files_both_hashes`](Self::iter_files_both_hashes).
+    pub fn set_read_backend(&mut self, read_backend: ReadBackend) {
+        self.read_backend = read_backend;
+    }
+
+    /// Sets the read-ahead hint used when opening data files. Any files that are already open are
+    /// closed, so that they will be reopened with the new hint.
+    pub fn set_read_ahead(&mut self, read_ahead: ReadAhead) {
+        if read_ahead != self.read_ahead {
+            self.read_ahead = read_ahead;
+            self.files.clear();
         }
     }
 
-    fn build_data_path(root_path: &Path, id: SqPackId, dat_number: u8) -> PathBuf {
+    /// Sets whether [`fetch_data`](Self::fetch_data) decompresses the blocks of large entries in
+    /// parallel, on rayon's global thread pool. This is off by default, since it doesn't help
+    /// when each thread already has its own `DataFileSet`.
+    #[cfg(feature = "rayon")]
+    pub fn set_parallel_decompression(&mut self, enabled: bool) {
+        self.parallel_decompression = enabled;
+    }
+
+    fn build_data_path(
+        root_path: &Path,
+        id: SqPackId,
+        platform_id: PlatformId,
+        dat_number: u8,
+    ) -> PathBuf {
         root_path
             .join("game")
             .join("sqpack")
-            .join(id.expansion.name())
-            .join(format!(
-                "{:02x}{:02x}{:02x}.win32.dat{}",
-                id.category as u8, id.expansion as u8, id.number, dat_number,
-            ))
+            .join(&*id.expansion.name())
+            .join(id.file_name(platform_id, &format!("dat{}", dat_number)))
+    }
+
+    /// Returns the platform a pack was built for. See [`GameData::platform_id`].
+    pub fn platform_id(&self, pack_id: SqPackId) -> PlatformId {
+        self.platforms
+            .get(&pack_id)
+            .copied()
+            .unwrap_or(PlatformId::Win32)
     }
 
-    pub fn open(&mut self, pack_id: SqPackId, dat_number: u8) -> Result<&mut File, io::Error> {
+    fn endianness(&self, pack_id: SqPackId) -> Endianness {
+        self.platform_id(pack_id).endianness()
+    }
+
+    pub fn open(&mut self, pack_id: SqPackId, dat_number: u8) -> Result<&mut DataFile, io::Error> {
         let key = DataFileKey {
             pack_id,
             dat_number,
         };
+        let platform_id = self.platform_id(pack_id);
         Ok(match self.files.entry(key) {
-            std::collections::btree_map::Entry::Vacant(e) => e.insert(File::open(
-                Self::build_data_path(&self.root_path, pack_id, dat_number),
-            )?),
+            std::collections::btree_map::Entry::Vacant(e) => e.insert(match &self.memory {
+                Some(memory) => memory.open_dat(pack_id, dat_number)?,
+                None => DataFile::File(open_data_file(
+                    &Self::build_data_path(&self.root_path, pack_id, platform_id, dat_number),
+                    self.read_ahead,
+                )?),
+            }),
             std::collections::btree_map::Entry::Occupied(e) => e.into_mut(),
         })
     }
 
-    pub fn fetch_data(
+    /// Opens the data file holding an entry. Unlike [`open`](Self::open), a missing file is
+    /// reported as [`Error::MissingDataFile`].
+    fn open_entry_file(
         &mut self,
         pack_id: SqPackId,
         file_pointer: FilePointer,
-    ) -> Result<Vec<u8>, Error> {
-        decompress_file(
-            self.open(pack_id, file_pointer.data_file_id())?,
-            file_pointer.offset(),
-        )
+    ) -> Result<&mut DataFile, Error> {
+        let dat_number = file_pointer.data_file_id();
+        self.open(pack_id, dat_number).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::MissingDataFile {
+                pack_id,
+                dat_number,
+            },
+            _ => Error::Io(e),
+        })
     }
 
-    pub fn iter_files<'a, I: IndexEntry>(
-        &'a mut self,
-        pack_id: SqPackId,
-        index: &'a Index<I>,
-    ) -> impl Iterator<Item = Result<(I::Hash, Vec<u8>), Error>> + 'a {
-        let mut entries: Vec<_> = index.iter().collect();
-        // Sort by file pointer to improve disk locality.
-        entries.sort_unstable_by_key(|(_, pointer)| *pointer);
-        entries.into_iter().map(move |(hash, pointer)| {
-            Ok((
-                hash,
-                decompress_file(
-                    self.open(pack_id, pointer.data_file_id())?,
-                    pointer.offset(),
-                )?,
-            ))
-        })
+    /// Reads the header that follows the SqPack header in a data file, checking that it records
+    /// the same data file number as the file's name.
+    pub fn data_header(&mut self, pack_id: SqPackId, dat_number: u8) -> Result<DataHeader, Error> {
+        let header = read_data_header(self.open(pack_id, dat_number)?)?;
+        if header.spanned_dat != u32::from(dat_number) + 1 {
+            return Err(Error::DataFileMismatch {
+                pack_id,
+                dat_number,
+                spanned_dat: header.spanned_dat,
+            });
+        }
+        Ok(header)
     }
 
-    pub fn iter_files_both_hashes<'a>(
-        &'a mut self,
+    /// Returns the parsed headers of a data entry, describing its type and the locations of its
+    /// blocks. Headers are cached, so a following call to [`fetch_data`](Self::fetch_data) for
+    /// the same entry will not parse them again.
+    pub fn entry_blocks(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<&DataBlocks, Error> {
+        self.cached_entry_blocks(pack_id, file_pointer)?;
+        Ok(self.header_cache.get(&(pack_id, file_pointer)).unwrap())
+    }
+
+    fn cached_entry_blocks(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<Arc<DataBlocks>, Error> {
+        let key = (pack_id, file_pointer);
+        if let Some(blocks) = self.header_cache.get(&key) {
+            return Ok(Arc::clone(blocks));
+        }
+        let endianness = self.endianness(pack_id);
+        let blocks = Arc::new(read_data_entry_headers(
+            self.open_entry_file(pack_id, file_pointer)?,
+            file_pointer.offset(),
+            endianness,
+        )?);
+        self.header_cache.insert(key, Arc::clone(&blocks));
+        Ok(blocks)
+    }
+
+    /// Opens a data entry for reading, decompressing its contents as they are read. See
+    /// [`FileReader`].
+    pub fn open_entry(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<FileReader<'_>, Error> {
+        let blocks = self.cached_entry_blocks(pack_id, file_pointer)?;
+        let endianness = self.endianness(pack_id);
+        FileReader::new(
+            self.open_entry_file(pack_id, file_pointer)?,
+            blocks,
+            endianness,
+        )
+    }
+
+    /// Returns the uncompressed size of a data entry, from its headers.
+    pub fn entry_size(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<u32, Error> {
+        let endianness = self.endianness(pack_id);
+        read_data_entry_size(
+            self.open_entry_file(pack_id, file_pointer)?,
+            file_pointer.offset(),
+            endianness,
+        )
+    }
+
+    /// Returns a summary of a data entry, from its headers. See [`FileMetadata`].
+    pub fn entry_metadata(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<FileMetadata, Error> {
+        let blocks = self.cached_entry_blocks(pack_id, file_pointer)?;
+        let uncompressed_size = match blocks.total_decompressed_size() {
+            Some(size) => size,
+            None => self.entry_size(pack_id, file_pointer)?,
+        };
+        Ok(FileMetadata {
+            entry_type: blocks.entry_type(),
+            uncompressed_size,
+            compressed_size: blocks.total_compressed_size(),
+            block_count: blocks.blocks().count(),
+            dat_number: file_pointer.data_file_id(),
+        })
+    }
+
+    /// Reads and decompresses a file. If a [`MemoryBudget`] is set, the file's size is only
+    /// reserved while it is decompressed. Use [`fetch_data_reserved`](Self::fetch_data_reserved)
+    /// to keep the reservation while the data is in use.
+    pub fn fetch_data(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<Vec<u8>, Error> {
+        self.fetch_data_reserved(pack_id, file_pointer)
+            .map(|(data, _reservation)| data)
+    }
+
+    /// Reads and decompresses a file, like [`fetch_data`](Self::fetch_data), and returns the
+    /// file's reservation from the [`MemoryBudget`], if one is set, along with its data. Dropping
+    /// the reservation once the data is no longer needed lets other threads fetch files. A thread
+    /// that holds a reservation waits on itself if it fetches more than the rest of the budget, so
+    /// reservations should be dropped before fetching the next file.
+    pub fn fetch_data_reserved(
+        &mut self,
+        pack_id: SqPackId,
+        file_pointer: FilePointer,
+    ) -> Result<(Vec<u8>, Option<Reservation>), Error> {
+        let blocks = self.cached_entry_blocks(pack_id, file_pointer)?;
+        let reservation = self.memory_budget.as_ref().map(|budget| {
+            budget.reserve(blocks.total_decompressed_size().unwrap_or_default().into())
+        });
+        let endianness = self.endianness(pack_id);
+        #[cfg(feature = "rayon")]
+        if self.parallel_decompression && blocks.all_blocks().count() >= PARALLEL_MIN_BLOCKS {
+            let data = parser::decompress_blocks_parallel(
+                self.open_entry_file(pack_id, file_pointer)?,
+                &blocks,
+                endianness,
+            )?;
+            return Ok((data, reservation));
+        }
+        let data = decompress_blocks(
+            self.open_entry_file(pack_id, file_pointer)?,
+            &blocks,
+            endianness,
+        )?;
+        Ok((data, reservation))
+    }
+
+    /// Iterates over the contents of every file in an index, sorted by file pointer. See
+    /// [`iter_files_ordered`](Self::iter_files_ordered).
+    pub fn iter_files<'a, I: IndexEntry>(
+        &'a mut self,
+        pack_id: SqPackId,
+        index: &'a Index<I>,
+    ) -> impl Iterator<Item = Result<(I::Hash, Vec<u8>), Error>> + 'a {
+        let mut entries: Vec<_> = index.iter().collect();
+        // Sort by file pointer to improve disk locality.
+        entries.sort_unstable_by_key(|(_, pointer)| *pointer);
+        EntryReader::new(self, pack_id, entries)
+    }
+
+    /// Iterates over the contents of every file in an index, in the given order. Ties are broken
+    /// by file pointer, so the order is the same from one run to the next.
+    ///
+    /// Sorting by [`FileOrder::Size`] reads the headers of every entry before the first file is
+    /// returned.
+    #[allow(clippy::type_complexity)]
+    pub fn iter_files_ordered<'a, I: IndexEntry>(
+        &'a mut self,
+        pack_id: SqPackId,
+        index: &'a Index<I>,
+        order: FileOrder,
+    ) -> Result<impl Iterator<Item = Result<(I::Hash, Vec<u8>), Error>> + 'a, Error> {
+        let mut entries: Vec<_> = index.iter().collect();
+        match order {
+            FileOrder::Offset => entries.sort_unstable_by_key(|(_, pointer)| *pointer),
+            FileOrder::Hash => entries.sort_unstable_by_key(|(hash, pointer)| (*hash, *pointer)),
+            FileOrder::Size => {
+                let mut sizes = BTreeMap::new();
+                for (_, pointer) in entries.iter() {
+                    if let std::collections::btree_map::Entry::Vacant(e) = sizes.entry(*pointer) {
+                        let endianness = self.endianness(pack_id);
+                        let file = self.open(pack_id, pointer.data_file_id())?;
+                        e.insert(read_data_entry_size(file, pointer.offset(), endianness)?);
+                    }
+                }
+                entries.sort_unstable_by_key(|(_, pointer)| (sizes[pointer], *pointer));
+            }
+        }
+        Ok(EntryReader::new(self, pack_id, entries))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn iter_files_both_hashes<'a>(
+        &'a mut self,
         pack_id: SqPackId,
         index: &'a Index<IndexEntry1>,
         index2: &'a Index<IndexEntry2>,
@@ -981,22 +2881,25 @@ impl DataFileSet {
                 .replace(hash)
                 .is_none());
         }
-        entries.into_iter().map(move |(pointer, (hash1, hash2))| {
-            Ok((
-                hash1,
-                hash2,
-                decompress_file(
-                    self.open(pack_id, pointer.data_file_id())?,
-                    pointer.offset(),
-                )?,
-            ))
-        })
+        let entries = entries
+            .into_iter()
+            .map(|(pointer, hashes)| (hashes, pointer))
+            .collect();
+        EntryReader::new(self, pack_id, entries)
+            .map(|res| res.map(|((hash1, hash2), data)| (hash1, hash2, data)))
     }
 
     pub fn max_dat_number(&self, pack_id: SqPackId) -> u8 {
+        if let Some(memory) = &self.memory {
+            return memory.packs().get(&pack_id).map_or(0, |pack| {
+                u8::try_from(pack.dats.len().saturating_sub(1)).unwrap_or(u8::MAX)
+            });
+        }
         let mut number = 0;
         for i in 0u8.. {
-            if Self::build_data_path(&self.root_path, pack_id, i).is_file() {
+            if Self::build_data_path(&self.root_path, pack_id, self.platform_id(pack_id), i)
+                .is_file()
+            {
                 number = i;
             } else {
                 break;
@@ -1006,11 +2909,15 @@ impl DataFileSet {
     }
 }
 
+#[cfg(feature = "std")]
 pub enum PathOrHashes {
     Path(String),
     Hashes(IndexHash1, IndexHash2),
 }
 
+/// Writes several new packs into `base`, the `game/sqpack` directory of an installation, using
+/// each pack's side table if one is given. See [`encoding::write_pack`] to write a single pack.
+#[cfg(feature = "std")]
 pub fn write_packs<
     PackIt: Iterator<Item = (SqPackId, FileIt)>,
     FileIt: Iterator<Item = (PathOrHashes, Vec<u8>)>,
@@ -1021,8 +2928,7 @@ pub fn write_packs<
     mut side_tables: BTreeMap<SqPackId, SideTables>,
 ) -> Result<(), Error> {
     for (pack_id, files) in packs {
-        let io = RealPackIO::new(base.clone(), platform_id, pack_id)?;
-        let mut writer = PackSetWriter::new(io, PlatformId::Win32, pack_id)?;
+        let mut writer = create_pack_writer(&base, platform_id, pack_id)?;
         if let Some(side_table) = side_tables.remove(&pack_id) {
             writer.set_side_table(side_table);
         }
@@ -1036,87 +2942,1669 @@ pub fn write_packs<
         }
         writer.finalize()?;
     }
-    todo!()
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::BTreeMap,
         convert::TryInto,
         fs::File,
         io::{Cursor, Read, Seek, SeekFrom, Write},
+        path::Path,
         sync::{Arc, RwLock},
     };
 
     use tomestone_common::test_game_data_or_skip;
 
     use crate::{
-        encoding::{PackIO, PackSetWriter, SetLen},
+        encoding::{write_pack, PackIO, PackSetWriter, SetLen},
         sidetables::build_side_tables,
-        Category, Expansion, GameData, IndexEntry1, IndexEntry2,
+        write_packs, AccessOutcome, AccessTarget, Category, EntryType, Error, Expansion, FileOrder,
+        GameData, GameVersion, IndexEntry, IndexEntry1, IndexEntry2, IndexHash, IndexHash1,
+        IndexHash2, MemoryProvider, PathOrHashes, PlatformId, SqPackId,
     };
 
     #[test]
-    fn expansion_round_trip() {
-        assert_eq!(Expansion::parse_name("ffxiv").unwrap().name(), "ffxiv");
-        assert_eq!(Expansion::parse_name("ex1").unwrap().name(), "ex1");
-        assert_eq!(Expansion::parse_name("ex2").unwrap().name(), "ex2");
-        assert_eq!(Expansion::parse_name("ex3").unwrap().name(), "ex3");
+    fn hash_functions() {
+        assert_eq!(crate::crc32(b""), 0xffffffff);
+        assert_eq!(crate::crc32(b"exd"), !0x1c648666);
+        assert_eq!(
+            crate::sha1(b"abc"),
+            hex::decode("a9993e364706816aba3e25717850c26c9cd0d89d")
+                .unwrap()
+                .as_slice()
+        );
+    }
+
+    /// Writes a pack set containing the given files into an installation directory structure
+    /// under `root`.
+    pub(crate) fn write_test_pack(root: &Path, pack_id: SqPackId, files: &[(&str, &[u8])]) {
+        write_pack(
+            &root.join("game").join("sqpack"),
+            pack_id,
+            files.iter().copied(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn write_several_packs() {
+        let dir = tempfile::tempdir().unwrap();
+        let exd_pack = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let ui_pack = SqPackId {
+            category: Category::Ui,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let packs = vec![
+            (
+                exd_pack,
+                vec![(
+                    PathOrHashes::Path("exd/root.exl".to_string()),
+                    b"EXLT,2\r\n".to_vec(),
+                )],
+            ),
+            (
+                ui_pack,
+                vec![(
+                    PathOrHashes::Hashes(
+                        IndexHash1::hash("ui/icon/000000/000001.tex"),
+                        IndexHash2::hash("ui/icon/000000/000001.tex"),
+                    ),
+                    b"icon".to_vec(),
+                )],
+            ),
+        ];
+        write_packs(
+            dir.path().join("game").join("sqpack"),
+            PlatformId::Win32,
+            packs
+                .into_iter()
+                .map(|(pack_id, files)| (pack_id, files.into_iter())),
+            BTreeMap::new(),
+        )
+        .unwrap();
 
+        let game_data = GameData::new(dir.path()).unwrap();
         assert_eq!(
-            Expansion::parse_name(Expansion::Base.name()).unwrap(),
-            Expansion::Base
+            game_data.iter_packs().collect::<Vec<_>>(),
+            [ui_pack, exd_pack]
         );
+        let mut data_file_set = game_data.data_files();
         assert_eq!(
-            Expansion::parse_name(Expansion::Ex1.name()).unwrap(),
-            Expansion::Ex1
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/root.exl")
+                .unwrap()
+                .unwrap(),
+            b"EXLT,2\r\n"
         );
         assert_eq!(
-            Expansion::parse_name(Expansion::Ex2.name()).unwrap(),
-            Expansion::Ex2
+            game_data
+                .lookup_path_data(&mut data_file_set, "ui/icon/000000/000001.tex")
+                .unwrap()
+                .unwrap(),
+            b"icon"
         );
+    }
+
+    #[test]
+    fn ps3_pack() {
+        let dir = tempfile::tempdir().unwrap();
+        let sqpack_dir = dir.path().join("game").join("sqpack");
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let large = (0..20000u32).map(|i| i as u8).collect::<Vec<u8>>();
+        let files = vec![
+            (
+                PathOrHashes::Path("exd/root.exl".to_string()),
+                b"EXLT,2\r\n".to_vec(),
+            ),
+            (
+                PathOrHashes::Path("exd/large.exd".to_string()),
+                large.clone(),
+            ),
+        ];
+        write_packs(
+            sqpack_dir.clone(),
+            PlatformId::Ps3,
+            [(pack_id, files.into_iter())].into_iter(),
+            BTreeMap::new(),
+        )
+        .unwrap();
+
+        // Integers after the platform ID are big-endian, in both headers.
+        let index = std::fs::read(sqpack_dir.join("ffxiv").join("0a0000.ps3.index")).unwrap();
+        assert_eq!(index[8], PlatformId::Ps3 as u8);
+        assert_eq!(index[0x0c..0x10], [0, 0, 4, 0]);
+        assert_eq!(index[0x400..0x404], [0, 0, 4, 0]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [pack_id]);
+        assert_eq!(game_data.platform_id(pack_id), PlatformId::Ps3);
+        let mut data_file_set = game_data.data_files();
         assert_eq!(
-            Expansion::parse_name(Expansion::Ex3.name()).unwrap(),
-            Expansion::Ex3
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/root.exl")
+                .unwrap()
+                .unwrap(),
+            b"EXLT,2\r\n"
         );
         assert_eq!(
-            Expansion::parse_name(Expansion::Ex4.name()).unwrap(),
-            Expansion::Ex4
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/large.exd")
+                .unwrap()
+                .unwrap(),
+            large
         );
+    }
+
+    #[test]
+    fn access_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/root.exl", b"EXLT,2\r\n")]);
+        assert!(GameData::new(dir.path()).unwrap().access_log().is_none());
+
+        let game_data = GameData::builder()
+            .access_log(true)
+            .open(dir.path())
+            .unwrap();
+        assert!(game_data.exists("EXD/Root.exl").unwrap());
+        assert!(!game_data.exists("exd/missing.exh").unwrap());
+        let hash = IndexHash2::hash("exd/root.exl");
+        assert_eq!(game_data.lookup_hash_2_locator(&hash).unwrap().len(), 1);
+
+        let log = game_data.access_log().unwrap();
+        let records = log.records();
         assert_eq!(
-            Expansion::parse_name(Expansion::Ex5.name()).unwrap(),
-            Expansion::Ex5
+            records
+                .iter()
+                .map(|record| (record.target.clone(), record.outcome.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    AccessTarget::Path("exd/root.exl".to_string()),
+                    AccessOutcome::Found(vec![pack_id]),
+                ),
+                (
+                    AccessTarget::Path("exd/missing.exh".to_string()),
+                    AccessOutcome::NotFound,
+                ),
+                (
+                    AccessTarget::Hash2(hash),
+                    AccessOutcome::Found(vec![pack_id])
+                ),
+            ]
+        );
+        assert!(records[0].elapsed <= records[2].elapsed);
+        assert_eq!(log.found_paths(), ["exd/root.exl"]);
+
+        let mut json = Vec::new();
+        log.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"started\":"));
+        assert!(json.contains("\"path\":\"exd/missing.exh\",\"outcome\":\"not_found\"}"));
+        assert!(json.contains(&format!(
+            "\"path_crc\":\"{:08x}\",\"outcome\":\"found\",\"packs\":[\"0a0000\"]}}",
+            hash.path_crc
+        )));
+    }
+
+    #[test]
+    fn detected_expansions() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_pack = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), base_pack, &[("exd/root.exl", b"EXLT,2\r\n")]);
+        // A base game install, without any expansion directories.
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert_eq!(
+            game_data.detected_expansions().collect::<Vec<_>>(),
+            [Expansion::Base]
+        );
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [base_pack]);
+
+        let ex1_pack = SqPackId {
+            category: Category::Music,
+            expansion: Expansion::Ex1,
+            number: 0,
+        };
+        write_test_pack(dir.path(), ex1_pack, &[("music/ex1/empty.scd", b"")]);
+        std::fs::create_dir(dir.path().join("game").join("sqpack").join("ex2")).unwrap();
+        let game_data = GameData::builder()
+            .only_expansions(&[Expansion::Base])
+            .open(dir.path())
+            .unwrap();
+        assert_eq!(
+            game_data.detected_expansions().collect::<Vec<_>>(),
+            [Expansion::Base, Expansion::Ex1, Expansion::Ex2]
+        );
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [base_pack]);
+    }
+
+    #[test]
+    fn versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let ex1_pack = SqPackId {
+            category: Category::Music,
+            expansion: Expansion::Ex1,
+            number: 0,
+        };
+        write_test_pack(dir.path(), ex1_pack, &[("music/ex1/empty.scd", b"")]);
+        let sqpack_dir = dir.path().join("game").join("sqpack");
+        std::fs::create_dir(sqpack_dir.join("ex2")).unwrap();
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert!(game_data.game_version().unwrap().is_none());
+        assert!(game_data.expansion_versions().unwrap().is_empty());
+
+        std::fs::write(
+            dir.path().join("game").join("ffxivgame.ver"),
+            "2023.09.28.0000.0000",
+        )
+        .unwrap();
+        std::fs::write(
+            sqpack_dir.join("ex1").join("ex1.ver"),
+            "2023.09.15.0000.0001\r\n",
+        )
+        .unwrap();
+        let game_version = game_data.game_version().unwrap().unwrap();
+        assert_eq!(
+            game_version,
+            GameVersion {
+                year: 2023,
+                month: 9,
+                day: 28,
+                part: 0,
+                revision: 0,
+            }
+        );
+        assert_eq!(game_version.to_string(), "2023.09.28.0000.0000");
+        let expansion_versions = game_data.expansion_versions().unwrap();
+        assert_eq!(
+            expansion_versions.keys().copied().collect::<Vec<_>>(),
+            [Expansion::Ex1]
+        );
+        assert!(expansion_versions[&Expansion::Ex1] < game_version);
+
+        // Expansion versions stop at the first gap.
+        std::fs::create_dir(sqpack_dir.join("ex3")).unwrap();
+        std::fs::write(
+            sqpack_dir.join("ex3").join("ex3.ver"),
+            "2023.09.15.0000.0000",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("boot")).unwrap();
+        std::fs::write(
+            dir.path().join("boot").join("ffxivboot.ver"),
+            "2023.01.01.0000.0001",
+        )
+        .unwrap();
+        let versions = game_data.install_versions().unwrap();
+        assert_eq!(versions.boot.as_deref(), Some("2023.01.01.0000.0001"));
+        assert_eq!(versions.game.as_deref(), Some("2023.09.28.0000.0000"));
+        assert_eq!(
+            versions.expansions.into_iter().collect::<Vec<_>>(),
+            [(1, "2023.09.15.0000.0001".to_string())]
+        );
+
+        for invalid in [
+            "",
+            "2023.09.28",
+            "2023.09.28.0000.000a",
+            "2023.300.28.0000.0000",
+        ] {
+            assert!(matches!(
+                invalid.parse::<GameVersion>(),
+                Err(Error::InvalidVersion(_))
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skipped_non_utf8_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/root.exl", b"EXLT,2\r\n")]);
+        let sqpack_dir = dir.path().join("game").join("sqpack");
+        let bad_file = sqpack_dir
+            .join("ffxiv")
+            .join(OsStr::from_bytes(b"0a0000\xff.win32.index"));
+        std::fs::write(&bad_file, b"").unwrap();
+        let bad_dir = sqpack_dir.join(OsStr::from_bytes(b"ex\xff"));
+        std::fs::create_dir(&bad_dir).unwrap();
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [pack_id]);
+        let mut skipped = game_data.skipped_paths().to_vec();
+        skipped.sort();
+        let mut expected = vec![bad_file, bad_dir];
+        expected.sort();
+        assert_eq!(skipped, expected);
+    }
+
+    #[test]
+    fn iteration_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_ids = [
+            (Category::Ui, Expansion::Base, 0),
+            (Category::Exd, Expansion::Base, 0),
+            (Category::Music, Expansion::Ex1, 0),
+            (Category::Music, Expansion::Base, 1),
+            (Category::Music, Expansion::Base, 0),
+            (Category::Music, Expansion::Unknown(6), 0),
+        ]
+        .map(|(category, expansion, number)| SqPackId {
+            category,
+            expansion,
+            number,
+        });
+        for pack_id in pack_ids {
+            write_test_pack(dir.path(), pack_id, &[("music/empty.scd", b"")]);
+        }
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut sorted_pack_ids = pack_ids.to_vec();
+        sorted_pack_ids.sort();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), sorted_pack_ids);
+        assert_eq!(
+            game_data
+                .iter_packs_category_expansion(Category::Music, Expansion::Base)
+                .map(|pack_id| pack_id.number)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+
+        let pack_id = pack_ids[1];
+        let files: [(&str, &[u8]); 4] = [
+            ("exd/a.exh", &[1; 300]),
+            ("exd/b.exh", &[2; 100]),
+            ("exd/c.exh", &[3; 20000]),
+            ("exd/d.exh", &[4; 5]),
+        ];
+        write_test_pack(dir.path(), pack_id, &files);
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        let mut data_file_set = game_data.data_files();
+
+        let by_offset = data_file_set
+            .iter_files_ordered(pack_id, index, FileOrder::Offset)
+            .unwrap()
+            .map(|res| res.unwrap().1[0])
+            .collect::<Vec<_>>();
+        assert_eq!(by_offset, [1, 2, 3, 4]);
+        assert_eq!(
+            data_file_set
+                .iter_files(pack_id, index)
+                .map(|res| res.unwrap().1[0])
+                .collect::<Vec<_>>(),
+            by_offset
+        );
+
+        let by_size = data_file_set
+            .iter_files_ordered(pack_id, index, FileOrder::Size)
+            .unwrap()
+            .map(|res| res.unwrap().1.len())
+            .collect::<Vec<_>>();
+        assert_eq!(by_size, [5, 100, 300, 20000]);
+
+        let by_hash = data_file_set
+            .iter_files_ordered(pack_id, index, FileOrder::Hash)
+            .unwrap()
+            .map(|res| res.unwrap().0)
+            .collect::<Vec<_>>();
+        let mut expected_hashes = files
+            .iter()
+            .map(|(path, _)| IndexHash2::hash(path))
+            .collect::<Vec<_>>();
+        expected_hashes.sort();
+        assert_eq!(by_hash, expected_hashes);
+    }
+
+    #[test]
+    fn index_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[
+                ("exd/a.exh", b"a"),
+                ("exd/b.exh", b"b"),
+                ("exd/sub/c.exh", b"c"),
+            ],
+        );
+        let game_data = GameData::new(dir.path()).unwrap();
+
+        let index = game_data.get_index_1(&pack_id).unwrap().unwrap();
+        assert_eq!(index.dat_file_count(), 1);
+        assert!(index.collision_table().is_empty());
+        let folder_table = index.folder_table();
+        assert_eq!(folder_table.len(), 2);
+        let mut folder_crcs = [
+            IndexHash1::hash("exd/a.exh").folder_crc,
+            IndexHash1::hash("exd/sub/c.exh").folder_crc,
+        ];
+        folder_crcs.sort();
+        assert_eq!(
+            folder_table
+                .iter()
+                .map(|f| f.folder_crc)
+                .collect::<Vec<_>>(),
+            folder_crcs
+        );
+        assert_eq!(
+            folder_table.iter().map(|f| f.files_span).sum::<u32>(),
+            3 * 16
+        );
+        let sub_folder = index
+            .folder(IndexHash1::hash("exd/sub/c.exh").folder_crc)
+            .unwrap();
+        assert_eq!(sub_folder.files_span, 16);
+        assert!(index
+            .folder(IndexHash1::hash("bgcommon/a.tex").folder_crc)
+            .is_none());
+
+        let index2 = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        assert_eq!(index2.dat_file_count(), 1);
+        assert!(index2.folder_table().is_empty());
+    }
+
+    #[test]
+    fn index_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let paths = ["exd/a.exh", "exd/b.exh", "exd/c.exh", "exd/sub/d.exh"];
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &paths.map(|path| (path, path.as_bytes())),
+        );
+
+        for memory_map in [false, true] {
+            let game_data = GameData::builder()
+                .memory_map_indexes(memory_map)
+                .open(dir.path())
+                .unwrap();
+            let index = game_data.get_index_1(&pack_id).unwrap().unwrap();
+            let mut hashes = paths[..3]
+                .iter()
+                .map(|path| IndexHash1::hash(path))
+                .collect::<Vec<_>>();
+            hashes.sort();
+            let folder_crc = hashes[0].folder_crc;
+            assert_eq!(
+                index
+                    .folder_entries(folder_crc)
+                    .map(|entry| entry.hash())
+                    .collect::<Vec<_>>(),
+                hashes
+            );
+            assert_eq!(
+                index
+                    .folder_entries(IndexHash1::hash("exd/sub/d.exh").folder_crc)
+                    .len(),
+                1
+            );
+            assert_eq!(index.folder_entries(folder_crc ^ 1).len(), 0);
+
+            assert_eq!(index.range(..).len(), paths.len());
+            assert_eq!(
+                index
+                    .range(hashes[1]..)
+                    .take(2)
+                    .map(|entry| entry.hash())
+                    .collect::<Vec<_>>(),
+                hashes[1..]
+            );
+            assert_eq!(
+                index
+                    .range(hashes[0]..hashes[2])
+                    .map(|entry| entry.hash())
+                    .collect::<Vec<_>>(),
+                hashes[..2]
+            );
+            assert_eq!(index.range(hashes[2]..hashes[0]).len(), 0);
+        }
+    }
+
+    #[test]
+    fn colliding_hashes() {
+        use crate::{CollisionEntry, FilePointer, HashCollision, Index, IndexEntry, IndexPointer};
+
+        let hash = IndexHash2::hash("exd/a.exh");
+        let other_hash = IndexHash2::hash("exd/b.exh");
+        let collision = |path: &str, offset| CollisionEntry {
+            hash,
+            pointer: FilePointer::new(0, offset),
+            _maybe_collision_index: 0,
+            path: path.to_string(),
+        };
+        let mut entries = vec![
+            IndexEntry2 {
+                hash,
+                pointer: IndexPointer::Collision,
+            },
+            IndexEntry2 {
+                hash: other_hash,
+                pointer: IndexPointer::Pointer(FilePointer::new(0, 0x300)),
+            },
+        ];
+        entries.sort_by_key(|entry| entry.hash);
+        let index = Index::new(
+            entries,
+            vec![
+                collision("exd/a.exh", 0x100),
+                collision("exd/a2.exh", 0x200),
+            ],
+            Vec::new(),
+            Vec::new(),
+            1,
+        );
+        assert_eq!(
+            index.get_pointers(&hash),
+            [FilePointer::new(0, 0x100), FilePointer::new(0, 0x200)]
+        );
+        assert_eq!(
+            index.get_pointers(&other_hash),
+            [FilePointer::new(0, 0x300)]
+        );
+        assert!(index
+            .get_pointers(&IndexHash2::hash("exd/c.exh"))
+            .is_empty());
+        assert_eq!(index.lookup("exd/a.exh"), Some(FilePointer::new(0, 0x100)));
+        assert_eq!(
+            index
+                .collisions(&hash)
+                .iter()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            ["exd/a.exh", "exd/a2.exh"]
+        );
+        assert!(index.collisions(&other_hash).is_empty());
+        assert_eq!(index.get_all(&hash).len(), 1);
+        assert_eq!(
+            index.hash_collisions(),
+            [HashCollision {
+                hash,
+                table_entries: 1,
+                paths: vec!["exd/a.exh", "exd/a2.exh"],
+            }]
+        );
+
+        // A duplicate in the file table, which `get` would only return one of.
+        let duplicates = Index::new(
+            vec![
+                IndexEntry2 {
+                    hash: other_hash,
+                    pointer: IndexPointer::Pointer(FilePointer::new(0, 0x300)),
+                },
+                IndexEntry2 {
+                    hash: other_hash,
+                    pointer: IndexPointer::Pointer(FilePointer::new(0, 0x400)),
+                },
+            ],
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            1,
+        );
+        assert_eq!(
+            duplicates
+                .get_all(&other_hash)
+                .iter()
+                .map(|entry| entry.pointer().to_u32())
+                .collect::<Vec<_>>(),
+            [
+                IndexPointer::Pointer(FilePointer::new(0, 0x300)).to_u32(),
+                IndexPointer::Pointer(FilePointer::new(0, 0x400)).to_u32()
+            ]
+        );
+        assert_eq!(
+            duplicates.hash_collisions(),
+            [HashCollision {
+                hash: other_hash,
+                table_entries: 2,
+                paths: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn game_data_builder() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_ids = [
+            (Category::Exd, Expansion::Base),
+            (Category::Music, Expansion::Base),
+            (Category::Music, Expansion::Ex1),
+        ]
+        .map(|(category, expansion)| SqPackId {
+            category,
+            expansion,
+            number: 0,
+        });
+        for pack_id in pack_ids {
+            write_test_pack(dir.path(), pack_id, &[("a/b.c", b"")]);
+        }
+
+        let game_data = GameData::builder()
+            .only(&[Category::Exd])
+            .open(dir.path())
+            .unwrap();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [pack_ids[0]]);
+        assert!(game_data.get_index_2(&pack_ids[1]).is_none());
+
+        let game_data = GameData::builder()
+            .only(&[Category::Music])
+            .only_expansions(&[Expansion::Ex1])
+            .open(dir.path())
+            .unwrap();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [pack_ids[2]]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), pack_ids);
+    }
+
+    #[test]
+    fn indexes_loaded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/a.exh", b"a")]);
+        let game_data = GameData::new(dir.path()).unwrap();
+        let first = game_data.get_index_1(&pack_id).unwrap().unwrap();
+
+        // Once loaded, an index is cached, and its file isn't read again. Indexes that haven't
+        // been used yet are only read on first use.
+        let sqpack = dir.path().join("game").join("sqpack").join("ffxiv");
+        std::fs::remove_file(sqpack.join("0a0000.win32.index")).unwrap();
+        std::fs::remove_file(sqpack.join("0a0000.win32.index2")).unwrap();
+        let second = game_data.get_index_1(&pack_id).unwrap().unwrap();
+        assert!(std::ptr::eq(first, second));
+        assert!(game_data.get_index_2(&pack_id).unwrap().is_err());
+    }
+
+    #[test]
+    fn unknown_category() {
+        assert_eq!(Category::from_u8(0xA), Category::Exd);
+        assert_eq!(Category::from_u8(0xD), Category::Unknown(0xD));
+        assert_eq!(Category::Unknown(0xA), Category::Exd);
+        assert!(Category::Music < Category::Unknown(0xD));
+        assert!(Category::Unknown(0xD) < Category::SqpackTest);
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Unknown(0xD),
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("new/file.bin", b"contents")]);
+        assert!(dir
+            .path()
+            .join("game/sqpack/ffxiv/0d0000.win32.index2")
+            .is_file());
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), vec![pack_id]);
+        let mut data_file_set = game_data.data_files();
+        assert_eq!(
+            game_data
+                .lookup_hash_2_data(&mut data_file_set, &IndexHash2::hash("new/file.bin"))
+                .unwrap(),
+            vec![b"contents".to_vec()]
+        );
+    }
+
+    #[test]
+    fn index_consistency() {
+        use crate::{compare_indexes, FilePointer, Index, IndexDiscrepancy, IndexPointer};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[("exd/a.exh", b"a"), ("exd/b.exh", b"b")],
+        );
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert_eq!(
+            game_data
+                .check_index_consistency(&pack_id)
+                .unwrap()
+                .unwrap(),
+            Vec::new()
+        );
+
+        let index_1 = game_data.get_index_1(&pack_id).unwrap().unwrap();
+        let index_2 = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        let pointer_a = index_2.lookup("exd/a.exh").unwrap();
+        let pointer_b = index_2.lookup("exd/b.exh").unwrap();
+        let moved = FilePointer::new(0, pointer_b.offset() + 0x80);
+        let tampered = Index::new(
+            vec![
+                IndexEntry2 {
+                    hash: IndexHash2::hash("exd/a.exh"),
+                    pointer: IndexPointer::Pointer(pointer_a),
+                },
+                IndexEntry2 {
+                    hash: IndexHash2::hash("exd/b.exh"),
+                    pointer: IndexPointer::Pointer(moved),
+                },
+            ],
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            2,
+        );
+        assert_eq!(
+            compare_indexes(index_1, &tampered),
+            vec![
+                IndexDiscrepancy::DatFileCount {
+                    index_1: 1,
+                    index_2: 2
+                },
+                IndexDiscrepancy::MissingFromIndex2 {
+                    hash: IndexHash1::hash("exd/b.exh"),
+                    pointer: pointer_b,
+                },
+                IndexDiscrepancy::MissingFromIndex1 {
+                    hash: IndexHash2::hash("exd/b.exh"),
+                    pointer: moved,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn salvage_truncated_index() {
+        use crate::parser::index_segment_headers;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[
+                ("exd/a.exh", b"a"),
+                ("exd/b.exh", b"b"),
+                ("exd/c.exh", b"c"),
+            ],
+        );
+        let game_data = GameData::new(dir.path()).unwrap();
+
+        let salvaged = game_data.salvage_index_1(&pack_id).unwrap().unwrap();
+        assert_eq!(salvaged.truncated_at, None);
+        assert_eq!(salvaged.index.iter().count(), 3);
+        assert_eq!(salvaged.index.folder_table().len(), 1);
+
+        // Cut the file partway through the second file table entry.
+        let path = game_data.build_index_path::<IndexEntry1>(pack_id);
+        let data = std::fs::read(&path).unwrap();
+        let (_, (_, _, segment_headers)) =
+            index_segment_headers(nom::number::Endianness::Little)(&data[1024..]).unwrap();
+        let file_table_offset = u64::from(segment_headers[0].offset);
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_len(file_table_offset + 16 + 8).unwrap();
+        drop(file);
+
+        let salvaged = game_data.salvage_index_1(&pack_id).unwrap().unwrap();
+        assert_eq!(salvaged.truncated_at, Some(file_table_offset + 16));
+        assert_eq!(salvaged.index.iter().count(), 1);
+        assert!(salvaged.index.folder_table().is_empty());
+        assert_eq!(salvaged.index.dat_file_count(), 1);
+        assert!(game_data.get_index_1(&pack_id).unwrap().is_err());
+
+        // Nothing can be recovered if the segment headers are missing.
+        let index2_path = game_data.build_index_path::<IndexEntry2>(pack_id);
+        let data2 = std::fs::read(&index2_path).unwrap();
+        std::fs::write(&index2_path, &data2[..1024]).unwrap();
+        assert!(game_data.salvage_index_2(&pack_id).unwrap().is_err());
+    }
+
+    #[test]
+    fn data_block_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let contents = vec![0x5a; 40000];
+        write_test_pack(dir.path(), pack_id, &[("exd/big.exd", &contents)]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        let (_, pointer) = index.iter().next().unwrap();
+        let mut data_file_set = game_data.data_files();
+        let blocks = data_file_set.entry_blocks(pack_id, pointer).unwrap();
+        let infos = blocks.blocks().collect::<Vec<_>>();
+        assert_eq!(infos.len(), 3);
+        assert_eq!(
+            infos.iter().map(|info| info.offset).collect::<Vec<_>>(),
+            blocks.all_blocks().collect::<Vec<_>>()
+        );
+        assert!(infos
+            .windows(2)
+            .all(|pair| pair[0].offset + pair[0].compressed_size == pair[1].offset));
+        assert_eq!(blocks.total_decompressed_size(), Some(40000));
+        assert_eq!(
+            blocks.total_compressed_size(),
+            infos.iter().map(|info| info.compressed_size).sum::<u32>()
+        );
+    }
+
+    #[test]
+    fn missing_data_file() {
+        use crate::{check_dat_number, DataFileSet, Error, FilePointer, Index};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/a.exh", b"abc")]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        let header = data_file_set.data_header(pack_id, 0).unwrap();
+        assert_eq!(header.spanned_dat, 1);
+        assert!(header.data_size > 0);
+
+        let (_, pointer) = game_data.lookup_path_locator("exd/a.exh").unwrap().unwrap();
+        std::fs::remove_file(DataFileSet::build_data_path(
+            dir.path(),
+            pack_id,
+            PlatformId::Win32,
+            0,
+        ))
+        .unwrap();
+        let mut data_file_set = game_data.data_files();
+        assert!(matches!(
+            data_file_set.fetch_data(pack_id, pointer),
+            Err(Error::MissingDataFile { dat_number: 0, .. })
+        ));
+
+        let index = Index::<IndexEntry2>::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), 1);
+        assert!(check_dat_number(pack_id, &index, FilePointer::new(0, 0x80)).is_ok());
+        assert!(matches!(
+            check_dat_number(pack_id, &index, FilePointer::new(1, 0x80)),
+            Err(Error::MissingDataFile { dat_number: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn spanned_data_files() {
+        use crate::{
+            encoding::{PackSetWriter, RealPackIO},
+            sidetables::SideTables,
+            DataFileSet, Error, PlatformId,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let sqpack_dir = dir.path().join("game").join("sqpack");
+        std::fs::create_dir_all(sqpack_dir.join("ffxiv")).unwrap();
+        let io = RealPackIO::new(sqpack_dir, PlatformId::Win32, pack_id).unwrap();
+        let mut writer = PackSetWriter::new(io, PlatformId::Win32, pack_id).unwrap();
+        // Reserve all of .dat0, so the file is written to .dat1.
+        writer.set_side_table(SideTables {
+            reserved_file_space: vec![2000000000],
+            ..Default::default()
+        });
+        writer.add_file("exd/a.exh", b"abc").unwrap();
+        writer.finalize().unwrap();
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let (_, pointer) = game_data.lookup_path_locator("exd/a.exh").unwrap().unwrap();
+        assert_eq!(pointer.data_file_id, 1);
+        let mut data_file_set = game_data.data_files();
+        assert_eq!(
+            data_file_set.data_header(pack_id, 0).unwrap().spanned_dat,
+            1
+        );
+        assert_eq!(
+            data_file_set.data_header(pack_id, 1).unwrap().spanned_dat,
+            2
+        );
+        assert_eq!(
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/a.exh")
+                .unwrap()
+                .as_deref(),
+            Some(&b"abc"[..])
         );
 
-        assert_eq!(Expansion::from_u8(0).unwrap() as u8, 0);
-        assert_eq!(Expansion::from_u8(1).unwrap() as u8, 1);
-        assert_eq!(Expansion::from_u8(2).unwrap() as u8, 2);
-        assert_eq!(Expansion::from_u8(3).unwrap() as u8, 3);
+        // A .dat0 copied over .dat1.
+        std::fs::copy(
+            DataFileSet::build_data_path(dir.path(), pack_id, PlatformId::Win32, 0),
+            DataFileSet::build_data_path(dir.path(), pack_id, PlatformId::Win32, 1),
+        )
+        .unwrap();
+        let mut data_file_set = game_data.data_files();
+        assert!(matches!(
+            data_file_set.data_header(pack_id, 1),
+            Err(Error::DataFileMismatch {
+                dat_number: 1,
+                spanned_dat: 1,
+                ..
+            })
+        ));
+    }
 
+    #[test]
+    fn memory_provider() {
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let mut provider = MemoryProvider::new();
+        provider
+            .write_pack(
+                pack_id,
+                [
+                    ("exd/root.exl", &b"EXLT,2\r\n"[..]),
+                    ("exd/item.exh", &[7; 1000][..]),
+                ],
+            )
+            .unwrap();
+        let game_data = GameData::from_provider(provider);
+        assert_eq!(game_data.iter_packs().collect::<Vec<_>>(), [pack_id]);
+        assert!(game_data.game_version().unwrap().is_none());
+
+        let mut data_file_set = game_data.data_files();
+        assert_eq!(
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/root.exl")
+                .unwrap()
+                .unwrap(),
+            b"EXLT,2\r\n"
+        );
+        let mut data = Vec::new();
+        game_data
+            .open_file(&mut data_file_set, "exd/item.exh")
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, [7; 1000]);
+        assert!(game_data
+            .lookup_path_data(&mut data_file_set, "exd/missing.exh")
+            .unwrap()
+            .is_none());
+        let index = game_data.get_index_1(&pack_id).unwrap().unwrap();
+        assert_eq!(data_file_set.iter_files(pack_id, index).count(), 2);
         assert_eq!(
-            Expansion::from_u8(Expansion::Base as u8).unwrap(),
+            game_data
+                .salvage_index_2(&pack_id)
+                .unwrap()
+                .unwrap()
+                .index
+                .iter()
+                .count(),
+            2
+        );
+        assert!(matches!(
+            data_file_set.open(pack_id, 1),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn file_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let contents = vec![0x5a; 40000];
+        write_test_pack(dir.path(), pack_id, &[("exd/big.exd", &contents)]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        let metadata = game_data
+            .file_metadata(&mut data_file_set, "exd/big.exd")
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.entry_type, EntryType::Binary);
+        assert_eq!(metadata.uncompressed_size, 40000);
+        assert_eq!(metadata.block_count, 3);
+        assert_eq!(metadata.dat_number, 0);
+        assert!(metadata.compressed_size > 0 && metadata.compressed_size < 40000);
+        assert!(game_data
+            .file_metadata(&mut data_file_set, "exd/missing.exd")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn open_file() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Music,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        // Long enough to be split into several blocks.
+        let contents = (0..100_000u32)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        write_test_pack(dir.path(), pack_id, &[("music/long.scd", &contents)]);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        let mut reader = game_data
+            .open_file(&mut data_file_set, "music/long.scd")
+            .unwrap()
+            .unwrap();
+        let mut read = Vec::new();
+        let mut buf = [0; 1000];
+        loop {
+            let len = reader.read(&mut buf).unwrap();
+            if len == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..len]);
+        }
+        assert!(read == contents);
+        assert!(game_data
+            .open_file(&mut data_file_set, "music/missing.scd")
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_decompression() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Cut,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let contents = (0..200_000u32)
+            .map(|i| (i % 241) as u8)
+            .collect::<Vec<u8>>();
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[("cut/long.scd", &contents), ("cut/short.scd", b"short")],
+        );
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        data_file_set.set_parallel_decompression(true);
+        for (path, expected) in [
+            ("cut/long.scd", &contents[..]),
+            ("cut/short.scd", &b"short"[..]),
+        ] {
+            let data = game_data
+                .lookup_path_data(&mut data_file_set, path)
+                .unwrap()
+                .unwrap();
+            assert!(data == expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn fetch_data_reserved() {
+        use std::{sync::mpsc, thread, time::Duration};
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Music,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[("music/a.scd", &[1; 1000]), ("music/b.scd", &[2; 1000])],
+        );
+        let game_data = GameData::builder()
+            .memory_budget(1500)
+            .open(dir.path())
+            .unwrap();
+        assert_eq!(game_data.memory_budget().unwrap().limit(), 1500);
+        let locate = |path| game_data.lookup_path_locator(path).unwrap().unwrap();
+
+        let mut data_file_set = game_data.data_files();
+        let (id, pointer) = locate("music/a.scd");
+        let (data, reservation) = data_file_set.fetch_data_reserved(id, pointer).unwrap();
+        assert_eq!(data, [1; 1000]);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let mut data_file_set = game_data.data_files();
+                let (id, pointer) = locate("music/b.scd");
+                let (data, _reservation) = data_file_set.fetch_data_reserved(id, pointer).unwrap();
+                sender.send(data).unwrap();
+            });
+            // The second file doesn't fit in the budget until the first is released.
+            assert_eq!(
+                receiver.recv_timeout(Duration::from_millis(100)),
+                Err(mpsc::RecvTimeoutError::Timeout)
+            );
+            drop(reservation);
+            assert_eq!(receiver.recv().unwrap(), [2; 1000]);
+        });
+    }
+
+    #[test]
+    fn memory_mapped_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let files: [(&str, &[u8]); 3] = [
+            ("exd/a.exh", &[1; 300]),
+            ("exd/b.exh", &[2; 20000]),
+            ("exd/sub/c.exh", b"c"),
+        ];
+        write_test_pack(dir.path(), pack_id, &files);
+
+        let loaded = GameData::new(dir.path()).unwrap();
+        let mapped = GameData::builder()
+            .memory_map_indexes(true)
+            .open(dir.path())
+            .unwrap();
+        let index_1 = mapped.get_index_1(&pack_id).unwrap().unwrap();
+        assert_eq!(
+            index_1.iter().collect::<Vec<_>>(),
+            loaded
+                .get_index_1(&pack_id)
+                .unwrap()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            mapped
+                .get_index_2(&pack_id)
+                .unwrap()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            loaded
+                .get_index_2(&pack_id)
+                .unwrap()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        let hash = IndexHash1::hash("exd/b.exh");
+        assert_eq!(
+            index_1.get(&hash).map(|entry| entry.pointer().to_u32()),
+            index_1
+                .get_entry(&hash)
+                .map(|entry| entry.pointer().to_u32())
+        );
+        assert!(index_1.get(&IndexHash1::hash("exd/missing.exh")).is_none());
+        assert!(format!("{:?}", index_1).starts_with("Index {"));
+        assert!(mapped.contains_folder("exd/sub").unwrap());
+        assert!(!mapped.contains_folder("exd/missing").unwrap());
+
+        let mut data_file_set = mapped.data_files();
+        for (path, contents) in files {
+            let data = mapped.lookup_path_data(&mut data_file_set, path).unwrap();
+            assert_eq!(data.as_deref(), Some(contents), "{}", path);
+        }
+        assert_eq!(
+            mapped
+                .lookup_path_data(&mut data_file_set, "exd/missing.exh")
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn iter_and_extract_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        let files: [(&str, &[u8]); 3] = [
+            ("exd/a.exh", &[1; 300]),
+            ("exd/b.exh", &[2; 20000]),
+            ("exd/sub/c.exh", b"c"),
+        ];
+        write_test_pack(dir.path(), pack_id, &files);
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let entries = game_data
+            .iter_files(&pack_id)
+            .unwrap()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.windows(2).all(|pair| pair[0].2 < pair[1].2));
+        for (path, _) in files {
+            assert!(entries.iter().any(|(hash_1, hash_2, _)| {
+                *hash_1 == Some(IndexHash1::hash(path)) && *hash_2 == Some(IndexHash2::hash(path))
+            }));
+        }
+
+        let output_dir = dir.path().join("out");
+        let mut data_file_set = game_data.data_files();
+        let count = game_data
+            .extract_pack(&mut data_file_set, &pack_id, &output_dir)
+            .unwrap()
+            .unwrap();
+        assert_eq!(count, 3);
+        for (path, contents) in files {
+            let hash = IndexHash1::hash(path);
+            let file = output_dir
+                .join(format!("{:08x}", hash.folder_crc))
+                .join(format!("{:08x}", hash.filename_crc));
+            assert_eq!(std::fs::read(file).unwrap(), contents, "{}", path);
+        }
+
+        let patterns = crate::patterns::PatternSet::parse("exd/*.exh\n!exd/b.exh\n");
+        let output_dir = dir.path().join("matching");
+        let count = game_data
+            .extract_pack_matching(
+                &mut data_file_set,
+                &pack_id,
+                &output_dir,
+                &patterns,
+                |hash_1, _| {
+                    // Only the .index hash is used, as for packs without an .index2 file.
+                    files
+                        .iter()
+                        .map(|(path, _)| *path)
+                        .find(|path| Some(IndexHash1::hash(path)) == hash_1)
+                        .map(str::to_string)
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(count, 1);
+        let hash = IndexHash1::hash("exd/a.exh");
+        assert!(output_dir
+            .join(format!("{:08x}", hash.folder_crc))
+            .join(format!("{:08x}", hash.filename_crc))
+            .is_file());
+
+        assert!(game_data
+            .iter_files(&SqPackId {
+                category: Category::Bg,
+                expansion: Expansion::Base,
+                number: 0,
+            })
+            .is_none());
+    }
+
+    #[test]
+    fn exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/a.exh", b"contents")]);
+        // Lookups must not need the data file.
+        let sqpack_dir = dir.path().join("game").join("sqpack").join("ffxiv");
+        std::fs::remove_file(sqpack_dir.join("0a0000.win32.dat0")).unwrap();
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert!(game_data.exists("exd/a.exh").unwrap());
+        assert!(!game_data.exists("exd/b.exh").unwrap());
+        assert!(!game_data.exists("nonsense").unwrap());
+        assert!(game_data
+            .exists_hash(&IndexHash2::hash("exd/a.exh"))
+            .unwrap());
+        assert!(!game_data
+            .exists_hash(&IndexHash2::hash("exd/b.exh"))
+            .unwrap());
+    }
+
+    #[test]
+    fn path_normalization() {
+        use crate::normalize_path;
+
+        assert_eq!(normalize_path("exd/root.exl"), "exd/root.exl");
+        assert_eq!(normalize_path("EXD/Root.EXL"), "exd/root.exl");
+        assert_eq!(
+            normalize_path("exd\\quest\\Quest.exh"),
+            "exd/quest/quest.exh"
+        );
+        assert_eq!(normalize_path("  /exd//root.exl\n"), "exd/root.exl");
+        assert_eq!(normalize_path("\\\\exd\\\\root.exl"), "exd/root.exl");
+        // Only ASCII case is folded, and other characters are kept as is.
+        assert_eq!(normalize_path("UI/Ärger/İcon.TEX"), "ui/Ärger/İcon.tex");
+        // Decomposed characters are composed, before and after other changes.
+        assert_eq!(normalize_path("UI/A\u{308}rger.TEX"), "ui/\u{c4}rger.tex");
+        assert_eq!(normalize_path("ui/\u{c4}rger.tex"), "ui/\u{c4}rger.tex");
+        assert!(matches!(
+            normalize_path("ui/\u{c4}rger.tex"),
+            std::borrow::Cow::Borrowed(_)
+        ));
+        assert_eq!(normalize_path("ui\\a\u{308}"), "ui/\u{e4}");
+        assert_eq!(
+            IndexHash2::hash("UI/A\u{308}rger.TEX"),
+            IndexHash2::hash("ui/\u{c4}rger.tex")
+        );
+        assert_eq!(normalize_path(""), "");
+
+        for spelling in ["EXD\\A.exh", " /exd//a.EXH", "exd/a.exh"] {
+            assert_eq!(IndexHash1::hash(spelling), IndexHash1::hash("exd/a.exh"));
+            assert_eq!(IndexHash2::hash(spelling), IndexHash2::hash("exd/a.exh"));
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/a.exh", b"contents")]);
+        let game_data = GameData::new(dir.path()).unwrap();
+        assert!(game_data.exists("EXD\\A.exh").unwrap());
+        assert!(game_data.exists("/exd/a.exh").unwrap());
+        assert!(game_data.contains_folder("EXD").unwrap());
+    }
+
+    #[test]
+    fn lookup_falls_back_to_index_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/a.exh", b"contents")]);
+
+        // Change the hash of the only entry in the .index2 file, so the path isn't found there.
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index2_path = game_data.build_index_path::<IndexEntry2>(pack_id);
+        let mut data = std::fs::read(&index2_path).unwrap();
+        let offset = u32::from_le_bytes(data[0x408..0x40c].try_into().unwrap()) as usize;
+        data[offset..offset + 4].copy_from_slice(&0xffffffffu32.to_le_bytes());
+        std::fs::write(&index2_path, data).unwrap();
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index_2 = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        assert_eq!(index_2.lookup("exd/a.exh"), None);
+        let mut data_file_set = game_data.data_files();
+        assert_eq!(
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/a.exh")
+                .unwrap()
+                .as_deref(),
+            Some(&b"contents"[..])
+        );
+    }
+
+    #[test]
+    fn replace_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[("exd/a.exh", &[1; 300]), ("exd/b.exh", &[2; 20000])],
+        );
+
+        let mut game_data = GameData::new(dir.path()).unwrap();
+        let contents = vec![3; 50000];
+        assert!(game_data.replace_file("exd/a.exh", &contents).unwrap());
+        assert!(!game_data.replace_file("exd/missing.exh", b"").unwrap());
+        let mut data_file_set = game_data.data_files();
+        assert_eq!(
+            game_data
+                .lookup_path_data(&mut data_file_set, "exd/a.exh")
+                .unwrap(),
+            Some(contents.clone())
+        );
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let mut data_file_set = game_data.data_files();
+        for (path, expected) in [("exd/a.exh", contents), ("exd/b.exh", vec![2; 20000])] {
+            let data = game_data
+                .lookup_path_data(&mut data_file_set, path)
+                .unwrap();
+            assert_eq!(data, Some(expected), "{}", path);
+        }
+
+        let pack_dir = dir.path().join("game").join("sqpack").join("ffxiv");
+        for extension in ["index", "index2", "dat0"] {
+            let data = std::fs::read(pack_dir.join(format!("0a0000.win32.{}", extension))).unwrap();
+            assert_eq!(
+                data[0x7c0..0x7d4],
+                crate::sha1(&data[0x400..0x7c0]),
+                "{}",
+                extension
+            );
+        }
+        for extension in ["index", "index2"] {
+            let data = std::fs::read(pack_dir.join(format!("0a0000.win32.{}", extension))).unwrap();
+            let field =
+                |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            let (offset, size) = (field(0x408) as usize, field(0x40c) as usize);
+            assert_eq!(
+                data[0x410..0x424],
+                crate::sha1(&data[offset..offset + size]),
+                "{}",
+                extension
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn replace_file_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(dir.path(), pack_id, &[("exd/a.exh", b"contents")]);
+
+        // Another process holding the data file open, standing in for the game.
+        let dat_path = dir.path().join("game/sqpack/ffxiv/0a0000.win32.dat0");
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .stdin(std::fs::File::open(dat_path).unwrap())
+            .spawn()
+            .unwrap();
+        let mut game_data = GameData::new(dir.path()).unwrap();
+        let result = game_data.replace_file("exd/a.exh", b"replaced");
+        let mut game_data = GameData::builder()
+            .allow_writes_while_in_use(true)
+            .open(dir.path())
+            .unwrap();
+        let forced = game_data.replace_file("exd/a.exh", b"replaced");
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        match result {
+            Err(Error::InUse(usage)) => {
+                assert!(usage.processes.is_empty());
+                assert_eq!(usage.open_files.len(), 1);
+                assert_eq!(usage.open_files[0].pid, Some(child.id()));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(forced.unwrap());
+    }
+
+    #[test]
+    fn entry_header_cache() {
+        use crate::{
+            DataBlocks, EntryHeaderCache, FilePointer, SqPackId, ENTRY_HEADER_CACHE_CAPACITY,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let pack_id = SqPackId {
+            category: Category::Exd,
+            expansion: Expansion::Base,
+            number: 0,
+        };
+        write_test_pack(
+            dir.path(),
+            pack_id,
+            &[("exd/foobar.txt", b"Hello, world\n")],
+        );
+
+        let game_data = GameData::new(dir.path()).unwrap();
+        let index = game_data.get_index_2(&pack_id).unwrap().unwrap();
+        let (_, pointer) = index.iter().next().unwrap();
+        let mut data_file_set = game_data.data_files();
+        assert!(matches!(
+            data_file_set.entry_blocks(pack_id, pointer).unwrap(),
+            DataBlocks::Binary { blocks, .. } if blocks.len() == 1
+        ));
+        assert!(data_file_set
+            .header_cache
+            .get(&(pack_id, pointer))
+            .is_some());
+        assert_eq!(
+            data_file_set.fetch_data(pack_id, pointer).unwrap(),
+            b"Hello, world\n"
+        );
+
+        let mut cache = EntryHeaderCache::default();
+        for i in 0..=ENTRY_HEADER_CACHE_CAPACITY {
+            let pointer = FilePointer::new(0, u32::try_from(i).unwrap() * 128);
+            cache.insert((pack_id, pointer), Arc::new(DataBlocks::Empty));
+        }
+        assert_eq!(cache.map.len(), ENTRY_HEADER_CACHE_CAPACITY);
+        assert!(cache.get(&(pack_id, FilePointer::new(0, 0))).is_none());
+        assert!(cache.get(&(pack_id, FilePointer::new(0, 128))).is_some());
+    }
+
+    #[test]
+    fn sqpack_date_time() {
+        use crate::SqPackDateTime;
+
+        let date_time = SqPackDateTime::from_packed(20230613, 15073542).unwrap();
+        assert_eq!(
+            date_time,
+            SqPackDateTime {
+                year: 2023,
+                month: 6,
+                day: 13,
+                hour: 15,
+                minute: 7,
+            }
+        );
+        assert_eq!(date_time.to_string(), "2023-06-13 15:07");
+        assert_eq!(SqPackDateTime::from_packed(0, 0), None);
+        assert_eq!(SqPackDateTime::from_packed(20231301, 0), None);
+        assert_eq!(SqPackDateTime::from_packed(20230101, 6000), None);
+    }
+
+    #[test]
+    fn expansion_round_trip() {
+        assert_eq!(Expansion::parse_name("ffxiv").unwrap().name(), "ffxiv");
+        assert_eq!(Expansion::parse_name("ex1").unwrap().name(), "ex1");
+        assert_eq!(Expansion::parse_name("ex2").unwrap().name(), "ex2");
+        assert_eq!(Expansion::parse_name("ex3").unwrap().name(), "ex3");
+
+        assert_eq!(
+            Expansion::parse_name(&Expansion::Base.name()).unwrap(),
             Expansion::Base
         );
         assert_eq!(
-            Expansion::from_u8(Expansion::Ex1 as u8).unwrap(),
+            Expansion::parse_name(&Expansion::Ex1.name()).unwrap(),
             Expansion::Ex1
         );
         assert_eq!(
-            Expansion::from_u8(Expansion::Ex2 as u8).unwrap(),
+            Expansion::parse_name(&Expansion::Ex2.name()).unwrap(),
             Expansion::Ex2
         );
         assert_eq!(
-            Expansion::from_u8(Expansion::Ex3 as u8).unwrap(),
+            Expansion::parse_name(&Expansion::Ex3.name()).unwrap(),
             Expansion::Ex3
         );
         assert_eq!(
-            Expansion::from_u8(Expansion::Ex4 as u8).unwrap(),
+            Expansion::parse_name(&Expansion::Ex4.name()).unwrap(),
             Expansion::Ex4
         );
         assert_eq!(
-            Expansion::from_u8(Expansion::Ex5 as u8).unwrap(),
+            Expansion::parse_name(&Expansion::Ex5.name()).unwrap(),
             Expansion::Ex5
         );
+
+        assert_eq!(Expansion::from_u8(0).to_u8(), 0);
+        assert_eq!(Expansion::from_u8(1).to_u8(), 1);
+        assert_eq!(Expansion::from_u8(2).to_u8(), 2);
+        assert_eq!(Expansion::from_u8(3).to_u8(), 3);
+
+        assert_eq!(Expansion::from_u8(Expansion::Base.to_u8()), Expansion::Base);
+        assert_eq!(Expansion::from_u8(Expansion::Ex1.to_u8()), Expansion::Ex1);
+        assert_eq!(Expansion::from_u8(Expansion::Ex2.to_u8()), Expansion::Ex2);
+        assert_eq!(Expansion::from_u8(Expansion::Ex3.to_u8()), Expansion::Ex3);
+        assert_eq!(Expansion::from_u8(Expansion::Ex4.to_u8()), Expansion::Ex4);
+        assert_eq!(Expansion::from_u8(Expansion::Ex5.to_u8()), Expansion::Ex5);
+
+        assert_eq!(Expansion::parse_name("ex6").unwrap(), Expansion::Unknown(6));
+        assert_eq!(Expansion::Unknown(6).name(), "ex6");
+        assert_eq!(Expansion::from_u8(6), Expansion::Unknown(6));
+        assert_eq!(Expansion::Unknown(5), Expansion::Ex5);
+        assert!(Expansion::parse_name("ex").is_err());
+        assert!(Expansion::parse_name("ex0").is_err());
+        assert!(Expansion::parse_name("ex01").is_err());
+        assert!(Expansion::parse_name("ex+1").is_err());
+        assert!(Expansion::parse_name("ex256").is_err());
     }
 
     #[derive(Clone)]
@@ -1194,12 +4682,11 @@ mod tests {
 
         fn open_dat_file(&mut self, number: u8) -> Result<Self::F, std::io::Error> {
             if number <= self.files.len().try_into().unwrap() {
-                self.files
-                    .resize_with((number + 1).try_into().unwrap(), || MockFile {
-                        inner: Arc::new(RwLock::new(Cursor::new(Vec::new()))),
-                    })
+                self.files.resize_with((number + 1).into(), || MockFile {
+                    inner: Arc::new(RwLock::new(Cursor::new(Vec::new()))),
+                })
             }
-            Ok(self.files[TryInto::<usize>::try_into(number).unwrap()].clone())
+            Ok(self.files[Into::<usize>::into(number)].clone())
         }
     }
 
@@ -1421,7 +4908,7 @@ mod tests {
                     &original_index2_file,
                 );
             }
-            let mut dat_matches = mocked_io.files.len() == original_dat_file_count.into();
+            let mut dat_matches = mocked_io.files.len() == usize::from(original_dat_file_count);
             if !dat_matches {
                 println!(
                     "Wrong number of .dat files, expected {}, got {}",
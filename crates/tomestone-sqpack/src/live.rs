@@ -0,0 +1,370 @@
+//! Checks whether a game installation is in use by another program, so that its packs aren't
+//! modified underneath the game, its launcher, or its updater.
+//!
+//! Running processes are found through `/proc` on Linux, which includes the game running under
+//! Wine, and through a process snapshot on Windows. Files held open are found through
+//! `/proc/<pid>/fd` on Linux, and on Windows by trying to open each pack file exclusively. Other
+//! platforms return an [`io::ErrorKind::Unsupported`] error rather than reporting that nothing
+//! is in use.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(any(target_os = "linux", target_os = "android", windows, test))]
+use std::fs;
+
+/// The boot executables whose hashes are reported by the launcher, in the order it reports
+/// them.
+pub const BOOT_EXECUTABLES: &[&str] = &[
+    "ffxivboot.exe",
+    "ffxivboot64.exe",
+    "ffxivlauncher.exe",
+    "ffxivlauncher64.exe",
+    "ffxivupdater.exe",
+    "ffxivupdater64.exe",
+];
+
+/// The game's own executables, which are not reported by the launcher.
+pub const GAME_EXECUTABLES: &[&str] = &["ffxiv.exe", "ffxiv_dx11.exe"];
+
+/// A running process of the game, or of one of its boot executables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameProcess {
+    pub pid: u32,
+    /// The name of the executable, in lowercase.
+    pub name: String,
+}
+
+/// A file inside an installation that another process has open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFile {
+    /// The process holding the file open, if it is known. Windows only reports that a file is
+    /// in use, not by which process.
+    pub pid: Option<u32>,
+    pub path: PathBuf,
+}
+
+/// Everything found to be using an installation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub processes: Vec<GameProcess>,
+    pub open_files: Vec<OpenFile>,
+}
+
+impl Usage {
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty() && self.open_files.is_empty()
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut items = Vec::new();
+        for process in self.processes.iter() {
+            items.push(format!("{} is running ({})", process.name, process.pid));
+        }
+        for file in self.open_files.iter() {
+            match file.pid {
+                Some(pid) => items.push(format!("{} is open ({})", file.path.display(), pid)),
+                None => items.push(format!("{} is open", file.path.display())),
+            }
+        }
+        f.write_str(&items.join(", "))
+    }
+}
+
+fn is_game_executable(name: &str) -> bool {
+    BOOT_EXECUTABLES.contains(&name) || GAME_EXECUTABLES.contains(&name)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} isn't supported on this platform", what),
+    )
+}
+
+/// Lists running processes of the game, its launcher, or its updater.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn running_game_processes() -> io::Result<Vec<GameProcess>> {
+    find_game_processes(Path::new("/proc"))
+}
+
+/// Lists running processes of the game, its launcher, or its updater.
+#[cfg(windows)]
+pub fn running_game_processes() -> io::Result<Vec<GameProcess>> {
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, INVALID_HANDLE_VALUE},
+        System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
+        },
+    };
+
+    // Safety: the snapshot handle is checked before use, and closed below.
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let mut entry = PROCESSENTRY32W {
+        dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+    let mut processes = Vec::new();
+    // Safety: `entry` is a valid, writable PROCESSENTRY32W with `dwSize` set.
+    let mut more = unsafe { Process32FirstW(snapshot, &mut entry) } != 0;
+    while more {
+        let length = entry
+            .szExeFile
+            .iter()
+            .position(|unit| *unit == 0)
+            .unwrap_or(entry.szExeFile.len());
+        let name = String::from_utf16_lossy(&entry.szExeFile[..length]).to_lowercase();
+        if is_game_executable(&name) {
+            processes.push(GameProcess {
+                pid: entry.th32ProcessID,
+                name,
+            });
+        }
+        // Safety: as above.
+        more = unsafe { Process32NextW(snapshot, &mut entry) } != 0;
+    }
+    // Safety: the handle is valid, and isn't used again.
+    unsafe { CloseHandle(snapshot) };
+    processes.sort_by_key(|process| process.pid);
+    Ok(processes)
+}
+
+/// Lists running processes of the game, its launcher, or its updater.
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+pub fn running_game_processes() -> io::Result<Vec<GameProcess>> {
+    Err(unsupported("listing processes"))
+}
+
+/// Lists the files inside an installation that other processes have open.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn open_install_files(root: &Path) -> io::Result<Vec<OpenFile>> {
+    find_open_files(
+        Path::new("/proc"),
+        &root.canonicalize()?,
+        std::process::id(),
+    )
+}
+
+/// Lists the pack files of an installation that other processes have open. Each file is opened
+/// without sharing, which fails while any other handle to it is open.
+#[cfg(windows)]
+pub fn open_install_files(root: &Path) -> io::Result<Vec<OpenFile>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    use windows_sys::Win32::Foundation::ERROR_SHARING_VIOLATION;
+
+    let sqpack_dir = root.join("game").join("sqpack");
+    let repositories = match fs::read_dir(&sqpack_dir) {
+        Ok(repositories) => repositories,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut open_files = Vec::new();
+    for repository in repositories {
+        let repository = repository?;
+        if !repository.file_type()?.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(repository.path())? {
+            let path = entry?.path();
+            match fs::OpenOptions::new().read(true).share_mode(0).open(&path) {
+                Ok(_) => {}
+                Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION as i32) => {
+                    open_files.push(OpenFile { pid: None, path });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    open_files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(open_files)
+}
+
+/// Lists the files inside an installation that other processes have open.
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+pub fn open_install_files(_root: &Path) -> io::Result<Vec<OpenFile>> {
+    Err(unsupported("checking for open files"))
+}
+
+/// Checks for running game processes, and for files inside the installation held open by other
+/// processes.
+pub fn install_usage(root: &Path) -> io::Result<Usage> {
+    Ok(Usage {
+        processes: running_game_processes()?,
+        open_files: open_install_files(root)?,
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", test))]
+fn find_game_processes(proc_dir: &Path) -> io::Result<Vec<GameProcess>> {
+    let mut processes = Vec::new();
+    for (pid, process_dir) in process_dirs(proc_dir)? {
+        // Processes may exit while they are being listed, so errors are skipped.
+        let command_line = match fs::read(process_dir.join("cmdline")) {
+            Ok(command_line) => command_line,
+            Err(_) => continue,
+        };
+        let program = command_line.split(|byte| *byte == 0).next().unwrap();
+        let program = String::from_utf8_lossy(program);
+        // Programs run under Wine have Windows paths.
+        let name = program.rsplit(['/', '\\']).next().unwrap().to_lowercase();
+        if is_game_executable(&name) {
+            processes.push(GameProcess { pid, name });
+        }
+    }
+    Ok(processes)
+}
+
+/// Finds file descriptors of processes other than `own_pid` that point inside `root`, which
+/// must be canonical. Processes belonging to other users can't be inspected, and are skipped.
+#[cfg(any(target_os = "linux", target_os = "android", all(test, unix)))]
+fn find_open_files(proc_dir: &Path, root: &Path, own_pid: u32) -> io::Result<Vec<OpenFile>> {
+    let mut open_files = Vec::new();
+    for (pid, process_dir) in process_dirs(proc_dir)? {
+        if pid == own_pid {
+            continue;
+        }
+        let descriptors = match fs::read_dir(process_dir.join("fd")) {
+            Ok(descriptors) => descriptors,
+            Err(_) => continue,
+        };
+        for descriptor in descriptors {
+            let target = match descriptor.and_then(|descriptor| fs::read_link(descriptor.path())) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            if target.starts_with(root) {
+                let open_file = OpenFile {
+                    pid: Some(pid),
+                    path: target,
+                };
+                if !open_files.contains(&open_file) {
+                    open_files.push(open_file);
+                }
+            }
+        }
+    }
+    Ok(open_files)
+}
+
+/// Lists the per-process directories in `/proc`, sorted by process ID.
+#[cfg(any(target_os = "linux", target_os = "android", test))]
+fn process_dirs(proc_dir: &Path) -> io::Result<Vec<(u32, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(proc_dir)? {
+        let entry = entry?;
+        if let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            dirs.push((pid, entry.path()));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{find_game_processes, GameProcess, OpenFile, Usage};
+
+    #[test]
+    fn game_processes() {
+        let directory = tempfile::tempdir().unwrap();
+        let proc_dir = directory.path();
+        for (pid, command_line) in [
+            ("1", &b"/sbin/init\0splash\0"[..]),
+            (
+                "42",
+                b"C:\\Program Files (x86)\\SquareEnix\\game\\ffxiv_dx11.exe\0DEV.TestSID=0\0",
+            ),
+            ("7", b"Z:\\ffxiv\\boot\\FFXIVLauncher64.exe\0"),
+            ("self", b"C:\\game\\ffxiv_dx11.exe\0"),
+        ] {
+            fs::create_dir(proc_dir.join(pid)).unwrap();
+            fs::write(proc_dir.join(pid).join("cmdline"), command_line).unwrap();
+        }
+        // A process that exited while the directory was read.
+        fs::create_dir(proc_dir.join("99")).unwrap();
+
+        assert_eq!(
+            find_game_processes(proc_dir).unwrap(),
+            [
+                GameProcess {
+                    pid: 7,
+                    name: "ffxivlauncher64.exe".to_string()
+                },
+                GameProcess {
+                    pid: 42,
+                    name: "ffxiv_dx11.exe".to_string()
+                },
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_files() {
+        use std::os::unix::fs::symlink;
+
+        use super::find_open_files;
+
+        let directory = tempfile::tempdir().unwrap();
+        let proc_dir = directory.path().join("proc");
+        let root = directory.path().join("install");
+        let index = root.join("game/sqpack/ffxiv/0a0000.win32.index");
+        for (pid, descriptors) in [
+            ("3", vec![directory.path().join("elsewhere"), index.clone()]),
+            // Our own process, which is ignored.
+            ("5", vec![index.clone()]),
+            ("8", vec![root.join("game/ffxiv_dx11.exe"), index.clone()]),
+        ] {
+            let fd_dir = proc_dir.join(pid).join("fd");
+            fs::create_dir_all(&fd_dir).unwrap();
+            for (i, target) in descriptors.into_iter().enumerate() {
+                symlink(target, fd_dir.join(i.to_string())).unwrap();
+            }
+        }
+        // A process whose descriptors can't be read.
+        fs::create_dir(proc_dir.join("13")).unwrap();
+
+        let usage = Usage {
+            processes: Vec::new(),
+            open_files: find_open_files(&proc_dir, &root, 5).unwrap(),
+        };
+        assert_eq!(
+            usage.open_files,
+            [
+                OpenFile {
+                    pid: Some(3),
+                    path: index.clone(),
+                },
+                OpenFile {
+                    pid: Some(8),
+                    path: root.join("game/ffxiv_dx11.exe"),
+                },
+                OpenFile {
+                    pid: Some(8),
+                    path: index.clone(),
+                },
+            ]
+        );
+        assert!(!usage.is_empty());
+        assert!(Usage::default().is_empty());
+        assert!(usage
+            .to_string()
+            .starts_with(&format!("{} is open (3)", index.display())));
+    }
+}
@@ -0,0 +1,196 @@
+//! Packs held in memory instead of in an installation directory, for tests, fuzzers, and
+//! platforms without a filesystem. See [`GameData::from_provider`](crate::GameData::from_provider).
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{
+    encoding::{PackIO, PackSetWriter, SetLen},
+    PlatformId, SqPackId,
+};
+
+/// The files of one pack.
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryPack {
+    pub(crate) index: Arc<[u8]>,
+    pub(crate) index2: Arc<[u8]>,
+    pub(crate) dats: Vec<Arc<[u8]>>,
+}
+
+/// A set of packs, each given as the contents of its `.index`, `.index2`, and `.dat` files.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryProvider {
+    packs: BTreeMap<SqPackId, MemoryPack>,
+}
+
+impl MemoryProvider {
+    pub fn new() -> MemoryProvider {
+        MemoryProvider::default()
+    }
+
+    /// Adds a pack, replacing any pack with the same ID. The data files are given in order,
+    /// starting with `.dat0`.
+    pub fn add_pack(
+        &mut self,
+        pack_id: SqPackId,
+        index: Vec<u8>,
+        index2: Vec<u8>,
+        dats: Vec<Vec<u8>>,
+    ) {
+        self.packs.insert(
+            pack_id,
+            MemoryPack {
+                index: index.into(),
+                index2: index2.into(),
+                dats: dats.into_iter().map(Arc::from).collect(),
+            },
+        );
+    }
+
+    /// Writes a new pack containing the given files, like
+    /// [`encoding::write_pack`](crate::encoding::write_pack), and adds it.
+    pub fn write_pack<'a>(
+        &mut self,
+        pack_id: SqPackId,
+        files: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    ) -> Result<(), io::Error> {
+        let io = MemoryPackIO::default();
+        let (index, index2, dats) = (io.index.clone(), io.index2.clone(), Rc::clone(&io.dats));
+        let mut writer = PackSetWriter::new(io, PlatformId::Win32, pack_id)?;
+        for (path, data) in files {
+            writer.add_file(path, data)?;
+        }
+        writer.finalize()?;
+        let dats = dats.borrow().iter().map(MemoryFile::contents).collect();
+        self.add_pack(pack_id, index.contents(), index2.contents(), dats);
+        Ok(())
+    }
+
+    pub(crate) fn packs(&self) -> &BTreeMap<SqPackId, MemoryPack> {
+        &self.packs
+    }
+
+    /// Opens a data file, or returns a "not found" error, as if it were opened from disk.
+    pub(crate) fn open_dat(&self, pack_id: SqPackId, dat_number: u8) -> io::Result<DataFile> {
+        self.packs
+            .get(&pack_id)
+            .and_then(|pack| pack.dats.get(usize::from(dat_number)))
+            .map(|data| DataFile::Memory(Cursor::new(Arc::clone(data))))
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// A data file opened by a [`DataFileSet`](crate::DataFileSet), either from disk, or from a
+/// [`MemoryProvider`].
+#[derive(Debug)]
+pub enum DataFile {
+    File(File),
+    Memory(Cursor<Arc<[u8]>>),
+}
+
+impl DataFile {
+    /// Returns the length of the data file.
+    pub fn len(&self) -> io::Result<u64> {
+        match self {
+            DataFile::File(file) => Ok(file.metadata()?.len()),
+            DataFile::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+        }
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl Read for DataFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            DataFile::File(file) => file.read(buf),
+            DataFile::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for DataFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            DataFile::File(file) => file.seek(pos),
+            DataFile::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// A growable buffer, shared between the handles that [`PackSetWriter`] opens and the
+/// [`MemoryProvider`] that collects the result.
+#[derive(Clone, Default)]
+struct MemoryFile(Rc<RefCell<Cursor<Vec<u8>>>>);
+
+impl MemoryFile {
+    fn contents(&self) -> Vec<u8> {
+        self.0.borrow().get_ref().clone()
+    }
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.borrow_mut().seek(pos)
+    }
+}
+
+impl SetLen for MemoryFile {
+    fn set_len(&self, size: u64) -> Result<(), io::Error> {
+        let size =
+            usize::try_from(size).map_err(|_| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        self.0.borrow_mut().get_mut().resize(size, 0);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct MemoryPackIO {
+    index: MemoryFile,
+    index2: MemoryFile,
+    dats: Rc<RefCell<Vec<MemoryFile>>>,
+}
+
+impl PackIO for MemoryPackIO {
+    type F = MemoryFile;
+
+    fn open_index_file(&mut self) -> Result<MemoryFile, io::Error> {
+        Ok(self.index.clone())
+    }
+
+    fn open_index2_file(&mut self) -> Result<MemoryFile, io::Error> {
+        Ok(self.index2.clone())
+    }
+
+    fn open_dat_file(&mut self, number: u8) -> Result<MemoryFile, io::Error> {
+        let mut dats = self.dats.borrow_mut();
+        if dats.len() <= usize::from(number) {
+            dats.resize_with(usize::from(number) + 1, MemoryFile::default);
+        }
+        Ok(dats[usize::from(number)].clone())
+    }
+}
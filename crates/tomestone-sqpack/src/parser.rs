@@ -1,28 +1,39 @@
+use alloc::{string::ToString, vec::Vec};
+use core::convert::TryInto;
+#[cfg(feature = "std")]
 use std::{
-    convert::TryInto,
-    fs::File,
+    fs::{self, File},
     io::{self, BufRead, Read, Seek, SeekFrom},
     path::PathBuf,
 };
 
+#[cfg(feature = "std")]
+use nom::Needed;
 use nom::{
     branch::alt,
     bytes::streaming::{tag, take, take_while},
     combinator::{complete, map, map_opt, map_parser, map_res, peek, verify},
     error::{ErrorKind, ParseError},
     multi::{count, length_data, length_value},
-    number::streaming::{le_u16, le_u32, le_u8},
+    number::{
+        streaming::{le_u8, u16 as endian_u16, u32 as endian_u32},
+        Endianness,
+    },
     sequence::{pair, terminated, tuple},
-    Err, IResult, Needed,
+    Err, IResult,
 };
-use sha1::{Digest, Sha1};
 
+#[cfg(feature = "std")]
+use memmap2::Mmap;
 use tomestone_common::null_padding;
 
+#[cfg(feature = "std")]
+use crate::compression::decompress_sqpack_block;
 use crate::{
-    compression::decompress_sqpack_block, CollisionEntry, DataBlocks, Error, FilePointer, Index,
-    IndexEntry, IndexEntry1, IndexEntry2, IndexHash1, IndexHash2, IndexPointer, IndexSegmentHeader,
-    PlatformId, SqPackType, ZeroEntry, SHA1_OUTPUT_SIZE,
+    CollisionEntry, DataBlocks, DataHeader, Error, FilePointer, FolderEntry, Index, IndexEntry,
+    IndexEntry1, IndexEntry2, IndexHash1, IndexHash2, IndexPointer, IndexSegmentHeader,
+    ModelHeader, PlatformId, SalvagedIndex, SqPackHeader, SqPackType, ZeroEntry,
+    MODEL_SECTION_ORDER, SHA1_OUTPUT_SIZE,
 };
 
 fn sqpack_magic(input: &[u8]) -> IResult<&[u8], ()> {
@@ -48,8 +59,8 @@ fn platform_id(input: &[u8]) -> IResult<&[u8], PlatformId> {
     map_res(le_u8, PlatformId::from_u8)(input)
 }
 
-fn sqpack_type(input: &[u8]) -> IResult<&[u8], SqPackType> {
-    map_res(le_u32, SqPackType::from_u32)(input)
+fn sqpack_type(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], SqPackType> {
+    move |input| map_res(endian_u32(endianness), SqPackType::from_u32)(input)
 }
 
 /// Parses a SqPack header (excluding its integrity check hash).
@@ -59,28 +70,40 @@ fn sqpack_type(input: &[u8]) -> IResult<&[u8], SqPackType> {
 /// 0x008-0x00C: Platform ID
 /// 0x00C-0x010: Size
 /// 0x010-0x014: Version (0 or 1)
-/// 0x014-0x018: Type (1 for data, 2 for index)
-/// 0x018-0x01C: Date
-/// 0x01C-0x020: Time
+/// 0x014-0x018: Type (0 for SQDB, 1 for data, 2 for index)
+/// 0x018-0x01C: Date (decimal YYYYMMDD, or zero)
+/// 0x01C-0x020: Time (decimal HHMMSScc, or zero)
 /// 0x020-0x024: "\xff\xff\xff\xff"
 /// 0x024-0x3c0: Null bytes
 /// ```
-fn sqpack_header_inner(input: &[u8]) -> IResult<&[u8], (PlatformId, u32, u32, SqPackType)> {
+///
+/// Integers after the platform ID are big-endian in PS3 files, and little-endian otherwise.
+fn sqpack_header_inner(input: &[u8]) -> IResult<&[u8], SqPackHeader> {
+    let (input, (_, platform_id, _)) = tuple((
+        alt((sqpack_magic, alternate_dat_magic)),
+        platform_id,
+        null_padding(3),
+    ))(input)?;
+    let endianness = platform_id.endianness();
     map(
         tuple((
-            alt((sqpack_magic, alternate_dat_magic)),
-            platform_id,
-            null_padding(3),
-            le_u32, // note that this doesn't seem to match header size for .dat2 files
-            verify(le_u32, |version| *version == 0 || *version == 1),
-            sqpack_type,
-            le_u32,
-            le_u32,
+            endian_u32(endianness), // note that this doesn't seem to match header size for .dat2 files
+            verify(endian_u32(endianness), |version| {
+                *version == 0 || *version == 1
+            }),
+            sqpack_type(endianness),
+            endian_u32(endianness),
+            endian_u32(endianness),
             tag(b"\xff\xff\xff\xff"),
             null_padding(0x39c),
         )),
-        |(_, platform_id, _, size, version, sqpack_type, _date, _time, _, _)| {
-            (platform_id, size, version, sqpack_type)
+        move |(size, version, sqpack_type, packed_date, packed_time, _, _)| SqPackHeader {
+            platform_id,
+            size,
+            version,
+            sqpack_type,
+            packed_date,
+            packed_time,
         },
     )(input)
 }
@@ -94,7 +117,7 @@ pub fn integrity_checked_header<
     input: &'a [u8],
     length_parser: LP,
     contents_parser: CP,
-) -> IResult<&[u8], O> {
+) -> IResult<&'a [u8], O> {
     const HASH_OFFSET: usize = 0x3c0;
 
     map_parser(
@@ -110,9 +133,7 @@ pub fn integrity_checked_header<
                     ),
                 ),
                 |(header_input, header_hash): &(&[u8], &[u8; SHA1_OUTPUT_SIZE])| {
-                    let mut hash = Sha1::new();
-                    hash.update(header_input);
-                    &*hash.finalize() == *header_hash
+                    crate::sha1(header_input) == **header_hash
                 },
             ),
             |(header_input, _header_hash)| header_input,
@@ -128,32 +149,39 @@ pub fn integrity_checked_header<
 /// 0x008-0x00C: Platform ID
 /// 0x00C-0x010: Size
 /// 0x010-0x014: Version (0 or 1)
-/// 0x014-0x018: Type (1 for data, 2 for index)
-/// 0x018-0x01C: Date
-/// 0x01C-0x020: Time
+/// 0x014-0x018: Type (0 for SQDB, 1 for data, 2 for index)
+/// 0x018-0x01C: Date (decimal YYYYMMDD, or zero)
+/// 0x01C-0x020: Time (decimal HHMMSScc, or zero)
 /// 0x020-0x024: "\xff\xff\xff\xff"
 /// 0x024-0x3c0: Null bytes
 /// 0x3c0-0x3d4: SHA-1 hash of the preceding 0x3c0 bytes
 /// 0x3d4-0x400: Null bytes
 /// ```
-pub(crate) fn sqpack_header_outer(
-    input: &[u8],
-) -> IResult<&[u8], (PlatformId, u32, u32, SqPackType)> {
+pub(crate) fn sqpack_header(input: &[u8]) -> IResult<&[u8], SqPackHeader> {
     integrity_checked_header(input, |_| Ok((b"", 1024usize)), sqpack_header_inner)
 }
 
-fn index_segment_header(input: &[u8]) -> IResult<&[u8], IndexSegmentHeader> {
-    map(
-        tuple((le_u32, le_u32, take(SHA1_OUTPUT_SIZE))),
-        |(offset, size, hash): (u32, u32, &[u8])| IndexSegmentHeader {
-            offset,
-            size,
-            hash: hash.try_into().unwrap(),
-        },
-    )(input)
+fn index_segment_header(
+    endianness: Endianness,
+) -> impl Fn(&[u8]) -> IResult<&[u8], IndexSegmentHeader> {
+    move |input| {
+        map(
+            tuple((
+                endian_u32(endianness),
+                endian_u32(endianness),
+                take(SHA1_OUTPUT_SIZE),
+            )),
+            |(offset, size, hash): (u32, u32, &[u8])| IndexSegmentHeader {
+                offset,
+                size,
+                hash: hash.try_into().unwrap(),
+            },
+        )(input)
+    }
 }
 
-/// Parses an index file header.
+/// Parses an index file header. The four segments contain the file table, the collision table,
+/// the table of empty data entries, and the folder table, respectively.
 ///
 /// ```text
 /// 0x400-0x404: Header length
@@ -175,50 +203,53 @@ fn index_segment_header(input: &[u8]) -> IResult<&[u8], IndexSegmentHeader> {
 /// 0x4e8-0x4ec: Fourth index segment size
 /// 0x4ec-0x500: Fourth index segment SHA-1 hash
 /// ```
+#[allow(clippy::type_complexity)]
 pub(crate) fn index_segment_headers(
-    input: &[u8],
-) -> IResult<&[u8], (u32, u32, [IndexSegmentHeader; 4])> {
-    integrity_checked_header(
-        input,
-        map(le_u32, |size| size.try_into().unwrap()),
-        map(
-            tuple((
-                le_u32,
-                tag(b"\x01\x00\x00\x00"),
-                index_segment_header,
-                null_padding(44),
-                le_u32,
-                index_segment_header,
-                null_padding(44),
-                index_segment_header,
-                null_padding(44),
-                index_segment_header,
-            )),
-            |(
-                size,
-                _,
-                segment_header_1,
-                _,
-                number_of_dat_files,
-                segment_header_2,
-                _,
-                segment_header_3,
-                _,
-                segment_header_4,
-            )| {
-                (
+    endianness: Endianness,
+) -> impl Fn(&[u8]) -> IResult<&[u8], (u32, u32, [IndexSegmentHeader; 4])> {
+    move |input| {
+        integrity_checked_header(
+            input,
+            map(endian_u32(endianness), |size| size.try_into().unwrap()),
+            map(
+                tuple((
+                    endian_u32(endianness),
+                    verify(endian_u32(endianness), |value| *value == 1),
+                    index_segment_header(endianness),
+                    null_padding(44),
+                    endian_u32(endianness),
+                    index_segment_header(endianness),
+                    null_padding(44),
+                    index_segment_header(endianness),
+                    null_padding(44),
+                    index_segment_header(endianness),
+                )),
+                |(
                     size,
+                    _,
+                    segment_header_1,
+                    _,
                     number_of_dat_files,
-                    [
-                        segment_header_1,
-                        segment_header_2,
-                        segment_header_3,
-                        segment_header_4,
-                    ],
-                )
-            },
-        ),
-    )
+                    segment_header_2,
+                    _,
+                    segment_header_3,
+                    _,
+                    segment_header_4,
+                )| {
+                    (
+                        size,
+                        number_of_dat_files,
+                        [
+                            segment_header_1,
+                            segment_header_2,
+                            segment_header_3,
+                            segment_header_4,
+                        ],
+                    )
+                },
+            ),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -246,84 +277,157 @@ impl DataContentType {
 #[derive(Debug)]
 struct DataEntryHeaderCommon {
     content_type: DataContentType,
-    _uncompressed_size: u32,
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    uncompressed_size: u32,
     _block_buffer_size: u32,
     num_blocks: u16,
 }
 
-fn data_entry_header_common(input: &[u8]) -> IResult<&[u8], (u32, DataEntryHeaderCommon)> {
-    map(
-        tuple((
-            le_u32,
-            map_opt(le_u32, DataContentType::parse),
-            le_u32,
-            le_u32,
-            le_u32,
-            le_u16,
-            le_u16,
-        )),
-        |(
-            header_length,
-            content_type,
-            uncompressed_size,
-            _unknown,
-            block_buffer_size,
-            num_blocks,
-            _todo,
-        )| {
-            (
+fn data_entry_header_common(
+    endianness: Endianness,
+) -> impl Fn(&[u8]) -> IResult<&[u8], (u32, DataEntryHeaderCommon)> {
+    move |input| {
+        map(
+            tuple((
+                endian_u32(endianness),
+                map_opt(endian_u32(endianness), DataContentType::parse),
+                endian_u32(endianness),
+                endian_u32(endianness),
+                endian_u32(endianness),
+                endian_u16(endianness),
+                endian_u16(endianness),
+            )),
+            |(
                 header_length,
-                DataEntryHeaderCommon {
-                    content_type,
-                    _uncompressed_size: uncompressed_size,
-                    _block_buffer_size: block_buffer_size << 7,
-                    num_blocks,
-                },
-            )
-        },
-    )(input)
+                content_type,
+                uncompressed_size,
+                _unknown,
+                block_buffer_size,
+                num_blocks,
+                _todo,
+            )| {
+                (
+                    header_length,
+                    DataEntryHeaderCommon {
+                        content_type,
+                        uncompressed_size,
+                        _block_buffer_size: block_buffer_size << 7,
+                        num_blocks,
+                    },
+                )
+            },
+        )(input)
+    }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn type_2_block_table<'a>(
+    endianness: Endianness,
     num_blocks: u16,
 ) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<(u32, u16, u16)>, nom::error::Error<&'a [u8]>> {
     count(
-        tuple((le_u32, le_u16, le_u16)),
-        num_blocks.try_into().unwrap(),
+        tuple((
+            endian_u32(endianness),
+            endian_u16(endianness),
+            endian_u16(endianness),
+        )),
+        num_blocks.into(),
     )
 }
 
-fn type_3_block_table<'a>(
-    _num_blocks: u16,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], (Vec<u32>, Vec<u32>, Vec<u32>, u16)> {
-    tuple((
-        count(le_u32, 11),
-        count(le_u32, 11),
-        count(le_u32, 11),
-        le_u16,
-        // count(le_u16, TryInto::<usize>::try_into(num_blocks).unwrap()),
-    ))
+/// Block table of a model entry, along with the fields of the model file header.
+struct ModelBlockTable {
+    header: ModelHeader,
+    offsets: Vec<u32>,
+    block_indices: Vec<u16>,
+    block_counts: Vec<u16>,
+    block_sizes: Vec<u16>,
 }
 
+/// Parses the headers of a model entry, starting from the beginning of the entry. Model entries
+/// use the fields after the uncompressed size differently from other entries.
+fn type_3_block_table(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], ModelBlockTable> {
+    move |input| {
+        let u16 = || endian_u16(endianness);
+        let u32 = || endian_u32(endianness);
+        let (
+            input,
+            (
+                _,
+                num_blocks,
+                _used_num_blocks,
+                version,
+                _decompressed_sizes,
+                _compressed_sizes,
+                offsets,
+                block_indices,
+                block_counts,
+                vertex_declaration_count,
+                material_count,
+                lod_count,
+                index_buffer_streaming,
+                edge_geometry,
+                _,
+            ),
+        ) = tuple((
+            take(12usize),
+            u32(),
+            u32(),
+            u32(),
+            count(u32(), 11),
+            count(u32(), 11),
+            count(u32(), 11),
+            count(u16(), 11),
+            count(u16(), 11),
+            u16(),
+            u16(),
+            le_u8,
+            le_u8,
+            le_u8,
+            le_u8,
+        ))(input)?;
+        let (input, block_sizes) = count(u16(), num_blocks.try_into().unwrap())(input)?;
+        Ok((
+            input,
+            ModelBlockTable {
+                header: ModelHeader {
+                    version,
+                    vertex_declaration_count,
+                    material_count,
+                    lod_count,
+                    index_buffer_streaming: index_buffer_streaming != 0,
+                    edge_geometry: edge_geometry != 0,
+                },
+                offsets,
+                block_indices,
+                block_counts,
+                block_sizes,
+            },
+        ))
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn type_4_block_table<'a>(
+    endianness: Endianness,
     num_blocks: u16,
 ) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], (Vec<(u32, u32, u32, u32, u32)>, Vec<u16>)> {
     move |input: &[u8]| {
         let (input, frame_infos) = count(
             tuple((
-                le_u32, // frame_offset
-                le_u32, // frame_size
-                le_u32, // _unknown
-                le_u32, // frame_block_size_offset
-                le_u32, // frame_block_size_count
+                endian_u32(endianness), // frame_offset
+                endian_u32(endianness), // frame_size
+                endian_u32(endianness), // _unknown
+                endian_u32(endianness), // frame_block_size_offset
+                endian_u32(endianness), // frame_block_size_count
             )),
-            num_blocks.try_into().unwrap(),
+            num_blocks.into(),
         )(input)?;
         let size_field_count = frame_infos
             .iter()
             .map(|tuple| TryInto::<usize>::try_into(tuple.4).unwrap())
             .sum();
-        let (input, frame_block_sizes) = count(le_u16, size_field_count)(input)?;
+        let (input, frame_block_sizes) = count(endian_u16(endianness), size_field_count)(input)?;
         Ok((input, (frame_infos, frame_block_sizes)))
     }
 }
@@ -342,6 +446,8 @@ fn type_4_block_table<'a>(
 /// 0x18-end: Block tables (varies)
 /// ```
 ///
+/// Integers are in the byte order of the platform the data file was built for.
+///
 /// Type 2/binary entry block table:
 /// ```text
 /// 0x18-0x1c: block offset
@@ -349,137 +455,312 @@ fn type_4_block_table<'a>(
 /// 0x1e-0x20: decompressed data size
 /// (repeats)
 /// ```
-fn data_entry_headers(start_position: u32) -> impl FnMut(&[u8]) -> IResult<&[u8], DataBlocks> {
+///
+/// Type 3/model entry headers. The fields after the uncompressed size differ from other entries.
+/// Each table has one entry per section of the model file: the stack, the runtime data, three
+/// vertex buffers, three edge geometry buffers, and three index buffers, one of each for every
+/// level of detail. Section offsets are relative to the end of the headers.
+/// ```text
+/// 0x0c-0x10: number of blocks
+/// 0x10-0x14: number of blocks used
+/// 0x14-0x18: model version
+/// 0x18-0x44: section decompressed sizes
+/// 0x44-0x70: section compressed sizes
+/// 0x70-0x9c: section offsets
+/// 0x9c-0xb2: index of each section's first block size
+/// 0xb2-0xc8: number of blocks in each section
+/// 0xc8-0xca: number of vertex declarations
+/// 0xca-0xcc: number of materials
+/// 0xcc-0xcd: number of levels of detail
+/// 0xcd-0xce: index buffer streaming enabled
+/// 0xce-0xcf: edge geometry enabled
+/// 0xcf-0xd0: padding
+/// u16 block sizes
+/// ```
+///
+/// Type 4/texture entry block table, with one frame per mipmap level, followed by the sizes of
+/// the blocks of every frame. Frame offsets are relative to the end of the headers, where an
+/// uncompressed texture header is stored before the first frame.
+/// ```text
+/// 0x18-0x1c: frame offset
+/// 0x1c-0x20: frame compressed size
+/// 0x20-0x24: frame decompressed size
+/// 0x24-0x28: index of the frame's first block size
+/// 0x28-0x2c: number of blocks in the frame
+/// (repeats)
+/// u16 block sizes
+/// ```
+fn data_entry_headers(
+    endianness: Endianness,
+    start_position: u32,
+) -> impl FnMut(&[u8]) -> IResult<&[u8], DataBlocks> {
     move |input: &[u8]| {
-        let (input, header_data) = length_data(peek(le_u32))(input)?;
+        let (input, entry_headers) = length_data(peek(endian_u32(endianness)))(input)?;
         let (header_data, (header_length, header_common)) =
-            complete(data_entry_header_common)(header_data)?;
+            complete(data_entry_header_common(endianness))(entry_headers)?;
         let base_position = start_position + header_length;
         let blocks = match header_common.content_type {
             DataContentType::Empty => DataBlocks::Empty,
             DataContentType::Unsupported => DataBlocks::Unsupported,
             DataContentType::Binary => {
-                let (_, blocks) =
-                    complete(type_2_block_table(header_common.num_blocks))(header_data)?;
+                let (_, blocks) = complete(type_2_block_table(
+                    endianness,
+                    header_common.num_blocks,
+                ))(header_data)?;
                 DataBlocks::Binary {
                     base_position,
                     blocks,
                 }
             }
             DataContentType::Model => {
-                let (_, _) = complete(type_3_block_table(header_common.num_blocks))(header_data)?;
-                DataBlocks::Model()
+                let (_, table) = complete(type_3_block_table(endianness))(entry_headers)?;
+                let mut section_block_counts = [0; 11];
+                let mut blocks = Vec::with_capacity(table.block_sizes.len());
+                for (section, section_block_count) in MODEL_SECTION_ORDER
+                    .iter()
+                    .zip(section_block_counts.iter_mut())
+                {
+                    let start = usize::from(table.block_indices[*section]);
+                    let count = usize::from(table.block_counts[*section]);
+                    if count == 0 {
+                        continue;
+                    }
+                    let block_sizes =
+                        table.block_sizes.get(start..start + count).ok_or_else(|| {
+                            Err::Error(nom::error::Error::new(entry_headers, ErrorKind::Verify))
+                        })?;
+                    let mut offset = table.offsets[*section];
+                    for block_size in block_sizes {
+                        blocks.push((offset, *block_size));
+                        offset += u32::from(*block_size);
+                    }
+                    *section_block_count = table.block_counts[*section];
+                }
+                DataBlocks::Model {
+                    base_position,
+                    header: table.header,
+                    section_block_counts,
+                    blocks,
+                }
             }
             DataContentType::Texture => {
-                let (_, _) = complete(type_4_block_table(header_common.num_blocks))(header_data)?;
-                DataBlocks::Texture()
+                let (_, (frame_infos, frame_block_sizes)) = complete(type_4_block_table(
+                    endianness,
+                    header_common.num_blocks,
+                ))(header_data)?;
+                let mut blocks = Vec::with_capacity(frame_block_sizes.len());
+                for (frame_offset, _, _, block_size_offset, block_size_count) in frame_infos.iter()
+                {
+                    let block_sizes = usize::try_from(*block_size_offset)
+                        .ok()
+                        .zip(usize::try_from(*block_size_count).ok())
+                        .and_then(|(start, count)| {
+                            frame_block_sizes.get(start..start.checked_add(count)?)
+                        })
+                        .ok_or_else(|| {
+                            Err::Error(nom::error::Error::new(header_data, ErrorKind::Verify))
+                        })?;
+                    let mut offset = *frame_offset;
+                    for block_size in block_sizes {
+                        blocks.push((offset, *block_size));
+                        offset += u32::from(*block_size);
+                    }
+                }
+                DataBlocks::Texture {
+                    base_position,
+                    header_size: frame_infos.first().map_or(0, |frame_info| frame_info.0),
+                    blocks,
+                }
             }
         };
         Ok((input, blocks))
     }
 }
 
-fn block_header(input: &[u8]) -> IResult<&[u8], (u32, u32)> {
-    let (_, header_length) = le_u32(input)?;
-    map_parser(
-        take(TryInto::<usize>::try_into(header_length).unwrap()),
-        complete(map(
-            tuple((le_u32, null_padding(4), le_u32, le_u32)),
-            |(_, _, compressed_length, decompressed_length)| {
-                (compressed_length, decompressed_length)
-            },
-        )),
-    )(input)
+#[cfg(feature = "std")]
+fn block_header(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], (u32, u32)> {
+    move |input| {
+        let (_, header_length) = endian_u32(endianness)(input)?;
+        map_parser(
+            take(TryInto::<usize>::try_into(header_length).unwrap()),
+            complete(map(
+                tuple((
+                    endian_u32(endianness),
+                    null_padding(4),
+                    endian_u32(endianness),
+                    endian_u32(endianness),
+                )),
+                |(_, _, compressed_length, decompressed_length)| {
+                    (compressed_length, decompressed_length)
+                },
+            )),
+        )(input)
+    }
 }
 
-fn index_hash_1(input: &[u8]) -> IResult<&[u8], IndexHash1> {
-    map(pair(le_u32, le_u32), |(filename_crc, folder_crc)| {
-        IndexHash1::new(folder_crc, filename_crc)
-    })(input)
+fn index_hash_1(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], IndexHash1> {
+    move |input| {
+        map(
+            pair(endian_u32(endianness), endian_u32(endianness)),
+            |(filename_crc, folder_crc)| IndexHash1::new(folder_crc, filename_crc),
+        )(input)
+    }
 }
 
-fn index_hash_2(input: &[u8]) -> IResult<&[u8], IndexHash2> {
-    map(le_u32, IndexHash2::new)(input)
+fn index_hash_2(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], IndexHash2> {
+    move |input| map(endian_u32(endianness), IndexHash2::new)(input)
 }
 
-fn index_entry_1(input: &[u8]) -> IResult<&[u8], IndexEntry1> {
-    map(
-        tuple((
-            index_hash_1,
-            map(le_u32, IndexPointer::from_u32),
-            null_padding(4),
-        )),
-        |(hash, pointer, _)| IndexEntry1 { hash, pointer },
-    )(input)
+fn index_entry_1(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], IndexEntry1> {
+    move |input| {
+        map(
+            tuple((
+                index_hash_1(endianness),
+                map(endian_u32(endianness), IndexPointer::from_u32),
+                null_padding(4),
+            )),
+            |(hash, pointer, _)| IndexEntry1 { hash, pointer },
+        )(input)
+    }
 }
 
-fn index_entry_2(input: &[u8]) -> IResult<&[u8], IndexEntry2> {
-    map(
-        pair(index_hash_2, map(le_u32, IndexPointer::from_u32)),
-        |(hash, pointer)| IndexEntry2 { hash, pointer },
-    )(input)
+fn index_entry_2(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], IndexEntry2> {
+    move |input| {
+        map(
+            pair(
+                index_hash_2(endianness),
+                map(endian_u32(endianness), IndexPointer::from_u32),
+            ),
+            |(hash, pointer)| IndexEntry2 { hash, pointer },
+        )(input)
+    }
 }
 
-fn collision_entry_1(input: &[u8]) -> IResult<&[u8], CollisionEntry<IndexHash1>> {
-    map(
-        tuple((
-            index_hash_1,
-            map(le_u32, FilePointer::from_u32),
-            le_u32,
-            length_value(
-                |input| Ok((input, 240usize)),
-                map_res(
-                    terminated(take_while(|byte: u8| byte != 0), tag(b"\x00")),
-                    std::str::from_utf8,
+/// Reads a `u32` field of an entry in a memory-mapped file, or of any other header held in memory.
+#[cfg(feature = "std")]
+pub(crate) fn mapped_field(bytes: &[u8], offset: usize, endianness: Endianness) -> u32 {
+    let bytes = bytes[offset..offset + 4].try_into().unwrap();
+    match endianness {
+        Endianness::Big => u32::from_be_bytes(bytes),
+        Endianness::Little | Endianness::Native => u32::from_le_bytes(bytes),
+    }
+}
+
+/// Reads an `.index` entry from a memory-mapped file. Padding isn't checked, since entries are
+/// only read as they are looked up.
+#[cfg(feature = "std")]
+fn mapped_entry_1(bytes: &[u8], endianness: Endianness) -> IndexEntry1 {
+    let field = |offset: usize| mapped_field(bytes, offset, endianness);
+    IndexEntry1 {
+        hash: IndexHash1::new(field(4), field(0)),
+        pointer: IndexPointer::from_u32(field(8)),
+    }
+}
+
+/// Reads an `.index2` entry from a memory-mapped file.
+#[cfg(feature = "std")]
+fn mapped_entry_2(bytes: &[u8], endianness: Endianness) -> IndexEntry2 {
+    let field = |offset: usize| mapped_field(bytes, offset, endianness);
+    IndexEntry2 {
+        hash: IndexHash2::new(field(0)),
+        pointer: IndexPointer::from_u32(field(4)),
+    }
+}
+
+fn collision_entry_1(
+    endianness: Endianness,
+) -> impl Fn(&[u8]) -> IResult<&[u8], CollisionEntry<IndexHash1>> {
+    move |input| {
+        map(
+            tuple((
+                index_hash_1(endianness),
+                map(endian_u32(endianness), FilePointer::from_u32),
+                endian_u32(endianness),
+                length_value(
+                    |input| Ok((input, 240usize)),
+                    map_res(
+                        terminated(take_while(|byte: u8| byte != 0), tag(b"\x00")),
+                        core::str::from_utf8,
+                    ),
                 ),
-            ),
-        )),
-        |(hash, pointer, collision_index, path)| CollisionEntry {
-            hash,
-            pointer,
-            _maybe_collision_index: collision_index,
-            path: path.to_string(),
-        },
-    )(input)
+            )),
+            |(hash, pointer, collision_index, path)| CollisionEntry {
+                hash,
+                pointer,
+                _maybe_collision_index: collision_index,
+                path: path.to_string(),
+            },
+        )(input)
+    }
 }
 
-fn collision_entry_2(input: &[u8]) -> IResult<&[u8], CollisionEntry<IndexHash2>> {
-    map(
-        tuple((
-            index_hash_2,
-            null_padding(4),
-            map(le_u32, FilePointer::from_u32),
-            le_u32,
-            length_value(
-                |input| Ok((input, 240usize)),
-                map_res(
-                    terminated(take_while(|byte: u8| byte != 0), tag(b"\x00")),
-                    std::str::from_utf8,
+fn collision_entry_2(
+    endianness: Endianness,
+) -> impl Fn(&[u8]) -> IResult<&[u8], CollisionEntry<IndexHash2>> {
+    move |input| {
+        map(
+            tuple((
+                index_hash_2(endianness),
+                null_padding(4),
+                map(endian_u32(endianness), FilePointer::from_u32),
+                endian_u32(endianness),
+                length_value(
+                    |input| Ok((input, 240usize)),
+                    map_res(
+                        terminated(take_while(|byte: u8| byte != 0), tag(b"\x00")),
+                        core::str::from_utf8,
+                    ),
                 ),
-            ),
-        )),
-        |(hash, _, pointer, collision_index, path)| CollisionEntry {
-            hash,
-            pointer,
-            _maybe_collision_index: collision_index,
-            path: path.to_string(),
-        },
-    )(input)
+            )),
+            |(hash, _, pointer, collision_index, path)| CollisionEntry {
+                hash,
+                pointer,
+                _maybe_collision_index: collision_index,
+                path: path.to_string(),
+            },
+        )(input)
+    }
 }
 
-fn tombstone_entry_parser(input: &[u8]) -> IResult<&[u8], ZeroEntry> {
-    map(
-        tuple((le_u8, null_padding(3), le_u32, le_u32, null_padding(4))),
-        |(data_file_id, _, shifted_offset, shifted_length, _)| ZeroEntry {
-            shifted_length: shifted_length - 1,
-            pointer: FilePointer::new(data_file_id, shifted_offset << 7),
-        },
-    )(input)
+fn folder_entry(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], FolderEntry> {
+    move |input| {
+        map(
+            tuple((
+                endian_u32(endianness),
+                endian_u32(endianness),
+                endian_u32(endianness),
+                null_padding(4),
+            )),
+            |(folder_crc, files_offset, files_span, ())| FolderEntry {
+                folder_crc,
+                files_offset,
+                files_span,
+            },
+        )(input)
+    }
+}
+
+fn tombstone_entry_parser(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], ZeroEntry> {
+    move |input| {
+        map(
+            tuple((
+                map_res(endian_u32(endianness), u8::try_from),
+                endian_u32(endianness),
+                endian_u32(endianness),
+                null_padding(4),
+            )),
+            |(data_file_id, shifted_offset, shifted_length, _)| ZeroEntry {
+                shifted_length: shifted_length - 1,
+                pointer: FilePointer::new(data_file_id, shifted_offset << 7),
+            },
+        )(input)
+    }
 }
 
 /// `GrowableBufReader` adds buffering to a reader, and allows callers to dynamically request a
 /// larger buffer on the fly. EOF handling is decoupled from consuming the buffer, so callers can
 /// see when the buffer cannot be grown anymore, without having to mark it all as consumed first.
+#[cfg(feature = "std")]
 pub struct GrowableBufReader<R: Read> {
     inner: R,
     buf: Vec<u8>,
@@ -487,6 +768,7 @@ pub struct GrowableBufReader<R: Read> {
     cap: usize,
 }
 
+#[cfg(feature = "std")]
 impl<R: Read> GrowableBufReader<R> {
     pub fn with_capacity(inner: R, capacity: usize) -> GrowableBufReader<R> {
         let buf = vec![0; capacity];
@@ -531,6 +813,7 @@ impl<R: Read> GrowableBufReader<R> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<R: Read> Read for GrowableBufReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let nread = {
@@ -542,6 +825,7 @@ impl<R: Read> Read for GrowableBufReader<R> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<R: Read + Seek> Seek for GrowableBufReader<R> {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
         if let SeekFrom::Current(_) = pos {
@@ -554,6 +838,7 @@ impl<R: Read + Seek> Seek for GrowableBufReader<R> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<R: Read> BufRead for GrowableBufReader<R> {
     fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
         if self.pos >= self.cap {
@@ -573,6 +858,7 @@ impl<R: Read> BufRead for GrowableBufReader<R> {
 /// `Incomplete` value, the `GrowableBufReader` will be filled, and the parser will be re-ran,
 /// until parsing succeeds. Upon return, the position tracked by `GrowableBufReader` will point
 /// after the data that the parser consumed.
+#[cfg(feature = "std")]
 pub fn drive_streaming_parser<R, F, O>(
     reader: &mut GrowableBufReader<R>,
     mut parser: F,
@@ -613,6 +899,7 @@ where
 /// Apply a streaming parser to a seekable stream. This function uses its own buffer, rather than
 /// the one from `GrowableBufReader`. Upon return, the stream's position will be right after the
 /// bytes consumed by the parser.
+#[cfg(feature = "std")]
 pub fn drive_streaming_parser_smaller<RS, F, O>(mut reader: RS, mut parser: F) -> Result<O, Error>
 where
     RS: Read + Seek,
@@ -652,23 +939,31 @@ where
     }
 }
 
+/// Loads an index. The entry and collision parsers are created for the byte order of the file,
+/// which depends on its platform.
+#[cfg(feature = "std")]
 fn load_index_reader<
+    R: Read + Seek,
     I: IndexEntry,
     EP: Fn(&[u8]) -> IResult<&[u8], I>,
     CP: Fn(&[u8]) -> IResult<&[u8], CollisionEntry<I::Hash>>,
 >(
-    bufreader: &mut GrowableBufReader<File>,
-    entry_parser: EP,
-    collision_parser: CP,
+    bufreader: &mut GrowableBufReader<R>,
+    entry_parser: impl Fn(Endianness) -> EP,
+    collision_parser: impl Fn(Endianness) -> CP,
 ) -> Result<Index<I>, Error> {
-    let file_header = drive_streaming_parser(bufreader, sqpack_header_outer)?;
-    let size = file_header.1;
+    let file_header = drive_streaming_parser(bufreader, sqpack_header)?;
+    let size = file_header.size;
+    let endianness = file_header.platform_id.endianness();
+    let entry_parser = entry_parser(endianness);
+    let collision_parser = collision_parser(endianness);
 
     bufreader.seek(SeekFrom::Start(size.into()))?;
-    let index_header = drive_streaming_parser(bufreader, index_segment_headers)?;
+    let index_header = drive_streaming_parser(bufreader, index_segment_headers(endianness))?;
     let first_segment_header = &index_header.2[0];
     let second_segment_header = &index_header.2[1];
     let third_segment_header = &index_header.2[2];
+    let fourth_segment_header = &index_header.2[3];
 
     bufreader.seek(SeekFrom::Start(first_segment_header.offset.into()))?;
     let entry_count = first_segment_header.size / I::SIZE;
@@ -690,68 +985,642 @@ fn load_index_reader<
     let entry_count = third_segment_header.size / 16;
     let mut tombstone_entries = Vec::with_capacity(entry_count.try_into().unwrap());
     for _ in 0..entry_count {
-        let entry = drive_streaming_parser(bufreader, tombstone_entry_parser)?;
+        let entry = drive_streaming_parser(bufreader, tombstone_entry_parser(endianness))?;
         tombstone_entries.push(entry);
     }
 
+    bufreader.seek(SeekFrom::Start(fourth_segment_header.offset.into()))?;
+    let entry_count = fourth_segment_header.size / 16;
+    let mut folder_entries = Vec::with_capacity(entry_count.try_into().unwrap());
+    for _ in 0..entry_count {
+        let entry = drive_streaming_parser(bufreader, folder_entry(endianness))?;
+        folder_entries.push(entry);
+    }
+
     Ok(Index::new(
         index_entries,
         collision_entries,
         tombstone_entries,
+        folder_entries,
+        index_header.1,
     ))
 }
 
+/// Parses as many records as possible from one index segment, stopping at the end of the
+/// segment, at the end of the file, at the first record that fails to parse, or at the first
+/// record that is out of order. Returns the records, and the offset of the first record that could
+/// not be recovered, if any.
+fn salvage_segment<O, P, K>(
+    data: &[u8],
+    segment_header: &IndexSegmentHeader,
+    record_size: u32,
+    record_count: u32,
+    mut parser: P,
+    sort_key: impl Fn(&O) -> K,
+) -> (Vec<O>, Option<u64>)
+where
+    P: FnMut(&[u8]) -> IResult<&[u8], O>,
+    K: PartialOrd,
+{
+    let mut records: Vec<O> = Vec::new();
+    for i in 0..record_count {
+        let offset = u64::from(segment_header.offset) + u64::from(i) * u64::from(record_size);
+        let input = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| data.get(offset..))
+            .unwrap_or_default();
+        let record = match parser(input) {
+            Ok((_, record)) => record,
+            Err(_) => return (records, Some(offset)),
+        };
+        if let Some(last) = records.last() {
+            if sort_key(last) > sort_key(&record) {
+                return (records, Some(offset));
+            }
+        }
+        records.push(record);
+    }
+    (records, None)
+}
+
+/// Parses the SqPack header and the segment headers of an index, returning the byte order of the
+/// file, the number of data files, and the segment headers.
+fn index_headers(data: &[u8]) -> Result<(Endianness, u32, [IndexSegmentHeader; 4]), Error> {
+    let (_, file_header) = sqpack_header(data).map_err(nom_error_kind)?;
+    let endianness = file_header.platform_id.endianness();
+    let header_end = usize::try_from(file_header.size)
+        .ok()
+        .and_then(|size| data.get(size..))
+        .ok_or(Error::Nom(ErrorKind::Eof))?;
+    let (_, (_, dat_file_count, segment_headers)) =
+        index_segment_headers(endianness)(header_end).map_err(nom_error_kind)?;
+    Ok((endianness, dat_file_count, segment_headers))
+}
+
+/// Loads an index from a possibly truncated or partially corrupted file. The headers must be
+/// intact, but each segment is recovered up to the first record that is missing, malformed, or
+/// out of order.
+fn salvage_index<
+    I: IndexEntry,
+    EP: Fn(&[u8]) -> IResult<&[u8], I>,
+    CP: Fn(&[u8]) -> IResult<&[u8], CollisionEntry<I::Hash>>,
+>(
+    data: &[u8],
+    entry_parser: impl Fn(Endianness) -> EP,
+    collision_parser: impl Fn(Endianness) -> CP,
+) -> Result<SalvagedIndex<I>, Error> {
+    let (endianness, dat_file_count, segment_headers) = index_headers(data)?;
+
+    let (index_entries, truncated_1) = salvage_segment(
+        data,
+        &segment_headers[0],
+        I::SIZE,
+        segment_headers[0].size / I::SIZE,
+        entry_parser(endianness),
+        IndexEntry::hash,
+    );
+    let (collision_entries, truncated_2) = salvage_segment(
+        data,
+        &segment_headers[1],
+        256,
+        (segment_headers[1].size / 256).saturating_sub(1),
+        collision_parser(endianness),
+        |entry: &CollisionEntry<I::Hash>| entry.hash,
+    );
+    let (tombstone_entries, truncated_3) = salvage_segment(
+        data,
+        &segment_headers[2],
+        16,
+        segment_headers[2].size / 16,
+        tombstone_entry_parser(endianness),
+        |_| (),
+    );
+    let (folder_entries, truncated_4) = salvage_segment(
+        data,
+        &segment_headers[3],
+        16,
+        segment_headers[3].size / 16,
+        folder_entry(endianness),
+        |entry: &FolderEntry| entry.folder_crc,
+    );
+    let truncation_point = [truncated_1, truncated_2, truncated_3, truncated_4]
+        .into_iter()
+        .flatten()
+        .min();
+
+    Ok(SalvagedIndex {
+        index: Index::new(
+            index_entries,
+            collision_entries,
+            tombstone_entries,
+            folder_entries,
+            dat_file_count,
+        ),
+        truncated_at: truncation_point,
+    })
+}
+
+/// Parses the header that follows the SqPack header in a data file.
+///
+/// ```text
+/// 0x400-0x404: Data header length
+/// 0x404-0x408: Null bytes
+/// 0x408-0x40C: 16
+/// 0x40C-0x410: File length, shifted right by 7
+/// 0x410-0x414: Data file number plus one
+/// 0x414-0x418: Null bytes
+/// 0x418-0x41C: Data file size limit
+/// 0x41C-0x420: Null bytes
+/// 0x420-0x434: SHA-1 hash of the data section, or null bytes
+/// 0x434-0x7c0: Null bytes
+/// 0x7c0-0x7d4: SHA-1 hash of the preceding 0x3c0 bytes
+/// 0x7d4-0x800: Null bytes
+/// ```
+///
+/// Integers are in the byte order given by the platform ID in the SqPack header.
+fn data_header(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], DataHeader> {
+    move |input| {
+        integrity_checked_header(
+            input,
+            map(endian_u32(endianness), |size| size.try_into().unwrap()),
+            map(
+                tuple((
+                    endian_u32(endianness),
+                    null_padding(4),
+                    endian_u32(endianness),
+                    endian_u32(endianness),
+                    endian_u32(endianness),
+                    null_padding(4),
+                    endian_u32(endianness),
+                )),
+                |(_, _, _, shifted_data_size, spanned_dat, _, max_file_size)| DataHeader {
+                    data_size: u64::from(shifted_data_size) << 7,
+                    spanned_dat,
+                    max_file_size,
+                },
+            ),
+        )
+    }
+}
+
+/// Reads the SqPack header and the second header of a data file.
+#[cfg(feature = "std")]
+pub fn read_data_header<R: Read + Seek>(file: &mut R) -> Result<DataHeader, Error> {
+    let mut buf = [0; 0x800];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut buf)?;
+    parse_data_header(&buf)
+}
+
+/// Parses the SqPack header and the second header of a data file, from the start of its contents.
+pub fn parse_data_header(data: &[u8]) -> Result<DataHeader, Error> {
+    let (input, sqpack_header) = sqpack_header(data).map_err(nom_error_kind)?;
+    let (_, header) =
+        data_header(sqpack_header.platform_id.endianness())(input).map_err(nom_error_kind)?;
+    Ok(header)
+}
+
+fn nom_error_kind(e: nom::Err<nom::error::Error<&[u8]>>) -> Error {
+    match e {
+        Err::Incomplete(_) => Error::Nom(ErrorKind::Eof),
+        Err::Error(e) | Err::Failure(e) => Error::Nom(e.code),
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn salvage_index_1(path: PathBuf) -> Result<SalvagedIndex<IndexEntry1>, Error> {
+    salvage_index_1_data(&fs::read(path)?)
+}
+
+pub fn salvage_index_1_data(data: &[u8]) -> Result<SalvagedIndex<IndexEntry1>, Error> {
+    salvage_index(data, index_entry_1, collision_entry_1)
+}
+
+#[cfg(feature = "std")]
+pub fn salvage_index_2(path: PathBuf) -> Result<SalvagedIndex<IndexEntry2>, Error> {
+    salvage_index_2_data(&fs::read(path)?)
+}
+
+pub fn salvage_index_2_data(data: &[u8]) -> Result<SalvagedIndex<IndexEntry2>, Error> {
+    salvage_index(data, index_entry_2, collision_entry_2)
+}
+
+#[cfg(feature = "std")]
 pub fn load_index_1(path: PathBuf) -> Result<Index<IndexEntry1>, Error> {
     let file = File::open(path)?;
     let mut bufreader = GrowableBufReader::new(file);
     load_index_reader(&mut bufreader, index_entry_1, collision_entry_1)
 }
 
+#[cfg(feature = "std")]
 pub fn load_index_2(path: PathBuf) -> Result<Index<IndexEntry2>, Error> {
     let file = File::open(path)?;
     let mut bufreader = GrowableBufReader::new(file);
     load_index_reader(&mut bufreader, index_entry_2, collision_entry_2)
 }
 
-pub fn decompress_file(mut file: &mut File, data_entry_offset: u32) -> Result<Vec<u8>, Error> {
-    // Note that file decompression could be parallelized by splitting different blocks across
-    // threads. This is probably why the file format has multiple blocks per entry.
+/// Loads an index from its contents in memory. Unlike [`salvage_index`], every segment must be
+/// complete.
+fn parse_index<
+    I: IndexEntry,
+    EP: Fn(&[u8]) -> IResult<&[u8], I>,
+    CP: Fn(&[u8]) -> IResult<&[u8], CollisionEntry<I::Hash>>,
+>(
+    data: &[u8],
+    entry_parser: impl Fn(Endianness) -> EP,
+    collision_parser: impl Fn(Endianness) -> CP,
+) -> Result<Index<I>, Error> {
+    let (endianness, dat_file_count, segment_headers) = index_headers(data)?;
+
+    let index_entries = parse_segment(
+        data,
+        &segment_headers[0],
+        I::SIZE,
+        segment_headers[0].size / I::SIZE,
+        entry_parser(endianness),
+    )?;
+    let collision_entries = parse_segment(
+        data,
+        &segment_headers[1],
+        256,
+        (segment_headers[1].size / 256).saturating_sub(1),
+        collision_parser(endianness),
+    )?;
+    let tombstone_entries = parse_segment(
+        data,
+        &segment_headers[2],
+        16,
+        segment_headers[2].size / 16,
+        tombstone_entry_parser(endianness),
+    )?;
+    let folder_entries = parse_segment(
+        data,
+        &segment_headers[3],
+        16,
+        segment_headers[3].size / 16,
+        folder_entry(endianness),
+    )?;
+
+    Ok(Index::new(
+        index_entries,
+        collision_entries,
+        tombstone_entries,
+        folder_entries,
+        dat_file_count,
+    ))
+}
+
+/// Loads an index from its contents, rather than from a file.
+pub fn parse_index_1(data: &[u8]) -> Result<Index<IndexEntry1>, Error> {
+    parse_index(data, index_entry_1, collision_entry_1)
+}
+
+/// Loads an `.index2` file from its contents, rather than from a file.
+pub fn parse_index_2(data: &[u8]) -> Result<Index<IndexEntry2>, Error> {
+    parse_index(data, index_entry_2, collision_entry_2)
+}
+
+/// Parses the headers of the data entry at the given offset, from the contents of a whole data
+/// file built for the given platform.
+pub fn parse_data_entry_headers(
+    data: &[u8],
+    data_entry_offset: u32,
+    platform_id: PlatformId,
+) -> Result<DataBlocks, Error> {
+    let input = usize::try_from(data_entry_offset)
+        .ok()
+        .and_then(|offset| data.get(offset..))
+        .ok_or(Error::Nom(ErrorKind::Eof))?;
+    let (_, blocks) = data_entry_headers(platform_id.endianness(), data_entry_offset)(input)
+        .map_err(nom_error_kind)?;
+    Ok(blocks)
+}
+
+/// Loads an index, leaving its first segment in a memory-mapped file. Entries in the first segment
+/// are parsed as they are looked up, while the other segments are small, and are parsed up front.
+#[cfg(feature = "std")]
+fn map_index<I: IndexEntry, CP: Fn(&[u8]) -> IResult<&[u8], CollisionEntry<I::Hash>>>(
+    path: PathBuf,
+    mapped_entry: fn(&[u8], Endianness) -> I,
+    collision_parser: impl Fn(Endianness) -> CP,
+) -> Result<Index<I>, Error> {
+    let file = File::open(path)?;
+    // Safety: the mapping is only sound as long as the file isn't truncated or modified while it
+    // is mapped. This is documented on `GameDataBuilder::memory_map_indexes`.
+    let map = unsafe { Mmap::map(&file)? };
+    let data = &map[..];
+    let (endianness, dat_file_count, segment_headers) = index_headers(data)?;
+
+    let table_start = usize::try_from(segment_headers[0].offset).unwrap();
+    let entry_count = usize::try_from(segment_headers[0].size / I::SIZE).unwrap();
+    let table_end = table_start + entry_count * usize::try_from(I::SIZE).unwrap();
+    if table_end > data.len() {
+        return Err(Error::Nom(ErrorKind::Eof));
+    }
+    let collision_entries = parse_segment(
+        data,
+        &segment_headers[1],
+        256,
+        (segment_headers[1].size / 256).saturating_sub(1),
+        collision_parser(endianness),
+    )?;
+    let tombstone_entries = parse_segment(
+        data,
+        &segment_headers[2],
+        16,
+        segment_headers[2].size / 16,
+        tombstone_entry_parser(endianness),
+    )?;
+    let folder_entries = parse_segment(
+        data,
+        &segment_headers[3],
+        16,
+        segment_headers[3].size / 16,
+        folder_entry(endianness),
+    )?;
+
+    Ok(Index::new_mapped(
+        map,
+        table_start..table_end,
+        mapped_entry,
+        endianness,
+        collision_entries,
+        tombstone_entries,
+        folder_entries,
+        dat_file_count,
+    ))
+}
+
+/// Parses every record of an index segment from a file in memory.
+fn parse_segment<O>(
+    data: &[u8],
+    segment_header: &IndexSegmentHeader,
+    record_size: u32,
+    record_count: u32,
+    parser: impl Fn(&[u8]) -> IResult<&[u8], O>,
+) -> Result<Vec<O>, Error> {
+    let mut records = Vec::with_capacity(record_count.try_into().unwrap());
+    for i in 0..record_count {
+        let offset = u64::from(segment_header.offset) + u64::from(i) * u64::from(record_size);
+        let input = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| data.get(offset..))
+            .ok_or(Error::Nom(ErrorKind::Eof))?;
+        let (_, record) = parser(input).map_err(nom_error_kind)?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "std")]
+pub fn map_index_1(path: PathBuf) -> Result<Index<IndexEntry1>, Error> {
+    map_index(path, mapped_entry_1, collision_entry_1)
+}
+
+#[cfg(feature = "std")]
+pub fn map_index_2(path: PathBuf) -> Result<Index<IndexEntry2>, Error> {
+    map_index(path, mapped_entry_2, collision_entry_2)
+}
+
+#[cfg(feature = "std")]
+pub fn decompress_file<R: Read + Seek>(
+    file: &mut R,
+    data_entry_offset: u32,
+    endianness: Endianness,
+) -> Result<Vec<u8>, Error> {
+    let blocks = read_data_entry_headers(file, data_entry_offset, endianness)?;
+    decompress_blocks(file, &blocks, endianness)
+}
+
+/// Reads and parses the headers of the data entry at the given offset. `endianness` is the byte
+/// order of the platform the data file was built for.
+#[cfg(feature = "std")]
+pub fn read_data_entry_headers<R: Read + Seek>(
+    file: &mut R,
+    data_entry_offset: u32,
+    endianness: Endianness,
+) -> Result<DataBlocks, Error> {
+    file.seek(SeekFrom::Start(data_entry_offset.into()))?;
+    drive_streaming_parser_smaller(
+        &mut *file,
+        data_entry_headers(endianness, data_entry_offset),
+    )
+}
+
+/// Reads the uncompressed size of the data entry at the given offset, from its headers.
+#[cfg(feature = "std")]
+pub fn read_data_entry_size<R: Read + Seek>(
+    file: &mut R,
+    data_entry_offset: u32,
+    endianness: Endianness,
+) -> Result<u32, Error> {
     file.seek(SeekFrom::Start(data_entry_offset.into()))?;
-    let blocks = drive_streaming_parser_smaller(&mut *file, data_entry_headers(data_entry_offset))?;
+    let (_header_length, header_common) =
+        drive_streaming_parser_smaller(&mut *file, data_entry_header_common(endianness))?;
+    Ok(header_common.uncompressed_size)
+}
+
+/// Size of the header of a decompressed model file.
+#[cfg(feature = "std")]
+const MODEL_HEADER_SIZE: usize = 0x44;
+
+/// Reads and decompresses each block of a data entry, given its already-parsed headers.
+#[cfg(feature = "std")]
+pub fn decompress_blocks<R: Read + Seek>(
+    file: &mut R,
+    blocks: &DataBlocks,
+    endianness: Endianness,
+) -> Result<Vec<u8>, Error> {
     let mut compressed = Vec::new();
+    decompress_blocks_with(file, blocks, |file, block_offsets, decompressed| {
+        for block_offset in block_offsets {
+            decompress_block(
+                file,
+                block_offset,
+                endianness,
+                &mut compressed,
+                decompressed,
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Decompresses the blocks of an entry using a thread pool. Blocks are read one after another,
+/// and then decompressed in parallel, so this needs enough memory to hold every compressed block
+/// at once.
+#[cfg(all(feature = "std", feature = "rayon"))]
+pub fn decompress_blocks_parallel<R: Read + Seek>(
+    file: &mut R,
+    blocks: &DataBlocks,
+    endianness: Endianness,
+) -> Result<Vec<u8>, Error> {
+    use rayon::prelude::*;
+
+    decompress_blocks_with(file, blocks, |file, block_offsets, decompressed| {
+        let mut stored_blocks = Vec::new();
+        for block_offset in block_offsets {
+            file.seek(SeekFrom::Start(block_offset.into()))?;
+            let (compressed_length, decompressed_length) =
+                drive_streaming_parser_smaller(&mut *file, block_header(endianness))?;
+            let mut contents = Vec::new();
+            if compressed_length == 32000 {
+                (&mut *file)
+                    .take(decompressed_length.into())
+                    .read_to_end(&mut contents)?;
+                stored_blocks.push((None, contents));
+            } else {
+                (&mut *file)
+                    .take(compressed_length.into())
+                    .read_to_end(&mut contents)?;
+                stored_blocks.push((Some(decompressed_length), contents));
+            }
+        }
+        let block_contents = stored_blocks
+            .into_par_iter()
+            .map(
+                |(decompressed_length, contents)| match decompressed_length {
+                    Some(length) => decompress_sqpack_block(&contents, length.try_into().unwrap()),
+                    None => Ok(contents),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        for contents in block_contents {
+            decompressed.extend_from_slice(&contents);
+        }
+        Ok(())
+    })
+}
+
+/// Assembles the contents of an entry. `decompress_run` is called with consecutive runs of
+/// block offsets, and appends their decompressed contents.
+#[cfg(feature = "std")]
+fn decompress_blocks_with<R, F>(
+    file: &mut R,
+    blocks: &DataBlocks,
+    mut decompress_run: F,
+) -> Result<Vec<u8>, Error>
+where
+    R: Read + Seek,
+    F: FnMut(&mut R, &mut dyn Iterator<Item = u32>, &mut Vec<u8>) -> Result<(), Error>,
+{
     let mut decompressed = Vec::new();
-    for block_offset in blocks.all_blocks() {
-        file.seek(SeekFrom::Start(block_offset.into()))?;
-        let (compressed_length, decompressed_length) =
-            drive_streaming_parser_smaller(&mut *file, block_header)?;
-        if compressed_length == 32000 {
-            let mut take = file.take(decompressed_length.try_into().unwrap());
-            take.read_to_end(&mut decompressed)?;
-            file = take.into_inner();
-        } else {
-            let mut take = file.take(compressed_length.try_into().unwrap());
-            take.read_to_end(&mut compressed)?;
-            let block_decompressed =
-                decompress_sqpack_block(&compressed, decompressed_length.try_into().unwrap())?;
-            decompressed.extend_from_slice(&block_decompressed);
-            compressed.clear();
-            file = take.into_inner();
+    match blocks {
+        DataBlocks::Texture {
+            base_position,
+            header_size,
+            ..
+        } => {
+            file.seek(SeekFrom::Start((*base_position).into()))?;
+            (&mut *file)
+                .take((*header_size).into())
+                .read_to_end(&mut decompressed)?;
+        }
+        DataBlocks::Model {
+            header,
+            section_block_counts,
+            ..
+        } => {
+            // Leave room for the model file header, which is filled in once the size of each
+            // section is known.
+            decompressed.resize(MODEL_HEADER_SIZE, 0);
+            let mut block_offsets = blocks.all_blocks();
+            let mut sections = [(0, 0); 11];
+            for (block_count, section) in section_block_counts.iter().zip(sections.iter_mut()) {
+                let start = decompressed.len();
+                decompress_run(
+                    file,
+                    &mut block_offsets.by_ref().take((*block_count).into()),
+                    &mut decompressed,
+                )?;
+                *section = (start, decompressed.len() - start);
+            }
+            decompressed[..MODEL_HEADER_SIZE]
+                .copy_from_slice(&model_file_header(header, &sections));
+            return Ok(decompressed);
         }
+        _ => {}
     }
+    decompress_run(file, &mut blocks.all_blocks(), &mut decompressed)?;
     Ok(decompressed)
 }
 
+/// Reads and decompresses one block, appending its contents to `decompressed`. `compressed` is
+/// used as a buffer.
+#[cfg(feature = "std")]
+pub(crate) fn decompress_block<R: Read + Seek>(
+    file: &mut R,
+    block_offset: u32,
+    endianness: Endianness,
+    compressed: &mut Vec<u8>,
+    decompressed: &mut Vec<u8>,
+) -> Result<(), Error> {
+    file.seek(SeekFrom::Start(block_offset.into()))?;
+    let (compressed_length, decompressed_length) =
+        drive_streaming_parser_smaller(&mut *file, block_header(endianness))?;
+    if compressed_length == 32000 {
+        (&mut *file)
+            .take(decompressed_length.into())
+            .read_to_end(decompressed)?;
+    } else {
+        compressed.clear();
+        (&mut *file)
+            .take(compressed_length.into())
+            .read_to_end(compressed)?;
+        let block_decompressed =
+            decompress_sqpack_block(compressed, decompressed_length.try_into().unwrap())?;
+        decompressed.extend_from_slice(&block_decompressed);
+    }
+    Ok(())
+}
+
+/// Encodes the header of a model file, given the position and size of each section of the
+/// decompressed file, in the order of [`MODEL_SECTION_ORDER`].
+#[cfg(feature = "std")]
+fn model_file_header(
+    header: &ModelHeader,
+    sections: &[(usize, usize); 11],
+) -> [u8; MODEL_HEADER_SIZE] {
+    let u32_field = |value: usize| u32::try_from(value).unwrap().to_le_bytes();
+    // Each level of detail has a vertex buffer, edge geometry, and an index buffer.
+    let lods = &sections[2..];
+    let mut output = Vec::with_capacity(MODEL_HEADER_SIZE);
+    output.extend_from_slice(&header.version.to_le_bytes());
+    output.extend_from_slice(&u32_field(sections[0].1));
+    output.extend_from_slice(&u32_field(sections[1].1));
+    output.extend_from_slice(&header.vertex_declaration_count.to_le_bytes());
+    output.extend_from_slice(&header.material_count.to_le_bytes());
+    for lod in lods.chunks(3) {
+        output.extend_from_slice(&u32_field(lod[0].0));
+    }
+    for lod in lods.chunks(3) {
+        output.extend_from_slice(&u32_field(lod[2].0));
+    }
+    for lod in lods.chunks(3) {
+        output.extend_from_slice(&u32_field(lod[0].1));
+    }
+    for lod in lods.chunks(3) {
+        output.extend_from_slice(&u32_field(lod[2].1));
+    }
+    output.push(header.lod_count);
+    output.push(header.index_buffer_streaming.into());
+    output.push(header.edge_geometry.into());
+    output.push(0);
+    output.try_into().unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use std::num::NonZeroUsize;
 
     use nom::{
         error::{ErrorKind, ParseError},
+        number::Endianness,
         Err, Needed,
     };
 
-    use super::{sqpack_header_inner, sqpack_header_outer};
-    use crate::{IndexPointer, PlatformId, SqPackType};
+    use super::{data_entry_headers, sqpack_header, sqpack_header_inner};
+    use crate::{BlockInfo, DataBlocks, IndexPointer, PlatformId, SqPackHeader, SqPackType};
 
     #[test]
     fn test_null_padding() {
@@ -799,7 +1668,14 @@ mod tests {
 
     #[test]
     fn test_sqpack_type() {
-        use super::sqpack_type;
+        use nom::number::Endianness;
+        assert_eq!(
+            super::sqpack_type(Endianness::Big)(&[0, 0, 0, SqPackType::Index as u8][..])
+                .unwrap()
+                .1,
+            SqPackType::Index
+        );
+        let sqpack_type = super::sqpack_type(Endianness::Little);
         assert_eq!(
             sqpack_type(&[SqPackType::Sqdb as u8, 0, 0, 0][..])
                 .unwrap()
@@ -964,21 +1840,327 @@ mod tests {
             \x00\x00\x00\x00\x00\x00\x00\x00\
             \x00\x00\x00\x00\
         ";
+        let expected = SqPackHeader {
+            platform_id: PlatformId::Win32,
+            size: 1024,
+            version: 1,
+            sqpack_type: SqPackType::Index,
+            packed_date: 0,
+            packed_time: 0,
+        };
+        assert_eq!(sqpack_header_inner(data).unwrap().1, expected);
+        assert_eq!(sqpack_header(data).unwrap(), (&b""[..], expected));
+        assert_eq!(crate::parse_sqpack_header(data).unwrap(), expected);
+        #[allow(deprecated)]
+        let tuple = crate::sqpack_header_outer(data).unwrap().1;
+        assert_eq!(tuple, (PlatformId::Win32, 1024, 1, SqPackType::Index));
+    }
+
+    #[test]
+    fn test_texture_entry_headers() {
+        let mut data = Vec::new();
+        for field in [128u32, 4, 0x2000, 0, 3] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        for frame in [
+            [80u32, 0x180, 0x1800, 0, 2],
+            [80 + 0x180, 0x80, 0x800, 2, 1],
+        ] {
+            for field in frame {
+                data.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        for block_size in [0x100u16, 0x80, 0x80] {
+            data.extend_from_slice(&block_size.to_le_bytes());
+        }
+        data.resize(128, 0);
+
+        let (_, blocks) = data_entry_headers(Endianness::Little, 0x1000)(&data).unwrap();
+        assert!(matches!(
+            &blocks,
+            DataBlocks::Texture {
+                base_position: 0x1080,
+                header_size: 80,
+                blocks,
+            } if blocks == &[(80, 0x100), (0x150, 0x80), (0x1d0, 0x80)]
+        ));
         assert_eq!(
-            sqpack_header_inner(data).unwrap().1,
-            (PlatformId::Win32, 1024, 1, SqPackType::Index)
+            blocks.blocks().collect::<Vec<_>>(),
+            [
+                BlockInfo {
+                    offset: 0x10d0,
+                    compressed_size: 0x100,
+                    decompressed_size: None,
+                },
+                BlockInfo {
+                    offset: 0x11d0,
+                    compressed_size: 0x80,
+                    decompressed_size: None,
+                },
+                BlockInfo {
+                    offset: 0x1250,
+                    compressed_size: 0x80,
+                    decompressed_size: None,
+                },
+            ]
         );
+        assert_eq!(blocks.total_compressed_size(), 0x200);
+        assert_eq!(blocks.total_decompressed_size(), None);
+
+        // The same headers, found at their offset within a whole data file.
+        let mut file = vec![0; 0x1000];
+        file.extend_from_slice(&data);
         assert_eq!(
-            sqpack_header_outer(data).unwrap(),
-            (&b""[..], (PlatformId::Win32, 1024, 1, SqPackType::Index))
+            super::parse_data_entry_headers(&file, 0x1000, PlatformId::Win32)
+                .unwrap()
+                .blocks()
+                .collect::<Vec<_>>(),
+            blocks.blocks().collect::<Vec<_>>()
+        );
+        assert!(
+            super::parse_data_entry_headers(&file[..0x1040], 0x1000, PlatformId::Win32).is_err()
         );
     }
 
+    /// Encodes a block, with its header, padded to a multiple of 128 bytes.
+    fn block(stored: &[u8], compressed_length: u32, decompressed_length: u32) -> Vec<u8> {
+        let mut block = Vec::new();
+        for field in [16, 0, compressed_length, decompressed_length] {
+            block.extend_from_slice(&field.to_le_bytes());
+        }
+        block.extend_from_slice(stored);
+        block.resize(block.len().div_ceil(128) * 128, 0);
+        block
+    }
+
+    #[test]
+    fn test_texture_payload() {
+        let texture_header = [0xaau8; 80];
+        let mip_0 = [1u8; 0x400];
+        let mip_1 = [2u8; 0x100];
+        let compressed = crate::compression::compress_sqpack_block(&mip_0).unwrap();
+        let blocks = [
+            block(&compressed, compressed.len() as u32, 0x400),
+            block(&mip_1, 32000, 0x100),
+        ];
+
+        let mut data = Vec::new();
+        for field in [128u32, 4, 0x550, 0, 0] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        let mut frame_offset = 80;
+        for (index, block) in blocks.iter().enumerate() {
+            for field in [frame_offset, block.len() as u32, 0, index as u32, 1] {
+                data.extend_from_slice(&field.to_le_bytes());
+            }
+            frame_offset += block.len() as u32;
+        }
+        for block in blocks.iter() {
+            data.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        }
+        data.resize(128, 0);
+        data.extend_from_slice(&texture_header);
+        for block in blocks.iter() {
+            data.extend_from_slice(block);
+        }
+
+        let payload = crate::decompress_entry(&data).unwrap();
+        assert_eq!(payload.len(), 0x550);
+        assert_eq!(&payload[..80], &texture_header);
+        assert_eq!(&payload[80..0x450], &mip_0);
+        assert_eq!(&payload[0x450..], &mip_1);
+    }
+
+    #[test]
+    fn test_model_payload() {
+        let stack = [1u8; 0x20];
+        let vertices = [2u8; 0x100];
+        let indices = [3u8; 0x30];
+        let compressed = crate::compression::compress_sqpack_block(&vertices).unwrap();
+        let blocks = [
+            block(&stack, 32000, 0x20),
+            block(&compressed, compressed.len() as u32, 0x100),
+            block(&indices, 32000, 0x30),
+        ];
+
+        let mut data = Vec::new();
+        for field in [0x100u32, 3, 0x194, 3, 3, 5] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        // Decompressed and compressed sizes are not used.
+        data.resize(0x70, 0);
+        // Section offsets, block size indices, and block counts, for the stack, the vertex
+        // buffer of the first level of detail, and its index buffer.
+        let mut offsets = [0u32; 11];
+        offsets[2] = blocks[0].len() as u32;
+        offsets[8] = (blocks[0].len() + blocks[1].len()) as u32;
+        for offset in offsets {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        for index in [0u16, 1, 1, 2, 2, 2, 2, 2, 2, 3, 3] {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        for count in [1u16, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0] {
+            data.extend_from_slice(&count.to_le_bytes());
+        }
+        for field in [4u16, 2] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&[1, 0, 1, 0]);
+        for block in blocks.iter() {
+            data.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        }
+        data.resize(0x100, 0);
+        for block in blocks.iter() {
+            data.extend_from_slice(block);
+        }
+
+        let payload = crate::decompress_entry(&data).unwrap();
+        assert_eq!(payload.len(), 0x194);
+        let field =
+            |offset: usize| u32::from_le_bytes(payload[offset..offset + 4].try_into().unwrap());
+        // Version, stack size, and runtime size.
+        assert_eq!([field(0), field(4), field(8)], [5, 0x20, 0]);
+        // Vertex buffer offsets, index buffer offsets, vertex buffer sizes, and index buffer
+        // sizes.
+        assert_eq!(
+            (0x10..0x40).step_by(4).map(field).collect::<Vec<_>>(),
+            [0x64, 0x194, 0x194, 0x164, 0x194, 0x194, 0x100, 0, 0, 0x30, 0, 0]
+        );
+        assert_eq!(&payload[0x40..0x44], &[1, 0, 1, 0]);
+        assert_eq!(&payload[0x44..0x64], &stack);
+        assert_eq!(&payload[0x64..0x164], &vertices);
+        assert_eq!(&payload[0x164..], &indices);
+    }
+
     #[test]
     fn test_pointer_roundtrip() {
         let pointer = IndexPointer::from_u32(0x260);
         assert_eq!(pointer.to_u32(), 0x260);
     }
+
+    /// Pads a header that starts at `start` and appends its hash.
+    fn finish_header(data: &mut Vec<u8>, start: usize) {
+        data.resize(start + 0x3c0, 0);
+        let hash = crate::sha1(&data[start..]);
+        data.extend_from_slice(&hash);
+        data.resize(start + 0x400, 0);
+    }
+
+    /// Builds a big-endian index file for the PS3, with the given segments.
+    fn ps3_index(segments: [&[u8]; 4]) -> Vec<u8> {
+        let mut data = b"SqPack\x00\x00\x01\x00\x00\x00".to_vec();
+        for field in [0x400u32, 1, 2, 0, 0, 0xffffffff] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        finish_header(&mut data, 0);
+
+        data.extend_from_slice(&0x400u32.to_be_bytes());
+        data.extend_from_slice(&1u32.to_be_bytes());
+        let mut offset = 0x800;
+        for (i, segment) in segments.iter().enumerate() {
+            if i == 1 {
+                // Number of data files.
+                data.extend_from_slice(&1u32.to_be_bytes());
+            }
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+            data.extend_from_slice(&crate::sha1(segment));
+            if i < 3 {
+                data.resize(data.len() + 44, 0);
+            }
+            offset += segment.len();
+        }
+        finish_header(&mut data, 0x400);
+
+        for segment in segments {
+            data.extend_from_slice(segment);
+        }
+        data
+    }
+
+    #[test]
+    fn test_ps3_index() {
+        use crate::{FilePointer, FolderEntry, IndexEntry, IndexHash1, ZeroEntry};
+
+        let be_fields = |fields: &[u32]| -> Vec<u8> {
+            fields
+                .iter()
+                .flat_map(|field| field.to_be_bytes())
+                .collect()
+        };
+        let pointer = IndexPointer::Pointer(FilePointer::new(0, 0x80)).to_u32();
+        let entries = be_fields(&[0x1111, 0xaaaa, pointer, 0, 0x2222, 0xaaaa, pointer, 0]);
+        let collisions = [0; 256];
+        let tombstones = be_fields(&[0, 0x100 >> 7, 3, 0]);
+        let folders = be_fields(&[0xaaaa, 0x800, 32, 0]);
+        let data = ps3_index([&entries, &collisions, &tombstones, &folders]);
+
+        let (_, header) = sqpack_header(&data).unwrap();
+        assert_eq!(header.platform_id, PlatformId::Ps3);
+        assert_eq!(header.size, 0x400);
+        assert_eq!(header.sqpack_type, SqPackType::Index);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0a0000.ps3.index");
+        std::fs::write(&path, &data).unwrap();
+        let loaded = super::load_index_1(path.clone()).unwrap();
+        let mapped = super::map_index_1(path.clone()).unwrap();
+        let salvaged = super::salvage_index_1(path).unwrap();
+        assert_eq!(salvaged.truncated_at, None);
+        let parsed = super::parse_index_1(&data).unwrap();
+        for index in [&loaded, &mapped, &salvaged.index, &parsed] {
+            assert_eq!(index.dat_file_count(), 1);
+            let hashes = index.iter().map(|(hash, _)| hash).collect::<Vec<_>>();
+            assert_eq!(
+                hashes,
+                [
+                    IndexHash1::new(0xaaaa, 0x1111),
+                    IndexHash1::new(0xaaaa, 0x2222)
+                ]
+            );
+            let entry = index.get(&IndexHash1::new(0xaaaa, 0x2222)).unwrap();
+            assert_eq!(entry.hash(), IndexHash1::new(0xaaaa, 0x2222));
+            assert_eq!(
+                index.get_pointers(&IndexHash1::new(0xaaaa, 0x2222)),
+                [FilePointer::new(0, 0x80)]
+            );
+            assert_eq!(
+                index.tombstone_table(),
+                [ZeroEntry::new(FilePointer::new(0, 0x100), 2)]
+            );
+            assert_eq!(
+                index.folder_table(),
+                [FolderEntry {
+                    folder_crc: 0xaaaa,
+                    files_offset: 0x800,
+                    files_span: 32,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_ps3_data_header() {
+        let mut data = b"SqPack\x00\x00\x01\x00\x00\x00".to_vec();
+        for field in [0x400u32, 1, 1, 0, 0, 0xffffffff] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        finish_header(&mut data, 0);
+        for field in [0x400u32, 0, 16, 0x300 >> 7, 2, 0, 2000000000] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        finish_header(&mut data, 0x400);
+
+        let header = super::read_data_header(&mut std::io::Cursor::new(data)).unwrap();
+        assert_eq!(header.data_size, 0x300);
+        assert_eq!(header.spanned_dat, 2);
+        assert_eq!(header.max_file_size, 2000000000);
+    }
 }
 
 #[cfg(test)]
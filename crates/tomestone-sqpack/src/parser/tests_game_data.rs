@@ -20,7 +20,7 @@ fn forall_sqpack(f: impl Fn(PathBuf, GrowableBufReader<File>) + UnwindSafe + Ref
     };
     let sqpack_dir = PathBuf::from(root).join("game").join("sqpack");
     for expansion in Expansion::iter_all() {
-        let path = sqpack_dir.join(expansion.name());
+        let path = sqpack_dir.join(&*expansion.name());
         if !path.is_dir() {
             continue;
         }
@@ -5,7 +5,7 @@ use r2d2::PooledConnection;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{params, Statement};
 
-use crate::{IndexHash, IndexHash1, IndexHash2};
+use crate::{normalize_path, IndexHash, IndexHash1, IndexHash2};
 
 const FILENAME: &str = "paths.db";
 
@@ -118,6 +118,8 @@ impl PathDb {
             connection.prepare("SELECT path FROM index_2_path WHERE crc = ?")?;
         let index_2_insert_stmt =
             connection.prepare("INSERT OR IGNORE INTO index_2_path (crc, path) VALUES (?, ?)")?;
+        let index_2_folder_stmt = connection
+            .prepare("SELECT path FROM index_2_path WHERE substr(path, 1, length(?1)) = ?1")?;
         Ok(PreparedStatements {
             index_1_folder_lookup_stmt,
             index_1_folder_insert_stmt,
@@ -125,6 +127,7 @@ impl PathDb {
             index_1_filename_insert_stmt,
             index_2_lookup_stmt,
             index_2_insert_stmt,
+            index_2_folder_stmt,
         })
     }
 }
@@ -136,6 +139,7 @@ pub struct PreparedStatements<'a> {
     index_1_filename_insert_stmt: Statement<'a>,
     index_2_lookup_stmt: Statement<'a>,
     index_2_insert_stmt: Statement<'a>,
+    index_2_folder_stmt: Statement<'a>,
 }
 
 impl<'a> PreparedStatements<'a> {
@@ -165,16 +169,16 @@ impl<'a> PreparedStatements<'a> {
         {
             let hash = IndexHash2::hash(path);
             self.index_2_insert_stmt
-                .execute(params![hash.path_crc, path.to_lowercase()])?;
+                .execute(params![hash.path_crc, normalize_path(path)])?;
         }
         Ok(())
     }
 
     pub fn add_folder(&mut self, path: &str) -> Result<(), DbError> {
-        let lowercase = path.to_lowercase();
-        let crc = crate::crc32(lowercase.as_bytes());
+        let folder = normalize_path(path);
+        let crc = crate::crc32(folder.as_bytes());
         self.index_1_folder_insert_stmt
-            .execute(params![crc, lowercase])?;
+            .execute(params![crc, folder])?;
         Ok(())
     }
 
@@ -184,4 +188,34 @@ impl<'a> PreparedStatements<'a> {
             .query_map([hash.path_crc], |row| row.get::<_, String>(0))?
             .collect::<Result<Vec<String>, rusqlite::Error>>()?)
     }
+
+    /// Looks up the full path of a file from its `.index2` hash, or its `.index` hash if that
+    /// doesn't find it. Returns `None` unless exactly one path is known.
+    pub fn resolve_path(
+        &mut self,
+        hash_1: Option<IndexHash1>,
+        hash_2: Option<IndexHash2>,
+    ) -> Result<Option<String>, DbError> {
+        if let Some(hash) = hash_2 {
+            if let [path] = &self.index_2_lookup(hash)?[..] {
+                return Ok(Some(path.clone()));
+            }
+        }
+        if let Some(hash) = hash_1 {
+            let (folders, filenames) = self.index_1_lookup(hash)?;
+            if let ([folder], [filename]) = (&folders[..], &filenames[..]) {
+                return Ok(Some(format!("{}/{}", folder, filename)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns all known paths within a folder, including those in subfolders.
+    pub fn paths_in_folder(&mut self, folder: &str) -> Result<Vec<String>, DbError> {
+        let prefix = format!("{}/", normalize_path(folder).trim_end_matches('/'));
+        Ok(self
+            .index_2_folder_stmt
+            .query_map([prefix], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?)
+    }
 }
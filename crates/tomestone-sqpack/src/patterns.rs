@@ -0,0 +1,181 @@
+//! Pattern sets select which files to extract, and are usually read from a
+//! `.tomestone-patterns` file.
+//!
+//! The syntax is a subset of `.gitignore`. Each line holds one pattern. Blank lines and lines
+//! starting with `#` are ignored. In a pattern:
+//!
+//! - `*` matches within one folder, `?` matches one character other than `/`, and `**` matches
+//!   any number of folders.
+//! - A trailing `/` matches everything in a folder.
+//! - A leading `!` excludes files that earlier patterns matched.
+//!
+//! Character classes such as `[abc]` and backslash escapes aren't supported, and these characters
+//! only match themselves. Unlike `.gitignore`, patterns always match whole paths, starting from the
+//! category folder.
+//! When several patterns match a file, the last one wins. Paths are compared without regard to
+//! case. Files whose paths aren't known can be selected by hash. Use `<xxxxxxxx>` for a path CRC
+//! from an `.index2` file, or `<xxxxxxxx>/<xxxxxxxx>` for the folder and filename CRCs from an
+//! `.index` file.
+
+use std::{fs, io, path::Path};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{IndexHash1, IndexHash2};
+
+/// The usual name of a pattern set file.
+pub const FILE_NAME: &str = ".tomestone-patterns";
+
+enum Matcher {
+    Path(Regex),
+    Hash1(IndexHash1),
+    Hash2(IndexHash2),
+}
+
+struct Rule {
+    negated: bool,
+    matcher: Matcher,
+}
+
+pub struct PatternSet {
+    rules: Vec<Rule>,
+}
+
+impl PatternSet {
+    pub fn parse(text: &str) -> PatternSet {
+        static HASH_1_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new("^<([0-9A-Fa-f]{8})>/<([0-9A-Fa-f]{8})>$").unwrap());
+        static HASH_2_RE: Lazy<Regex> = Lazy::new(|| Regex::new("^<([0-9A-Fa-f]{8})>$").unwrap());
+
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, line),
+            };
+            let crc = |digits: &str| u32::from_str_radix(digits, 16).unwrap();
+            let matcher = if let Some(captures) = HASH_1_RE.captures(pattern) {
+                Matcher::Hash1(IndexHash1::new(crc(&captures[1]), crc(&captures[2])))
+            } else if let Some(captures) = HASH_2_RE.captures(pattern) {
+                Matcher::Hash2(IndexHash2::new(crc(&captures[1])))
+            } else {
+                Matcher::Path(glob_regex(pattern))
+            };
+            rules.push(Rule { negated, matcher });
+        }
+        PatternSet { rules }
+    }
+
+    pub fn load(path: &Path) -> io::Result<PatternSet> {
+        Ok(PatternSet::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Checks whether a file is selected, given its path, if known, and its hashes.
+    pub fn matches(
+        &self,
+        path: Option<&str>,
+        hash_1: Option<IndexHash1>,
+        hash_2: Option<IndexHash2>,
+    ) -> bool {
+        let mut selected = false;
+        for rule in self.rules.iter() {
+            let matched = match &rule.matcher {
+                Matcher::Path(regex) => path.is_some_and(|path| regex.is_match(path)),
+                Matcher::Hash1(hash) => hash_1 == Some(*hash),
+                Matcher::Hash2(hash) => hash_2 == Some(*hash),
+            };
+            if matched {
+                selected = !rule.negated;
+            }
+        }
+        selected
+    }
+}
+
+/// Translates a glob pattern into a regular expression matching whole paths.
+fn glob_regex(pattern: &str) -> Regex {
+    let (pattern, folder) = match pattern.strip_suffix('/') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut regex = String::from("(?i)^");
+    let mut rest = pattern.trim_start_matches('/');
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("**/") {
+            regex.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            regex.push_str(".*");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('*') {
+            regex.push_str("[^/]*");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('?') {
+            regex.push_str("[^/]");
+            rest = after;
+        } else {
+            let c = rest.chars().next().unwrap();
+            regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if folder {
+        regex.push_str("/.*");
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatternSet;
+    use crate::{IndexHash, IndexHash1, IndexHash2};
+
+    #[test]
+    fn pattern_set() {
+        let patterns = PatternSet::parse(
+            "# All sheets, and icons\n\
+            exd/\n\
+            ui/icon/**/*.tex\n\
+            \n\
+            !exd/cut_scene/\n\
+            !ui/icon/*/*_hr1.tex\n\
+            <0badf00d>\n\
+            <00000001>/<00000002>\n",
+        );
+        let matches = |path: &str| {
+            patterns.matches(
+                Some(path),
+                Some(IndexHash1::hash(path)),
+                Some(IndexHash2::hash(path)),
+            )
+        };
+        assert!(matches("exd/root.exl"));
+        assert!(matches("EXD/Item.exh"));
+        assert!(matches("exd/quest/000/quest_0001.exh"));
+        assert!(!matches("exd/cut_scene/000/voiceman_00000.exh"));
+        assert!(matches("ui/icon/000000/000001.tex"));
+        assert!(!matches("ui/icon/000000/000001_hr1.tex"));
+        assert!(!matches("ui/icon/000000/000001.atex"));
+        assert!(!matches(
+            "bg/ffxiv/fst_f1/twn/common/texture/f1t0_a0_wall1_d.tex"
+        ));
+        assert!(!matches("exdx/root.exl"));
+
+        assert!(patterns.matches(None, None, Some(IndexHash2::new(0x0badf00d))));
+        assert!(patterns.matches(None, Some(IndexHash1::new(1, 2)), None));
+        assert!(!patterns.matches(None, Some(IndexHash1::new(2, 1)), None));
+
+        // Brackets and backslashes have no special meaning.
+        let patterns = PatternSet::parse("exd/[ab].exh\nui/\\*.tex\n");
+        assert!(patterns.matches(Some("exd/[ab].exh"), None, None));
+        assert!(!patterns.matches(Some("exd/a.exh"), None, None));
+        assert!(patterns.matches(Some("ui/\\icon.tex"), None, None));
+        assert!(!patterns.matches(Some("ui/*.tex"), None, None));
+    }
+}
@@ -11,7 +11,10 @@ use std::{
 
 use nom::{
     combinator::{map, map_opt},
-    number::streaming::{le_u16, le_u32},
+    number::{
+        streaming::{le_u16, le_u32},
+        Endianness,
+    },
     sequence::tuple,
     Finish, IResult,
 };
@@ -19,10 +22,10 @@ use nom::{
 use crate::{
     parser::{
         drive_streaming_parser, drive_streaming_parser_smaller, index_segment_headers,
-        sqpack_header_outer, type_2_block_table, DataContentType, GrowableBufReader,
+        sqpack_header, type_2_block_table, DataContentType, GrowableBufReader,
     },
-    DataFileSet, Error, FilePointer, GameData, IndexEntry1, IndexEntry2, IndexHash2, SqPackId,
-    ZeroEntry,
+    DataFile, DataFileSet, Error, FilePointer, GameData, IndexEntry1, IndexEntry2, IndexHash2,
+    SqPackDateTime, SqPackId, ZeroEntry,
 };
 
 /// This structure provides extra information, beyond the list of compressed files in each SqPack
@@ -117,7 +120,7 @@ fn data_entry_header_raw(input: &[u8]) -> IResult<&[u8], DataEntryHeaderRaw> {
 /// entry. It will read enough of the entry to determine its length, and seek to the end of it,
 /// rounded up to the next 128-byte boundary.
 fn skip_entry(
-    file: &mut File,
+    file: &mut DataFile,
     entry_offset: u64,
     entry_header_fields: &DataEntryHeaderRaw,
 ) -> Result<(), Error> {
@@ -130,7 +133,7 @@ fn skip_entry(
             ))?;
             let mut block_table_entry_buf = vec![0; 8];
             file.read_exact(&mut block_table_entry_buf)?;
-            let block_entry = type_2_block_table(1)(&block_table_entry_buf)
+            let block_entry = type_2_block_table(Endianness::Little, 1)(&block_table_entry_buf)
                 .finish()
                 .map_err(|e| e.code)?
                 .1[0];
@@ -163,7 +166,7 @@ fn skip_entry(
                     + u64::from(block_header_length)
                     + u64::from(compressed_length)
             };
-            let final_position = (last_block_end + 127) / 128 * 128;
+            let final_position = last_block_end.div_ceil(128) * 128;
             file.seek(SeekFrom::Start(final_position))?;
         }
         DataContentType::Model => todo!(),
@@ -200,10 +203,12 @@ pub fn build_side_tables(
         if let DataContentType::Binary = entry_header_fields.data_content_type {
             let mut block_table_buf = vec![0; entry_header_fields.number_of_blocks as usize * 8];
             file.read_exact(&mut block_table_buf).unwrap();
-            let block_table =
-                type_2_block_table(entry_header_fields.number_of_blocks)(&block_table_buf)
-                    .unwrap()
-                    .1;
+            let block_table = type_2_block_table(
+                Endianness::Little,
+                entry_header_fields.number_of_blocks,
+            )(&block_table_buf)
+            .unwrap()
+            .1;
             let block_compression = block_table
                 .iter()
                 .map(|(block_offset, _, _)| {
@@ -246,7 +251,7 @@ pub fn build_side_tables(
         let mut file = data_file_set.open(pack_id, dat_file_number).unwrap();
 
         // Save the original lengths of each data file.
-        let file_len = file.metadata().unwrap().len().try_into().unwrap();
+        let file_len = file.len().unwrap().try_into().unwrap();
         reserved_file_space[usize::from(dat_file_number)] = file_len;
 
         // Save the date and time fields from each data header.
@@ -256,20 +261,7 @@ pub fn build_side_tables(
         let packed_date = u32::from_le_bytes(buf[..4].try_into().unwrap());
         let packed_time = u32::from_le_bytes(buf[4..].try_into().unwrap());
         if packed_date != 0 || packed_time != 0 {
-            let year = packed_date / 10000;
-            assert!(year > 1900);
-            let month = (packed_date / 100) % 100;
-            assert!((1..=12).contains(&month));
-            let date = packed_date % 100;
-            assert!((1..=31).contains(&date));
-
-            let hour = packed_time / 1000000;
-            assert!(hour <= 24);
-            let minute = (packed_time / 10000) % 100;
-            assert!(minute <= 59);
-            let second = (packed_time / 100) % 100;
-            assert!(second <= 59);
-
+            assert!(SqPackDateTime::from_packed(packed_date, packed_time).is_some());
             sqpack_data_datetimes.insert(dat_file_number, (packed_date, packed_time));
         }
 
@@ -327,8 +319,12 @@ pub fn build_side_tables(
     // Check if index segment offset fields are nonzero.
     let file = File::open(game_data.build_index_path::<IndexEntry1>(pack_id)).unwrap();
     let mut bufreader = GrowableBufReader::new(file);
-    let file_header = drive_streaming_parser(&mut bufreader, sqpack_header_outer).unwrap();
-    let index_header = drive_streaming_parser(&mut bufreader, index_segment_headers).unwrap();
+    let file_header = drive_streaming_parser(&mut bufreader, sqpack_header).unwrap();
+    let index_header = drive_streaming_parser(
+        &mut bufreader,
+        index_segment_headers(file_header.platform_id.endianness()),
+    )
+    .unwrap();
     let segment_headers = index_header.2;
     let index_empty_segment_offset_present = [
         segment_headers[0].offset != 0,
@@ -339,8 +335,12 @@ pub fn build_side_tables(
 
     let file = File::open(game_data.build_index_path::<IndexEntry2>(pack_id)).unwrap();
     let mut bufreader = GrowableBufReader::new(file);
-    let file_header = drive_streaming_parser(&mut bufreader, sqpack_header_outer).unwrap();
-    let index_header = drive_streaming_parser(&mut bufreader, index_segment_headers).unwrap();
+    let file_header = drive_streaming_parser(&mut bufreader, sqpack_header).unwrap();
+    let index_header = drive_streaming_parser(
+        &mut bufreader,
+        index_segment_headers(file_header.platform_id.endianness()),
+    )
+    .unwrap();
     let segment_headers = index_header.2;
     let index2_empty_segment_offset_present = [
         segment_headers[0].offset != 0,
@@ -0,0 +1,116 @@
+//! Streaming reads of individual files, decompressing one block at a time.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use nom::number::Endianness;
+
+use crate::{
+    parser::{decompress_block, decompress_blocks},
+    DataBlocks, DataFile, Error,
+};
+
+/// A piece of a file's contents, in the order it is read.
+enum Chunk {
+    /// Data stored without compression, such as the header of a texture.
+    Raw { offset: u32, len: u32 },
+    /// A block, which may be compressed, at the given offset.
+    Block(u32),
+}
+
+/// Reads the contents of a file from a data file, returned by
+/// [`GameData::open_file`](crate::GameData::open_file) and
+/// [`DataFileSet::open_entry`](crate::DataFileSet::open_entry).
+///
+/// Blocks are decompressed as they are read, so at most one block is held in memory at a time.
+/// Model files are the exception, and are decompressed in full when opened, because their header
+/// depends on the size of every section.
+pub struct FileReader<'a> {
+    file: &'a mut DataFile,
+    chunks: std::vec::IntoIter<Chunk>,
+    /// Decompressed data that has not been read yet, starting at `position`.
+    buffer: Vec<u8>,
+    position: usize,
+    compressed: Vec<u8>,
+    /// Byte order of the block headers.
+    endianness: Endianness,
+}
+
+impl<'a> FileReader<'a> {
+    pub(crate) fn new(
+        file: &'a mut DataFile,
+        blocks: Arc<DataBlocks>,
+        endianness: Endianness,
+    ) -> Result<FileReader<'a>, Error> {
+        let mut chunks = Vec::new();
+        let mut buffer = Vec::new();
+        match &*blocks {
+            DataBlocks::Model { .. } => buffer = decompress_blocks(file, &blocks, endianness)?,
+            DataBlocks::Texture {
+                base_position,
+                header_size,
+                ..
+            } => {
+                chunks.push(Chunk::Raw {
+                    offset: *base_position,
+                    len: *header_size,
+                });
+                chunks.extend(blocks.all_blocks().map(Chunk::Block));
+            }
+            _ => chunks.extend(blocks.all_blocks().map(Chunk::Block)),
+        }
+        Ok(FileReader {
+            file,
+            chunks: chunks.into_iter(),
+            buffer,
+            position: 0,
+            compressed: Vec::new(),
+            endianness,
+        })
+    }
+
+    /// Reads the next chunk into the buffer. Returns false once there are no more chunks.
+    fn fill_buffer(&mut self) -> Result<bool, Error> {
+        self.buffer.clear();
+        self.position = 0;
+        match self.chunks.next() {
+            Some(Chunk::Raw { offset, len }) => {
+                self.file.seek(SeekFrom::Start(offset.into()))?;
+                (&mut *self.file)
+                    .take(len.into())
+                    .read_to_end(&mut self.buffer)?;
+            }
+            Some(Chunk::Block(offset)) => {
+                decompress_block(
+                    self.file,
+                    offset,
+                    self.endianness,
+                    &mut self.compressed,
+                    &mut self.buffer,
+                )?;
+            }
+            None => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            match self.fill_buffer() {
+                Ok(true) => {}
+                Ok(false) => return Ok(0),
+                Err(Error::Io(e)) => return Err(e),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            }
+        }
+        let remaining = &self.buffer[self.position..];
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
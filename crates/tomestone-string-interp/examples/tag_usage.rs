@@ -1,4 +1,4 @@
-use std::{process, usize};
+use std::process;
 
 use tomestone_exdf::{Dataset, Language, RootList, Value};
 use tomestone_sqpack::GameData;
@@ -31,6 +31,7 @@ struct TagUsageCounterVisitor {
     format: usize,
     two_digit_value: usize,
     tag_26: usize,
+    link: usize,
     sheet: usize,
     string_value: usize,
     string_value_sentence_case: usize,
@@ -86,6 +87,7 @@ impl Visitor for TagUsageCounterVisitor {
             Segment::TodoFormat(_, _) => self.format += 1,
             Segment::TwoDigitValue(_) => self.two_digit_value += 1,
             Segment::Todo26(_, _, _) => self.tag_26 += 1,
+            Segment::Link(_) => self.link += 1,
             Segment::Sheet { .. } => self.sheet += 1,
             Segment::StringValue(_) => self.string_value += 1,
             Segment::StringValueSentenceCase(_) => self.string_value_sentence_case += 1,
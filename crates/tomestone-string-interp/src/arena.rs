@@ -0,0 +1,599 @@
+//! Strings parsed into an arena, for processing many strings without allocating each segment and
+//! expression separately. See [`Text::parse_in`].
+
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec::Vec};
+use core::num::NonZeroU8;
+
+use bumpalo::{collections::Vec as BumpVec, Bump};
+use nom::{
+    bytes::complete::{tag, take_while},
+    combinator::{all_consuming, map},
+    error::{ErrorKind, ParseError},
+    multi::length_value,
+    number::complete::be_u8,
+    sequence::{pair, tuple},
+    IResult,
+};
+
+use crate::{
+    parser::{boolean, integer, integer_usize},
+    Error, Expression, Segment, Text,
+};
+
+/// An expression allocated in an arena. See [`Expression`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpressionRef<'a, 'bump> {
+    GreaterThanOrEqual(&'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)),
+    GreaterThan(&'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)),
+    LessThanOrEqual(&'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)),
+    LessThan(&'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)),
+    Equal(&'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)),
+    NotEqual(&'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)),
+    TopLevelParameter(u8),
+    InputParameter(u32),
+    PlayerParameter(u32),
+    StringParameter(u32),
+    ObjectParameter(u32),
+    TodoEC,
+    Integer(u32),
+    Text(TextRef<'a, 'bump>),
+}
+
+impl ExpressionRef<'_, '_> {
+    /// Copies this expression out of the arena.
+    pub fn to_expression(&self) -> Expression {
+        let pair = |boite: &(ExpressionRef, ExpressionRef)| {
+            Box::new((boite.0.to_expression(), boite.1.to_expression()))
+        };
+        match self {
+            ExpressionRef::GreaterThanOrEqual(boite) => Expression::GreaterThanOrEqual(pair(boite)),
+            ExpressionRef::GreaterThan(boite) => Expression::GreaterThan(pair(boite)),
+            ExpressionRef::LessThanOrEqual(boite) => Expression::LessThanOrEqual(pair(boite)),
+            ExpressionRef::LessThan(boite) => Expression::LessThan(pair(boite)),
+            ExpressionRef::Equal(boite) => Expression::Equal(pair(boite)),
+            ExpressionRef::NotEqual(boite) => Expression::NotEqual(pair(boite)),
+            ExpressionRef::TopLevelParameter(value) => Expression::TopLevelParameter(*value),
+            ExpressionRef::InputParameter(value) => Expression::InputParameter(*value),
+            ExpressionRef::PlayerParameter(value) => Expression::PlayerParameter(*value),
+            ExpressionRef::StringParameter(value) => Expression::StringParameter(*value),
+            ExpressionRef::ObjectParameter(value) => Expression::ObjectParameter(*value),
+            ExpressionRef::TodoEC => Expression::TodoEC,
+            ExpressionRef::Integer(value) => Expression::Integer(*value),
+            ExpressionRef::Text(text) => Expression::Text(text.to_text()),
+        }
+    }
+}
+
+/// A segment allocated in an arena. See [`Segment`]. Literal text and the contents of tags that
+/// hold raw bytes are borrowed from the parsed input, and never contain null bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentRef<'a, 'bump> {
+    Literal(&'a str),
+    TodoResetTime(&'a [u8]),
+    Time(ExpressionRef<'a, 'bump>),
+    If {
+        condition: ExpressionRef<'a, 'bump>,
+        true_value: ExpressionRef<'a, 'bump>,
+        false_value: ExpressionRef<'a, 'bump>,
+    },
+    Switch {
+        discriminant: ExpressionRef<'a, 'bump>,
+        cases: &'bump [ExpressionRef<'a, 'bump>],
+    },
+    Todo0A(ExpressionRef<'a, 'bump>),
+    IfEquals {
+        left: ExpressionRef<'a, 'bump>,
+        right: ExpressionRef<'a, 'bump>,
+        true_value: ExpressionRef<'a, 'bump>,
+        false_value: ExpressionRef<'a, 'bump>,
+    },
+    Todo0F {
+        player: ExpressionRef<'a, 'bump>,
+        self_value: ExpressionRef<'a, 'bump>,
+        other_value: ExpressionRef<'a, 'bump>,
+    },
+    NewLine,
+    GuiIcon(ExpressionRef<'a, 'bump>),
+    ColorChange(ExpressionRef<'a, 'bump>),
+    Todo14(ExpressionRef<'a, 'bump>),
+    SoftHyphen,
+    Todo17,
+    Todo19(bool),
+    Emphasis(bool),
+    Todo1B(&'a [u8]),
+    Todo1C(&'a [u8]),
+    NonBreakingSpace,
+    CommandIcon(ExpressionRef<'a, 'bump>),
+    Dash,
+    IntegerValue(ExpressionRef<'a, 'bump>),
+    TodoFormat(ExpressionRef<'a, 'bump>, &'a [u8]),
+    TwoDigitValue(ExpressionRef<'a, 'bump>),
+    Todo26(
+        ExpressionRef<'a, 'bump>,
+        ExpressionRef<'a, 'bump>,
+        ExpressionRef<'a, 'bump>,
+    ),
+    Link(&'bump [ExpressionRef<'a, 'bump>]),
+    Sheet {
+        name: ExpressionRef<'a, 'bump>,
+        row_index: ExpressionRef<'a, 'bump>,
+        column_index: Option<ExpressionRef<'a, 'bump>>,
+        parameters: &'bump [ExpressionRef<'a, 'bump>],
+    },
+    StringValue(ExpressionRef<'a, 'bump>),
+    StringValueSentenceCase(ExpressionRef<'a, 'bump>),
+    Split {
+        input: ExpressionRef<'a, 'bump>,
+        separator: ExpressionRef<'a, 'bump>,
+        index: ExpressionRef<'a, 'bump>,
+    },
+    StringValueTitleCase(ExpressionRef<'a, 'bump>),
+    AutoTranslate(ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>),
+    StringValueLowerCase(ExpressionRef<'a, 'bump>),
+    SheetJa(&'bump [ExpressionRef<'a, 'bump>]),
+    SheetEn(&'bump [ExpressionRef<'a, 'bump>]),
+    SheetDe(&'bump [ExpressionRef<'a, 'bump>]),
+    SheetFr(&'bump [ExpressionRef<'a, 'bump>]),
+    Todo40(ExpressionRef<'a, 'bump>),
+    Foreground(ExpressionRef<'a, 'bump>),
+    Glow(ExpressionRef<'a, 'bump>),
+    Ruby {
+        annotated: ExpressionRef<'a, 'bump>,
+        annotation: ExpressionRef<'a, 'bump>,
+    },
+    ZeroPaddedValue {
+        value: ExpressionRef<'a, 'bump>,
+        digits: ExpressionRef<'a, 'bump>,
+    },
+    Todo51(ExpressionRef<'a, 'bump>),
+    Todo60(&'a [u8]),
+    Todo61(ExpressionRef<'a, 'bump>),
+}
+
+impl SegmentRef<'_, '_> {
+    /// Copies this segment out of the arena.
+    pub fn to_segment(&self) -> Segment {
+        fn data(data: &[u8]) -> Vec<NonZeroU8> {
+            data.iter()
+                .map(|byte| NonZeroU8::new(*byte).unwrap())
+                .collect()
+        }
+
+        fn list(expressions: &[ExpressionRef]) -> Vec<Expression> {
+            expressions
+                .iter()
+                .map(ExpressionRef::to_expression)
+                .collect()
+        }
+
+        match self {
+            SegmentRef::Literal(string) => Segment::Literal((*string).to_owned()),
+            SegmentRef::TodoResetTime(arg) => Segment::TodoResetTime(data(arg)),
+            SegmentRef::Time(arg) => Segment::Time(arg.to_expression()),
+            SegmentRef::If {
+                condition,
+                true_value,
+                false_value,
+            } => Segment::If {
+                condition: condition.to_expression(),
+                true_value: true_value.to_expression(),
+                false_value: false_value.to_expression(),
+            },
+            SegmentRef::Switch {
+                discriminant,
+                cases,
+            } => Segment::Switch {
+                discriminant: discriminant.to_expression(),
+                cases: list(cases),
+            },
+            SegmentRef::Todo0A(arg) => Segment::Todo0A(arg.to_expression()),
+            SegmentRef::IfEquals {
+                left,
+                right,
+                true_value,
+                false_value,
+            } => Segment::IfEquals {
+                left: left.to_expression(),
+                right: right.to_expression(),
+                true_value: true_value.to_expression(),
+                false_value: false_value.to_expression(),
+            },
+            SegmentRef::Todo0F {
+                player,
+                self_value,
+                other_value,
+            } => Segment::Todo0F {
+                player: player.to_expression(),
+                self_value: self_value.to_expression(),
+                other_value: other_value.to_expression(),
+            },
+            SegmentRef::NewLine => Segment::NewLine,
+            SegmentRef::GuiIcon(arg) => Segment::GuiIcon(arg.to_expression()),
+            SegmentRef::ColorChange(arg) => Segment::ColorChange(arg.to_expression()),
+            SegmentRef::Todo14(arg) => Segment::Todo14(arg.to_expression()),
+            SegmentRef::SoftHyphen => Segment::SoftHyphen,
+            SegmentRef::Todo17 => Segment::Todo17,
+            SegmentRef::Todo19(arg) => Segment::Todo19(*arg),
+            SegmentRef::Emphasis(arg) => Segment::Emphasis(*arg),
+            SegmentRef::Todo1B(arg) => Segment::Todo1B(data(arg)),
+            SegmentRef::Todo1C(arg) => Segment::Todo1C(data(arg)),
+            SegmentRef::NonBreakingSpace => Segment::NonBreakingSpace,
+            SegmentRef::CommandIcon(arg) => Segment::CommandIcon(arg.to_expression()),
+            SegmentRef::Dash => Segment::Dash,
+            SegmentRef::IntegerValue(arg) => Segment::IntegerValue(arg.to_expression()),
+            SegmentRef::TodoFormat(arg1, arg2) => {
+                Segment::TodoFormat(arg1.to_expression(), data(arg2))
+            }
+            SegmentRef::TwoDigitValue(arg) => Segment::TwoDigitValue(arg.to_expression()),
+            SegmentRef::Todo26(arg1, arg2, arg3) => Segment::Todo26(
+                arg1.to_expression(),
+                arg2.to_expression(),
+                arg3.to_expression(),
+            ),
+            SegmentRef::Link(args) => Segment::Link(list(args)),
+            SegmentRef::Sheet {
+                name,
+                row_index,
+                column_index,
+                parameters,
+            } => Segment::Sheet {
+                name: name.to_expression(),
+                row_index: row_index.to_expression(),
+                column_index: column_index.as_ref().map(ExpressionRef::to_expression),
+                parameters: list(parameters),
+            },
+            SegmentRef::StringValue(arg) => Segment::StringValue(arg.to_expression()),
+            SegmentRef::StringValueSentenceCase(arg) => {
+                Segment::StringValueSentenceCase(arg.to_expression())
+            }
+            SegmentRef::Split {
+                input,
+                separator,
+                index,
+            } => Segment::Split {
+                input: input.to_expression(),
+                separator: separator.to_expression(),
+                index: index.to_expression(),
+            },
+            SegmentRef::StringValueTitleCase(arg) => {
+                Segment::StringValueTitleCase(arg.to_expression())
+            }
+            SegmentRef::AutoTranslate(arg1, arg2) => {
+                Segment::AutoTranslate(arg1.to_expression(), arg2.to_expression())
+            }
+            SegmentRef::StringValueLowerCase(arg) => {
+                Segment::StringValueLowerCase(arg.to_expression())
+            }
+            SegmentRef::SheetJa(args) => Segment::SheetJa(list(args)),
+            SegmentRef::SheetEn(args) => Segment::SheetEn(list(args)),
+            SegmentRef::SheetDe(args) => Segment::SheetDe(list(args)),
+            SegmentRef::SheetFr(args) => Segment::SheetFr(list(args)),
+            SegmentRef::Todo40(arg) => Segment::Todo40(arg.to_expression()),
+            SegmentRef::Foreground(arg) => Segment::Foreground(arg.to_expression()),
+            SegmentRef::Glow(arg) => Segment::Glow(arg.to_expression()),
+            SegmentRef::Ruby {
+                annotated,
+                annotation,
+            } => Segment::Ruby {
+                annotated: annotated.to_expression(),
+                annotation: annotation.to_expression(),
+            },
+            SegmentRef::ZeroPaddedValue { value, digits } => Segment::ZeroPaddedValue {
+                value: value.to_expression(),
+                digits: digits.to_expression(),
+            },
+            SegmentRef::Todo51(arg) => Segment::Todo51(arg.to_expression()),
+            SegmentRef::Todo60(arg) => Segment::Todo60(data(arg)),
+            SegmentRef::Todo61(arg) => Segment::Todo61(arg.to_expression()),
+        }
+    }
+}
+
+/// A string allocated in an arena, returned by [`Text::parse_in`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRef<'a, 'bump> {
+    segments: &'bump [SegmentRef<'a, 'bump>],
+}
+
+impl<'a, 'bump> TextRef<'a, 'bump> {
+    pub fn segments(&self) -> &'bump [SegmentRef<'a, 'bump>] {
+        self.segments
+    }
+
+    /// Copies this string out of the arena.
+    pub fn to_text(&self) -> Text {
+        Text::new(self.segments.iter().map(SegmentRef::to_segment).collect())
+    }
+}
+
+type ParseResult<'a, T> = IResult<&'a [u8], T, Error>;
+
+fn expression_pair<'a, 'bump>(
+    bump: &'bump Bump,
+    input: &'a [u8],
+) -> ParseResult<'a, &'bump (ExpressionRef<'a, 'bump>, ExpressionRef<'a, 'bump>)> {
+    map(
+        pair(
+            |input| expression(bump, input),
+            |input| expression(bump, input),
+        ),
+        |pair| &*bump.alloc(pair),
+    )(input)
+}
+
+fn expression<'a, 'bump>(
+    bump: &'bump Bump,
+    input: &'a [u8],
+) -> ParseResult<'a, ExpressionRef<'a, 'bump>> {
+    use crate::types::expr::*;
+
+    let (rest, type_byte) = be_u8(input)?;
+    match type_byte {
+        1..=0xcf => Ok((rest, ExpressionRef::Integer(type_byte as u32 - 1))),
+        0xd0..=0xdf => Ok((rest, ExpressionRef::TopLevelParameter(type_byte - 0xd0))),
+        GEQ => map(
+            |input| expression_pair(bump, input),
+            ExpressionRef::GreaterThanOrEqual,
+        )(rest),
+        GT => map(
+            |input| expression_pair(bump, input),
+            ExpressionRef::GreaterThan,
+        )(rest),
+        LEQ => map(
+            |input| expression_pair(bump, input),
+            ExpressionRef::LessThanOrEqual,
+        )(rest),
+        LT => map(
+            |input| expression_pair(bump, input),
+            ExpressionRef::LessThan,
+        )(rest),
+        EQ => map(|input| expression_pair(bump, input), ExpressionRef::Equal)(rest),
+        NEQ => map(
+            |input| expression_pair(bump, input),
+            ExpressionRef::NotEqual,
+        )(rest),
+        INPUT_PARAM => map(integer, ExpressionRef::InputParameter)(rest),
+        PLAYER_PARAM => map(integer, ExpressionRef::PlayerParameter)(rest),
+        STRING_PARAM => map(integer, ExpressionRef::StringParameter)(rest),
+        OBJECT_PARAM => map(integer, ExpressionRef::ObjectParameter)(rest),
+        TODO_EC => Ok((rest, ExpressionRef::TodoEC)),
+        // Integers wider than one byte are encoded the same way as integer arguments.
+        BYTE..=INT32 => map(integer, ExpressionRef::Integer)(input),
+        TAGGED_TEXT => length_value(
+            integer_usize,
+            all_consuming(map(|input| tagged_text(bump, input), ExpressionRef::Text)),
+        )(rest),
+        _ => Err(nom::Err::Error(Error::from_error_kind(
+            rest,
+            ErrorKind::Alt,
+        ))),
+    }
+}
+
+/// Parses expressions until the end of the input, requiring at least `min` of them.
+fn expressions<'a, 'bump>(
+    bump: &'bump Bump,
+    min: usize,
+) -> impl Fn(&'a [u8]) -> ParseResult<'a, &'bump [ExpressionRef<'a, 'bump>]>
+where
+    'a: 'bump,
+{
+    move |mut input: &'a [u8]| {
+        let mut expressions = BumpVec::new_in(bump);
+        while !input.is_empty() {
+            let (rest, expression) = expression(bump, input)?;
+            expressions.push(expression);
+            input = rest;
+        }
+        if expressions.len() < min {
+            return Err(nom::Err::Error(Error::from_error_kind(
+                input,
+                ErrorKind::ManyMN,
+            )));
+        }
+        Ok((input, expressions.into_bump_slice()))
+    }
+}
+
+fn c_string_data(data: &[u8]) -> Result<&[u8], nom::Err<Error>> {
+    if data.contains(&0) {
+        return Err(nom::Err::Error(Error::NullByte));
+    }
+    Ok(data)
+}
+
+fn segment<'a, 'bump>(
+    bump: &'bump Bump,
+    input: &'a [u8],
+) -> ParseResult<'a, SegmentRef<'a, 'bump>> {
+    use crate::types::tag::*;
+
+    let (input, type_byte) = be_u8(input)?;
+    let (input, contents) = length_value(integer_usize, take_while(|_| true))(input)?;
+    let one = |input| expression(bump, input);
+    let segment = match type_byte {
+        TODO_RESET_TIME => SegmentRef::TodoResetTime(c_string_data(contents)?),
+        TIME => SegmentRef::Time(all_consuming(one)(contents)?.1),
+        IF => {
+            let (_, (condition, true_value, false_value)) =
+                all_consuming(tuple((one, one, one)))(contents)?;
+            SegmentRef::If {
+                condition,
+                true_value,
+                false_value,
+            }
+        }
+        SWITCH => {
+            let (_, (discriminant, cases)) = pair(one, expressions(bump, 1))(contents)?;
+            SegmentRef::Switch {
+                discriminant,
+                cases,
+            }
+        }
+        TODO_0A => SegmentRef::Todo0A(all_consuming(one)(contents)?.1),
+        IF_EQUALS => {
+            let (_, (left, right, true_value, false_value)) =
+                all_consuming(tuple((one, one, one, one)))(contents)?;
+            SegmentRef::IfEquals {
+                left,
+                right,
+                true_value,
+                false_value,
+            }
+        }
+        TODO_0F => {
+            let (_, (player, self_value, other_value)) =
+                all_consuming(tuple((one, one, one)))(contents)?;
+            SegmentRef::Todo0F {
+                player,
+                self_value,
+                other_value,
+            }
+        }
+        NEW_LINE => no_data(contents, SegmentRef::NewLine)?,
+        GUI_ICON => SegmentRef::GuiIcon(all_consuming(one)(contents)?.1),
+        COLOR_CHANGE => SegmentRef::ColorChange(all_consuming(one)(contents)?.1),
+        TODO_14 => SegmentRef::Todo14(all_consuming(one)(contents)?.1),
+        SOFT_HYPHEN => no_data(contents, SegmentRef::SoftHyphen)?,
+        TODO_17 => no_data(contents, SegmentRef::Todo17)?,
+        TODO_19 => SegmentRef::Todo19(all_consuming(boolean)(contents)?.1),
+        EMPHASIS => SegmentRef::Emphasis(all_consuming(boolean)(contents)?.1),
+        TODO_1B => SegmentRef::Todo1B(c_string_data(contents)?),
+        TODO_1C => SegmentRef::Todo1C(c_string_data(contents)?),
+        NON_BREAKING_SPACE => no_data(contents, SegmentRef::NonBreakingSpace)?,
+        COMMAND_ICON => SegmentRef::CommandIcon(all_consuming(one)(contents)?.1),
+        DASH => no_data(contents, SegmentRef::Dash)?,
+        INTEGER_VALUE => SegmentRef::IntegerValue(all_consuming(one)(contents)?.1),
+        TODO_FORMAT => {
+            let (data, arg1) = one(contents)?;
+            SegmentRef::TodoFormat(arg1, c_string_data(data)?)
+        }
+        TWO_DIGIT_VALUE => SegmentRef::TwoDigitValue(all_consuming(one)(contents)?.1),
+        TODO_26 => {
+            let (_, (arg1, arg2, arg3)) = all_consuming(tuple((one, one, one)))(contents)?;
+            SegmentRef::Todo26(arg1, arg2, arg3)
+        }
+        LINK => SegmentRef::Link(expressions(bump, 1)(contents)?.1),
+        SHEET => {
+            let (rest, (name, row_index)) = pair(one, one)(contents)?;
+            let (column_index, parameters) = if rest.is_empty() {
+                (None, &[][..])
+            } else {
+                let (_, (column_index, parameters)) = pair(one, expressions(bump, 0))(rest)?;
+                (Some(column_index), parameters)
+            };
+            SegmentRef::Sheet {
+                name,
+                row_index,
+                column_index,
+                parameters,
+            }
+        }
+        STRING_VALUE => SegmentRef::StringValue(all_consuming(one)(contents)?.1),
+        STRING_VALUE_SENTENCE_CASE => {
+            SegmentRef::StringValueSentenceCase(all_consuming(one)(contents)?.1)
+        }
+        SPLIT => {
+            let (_, (input, separator, index)) = all_consuming(tuple((one, one, one)))(contents)?;
+            SegmentRef::Split {
+                input,
+                separator,
+                index,
+            }
+        }
+        STRING_VALUE_TITLE_CASE => {
+            SegmentRef::StringValueTitleCase(all_consuming(one)(contents)?.1)
+        }
+        AUTO_TRANSLATE => {
+            let (_, (arg1, arg2)) = all_consuming(pair(one, one))(contents)?;
+            SegmentRef::AutoTranslate(arg1, arg2)
+        }
+        STRING_VALUE_LOWER_CASE => {
+            SegmentRef::StringValueLowerCase(all_consuming(one)(contents)?.1)
+        }
+        SHEET_JA => SegmentRef::SheetJa(expressions(bump, 3)(contents)?.1),
+        SHEET_EN => SegmentRef::SheetEn(expressions(bump, 3)(contents)?.1),
+        SHEET_DE => SegmentRef::SheetDe(expressions(bump, 3)(contents)?.1),
+        SHEET_FR => SegmentRef::SheetFr(expressions(bump, 3)(contents)?.1),
+        TODO_40 => SegmentRef::Todo40(all_consuming(one)(contents)?.1),
+        FOREGROUND => SegmentRef::Foreground(all_consuming(one)(contents)?.1),
+        GLOW => SegmentRef::Glow(all_consuming(one)(contents)?.1),
+        RUBY => {
+            let (_, (annotated, annotation)) = all_consuming(pair(one, one))(contents)?;
+            SegmentRef::Ruby {
+                annotated,
+                annotation,
+            }
+        }
+        ZERO_PADDED_VALUE => {
+            let (_, (value, digits)) = all_consuming(pair(one, one))(contents)?;
+            SegmentRef::ZeroPaddedValue { value, digits }
+        }
+        TODO_51 => SegmentRef::Todo51(all_consuming(one)(contents)?.1),
+        TODO_60 => SegmentRef::Todo60(c_string_data(contents)?),
+        TODO_61 => SegmentRef::Todo61(all_consuming(one)(contents)?.1),
+        _ => {
+            return Err(nom::Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Alt,
+            )))
+        }
+    };
+    Ok((input, segment))
+}
+
+/// Checks that a tag without arguments has no contents.
+fn no_data<'a, 'bump>(
+    contents: &'a [u8],
+    segment: SegmentRef<'a, 'bump>,
+) -> Result<SegmentRef<'a, 'bump>, nom::Err<Error>> {
+    if !contents.is_empty() {
+        return Err(nom::Err::Error(Error::from_error_kind(
+            contents,
+            ErrorKind::Eof,
+        )));
+    }
+    Ok(segment)
+}
+
+/// Parses a string into an arena. This follows [`tagged_text`](crate::parser::tagged_text),
+/// except that literal text is borrowed from the input.
+pub(crate) fn tagged_text<'a, 'bump>(
+    bump: &'bump Bump,
+    mut input: &'a [u8],
+) -> ParseResult<'a, TextRef<'a, 'bump>> {
+    fn literal(data: &[u8]) -> Result<&str, nom::Err<Error>> {
+        core::str::from_utf8(data).map_err(|_| {
+            // Build the same error as the owned parser, which only happens for malformed input.
+            nom::Err::Failure(String::from_utf8(data.to_vec()).unwrap_err().into())
+        })
+    }
+
+    let mut segments = BumpVec::new_in(bump);
+    while !input.is_empty() {
+        if let Some(start_pos) = input.iter().position(|&byte| byte == 2) {
+            if start_pos > 0 {
+                segments.push(SegmentRef::Literal(literal(&input[..start_pos])?));
+            }
+            input = &input[start_pos + 1..];
+            {
+                let (new_input, segment) = segment(bump, input)?;
+                segments.push(segment);
+                input = new_input;
+            }
+            {
+                let (new_input, _) = tag(b"\x03")(input)?;
+                input = new_input;
+            }
+        } else {
+            segments.push(SegmentRef::Literal(literal(input)?));
+            input = &input[input.len()..input.len()];
+        }
+    }
+    Ok((
+        input,
+        TextRef {
+            segments: segments.into_bump_slice(),
+        },
+    ))
+}
@@ -0,0 +1,217 @@
+//! Maps this crate's tag and expression kinds to the names used by Lumina and Dalamud.
+//!
+//! Lumina names each tag after its macro code, e.g. tag 0x2c is `MacroCode.Split`, and
+//! Dalamud's macro syntax uses the lower case forms, as in `<split(...)>`. Since tags are
+//! matched by code, some names differ in meaning from this crate's names where the two
+//! projects' understanding of a tag differs, e.g. [`Segment::Emphasis`] is `Italic`.
+
+use crate::{Expression, LinkPayload, Segment};
+
+/// Segment kinds, as returned by [`Segment::kind`], with their tag codes and Lumina macro names.
+const MACRO_NAMES: &[(&str, u8, &str)] = &[
+    ("TodoResetTime", 0x06, "SetResetTime"),
+    ("Time", 0x07, "SetTime"),
+    ("If", 0x08, "If"),
+    ("Switch", 0x09, "Switch"),
+    ("Todo0A", 0x0a, "PcName"),
+    ("IfEquals", 0x0c, "IfPcName"),
+    ("Todo0F", 0x0f, "IfSelf"),
+    ("NewLine", 0x10, "NewLine"),
+    ("GuiIcon", 0x12, "Icon"),
+    ("ColorChange", 0x13, "Color"),
+    ("Todo14", 0x14, "EdgeColor"),
+    ("SoftHyphen", 0x16, "SoftHyphen"),
+    ("Todo17", 0x17, "Key"),
+    ("Todo19", 0x19, "Bold"),
+    ("Emphasis", 0x1a, "Italic"),
+    ("Todo1B", 0x1b, "Edge"),
+    ("Todo1C", 0x1c, "Shadow"),
+    ("NonBreakingSpace", 0x1d, "NonBreakingSpace"),
+    ("CommandIcon", 0x1e, "Icon2"),
+    ("Dash", 0x1f, "Hyphen"),
+    ("IntegerValue", 0x20, "Num"),
+    ("TodoFormat", 0x22, "Kilo"),
+    ("TwoDigitValue", 0x24, "Sec"),
+    ("Todo26", 0x26, "Float"),
+    ("Link", 0x27, "Link"),
+    ("Sheet", 0x28, "Sheet"),
+    ("StringValue", 0x29, "String"),
+    ("StringValueSentenceCase", 0x2b, "Head"),
+    ("Split", 0x2c, "Split"),
+    ("StringValueTitleCase", 0x2d, "HeadAll"),
+    ("AutoTranslate", 0x2e, "Fixed"),
+    ("StringValueLowerCase", 0x2f, "Lower"),
+    ("SheetJa", 0x30, "JaNoun"),
+    ("SheetEn", 0x31, "EnNoun"),
+    ("SheetDe", 0x32, "DeNoun"),
+    ("SheetFr", 0x33, "FrNoun"),
+    ("Todo40", 0x40, "LowerHead"),
+    ("Foreground", 0x48, "ColorType"),
+    ("Glow", 0x49, "EdgeColorType"),
+    ("Ruby", 0x4a, "Ruby"),
+    ("ZeroPaddedValue", 0x50, "Digit"),
+    ("Todo51", 0x51, "Ordinal"),
+    ("Todo60", 0x60, "Sound"),
+    ("Todo61", 0x61, "LevelPos"),
+];
+
+/// Lumina's names for the time values selected by [`Expression::TopLevelParameter`], starting
+/// at parameter 8.
+const TIME_PARAMETER_NAMES: [&str; 8] = [
+    "Millisecond",
+    "Second",
+    "Minute",
+    "Hour",
+    "Day",
+    "WeekDay",
+    "Month",
+    "Year",
+];
+
+/// Expression kinds, as returned by [`Expression::kind`], with their Lumina expression type
+/// names.
+const EXPRESSION_NAMES: &[(&str, &str)] = &[
+    ("GreaterThanOrEqual", "GreaterThanOrEqualTo"),
+    ("GreaterThan", "GreaterThan"),
+    ("LessThanOrEqual", "LessThanOrEqualTo"),
+    ("LessThan", "LessThan"),
+    ("Equal", "Equal"),
+    ("NotEqual", "NotEqual"),
+    ("InputParameter", "LocalNumber"),
+    ("PlayerParameter", "GlobalNumber"),
+    ("StringParameter", "LocalString"),
+    ("ObjectParameter", "GlobalString"),
+    ("TodoEC", "StackColor"),
+];
+
+/// Returns the Lumina macro name for a segment's tag, or `None` for literal text.
+pub fn lumina_macro_name(segment: &Segment) -> Option<&'static str> {
+    let kind = segment.kind();
+    MACRO_NAMES
+        .iter()
+        .find(|(name, _, _)| *name == kind)
+        .map(|(_, _, lumina)| *lumina)
+}
+
+/// Looks up a Lumina macro name, ignoring case so Dalamud's macro syntax names also match.
+/// Returns the tag code and the corresponding [`Segment::kind`].
+pub fn segment_kind_from_lumina(name: &str) -> Option<(u8, &'static str)> {
+    MACRO_NAMES
+        .iter()
+        .find(|(_, _, lumina)| lumina.eq_ignore_ascii_case(name))
+        .map(|(kind, code, _)| (*code, *kind))
+}
+
+/// Returns the Lumina expression type name for an expression. Integer and text expressions,
+/// which Lumina represents as plain values, and top level parameters other than times, have no
+/// name.
+pub fn lumina_expression_name(expr: &Expression) -> Option<&'static str> {
+    if let Expression::TopLevelParameter(index) = expr {
+        return TIME_PARAMETER_NAMES
+            .get(usize::from(*index).checked_sub(8)?)
+            .copied();
+    }
+    let kind = expr.kind();
+    EXPRESSION_NAMES
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .map(|(_, lumina)| *lumina)
+}
+
+/// Converts a Lumina expression type name back to an expression. Parameter expressions are
+/// given the index `parameter`; it is ignored for the other kinds. Returns `None` for unknown
+/// names and for comparisons, which need operands.
+pub fn expression_from_lumina(name: &str, parameter: u32) -> Option<Expression> {
+    if let Some(index) = TIME_PARAMETER_NAMES
+        .iter()
+        .position(|time| time.eq_ignore_ascii_case(name))
+    {
+        return Some(Expression::TopLevelParameter(index as u8 + 8));
+    }
+    let (kind, _) = EXPRESSION_NAMES
+        .iter()
+        .find(|(_, lumina)| lumina.eq_ignore_ascii_case(name))?;
+    match *kind {
+        "InputParameter" => Some(Expression::InputParameter(parameter)),
+        "PlayerParameter" => Some(Expression::PlayerParameter(parameter)),
+        "StringParameter" => Some(Expression::StringParameter(parameter)),
+        "ObjectParameter" => Some(Expression::ObjectParameter(parameter)),
+        "TodoEC" => Some(Expression::TodoEC),
+        _ => None,
+    }
+}
+
+/// Returns the name of the Dalamud payload class that represents a segment. Segments without a
+/// dedicated class are `RawPayload`s in Dalamud.
+pub fn dalamud_payload_name(segment: &Segment) -> &'static str {
+    match segment {
+        Segment::Literal(_) => "TextPayload",
+        Segment::NewLine => "NewLinePayload",
+        Segment::Dash => "SeHyphenPayload",
+        Segment::GuiIcon(_) => "IconPayload",
+        Segment::Emphasis(_) => "EmphasisItalicPayload",
+        Segment::AutoTranslate(..) => "AutoTranslatePayload",
+        Segment::Foreground(_) => "UIForegroundPayload",
+        Segment::Glow(_) => "UIGlowPayload",
+        Segment::Link(arguments) => match crate::decode_link(arguments) {
+            LinkPayload::Character { .. } => "PlayerPayload",
+            LinkPayload::Item { .. } => "ItemPayload",
+            LinkPayload::MapPosition { .. } => "MapLinkPayload",
+            LinkPayload::Quest { .. } => "QuestPayload",
+            LinkPayload::Status { .. } => "StatusPayload",
+            LinkPayload::PartyFinder { .. } => "PartyFinderPayload",
+            _ => "RawPayload",
+        },
+        _ => "RawPayload",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        dalamud_payload_name, expression_from_lumina, lumina_expression_name, lumina_macro_name,
+        segment_kind_from_lumina, MACRO_NAMES,
+    };
+    use crate::{Expression, LinkPayload, Segment};
+
+    #[test]
+    fn names() {
+        assert_eq!(lumina_macro_name(&Segment::Dash), Some("Hyphen"));
+        assert_eq!(lumina_macro_name(&Segment::Literal("a".to_string())), None);
+        assert_eq!(segment_kind_from_lumina("split"), Some((0x2c, "Split")));
+        assert_eq!(
+            segment_kind_from_lumina("Fixed"),
+            Some((0x2e, "AutoTranslate"))
+        );
+        assert_eq!(segment_kind_from_lumina("Bogus"), None);
+        for (kind, code, lumina) in MACRO_NAMES.iter() {
+            assert_eq!(segment_kind_from_lumina(lumina), Some((*code, *kind)));
+        }
+
+        for expr in [
+            Expression::InputParameter(3),
+            Expression::StringParameter(3),
+            Expression::TopLevelParameter(11),
+            Expression::TodoEC,
+        ] {
+            let name = lumina_expression_name(&expr).unwrap();
+            assert_eq!(expression_from_lumina(name, 3), Some(expr.clone()));
+        }
+        assert_eq!(
+            lumina_expression_name(&Expression::TopLevelParameter(11)),
+            Some("Hour")
+        );
+        assert_eq!(
+            lumina_expression_name(&Expression::TopLevelParameter(2)),
+            None
+        );
+        assert_eq!(lumina_expression_name(&Expression::Integer(2)), None);
+
+        let item_link = Segment::Link(vec![
+            Expression::Integer(LinkPayload::ITEM),
+            Expression::Integer(5000),
+        ]);
+        assert_eq!(dalamud_payload_name(&item_link), "ItemPayload");
+        assert_eq!(dalamud_payload_name(&Segment::Todo17), "RawPayload");
+    }
+}
@@ -0,0 +1,159 @@
+//! Compares two strings segment by segment, separating changes to the text itself from changes
+//! to tags, so that formatting-only edits can be filtered out when reviewing translations.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Segment, Text};
+
+/// One difference between two strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<'a> {
+    /// A run of literal text was reworded.
+    TextChanged { old: &'a str, new: &'a str },
+    /// A tag of the same kind is present in both strings, but its parameters differ.
+    TagChanged { old: &'a Segment, new: &'a Segment },
+    /// A segment only present in the old string.
+    Removed(&'a Segment),
+    /// A segment only present in the new string.
+    Added(&'a Segment),
+}
+
+impl Change<'_> {
+    /// Returns true if this change affects literal text, rather than only tags.
+    pub fn is_text(&self) -> bool {
+        match self {
+            Change::TextChanged { .. } => true,
+            Change::TagChanged { .. } => false,
+            Change::Removed(segment) | Change::Added(segment) => {
+                matches!(segment, Segment::Literal(_))
+            }
+        }
+    }
+}
+
+/// Aligns the segments of two strings, and returns the differences in order. Segments are
+/// aligned by a longest common subsequence. Within each unaligned stretch, literals and tags of
+/// the same kind are paired with each other in order, and the rest are reported as added or
+/// removed.
+pub fn diff<'a>(a: &'a Text, b: &'a Text) -> Vec<Change<'a>> {
+    let (a, b) = (&a.segments, &b.segments);
+
+    // lengths[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut gap_a, mut gap_b) = (i, j);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            pair_gap(&a[gap_a..i], &b[gap_b..j], &mut changes);
+            i += 1;
+            j += 1;
+            gap_a = i;
+            gap_b = j;
+        } else if j == b.len() || (i < a.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pair_gap(&a[gap_a..], &b[gap_b..], &mut changes);
+    changes
+}
+
+fn pair_gap<'a>(old: &'a [Segment], new: &'a [Segment], changes: &mut Vec<Change<'a>>) {
+    let mut next_new = 0;
+    for old_segment in old.iter() {
+        let matching = new[next_new..]
+            .iter()
+            .position(|new_segment| new_segment.kind() == old_segment.kind());
+        match matching {
+            Some(offset) => {
+                for added in new[next_new..next_new + offset].iter() {
+                    changes.push(Change::Added(added));
+                }
+                let new_segment = &new[next_new + offset];
+                next_new += offset + 1;
+                changes.push(match (old_segment, new_segment) {
+                    (Segment::Literal(old), Segment::Literal(new)) => {
+                        Change::TextChanged { old, new }
+                    }
+                    _ => Change::TagChanged {
+                        old: old_segment,
+                        new: new_segment,
+                    },
+                });
+            }
+            None => changes.push(Change::Removed(old_segment)),
+        }
+    }
+    for added in new[next_new..].iter() {
+        changes.push(Change::Added(added));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change};
+    use crate::{Expression, Segment, Text};
+
+    #[test]
+    fn structural_diff() {
+        let literal = |s: &str| Segment::Literal(s.to_string());
+        let old = Text::new(vec![
+            Segment::Foreground(Expression::Integer(500)),
+            literal("Obtain a potion"),
+            Segment::Foreground(Expression::Integer(0)),
+            Segment::NewLine,
+            literal("Quickly."),
+        ]);
+        let new = Text::new(vec![
+            Segment::Foreground(Expression::Integer(501)),
+            literal("Obtain a potion"),
+            Segment::Foreground(Expression::Integer(0)),
+            Segment::NewLine,
+            literal("Hurry."),
+            Segment::Dash,
+        ]);
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            [
+                Change::TagChanged {
+                    old: &Segment::Foreground(Expression::Integer(500)),
+                    new: &Segment::Foreground(Expression::Integer(501)),
+                },
+                Change::TextChanged {
+                    old: "Quickly.",
+                    new: "Hurry.",
+                },
+                Change::Added(&Segment::Dash),
+            ]
+        );
+        assert_eq!(
+            changes.iter().map(Change::is_text).collect::<Vec<_>>(),
+            [false, true, false]
+        );
+
+        assert!(diff(&old, &old).is_empty());
+        let removed = Text::new(vec![literal("Quickly.")]);
+        assert_eq!(
+            diff(&old, &removed),
+            [
+                Change::Removed(&old.segments[0]),
+                Change::Removed(&old.segments[1]),
+                Change::Removed(&old.segments[2]),
+                Change::Removed(&old.segments[3]),
+            ]
+        );
+    }
+}
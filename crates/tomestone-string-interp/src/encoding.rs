@@ -1,4 +1,5 @@
-use std::convert::TryInto;
+use alloc::{vec, vec::Vec};
+use core::convert::TryInto;
 
 use crate::{Expression, Segment, Text};
 
@@ -7,8 +8,8 @@ pub enum EncodeError {
     NullByte,
 }
 
-impl std::fmt::Display for EncodeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             EncodeError::UnrepresentableInteger => write!(f, "integer value isn't representable"),
             EncodeError::NullByte => write!(f, "null byte in literal text string"),
@@ -359,6 +360,16 @@ fn encode_tag(buf: &mut Vec<u8>, tag: &Segment) -> Result<(), EncodeError> {
             buf.append(&mut tag_data);
             buf.push(3);
         }
+        Segment::Link(args) => {
+            buf.extend_from_slice(&[2, LINK]);
+            let mut tag_data = vec![];
+            for arg in args.iter() {
+                encode_expression(&mut tag_data, arg)?;
+            }
+            encode_integer(buf, tag_data.len().try_into().unwrap())?;
+            buf.append(&mut tag_data);
+            buf.push(3);
+        }
         Segment::Sheet {
             name,
             row_index,
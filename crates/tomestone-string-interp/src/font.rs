@@ -0,0 +1,340 @@
+//! Parses FDT font descriptions, and measures and wraps text with their glyph metrics.
+//!
+//! An FDT file starts with an `fcsv0100` header pointing to a glyph table (`fthd0100`) and a
+//! kerning table (`knhd0100`). Characters are keyed by their UTF-8 encoding, packed into a
+//! big-endian integer, so `é` is 0xC3A9.
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::map,
+    multi::count,
+    number::complete::{le_f32, le_i32, le_i8, le_u16, le_u32, le_u8},
+    sequence::tuple,
+    IResult,
+};
+
+use crate::{Error, Text};
+
+/// Metrics and texture location of one glyph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph {
+    pub texture_index: u16,
+    pub texture_x: u16,
+    pub texture_y: u16,
+    pub width: u8,
+    pub height: u8,
+    /// Added to the width to get the distance to the next glyph.
+    pub next_offset_x: i8,
+    pub offset_y: i8,
+}
+
+impl Glyph {
+    /// Horizontal distance from this glyph to the next one, in pixels.
+    pub fn advance(&self) -> i32 {
+        i32::from(self.width) + i32::from(self.next_offset_x)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Font {
+    pub size: f32,
+    pub line_height: u32,
+    pub ascent: u32,
+    pub texture_width: u16,
+    pub texture_height: u16,
+    glyphs: BTreeMap<char, Glyph>,
+    kerning: BTreeMap<(char, char), i32>,
+}
+
+fn packed_char(packed: u32) -> Option<char> {
+    let bytes = packed.to_be_bytes();
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(3);
+    core::str::from_utf8(&bytes[start..]).ok()?.chars().next()
+}
+
+fn glyph_entry(input: &[u8]) -> IResult<&[u8], (u32, Glyph), Error> {
+    map(
+        tuple((
+            le_u32, le_u16, le_u16, le_u16, le_u16, le_u8, le_u8, le_i8, le_i8,
+        )),
+        |(
+            utf8,
+            _sjis,
+            texture_index,
+            texture_x,
+            texture_y,
+            width,
+            height,
+            next_offset_x,
+            offset_y,
+        )| {
+            (
+                utf8,
+                Glyph {
+                    texture_index,
+                    texture_x,
+                    texture_y,
+                    width,
+                    height,
+                    next_offset_x,
+                    offset_y,
+                },
+            )
+        },
+    )(input)
+}
+
+fn kerning_entry(input: &[u8]) -> IResult<&[u8], (u32, u32, i32), Error> {
+    map(
+        tuple((le_u32, le_u32, le_u16, le_u16, le_i32)),
+        |(left, right, _, _, offset)| (left, right, offset),
+    )(input)
+}
+
+fn finish<T>(res: IResult<&[u8], T, Error>) -> Result<(&[u8], T), Error> {
+    res.map_err(|e| match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => Error::Nom(nom::error::ErrorKind::Eof),
+    })
+}
+
+fn section(data: &[u8], offset: u32) -> Result<&[u8], Error> {
+    data.get(offset as usize..)
+        .ok_or(Error::Nom(nom::error::ErrorKind::Eof))
+}
+
+impl Font {
+    pub fn parse(data: &[u8]) -> Result<Font, Error> {
+        let (_, (_, glyph_table_offset, kerning_table_offset)) =
+            finish(tuple((tag(b"fcsv0100"), le_u32, le_u32))(data))?;
+
+        let (rest, (_, glyph_count, kerning_count, _, texture_width, texture_height)) =
+            finish(tuple((
+                tag(b"fthd0100"),
+                le_u32,
+                le_u16,
+                take(2usize),
+                le_u16,
+                le_u16,
+            ))(section(data, glyph_table_offset)?))?;
+        let (rest, (size, line_height, ascent)) = finish(tuple((le_f32, le_u32, le_u32))(rest))?;
+        let (_, glyph_entries) = finish(count(glyph_entry, glyph_count as usize)(rest))?;
+
+        let mut kerning = BTreeMap::new();
+        if kerning_count > 0 {
+            let (rest, (_, table_count, _)) =
+                finish(tuple((tag(b"knhd0100"), le_u32, take(4usize)))(section(
+                    data,
+                    kerning_table_offset,
+                )?))?;
+            let entries = table_count.min(u32::from(kerning_count)) as usize;
+            let (_, kerning_entries) = finish(count(kerning_entry, entries)(rest))?;
+            for (left, right, offset) in kerning_entries {
+                if let (Some(left), Some(right)) = (packed_char(left), packed_char(right)) {
+                    kerning.insert((left, right), offset);
+                }
+            }
+        }
+
+        Ok(Font {
+            size,
+            line_height,
+            ascent,
+            texture_width,
+            texture_height,
+            glyphs: glyph_entries
+                .into_iter()
+                .filter_map(|(utf8, glyph)| Some((packed_char(utf8)?, glyph)))
+                .collect(),
+            kerning,
+        })
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c)
+    }
+
+    /// Kerning adjustment between two adjacent characters, in pixels.
+    pub fn kerning(&self, left: char, right: char) -> i32 {
+        self.kerning.get(&(left, right)).copied().unwrap_or(0)
+    }
+
+    /// Measures the width of a single line of text, in pixels. Characters without a glyph take
+    /// up no space.
+    pub fn measure_line(&self, line: &str) -> u32 {
+        let mut width = 0;
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, c);
+            }
+            if let Some(glyph) = self.glyph(c) {
+                width += glyph.advance();
+            }
+            previous = Some(c);
+        }
+        width.max(0) as u32
+    }
+
+    /// Measures the width of the widest line of text, in pixels.
+    pub fn measure(&self, text: &str) -> u32 {
+        text.split('\n')
+            .map(|line| self.measure_line(line))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Breaks text into lines no wider than `max_width`. Lines are broken at spaces, which are
+    /// dropped, and at existing line breaks. Non-breaking spaces (U+00A0) keep words together.
+    /// A word too wide for a line on its own is broken between characters.
+    pub fn wrap(&self, text: &str, max_width: u32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", line, word)
+                };
+                if self.measure_line(&candidate) <= max_width {
+                    line = candidate;
+                    continue;
+                }
+                if !line.is_empty() {
+                    lines.push(core::mem::take(&mut line));
+                }
+                for c in word.chars() {
+                    line.push(c);
+                    if line.chars().count() > 1 && self.measure_line(&line) > max_width {
+                        line.pop();
+                        lines.push(core::mem::replace(&mut line, c.to_string()));
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+/// Measures the width of the widest line of a string, in pixels, after rendering it with
+/// [`Text::to_plain_text`].
+pub fn measure(text: &Text, font: &Font) -> u32 {
+    font.measure(&text.to_plain_text())
+}
+
+/// Renders a string with [`Text::to_plain_text`], and breaks it into lines with [`Font::wrap`].
+pub fn wrap(text: &Text, font: &Font, max_width: u32) -> Vec<String> {
+    font.wrap(&text.to_plain_text(), max_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_sqpack::GameData;
+
+    use super::{measure, wrap, Font};
+    use crate::{Segment, Text};
+
+    fn glyph_entry(c: char, width: u8, next_offset_x: i8) -> Vec<u8> {
+        let mut utf8 = [0; 4];
+        let len = c.encode_utf8(&mut utf8).len();
+        let mut packed = [0; 4];
+        packed[4 - len..].copy_from_slice(&utf8[..len]);
+        let mut entry = u32::from_be_bytes(packed).to_le_bytes().to_vec();
+        entry.extend_from_slice(&[0; 8]);
+        entry.extend_from_slice(&[width, 12, next_offset_x as u8, 0]);
+        entry
+    }
+
+    fn test_font() -> Vec<u8> {
+        let glyphs = [
+            glyph_entry(' ', 3, 0),
+            glyph_entry('A', 6, 1),
+            glyph_entry('V', 6, 1),
+            glyph_entry('a', 5, 1),
+            glyph_entry('é', 5, 1),
+            glyph_entry('\u{a0}', 3, 0),
+        ];
+        let mut data = b"fcsv0100".to_vec();
+        data.extend_from_slice(&0x20u32.to_le_bytes());
+        let kerning_offset = 0x20 + 0x20 + glyphs.len() as u32 * 0x10;
+        data.extend_from_slice(&kerning_offset.to_le_bytes());
+        data.resize(0x20, 0);
+
+        data.extend_from_slice(b"fthd0100");
+        data.extend_from_slice(&(glyphs.len() as u32).to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+        data.extend_from_slice(&1024u16.to_le_bytes());
+        data.extend_from_slice(&1024u16.to_le_bytes());
+        data.extend_from_slice(&12.0f32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&12u32.to_le_bytes());
+        for glyph in glyphs.iter() {
+            data.extend_from_slice(glyph);
+        }
+
+        data.extend_from_slice(b"knhd0100");
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&u32::from(b'A').to_le_bytes());
+        data.extend_from_slice(&u32::from(b'V').to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(-2i32).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn metrics() {
+        let font = Font::parse(&test_font()).unwrap();
+        assert_eq!(font.line_height, 16);
+        assert_eq!(font.glyph('é').unwrap().advance(), 6);
+        assert_eq!(font.measure_line("AV"), 7 + 7 - 2);
+        assert_eq!(font.measure("aa\naaa"), 18);
+        assert_eq!(
+            measure(
+                &Text::new(vec![
+                    Segment::Literal("A".to_string()),
+                    Segment::NewLine,
+                    Segment::Literal("éé".to_string()),
+                ]),
+                &font
+            ),
+            12
+        );
+
+        assert_eq!(font.wrap("aa aa aa", 30), ["aa aa", "aa"]);
+        assert_eq!(font.wrap("aa\u{a0}aa aa", 30), ["aa\u{a0}aa", "aa"]);
+        assert_eq!(font.wrap("aaaaaaa", 30), ["aaaaa", "aa"]);
+        assert_eq!(
+            wrap(
+                &Text::new(vec![Segment::Literal("A a".to_string())]),
+                &font,
+                100
+            ),
+            ["A a"]
+        );
+        assert!(Font::parse(b"fcsv0100").is_err());
+    }
+
+    #[test]
+    fn font_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let data = game_data
+            .lookup_path_data(&mut data_file_set, "common/font/AXIS_12.fdt")
+            .unwrap()
+            .unwrap();
+        let font = Font::parse(&data).unwrap();
+        assert!(font.glyph('A').is_some());
+        assert!(font.measure("Hello") > 0);
+    }
+}
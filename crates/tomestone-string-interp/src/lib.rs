@@ -1,13 +1,42 @@
-use std::{fmt, num::NonZeroU8, string::FromUtf8Error, vec::IntoIter};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    boxed::Box,
+    string::{FromUtf8Error, String},
+    vec::{IntoIter, Vec},
+};
+use core::{fmt, num::NonZeroU8};
 
 use nom::Finish;
 
+#[cfg(feature = "bumpalo")]
+mod arena;
+mod compat;
+mod diff;
 mod encoding;
+mod font;
+mod link;
 mod parser;
 mod serialization;
+mod speech;
+mod stats;
+mod truncate;
 mod types;
 
+#[cfg(feature = "bumpalo")]
+pub use arena::{ExpressionRef, SegmentRef, TextRef};
+pub use compat::{
+    dalamud_payload_name, expression_from_lumina, lumina_expression_name, lumina_macro_name,
+    segment_kind_from_lumina,
+};
+pub use diff::{diff, Change};
 pub use encoding::{encode, EncodeError};
+pub use font::{measure, wrap, Font, Glyph};
+pub use link::{decode_link, LinkPayload};
+pub use speech::{number_to_words, SpeechContext, SpeechResolver};
+pub use stats::TagStatistics;
 
 #[derive(Debug)]
 pub enum Error {
@@ -138,6 +167,11 @@ pub trait Visitor {
                 arg2.accept(self);
                 arg3.accept(self);
             }
+            Segment::Link(args) => {
+                for arg in args {
+                    arg.accept(self);
+                }
+            }
             Segment::Sheet {
                 name,
                 row_index,
@@ -309,6 +343,11 @@ pub trait MutVisitor {
                 arg2.accept_mut(self);
                 arg3.accept_mut(self);
             }
+            Segment::Link(args) => {
+                for arg in args {
+                    arg.accept_mut(self);
+                }
+            }
             Segment::Sheet {
                 name,
                 row_index,
@@ -425,6 +464,28 @@ pub enum Expression {
     Text(Text),
 }
 
+impl Expression {
+    /// Returns the name of this expression's variant, for reporting and statistics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Expression::GreaterThanOrEqual(..) => "GreaterThanOrEqual",
+            Expression::GreaterThan(..) => "GreaterThan",
+            Expression::LessThanOrEqual(..) => "LessThanOrEqual",
+            Expression::LessThan(..) => "LessThan",
+            Expression::Equal(..) => "Equal",
+            Expression::NotEqual(..) => "NotEqual",
+            Expression::TopLevelParameter(..) => "TopLevelParameter",
+            Expression::InputParameter(..) => "InputParameter",
+            Expression::PlayerParameter(..) => "PlayerParameter",
+            Expression::StringParameter(..) => "StringParameter",
+            Expression::ObjectParameter(..) => "ObjectParameter",
+            Expression::TodoEC => "TodoEC",
+            Expression::Integer(..) => "Integer",
+            Expression::Text(..) => "Text",
+        }
+    }
+}
+
 impl TreeNode for Expression {
     fn accept<V: Visitor>(&self, visitor: &mut V) {
         visitor.visit_expression(self);
@@ -488,6 +549,9 @@ pub enum Segment {
     TodoFormat(Expression, Vec<NonZeroU8>),
     TwoDigitValue(Expression),
     Todo26(Expression, Expression, Expression),
+    /// Starts or ends an interactive link, such as an item link in chat. The first argument is
+    /// the link type. See [`Segment::link_payload`].
+    Link(Vec<Expression>),
     /// Looks up a value from one of the tables. The resulting value may be either a number or a
     /// string. The first argument is the name of the table, the second argument is the index of a
     /// row, and the third argument is the index of a column. Any remaining arguments are passed as
@@ -503,7 +567,8 @@ pub enum Segment {
     /// Takes a string-valued expression, and capitalizes the first character.
     StringValueSentenceCase(Expression),
     /// Split a string at each occurrence of a separator, and return one of the resulting
-    /// substrings.
+    /// substrings. The index is one-based, so `<split(<string(lstr1)>, ,1)>` gives a
+    /// character's first name. See [`split_string`].
     Split {
         input: Expression,
         separator: Expression,
@@ -534,6 +599,59 @@ pub enum Segment {
     Todo61(Expression),
 }
 
+impl Segment {
+    /// Returns the name of this segment's variant, for reporting and statistics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Segment::Literal(..) => "Literal",
+            Segment::TodoResetTime(..) => "TodoResetTime",
+            Segment::Time(..) => "Time",
+            Segment::If { .. } => "If",
+            Segment::Switch { .. } => "Switch",
+            Segment::Todo0A(..) => "Todo0A",
+            Segment::IfEquals { .. } => "IfEquals",
+            Segment::Todo0F { .. } => "Todo0F",
+            Segment::NewLine => "NewLine",
+            Segment::GuiIcon(..) => "GuiIcon",
+            Segment::ColorChange(..) => "ColorChange",
+            Segment::Todo14(..) => "Todo14",
+            Segment::SoftHyphen => "SoftHyphen",
+            Segment::Todo17 => "Todo17",
+            Segment::Todo19(..) => "Todo19",
+            Segment::Emphasis(..) => "Emphasis",
+            Segment::Todo1B(..) => "Todo1B",
+            Segment::Todo1C(..) => "Todo1C",
+            Segment::NonBreakingSpace => "NonBreakingSpace",
+            Segment::CommandIcon(..) => "CommandIcon",
+            Segment::Dash => "Dash",
+            Segment::IntegerValue(..) => "IntegerValue",
+            Segment::TodoFormat(..) => "TodoFormat",
+            Segment::TwoDigitValue(..) => "TwoDigitValue",
+            Segment::Todo26(..) => "Todo26",
+            Segment::Link(..) => "Link",
+            Segment::Sheet { .. } => "Sheet",
+            Segment::StringValue(..) => "StringValue",
+            Segment::StringValueSentenceCase(..) => "StringValueSentenceCase",
+            Segment::Split { .. } => "Split",
+            Segment::StringValueTitleCase(..) => "StringValueTitleCase",
+            Segment::AutoTranslate(..) => "AutoTranslate",
+            Segment::StringValueLowerCase(..) => "StringValueLowerCase",
+            Segment::SheetJa(..) => "SheetJa",
+            Segment::SheetEn(..) => "SheetEn",
+            Segment::SheetDe(..) => "SheetDe",
+            Segment::SheetFr(..) => "SheetFr",
+            Segment::Todo40(..) => "Todo40",
+            Segment::Foreground(..) => "Foreground",
+            Segment::Glow(..) => "Glow",
+            Segment::Ruby { .. } => "Ruby",
+            Segment::ZeroPaddedValue { .. } => "ZeroPaddedValue",
+            Segment::Todo51(..) => "Todo51",
+            Segment::Todo60(..) => "Todo60",
+            Segment::Todo61(..) => "Todo61",
+        }
+    }
+}
+
 impl TreeNode for Segment {
     fn accept<V: Visitor>(&self, visitor: &mut V) {
         visitor.visit_tag(self);
@@ -544,6 +662,29 @@ impl TreeNode for Segment {
     }
 }
 
+/// Evaluates a [`Segment::Split`] tag: splits `input` at each occurrence of `separator`, and
+/// returns the substring at the one-based `index`. An empty separator leaves the input in one
+/// piece. Returns an empty string if the index is zero or past the last substring.
+pub fn split_string<'a>(input: &'a str, separator: &str, index: u32) -> &'a str {
+    let index = match (index as usize).checked_sub(1) {
+        Some(index) => index,
+        None => return "",
+    };
+    if separator.is_empty() {
+        return if index == 0 { input } else { "" };
+    }
+    input.split(separator).nth(index).unwrap_or("")
+}
+
+/// Parses a string cell and renders it with [`Text::to_plain_text`], or decodes it lossily as
+/// UTF-8 if it can't be parsed.
+pub fn plain_text(data: &[u8]) -> String {
+    match Text::parse(data) {
+        Ok(text) => text.to_plain_text(),
+        Err(_) => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
 impl fmt::Debug for Segment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -615,6 +756,7 @@ impl fmt::Debug for Segment {
                 .field(arg2)
                 .field(arg3)
                 .finish(),
+            Segment::Link(args) => f.debug_tuple("Link").field(args).finish(),
             Segment::Sheet {
                 name,
                 row_index,
@@ -702,6 +844,20 @@ impl Text {
             .map(|(_, text)| text)
     }
 
+    /// Parses a string like [`Text::parse`], but allocates its segments and expressions in
+    /// `bump`, and borrows literal text from `input`. When parsing every string of a sheet, the
+    /// arena can be reset between pages, along with the rows from
+    /// `tomestone_exdf::DatasetPageIter::next_in`.
+    #[cfg(feature = "bumpalo")]
+    pub fn parse_in<'a, 'bump>(
+        input: &'a [u8],
+        bump: &'bump bumpalo::Bump,
+    ) -> Result<TextRef<'a, 'bump>, Error> {
+        nom::combinator::complete(|input| arena::tagged_text(bump, input))(input)
+            .finish()
+            .map(|(_, text)| text)
+    }
+
     pub fn encode(&self) -> Result<Vec<u8>, encoding::EncodeError> {
         encoding::encode(self)
     }
@@ -709,6 +865,103 @@ impl Text {
     pub fn into_vec(self) -> Vec<Segment> {
         self.segments
     }
+
+    /// Renders the literal text content, without evaluating any expressions. Both branches of
+    /// conditionals and every case of switches are included, separated by spaces, so the result
+    /// is suited for searching rather than display. Values looked up from sheets or parameters
+    /// are omitted.
+    pub fn to_plain_text(&self) -> String {
+        let mut visitor = PlainTextVisitor::default();
+        self.accept(&mut visitor);
+        visitor.output
+    }
+
+    /// Renders the literal text content like [`Text::to_plain_text`], and calls `render_link`
+    /// for each link tag. Any text it returns is inserted in place of the tag.
+    pub fn to_plain_text_with_links(
+        &self,
+        mut render_link: impl FnMut(&LinkPayload) -> Option<String>,
+    ) -> String {
+        let mut visitor = PlainTextVisitor {
+            output: String::new(),
+            render_link: Some(&mut render_link),
+        };
+        self.accept(&mut visitor);
+        visitor.output
+    }
+
+    /// Renders the text as sentences for text-to-speech. See [`SpeechContext`].
+    pub fn to_speech(&self, context: &mut SpeechContext) -> String {
+        speech::to_speech(self, context)
+    }
+}
+
+type RenderLink<'a> = &'a mut dyn FnMut(&LinkPayload) -> Option<String>;
+
+#[derive(Default)]
+struct PlainTextVisitor<'a> {
+    output: String,
+    render_link: Option<RenderLink<'a>>,
+}
+
+impl PlainTextVisitor<'_> {
+    fn alternatives<'a>(&mut self, alternatives: impl IntoIterator<Item = &'a Expression>) {
+        for (i, expr) in alternatives.into_iter().enumerate() {
+            if i > 0 && !self.output.is_empty() && !self.output.ends_with(char::is_whitespace) {
+                self.output.push(' ');
+            }
+            expr.accept(self);
+        }
+    }
+}
+
+impl Visitor for PlainTextVisitor<'_> {
+    fn visit_tag(&mut self, tag: &Segment) {
+        match tag {
+            Segment::Literal(string) => self.output.push_str(string),
+            Segment::NewLine => self.output.push('\n'),
+            Segment::NonBreakingSpace => self.output.push(' '),
+            Segment::Dash => self.output.push('-'),
+            Segment::If {
+                true_value,
+                false_value,
+                ..
+            }
+            | Segment::IfEquals {
+                true_value,
+                false_value,
+                ..
+            } => self.alternatives([true_value, false_value]),
+            Segment::Todo0F {
+                self_value,
+                other_value,
+                ..
+            } => self.alternatives([self_value, other_value]),
+            Segment::Switch { cases, .. } => self.alternatives(cases),
+            Segment::Emphasis(_) | Segment::SoftHyphen => {}
+            Segment::StringValue(expr)
+            | Segment::StringValueSentenceCase(expr)
+            | Segment::StringValueTitleCase(expr)
+            | Segment::StringValueLowerCase(expr)
+            | Segment::Foreground(expr)
+            | Segment::Glow(expr) => expr.accept(self),
+            Segment::Ruby { annotated, .. } => annotated.accept(self),
+            Segment::Link(arguments) => {
+                if let Some(render_link) = &mut self.render_link {
+                    if let Some(rendered) = render_link(&decode_link(arguments)) {
+                        self.output.push_str(&rendered);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        if let Expression::Text(text) = expr {
+            text.accept(self);
+        }
+    }
 }
 
 impl TreeNode for Text {
@@ -798,7 +1051,7 @@ impl Extend<Segment> for TextAccumulator {
 
 #[cfg(test)]
 mod tests {
-    use super::{Segment, Text};
+    use super::{split_string, Expression, Segment, Text};
 
     #[test]
     fn simple() {
@@ -809,6 +1062,83 @@ mod tests {
             }
         );
     }
+
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    fn parse_in() {
+        use super::{ExpressionRef, SegmentRef};
+
+        let bump = bumpalo::Bump::new();
+        let input = b"Hello\x02\x10\x01\x03world\x02\x29\x03\xea\x02\x03";
+        let text = Text::parse_in(input, &bump).unwrap();
+        assert_eq!(
+            text.segments(),
+            [
+                SegmentRef::Literal("Hello"),
+                SegmentRef::NewLine,
+                SegmentRef::Literal("world"),
+                SegmentRef::StringValue(ExpressionRef::StringParameter(1)),
+            ]
+        );
+        assert_eq!(text.to_text(), Text::parse(input).unwrap());
+        assert!(Text::parse_in(b"\x02\x10\x02\x01\x01\x03", &bump).is_err());
+    }
+
+    #[test]
+    fn plain_text() {
+        let literal = |s: &str| Expression::Text(Text::new(vec![Segment::Literal(s.to_string())]));
+        let text = Text::new(vec![
+            Segment::Literal("Hello".to_string()),
+            Segment::NonBreakingSpace,
+            Segment::If {
+                condition: Expression::PlayerParameter(4),
+                true_value: literal("sir"),
+                false_value: literal("madam"),
+            },
+            Segment::NewLine,
+            Segment::Emphasis(true),
+            Segment::Literal("Well".to_string()),
+            Segment::Dash,
+            Segment::Literal("met".to_string()),
+            Segment::Emphasis(false),
+            Segment::Sheet {
+                name: literal("Item"),
+                row_index: Expression::Integer(1),
+                column_index: None,
+                parameters: Vec::new(),
+            },
+        ]);
+        assert_eq!(text.to_plain_text(), "Hello sir madam\nWell-met");
+
+        assert_eq!(super::plain_text(b"Potion"), "Potion");
+        assert_eq!(super::plain_text(b"Po\xfftion"), "Po\u{fffd}tion");
+    }
+
+    #[test]
+    fn split() {
+        // <split(<string(lstr1)>, ,2)>
+        let data = b"\x02\x2c\x0d\xff\x07\x02\x29\x03\xea\x02\x03\xff\x02 \x03\x03";
+        let text = Text::parse(data).unwrap();
+        assert_eq!(
+            text,
+            Text::new(vec![Segment::Split {
+                input: Expression::Text(Text::new(vec![Segment::StringValue(
+                    Expression::StringParameter(1)
+                )])),
+                separator: Expression::Text(Text::new(vec![Segment::Literal(" ".to_string())])),
+                index: Expression::Integer(2),
+            }])
+        );
+        assert_eq!(text.encode().ok().as_deref(), Some(&data[..]));
+
+        assert_eq!(split_string("Wedge Antilles", " ", 1), "Wedge");
+        assert_eq!(split_string("Wedge Antilles", " ", 2), "Antilles");
+        assert_eq!(split_string("Wedge Antilles", " ", 3), "");
+        assert_eq!(split_string("Wedge Antilles", " ", 0), "");
+        assert_eq!(split_string("a--b--c", "--", 3), "c");
+        assert_eq!(split_string("Wedge", "", 1), "Wedge");
+        assert_eq!(split_string("Wedge", "", 2), "");
+    }
 }
 
 #[cfg(test)]
@@ -996,13 +1326,13 @@ mod proptests {
             .choose(&[
                 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22,
                 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43,
+                44,
             ])
             .unwrap()
         {
             0 => {
                 let string = String::arbitrary(g);
-                let mut string =
-                    string.replace(|char| char == '\x00' || char == '\x02', "\u{fffd}");
+                let mut string = string.replace(['\x00', '\x02'], "\u{fffd}");
                 if string.is_empty() {
                     string.push('\u{fffd}');
                 }
@@ -1138,6 +1468,16 @@ mod proptests {
             41 => Segment::Todo51(arbitrary_expr(g, depth)),
             42 => Segment::Todo60(Arbitrary::arbitrary(g)),
             43 => Segment::Todo61(arbitrary_expr(g, depth)),
+            44 => {
+                let mut args: Vec<Expression> = Vec::<()>::arbitrary(g)
+                    .into_iter()
+                    .map(|()| arbitrary_expr(g, depth))
+                    .collect();
+                if args.is_empty() {
+                    args.push(arbitrary_expr(g, depth));
+                }
+                Segment::Link(args)
+            }
             _ => unreachable!(),
         }
     }
@@ -1482,6 +1822,9 @@ mod proptests {
                 Segment::StringValueLowerCase(arg) => {
                     Box::new(shrink_expr(arg).map(Segment::StringValueLowerCase))
                 }
+                Segment::Link(args) => {
+                    Box::new(shrink_expr_list_preserve_length(args).map(Segment::Link))
+                }
                 Segment::SheetJa(args) => {
                     Box::new(shrink_expr_list_preserve_length(args).map(Segment::SheetJa))
                 }
@@ -1571,7 +1914,7 @@ mod proptests {
             segments: vec![tag],
         };
         if let Ok(data) = crate::encoding::encode(&text) {
-            !data.iter().any(|byte| *byte == 0)
+            !data.contains(&0)
         } else {
             true
         }
@@ -1617,6 +1960,31 @@ mod proptests {
         QuickCheck::new().quickcheck(property_encode_round_trip as fn(Segment) -> TestResult);
     }
 
+    #[cfg(feature = "bumpalo")]
+    fn property_parse_in(text: Text) -> TestResult {
+        let Ok(data) = crate::encoding::encode(&text) else {
+            return TestResult::discard();
+        };
+        let bump = bumpalo::Bump::new();
+        match (Text::parse(&data), Text::parse_in(&data, &bump)) {
+            (Ok(parsed), Ok(parsed_in)) if parsed == parsed_in.to_text() => TestResult::passed(),
+            (Err(_), Err(_)) => TestResult::passed(),
+            (parsed, parsed_in) => {
+                eprintln!(
+                    "arena parse differs, {:02x?} => {:?}, {:?}",
+                    data, parsed, parsed_in
+                );
+                TestResult::failed()
+            }
+        }
+    }
+
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    fn parse_in_matches_parse() {
+        QuickCheck::new().quickcheck(property_parse_in as fn(Text) -> TestResult);
+    }
+
     #[test]
     fn regression_01() {
         assert!(!property_encode_round_trip(Segment::Dash).is_failure());
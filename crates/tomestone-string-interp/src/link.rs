@@ -0,0 +1,223 @@
+//! Typed decoding of interactive link payloads.
+//!
+//! Link tags wrap a span of text, such as an item name in a chat message. The opening tag
+//! carries the link type and its target, and a closing tag with the terminator type ends the
+//! span. Type numbers are the decoded integer values, so the item link type, 2, is stored as the
+//! byte 0x03.
+
+use crate::{Expression, Segment, Text};
+
+/// Items with IDs above this offset are high quality versions of the item with the offset
+/// subtracted.
+const HIGH_QUALITY_OFFSET: u32 = 1_000_000;
+/// Items with IDs above this offset, and below [`HIGH_QUALITY_OFFSET`], are collectable versions.
+const COLLECTABLE_OFFSET: u32 = 500_000;
+
+/// The decoded contents of a [`Segment::Link`] tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkPayload<'a> {
+    /// A player character, with their home world and name.
+    Character {
+        world: u32,
+        name: Option<&'a Text>,
+    },
+    Item {
+        /// Row of the `Item` sheet.
+        item: u32,
+        high_quality: bool,
+        collectable: bool,
+        /// Display name included with links from chat, if any.
+        name: Option<&'a Text>,
+    },
+    MapPosition {
+        /// Row of the `TerritoryType` sheet.
+        territory_type: u32,
+        /// Row of the `Map` sheet.
+        map: u32,
+        /// Raw coordinates, in thousandths of world units.
+        x: i32,
+        y: i32,
+    },
+    Quest {
+        quest: u32,
+    },
+    Achievement {
+        achievement: u32,
+    },
+    HowTo {
+        how_to: u32,
+    },
+    PartyFinderNotification,
+    Status {
+        status: u32,
+    },
+    PartyFinder {
+        listing: u32,
+        world: u32,
+    },
+    AkatsukiNote {
+        note: u32,
+    },
+    /// Ends the span of linked text.
+    Terminator,
+    /// A link type that isn't recognized, or a link whose arguments don't match its type.
+    Unknown {
+        kind: Option<u32>,
+        arguments: &'a [Expression],
+    },
+}
+
+impl LinkPayload<'_> {
+    pub const CHARACTER: u32 = 0;
+    pub const ITEM: u32 = 2;
+    pub const MAP_POSITION: u32 = 3;
+    pub const QUEST: u32 = 4;
+    pub const ACHIEVEMENT: u32 = 5;
+    pub const HOW_TO: u32 = 6;
+    pub const PARTY_FINDER_NOTIFICATION: u32 = 7;
+    pub const STATUS: u32 = 8;
+    pub const PARTY_FINDER: u32 = 9;
+    pub const AKATSUKI_NOTE: u32 = 10;
+    pub const TERMINATOR: u32 = 0xce;
+}
+
+fn integer(arguments: &[Expression], index: usize) -> Option<u32> {
+    match arguments.get(index)? {
+        Expression::Integer(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn first_text(arguments: &[Expression]) -> Option<&Text> {
+    arguments.iter().find_map(|arg| match arg {
+        Expression::Text(text) => Some(text),
+        _ => None,
+    })
+}
+
+/// Decodes the arguments of a link tag.
+pub fn decode_link(arguments: &[Expression]) -> LinkPayload<'_> {
+    let unknown = LinkPayload::Unknown {
+        kind: integer(arguments, 0),
+        arguments,
+    };
+    let kind = match integer(arguments, 0) {
+        Some(kind) => kind,
+        None => return unknown,
+    };
+    let argument = |index| integer(arguments, index);
+    let payload = match kind {
+        LinkPayload::CHARACTER => argument(2).map(|world| LinkPayload::Character {
+            world,
+            name: first_text(arguments),
+        }),
+        LinkPayload::ITEM => argument(1).map(|raw| {
+            let (item, high_quality, collectable) = if raw > HIGH_QUALITY_OFFSET {
+                (raw - HIGH_QUALITY_OFFSET, true, false)
+            } else if raw > COLLECTABLE_OFFSET {
+                (raw - COLLECTABLE_OFFSET, false, true)
+            } else {
+                (raw, false, false)
+            };
+            LinkPayload::Item {
+                item,
+                high_quality,
+                collectable,
+                name: first_text(arguments),
+            }
+        }),
+        LinkPayload::MAP_POSITION => {
+            argument(1)
+                .zip(argument(2).zip(argument(3)))
+                .map(|(packed, (x, y))| LinkPayload::MapPosition {
+                    territory_type: packed >> 16,
+                    map: packed & 0xffff,
+                    x: x as i32,
+                    y: y as i32,
+                })
+        }
+        LinkPayload::QUEST => argument(1).map(|quest| LinkPayload::Quest { quest }),
+        LinkPayload::ACHIEVEMENT => {
+            argument(1).map(|achievement| LinkPayload::Achievement { achievement })
+        }
+        LinkPayload::HOW_TO => argument(1).map(|how_to| LinkPayload::HowTo { how_to }),
+        LinkPayload::PARTY_FINDER_NOTIFICATION => Some(LinkPayload::PartyFinderNotification),
+        LinkPayload::STATUS => argument(1).map(|status| LinkPayload::Status { status }),
+        LinkPayload::PARTY_FINDER => argument(1).map(|listing| LinkPayload::PartyFinder {
+            listing,
+            world: argument(3).unwrap_or(0),
+        }),
+        LinkPayload::AKATSUKI_NOTE => argument(1).map(|note| LinkPayload::AkatsukiNote { note }),
+        LinkPayload::TERMINATOR => Some(LinkPayload::Terminator),
+        _ => None,
+    };
+    payload.unwrap_or(unknown)
+}
+
+impl Segment {
+    /// Decodes this segment's link payload, if it is a link tag.
+    pub fn link_payload(&self) -> Option<LinkPayload<'_>> {
+        match self {
+            Segment::Link(arguments) => Some(decode_link(arguments)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkPayload;
+    use crate::{Expression, Segment, Text};
+
+    #[test]
+    fn decode() {
+        // An item link as sent in chat, followed by the item name and the terminator.
+        let data = b"\x02\x27\x08\x03\xf2\x13\x88\x02\x01\x01\x03Potion\x02\x27\x02\xcf\x03";
+        let text = Text::parse(data).unwrap();
+        let segments = text.clone().into_vec();
+        assert_eq!(
+            segments[0].link_payload(),
+            Some(LinkPayload::Item {
+                item: 5000,
+                high_quality: false,
+                collectable: false,
+                name: None,
+            })
+        );
+        assert_eq!(segments[1].link_payload(), None);
+        assert_eq!(segments[2].link_payload(), Some(LinkPayload::Terminator));
+        assert_eq!(text.encode().ok().as_deref(), Some(&data[..]));
+        assert_eq!(
+            text.to_plain_text_with_links(|payload| match payload {
+                LinkPayload::Item { item, .. } => Some(format!("[item {}] ", item)),
+                _ => None,
+            }),
+            "[item 5000] Potion"
+        );
+
+        let map_link = Segment::Link(vec![
+            Expression::Integer(LinkPayload::MAP_POSITION),
+            Expression::Integer(132 << 16 | 2),
+            Expression::Integer(-12_500i32 as u32),
+            Expression::Integer(8_000),
+        ]);
+        assert_eq!(
+            map_link.link_payload(),
+            Some(LinkPayload::MapPosition {
+                territory_type: 132,
+                map: 2,
+                x: -12_500,
+                y: 8_000,
+            })
+        );
+
+        let arguments = [Expression::Integer(40)];
+        assert_eq!(
+            Segment::Link(arguments.to_vec()).link_payload(),
+            Some(LinkPayload::Unknown {
+                kind: Some(40),
+                arguments: &arguments,
+            })
+        );
+    }
+}
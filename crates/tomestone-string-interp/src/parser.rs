@@ -1,4 +1,5 @@
-use std::{convert::TryInto, num::NonZeroU8};
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec, vec::Vec};
+use core::{convert::TryInto, num::NonZeroU8};
 
 use nom::{
     branch::alt,
@@ -13,19 +14,19 @@ use nom::{
 
 use super::{Error, Expression, Segment, Text};
 
-fn integer(input: &[u8]) -> IResult<&[u8], u32, Error> {
+pub(crate) fn integer(input: &[u8]) -> IResult<&[u8], u32, Error> {
     use crate::types::expr::*;
 
     let (input, first_byte) = be_u8(input)?;
     match first_byte {
         1..=0xEF => Ok((input, first_byte as u32 - 1)),
-        BYTE => map(be_u8, |byte| (byte as u32))(input),
-        BYTE_SHIFTED_8 => map(be_u8, |byte| ((byte as u32) << 8))(input),
-        BYTE_SHIFTED_16 => map(be_u8, |byte| ((byte as u32) << 16))(input),
-        BYTE_SHIFTED_24 => map(be_u8, |byte| ((byte as u32) << 24))(input),
-        INT16 => map(be_u16, |short| (short as u32))(input),
-        INT16_SHIFTED_8 => map(be_u16, |short| ((short as u32) << 8))(input),
-        INT16_SHIFTED_16 => map(be_u16, |short| ((short as u32) << 16))(input),
+        BYTE => map(be_u8, |byte| byte as u32)(input),
+        BYTE_SHIFTED_8 => map(be_u8, |byte| (byte as u32) << 8)(input),
+        BYTE_SHIFTED_16 => map(be_u8, |byte| (byte as u32) << 16)(input),
+        BYTE_SHIFTED_24 => map(be_u8, |byte| (byte as u32) << 24)(input),
+        INT16 => map(be_u16, |short| short as u32)(input),
+        INT16_SHIFTED_8 => map(be_u16, |short| (short as u32) << 8)(input),
+        INT16_SHIFTED_16 => map(be_u16, |short| (short as u32) << 16)(input),
         INT16_FIRST_AND_THIRD_BYTES => map(pair(be_u8, be_u8), |(hi, lo)| {
             ((hi as u32) << 16) | (lo as u32)
         })(input),
@@ -36,7 +37,7 @@ fn integer(input: &[u8]) -> IResult<&[u8], u32, Error> {
             ((hi as u32) << 24) | (lo as u32)
         })(input),
         INT24 => be_u24(input),
-        INT24_SHIFTED_8 => map(be_u24, |value| (value << 8))(input),
+        INT24_SHIFTED_8 => map(be_u24, |value| value << 8)(input),
         INT24_FIRST_SECOND_AND_FOURTH_BYTES => map(pair(be_u8, be_u16), |(hi, lo)| {
             ((hi as u32) << 24) | (lo as u32)
         })(input),
@@ -51,11 +52,11 @@ fn integer(input: &[u8]) -> IResult<&[u8], u32, Error> {
     }
 }
 
-fn integer_usize(input: &[u8]) -> IResult<&[u8], usize, Error> {
+pub(crate) fn integer_usize(input: &[u8]) -> IResult<&[u8], usize, Error> {
     map_res(integer, TryInto::try_into)(input)
 }
 
-fn boolean(input: &[u8]) -> IResult<&[u8], bool, Error> {
+pub(crate) fn boolean(input: &[u8]) -> IResult<&[u8], bool, Error> {
     map_res(integer, |n| match n {
         0 => Ok(false),
         1 => Ok(true),
@@ -256,6 +257,7 @@ fn segment(input: &[u8]) -> IResult<&[u8], Segment, Error> {
             tuple((expression, expression, expression)),
             |(arg1, arg2, arg3)| Segment::Todo26(arg1, arg2, arg3),
         ))(input),
+        LINK => contents(map(many1(expression), Segment::Link))(input),
         SHEET => contents(map(
             tuple((
                 expression,
@@ -3,7 +3,8 @@
 //! and other enum variants can be freely changed in the future. If needed, deserialization could
 //! be customized to prevent backwards compatibility issues.
 
-use std::{marker::PhantomData, mem::MaybeUninit};
+use alloc::{boxed::Box, vec::Vec};
+use core::{marker::PhantomData, mem::MaybeUninit};
 
 use crate::{Expression, Segment, Text};
 use serde::{
@@ -231,7 +232,7 @@ struct ExpressionVariantVisitor;
 impl<'de> Visitor<'de> for ExpressionVariantVisitor {
     type Value = ExpressionVariant;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str(
             "`int`, `param`, `geq`, `gt`, `leq`, `lt`, `eq`, `neq`, `input_param`, \
             `player_param`, `string_param`, `object_param`, or `text`",
@@ -289,9 +290,9 @@ where
 {
     type Value = [T; N];
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("an array of length ")?;
-        <usize as std::fmt::Display>::fmt(&N, formatter)
+        <usize as core::fmt::Display>::fmt(&N, formatter)
     }
 
     fn visit_seq<S>(self, mut access: S) -> Result<[T; N], S::Error>
@@ -322,7 +323,7 @@ where
                 if let (Some(valid_idx), Some(array)) = (self.valid_idx, &mut self.array) {
                     for elem in &mut array.as_mut()[..=valid_idx] {
                         unsafe {
-                            std::ptr::drop_in_place(elem.as_mut_ptr());
+                            core::ptr::drop_in_place(elem.as_mut_ptr());
                         }
                     }
                 }
@@ -340,7 +341,7 @@ where
                 None => return Err(S::Error::invalid_length(i, &self)),
             };
             unsafe {
-                std::ptr::write(elem.as_mut_ptr(), value);
+                core::ptr::write(elem.as_mut_ptr(), value);
             }
             drop_guard.valid_idx = Some(i);
         }
@@ -487,7 +488,7 @@ struct ExpressionVisitor;
 impl<'de> Visitor<'de> for ExpressionVisitor {
     type Value = Expression;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("an enum representing an expression")
     }
 
@@ -753,6 +754,9 @@ impl Serialize for Segment {
             Segment::Todo26(_, _, _) => Err(S::Error::custom(
                 "serialization of segments with tag 0x26 is not yet supported",
             )),
+            Segment::Link(_) => Err(S::Error::custom(
+                "serialization of segments with tag 0x27 is not yet supported",
+            )),
             Segment::Sheet {
                 name,
                 row_index,
@@ -876,7 +880,7 @@ struct IfSegmentFieldVisitor;
 impl<'de> Visitor<'de> for IfSegmentFieldVisitor {
     type Value = IfSegmentField;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("`condition`, `true_value`, or `false_value`")
     }
 
@@ -907,7 +911,7 @@ struct IfSegmentVisitor;
 impl<'de> Visitor<'de> for IfSegmentVisitor {
     type Value = Segment;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("a struct with fields `condition`, `true_value`, and `false_value`")
     }
 
@@ -966,7 +970,7 @@ struct SheetSegmentFieldVisitor;
 impl<'de> Visitor<'de> for SheetSegmentFieldVisitor {
     type Value = SheetSegmentField;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("`name`, `row_index`, `column_index`, or `parameters`")
     }
 
@@ -998,7 +1002,7 @@ struct SheetSegmentVisitor;
 impl<'de> Visitor<'de> for SheetSegmentVisitor {
     type Value = Segment;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter
             .write_str("a struct with fields `name`, `row_index`, `column_index`, and `parameters`")
     }
@@ -1068,7 +1072,7 @@ struct SplitSegmentFieldVisitor;
 impl<'de> Visitor<'de> for SplitSegmentFieldVisitor {
     type Value = SplitSegmentField;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("`input`, `separator`, or `index`")
     }
 
@@ -1099,7 +1103,7 @@ struct SplitSegmentVisitor;
 impl<'de> Visitor<'de> for SplitSegmentVisitor {
     type Value = Segment;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("a struct with fields `input`, `separator`, and `index`")
     }
 
@@ -1156,7 +1160,7 @@ struct RubySegmentFieldVisitor;
 impl<'de> Visitor<'de> for RubySegmentFieldVisitor {
     type Value = RubySegmentField;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("`annotated` or `annotation`")
     }
 
@@ -1186,7 +1190,7 @@ struct RubySegmentVisitor;
 impl<'de> Visitor<'de> for RubySegmentVisitor {
     type Value = Segment;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("a struct with fields `annotated` and `annotation`")
     }
 
@@ -1247,7 +1251,7 @@ struct SegmentVariantVisitor;
 impl<'de> Visitor<'de> for SegmentVariantVisitor {
     type Value = SegmentVariant;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str(
             "`literal`, `time`, `if`, `new_line`, `soft_hyphen`, `emphasis`, \
             `non_breaking_space`, `dash`, `sheet`, `string_value`, \
@@ -1314,7 +1318,7 @@ struct SegmentVisitor;
 impl<'de> Visitor<'de> for SegmentVisitor {
     type Value = Segment;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("an enum representing a text segment")
     }
 
@@ -1424,7 +1428,7 @@ struct TextVisitor;
 impl<'de> Visitor<'de> for TextVisitor {
     type Value = Text;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("a sequence representing a tagged text string")
     }
 
@@ -1892,6 +1896,12 @@ mod tests {
             "serialization of segments with tag 0x26 is not yet supported",
         );
 
+        assert_ser_tokens_error(
+            &Segment::Link(vec![Expression::Integer(2), Expression::Integer(4)]),
+            &[],
+            "serialization of segments with tag 0x27 is not yet supported",
+        );
+
         assert_tokens(
             &Segment::Sheet {
                 name: Expression::Text(Text::new(vec![Segment::Literal("SheetName".to_string())])),
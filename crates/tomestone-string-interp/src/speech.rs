@@ -0,0 +1,445 @@
+//! Renders text as plain sentences suited for text-to-speech.
+//!
+//! Unlike [`Text::to_plain_text`], this evaluates conditionals using the parameters in a
+//! [`SpeechContext`], so only one branch of each is spoken. Numbers are spelled out in English
+//! words, icons and formatting are dropped, and references to sheets or the auto-translate
+//! dictionary are resolved through a [`SpeechResolver`].
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{split_string, Expression, Segment, Text};
+
+// HashMap needs std, so builds without it use a BTreeMap for parameters instead.
+#[cfg(feature = "std")]
+type ParameterMap<V> = std::collections::HashMap<u32, V>;
+#[cfg(not(feature = "std"))]
+type ParameterMap<V> = alloc::collections::BTreeMap<u32, V>;
+
+/// Looks up text that strings refer to indirectly.
+pub trait SpeechResolver {
+    /// Returns the text of a sheet cell. If no column is given, the sheet's default column should
+    /// be used.
+    fn sheet_text(&mut self, _sheet: &str, _row: u32, _column: Option<u32>) -> Option<String> {
+        None
+    }
+
+    /// Returns the text of an auto-translate dictionary entry.
+    fn auto_translate(&mut self, _group: u32, _key: u32) -> Option<String> {
+        None
+    }
+}
+
+/// Parameter values used to evaluate text for speech.
+#[derive(Default)]
+pub struct SpeechContext<'a> {
+    /// Integer input parameters, such as item counts, keyed by parameter number.
+    pub input_parameters: ParameterMap<u32>,
+    /// Player parameters, such as the player's gender, keyed by parameter number.
+    pub player_parameters: ParameterMap<u32>,
+    /// String parameters, such as the player's name, keyed by parameter number.
+    pub string_parameters: ParameterMap<String>,
+    pub resolver: Option<&'a mut dyn SpeechResolver>,
+}
+
+enum Evaluated {
+    Integer(u32),
+    String(String),
+}
+
+impl<'a> SpeechContext<'a> {
+    pub fn new() -> SpeechContext<'a> {
+        SpeechContext::default()
+    }
+
+    pub fn with_resolver(resolver: &'a mut dyn SpeechResolver) -> SpeechContext<'a> {
+        SpeechContext {
+            resolver: Some(resolver),
+            ..SpeechContext::default()
+        }
+    }
+
+    fn sheet_text(&mut self, sheet: &str, row: u32, column: Option<u32>) -> Option<String> {
+        self.resolver.as_mut()?.sheet_text(sheet, row, column)
+    }
+
+    fn auto_translate(&mut self, group: u32, key: u32) -> Option<String> {
+        self.resolver.as_mut()?.auto_translate(group, key)
+    }
+
+    fn integer(&mut self, expr: &Expression) -> Option<u32> {
+        match self.evaluate(expr)? {
+            Evaluated::Integer(value) => Some(value),
+            Evaluated::String(value) => value.trim().parse().ok(),
+        }
+    }
+
+    fn string(&mut self, expr: &Expression) -> String {
+        match self.evaluate(expr) {
+            Some(Evaluated::String(value)) => value,
+            Some(Evaluated::Integer(value)) => number_to_words(value),
+            None => String::new(),
+        }
+    }
+
+    /// Evaluates an expression, returning `None` if it depends on a parameter that was not
+    /// provided.
+    fn evaluate(&mut self, expr: &Expression) -> Option<Evaluated> {
+        let mut compare = |pair: &(Expression, Expression), op: fn(u32, u32) -> bool| {
+            let left = self.integer(&pair.0)?;
+            let right = self.integer(&pair.1)?;
+            Some(Evaluated::Integer(u32::from(op(left, right))))
+        };
+        match expr {
+            Expression::GreaterThanOrEqual(pair) => compare(pair, |a, b| a >= b),
+            Expression::GreaterThan(pair) => compare(pair, |a, b| a > b),
+            Expression::LessThanOrEqual(pair) => compare(pair, |a, b| a <= b),
+            Expression::LessThan(pair) => compare(pair, |a, b| a < b),
+            Expression::Equal(pair) => compare(pair, |a, b| a == b),
+            Expression::NotEqual(pair) => compare(pair, |a, b| a != b),
+            Expression::InputParameter(index) => self
+                .input_parameters
+                .get(index)
+                .copied()
+                .map(Evaluated::Integer),
+            Expression::PlayerParameter(index) => self
+                .player_parameters
+                .get(index)
+                .copied()
+                .map(Evaluated::Integer),
+            Expression::StringParameter(index) => self
+                .string_parameters
+                .get(index)
+                .cloned()
+                .map(Evaluated::String),
+            Expression::Integer(value) => Some(Evaluated::Integer(*value)),
+            Expression::Text(text) => Some(Evaluated::String(self.render(text))),
+            Expression::TopLevelParameter(_)
+            | Expression::ObjectParameter(_)
+            | Expression::TodoEC => None,
+        }
+    }
+
+    /// Chooses a branch of a conditional. When the condition can't be evaluated, the true branch
+    /// is spoken. For plural suffixes, which test whether a count is greater than one, that is the
+    /// plural form, which reads naturally when the count is unknown.
+    fn branch(
+        &mut self,
+        condition: Option<bool>,
+        true_value: &Expression,
+        false_value: &Expression,
+    ) -> String {
+        if condition.unwrap_or(true) {
+            self.string(true_value)
+        } else {
+            self.string(false_value)
+        }
+    }
+
+    fn render(&mut self, text: &Text) -> String {
+        let mut output = String::new();
+        for segment in text.segments.iter() {
+            match segment {
+                Segment::Literal(string) => output.push_str(&expand_numbers(string)),
+                Segment::NewLine | Segment::NonBreakingSpace => output.push(' '),
+                Segment::Dash => output.push('-'),
+                Segment::If {
+                    condition,
+                    true_value,
+                    false_value,
+                } => {
+                    let condition = self.integer(condition).map(|value| value != 0);
+                    output.push_str(&self.branch(condition, true_value, false_value));
+                }
+                Segment::IfEquals {
+                    left,
+                    right,
+                    true_value,
+                    false_value,
+                } => {
+                    let condition = self
+                        .integer(left)
+                        .zip(self.integer(right))
+                        .map(|(left, right)| left == right);
+                    output.push_str(&self.branch(condition, true_value, false_value));
+                }
+                Segment::Todo0F {
+                    self_value,
+                    other_value,
+                    ..
+                } => output.push_str(&self.branch(None, self_value, other_value)),
+                Segment::Switch {
+                    discriminant,
+                    cases,
+                } => {
+                    // Cases are numbered from one.
+                    let case = self
+                        .integer(discriminant)
+                        .and_then(|value| value.checked_sub(1))
+                        .and_then(|index| cases.get(usize::try_from(index).ok()?))
+                        .or_else(|| cases.first());
+                    if let Some(case) = case {
+                        output.push_str(&self.string(case));
+                    }
+                }
+                Segment::IntegerValue(expr) | Segment::TwoDigitValue(expr) => {
+                    if let Some(value) = self.integer(expr) {
+                        output.push_str(&number_to_words(value));
+                    }
+                }
+                Segment::ZeroPaddedValue { value, .. } => {
+                    if let Some(value) = self.integer(value) {
+                        output.push_str(&number_to_words(value));
+                    }
+                }
+                Segment::StringValue(expr) => output.push_str(&self.string(expr)),
+                Segment::StringValueSentenceCase(expr) => {
+                    let value = self.string(expr);
+                    let mut chars = value.chars();
+                    if let Some(first) = chars.next() {
+                        output.extend(first.to_uppercase());
+                        output.push_str(chars.as_str());
+                    }
+                }
+                Segment::StringValueTitleCase(expr) => {
+                    let value = self.string(expr);
+                    let mut word_start = true;
+                    for c in value.chars() {
+                        if word_start {
+                            output.extend(c.to_uppercase());
+                        } else {
+                            output.push(c);
+                        }
+                        word_start = c.is_whitespace();
+                    }
+                }
+                Segment::StringValueLowerCase(expr) => {
+                    output.push_str(&self.string(expr).to_lowercase())
+                }
+                Segment::Sheet {
+                    name,
+                    row_index,
+                    column_index,
+                    ..
+                } => {
+                    let name = self.string(name);
+                    let column = column_index.as_ref().and_then(|expr| self.integer(expr));
+                    if let Some(row) = self.integer(row_index) {
+                        if let Some(value) = self.sheet_text(&name, row, column) {
+                            output.push_str(&value);
+                        }
+                    }
+                }
+                Segment::AutoTranslate(group, key) => {
+                    if let (Some(group), Some(key)) = (self.integer(group), self.integer(key)) {
+                        if let Some(value) = self.auto_translate(group, key) {
+                            output.push_str(&value);
+                        }
+                    }
+                }
+                Segment::Split {
+                    input,
+                    separator,
+                    index,
+                } => {
+                    let input = self.string(input);
+                    let separator = self.string(separator);
+                    let index = self.integer(index).unwrap_or(1);
+                    output.push_str(split_string(&input, &separator, index));
+                }
+                Segment::Ruby { annotated, .. } => output.push_str(&self.string(annotated)),
+                // Icons, colors, emphasis, and other formatting aren't spoken.
+                _ => {}
+            }
+        }
+        output
+    }
+}
+
+/// Renders text for speech, and collapses runs of whitespace.
+pub(crate) fn to_speech(text: &Text, context: &mut SpeechContext) -> String {
+    context
+        .render(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Replaces each run of digits in a literal with English words. Digit groups separated by
+/// commas, as in "1,000", are read as one number.
+fn expand_numbers(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if !c.is_ascii_digit() {
+            output.push(c);
+            continue;
+        }
+        let mut end = start + 1;
+        while let Some((i, c)) = chars.peek().copied() {
+            if c.is_ascii_digit() {
+                end = i + 1;
+                chars.next();
+            } else if c == ',' && text[i + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let digits = text[start..end].replace(',', "");
+        match digits.parse() {
+            Ok(value) => output.push_str(&number_to_words(value)),
+            Err(_) => output.push_str(&text[start..end]),
+        }
+    }
+    output
+}
+
+/// Spells out a number in English words, e.g. "one thousand two hundred thirty-four".
+pub fn number_to_words(value: u32) -> String {
+    const ONES: [&str; 20] = [
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const TENS: [&str; 10] = [
+        "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    const SCALES: [(u32, &str); 3] = [
+        (1_000_000_000, "billion"),
+        (1_000_000, "million"),
+        (1_000, "thousand"),
+    ];
+
+    fn below_thousand(value: u32, words: &mut Vec<String>) {
+        if value >= 100 {
+            words.push(ONES[(value / 100) as usize].to_string());
+            words.push("hundred".to_string());
+        }
+        let rest = value % 100;
+        match rest {
+            0 => {}
+            1..=19 => words.push(ONES[rest as usize].to_string()),
+            _ if rest.is_multiple_of(10) => words.push(TENS[(rest / 10) as usize].to_string()),
+            _ => words.push(format!(
+                "{}-{}",
+                TENS[(rest / 10) as usize],
+                ONES[(rest % 10) as usize]
+            )),
+        }
+    }
+
+    if value == 0 {
+        return ONES[0].to_string();
+    }
+    let mut words = Vec::new();
+    let mut rest = value;
+    for (scale, name) in SCALES {
+        if rest >= scale {
+            below_thousand(rest / scale, &mut words);
+            words.push(name.to_string());
+            rest %= scale;
+        }
+    }
+    below_thousand(rest, &mut words);
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_numbers, number_to_words, SpeechContext, SpeechResolver};
+    use crate::{Expression, Segment, Text};
+
+    fn literal(s: &str) -> Expression {
+        Expression::Text(Text::new(vec![Segment::Literal(s.to_string())]))
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number_to_words(0), "zero");
+        assert_eq!(number_to_words(15), "fifteen");
+        assert_eq!(number_to_words(40), "forty");
+        assert_eq!(
+            number_to_words(1234),
+            "one thousand two hundred thirty-four"
+        );
+        assert_eq!(number_to_words(2_000_001), "two million one");
+        assert_eq!(
+            expand_numbers("Gather 1,500 gil, 3 times, in Lv. 50."),
+            "Gather one thousand five hundred gil, three times, in Lv. fifty."
+        );
+    }
+
+    struct Resolver;
+
+    impl SpeechResolver for Resolver {
+        fn sheet_text(&mut self, sheet: &str, row: u32, _column: Option<u32>) -> Option<String> {
+            (sheet == "Item" && row == 4).then(|| "potion".to_string())
+        }
+    }
+
+    #[test]
+    fn speech() {
+        let text = Text::new(vec![
+            Segment::GuiIcon(Expression::Integer(1)),
+            Segment::Literal("Obtain ".to_string()),
+            Segment::IntegerValue(Expression::InputParameter(1)),
+            Segment::NonBreakingSpace,
+            Segment::Sheet {
+                name: literal("Item"),
+                row_index: Expression::Integer(4),
+                column_index: None,
+                parameters: Vec::new(),
+            },
+            Segment::If {
+                condition: Expression::GreaterThan(Box::new((
+                    Expression::InputParameter(1),
+                    Expression::Integer(1),
+                ))),
+                true_value: literal("s"),
+                false_value: literal(""),
+            },
+            Segment::Literal(",".to_string()),
+            Segment::NewLine,
+            Segment::Switch {
+                discriminant: Expression::PlayerParameter(4),
+                cases: vec![literal("sir"), literal("madam")],
+            },
+            Segment::Literal(".".to_string()),
+        ]);
+
+        let mut resolver = Resolver;
+        let mut context = SpeechContext::with_resolver(&mut resolver);
+        context.input_parameters.insert(1, 3);
+        context.player_parameters.insert(4, 2);
+        assert_eq!(text.to_speech(&mut context), "Obtain three potions, madam.");
+
+        let mut context = SpeechContext::with_resolver(&mut resolver);
+        context.input_parameters.insert(1, 1);
+        assert_eq!(text.to_speech(&mut context), "Obtain one potion, sir.");
+
+        // Without parameters, the count is unknown, so the plural branch is spoken.
+        let mut context = SpeechContext::new();
+        assert_eq!(text.to_speech(&mut context), "Obtain s, sir.");
+    }
+}
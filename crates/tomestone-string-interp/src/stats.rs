@@ -0,0 +1,137 @@
+//! Counts how often each tag and expression kind appears across a corpus of strings.
+
+use alloc::collections::BTreeMap;
+
+use crate::{Expression, Segment, Text, TreeNode, Visitor};
+
+/// Frequencies of tags and expressions in a set of strings, along with the deepest nesting of
+/// text inside expressions.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TagStatistics {
+    /// Number of strings added.
+    pub strings: u64,
+    /// Number of strings that failed to parse.
+    pub parse_failures: u64,
+    /// Counts of each segment kind, keyed by [`Segment::kind`]. Literal text is included.
+    pub tags: BTreeMap<&'static str, u64>,
+    /// Counts of each expression kind, keyed by [`Expression::kind`].
+    pub expressions: BTreeMap<&'static str, u64>,
+    /// Deepest nesting of text within tag arguments. Strings without any nested text have depth
+    /// zero.
+    pub max_depth: usize,
+}
+
+impl TagStatistics {
+    pub fn new() -> TagStatistics {
+        TagStatistics::default()
+    }
+
+    /// Adds a parsed string to the statistics.
+    pub fn add(&mut self, text: &Text) {
+        self.strings += 1;
+        let mut visitor = StatisticsVisitor {
+            statistics: self,
+            depth: 0,
+        };
+        text.accept(&mut visitor);
+    }
+
+    /// Parses a string and adds it to the statistics, or counts it as a failure.
+    pub fn add_encoded(&mut self, data: &[u8]) {
+        match Text::parse(data) {
+            Ok(text) => self.add(&text),
+            Err(_) => {
+                self.strings += 1;
+                self.parse_failures += 1;
+            }
+        }
+    }
+
+    /// Combines statistics gathered from separate sets of strings.
+    pub fn merge(&mut self, other: &TagStatistics) {
+        self.strings += other.strings;
+        self.parse_failures += other.parse_failures;
+        for (kind, count) in other.tags.iter() {
+            *self.tags.entry(kind).or_default() += count;
+        }
+        for (kind, count) in other.expressions.iter() {
+            *self.expressions.entry(kind).or_default() += count;
+        }
+        self.max_depth = self.max_depth.max(other.max_depth);
+    }
+}
+
+struct StatisticsVisitor<'a> {
+    statistics: &'a mut TagStatistics,
+    depth: usize,
+}
+
+impl Visitor for StatisticsVisitor<'_> {
+    fn visit_tag(&mut self, tag: &Segment) {
+        *self.statistics.tags.entry(tag.kind()).or_default() += 1;
+        self.recurse_tag(tag);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        *self.statistics.expressions.entry(expr.kind()).or_default() += 1;
+        if let Expression::Text(_) = expr {
+            self.depth += 1;
+            self.statistics.max_depth = self.statistics.max_depth.max(self.depth);
+            self.recurse_expression(expr);
+            self.depth -= 1;
+        } else {
+            self.recurse_expression(expr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagStatistics;
+    use crate::{Expression, Segment, Text};
+
+    #[test]
+    fn statistics() {
+        let nested = Expression::Text(Text::new(vec![Segment::IntegerValue(
+            Expression::InputParameter(1),
+        )]));
+        let text = Text::new(vec![
+            Segment::Literal("You have ".to_string()),
+            Segment::If {
+                condition: Expression::GreaterThan(Box::new((
+                    Expression::InputParameter(1),
+                    Expression::Integer(1),
+                ))),
+                true_value: Expression::Text(Text::new(vec![Segment::StringValue(nested)])),
+                false_value: Expression::Integer(0),
+            },
+            Segment::NewLine,
+        ]);
+
+        let mut statistics = TagStatistics::new();
+        statistics.add(&text);
+        statistics.add_encoded(b"Hello");
+        assert_eq!(statistics.strings, 2);
+        assert_eq!(statistics.parse_failures, 0);
+        assert_eq!(statistics.max_depth, 2);
+        assert_eq!(
+            statistics.tags.iter().collect::<Vec<_>>(),
+            [
+                (&"If", &1),
+                (&"IntegerValue", &1),
+                (&"Literal", &2),
+                (&"NewLine", &1),
+                (&"StringValue", &1),
+            ]
+        );
+        assert_eq!(statistics.expressions["InputParameter"], 2);
+        assert_eq!(statistics.expressions["Text"], 2);
+
+        let mut merged = TagStatistics::new();
+        merged.merge(&statistics);
+        merged.merge(&statistics);
+        assert_eq!(merged.strings, 4);
+        assert_eq!(merged.tags["Literal"], 4);
+        assert_eq!(merged.max_depth, 2);
+    }
+}
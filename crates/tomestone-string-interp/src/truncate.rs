@@ -0,0 +1,158 @@
+//! Shortens strings for previews, without leaving color or emphasis tags open.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Expression, Segment, Text};
+
+/// Tracks which formatting tags are open at a point in a string.
+#[derive(Default)]
+struct FormattingState {
+    emphasis: bool,
+    colors: usize,
+    foregrounds: usize,
+    glows: usize,
+}
+
+impl FormattingState {
+    fn update(&mut self, segment: &Segment) {
+        let push_or_pop = |depth: &mut usize, push: bool| {
+            if push {
+                *depth += 1;
+            } else {
+                *depth = depth.saturating_sub(1);
+            }
+        };
+        match segment {
+            Segment::Emphasis(enabled) => self.emphasis = *enabled,
+            Segment::ColorChange(expr) => {
+                push_or_pop(&mut self.colors, *expr != Expression::TodoEC)
+            }
+            Segment::Foreground(expr) => {
+                push_or_pop(&mut self.foregrounds, *expr != Expression::Integer(0))
+            }
+            Segment::Glow(expr) => push_or_pop(&mut self.glows, *expr != Expression::Integer(0)),
+            _ => {}
+        }
+    }
+
+    /// Returns the tags that close everything still open, innermost kinds first.
+    fn closing_tags(&self) -> Vec<Segment> {
+        let mut tags = Vec::new();
+        if self.emphasis {
+            tags.push(Segment::Emphasis(false));
+        }
+        tags.extend((0..self.colors).map(|_| Segment::ColorChange(Expression::TodoEC)));
+        tags.extend((0..self.glows).map(|_| Segment::Glow(Expression::Integer(0))));
+        tags.extend((0..self.foregrounds).map(|_| Segment::Foreground(Expression::Integer(0))));
+        tags
+    }
+}
+
+/// Returns how many visible characters a segment takes up, if it is not literal text.
+fn tag_width(segment: &Segment) -> usize {
+    match segment {
+        Segment::NewLine | Segment::NonBreakingSpace | Segment::Dash => 1,
+        _ => 0,
+    }
+}
+
+impl Text {
+    /// Shortens this text to at most `max_chars` visible characters, followed by `ellipsis` if
+    /// anything was cut off. Literal text is cut between characters, and any color, glow, or
+    /// emphasis tags left open are closed after the ellipsis. Tags whose output depends on
+    /// parameters or sheets are not evaluated, and count as zero characters wide. The ellipsis
+    /// is not counted against `max_chars`.
+    pub fn truncate(&self, max_chars: usize, ellipsis: &str) -> Text {
+        let mut segments = Vec::new();
+        let mut state = FormattingState::default();
+        let mut remaining = max_chars;
+        for segment in self.segments.iter() {
+            let (kept, fits) = match segment {
+                Segment::Literal(literal) => match literal.char_indices().nth(remaining) {
+                    Some((end, _)) => (Some(Segment::Literal(literal[..end].to_string())), false),
+                    None => {
+                        remaining -= literal.chars().count();
+                        (Some(segment.clone()), true)
+                    }
+                },
+                _ if tag_width(segment) > remaining => (None, false),
+                _ => {
+                    remaining -= tag_width(segment);
+                    (Some(segment.clone()), true)
+                }
+            };
+            if let Some(kept) = kept {
+                state.update(&kept);
+                if kept != Segment::Literal(String::new()) {
+                    segments.push(kept);
+                }
+            }
+            if !fits {
+                push_literal(&mut segments, ellipsis);
+                segments.extend(state.closing_tags());
+                return Text::new(segments);
+            }
+        }
+        self.clone()
+    }
+}
+
+/// Appends literal text, merging it with a preceding literal.
+fn push_literal(segments: &mut Vec<Segment>, literal: &str) {
+    if literal.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(Segment::Literal(previous)) => previous.push_str(literal),
+        _ => segments.push(Segment::Literal(literal.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Expression, Segment, Text};
+
+    #[test]
+    fn truncate() {
+        let literal = |s: &str| Segment::Literal(s.to_string());
+        let text = Text::new(vec![
+            literal("Héllo "),
+            Segment::Foreground(Expression::Integer(500)),
+            Segment::Emphasis(true),
+            literal("wörld"),
+            Segment::Emphasis(false),
+            Segment::Foreground(Expression::Integer(0)),
+            Segment::NewLine,
+            literal("Bye"),
+        ]);
+        assert_eq!(text.truncate(100, "…"), text);
+        assert_eq!(text.truncate(3, "…"), Text::new(vec![literal("Hél…")]));
+        assert_eq!(
+            text.truncate(8, "…"),
+            Text::new(vec![
+                literal("Héllo "),
+                Segment::Foreground(Expression::Integer(500)),
+                Segment::Emphasis(true),
+                literal("wö…"),
+                Segment::Emphasis(false),
+                Segment::Foreground(Expression::Integer(0)),
+            ])
+        );
+        assert_eq!(
+            text.truncate(11, "..."),
+            Text::new(vec![
+                literal("Héllo "),
+                Segment::Foreground(Expression::Integer(500)),
+                Segment::Emphasis(true),
+                literal("wörld"),
+                Segment::Emphasis(false),
+                Segment::Foreground(Expression::Integer(0)),
+                literal("..."),
+            ])
+        );
+        assert!(text.truncate(8, "…").encode().is_ok());
+    }
+}
@@ -22,6 +22,7 @@ pub const INTEGER_VALUE: u8 = 0x20;
 pub const TODO_FORMAT: u8 = 0x22;
 pub const TWO_DIGIT_VALUE: u8 = 0x24;
 pub const TODO_26: u8 = 0x26;
+pub const LINK: u8 = 0x27;
 pub const SHEET: u8 = 0x28;
 pub const STRING_VALUE: u8 = 0x29;
 pub const STRING_VALUE_SENTENCE_CASE: u8 = 0x2b;
@@ -0,0 +1,406 @@
+//! Block compression encoders. Each format divides the image into four by four blocks of
+//! pixels, and stores each block as a pair of endpoints and an index per pixel selecting a
+//! value interpolated between them. Endpoints are chosen from the bounding box of the block's
+//! values, which is fast and works well for the smooth gradients typical of game textures.
+
+use crate::RgbaImage;
+
+type Block = [[u8; 4]; 16];
+
+/// Calls `encode` on each block of the image, in row-major order, and concatenates the results.
+/// Blocks that extend past the edge of the image repeat the edge pixels.
+fn compress_blocks<const N: usize>(
+    image: &RgbaImage,
+    encode: impl Fn(&Block) -> [u8; N],
+) -> Vec<u8> {
+    let blocks_wide = image.width.div_ceil(4);
+    let blocks_high = image.height.div_ceil(4);
+    let mut output = Vec::with_capacity((blocks_wide * blocks_high) as usize * N);
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let mut block = [[0; 4]; 16];
+            for (i, pixel) in block.iter_mut().enumerate() {
+                let x = block_x * 4 + i as u32 % 4;
+                let y = block_y * 4 + i as u32 / 4;
+                *pixel = image.pixel_clamped(x, y);
+            }
+            output.extend_from_slice(&encode(&block));
+        }
+    }
+    output
+}
+
+fn squared_distance<const N: usize>(a: &[u8], b: &[u8; N]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| {
+            let difference = i32::from(*a) - i32::from(*b);
+            (difference * difference) as u32
+        })
+        .sum()
+}
+
+fn nearest<const N: usize>(palette: &[[u8; N]], value: &[u8]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| squared_distance(value, entry))
+        .map(|(index, _)| index)
+        .unwrap()
+}
+
+/// Picks endpoints at opposite corners of the bounding box of the first `N` channels. The
+/// corners are chosen along the diagonal that follows the block's variation: channels that
+/// decrease as the channel with the widest range increases have their bounds swapped.
+fn bounding_box_endpoints<const N: usize>(block: &Block) -> ([u8; N], [u8; N]) {
+    let mut min = [255u8; N];
+    let mut max = [0u8; N];
+    let mut sum = [0i32; N];
+    for pixel in block.iter() {
+        for channel in 0..N {
+            min[channel] = min[channel].min(pixel[channel]);
+            max[channel] = max[channel].max(pixel[channel]);
+            sum[channel] += i32::from(pixel[channel]);
+        }
+    }
+    let widest = (0..N)
+        .max_by_key(|channel| max[*channel] - min[*channel])
+        .unwrap();
+    for channel in 0..N {
+        // Scaled by the number of pixels, to stay in integers.
+        let covariance: i32 = block
+            .iter()
+            .map(|pixel| {
+                (i32::from(pixel[widest]) * 16 - sum[widest])
+                    * (i32::from(pixel[channel]) * 16 - sum[channel])
+            })
+            .sum();
+        if covariance < 0 {
+            std::mem::swap(&mut min[channel], &mut max[channel]);
+        }
+    }
+    (min, max)
+}
+
+fn to_rgb565(color: [u8; 3]) -> u16 {
+    let r = (u16::from(color[0]) * 31 + 127) / 255;
+    let g = (u16::from(color[1]) * 63 + 127) / 255;
+    let b = (u16::from(color[2]) * 31 + 127) / 255;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_rgb565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+fn encode_bc1_block(block: &Block) -> [u8; 8] {
+    let (mut min, mut max) = bounding_box_endpoints::<3>(block);
+    // Pull the endpoints in slightly, so the interpolated colors cover the block more evenly.
+    for channel in 0..3 {
+        let inset = (i16::from(max[channel]) - i16::from(min[channel])) / 16;
+        min[channel] = (i16::from(min[channel]) + inset) as u8;
+        max[channel] = (i16::from(max[channel]) - inset) as u8;
+    }
+
+    let mut color0 = to_rgb565(max);
+    let mut color1 = to_rgb565(min);
+    let mut output = [0; 8];
+    if color0 == color1 {
+        output[..2].copy_from_slice(&color0.to_le_bytes());
+        output[2..4].copy_from_slice(&color1.to_le_bytes());
+        return output;
+    }
+    // The first endpoint must be larger, otherwise the block uses three color mode.
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+    let (end0, end1) = (from_rgb565(color0), from_rgb565(color1));
+    let mix = |a: u8, b: u8| ((2 * u16::from(a) + u16::from(b) + 1) / 3) as u8;
+    let palette = [
+        end0,
+        end1,
+        [
+            mix(end0[0], end1[0]),
+            mix(end0[1], end1[1]),
+            mix(end0[2], end1[2]),
+        ],
+        [
+            mix(end1[0], end0[0]),
+            mix(end1[1], end0[1]),
+            mix(end1[2], end0[2]),
+        ],
+    ];
+    let mut indices = 0u32;
+    for (i, pixel) in block.iter().enumerate() {
+        indices |= (nearest(&palette, &pixel[..3]) as u32) << (i * 2);
+    }
+    output[..2].copy_from_slice(&color0.to_le_bytes());
+    output[2..4].copy_from_slice(&color1.to_le_bytes());
+    output[4..].copy_from_slice(&indices.to_le_bytes());
+    output
+}
+
+/// Encodes one channel of a block, as used for BC3's alpha and both of BC5's channels.
+fn encode_bc4_block(values: [u8; 16]) -> [u8; 8] {
+    let max = *values.iter().max().unwrap();
+    let min = *values.iter().min().unwrap();
+    let mut output = [0; 8];
+    output[0] = max;
+    output[1] = min;
+    if max == min {
+        return output;
+    }
+    let mut palette = [[max], [min], [0], [0], [0], [0], [0], [0]];
+    for (step, entry) in palette[2..].iter_mut().enumerate() {
+        let weight = step as u16 + 1;
+        entry[0] = (((7 - weight) * u16::from(max) + weight * u16::from(min) + 3) / 7) as u8;
+    }
+    let mut indices = 0u64;
+    for (i, value) in values.iter().enumerate() {
+        indices |= (nearest(&palette, &[*value]) as u64) << (i * 3);
+    }
+    output[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    output
+}
+
+fn channel(block: &Block, channel: usize) -> [u8; 16] {
+    let mut values = [0; 16];
+    for (value, pixel) in values.iter_mut().zip(block.iter()) {
+        *value = pixel[channel];
+    }
+    values
+}
+
+/// Writes bits into a BC7 block, starting from the least significant bit.
+struct BitWriter {
+    bits: u128,
+    position: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, width: u32) {
+        self.bits |= u128::from(value) << self.position;
+        self.position += width;
+    }
+}
+
+const BC7_WEIGHTS_4: [u16; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Quantizes an endpoint to seven bits per channel plus a shared low bit, choosing the low bit
+/// that minimizes the error. Returns the seven bit values, the low bit, and the resulting color.
+fn quantize_bc7_endpoint(endpoint: [u8; 4]) -> ([u8; 4], u8, [u8; 4]) {
+    (0..2u8)
+        .map(|p_bit| {
+            let mut quantized = [0; 4];
+            let mut color = [0; 4];
+            for channel in 0..4 {
+                let value = (i32::from(endpoint[channel]) - i32::from(p_bit) + 1) / 2;
+                quantized[channel] = value.clamp(0, 127) as u8;
+                color[channel] = (quantized[channel] << 1) | p_bit;
+            }
+            (quantized, p_bit, color)
+        })
+        .min_by_key(|(_, _, color)| squared_distance(&endpoint, color))
+        .unwrap()
+}
+
+/// Encodes a block using BC7 mode 6: a single pair of RGBA endpoints with sixteen levels.
+fn encode_bc7_block(block: &Block) -> [u8; 16] {
+    let (min, max) = bounding_box_endpoints::<4>(block);
+    let mut endpoints = [quantize_bc7_endpoint(min), quantize_bc7_endpoint(max)];
+
+    let palette = |endpoints: &[([u8; 4], u8, [u8; 4]); 2]| {
+        let (start, end) = (endpoints[0].2, endpoints[1].2);
+        BC7_WEIGHTS_4.map(|weight| {
+            let mut color = [0; 4];
+            for channel in 0..4 {
+                color[channel] = (((64 - weight) * u16::from(start[channel])
+                    + weight * u16::from(end[channel])
+                    + 32)
+                    >> 6) as u8;
+            }
+            color
+        })
+    };
+    let palette_colors = palette(&endpoints);
+    let mut indices = block.map(|pixel| nearest(&palette_colors, &pixel) as u32);
+    // The first pixel's index is stored with its high bit omitted, so it must be below eight.
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        for index in indices.iter_mut() {
+            *index = 15 - *index;
+        }
+    }
+
+    let mut writer = BitWriter {
+        bits: 0,
+        position: 0,
+    };
+    writer.write(1 << 6, 7);
+    for channel in 0..4 {
+        for endpoint in endpoints.iter() {
+            writer.write(u32::from(endpoint.0[channel]), 7);
+        }
+    }
+    for endpoint in endpoints.iter() {
+        writer.write(u32::from(endpoint.1), 1);
+    }
+    writer.write(indices[0], 3);
+    for index in indices[1..].iter() {
+        writer.write(*index, 4);
+    }
+    writer.bits.to_le_bytes()
+}
+
+/// Compresses an image to BC1. Alpha is discarded.
+pub fn compress_bc1(image: &RgbaImage) -> Vec<u8> {
+    compress_blocks(image, encode_bc1_block)
+}
+
+/// Compresses an image to BC3.
+pub fn compress_bc3(image: &RgbaImage) -> Vec<u8> {
+    compress_blocks(image, |block| {
+        let mut output = [0; 16];
+        output[..8].copy_from_slice(&encode_bc4_block(channel(block, 3)));
+        output[8..].copy_from_slice(&encode_bc1_block(block));
+        output
+    })
+}
+
+/// Compresses the red and green channels of an image to BC5.
+pub fn compress_bc5(image: &RgbaImage) -> Vec<u8> {
+    compress_blocks(image, |block| {
+        let mut output = [0; 16];
+        output[..8].copy_from_slice(&encode_bc4_block(channel(block, 0)));
+        output[8..].copy_from_slice(&encode_bc4_block(channel(block, 1)));
+        output
+    })
+}
+
+/// Compresses an image to BC7.
+pub fn compress_bc7(image: &RgbaImage) -> Vec<u8> {
+    compress_blocks(image, encode_bc7_block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_bc1, compress_bc3, compress_bc5, compress_bc7, from_rgb565};
+    use super::{RgbaImage, BC7_WEIGHTS_4};
+
+    fn decode_bc1(block: &[u8]) -> [[u8; 3]; 16] {
+        let color0 = u16::from_le_bytes([block[0], block[1]]);
+        let color1 = u16::from_le_bytes([block[2], block[3]]);
+        let (end0, end1) = (from_rgb565(color0), from_rgb565(color1));
+        let mix = |a: u8, b: u8| ((2 * u16::from(a) + u16::from(b) + 1) / 3) as u8;
+        let palette = [
+            end0,
+            end1,
+            [0, 1, 2].map(|c| mix(end0[c], end1[c])),
+            [0, 1, 2].map(|c| mix(end1[c], end0[c])),
+        ];
+        let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+        std::array::from_fn(|i| palette[(indices >> (i * 2)) as usize & 3])
+    }
+
+    fn decode_bc4(block: &[u8]) -> [u8; 16] {
+        let (max, min) = (u16::from(block[0]), u16::from(block[1]));
+        let mut indices = [0; 8];
+        indices[..6].copy_from_slice(&block[2..8]);
+        let indices = u64::from_le_bytes(indices);
+        std::array::from_fn(|i| match (indices >> (i * 3)) & 7 {
+            0 => max as u8,
+            1 => min as u8,
+            index => (((8 - index as u16) * max + (index as u16 - 1) * min + 3) / 7) as u8,
+        })
+    }
+
+    fn decode_bc7_mode6(block: &[u8]) -> [[u8; 4]; 16] {
+        let bits = u128::from_le_bytes(block.try_into().unwrap());
+        let read = |start: u32, width: u32| ((bits >> start) & ((1 << width) - 1)) as u16;
+        assert_eq!(read(0, 7), 1 << 6);
+        let endpoint = |which: u32| -> [u16; 4] {
+            let p_bit = read(63 + which, 1);
+            std::array::from_fn(|channel| {
+                (read(7 + (channel as u32 * 2 + which) * 7, 7) << 1) | p_bit
+            })
+        };
+        let (start, end) = (endpoint(0), endpoint(1));
+        std::array::from_fn(|i| {
+            let index = if i == 0 {
+                read(65, 3)
+            } else {
+                read(68 + (i as u32 - 1) * 4, 4)
+            };
+            let weight = BC7_WEIGHTS_4[index as usize];
+            std::array::from_fn(|c| (((64 - weight) * start[c] + weight * end[c] + 32) >> 6) as u8)
+        })
+    }
+
+    fn gradient() -> RgbaImage {
+        let pixels = (0..8u32 * 8)
+            .flat_map(|i| {
+                let (x, y) = (i % 8, i / 8);
+                [
+                    (x * 28 + y * 4) as u8,
+                    (x * 12 + y * 2 + 40) as u8,
+                    90,
+                    (255 - x * 20 - y * 3) as u8,
+                ]
+            })
+            .collect();
+        RgbaImage::new(8, 8, pixels).unwrap()
+    }
+
+    fn max_error(expected: &[u8], actual: &[u8]) -> u8 {
+        expected
+            .iter()
+            .zip(actual.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = gradient();
+        let first_block = |channels: &[usize]| {
+            (0..16)
+                .flat_map(|i| {
+                    let pixel = image.pixel_clamped(i % 4, i / 4);
+                    channels.iter().map(move |channel| pixel[*channel])
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let bc1 = compress_bc1(&image);
+        assert_eq!(bc1.len(), 4 * 8);
+        let decoded = decode_bc1(&bc1[..8]).concat();
+        assert!(max_error(&first_block(&[0, 1, 2]), &decoded) <= 12);
+
+        let bc3 = compress_bc3(&image);
+        assert_eq!(bc3.len(), 4 * 16);
+        assert!(max_error(&first_block(&[3]), &decode_bc4(&bc3[..8])) <= 4);
+
+        let bc5 = compress_bc5(&image);
+        assert_eq!(bc5.len(), 4 * 16);
+        assert!(max_error(&first_block(&[0]), &decode_bc4(&bc5[..8])) <= 8);
+        assert!(max_error(&first_block(&[1]), &decode_bc4(&bc5[8..16])) <= 4);
+
+        let bc7 = compress_bc7(&image);
+        assert_eq!(bc7.len(), 4 * 16);
+        let decoded = decode_bc7_mode6(&bc7[..16]).concat();
+        assert!(max_error(&first_block(&[0, 1, 2, 3]), &decoded) <= 4);
+
+        // A solid block has identical endpoints.
+        let solid = RgbaImage::new(1, 1, vec![200, 100, 50, 255]).unwrap();
+        let decoded = decode_bc1(&compress_bc1(&solid));
+        assert!(max_error(&[200, 100, 50], &decoded[5]) <= 4);
+    }
+}
@@ -0,0 +1,188 @@
+//! Splits the game's packed material textures into separate maps, and packs them back.
+//!
+//! Character normal maps store the normal's X and Y components in red and green, opacity in
+//! blue, and an index into the material's color set table in alpha. Mask maps pack several
+//! unrelated grayscale maps into their channels, with a layout that depends on the material's
+//! shader package. The layouts here follow the Endwalker character shaders.
+
+use crate::{Error, RgbaImage};
+
+/// The maps packed into a character normal map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalChannels {
+    /// Tangent space normal map, with the blue channel reconstructed, and opaque alpha.
+    pub normal: RgbaImage,
+    /// One byte per pixel.
+    pub opacity: Vec<u8>,
+    /// One byte per pixel. See [`colorset_rows`].
+    pub colorset_index: Vec<u8>,
+}
+
+/// Reconstructs the Z component of a unit normal from its X and Y components, each mapped from
+/// [-1, 1] to [0, 255].
+fn reconstruct_z(x: u8, y: u8) -> u8 {
+    let x = f32::from(x) / 127.5 - 1.0;
+    let y = f32::from(y) / 127.5 - 1.0;
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    ((z + 1.0) * 127.5).round() as u8
+}
+
+pub fn split_normal(image: &RgbaImage) -> NormalChannels {
+    let pixel_count = image.pixels.len() / 4;
+    let mut normal = Vec::with_capacity(image.pixels.len());
+    let mut opacity = Vec::with_capacity(pixel_count);
+    let mut colorset_index = Vec::with_capacity(pixel_count);
+    for pixel in image.pixels.chunks_exact(4) {
+        normal.extend_from_slice(&[pixel[0], pixel[1], reconstruct_z(pixel[0], pixel[1]), 255]);
+        opacity.push(pixel[2]);
+        colorset_index.push(pixel[3]);
+    }
+    NormalChannels {
+        normal: RgbaImage {
+            width: image.width,
+            height: image.height,
+            pixels: normal,
+        },
+        opacity,
+        colorset_index,
+    }
+}
+
+/// Packs a normal map, opacity, and color set indices into the game's layout. Only the red and
+/// green channels of the normal map are used.
+pub fn combine_normal(
+    normal: &RgbaImage,
+    opacity: &[u8],
+    colorset_index: &[u8],
+) -> Result<RgbaImage, Error> {
+    let pixel_count = normal.pixels.len() / 4;
+    if opacity.len() != pixel_count || colorset_index.len() != pixel_count {
+        return Err(Error::InvalidDimensions);
+    }
+    let pixels = normal
+        .pixels
+        .chunks_exact(4)
+        .zip(opacity.iter().zip(colorset_index.iter()))
+        .flat_map(|(pixel, (opacity, index))| [pixel[0], pixel[1], *opacity, *index])
+        .collect();
+    RgbaImage::new(normal.width, normal.height, pixels)
+}
+
+/// Decodes a color set index from a normal map's alpha channel. Rows are spaced 17 apart, so
+/// the sixteen rows cover the full range; values in between blend two adjacent rows. Returns
+/// the two rows and the weight of the second.
+pub fn colorset_rows(index: u8) -> (usize, usize, f32) {
+    let row = usize::from(index / 17);
+    let weight = f32::from(index % 17) / 17.0;
+    (row, (row + 1).min(15), weight)
+}
+
+/// Which channel of a mask map holds each property, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskLayout {
+    pub specular: Option<usize>,
+    pub roughness: Option<usize>,
+    pub occlusion: Option<usize>,
+}
+
+/// Returns the mask layout used by a shader package, given its file name, e.g.
+/// `character.shpk`.
+pub fn mask_layout(shader_package: &str) -> Option<MaskLayout> {
+    match shader_package {
+        "character.shpk" | "characterglass.shpk" => Some(MaskLayout {
+            specular: Some(0),
+            roughness: Some(1),
+            occlusion: Some(2),
+        }),
+        "skin.shpk" => Some(MaskLayout {
+            specular: Some(0),
+            roughness: Some(1),
+            occlusion: None,
+        }),
+        "hair.shpk" | "iris.shpk" => Some(MaskLayout {
+            specular: Some(0),
+            roughness: Some(1),
+            occlusion: Some(3),
+        }),
+        _ => None,
+    }
+}
+
+/// Converts a mask map to the occlusion, roughness, and metallic map used by glTF, with
+/// occlusion in red, roughness in green, and metallic in blue. Properties missing from the
+/// layout become full brightness for occlusion and zero for the others, and nothing is treated
+/// as metallic.
+pub fn mask_to_occlusion_roughness_metallic(mask: &RgbaImage, layout: MaskLayout) -> RgbaImage {
+    let pixels = mask
+        .pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let occlusion = layout.occlusion.map_or(255, |channel| pixel[channel]);
+            let roughness = layout.roughness.map_or(0, |channel| pixel[channel]);
+            [occlusion, roughness, 0, 255]
+        })
+        .collect();
+    RgbaImage {
+        width: mask.width,
+        height: mask.height,
+        pixels,
+    }
+}
+
+/// Extracts one channel of an image as a grayscale map, with one byte per pixel.
+pub fn extract_channel(image: &RgbaImage, channel: usize) -> Vec<u8> {
+    image
+        .pixels
+        .chunks_exact(4)
+        .map(|pixel| pixel[channel])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        colorset_rows, combine_normal, extract_channel, mask_layout,
+        mask_to_occlusion_roughness_metallic, split_normal,
+    };
+    use crate::RgbaImage;
+
+    #[test]
+    fn normal_channels() {
+        let packed = RgbaImage::new(2, 1, vec![128, 128, 255, 34, 255, 128, 40, 0]).unwrap();
+        let channels = split_normal(&packed);
+        assert_eq!(
+            channels.normal.pixels,
+            [128, 128, 255, 255, 255, 128, 128, 255]
+        );
+        assert_eq!(channels.opacity, [255, 40]);
+        assert_eq!(channels.colorset_index, [34, 0]);
+        assert_eq!(
+            combine_normal(
+                &channels.normal,
+                &channels.opacity,
+                &channels.colorset_index
+            )
+            .unwrap(),
+            packed
+        );
+        assert!(combine_normal(&channels.normal, &[0], &[0]).is_err());
+
+        assert_eq!(colorset_rows(34), (2, 3, 0.0));
+        assert_eq!(colorset_rows(255), (15, 15, 0.0));
+        let (row, next, weight) = colorset_rows(42);
+        assert_eq!((row, next), (2, 3));
+        assert!((weight - 8.0 / 17.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mask_maps() {
+        let mask = RgbaImage::new(1, 1, vec![10, 20, 30, 40]).unwrap();
+        let orm =
+            mask_to_occlusion_roughness_metallic(&mask, mask_layout("character.shpk").unwrap());
+        assert_eq!(orm.pixels, [30, 20, 0, 255]);
+        let orm = mask_to_occlusion_roughness_metallic(&mask, mask_layout("skin.shpk").unwrap());
+        assert_eq!(orm.pixels, [255, 20, 0, 255]);
+        assert_eq!(mask_layout("bg.shpk"), None);
+        assert_eq!(extract_channel(&mask, 3), [40]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Decoding of texture data back to RGBA pixels.
+
+use crate::{Error, RgbaImage, TexHeader, TextureFormat};
+
+fn expand(value: u16, bits: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    ((u32::from(value) * 255 + max / 2) / max) as u8
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    [
+        expand(color >> 11, 5),
+        expand((color >> 5) & 0x3f, 6),
+        expand(color & 0x1f, 5),
+    ]
+}
+
+/// Decodes a BC1 block. Blocks of BC3 images always use four colors, while BC1 blocks with the
+/// smaller endpoint first use three colors and transparent black.
+fn decode_bc1_block(block: &[u8], always_four_colors: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (end0, end1) = (rgb565(color0), rgb565(color1));
+    let opaque = |color: [u8; 3]| [color[0], color[1], color[2], 255];
+    let palette = if color0 > color1 || always_four_colors {
+        let mix = |a: u8, b: u8| ((2 * u16::from(a) + u16::from(b) + 1) / 3) as u8;
+        [
+            opaque(end0),
+            opaque(end1),
+            opaque([0, 1, 2].map(|c| mix(end0[c], end1[c]))),
+            opaque([0, 1, 2].map(|c| mix(end1[c], end0[c]))),
+        ]
+    } else {
+        let half = |a: u8, b: u8| (u16::from(a) + u16::from(b)).div_ceil(2) as u8;
+        [
+            opaque(end0),
+            opaque(end1),
+            opaque([0, 1, 2].map(|c| half(end0[c], end1[c]))),
+            [0; 4],
+        ]
+    };
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    std::array::from_fn(|i| palette[(indices >> (i * 2)) as usize & 3])
+}
+
+fn decode_bc4_block(block: &[u8]) -> [u8; 16] {
+    let (end0, end1) = (u16::from(block[0]), u16::from(block[1]));
+    let mut palette = [0u8; 8];
+    palette[0] = end0 as u8;
+    palette[1] = end1 as u8;
+    if end0 > end1 {
+        for (step, entry) in palette[2..].iter_mut().enumerate() {
+            let weight = step as u16 + 1;
+            *entry = (((7 - weight) * end0 + weight * end1 + 3) / 7) as u8;
+        }
+    } else {
+        for (step, entry) in palette[2..6].iter_mut().enumerate() {
+            let weight = step as u16 + 1;
+            *entry = (((5 - weight) * end0 + weight * end1 + 2) / 5) as u8;
+        }
+        palette[7] = 255;
+    }
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    std::array::from_fn(|i| palette[((indices >> (i * 3)) & 7) as usize])
+}
+
+/// Calls `decode` on each block of compressed data, and places the pixels in an image.
+fn decode_blocks(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_size: usize,
+    decode: impl Fn(&[u8]) -> [[u8; 4]; 16],
+) -> Vec<u8> {
+    let blocks_wide = width.div_ceil(4);
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    for (block_index, block) in data.chunks_exact(block_size).enumerate() {
+        let block_x = block_index as u32 % blocks_wide;
+        let block_y = block_index as u32 / blocks_wide;
+        for (i, pixel) in decode(block).iter().enumerate() {
+            let x = block_x * 4 + i as u32 % 4;
+            let y = block_y * 4 + i as u32 / 4;
+            if x < width && y < height {
+                let offset = (y as usize * width as usize + x as usize) * 4;
+                pixels[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+    pixels
+}
+
+/// Decodes one surface of texture data. BC7 is not supported.
+pub fn decompress(
+    data: &[u8],
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage, Error> {
+    let data = data
+        .get(..format.surface_size(width, height))
+        .ok_or(Error::InvalidDimensions)?;
+    let pixels = match format {
+        TextureFormat::B4G4R4A4 => data
+            .chunks_exact(2)
+            .flat_map(|pixel| {
+                let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+                [8, 4, 0, 12].map(|shift| expand((value >> shift) & 0xf, 4))
+            })
+            .collect(),
+        TextureFormat::B5G5R5A1 => data
+            .chunks_exact(2)
+            .flat_map(|pixel| {
+                let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+                [
+                    expand((value >> 10) & 0x1f, 5),
+                    expand((value >> 5) & 0x1f, 5),
+                    expand(value & 0x1f, 5),
+                    if value & 0x8000 != 0 { 255 } else { 0 },
+                ]
+            })
+            .collect(),
+        TextureFormat::B8G8R8A8 => data
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect(),
+        TextureFormat::Bc1 => decode_blocks(data, width, height, 8, |block| {
+            decode_bc1_block(block, false)
+        }),
+        TextureFormat::Bc3 => decode_blocks(data, width, height, 16, |block| {
+            let alpha = decode_bc4_block(&block[..8]);
+            let mut pixels = decode_bc1_block(&block[8..], true);
+            for (pixel, alpha) in pixels.iter_mut().zip(alpha.iter()) {
+                pixel[3] = *alpha;
+            }
+            pixels
+        }),
+        TextureFormat::Bc5 => decode_blocks(data, width, height, 16, |block| {
+            let red = decode_bc4_block(&block[..8]);
+            let green = decode_bc4_block(&block[8..]);
+            std::array::from_fn(|i| [red[i], green[i], 0, 255])
+        }),
+        TextureFormat::Bc7 => return Err(Error::UnsupportedFormat(format)),
+    };
+    RgbaImage::new(width, height, pixels)
+}
+
+/// Decodes the largest mipmap of a `.tex` file.
+pub fn decode_tex(data: &[u8]) -> Result<RgbaImage, Error> {
+    let header = TexHeader::parse(data)?;
+    let surface = data
+        .get(header.surface_offsets[0] as usize..)
+        .ok_or(Error::InvalidDimensions)?;
+    decompress(
+        surface,
+        header.format,
+        u32::from(header.width),
+        u32::from(header.height),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_tex, decompress};
+    use crate::{compress, encode_tex, RgbaImage, TextureFormat};
+
+    #[test]
+    fn decode() {
+        let image = RgbaImage::new(2, 1, vec![255, 0, 0, 255, 0, 255, 255, 0]).unwrap();
+        for format in [TextureFormat::B4G4R4A4, TextureFormat::B8G8R8A8] {
+            let data = encode_tex(&image, format, false).unwrap();
+            assert_eq!(decode_tex(&data).unwrap(), image, "{:?}", format);
+        }
+        let data = encode_tex(&image, TextureFormat::Bc3, false).unwrap();
+        let decoded = decode_tex(&data).unwrap();
+        assert_eq!((decoded.pixels[3], decoded.pixels[7]), (255, 0));
+
+        let decoded = decompress(
+            &compress(&image, TextureFormat::B5G5R5A1),
+            TextureFormat::B5G5R5A1,
+            2,
+            1,
+        )
+        .unwrap();
+        assert_eq!(decoded, image);
+
+        // Three color mode, with the smaller endpoint first, has transparent black.
+        let block = [0x00, 0x00, 0x1f, 0x00, 0b11_10_01_00, 0, 0, 0];
+        let decoded = decompress(&block, TextureFormat::Bc1, 4, 1).unwrap();
+        assert_eq!(
+            decoded.pixels,
+            [0, 0, 0, 255, 0, 0, 255, 255, 0, 0, 128, 255, 0, 0, 0, 0]
+        );
+
+        assert!(decompress(&[0; 16], TextureFormat::Bc7, 4, 4).is_err());
+        assert!(decompress(&[0; 4], TextureFormat::B8G8R8A8, 2, 1).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Locates icon textures, and lays icons out in contact sheets.
+
+use crate::RgbaImage;
+
+/// Returns the path of an icon's texture. Icons are grouped into folders of one thousand. The
+/// high resolution variants, with an `_hr1` suffix, are twice the size.
+pub fn icon_path(icon: u32, high_resolution: bool) -> String {
+    format!(
+        "ui/icon/{:06}/{:06}{}.tex",
+        icon / 1000 * 1000,
+        icon,
+        if high_resolution { "_hr1" } else { "" }
+    )
+}
+
+/// Arranges images in a grid with the given number of columns, left to right, then top to
+/// bottom. Each cell is as large as the largest image, and smaller images are placed in the top
+/// left corner of their cell. Unused space is transparent. Returns `None` if there are no
+/// images or no columns.
+pub fn contact_sheet(images: &[RgbaImage], columns: usize) -> Option<RgbaImage> {
+    if images.is_empty() || columns == 0 {
+        return None;
+    }
+    let cell_width = images.iter().map(|image| image.width).max()? as usize;
+    let cell_height = images.iter().map(|image| image.height).max()? as usize;
+    let columns = columns.min(images.len());
+    let rows = images.len().div_ceil(columns);
+    let width = cell_width * columns;
+    let height = cell_height * rows;
+
+    let mut pixels = vec![0; width * height * 4];
+    for (index, image) in images.iter().enumerate() {
+        let left = index % columns * cell_width;
+        let top = index / columns * cell_height;
+        let row_length = image.width as usize * 4;
+        for (y, row) in image.pixels.chunks_exact(row_length).enumerate() {
+            let offset = ((top + y) * width + left) * 4;
+            pixels[offset..offset + row_length].copy_from_slice(row);
+        }
+    }
+    Some(RgbaImage {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tomestone_common::test_game_data_or_skip;
+    use tomestone_sqpack::GameData;
+
+    use super::{contact_sheet, icon_path};
+    use crate::{decode_tex, RgbaImage};
+
+    #[test]
+    fn contact_sheets() {
+        assert_eq!(icon_path(20650, false), "ui/icon/020000/020650.tex");
+        assert_eq!(icon_path(999, true), "ui/icon/000000/000999_hr1.tex");
+
+        let red = RgbaImage::new(2, 2, [255, 0, 0, 255].repeat(4)).unwrap();
+        let blue = RgbaImage::new(1, 1, vec![0, 0, 255, 255]).unwrap();
+        let sheet = contact_sheet(&[red.clone(), blue.clone(), red], 2).unwrap();
+        assert_eq!((sheet.width, sheet.height), (4, 4));
+        assert_eq!(sheet.pixel_clamped(1, 1), [255, 0, 0, 255]);
+        assert_eq!(sheet.pixel_clamped(2, 0), [0, 0, 255, 255]);
+        assert_eq!(sheet.pixel_clamped(3, 1), [0, 0, 0, 0]);
+        assert_eq!(sheet.pixel_clamped(0, 3), [255, 0, 0, 255]);
+        assert_eq!(sheet.pixel_clamped(2, 2), [0, 0, 0, 0]);
+
+        let sheet = contact_sheet(&[blue], 8).unwrap();
+        assert_eq!((sheet.width, sheet.height), (1, 1));
+        assert!(contact_sheet(&[], 8).is_none());
+
+        let mut png = Vec::new();
+        sheet.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn icon_game_data() {
+        let (game_data, mut data_file_set) = test_game_data_or_skip!();
+        let data = game_data
+            .lookup_path_data(&mut data_file_set, &icon_path(20650, false))
+            .unwrap()
+            .unwrap();
+        let image = decode_tex(&data).unwrap();
+        assert_eq!((image.width, image.height), (40, 40));
+    }
+}
@@ -0,0 +1,382 @@
+//! Reading and writing of the game's `.tex` texture files.
+//!
+//! A `.tex` file is an 80 byte header followed by each mipmap level, largest first. The header
+//! gives the pixel format, the dimensions, the number of mipmaps, and the offset of each mipmap
+//! from the start of the file.
+//!
+//! Images can be written out as PNG with [`RgbaImage::write_png`].
+
+use std::{fmt, io::Write};
+
+use nom::{
+    combinator::{all_consuming, map_res},
+    multi::count,
+    number::complete::{le_u16, le_u32},
+    sequence::tuple,
+    Finish, IResult,
+};
+
+mod bcn;
+pub mod channels;
+mod decode;
+pub mod icon;
+
+pub use bcn::{compress_bc1, compress_bc3, compress_bc5, compress_bc7};
+pub use decode::{decode_tex, decompress};
+
+/// Size of the `.tex` header, which is also the offset of the first mipmap.
+pub const HEADER_SIZE: usize = 80;
+/// The most mipmaps a `.tex` header can point to.
+pub const MAX_MIP_LEVELS: usize = 13;
+/// Attribute flag for ordinary two-dimensional textures.
+pub const ATTRIBUTE_TEXTURE_2D: u32 = 0x0080_0000;
+
+#[derive(Debug)]
+pub enum Error {
+    Nom(nom::error::ErrorKind),
+    UnknownFormat(u32),
+    /// The format is known, but decoding it is not supported.
+    UnsupportedFormat(TextureFormat),
+    /// The image has a zero or out of range dimension, or its pixel buffer has the wrong length.
+    InvalidDimensions,
+}
+
+impl<'a> From<nom::error::Error<&'a [u8]>> for Error {
+    fn from(e: nom::error::Error<&'a [u8]>) -> Error {
+        Error::Nom(e.code)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Nom(e) => write!(f, "parsing error: {:?}", e),
+            Error::UnknownFormat(format) => write!(f, "unknown texture format {:#06x}", format),
+            Error::UnsupportedFormat(format) => {
+                write!(f, "decoding {:?} textures is not supported", format)
+            }
+            Error::InvalidDimensions => write!(f, "invalid image dimensions"),
+        }
+    }
+}
+
+/// Pixel formats that can be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// Uncompressed, with four bits per channel, packed into a little-endian 16 bit integer with
+    /// alpha in the high bits, then red, green, and blue. Used by most icons.
+    B4G4R4A4,
+    /// Uncompressed, with five bits per color channel and one bit of alpha in the high bit.
+    B5G5R5A1,
+    /// Uncompressed, in blue, green, red, alpha byte order.
+    B8G8R8A8,
+    /// Also known as DXT1. Opaque color, at four bits per pixel.
+    Bc1,
+    /// Also known as DXT5. Color with interpolated alpha, at eight bits per pixel.
+    Bc3,
+    /// Two independent channels, red and green, at eight bits per pixel. Used for normal maps.
+    Bc5,
+    /// Color with alpha, at eight bits per pixel, with higher quality than BC3.
+    Bc7,
+}
+
+impl TextureFormat {
+    pub fn from_u32(value: u32) -> Option<TextureFormat> {
+        match value {
+            0x1440 => Some(TextureFormat::B4G4R4A4),
+            0x1441 => Some(TextureFormat::B5G5R5A1),
+            0x1450 => Some(TextureFormat::B8G8R8A8),
+            0x3420 => Some(TextureFormat::Bc1),
+            0x3431 => Some(TextureFormat::Bc3),
+            0x6230 => Some(TextureFormat::Bc5),
+            0x6432 => Some(TextureFormat::Bc7),
+            _ => None,
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            TextureFormat::B4G4R4A4 => 0x1440,
+            TextureFormat::B5G5R5A1 => 0x1441,
+            TextureFormat::B8G8R8A8 => 0x1450,
+            TextureFormat::Bc1 => 0x3420,
+            TextureFormat::Bc3 => 0x3431,
+            TextureFormat::Bc5 => 0x6230,
+            TextureFormat::Bc7 => 0x6432,
+        }
+    }
+
+    /// Number of bytes used to store an image of the given size in this format.
+    pub fn surface_size(&self, width: u32, height: u32) -> usize {
+        let blocks = (width.div_ceil(4) * height.div_ceil(4)) as usize;
+        match self {
+            TextureFormat::B4G4R4A4 | TextureFormat::B5G5R5A1 => {
+                width as usize * height as usize * 2
+            }
+            TextureFormat::B8G8R8A8 => width as usize * height as usize * 4,
+            TextureFormat::Bc1 => blocks * 8,
+            TextureFormat::Bc3 | TextureFormat::Bc5 | TextureFormat::Bc7 => blocks * 16,
+        }
+    }
+}
+
+/// An uncompressed image, with four bytes per pixel in red, green, blue, alpha order, and rows
+/// stored top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<RgbaImage, Error> {
+        if width == 0 || height == 0 || pixels.len() != width as usize * height as usize * 4 {
+            return Err(Error::InvalidDimensions);
+        }
+        Ok(RgbaImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Writes the image as an eight bit RGBA PNG file.
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)
+    }
+
+    /// Returns the pixel at the given coordinates, clamped to the edges of the image.
+    pub fn pixel_clamped(&self, x: u32, y: u32) -> [u8; 4] {
+        let x = x.min(self.width - 1) as usize;
+        let y = y.min(self.height - 1) as usize;
+        let offset = (y * self.width as usize + x) * 4;
+        self.pixels[offset..offset + 4].try_into().unwrap()
+    }
+
+    /// Halves the image's dimensions, averaging each two by two square of pixels. Odd
+    /// dimensions are rounded down, to a minimum of one.
+    pub fn downsample(&self) -> RgbaImage {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let pixel = self.pixel_clamped(x * 2 + dx, y * 2 + dy);
+                    for (sum, channel) in sum.iter_mut().zip(pixel.iter()) {
+                        *sum += u32::from(*channel);
+                    }
+                }
+                pixels.extend(sum.iter().map(|sum| ((sum + 2) / 4) as u8));
+            }
+        }
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Returns this image followed by successively halved copies, down to one by one, or until
+    /// there are `max_levels` images.
+    pub fn mipmaps(&self, max_levels: usize) -> Vec<RgbaImage> {
+        let mut levels = vec![self.clone()];
+        while levels.len() < max_levels {
+            let last = levels.last().unwrap();
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+        levels
+    }
+}
+
+/// Compresses or converts an image to the given format.
+pub fn compress(image: &RgbaImage, format: TextureFormat) -> Vec<u8> {
+    let quantize = |value: u8, bits: u32| (u16::from(value) * ((1 << bits) - 1) + 127) / 255;
+    match format {
+        TextureFormat::B4G4R4A4 => image
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let value = (quantize(pixel[3], 4) << 12)
+                    | (quantize(pixel[0], 4) << 8)
+                    | (quantize(pixel[1], 4) << 4)
+                    | quantize(pixel[2], 4);
+                value.to_le_bytes()
+            })
+            .collect(),
+        TextureFormat::B5G5R5A1 => image
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let value = (u16::from(pixel[3] >= 128) << 15)
+                    | (quantize(pixel[0], 5) << 10)
+                    | (quantize(pixel[1], 5) << 5)
+                    | quantize(pixel[2], 5);
+                value.to_le_bytes()
+            })
+            .collect(),
+        TextureFormat::B8G8R8A8 => image
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect(),
+        TextureFormat::Bc1 => compress_bc1(image),
+        TextureFormat::Bc3 => compress_bc3(image),
+        TextureFormat::Bc5 => compress_bc5(image),
+        TextureFormat::Bc7 => compress_bc7(image),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TexHeader {
+    pub attribute: u32,
+    pub format: TextureFormat,
+    pub width: u16,
+    pub height: u16,
+    pub depth: u16,
+    pub mip_levels: u16,
+    /// First mipmap level used at each level of detail.
+    pub lod_offsets: [u32; 3],
+    /// Offset of each mipmap from the start of the file. Unused entries are zero.
+    pub surface_offsets: [u32; MAX_MIP_LEVELS],
+}
+
+fn tex_header(input: &[u8]) -> IResult<&[u8], TexHeader> {
+    map_res(
+        tuple((
+            le_u32,
+            le_u32,
+            le_u16,
+            le_u16,
+            le_u16,
+            le_u16,
+            count(le_u32, 3),
+            count(le_u32, MAX_MIP_LEVELS),
+        )),
+        |(attribute, format, width, height, depth, mip_levels, lod_offsets, surface_offsets)| {
+            Ok::<_, Error>(TexHeader {
+                attribute,
+                format: TextureFormat::from_u32(format).ok_or(Error::UnknownFormat(format))?,
+                width,
+                height,
+                depth,
+                mip_levels,
+                lod_offsets: lod_offsets.try_into().unwrap(),
+                surface_offsets: surface_offsets.try_into().unwrap(),
+            })
+        },
+    )(input)
+}
+
+impl TexHeader {
+    pub fn parse(data: &[u8]) -> Result<TexHeader, Error> {
+        let header = data
+            .get(..HEADER_SIZE)
+            .ok_or(Error::Nom(nom::error::ErrorKind::Eof))?;
+        let (_, header) = all_consuming(tex_header)(header).finish()?;
+        Ok(header)
+    }
+
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.attribute.to_le_bytes());
+        buf.extend_from_slice(&self.format.to_u32().to_le_bytes());
+        for value in [self.width, self.height, self.depth, self.mip_levels] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        for offset in self.lod_offsets.iter().chain(self.surface_offsets.iter()) {
+            buf.extend_from_slice(&offset.to_le_bytes());
+        }
+    }
+}
+
+/// Encodes an image as a complete `.tex` file, optionally with a full chain of mipmaps.
+pub fn encode_tex(
+    image: &RgbaImage,
+    format: TextureFormat,
+    generate_mipmaps: bool,
+) -> Result<Vec<u8>, Error> {
+    let width = u16::try_from(image.width).map_err(|_| Error::InvalidDimensions)?;
+    let height = u16::try_from(image.height).map_err(|_| Error::InvalidDimensions)?;
+    let levels = if generate_mipmaps {
+        image.mipmaps(MAX_MIP_LEVELS)
+    } else {
+        vec![image.clone()]
+    };
+    let surfaces = levels
+        .iter()
+        .map(|level| compress(level, format))
+        .collect::<Vec<_>>();
+
+    let mut surface_offsets = [0; MAX_MIP_LEVELS];
+    let mut offset = HEADER_SIZE as u32;
+    for (surface_offset, surface) in surface_offsets.iter_mut().zip(surfaces.iter()) {
+        *surface_offset = offset;
+        offset += surface.len() as u32;
+    }
+    let last_level = surfaces.len() as u32 - 1;
+    let header = TexHeader {
+        attribute: ATTRIBUTE_TEXTURE_2D,
+        format,
+        width,
+        height,
+        depth: 1,
+        mip_levels: surfaces.len() as u16,
+        lod_offsets: [0, 1.min(last_level), 2.min(last_level)],
+        surface_offsets,
+    };
+
+    let mut buf = Vec::with_capacity(offset as usize);
+    header.write(&mut buf);
+    for surface in surfaces {
+        buf.extend_from_slice(&surface);
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_tex, RgbaImage, TexHeader, TextureFormat, HEADER_SIZE};
+
+    #[test]
+    fn tex_file() {
+        let pixels = (0..8 * 6)
+            .flat_map(|i| [i as u8 * 5, 255 - i as u8, 128, 255])
+            .collect();
+        let image = RgbaImage::new(8, 6, pixels).unwrap();
+        let mipmaps = image.mipmaps(13);
+        assert_eq!(
+            mipmaps
+                .iter()
+                .map(|level| (level.width, level.height))
+                .collect::<Vec<_>>(),
+            [(8, 6), (4, 3), (2, 1), (1, 1)]
+        );
+
+        let data = encode_tex(&image, TextureFormat::Bc1, true).unwrap();
+        let header = TexHeader::parse(&data).unwrap();
+        assert_eq!(header.format, TextureFormat::Bc1);
+        assert_eq!((header.width, header.height, header.depth), (8, 6, 1));
+        assert_eq!(header.mip_levels, 4);
+        assert_eq!(header.lod_offsets, [0, 1, 2]);
+        assert_eq!(
+            header.surface_offsets[..5],
+            [80, 80 + 32, 80 + 32 + 8, 80 + 32 + 16, 0]
+        );
+        assert_eq!(data.len(), HEADER_SIZE + 32 + 8 * 3);
+
+        let data = encode_tex(&image, TextureFormat::B8G8R8A8, false).unwrap();
+        assert_eq!(&data[HEADER_SIZE..HEADER_SIZE + 4], &[128, 255, 0, 255]);
+        assert_eq!(TexHeader::parse(&data).unwrap().mip_levels, 1);
+
+        assert!(RgbaImage::new(8, 6, vec![0; 4]).is_err());
+    }
+}